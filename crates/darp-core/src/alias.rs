@@ -0,0 +1,87 @@
+// alias.rs
+
+use anyhow::{Result, anyhow};
+
+/// `line` split into words the way a shell would: whitespace separates words, single
+/// quotes keep everything literal, double quotes and backslashes escape.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("unterminated ' in '{}'", line)),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if matches!(c, '"' | '\\' | '$' | '`') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(anyhow!("unterminated \" in '{}'", line)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("unterminated \" in '{}'", line)),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Check an alias before it goes into config: a plain name that isn't one of `builtins`
+/// (the CLI's own subcommands), expanding to at least one word.
+pub fn validate_alias(name: &str, expansion: &str, builtins: &[String]) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('-')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Alias names are letters, digits, '-' and '_', got '{}'.",
+            name
+        ));
+    }
+    if builtins.iter().any(|b| b == name) {
+        return Err(anyhow!("'{}' is a darp command; pick another alias.", name));
+    }
+    if split_words(expansion)?.is_empty() {
+        return Err(anyhow!("Alias '{}' needs a command to expand to.", name));
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+// backups.rs
+
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Backups kept of config.json; the oldest go once there are more.
+pub const MAX_BACKUPS: usize = 20;
+
+/// `config-YYYYMMDD-HHMMSS-mmm.json` for `time` in UTC, so names sort oldest first.
+pub fn backup_file_name(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    // Days since the epoch to a civil date (Howard Hinnant's `civil_from_days`).
+    let z = secs / 86_400 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    let time_of_day = secs % 86_400;
+    format!(
+        "config-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}.json",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// The config backups in `dir`, newest first.
+pub fn list_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("config-") && n.ends_with(".json"))
+        })
+        .collect();
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Copy the current `config_path` into `dir` before `new_data` replaces it, then drop
+/// all but the newest `MAX_BACKUPS`. Nothing is kept when there is no config yet or it
+/// wouldn't change.
+pub fn back_up(dir: &Path, config_path: &Path, new_data: &[u8]) -> Result<()> {
+    let Ok(current) = fs::read(config_path) else {
+        return Ok(());
+    };
+    if current == new_data {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(backup_file_name(SystemTime::now())), current)?;
+    for old in list_backups(dir)?.into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// The `n`th newest backup in `dir` (1 is the latest).
+pub fn nth_backup(dir: &Path, n: usize) -> Result<PathBuf> {
+    let backups = list_backups(dir)?;
+    if backups.is_empty() {
+        return Err(anyhow!("No config backups in {}.", dir.display()));
+    }
+    n.checked_sub(1)
+        .and_then(|i| backups.get(i).cloned())
+        .ok_or_else(|| {
+            anyhow!(
+                "There are {} backup(s); pick one from 1 to {}.",
+                backups.len(),
+                backups.len()
+            )
+        })
+}
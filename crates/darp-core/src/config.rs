@@ -3,8 +3,15 @@ use dirs::home_dir;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::BTreeMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use crate::alias;
+use crate::backups;
+use crate::engine;
+use crate::resolver::{self, DomainDir, Placement};
+use crate::timings;
+
 /// Deserializer for `*field` override values. The double `Option` distinguishes
 /// "key absent" (outer `None`) from "key present with JSON null" (`Some(None)`).
 /// Pair with `#[serde(default)]` + `rename = "*field"` + `skip_serializing_if = "Option::is_none"`.
@@ -21,6 +28,61 @@ pub fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
     Ok(serde_json::from_slice(&data)?)
 }
 
+/// Replace `path` with `data` so that a crash leaves either the old file or the new one,
+/// never half of it: the data goes to a temporary file beside `path`, is synced, and is
+/// renamed over it. A symlinked `path` (e.g. into a dotfiles repo) keeps its link, and the
+/// file it points to is replaced. The new file keeps the old one's permissions, so a
+/// 0600 secrets file stays private.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = path
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file.", path.display()))?;
+    let tmp = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let permissions = fs::metadata(&path).ok().map(|m| m.permissions());
+    let written = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            // Before the data goes in, so it's never readable by more users than before.
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(anyhow!("Could not write {}: {}", path.display(), e));
+    }
+    // The rename is only durable once the directory entry is too. Not every platform can
+    // sync a directory, so a failure here is ignored.
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// The error for a config.json that can't be read as a config, pointing at the backups
+/// rather than carrying on with an empty config that the next save would write over it.
+fn unreadable_config(path: &Path, e: serde_json::Error) -> anyhow::Error {
+    anyhow!(
+        "{} is not a valid config: {}. Fix it by hand, or restore a backup with \
+         'darp config rollback' ('darp config rollback --list' shows them).",
+        path.display(),
+        e
+    )
+}
+
 /// Values available for `{token}` interpolation in `variables`, `serve_command`,
 /// and `host_portmappings`. This is language-agnostic: darp only assigns/exposes
 /// the values, and the config author wires any debugger-specific string (e.g.
@@ -52,6 +114,22 @@ pub fn substitute_tokens(input: &str, ctx: &TokenCtx) -> String {
     out
 }
 
+/// First reverse-proxy port `darp deploy` hands out; services count up from here.
+pub const PROXY_PORT_BASE: u16 = 50100;
+
+/// How far above a shared host user's `port_base` their debug ports start; proxy ports
+/// take the part of the range below.
+pub const SHARED_DEBUG_PORT_OFFSET: u16 = 500;
+
+/// Ports a shared host user's range spans; bases closer together than this collide.
+pub const SHARED_PORT_RANGE: u16 = 1000;
+
+/// Where shared host users' vhosts are collected when `proxy_dir` isn't set.
+pub const SHARED_PROXY_DIR: &str = "/tmp/darp-proxy";
+
+/// Most containers a service's `replicas` can ask for; each takes a proxy port.
+pub const MAX_REPLICAS: u32 = 16;
+
 /// Default base of the debug-port range assigned by `darp deploy`. A dedicated
 /// sparse block well clear of the crowded 9000–9100 dev zone (php-fpm 9000,
 /// Prometheus 9090, Kafka 9092, …) and below the ephemeral range (49152+).
@@ -120,6 +198,39 @@ pub fn portmap_debug_port(
         .map(|p| p as u16)
 }
 
+/// Every reverse-proxy and debug port assigned to services in `domain`, sorted.
+pub fn portmap_domain_ports(portmap: &serde_json::Value, domain: &str) -> Vec<u16> {
+    let mut ports = std::collections::BTreeSet::new();
+    let groups = portmap.get(domain).and_then(|d| d.as_object());
+    for services in groups.into_iter().flat_map(|g| g.values()) {
+        for entry in services.as_object().into_iter().flat_map(|s| s.values()) {
+            let proxy = entry
+                .get("port")
+                .and_then(|p| p.as_u64())
+                .or_else(|| entry.as_u64());
+            let debug = entry.get("debug_port").and_then(|p| p.as_u64());
+            ports.extend(proxy.into_iter().chain(debug).map(|p| p as u16));
+        }
+    }
+    ports.into_iter().collect()
+}
+
+/// The ports the reverse proxy listens on for services it reaches over the darp network
+/// (`direct_via_proxy`), which publish no port of their own, sorted.
+pub fn portmap_direct_proxy_ports(portmap: &serde_json::Value) -> Vec<u16> {
+    let mut ports = std::collections::BTreeSet::new();
+    for groups in portmap.as_object().into_iter().flat_map(|d| d.values()) {
+        for services in groups.as_object().into_iter().flat_map(|g| g.values()) {
+            for entry in services.as_object().into_iter().flat_map(|s| s.values()) {
+                if entry.get("direct_via_proxy").and_then(|d| d.as_bool()) == Some(true) {
+                    ports.extend(entry.get("port").and_then(|p| p.as_u64()).map(|p| p as u16));
+                }
+            }
+        }
+    }
+    ports.into_iter().collect()
+}
+
 /// Read a service's reverse-proxy port from a portmap value. Entries are either a
 /// bare number (legacy) or an object `{"port": N, ...}`.
 pub fn portmap_proxy_port(
@@ -140,38 +251,299 @@ pub fn portmap_proxy_port(
         .map(|p| p as u16)
 }
 
+/// The proxy ports of a service's extra replicas (the first replica uses its `port`),
+/// empty when it runs a single container.
+pub fn portmap_replica_ports(
+    portmap: &serde_json::Value,
+    domain: &str,
+    group: &str,
+    service: &str,
+) -> Vec<u16> {
+    portmap
+        .get(domain)
+        .and_then(|d| d.get(group))
+        .and_then(|g| g.get(service))
+        .and_then(|v| v.get("replica_ports"))
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.as_u64())
+        .map(|p| p as u16)
+        .collect()
+}
+
+/// The port clients dial for a tcp service in the portmap: its `stream_port` when it has
+/// one, else its host port when it runs on the host, else its proxy port.
+pub fn portmap_tcp_port(entry: &serde_json::Value) -> u64 {
+    entry
+        .get("stream_port")
+        .or_else(|| entry.get("host_port"))
+        .or_else(|| entry.get("port"))
+        .and_then(|p| p.as_u64())
+        .or_else(|| entry.as_u64())
+        .unwrap_or(0)
+}
+
+/// A service's hostname as deployed: the `host` recorded for it on a shared host, else
+/// `{service}.{domain}.test`.
+pub fn portmap_host(entry: &serde_json::Value, domain: &str, service: &str) -> String {
+    entry
+        .get("host")
+        .and_then(|h| h.as_str())
+        .map_or_else(|| service_host(service, domain, None), String::from)
+}
+
+/// `(domain, group, service)` for every service in the portmap, sorted.
+pub fn portmap_services(portmap: &serde_json::Value) -> Vec<(String, String, String)> {
+    let mut services: Vec<_> = portmap
+        .as_object()
+        .into_iter()
+        .flatten()
+        .flat_map(|(domain_name, groups)| {
+            groups
+                .as_object()
+                .into_iter()
+                .flatten()
+                .flat_map(move |(group_name, services)| {
+                    services
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(move |(s, _)| (domain_name.clone(), group_name.clone(), s.clone()))
+                })
+        })
+        .collect();
+    services.sort();
+    services
+}
+
+/// The hostname aliases recorded for a service in the portmap.
+pub fn portmap_aliases(entry: &serde_json::Value) -> Vec<String> {
+    entry
+        .get("aliases")
+        .and_then(|a| a.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a.as_str().map(String::from))
+        .collect()
+}
+
+/// The URLs recorded for a service's custom domains in the portmap.
+pub fn portmap_custom_urls(entry: &serde_json::Value) -> Vec<String> {
+    entry
+        .get("custom_urls")
+        .and_then(|a| a.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a.as_str().map(String::from))
+        .collect()
+}
+
+/// The proxy port for a service registered after the last deploy: one past the highest
+/// port in the portmap, or `base` when it's empty.
+pub fn next_proxy_port(portmap: &serde_json::Value, base: u16) -> u16 {
+    let highest = portmap
+        .as_object()
+        .into_iter()
+        .flat_map(|domains| domains.values())
+        .filter_map(|groups| groups.as_object())
+        .flat_map(|groups| groups.values())
+        .filter_map(|services| services.as_object())
+        .flat_map(|services| services.values())
+        .flat_map(|entry| {
+            let replicas = entry.get("replica_ports").and_then(|p| p.as_array());
+            entry
+                .get("port")
+                .and_then(|p| p.as_u64())
+                .or_else(|| entry.as_u64())
+                .into_iter()
+                .chain(replicas.into_iter().flatten().filter_map(|p| p.as_u64()))
+        })
+        .max();
+    highest.map_or(base, |p| p as u16 + 1)
+}
+
+/// Shell-style wildcard match: `*` is any run of characters, `?` any one character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    // Where the last `*` was and how much text it had swallowed, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star_pi, star_ti)) = star {
+            pi = star_pi + 1;
+            ti = star_ti + 1;
+            star = Some((star_pi, star_ti + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// The portmap limited to one domain and/or to services whose name or
+/// `{service}.{domain}.test` host matches a `glob_match` pattern. Groups and domains left
+/// empty are dropped.
+pub fn filter_portmap(
+    portmap: &serde_json::Value,
+    domain: Option<&str>,
+    pattern: Option<&str>,
+) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        if domain.is_some_and(|d| d != domain_name) {
+            continue;
+        }
+        let mut domain_map = serde_json::Map::new();
+        for (group_name, services) in groups.as_object().into_iter().flatten() {
+            let group_map: serde_json::Map<String, serde_json::Value> = services
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(service_name, _)| {
+                    pattern.is_none_or(|p| {
+                        glob_match(p, service_name)
+                            || glob_match(p, &format!("{}.{}.test", service_name, domain_name))
+                    })
+                })
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            if !group_map.is_empty() {
+                domain_map.insert(group_name.clone(), serde_json::Value::Object(group_map));
+            }
+        }
+        if !domain_map.is_empty() {
+            out.insert(domain_name.clone(), serde_json::Value::Object(domain_map));
+        }
+    }
+    serde_json::Value::Object(out)
+}
+
 #[derive(Clone, Debug)]
 pub struct DarpPaths {
-    pub _darp_root: PathBuf,
+    /// Where darp keeps its state and generated files (everything but config.json).
+    pub darp_root: PathBuf,
     pub config_path: PathBuf,
     pub portmap_path: PathBuf,
     pub dnsmasq_dir: PathBuf,
     pub vhost_container_conf: PathBuf,
+    /// The reverse proxy's `stream {}` block, for services with a stream port.
+    pub stream_container_conf: PathBuf,
     pub hosts_container_path: PathBuf,
     pub nginx_conf_path: PathBuf,
     pub container_host_ip_path: PathBuf,
+    /// Static files served by the reverse proxy at `http://darp.test/`.
+    pub www_dir: PathBuf,
+    pub services_index_path: PathBuf,
+    /// Lock files limiting how many engine operations run at once; see `Engine::acquire_slot`.
+    pub engine_slots_dir: PathBuf,
+    /// The folders found by the last scan, so a deploy can skip folders that haven't changed.
+    pub scan_manifest_path: PathBuf,
+    pub secrets_path: PathBuf,
+    /// Copies of config.json from before each change, for `darp config rollback`.
+    pub backups_dir: PathBuf,
+    /// Dockerfiles generated from services' `image_build` recipes.
+    pub images_dir: PathBuf,
+}
+
+/// `path` as an absolute directory: `{home}` and a leading `~` expand to the home
+/// directory, and relative paths are taken from `base`.
+pub fn absolute_dir(path: &str, base: &Path) -> Result<PathBuf> {
+    let home = home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.to_string_lossy(), rest)
+        }
+        _ => path.replace("{home}", &home.to_string_lossy()),
+    };
+    Ok(base.join(path))
 }
 
 impl DarpPaths {
+    /// Paths from the environment. `DARP_ROOT` (default `~/.darp`) holds everything;
+    /// `DARP_CONFIG_DIR` moves config.json elsewhere (e.g. a dotfiles repo) and
+    /// `DARP_STATE_DIR`, or `state_dir` in config.json, moves the rest. Relative values
+    /// are taken from the current directory, and `state_dir` from config.json's directory.
     pub fn from_env() -> Result<Self> {
+        Self::from_args(None, None)
+    }
+
+    /// `from_env`, overridden by the `--darp-root` and `--config` flags. `--darp-root`
+    /// stands in for `DARP_ROOT` and also sets aside `DARP_CONFIG_DIR` and `DARP_STATE_DIR`,
+    /// so one flag is enough to isolate a run; `--config` names config.json itself.
+    pub fn from_args(darp_root: Option<&str>, config: Option<&str>) -> Result<Self> {
         let home = home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
-        let darp_root_env = std::env::var("DARP_ROOT")
-            .unwrap_or_else(|_| home.join(".darp").to_string_lossy().into_owned());
-        let darp_root = PathBuf::from(darp_root_env);
+        let cwd = std::env::current_dir()?;
+        let root_flag = darp_root.is_some();
+        let from_flag = |value: Option<&str>| value.map(|v| absolute_dir(v, &cwd)).transpose();
+        let dir_from_env = |name: &str| -> Result<Option<PathBuf>> {
+            match std::env::var(name) {
+                Ok(value) if !value.is_empty() && !root_flag => {
+                    Ok(Some(absolute_dir(&value, &cwd)?))
+                }
+                _ => Ok(None),
+            }
+        };
+
+        let darp_root = match from_flag(darp_root)? {
+            Some(dir) => dir,
+            None => dir_from_env("DARP_ROOT")?.unwrap_or_else(|| home.join(".darp")),
+        };
+        let config_path = match from_flag(config)? {
+            Some(path) => path,
+            None => dir_from_env("DARP_CONFIG_DIR")?
+                .unwrap_or_else(|| darp_root.clone())
+                .join("config.json"),
+        };
+        let config_dir = config_path.parent().unwrap_or(&darp_root).to_path_buf();
+        let state_dir = match dir_from_env("DARP_STATE_DIR")? {
+            Some(dir) => dir,
+            None => match configured_state_dir(&config_path) {
+                Some(dir) => absolute_dir(&dir, &config_dir)?,
+                None => darp_root,
+            },
+        };
+        Ok(Self::new(config_path, state_dir))
+    }
 
-        Ok(Self {
-            _darp_root: darp_root.clone(),
-            config_path: darp_root.join("config.json"),
+    /// Every path darp uses, from where config.json is and the directory for the rest.
+    pub fn new(config_path: PathBuf, state_dir: PathBuf) -> Self {
+        let darp_root = state_dir;
+        Self {
+            darp_root: darp_root.clone(),
+            config_path,
             portmap_path: darp_root.join("portmap.json"),
             dnsmasq_dir: darp_root.join("dnsmasq.d"),
             vhost_container_conf: darp_root.join("vhost_container.conf"),
+            stream_container_conf: darp_root.join("stream_container.conf"),
             hosts_container_path: darp_root.join("hosts_container"),
             nginx_conf_path: darp_root.join("nginx.conf"),
             container_host_ip_path: darp_root.join("container_host_ip"),
-        })
+            www_dir: darp_root.join("www"),
+            services_index_path: darp_root.join("www").join("services.json"),
+            engine_slots_dir: darp_root.join("engine-slots"),
+            scan_manifest_path: darp_root.join("scan_manifest.json"),
+            secrets_path: darp_root.join("secrets.json"),
+            backups_dir: darp_root.join("backups"),
+            images_dir: darp_root.join("images"),
+        }
     }
 }
 
+/// The `state_dir` set in config.json, read on its own since the rest of the config
+/// isn't needed to find darp's files.
+fn configured_state_dir(config_path: &Path) -> Option<String> {
+    let data = fs::read(config_path).ok()?;
+    let value: serde_json::Value = serde_json::from_slice(&data).ok()?;
+    value.get("state_dir")?.as_str().map(String::from)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PreConfig {
     pub location: String,
@@ -195,14 +567,377 @@ pub struct Config {
     pub urls_in_hosts: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub wsl: Option<bool>,
+    /// Give every http service a `<service>-<domain>.local` name as well, which
+    /// `darp mdns` advertises to the rest of the network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mdns: Option<bool>,
+    /// Put the reverse proxy and service containers on darp's own bridge network and
+    /// proxy to container names. Defaults to true; `false` restores host-port proxying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub darp_network: Option<bool>,
     /// Base of the per-service debug-port range assigned by `darp deploy`.
     /// Defaults to `DEBUG_PORT_BASE` when unset.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub debug_port_base: Option<u16>,
+    /// Extra settings for the `darp-reverse-proxy` helper container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverse_proxy: Option<HelperContainer>,
+    /// Extra settings for the `darp-masq` helper container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnsmasq: Option<HelperContainer>,
+    /// Arguments added to every `run` the engine issues, helpers and services alike
+    /// (e.g. `--security-opt=label=disable`; a flag and its value are one argument).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine_args: Option<Vec<String>>,
+    /// Development server whose container engine darp drives over SSH.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<Remote>,
+    /// Shortcuts for darp invocations: `darp <name> [args]` runs `darp <expansion> [args]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<std::collections::BTreeMap<String, String>>,
+    /// Static `hostname: ip` entries added to the hosts files for every domain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<std::collections::BTreeMap<String, String>>,
+    /// Directory for darp's state and generated files when they shouldn't sit next to
+    /// config.json; relative to config.json's directory. `DARP_STATE_DIR` wins over it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_dir: Option<String>,
+    /// Template for service container names, with `{domain}`, `{service}` and `{user}`
+    /// tokens. Defaults to `engine::DEFAULT_CONTAINER_NAME`, or
+    /// `engine::SHARED_CONTAINER_NAME` on a shared host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+    /// This user's share of a machine several people run darp on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_host: Option<SharedHost>,
+    /// Container starts, stops, pulls and builds that may run at once across every darp
+    /// process; 0 removes the limit. Defaults to `engine::DEFAULT_ENGINE_CONCURRENCY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine_concurrency: Option<u32>,
+}
+
+/// One user's namespace on a shared development server: their services are
+/// `<service>.<domain>.<user>.test`, their containers carry the user in their name, and
+/// their ports come from a range of their own. Every user's vhosts go into `proxy_dir`,
+/// which the one reverse proxy on the machine serves.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SharedHost {
+    /// Name to namespace under; a DNS label (e.g. 'alice').
+    pub user: String,
+    /// First port of this user's `SHARED_PORT_RANGE` ports.
+    pub port_base: u16,
+    /// Directory shared by every user on the machine; defaults to `SHARED_PROXY_DIR`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_dir: Option<String>,
+}
+
+impl SharedHost {
+    pub fn proxy_dir(&self) -> PathBuf {
+        PathBuf::from(self.proxy_dir.as_deref().unwrap_or(SHARED_PROXY_DIR))
+    }
+}
+
+/// A service's hostname: `<service>.<domain>.test`, with `user` before `.test` on a
+/// shared host.
+pub fn service_host(service: &str, domain: &str, user: Option<&str>) -> String {
+    match user {
+        Some(user) => format!("{}.{}.{}.test", service, domain, user),
+        None => format!("{}.{}.test", service, domain),
+    }
+}
+
+pub fn validate_shared_user(user: &str) -> Result<()> {
+    let valid = !user.is_empty()
+        && user.len() <= 63
+        && user
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !user.starts_with('-')
+        && !user.ends_with('-');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "'{}' can't be a shared host user (lowercase letters, digits and '-', not at either end).",
+            user
+        ))
+    }
+}
+
+/// A development server darp deploys to instead of the local engine. Paths are handed
+/// to the remote engine unchanged, so domain locations must exist at the same absolute
+/// path on the server; darp copies its own generated files over on `deploy`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Remote {
+    /// SSH destination: `user@host` or a host alias from `~/.ssh/config`.
+    pub host: String,
+    /// IP address `.test` names resolve to, i.e. the server's address on your network.
+    pub address: String,
+    /// Engine URL, for when the default doesn't fit (e.g. a rootless Podman socket:
+    /// `ssh://me@devbox/run/user/1000/podman/podman.sock`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Point the local `/etc/resolver/test` at the server's dnsmasq on `darp install`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<bool>,
+}
+
+impl Remote {
+    /// The server named by an engine endpoint URL as (SSH destination, hostname):
+    /// `ssh://[user@]host[:port]` or `tcp://host:port`. Unix sockets and loopback
+    /// addresses are local, so they give None.
+    pub fn parse_engine_host(url: &str) -> Option<(String, String)> {
+        let (ssh, rest) = if let Some(rest) = url.strip_prefix("ssh://") {
+            (true, rest)
+        } else {
+            (false, url.strip_prefix("tcp://")?)
+        };
+        let authority = rest.split('/').next().unwrap_or_default();
+        let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+        let (hostname, has_port) = match host_port.strip_prefix('[') {
+            Some(bracketed) => (
+                bracketed.split(']').next().unwrap_or_default(),
+                bracketed.contains("]:"),
+            ),
+            None => (
+                host_port.split(':').next().unwrap_or_default(),
+                host_port.contains(':'),
+            ),
+        };
+        if hostname.is_empty() || matches!(hostname, "localhost" | "127.0.0.1" | "::1") {
+            return None;
+        }
+        let destination = match (ssh, has_port) {
+            (false, _) => hostname.to_string(),
+            // A port only fits in the URL form of an SSH destination.
+            (true, true) => format!("ssh://{}", authority),
+            (true, false) => authority.to_string(),
+        };
+        Some((destination, hostname.to_string()))
+    }
+
+    /// The remote an engine is already pointed at over SSH through `DOCKER_HOST` (Docker)
+    /// or `CONTAINER_HOST` (Podman), so darp treats that server as it does a configured
+    /// `remote`. `.test` names resolve to its address through its dnsmasq. The address
+    /// is the URL's host name as is; see `resolved_address`. Other URLs (`tcp://`,
+    /// `unix://`) give None: darp copies its files to a remote over SSH, which nothing
+    /// says such a host accepts.
+    pub fn from_engine_env(engine: Option<&str>) -> Option<Remote> {
+        let var = match engine? {
+            "docker" => "DOCKER_HOST",
+            "podman" => "CONTAINER_HOST",
+            _ => return None,
+        };
+        let url = std::env::var(var).ok()?;
+        if !url.starts_with("ssh://") {
+            return None;
+        }
+        let (host, hostname) = Self::parse_engine_host(&url)?;
+        Some(Remote {
+            host,
+            address: hostname,
+            url: Some(url),
+            resolver: Some(true),
+        })
+    }
+
+    /// `address` as an IP address. A host name is looked up the first time a command
+    /// needs it, not when the config loads, and the answer is kept for the rest of the
+    /// run. One that can't be resolved is warned about and used as is.
+    pub fn resolved_address(&self) -> String {
+        if self.address.parse::<std::net::IpAddr>().is_ok() {
+            return self.address.clone();
+        }
+        static RESOLVED: std::sync::OnceLock<std::sync::Mutex<BTreeMap<String, String>>> =
+            std::sync::OnceLock::new();
+        let mut resolved = RESOLVED
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        resolved
+            .entry(self.address.clone())
+            .or_insert_with(|| {
+                use std::net::ToSocketAddrs;
+                let addrs: Vec<_> = (self.address.as_str(), 0)
+                    .to_socket_addrs()
+                    .into_iter()
+                    .flatten()
+                    .map(|a| a.ip())
+                    .collect();
+                match addrs.iter().find(|ip| ip.is_ipv4()).or(addrs.first()) {
+                    Some(ip) => ip.to_string(),
+                    None => {
+                        tracing::warn!(
+                            "the remote's address, {}, can't be resolved; set an IP with 'darp config set remote'",
+                            self.address
+                        );
+                        self.address.clone()
+                    }
+                }
+            })
+            .clone()
+    }
+
+    /// The engine endpoint: `url` if set, else `ssh://<host>`, plus the default rootful
+    /// socket path for Podman (Docker's SSH transport finds the socket itself).
+    pub fn engine_url(&self, podman: bool) -> String {
+        if let Some(url) = &self.url {
+            return url.clone();
+        }
+        let host = self.host.trim_start_matches("ssh://");
+        if podman {
+            format!("ssh://{}/run/podman/podman.sock", host)
+        } else {
+            format!("ssh://{}", host)
+        }
+    }
+}
+
+/// User-supplied settings for one of darp's own helper containers.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HelperContainer {
+    /// Extra arguments passed to `<engine> run` before the image name
+    /// (e.g. `--network=devnet`, `--memory=128m`, `--restart=always`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_args: Option<Vec<String>>,
+}
+
+/// Helper container names accepted by `darp config add/rm helper-arg`.
+pub const HELPER_CONTAINER_VALUES: &[&str] = &["reverse-proxy", "dnsmasq"];
+
+/// Restart policies accepted for served containers. `on-failure:<N>` (a retry cap) is
+/// accepted too. Anything but `no` replaces darp's default `--rm`.
+pub const RESTART_POLICY_VALUES: &[&str] = &["no", "on-failure", "always", "unless-stopped"];
+
+/// Lower-case `engine` when it's one darp supports.
+fn validate_engine(engine: &str) -> Result<String> {
+    engine::EngineKind::parse(engine)
+        .map(|kind| kind.as_str().to_string())
+        .ok_or_else(|| crate::error::DarpError::UnknownEngine(engine.to_string()).into())
+}
+
+/// The engine a service runs on when it isn't the global `engine`: its domain's, else its
+/// environment's.
+pub fn engine_override<'a>(
+    domain: &'a Domain,
+    environment: Option<&'a Environment>,
+) -> Option<&'a str> {
+    domain
+        .engine
+        .as_deref()
+        .or_else(|| environment.and_then(|e| e.engine.as_deref()))
+}
+
+/// Engine `run` flags that take a value. An `engine_args` or `run_args` entry is one
+/// argument, so these have to be given as `--flag=value`.
+pub const RUN_VALUE_FLAGS: &[&str] = &[
+    "--add-host",
+    "--arch",
+    "--cap-add",
+    "--cap-drop",
+    "--cpus",
+    "--device",
+    "--dns",
+    "--entrypoint",
+    "--env",
+    "--env-file",
+    "--gpus",
+    "--group-add",
+    "--health-cmd",
+    "--health-interval",
+    "--hostname",
+    "--ipc",
+    "--label",
+    "--log-driver",
+    "--log-opt",
+    "--memory",
+    "--mount",
+    "--name",
+    "--network",
+    "--os",
+    "--pid",
+    "--platform",
+    "--pod",
+    "--publish",
+    "--pull",
+    "--restart",
+    "--security-opt",
+    "--shm-size",
+    "--stop-signal",
+    "--stop-timeout",
+    "--sysctl",
+    "--tmpfs",
+    "--ulimit",
+    "--user",
+    "--userns",
+    "--volume",
+    "--workdir",
+    "-e",
+    "-h",
+    "-l",
+    "-p",
+    "-u",
+    "-v",
+    "-w",
+];
+
+/// Check an `engine_args` / `run_args` entry: a single option, with any value joined on
+/// by `=` since the next entry isn't read as its value.
+fn validate_run_arg(arg: &str) -> Result<()> {
+    if !arg.starts_with('-') {
+        return Err(anyhow!(
+            "'{}' is not an option; give a flag and its value as one argument, '--flag=value'",
+            arg
+        ));
+    }
+    if RUN_VALUE_FLAGS.contains(&arg) {
+        return Err(anyhow!(
+            "'{}' takes a value; add it as '{}=<value>'",
+            arg,
+            arg
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_restart_policy(value: &str) -> Result<()> {
+    let valid = RESTART_POLICY_VALUES.contains(&value)
+        || value
+            .strip_prefix("on-failure:")
+            .is_some_and(|n| n.parse::<u32>().is_ok());
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid restart policy '{}' (must be one of: {}, or on-failure:<N>)",
+            value,
+            RESTART_POLICY_VALUES.join(", ")
+        ))
+    }
+}
+
+/// Append to a string list (serve hooks, tags); adding the same item twice is a no-op.
+fn add_list_item(list: &mut Option<Vec<String>>, item: &str) -> Result<()> {
+    let items = list.get_or_insert_with(Vec::new);
+    if !items.iter().any(|i| i == item) {
+        items.push(item.to_string());
+    }
+    Ok(())
+}
+
+/// Remove from a string list, dropping the list once it's empty. `None` if it wasn't there.
+fn rm_list_item(list: &mut Option<Vec<String>>, item: &str) -> Option<()> {
+    let items = list.as_mut()?;
+    let index = items.iter().position(|i| i == item)?;
+    items.remove(index);
+    if items.is_empty() {
+        *list = None;
+    }
+    Some(())
 }
 
 /// Allowed values for a service's connection_type. Absent/None is treated as "http".
-pub const CONNECTION_TYPE_VALUES: &[&str] = &["http", "websocket", "tcp"];
+pub const CONNECTION_TYPE_VALUES: &[&str] = &["http", "websocket", "tcp", "static"];
 
 pub fn validate_connection_type(value: &str) -> Result<()> {
     if CONNECTION_TYPE_VALUES.contains(&value) {
@@ -216,6 +951,74 @@ pub fn validate_connection_type(value: &str) -> Result<()> {
     }
 }
 
+/// Check a hostname darp writes to hosts files and `server_name`: letters, digits, `-`
+/// and `.`, not starting with `-` or `.` nor ending with `.`.
+pub fn validate_hostname(host: &str) -> Result<()> {
+    let valid = !host.is_empty()
+        && !host.starts_with(['-', '.'])
+        && !host.ends_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if !valid {
+        return Err(anyhow!("'{}' is not a valid hostname.", host));
+    }
+    Ok(())
+}
+
+/// Check an `extra_hosts` entry: a hostname and an IPv4 or IPv6 address.
+pub fn validate_extra_host(host: &str, ip: &str) -> Result<()> {
+    validate_hostname(host)?;
+    if ip.parse::<std::net::IpAddr>().is_err() {
+        return Err(anyhow!("'{}' is not an IP address.", ip));
+    }
+    Ok(())
+}
+
+/// Check a `container_name` template: it needs both `{domain}` and `{service}` so every
+/// service gets its own name, and takes no other tokens. Literal text must be valid in a
+/// container name; the tokens' values are sanitized when the name is built.
+pub fn validate_container_name(template: &str) -> Result<()> {
+    for token in ["{domain}", "{service}"] {
+        if !template.contains(token) {
+            return Err(anyhow!(
+                "The container name template needs {}, got '{}'.",
+                token,
+                template
+            ));
+        }
+    }
+    let literal = template
+        .replace("{domain}", "")
+        .replace("{service}", "")
+        .replace("{user}", "");
+    if let Some(c) = literal
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '_' | '.' | '-'))
+    {
+        return Err(anyhow!(
+            "'{}' can't be used in a container name (letters, digits, '_', '.' and '-'; tokens are {{domain}}, {{service}} and {{user}}).",
+            c
+        ));
+    }
+    Ok(())
+}
+
+/// Allowed values for a service's protocol. Absent/None is treated as "http".
+pub const PROTOCOL_VALUES: &[&str] = &["http", "grpc"];
+
+pub fn validate_protocol(value: &str) -> Result<()> {
+    if PROTOCOL_VALUES.contains(&value) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid protocol '{}' (must be one of: {})",
+            value,
+            PROTOCOL_VALUES.join(", ")
+        ))
+    }
+}
+
 pub fn resolve_location(location: &str) -> Result<PathBuf> {
     let home = home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
     let resolved = location.replace("{home}", &home.to_string_lossy());
@@ -310,6 +1113,31 @@ pub struct Domain {
         deserialize_with = "deserialize_nullable_override"
     )]
     pub connection_type_override: Option<Option<String>>,
+    /// Existing container network that this domain's serve/shell containers join
+    /// (e.g. one shared with a compose stack) instead of the engine's default bridge.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Under Podman, run this domain's service containers in one shared pod.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pod: Option<bool>,
+    /// Traffic splits, keyed by the service whose URL is split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canaries: Option<BTreeMap<String, Canary>>,
+    /// Static `hostname: ip` entries added to the hosts files next to the domain's
+    /// service URLs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<BTreeMap<String, String>>,
+    /// Container engine for this domain's services, when it isn't the global `engine`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+}
+
+/// Send `percent` of the requests for a service's URL to `service` (another service in
+/// the same domain) instead.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Canary {
+    pub service: String,
+    pub percent: u8,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -486,6 +1314,108 @@ pub struct Service {
         deserialize_with = "deserialize_nullable_override"
     )]
     pub connection_type_override: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<ResourceLimits>,
+    /// Restart policy for `darp serve`; overrides the environment's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<String>,
+    /// Run the containers as the host user; overrides the environment's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_user: Option<bool>,
+    /// Mount the service folder at `/app` read-only; overrides the environment's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_app: Option<bool>,
+    /// Image for `darp shell`; overrides the environment's. Falls back to
+    /// `default_container_image` when neither is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell_image: Option<String>,
+    /// A recipe darp builds the service's image from, in place of a Dockerfile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_build: Option<ImageBuild>,
+    /// Env file passed to the engine's `--env-file` by `darp serve`/`darp shell`.
+    /// Relative paths are resolved against the project directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+    /// Command (or http(s) URL to fetch) the engine runs to decide whether the service
+    /// is healthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<String>,
+    /// Commands `darp serve` runs in `/app` before the serve command (installing deps,
+    /// running migrations). The first failure stops the container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_serve: Option<Vec<String>>,
+    /// Commands run in the container once the serve command exits, including when the
+    /// container is stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_serve: Option<Vec<String>>,
+    /// Program started with `--entrypoint` in place of the `sh` darp runs its bootstrap
+    /// script with (e.g. `/busybox/sh`); it's given `-c <script>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entrypoint: Option<String>,
+    /// Extra arguments for the engine's `run` of this service's containers (e.g.
+    /// `--shm-size=1g`), after darp's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_args: Option<Vec<String>>,
+    /// One-line summary shown next to the service's URL in `darp urls` exports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Labels for picking out a subset of a domain (`darp up --tag`, `darp urls --tag`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Which wave `darp up` starts the service in: lower values first, 0 when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_order: Option<i32>,
+    /// Inject a reload script into the service's HTML pages (through the reverse proxy)
+    /// and reload them when its folder changes, while `darp live-reload` runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_reload: Option<bool>,
+    /// Another darp service (`service`, or `domain.service` in another domain) that the
+    /// proxy sends a copy of every request to. Its responses are discarded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<String>,
+    /// `grpc` to have the proxy speak gRPC (`grpc_pass`) to the service over its HTTP/2
+    /// listener; `http` (the default) otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// A dedicated host port on the reverse proxy for a `tcp` service, so clients reach it
+    /// at a stable `{svc}.{dom}.test:{port}` (e.g. 5432 for postgres).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<Stream>,
+    /// How many containers `darp serve` starts for the service, each on its own proxy
+    /// port, with the proxy balancing requests across them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<u32>,
+    /// Hostnames the service answers on besides `{svc}.{dom}.test` (e.g. `api.myapp.test`
+    /// or a bare `myapp.test`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
+    /// Real hostnames (e.g. `login.mycompany.dev`) routed to the service, for OAuth
+    /// redirect URIs and cookies that must match production exactly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_domains: Option<BTreeMap<String, CustomDomain>>,
+    /// Port a process run directly on the host (not by darp) listens on. `darp deploy`
+    /// proxies the service's URL to it on the host gateway, and `darp serve` starts nothing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_port: Option<u16>,
+}
+
+/// TLS for a custom domain: a certificate and key on the host (e.g. from `mkcert`) the
+/// proxy serves on port 443, picked by SNI. Without them the name is served over HTTP.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct CustomDomain {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// A port the reverse proxy's `stream {}` block listens on and forwards, TCP or UDP, to
+/// one service.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stream {
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -571,21 +1501,80 @@ pub struct Environment {
         deserialize_with = "deserialize_nullable_override"
     )]
     pub connection_type_override: Option<Option<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<ResourceLimits>,
+    /// Restart policy for `darp serve` containers; see `RESTART_POLICY_VALUES`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<String>,
+    /// Run `darp serve` and `darp shell` containers as the host user (`--user uid:gid`), so
+    /// files they create in `/app` aren't owned by root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_user: Option<bool>,
+    /// Mount the service folder at `/app` read-only, so a container can't change the
+    /// source tree (e.g. to reproduce a CI run).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_app: Option<bool>,
+    /// Image for `darp shell` containers, e.g. a tool-rich variant of the serve image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell_image: Option<String>,
+    /// Container engine for services served under this environment; a domain's `engine`
+    /// takes precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
 }
 
-/// Declaration state of a single field at a single layer.
-/// - `Absent`: neither `field` nor `*field` was declared at this layer.
-/// - `Set`: plain `field` with a non-null value.
-/// - `OverrideSet`: `*field` with a non-null value — resets the parent chain.
-/// - `OverrideNull`: `*field: null` — explicit "no value, don't inherit from parents".
-enum FieldDecl<T> {
-    Absent,
-    Set(T),
-    OverrideSet(T),
-    OverrideNull,
+/// A service's image described inline: `base` plus the packages to install and
+/// commands to run on top. darp generates a Dockerfile from it and builds it on first use.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ImageBuild {
+    pub base: String,
+    /// Installed with whichever of apk, apt-get, dnf or microdnf the base image has.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+    /// Shell commands, each its own `RUN` step, after the packages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run: Vec<String>,
 }
 
-fn decl_scalar<'a>(
+/// CPU/memory caps for a served or shelled container, passed to the engine as
+/// `--cpus` / `--memory`. Values are engine syntax (e.g. `"1.5"`, `"2g"`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ResourceLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.cpus.is_none() && self.memory.is_none()
+    }
+
+    /// Copy every limit `other` declares over this one.
+    fn overlay(&mut self, other: &ResourceLimits) {
+        if other.cpus.is_some() {
+            self.cpus = other.cpus.clone();
+        }
+        if other.memory.is_some() {
+            self.memory = other.memory.clone();
+        }
+    }
+}
+
+/// Declaration state of a single field at a single layer.
+/// - `Absent`: neither `field` nor `*field` was declared at this layer.
+/// - `Set`: plain `field` with a non-null value.
+/// - `OverrideSet`: `*field` with a non-null value — resets the parent chain.
+/// - `OverrideNull`: `*field: null` — explicit "no value, don't inherit from parents".
+enum FieldDecl<T> {
+    Absent,
+    Set(T),
+    OverrideSet(T),
+    OverrideNull,
+}
+
+fn decl_scalar<'a>(
     regular: &'a Option<String>,
     override_: &'a Option<Option<String>>,
 ) -> FieldDecl<&'a str> {
@@ -750,6 +1739,11 @@ pub struct ResolvedSettings {
     pub variables: Option<BTreeMap<String, String>>,
     pub volumes: Option<Vec<Volume>>,
     pub connection_type: Option<String>,
+    pub limits: Option<ResourceLimits>,
+    pub restart: Option<String>,
+    pub shell_image: Option<String>,
+    pub map_user: bool,
+    pub read_only_app: bool,
 }
 
 impl ResolvedSettings {
@@ -801,6 +1795,18 @@ impl ResolvedSettings {
             merge_vec(&mut volumes, &layer.volumes);
         }
 
+        // Limits only live on environments and services; the service wins per key.
+        let mut limits = ResourceLimits::default();
+        for layer_limits in [
+            environment.and_then(|e| e.limits.as_ref()),
+            service.and_then(|s| s.limits.as_ref()),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            limits.overlay(layer_limits);
+        }
+
         Self {
             domain_name,
             group_name,
@@ -815,6 +1821,22 @@ impl ResolvedSettings {
             variables,
             volumes,
             connection_type,
+            limits: (!limits.is_empty()).then_some(limits),
+            // Like limits, restart lives on environments and services only.
+            restart: service
+                .and_then(|s| s.restart.clone())
+                .or_else(|| environment.and_then(|e| e.restart.clone())),
+            shell_image: service
+                .and_then(|s| s.shell_image.clone())
+                .or_else(|| environment.and_then(|e| e.shell_image.clone())),
+            map_user: service
+                .and_then(|s| s.map_user)
+                .or_else(|| environment.and_then(|e| e.map_user))
+                .unwrap_or(false),
+            read_only_app: service
+                .and_then(|s| s.read_only_app)
+                .or_else(|| environment.and_then(|e| e.read_only_app))
+                .unwrap_or(false),
         }
     }
 
@@ -830,12 +1852,36 @@ impl ResolvedSettings {
             None => Some(base),
         }
     }
+
+    /// Like `resolve_full_image_name`, for `darp shell`: `shell_image` is preferred over
+    /// `default_container_image`, and image_repository applies to either.
+    pub fn resolve_shell_image_name(&self, cli_image: Option<&str>) -> Option<String> {
+        self.resolve_full_image_name(cli_image.or(self.shell_image.as_deref()))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Volume {
     pub container: String,
     pub host: String,
+    /// Mounted `:ro`, so the container can read but not change it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+}
+
+/// Whether a volume's `host` names an engine volume rather than a host path. Like the
+/// engines' own `-v`, a bare name without a `/` is a volume (e.g. `shop-node-modules`).
+pub fn is_volume_name(host: &str) -> bool {
+    let mut chars = host.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+impl Volume {
+    /// The engine volume this mounts, when `host` is a volume name.
+    pub fn volume_name(&self) -> Option<&str> {
+        is_volume_name(&self.host).then_some(self.host.as_str())
+    }
 }
 
 fn strip_nulls(value: &mut serde_json::Value) {
@@ -852,6 +1898,16 @@ fn strip_nulls(value: &mut serde_json::Value) {
     }
 }
 
+/// A service folder resolved against the config.
+struct LocatedService<'a> {
+    domain_name: &'a str,
+    domain: &'a Domain,
+    group_name: String,
+    group: Option<&'a Group>,
+    service: String,
+    dir: PathBuf,
+}
+
 pub struct ServiceContext<'a> {
     pub current_dir: PathBuf,
     pub current_directory_name: String,
@@ -866,30 +1922,60 @@ pub struct ServiceContext<'a> {
 
 impl Config {
     pub fn load(path: &Path) -> Result<Self> {
+        let _timing = timings::phase("config load");
         if !path.exists() {
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            fs::write(path, b"{}")?;
+            write_atomic(path, b"{}")?;
             return Ok(Self::default());
         }
 
         maybe_migrate(path)?;
 
         let data = fs::read(path)?;
-        let cfg: Config = serde_json::from_slice(&data).unwrap_or_default();
+        let cfg: Config = serde_json::from_slice(&data).map_err(|e| unreadable_config(path, e))?;
         Self::validate_no_double_declarations(&cfg)?;
         Ok(cfg)
     }
 
     pub fn save(&self, path: &Path) -> Result<()> {
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
         let mut value = serde_json::to_value(self)?;
         strip_nulls(&mut value);
         let data = serde_json::to_vec_pretty(&value)?;
-        fs::write(path, data)?;
+        write_atomic(path, &data)
+    }
+
+    /// `save` to `paths.config_path`, first keeping the version it replaces in
+    /// `paths.backups_dir` for `darp config rollback`.
+    pub fn save_with_backup(&self, paths: &DarpPaths) -> Result<()> {
+        let mut value = serde_json::to_value(self)?;
+        strip_nulls(&mut value);
+        let data = serde_json::to_vec_pretty(&value)?;
+        backups::back_up(&paths.backups_dir, &paths.config_path, &data)?;
+        self.save(&paths.config_path)
+    }
+
+    /// Add or replace an alias. `builtins` are the command names it may not shadow.
+    pub fn add_alias(&mut self, name: &str, expansion: &str, builtins: &[String]) -> Result<()> {
+        alias::validate_alias(name, expansion, builtins)?;
+        self.aliases
+            .get_or_insert_with(Default::default)
+            .insert(name.to_string(), expansion.to_string());
+        Ok(())
+    }
+
+    pub fn rm_alias(&mut self, name: &str) -> Result<()> {
+        let aliases = self
+            .aliases
+            .as_mut()
+            .ok_or_else(|| anyhow!("No aliases configured"))?;
+        if aliases.remove(name).is_none() {
+            return Err(anyhow!("Alias '{}' does not exist", name));
+        }
+        if aliases.is_empty() {
+            self.aliases = None;
+        }
         Ok(())
     }
 
@@ -927,13 +2013,148 @@ impl Config {
         Ok(())
     }
 
+    fn helper_container_mut(&mut self, helper: &str) -> Result<&mut Option<HelperContainer>> {
+        match helper {
+            "reverse-proxy" => Ok(&mut self.reverse_proxy),
+            "dnsmasq" => Ok(&mut self.dnsmasq),
+            _ => Err(anyhow!(
+                "invalid helper container '{}' (must be one of: {})",
+                helper,
+                HELPER_CONTAINER_VALUES.join(", ")
+            )),
+        }
+    }
+
+    pub fn add_helper_run_arg(&mut self, helper: &str, arg: &str) -> Result<()> {
+        let args = self
+            .helper_container_mut(helper)?
+            .get_or_insert_with(HelperContainer::default)
+            .run_args
+            .get_or_insert_with(Vec::new);
+        if args.iter().any(|a| a == arg) {
+            return Err(anyhow!("{} already has run arg '{}'", helper, arg));
+        }
+        args.push(arg.to_string());
+        Ok(())
+    }
+
+    pub fn add_engine_arg(&mut self, arg: &str) -> Result<()> {
+        validate_run_arg(arg)?;
+        let args = self.engine_args.get_or_insert_with(Vec::new);
+        if args.iter().any(|a| a == arg) {
+            return Err(anyhow!("engine arg '{}' is already configured", arg));
+        }
+        args.push(arg.to_string());
+        Ok(())
+    }
+
+    pub fn rm_engine_arg(&mut self, arg: &str) -> Result<()> {
+        let args = self
+            .engine_args
+            .as_mut()
+            .ok_or_else(|| anyhow!("No engine args are configured."))?;
+        let before = args.len();
+        args.retain(|a| a != arg);
+        if args.len() == before {
+            return Err(anyhow!("No engine arg '{}' is configured.", arg));
+        }
+        if args.is_empty() {
+            self.engine_args = None;
+        }
+        Ok(())
+    }
+
+    pub fn rm_helper_run_arg(&mut self, helper: &str, arg: &str) -> Result<()> {
+        let slot = self.helper_container_mut(helper)?;
+        let args = slot
+            .as_mut()
+            .and_then(|h| h.run_args.as_mut())
+            .ok_or_else(|| anyhow!("{} has no custom run args.", helper))?;
+        let before = args.len();
+        args.retain(|a| a != arg);
+        if args.len() == before {
+            return Err(anyhow!("{} has no run arg '{}'", helper, arg));
+        }
+        if args.is_empty() {
+            *slot = None;
+        }
+        Ok(())
+    }
+
     /// Build a full ServiceContext from the current working directory.
     /// Returns None when cwd isn't inside any configured domain.
     pub fn service_context_from_cwd(&self, env_cli: Option<String>) -> Option<ServiceContext<'_>> {
-        let current_dir = std::env::current_dir().ok()?;
-        let current_directory_name = current_dir.file_name()?.to_string_lossy().to_string();
+        self.service_context_for_dir(resolver::current_dir().ok()?, env_cli)
+    }
+
+    /// `service_context_from_cwd` for commands that need a service folder: the error says
+    /// why the current directory isn't one.
+    pub fn require_service_context(&self, env_cli: Option<String>) -> Result<ServiceContext<'_>> {
+        let cwd = resolver::current_dir()?;
+        match self.service_context_for_dir(cwd.clone(), env_cli) {
+            Some(ctx) => Ok(ctx),
+            None => Err(self.unmapped_dir_error(&cwd)),
+        }
+    }
+
+    /// Why `dir` doesn't map to a service: it's a domain or group folder itself, or it's
+    /// outside every domain.
+    pub fn unmapped_dir_error(&self, dir: &Path) -> anyhow::Error {
+        const NOT_FOUND: &str =
+            "Current directory does not exist in any darp domain configuration.";
+        if self.domains.as_ref().is_none_or(|d| d.is_empty()) {
+            return anyhow!(
+                "{} No domains are configured yet; create one with e.g. \
+'darp config set dom serve-command -l <folder> <domain> <command>'.",
+                NOT_FOUND
+            );
+        }
+
+        match resolver::locate(dir, &self.domain_dirs()) {
+            Placement::DomainRoot { domain } => {
+                return anyhow!(
+                    "Current directory is the folder of domain '{}'; run this from one of its service folders.",
+                    domain
+                );
+            }
+            Placement::GroupRoot { domain, group } => {
+                return anyhow!(
+                    "Current directory is the folder of group '{}' in domain '{}'; run this from one of its service folders.",
+                    group,
+                    domain
+                );
+            }
+            Placement::Service { .. } | Placement::Outside => {}
+        }
+        let locations: Vec<String> = self
+            .domain_dirs()
+            .into_iter()
+            .map(|d| {
+                format!(
+                    "  {} ({})",
+                    d.name,
+                    resolver::normalize(&d.location).display()
+                )
+            })
+            .collect();
+        anyhow!("{} Domain folders:\n{}", NOT_FOUND, locations.join("\n"))
+    }
 
-        let (domain_name, domain, group_name, group) = self.find_context_by_cwd(&current_dir)?;
+    /// Build a full ServiceContext for a service directory (or any folder below it), as
+    /// if darp were run from it. `current_dir` in the result is the service folder.
+    pub fn service_context_for_dir(
+        &self,
+        dir: PathBuf,
+        env_cli: Option<String>,
+    ) -> Option<ServiceContext<'_>> {
+        let LocatedService {
+            domain_name,
+            domain,
+            group_name,
+            group,
+            service: current_directory_name,
+            dir: current_dir,
+        } = self.locate_service(&dir)?;
         let domain_name = domain_name.to_string();
 
         let service = group
@@ -962,42 +2183,68 @@ impl Config {
         })
     }
 
+    /// Every domain whose location resolves, with its named groups, for `resolver::locate`.
+    pub fn domain_dirs(&self) -> Vec<DomainDir<'_>> {
+        self.domains
+            .iter()
+            .flatten()
+            .filter_map(|(name, d)| {
+                Some(DomainDir {
+                    name,
+                    location: resolve_location(&d.location).ok()?,
+                    groups: d
+                        .groups
+                        .iter()
+                        .flat_map(|g| g.keys())
+                        .map(String::as_str)
+                        .filter(|g| *g != ".")
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// The service `dir` maps to (see `resolver::locate`).
+    fn locate_service(&self, dir: &Path) -> Option<LocatedService<'_>> {
+        let Placement::Service {
+            domain: domain_name,
+            group: group_name,
+            service,
+            dir,
+        } = resolver::locate(dir, &self.domain_dirs())
+        else {
+            return None;
+        };
+        let (domain_name, domain) = self.domains.as_ref()?.get_key_value(domain_name)?;
+        let group = domain.groups.as_ref().and_then(|g| g.get(&group_name));
+        Some(LocatedService {
+            domain_name,
+            domain,
+            group_name,
+            group,
+            service,
+            dir,
+        })
+    }
+
     /// Find domain, group, and service context from the current working directory.
     /// Returns (domain_name, domain, group_name, group_opt) or None.
     ///
-    /// Detection logic:
-    /// 1. If parent dir matches a domain location → group = ".", service = current_dir
-    /// 2. If grandparent dir matches a domain location → group = parent_dir_name, service = current_dir
+    /// Detection logic, walking up from the directory to the domain folder:
+    /// 1. The first folder below the domain is a named group → group = it, service = the
+    ///    folder below that
+    /// 2. Otherwise → group = ".", service = that first folder
     pub fn find_context_by_cwd(
         &self,
         current_dir: &std::path::Path,
     ) -> Option<(&str, &Domain, String, Option<&Group>)> {
-        let _current_dir_name = current_dir.file_name()?.to_string_lossy().to_string();
-
-        let parent = current_dir.parent()?;
-        let parent_canonical = fs::canonicalize(parent).unwrap_or_else(|_| parent.to_path_buf());
-        let parent_key = parent_canonical.to_string_lossy().to_string();
-
-        // Step 1: parent = domain → group "."
-        if let Some((domain_name, domain)) = self.find_domain_by_location(&parent_key) {
-            let group = domain.groups.as_ref().and_then(|g| g.get("."));
-            return Some((domain_name, domain, ".".to_string(), group));
-        }
-
-        // Step 2: grandparent = domain → group = parent dir name
-        let grandparent = parent.parent()?;
-        let grandparent_canonical =
-            fs::canonicalize(grandparent).unwrap_or_else(|_| grandparent.to_path_buf());
-        let grandparent_key = grandparent_canonical.to_string_lossy().to_string();
-
-        let parent_dir_name = parent.file_name()?.to_string_lossy().to_string();
-
-        if let Some((domain_name, domain)) = self.find_domain_by_location(&grandparent_key) {
-            let group = domain.groups.as_ref().and_then(|g| g.get(&parent_dir_name));
-            return Some((domain_name, domain, parent_dir_name, group));
-        }
-
-        None
+        let located = self.locate_service(current_dir)?;
+        Some((
+            located.domain_name,
+            located.domain,
+            located.group_name,
+            located.group,
+        ))
     }
 
     pub fn find_domain_by_location(&self, canonical_path: &str) -> Option<(&str, &Domain)> {
@@ -1006,10 +2253,7 @@ impl Config {
             .iter()
             .find(|(_name, d)| {
                 resolve_location(&d.location)
-                    .map(|loc| {
-                        let canonical = fs::canonicalize(&loc).unwrap_or(loc);
-                        canonical.to_string_lossy() == canonical_path
-                    })
+                    .map(|loc| resolver::same_dir(&loc, Path::new(canonical_path)))
                     .unwrap_or(false)
             })
             .map(|(name, domain)| (name.as_str(), domain))
@@ -1047,6 +2291,36 @@ impl Config {
         Ok(PathBuf::from(s))
     }
 
+    /// Every named volume in the config, with where it's configured: `env <name>`,
+    /// `<domain>`, `<domain>/<group>` or `<domain>.<service>`.
+    pub fn named_volumes(&self) -> BTreeMap<String, Vec<String>> {
+        let mut named: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut add = |volumes: &Option<Vec<Volume>>, owner: String| {
+            for name in volumes.iter().flatten().filter_map(Volume::volume_name) {
+                named
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(owner.clone());
+            }
+        };
+        for (env_name, env) in self.environments.iter().flatten() {
+            add(&env.volumes, format!("env {}", env_name));
+        }
+        for (domain_name, domain) in self.domains.iter().flatten() {
+            add(&domain.volumes, domain_name.clone());
+            for (group_name, group) in domain.groups.iter().flatten() {
+                add(&group.volumes, format!("{}/{}", domain_name, group_name));
+                for (service_name, service) in group.services.iter().flatten() {
+                    add(
+                        &service.volumes,
+                        format!("{}.{}", domain_name, service_name),
+                    );
+                }
+            }
+        }
+        named
+    }
+
     // --- domain/env helpers ---
 
     pub fn add_domain(&mut self, name: &str, location: &str) -> Result<()> {
@@ -1103,6 +2377,39 @@ impl Config {
         ))
     }
 
+    pub fn set_domain_location(&mut self, domain_name: &str, location: &str) -> Result<()> {
+        self.domain_mut(domain_name)?.location = location.to_string();
+        Ok(())
+    }
+
+    /// Point a domain at the folder it was moved to. `location` may be relative to `cwd`
+    /// or start with `~`; it's stored absolute, with the home directory as `{home}`. Its
+    /// groups, services and settings stay as they are, and since ports are assigned by
+    /// domain name the next deploy gives its services the same ones.
+    pub fn move_domain(&mut self, domain_name: &str, location: &str, cwd: &Path) -> Result<()> {
+        let home = home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+        let new_dir = absolute_dir(location, cwd)?;
+        if !new_dir.is_dir() {
+            return Err(anyhow!("{} is not a directory.", new_dir.display()));
+        }
+        for (name, domain) in self.domains.iter().flatten() {
+            if name != domain_name && resolve_location(&domain.location)? == new_dir {
+                return Err(anyhow!(
+                    "Domain '{}' is already at {}.",
+                    name,
+                    new_dir.display()
+                ));
+            }
+        }
+        let stored = match new_dir.strip_prefix(&home) {
+            Ok(rest) if rest.as_os_str().is_empty() => "{home}".to_string(),
+            Ok(rest) => format!("{{home}}/{}", rest.display()),
+            Err(_) => new_dir.display().to_string(),
+        };
+        self.domain_mut(domain_name)?.location = stored;
+        Ok(())
+    }
+
     pub fn rm_domain(&mut self, name: &str) -> Result<()> {
         let domains = self
             .domains
@@ -1476,6 +2783,7 @@ impl Config {
         domain_name: &str,
         container_dir: &str,
         host_dir: &str,
+        read_only: bool,
     ) -> Result<()> {
         let domains = self
             .domains
@@ -1490,6 +2798,7 @@ impl Config {
         let new_vol = Volume {
             container: container_dir.to_string(),
             host: host_dir.to_string(),
+            read_only,
         };
 
         if vols
@@ -1506,8 +2815,11 @@ impl Config {
 
         vols.push(new_vol);
         println!(
-            "Added volume to domain '{}': {} -> {}",
-            domain_name, host_dir, container_dir
+            "Added volume to domain '{}': {} -> {}{}",
+            domain_name,
+            host_dir,
+            container_dir,
+            if read_only { " (read-only)" } else { "" }
         );
         Ok(())
     }
@@ -2143,6 +3455,7 @@ impl Config {
         group_name: &str,
         container_dir: &str,
         host_dir: &str,
+        read_only: bool,
     ) -> Result<()> {
         let domains = self
             .domains
@@ -2159,6 +3472,7 @@ impl Config {
         let new_vol = Volume {
             container: container_dir.to_string(),
             host: host_dir.to_string(),
+            read_only,
         };
 
         if vols
@@ -2176,8 +3490,12 @@ impl Config {
 
         vols.push(new_vol);
         println!(
-            "Added volume to group '{}' in domain '{}': {} -> {}",
-            group_name, domain_name, host_dir, container_dir
+            "Added volume to group '{}' in domain '{}': {} -> {}{}",
+            group_name,
+            domain_name,
+            host_dir,
+            container_dir,
+            if read_only { " (read-only)" } else { "" }
         );
         Ok(())
     }
@@ -2723,6 +4041,7 @@ impl Config {
         env_name: &str,
         container_dir: &str,
         host_dir: &str,
+        read_only: bool,
     ) -> Result<()> {
         let envs = self.environments.get_or_insert_with(BTreeMap::new);
         let env = envs.entry(env_name.to_string()).or_default();
@@ -2731,6 +4050,7 @@ impl Config {
         let new_vol = Volume {
             container: container_dir.to_string(),
             host: host_dir.to_string(),
+            read_only,
         };
 
         if vols
@@ -2747,8 +4067,11 @@ impl Config {
 
         vols.push(new_vol);
         println!(
-            "Added volume to environment '{}': {} -> {}",
-            env_name, host_dir, container_dir
+            "Added volume to environment '{}': {} -> {}{}",
+            env_name,
+            host_dir,
+            container_dir,
+            if read_only { " (read-only)" } else { "" }
         );
         Ok(())
     }
@@ -2795,6 +4118,7 @@ impl Config {
         service_name: &str,
         container_dir: &str,
         host_dir: &str,
+        read_only: bool,
     ) -> Result<()> {
         let domains = self
             .domains
@@ -2817,6 +4141,7 @@ impl Config {
         let new_vol = Volume {
             container: container_dir.to_string(),
             host: host_dir.to_string(),
+            read_only,
         };
 
         if vols
@@ -2834,8 +4159,12 @@ impl Config {
 
         vols.push(new_vol);
         println!(
-            "Added volume to service '{}.{}': {} -> {}",
-            domain_name, service_name, host_dir, container_dir
+            "Added volume to service '{}.{}': {} -> {}{}",
+            domain_name,
+            service_name,
+            host_dir,
+            container_dir,
+            if read_only { " (read-only)" } else { "" }
         );
         Ok(())
     }
@@ -3277,298 +4606,1766 @@ impl Config {
             .get_mut(service_name)
             .ok_or_else(|| anyhow!("service, {}, does not exist", service_name))?;
 
-        if svc.platform.is_none() {
+        if svc.platform.is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no custom platform.",
+                domain_name,
+                service_name
+            ));
+        }
+
+        svc.platform = None;
+        Ok(())
+    }
+
+    // Service-level default_container_image
+
+    pub fn set_service_default_container_image(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        image: &str,
+    ) -> Result<()> {
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+
+        let groups = domain.groups.get_or_insert_with(BTreeMap::new);
+        let group = groups.entry(group_name.to_string()).or_default();
+        let services = group.services.get_or_insert_with(BTreeMap::new);
+        let svc = services
+            .entry(service_name.to_string())
+            .or_insert_with(Service::default);
+
+        svc.default_container_image = Some(image.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_default_container_image(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+
+        let groups = domain
+            .groups
+            .as_mut()
+            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?;
+        let group = groups.get_mut(group_name).ok_or_else(|| {
+            anyhow!(
+                "group, {}, does not exist in domain {}",
+                group_name,
+                domain_name
+            )
+        })?;
+        let services = group.services.as_mut().ok_or_else(|| {
+            anyhow!(
+                "No services configured for group '{}' in domain {}",
+                group_name,
+                domain_name
+            )
+        })?;
+        let svc = services
+            .get_mut(service_name)
+            .ok_or_else(|| anyhow!("service, {}, does not exist", service_name))?;
+
+        if svc.default_container_image.is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no default_container_image.",
+                domain_name,
+                service_name
+            ));
+        }
+
+        svc.default_container_image = None;
+        Ok(())
+    }
+
+    // Domain-level connection_type
+
+    pub fn set_domain_connection_type(&mut self, domain_name: &str, value: &str) -> Result<()> {
+        validate_connection_type(value)?;
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+
+        domain.connection_type = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn rm_domain_connection_type(&mut self, domain_name: &str) -> Result<()> {
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+
+        if domain.connection_type.is_none() {
+            return Err(anyhow!(
+                "Domain '{}' has no custom connection_type.",
+                domain_name
+            ));
+        }
+
+        domain.connection_type = None;
+        Ok(())
+    }
+
+    // Group-level connection_type
+
+    pub fn set_group_connection_type(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        value: &str,
+    ) -> Result<()> {
+        validate_connection_type(value)?;
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+        let groups = domain.groups.get_or_insert_with(BTreeMap::new);
+        let group = groups.entry(group_name.to_string()).or_default();
+
+        group.connection_type = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn rm_group_connection_type(&mut self, domain_name: &str, group_name: &str) -> Result<()> {
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+        let groups = domain
+            .groups
+            .as_mut()
+            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?;
+        let group = groups.get_mut(group_name).ok_or_else(|| {
+            anyhow!(
+                "group, {}, does not exist in domain {}",
+                group_name,
+                domain_name
+            )
+        })?;
+
+        if group.connection_type.is_none() {
+            return Err(anyhow!(
+                "Group '{}' in domain '{}' has no custom connection_type.",
+                group_name,
+                domain_name
+            ));
+        }
+
+        group.connection_type = None;
+        Ok(())
+    }
+
+    // Service-level connection_type
+
+    pub fn set_service_connection_type(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        value: &str,
+    ) -> Result<()> {
+        validate_connection_type(value)?;
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+
+        let groups = domain.groups.get_or_insert_with(BTreeMap::new);
+        let group = groups.entry(group_name.to_string()).or_default();
+        let services = group.services.get_or_insert_with(BTreeMap::new);
+        let svc = services
+            .entry(service_name.to_string())
+            .or_insert_with(Service::default);
+
+        svc.connection_type = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_connection_type(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let domains = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?;
+        let domain = domains
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+
+        let groups = domain
+            .groups
+            .as_mut()
+            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?;
+        let group = groups.get_mut(group_name).ok_or_else(|| {
+            anyhow!(
+                "group, {}, does not exist in domain {}",
+                group_name,
+                domain_name
+            )
+        })?;
+        let services = group.services.as_mut().ok_or_else(|| {
+            anyhow!(
+                "No services configured for group '{}' in domain {}",
+                group_name,
+                domain_name
+            )
+        })?;
+        let svc = services
+            .get_mut(service_name)
+            .ok_or_else(|| anyhow!("service, {}, does not exist", service_name))?;
+
+        if svc.connection_type.is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no custom connection_type.",
+                domain_name,
+                service_name
+            ));
+        }
+
+        svc.connection_type = None;
+        Ok(())
+    }
+
+    // Environment-level connection_type
+
+    pub fn set_environment_connection_type(&mut self, env_name: &str, value: &str) -> Result<()> {
+        validate_connection_type(value)?;
+        let env = self
+            .environments
+            .as_mut()
+            .and_then(|e| e.get_mut(env_name))
+            .ok_or_else(|| anyhow!("Environment '{}' does not exist.", env_name))?;
+
+        env.connection_type = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn rm_environment_connection_type(&mut self, env_name: &str) -> Result<()> {
+        let env = self
+            .environments
+            .as_mut()
+            .and_then(|e| e.get_mut(env_name))
+            .ok_or_else(|| anyhow!("Environment '{}' does not exist.", env_name))?;
+
+        if env.connection_type.is_none() {
+            return Err(anyhow!(
+                "Environment '{}' has no custom connection_type.",
+                env_name
+            ));
+        }
+
+        env.connection_type = None;
+        Ok(())
+    }
+
+    fn domain_mut(&mut self, domain_name: &str) -> Result<&mut Domain> {
+        self.domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))
+    }
+
+    fn environment_mut(&mut self, env_name: &str) -> Result<&mut Environment> {
+        self.environments
+            .as_mut()
+            .and_then(|e| e.get_mut(env_name))
+            .ok_or_else(|| anyhow!("Environment '{}' does not exist.", env_name))
+    }
+
+    /// Look up a service for a setter, creating its group and service entries on demand.
+    fn service_entry_mut(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<&mut Service> {
+        let domain = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+        Ok(domain
+            .groups
+            .get_or_insert_with(BTreeMap::new)
+            .entry(group_name.to_string())
+            .or_default()
+            .services
+            .get_or_insert_with(BTreeMap::new)
+            .entry(service_name.to_string())
+            .or_default())
+    }
+
+    /// Look up an existing service for a remover.
+    fn existing_service_mut(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<&mut Service> {
+        let domain = self
+            .domains
+            .as_mut()
+            .ok_or_else(|| anyhow!("No domains configured"))?
+            .get_mut(domain_name)
+            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+        let group = domain
+            .groups
+            .as_mut()
+            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?
+            .get_mut(group_name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "group, {}, does not exist in domain {}",
+                    group_name,
+                    domain_name
+                )
+            })?;
+        group
+            .services
+            .as_mut()
+            .ok_or_else(|| {
+                anyhow!(
+                    "No services configured for group '{}' in domain {}",
+                    group_name,
+                    domain_name
+                )
+            })?
+            .get_mut(service_name)
+            .ok_or_else(|| anyhow!("service, {}, does not exist", service_name))
+    }
+
+    // Domain-level network
+
+    pub fn set_domain_network(&mut self, domain_name: &str, network: &str) -> Result<()> {
+        self.domain_mut(domain_name)?.network = Some(network.to_string());
+        Ok(())
+    }
+
+    pub fn rm_domain_network(&mut self, domain_name: &str) -> Result<()> {
+        if self.domain_mut(domain_name)?.network.take().is_none() {
+            return Err(anyhow!("Domain '{}' has no custom network.", domain_name));
+        }
+        Ok(())
+    }
+
+    // Domain-level canaries
+
+    pub fn set_domain_canary(
+        &mut self,
+        domain_name: &str,
+        service_name: &str,
+        canary_service: &str,
+        percent: u8,
+    ) -> Result<()> {
+        if !(1..=99).contains(&percent) {
+            return Err(anyhow!("Canary percent must be between 1 and 99."));
+        }
+        if service_name == canary_service {
+            return Err(anyhow!(
+                "A service can't be its own canary; pick another service in '{}'.",
+                domain_name
+            ));
+        }
+        self.domain_mut(domain_name)?
+            .canaries
+            .get_or_insert_with(BTreeMap::new)
+            .insert(
+                service_name.to_string(),
+                Canary {
+                    service: canary_service.to_string(),
+                    percent,
+                },
+            );
+        Ok(())
+    }
+
+    pub fn rm_domain_canary(&mut self, domain_name: &str, service_name: &str) -> Result<()> {
+        let domain = self.domain_mut(domain_name)?;
+        if domain
+            .canaries
+            .as_mut()
+            .and_then(|c| c.remove(service_name))
+            .is_none()
+        {
+            return Err(anyhow!(
+                "Domain '{}' has no canary for '{}'.",
+                domain_name,
+                service_name
+            ));
+        }
+        if domain.canaries.as_ref().is_some_and(|c| c.is_empty()) {
+            domain.canaries = None;
+        }
+        Ok(())
+    }
+
+    pub fn add_extra_host(&mut self, host: &str, ip: &str) -> Result<()> {
+        validate_extra_host(host, ip)?;
+        self.extra_hosts
+            .get_or_insert_with(BTreeMap::new)
+            .insert(host.to_string(), ip.to_string());
+        Ok(())
+    }
+
+    pub fn rm_extra_host(&mut self, host: &str) -> Result<()> {
+        if self
+            .extra_hosts
+            .as_mut()
+            .and_then(|h| h.remove(host))
+            .is_none()
+        {
+            return Err(anyhow!("No extra host '{}' is configured.", host));
+        }
+        if self.extra_hosts.as_ref().is_some_and(|h| h.is_empty()) {
+            self.extra_hosts = None;
+        }
+        Ok(())
+    }
+
+    pub fn set_state_dir(&mut self, path: &str) -> Result<()> {
+        if path.trim().is_empty() {
+            return Err(anyhow!("The state directory can't be empty."));
+        }
+        self.state_dir = Some(path.to_string());
+        Ok(())
+    }
+
+    pub fn rm_state_dir(&mut self) -> Result<()> {
+        if self.state_dir.take().is_none() {
+            return Err(anyhow!("No state directory is configured."));
+        }
+        Ok(())
+    }
+
+    pub fn set_container_name(&mut self, template: &str) -> Result<()> {
+        validate_container_name(template)?;
+        self.container_name = Some(template.to_string());
+        Ok(())
+    }
+
+    pub fn rm_container_name(&mut self) -> Result<()> {
+        if self.container_name.take().is_none() {
+            return Err(anyhow!("No container name template is configured."));
+        }
+        Ok(())
+    }
+
+    pub fn set_engine_concurrency(&mut self, limit: u32) -> Result<()> {
+        self.engine_concurrency = Some(limit);
+        Ok(())
+    }
+
+    pub fn rm_engine_concurrency(&mut self) -> Result<()> {
+        if self.engine_concurrency.take().is_none() {
+            return Err(anyhow!("No engine concurrency limit is configured."));
+        }
+        Ok(())
+    }
+
+    pub fn set_shared_host(
+        &mut self,
+        user: &str,
+        port_base: u16,
+        proxy_dir: Option<&str>,
+    ) -> Result<()> {
+        validate_shared_user(user)?;
+        if !(1024..=u16::MAX - SHARED_PORT_RANGE).contains(&port_base) {
+            return Err(anyhow!(
+                "The port base must be between 1024 and {}, got {}.",
+                u16::MAX - SHARED_PORT_RANGE,
+                port_base
+            ));
+        }
+        if let Some(dir) = proxy_dir {
+            if !Path::new(dir).is_absolute() {
+                return Err(anyhow!(
+                    "The proxy directory must be an absolute path, got '{}'.",
+                    dir
+                ));
+            }
+        }
+        self.shared_host = Some(SharedHost {
+            user: user.to_string(),
+            port_base,
+            proxy_dir: proxy_dir.map(String::from),
+        });
+        Ok(())
+    }
+
+    pub fn rm_shared_host(&mut self) -> Result<()> {
+        if self.shared_host.take().is_none() {
+            return Err(anyhow!("No shared host is configured."));
+        }
+        Ok(())
+    }
+
+    /// The shared host user hostnames are namespaced under, if any.
+    pub fn user(&self) -> Option<&str> {
+        self.shared_host.as_ref().map(|s| s.user.as_str())
+    }
+
+    pub fn service_host(&self, service: &str, domain: &str) -> String {
+        service_host(service, domain, self.user())
+    }
+
+    /// Host that direct URLs point at: the remote server's address, else `localhost`.
+    pub fn direct_host(&self) -> String {
+        self.remote
+            .as_ref()
+            .map_or_else(|| "localhost".to_string(), Remote::resolved_address)
+    }
+
+    /// First proxy port `darp deploy` hands out: the shared host user's base, or
+    /// `PROXY_PORT_BASE`.
+    pub fn first_proxy_port(&self) -> u16 {
+        self.shared_host
+            .as_ref()
+            .map_or(PROXY_PORT_BASE, |s| s.port_base)
+    }
+
+    /// First debug port: `debug_port_base` if set, else the upper part of the shared host
+    /// user's range, else `DEBUG_PORT_BASE`.
+    pub fn first_debug_port(&self) -> u16 {
+        self.debug_port_base.unwrap_or_else(|| {
+            self.shared_host
+                .as_ref()
+                .map_or(DEBUG_PORT_BASE, |s| s.port_base + SHARED_DEBUG_PORT_OFFSET)
+        })
+    }
+
+    pub fn add_domain_extra_host(&mut self, domain_name: &str, host: &str, ip: &str) -> Result<()> {
+        validate_extra_host(host, ip)?;
+        self.domain_mut(domain_name)?
+            .extra_hosts
+            .get_or_insert_with(BTreeMap::new)
+            .insert(host.to_string(), ip.to_string());
+        Ok(())
+    }
+
+    pub fn rm_domain_extra_host(&mut self, domain_name: &str, host: &str) -> Result<()> {
+        let domain = self.domain_mut(domain_name)?;
+        if domain
+            .extra_hosts
+            .as_mut()
+            .and_then(|h| h.remove(host))
+            .is_none()
+        {
+            return Err(anyhow!(
+                "Domain '{}' has no extra host '{}'.",
+                domain_name,
+                host
+            ));
+        }
+        if domain.extra_hosts.as_ref().is_some_and(|h| h.is_empty()) {
+            domain.extra_hosts = None;
+        }
+        Ok(())
+    }
+
+    /// `ip   host` lines for the global and per-domain `extra_hosts`, in the layout of the
+    /// service URL lines they're written after.
+    pub fn extra_host_lines(&self) -> Vec<String> {
+        self.extra_hosts
+            .iter()
+            .flatten()
+            .chain(
+                self.domains
+                    .iter()
+                    .flatten()
+                    .flat_map(|(_, d)| d.extra_hosts.iter().flatten()),
+            )
+            .map(|(host, ip)| format!("{}   {}\n", ip, host))
+            .collect()
+    }
+
+    // Domain-level pod
+
+    pub fn set_domain_pod(&mut self, domain_name: &str, pod: bool) -> Result<()> {
+        self.domain_mut(domain_name)?.pod = Some(pod);
+        Ok(())
+    }
+
+    pub fn rm_domain_pod(&mut self, domain_name: &str) -> Result<()> {
+        if self.domain_mut(domain_name)?.pod.take().is_none() {
+            return Err(anyhow!("Domain '{}' has no pod setting.", domain_name));
+        }
+        Ok(())
+    }
+
+    // Engine overrides (domain + environment)
+
+    pub fn set_domain_engine(&mut self, domain_name: &str, engine: &str) -> Result<()> {
+        self.domain_mut(domain_name)?.engine = Some(validate_engine(engine)?);
+        Ok(())
+    }
+
+    pub fn rm_domain_engine(&mut self, domain_name: &str) -> Result<()> {
+        if self.domain_mut(domain_name)?.engine.take().is_none() {
+            return Err(anyhow!(
+                "Domain '{}' has no engine of its own.",
+                domain_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_environment_engine(&mut self, env_name: &str, engine: &str) -> Result<()> {
+        self.environment_mut(env_name)?.engine = Some(validate_engine(engine)?);
+        Ok(())
+    }
+
+    pub fn rm_environment_engine(&mut self, env_name: &str) -> Result<()> {
+        if self.environment_mut(env_name)?.engine.take().is_none() {
+            return Err(anyhow!(
+                "Environment '{}' has no engine of its own.",
+                env_name
+            ));
+        }
+        Ok(())
+    }
+
+    /// The engine override of a service under its default environment (the one `darp
+    /// deploy` routes it by): see `engine_override`.
+    pub fn default_engine_override<'a>(
+        &'a self,
+        domain: &'a Domain,
+        group_name: &str,
+        service_name: &str,
+    ) -> Option<&'a str> {
+        let group = domain.groups.as_ref().and_then(|g| g.get(group_name));
+        let environment = group
+            .and_then(|g| g.services.as_ref())
+            .and_then(|s| s.get(service_name))
+            .and_then(|s| s.default_environment.as_ref())
+            .or_else(|| group.and_then(|g| g.default_environment.as_ref()))
+            .or(domain.default_environment.as_ref())
+            .and_then(|name| self.environments.as_ref()?.get(name));
+        engine_override(domain, environment)
+    }
+
+    // Service-level env_file
+
+    pub fn set_service_env_file(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        env_file: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.env_file = Some(env_file.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_env_file(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.env_file.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no env_file.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Service-level healthcheck
+
+    pub fn set_service_healthcheck(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        healthcheck: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.healthcheck = Some(healthcheck.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_healthcheck(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.healthcheck.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no healthcheck.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Service-level description
+
+    pub fn set_service_description(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        description: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.description = Some(description.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_description(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.description.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no description.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Service-level start order
+
+    pub fn set_service_start_order(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        start_order: i32,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.start_order = Some(start_order);
+        Ok(())
+    }
+
+    pub fn rm_service_start_order(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.start_order.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no start_order.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Service-level live reload
+
+    pub fn set_service_live_reload(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        live_reload: bool,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.live_reload = Some(live_reload);
+        Ok(())
+    }
+
+    pub fn rm_service_live_reload(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.live_reload.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no live_reload setting.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Service-level request mirroring
+
+    pub fn set_service_mirror(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        target: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.mirror = Some(target.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_mirror(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.mirror.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no mirror.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_protocol(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        value: &str,
+    ) -> Result<()> {
+        validate_protocol(value)?;
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.protocol = Some(value.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_protocol(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.protocol.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no protocol.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_host_port(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        port: u16,
+    ) -> Result<()> {
+        if [0, 53, 80, engine::TLS_PROXY_PORT, engine::GRPC_PROXY_PORT].contains(&port) {
+            return Err(anyhow!(
+                "Port {} is taken by darp's proxy or DNS; run the process on another port.",
+                port
+            ));
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.host_port = Some(port);
+        Ok(())
+    }
+
+    pub fn rm_service_host_port(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.host_port.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no host port.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_stream(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        port: u16,
+        udp: bool,
+    ) -> Result<()> {
+        if [0, 53, 80, engine::TLS_PROXY_PORT, engine::GRPC_PROXY_PORT].contains(&port) {
+            return Err(anyhow!(
+                "Port {} is taken by darp's proxy or DNS; pick another stream port.",
+                port
+            ));
+        }
+        let taken = self
+            .stream_ports()
+            .into_iter()
+            .find(|(owner, p, u)| *p == port && *u == udp && *owner != (domain_name, service_name));
+        if let Some(((owner_domain, owner_service), _, _)) = taken {
+            return Err(anyhow!(
+                "Port {} is already the stream port of '{}.{}'.",
+                port,
+                owner_domain,
+                owner_service
+            ));
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.stream = Some(Stream {
+            port,
+            udp: udp.then_some(true),
+        });
+        Ok(())
+    }
+
+    pub fn rm_service_stream(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.stream.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no stream port.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_replicas(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        count: u32,
+    ) -> Result<()> {
+        if !(1..=MAX_REPLICAS).contains(&count) {
+            return Err(anyhow!(
+                "Replicas must be between 1 and {}, got {}.",
+                MAX_REPLICAS,
+                count
+            ));
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.replicas = Some(count);
+        Ok(())
+    }
+
+    pub fn rm_service_replicas(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.replicas.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no replicas.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    /// `((domain, service), port, udp)` for every service with a stream port.
+    pub fn stream_ports(&self) -> Vec<((&str, &str), u16, bool)> {
+        self.domains
+            .iter()
+            .flatten()
+            .flat_map(|(domain_name, d)| {
+                d.groups
+                    .iter()
+                    .flatten()
+                    .flat_map(|(_, g)| g.services.iter().flatten())
+                    .filter_map(move |(service_name, s)| {
+                        let stream = s.stream.as_ref()?;
+                        Some((
+                            (domain_name.as_str(), service_name.as_str()),
+                            stream.port,
+                            stream.udp == Some(true),
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    /// Whether any service proxies gRPC, so the reverse proxy needs its HTTP/2 listener.
+    pub fn uses_grpc(&self) -> bool {
+        self.domains
+            .iter()
+            .flatten()
+            .flat_map(|(_, d)| d.groups.iter().flatten())
+            .flat_map(|(_, g)| g.services.iter().flatten())
+            .any(|(_, s)| s.protocol.as_deref() == Some("grpc"))
+    }
+
+    /// `(domain, location)` of every domain with a `static` service (its own, a group's or
+    /// a service's `connection_type`), whose folder the reverse proxy mounts to serve it.
+    pub fn static_domain_locations(&self) -> Result<Vec<(String, PathBuf)>> {
+        let is_static = |t: &Option<String>| t.as_deref() == Some("static");
+        self.domains
+            .iter()
+            .flatten()
+            .filter(|(_, d)| {
+                is_static(&d.connection_type)
+                    || d.groups.iter().flatten().any(|(_, g)| {
+                        is_static(&g.connection_type)
+                            || g.services
+                                .iter()
+                                .flatten()
+                                .any(|(_, s)| is_static(&s.connection_type))
+                    })
+            })
+            .map(|(domain_name, d)| Ok((domain_name.clone(), resolve_location(&d.location)?)))
+            .collect()
+    }
+
+    // Service-level tags
+
+    pub fn add_service_tag(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        tag: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        add_list_item(&mut svc.tags, tag)
+    }
+
+    pub fn rm_service_tag(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        tag: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        rm_list_item(&mut svc.tags, tag).ok_or_else(|| {
+            anyhow!(
+                "Service '{}.{}' has no tag '{}'.",
+                domain_name,
+                service_name,
+                tag
+            )
+        })
+    }
+
+    // Service-level hostname aliases
+    pub fn add_service_alias(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        hostname: &str,
+    ) -> Result<()> {
+        validate_hostname(hostname)?;
+        if hostname == self.service_host(service_name, domain_name) {
+            return Err(anyhow!(
+                "'{}' is already the service's own hostname.",
+                hostname
+            ));
+        }
+        if let Some((other_domain, other_service)) = self.service_answering_on(hostname) {
+            if (other_domain, other_service) != (domain_name, service_name) {
+                return Err(anyhow!(
+                    "'{}' already routes to '{}.{}'.",
+                    hostname,
+                    other_domain,
+                    other_service
+                ));
+            }
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        add_list_item(&mut svc.aliases, hostname)
+    }
+
+    pub fn rm_service_alias(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        hostname: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        rm_list_item(&mut svc.aliases, hostname).ok_or_else(|| {
+            anyhow!(
+                "Service '{}.{}' has no alias '{}'.",
+                domain_name,
+                service_name,
+                hostname
+            )
+        })
+    }
+
+    // Service-level custom domains
+    pub fn add_service_custom_domain(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        hostname: &str,
+        cert: Option<&str>,
+        key: Option<&str>,
+    ) -> Result<()> {
+        validate_hostname(hostname)?;
+        if hostname.ends_with(".test") {
+            return Err(anyhow!(
+                "'{}' is a .test name; add it with 'darp config add svc alias' instead.",
+                hostname
+            ));
+        }
+        if cert.is_some() != key.is_some() {
+            return Err(anyhow!(
+                "Pass both --cert and --key to serve '{}' over TLS.",
+                hostname
+            ));
+        }
+        if let Some((other_domain, other_service)) = self.service_answering_on(hostname) {
+            if (other_domain, other_service) != (domain_name, service_name) {
+                return Err(anyhow!(
+                    "'{}' already routes to '{}.{}'.",
+                    hostname,
+                    other_domain,
+                    other_service
+                ));
+            }
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.custom_domains.get_or_insert_with(BTreeMap::new).insert(
+            hostname.to_string(),
+            CustomDomain {
+                cert: cert.map(String::from),
+                key: key.map(String::from),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn rm_service_custom_domain(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        hostname: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        let removed = svc
+            .custom_domains
+            .as_mut()
+            .and_then(|d| d.remove(hostname))
+            .is_some();
+        if !removed {
+            return Err(anyhow!(
+                "Service '{}.{}' has no custom domain '{}'.",
+                domain_name,
+                service_name,
+                hostname
+            ));
+        }
+        if svc.custom_domains.as_ref().is_some_and(|d| d.is_empty()) {
+            svc.custom_domains = None;
+        }
+        Ok(())
+    }
+
+    /// `((domain, service), hostname, tls)` for every custom domain.
+    pub fn custom_domains(&self) -> Vec<((&str, &str), &str, &CustomDomain)> {
+        self.domains
+            .iter()
+            .flatten()
+            .flat_map(|(domain_name, d)| {
+                d.groups
+                    .iter()
+                    .flatten()
+                    .flat_map(|(_, g)| g.services.iter().flatten())
+                    .flat_map(move |(service_name, s)| {
+                        s.custom_domains.iter().flatten().map(move |(host, tls)| {
+                            (
+                                (domain_name.as_str(), service_name.as_str()),
+                                host.as_str(),
+                                tls,
+                            )
+                        })
+                    })
+            })
+            .collect()
+    }
+
+    /// `(hostname, cert, key)` for every custom domain served over TLS, paths resolved.
+    pub fn tls_certs(&self) -> Result<Vec<(String, PathBuf, PathBuf)>> {
+        self.custom_domains()
+            .into_iter()
+            .filter_map(|(_, host, tls)| Some((host, tls.cert.as_ref()?, tls.key.as_ref()?)))
+            .map(|(host, cert, key)| {
+                Ok((
+                    host.to_string(),
+                    resolve_location(cert)?,
+                    resolve_location(key)?,
+                ))
+            })
+            .collect()
+    }
+
+    /// `(domain, service)` of the service answering on `hostname` through an alias or a
+    /// custom domain.
+    fn service_answering_on(&self, hostname: &str) -> Option<(&str, &str)> {
+        self.domains
+            .iter()
+            .flatten()
+            .find_map(|(domain_name, domain)| {
+                domain
+                    .groups
+                    .iter()
+                    .flatten()
+                    .flat_map(|(_, g)| g.services.iter().flatten())
+                    .find(|(_, s)| {
+                        s.aliases.iter().flatten().any(|a| a == hostname)
+                            || s.custom_domains
+                                .as_ref()
+                                .is_some_and(|d| d.contains_key(hostname))
+                    })
+                    .map(|(service_name, _)| (domain_name.as_str(), service_name.as_str()))
+            })
+    }
+
+    /// The portmap plus a `null` entry for every service declared in config that `darp
+    /// deploy` hasn't given a port yet, so listings can show it as unassigned.
+    pub fn with_unassigned_services(&self, portmap: &serde_json::Value) -> serde_json::Value {
+        let mut out = portmap.clone();
+        for (domain_name, domain) in self.domains.iter().flatten() {
+            for (group_name, group) in domain.groups.iter().flatten() {
+                for service_name in group.services.iter().flatten().map(|(name, _)| name) {
+                    if portmap
+                        .get(domain_name)
+                        .and_then(|d| d.get(group_name))
+                        .and_then(|g| g.get(service_name))
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    let Some(root) = out.as_object_mut() else {
+                        return out;
+                    };
+                    let groups = root
+                        .entry(domain_name.clone())
+                        .or_insert_with(|| serde_json::json!({}));
+                    let services = groups.as_object_mut().map(|g| {
+                        g.entry(group_name.clone())
+                            .or_insert_with(|| serde_json::json!({}))
+                    });
+                    if let Some(services) = services.and_then(|s| s.as_object_mut()) {
+                        services.insert(service_name.clone(), serde_json::Value::Null);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// The portmap limited to services carrying at least one of `tags` (all of it when
+    /// `tags` is empty). Groups and domains left empty are dropped.
+    pub fn filter_portmap_by_tags(
+        &self,
+        portmap: &serde_json::Value,
+        tags: &[String],
+    ) -> serde_json::Value {
+        if tags.is_empty() {
+            return portmap.clone();
+        }
+        let tagged = |domain: &str, group: &str, service: &str| {
+            self.domains
+                .as_ref()
+                .and_then(|d| d.get(domain))
+                .and_then(|d| d.groups.as_ref())
+                .and_then(|g| g.get(group))
+                .and_then(|g| g.services.as_ref())
+                .and_then(|s| s.get(service))
+                .and_then(|s| s.tags.as_ref())
+                .is_some_and(|t| t.iter().any(|tag| tags.contains(tag)))
+        };
+
+        let mut out = serde_json::Map::new();
+        for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+            let mut domain_map = serde_json::Map::new();
+            for (group_name, services) in groups.as_object().into_iter().flatten() {
+                let group_map: serde_json::Map<String, serde_json::Value> = services
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(service_name, _)| tagged(domain_name, group_name, service_name))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                if !group_map.is_empty() {
+                    domain_map.insert(group_name.clone(), group_map.into());
+                }
+            }
+            if !domain_map.is_empty() {
+                out.insert(domain_name.clone(), domain_map.into());
+            }
+        }
+        out.into()
+    }
+
+    // Serve hooks (service)
+
+    pub fn add_service_pre_serve(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        command: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        add_list_item(&mut svc.pre_serve, command)
+    }
+
+    pub fn rm_service_pre_serve(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        command: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        rm_list_item(&mut svc.pre_serve, command).ok_or_else(|| {
+            anyhow!(
+                "Service '{}.{}' has no pre_serve command '{}'.",
+                domain_name,
+                service_name,
+                command
+            )
+        })
+    }
+
+    pub fn add_service_run_arg(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        arg: &str,
+    ) -> Result<()> {
+        validate_run_arg(arg)?;
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        add_list_item(&mut svc.run_args, arg)
+    }
+
+    pub fn rm_service_run_arg(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        arg: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        rm_list_item(&mut svc.run_args, arg).ok_or_else(|| {
+            anyhow!(
+                "Service '{}.{}' has no run arg '{}'.",
+                domain_name,
+                service_name,
+                arg
+            )
+        })
+    }
+
+    pub fn add_service_post_serve(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        command: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        add_list_item(&mut svc.post_serve, command)
+    }
+
+    pub fn rm_service_post_serve(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        command: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        rm_list_item(&mut svc.post_serve, command).ok_or_else(|| {
+            anyhow!(
+                "Service '{}.{}' has no post_serve command '{}'.",
+                domain_name,
+                service_name,
+                command
+            )
+        })
+    }
+
+    // Restart policy (environment + service)
+
+    pub fn set_environment_restart(&mut self, env_name: &str, policy: &str) -> Result<()> {
+        validate_restart_policy(policy)?;
+        self.environment_mut(env_name)?.restart = Some(policy.to_string());
+        Ok(())
+    }
+
+    pub fn rm_environment_restart(&mut self, env_name: &str) -> Result<()> {
+        if self.environment_mut(env_name)?.restart.take().is_none() {
+            return Err(anyhow!(
+                "Environment '{}' has no custom restart policy.",
+                env_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_restart(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        policy: &str,
+    ) -> Result<()> {
+        validate_restart_policy(policy)?;
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.restart = Some(policy.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_restart(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.restart.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no custom restart policy.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_entrypoint(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        entrypoint: &str,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.entrypoint = Some(entrypoint.to_string());
+        Ok(())
+    }
+
+    pub fn rm_service_entrypoint(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.entrypoint.take().is_none() {
+            return Err(anyhow!(
+                "Service '{}.{}' has no entrypoint.",
+                domain_name,
+                service_name
+            ));
+        }
+        Ok(())
+    }
+
+    // Host user mapping (environment + service)
+
+    pub fn set_environment_map_user(&mut self, env_name: &str, map_user: bool) -> Result<()> {
+        self.environment_mut(env_name)?.map_user = Some(map_user);
+        Ok(())
+    }
+
+    pub fn rm_environment_map_user(&mut self, env_name: &str) -> Result<()> {
+        if self.environment_mut(env_name)?.map_user.take().is_none() {
+            return Err(anyhow!(
+                "Environment '{}' has no map_user setting.",
+                env_name
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_service_map_user(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        map_user: bool,
+    ) -> Result<()> {
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.map_user = Some(map_user);
+        Ok(())
+    }
+
+    pub fn rm_service_map_user(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.map_user.take().is_none() {
             return Err(anyhow!(
-                "Service '{}.{}' has no custom platform.",
+                "Service '{}.{}' has no map_user setting.",
                 domain_name,
                 service_name
             ));
         }
+        Ok(())
+    }
 
-        svc.platform = None;
+    // Read-only /app (environment + service)
+
+    pub fn set_environment_read_only_app(&mut self, env_name: &str, read_only: bool) -> Result<()> {
+        self.environment_mut(env_name)?.read_only_app = Some(read_only);
         Ok(())
     }
 
-    // Service-level default_container_image
+    pub fn rm_environment_read_only_app(&mut self, env_name: &str) -> Result<()> {
+        if self
+            .environment_mut(env_name)?
+            .read_only_app
+            .take()
+            .is_none()
+        {
+            return Err(anyhow!(
+                "Environment '{}' has no read_only_app setting.",
+                env_name
+            ));
+        }
+        Ok(())
+    }
 
-    pub fn set_service_default_container_image(
+    pub fn set_service_read_only_app(
         &mut self,
         domain_name: &str,
         group_name: &str,
         service_name: &str,
-        image: &str,
+        read_only: bool,
     ) -> Result<()> {
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-
-        let groups = domain.groups.get_or_insert_with(BTreeMap::new);
-        let group = groups.entry(group_name.to_string()).or_default();
-        let services = group.services.get_or_insert_with(BTreeMap::new);
-        let svc = services
-            .entry(service_name.to_string())
-            .or_insert_with(Service::default);
-
-        svc.default_container_image = Some(image.to_string());
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.read_only_app = Some(read_only);
         Ok(())
     }
 
-    pub fn rm_service_default_container_image(
+    pub fn rm_service_read_only_app(
         &mut self,
         domain_name: &str,
         group_name: &str,
         service_name: &str,
     ) -> Result<()> {
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-
-        let groups = domain
-            .groups
-            .as_mut()
-            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?;
-        let group = groups.get_mut(group_name).ok_or_else(|| {
-            anyhow!(
-                "group, {}, does not exist in domain {}",
-                group_name,
-                domain_name
-            )
-        })?;
-        let services = group.services.as_mut().ok_or_else(|| {
-            anyhow!(
-                "No services configured for group '{}' in domain {}",
-                group_name,
-                domain_name
-            )
-        })?;
-        let svc = services
-            .get_mut(service_name)
-            .ok_or_else(|| anyhow!("service, {}, does not exist", service_name))?;
-
-        if svc.default_container_image.is_none() {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.read_only_app.take().is_none() {
             return Err(anyhow!(
-                "Service '{}.{}' has no default_container_image.",
+                "Service '{}.{}' has no read_only_app setting.",
                 domain_name,
                 service_name
             ));
         }
-
-        svc.default_container_image = None;
         Ok(())
     }
 
-    // Domain-level connection_type
-
-    pub fn set_domain_connection_type(&mut self, domain_name: &str, value: &str) -> Result<()> {
-        validate_connection_type(value)?;
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+    // Shell image (environment + service)
 
-        domain.connection_type = Some(value.to_string());
+    pub fn set_environment_shell_image(&mut self, env_name: &str, image: &str) -> Result<()> {
+        self.environment_mut(env_name)?.shell_image = Some(image.to_string());
         Ok(())
     }
 
-    pub fn rm_domain_connection_type(&mut self, domain_name: &str) -> Result<()> {
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-
-        if domain.connection_type.is_none() {
-            return Err(anyhow!(
-                "Domain '{}' has no custom connection_type.",
-                domain_name
-            ));
+    pub fn rm_environment_shell_image(&mut self, env_name: &str) -> Result<()> {
+        if self.environment_mut(env_name)?.shell_image.take().is_none() {
+            return Err(anyhow!("Environment '{}' has no shell_image.", env_name));
         }
-
-        domain.connection_type = None;
         Ok(())
     }
 
-    // Group-level connection_type
-
-    pub fn set_group_connection_type(
+    pub fn set_service_shell_image(
         &mut self,
         domain_name: &str,
         group_name: &str,
-        value: &str,
+        service_name: &str,
+        image: &str,
     ) -> Result<()> {
-        validate_connection_type(value)?;
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-        let groups = domain.groups.get_or_insert_with(BTreeMap::new);
-        let group = groups.entry(group_name.to_string()).or_default();
-
-        group.connection_type = Some(value.to_string());
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.shell_image = Some(image.to_string());
         Ok(())
     }
 
-    pub fn rm_group_connection_type(&mut self, domain_name: &str, group_name: &str) -> Result<()> {
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-        let groups = domain
-            .groups
-            .as_mut()
-            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?;
-        let group = groups.get_mut(group_name).ok_or_else(|| {
-            anyhow!(
-                "group, {}, does not exist in domain {}",
-                group_name,
-                domain_name
-            )
-        })?;
-
-        if group.connection_type.is_none() {
+    pub fn rm_service_shell_image(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.shell_image.take().is_none() {
             return Err(anyhow!(
-                "Group '{}' in domain '{}' has no custom connection_type.",
-                group_name,
-                domain_name
+                "Service '{}.{}' has no shell_image.",
+                domain_name,
+                service_name
             ));
         }
-
-        group.connection_type = None;
         Ok(())
     }
 
-    // Service-level connection_type
+    // Image build recipe (service only)
 
-    pub fn set_service_connection_type(
+    pub fn set_service_image_build(
         &mut self,
         domain_name: &str,
         group_name: &str,
         service_name: &str,
-        value: &str,
+        build: ImageBuild,
     ) -> Result<()> {
-        validate_connection_type(value)?;
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-
-        let groups = domain.groups.get_or_insert_with(BTreeMap::new);
-        let group = groups.entry(group_name.to_string()).or_default();
-        let services = group.services.get_or_insert_with(BTreeMap::new);
-        let svc = services
-            .entry(service_name.to_string())
-            .or_insert_with(Service::default);
-
-        svc.connection_type = Some(value.to_string());
+        if build.base.trim().is_empty() {
+            return Err(anyhow!("image_build needs a base image."));
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.image_build = Some(build);
         Ok(())
     }
 
-    pub fn rm_service_connection_type(
+    pub fn rm_service_image_build(
         &mut self,
         domain_name: &str,
         group_name: &str,
         service_name: &str,
     ) -> Result<()> {
-        let domains = self
-            .domains
-            .as_mut()
-            .ok_or_else(|| anyhow!("No domains configured"))?;
-        let domain = domains
-            .get_mut(domain_name)
-            .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
-
-        let groups = domain
-            .groups
-            .as_mut()
-            .ok_or_else(|| anyhow!("No groups configured for domain {}", domain_name))?;
-        let group = groups.get_mut(group_name).ok_or_else(|| {
-            anyhow!(
-                "group, {}, does not exist in domain {}",
-                group_name,
-                domain_name
-            )
-        })?;
-        let services = group.services.as_mut().ok_or_else(|| {
-            anyhow!(
-                "No services configured for group '{}' in domain {}",
-                group_name,
-                domain_name
-            )
-        })?;
-        let svc = services
-            .get_mut(service_name)
-            .ok_or_else(|| anyhow!("service, {}, does not exist", service_name))?;
-
-        if svc.connection_type.is_none() {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.image_build.take().is_none() {
             return Err(anyhow!(
-                "Service '{}.{}' has no custom connection_type.",
+                "Service '{}.{}' has no image_build.",
                 domain_name,
                 service_name
             ));
         }
-
-        svc.connection_type = None;
         Ok(())
     }
 
-    // Environment-level connection_type
+    // Resource limits (environment + service)
 
-    pub fn set_environment_connection_type(&mut self, env_name: &str, value: &str) -> Result<()> {
-        validate_connection_type(value)?;
-        let env = self
-            .environments
-            .as_mut()
-            .and_then(|e| e.get_mut(env_name))
-            .ok_or_else(|| anyhow!("Environment '{}' does not exist.", env_name))?;
+    pub fn set_environment_limits(
+        &mut self,
+        env_name: &str,
+        limits: &ResourceLimits,
+    ) -> Result<()> {
+        if limits.is_empty() {
+            return Err(anyhow!("Pass at least one of --cpus or --memory."));
+        }
+        let env = self.environment_mut(env_name)?;
+        env.limits
+            .get_or_insert_with(ResourceLimits::default)
+            .overlay(limits);
+        Ok(())
+    }
 
-        env.connection_type = Some(value.to_string());
+    pub fn rm_environment_limits(&mut self, env_name: &str) -> Result<()> {
+        let env = self.environment_mut(env_name)?;
+        if env.limits.take().is_none() {
+            return Err(anyhow!("Environment '{}' has no custom limits.", env_name));
+        }
         Ok(())
     }
 
-    pub fn rm_environment_connection_type(&mut self, env_name: &str) -> Result<()> {
-        let env = self
-            .environments
-            .as_mut()
-            .and_then(|e| e.get_mut(env_name))
-            .ok_or_else(|| anyhow!("Environment '{}' does not exist.", env_name))?;
+    pub fn set_service_limits(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+        limits: &ResourceLimits,
+    ) -> Result<()> {
+        if limits.is_empty() {
+            return Err(anyhow!("Pass at least one of --cpus or --memory."));
+        }
+        let svc = self.service_entry_mut(domain_name, group_name, service_name)?;
+        svc.limits
+            .get_or_insert_with(ResourceLimits::default)
+            .overlay(limits);
+        Ok(())
+    }
 
-        if env.connection_type.is_none() {
+    pub fn rm_service_limits(
+        &mut self,
+        domain_name: &str,
+        group_name: &str,
+        service_name: &str,
+    ) -> Result<()> {
+        let svc = self.existing_service_mut(domain_name, group_name, service_name)?;
+        if svc.limits.take().is_none() {
             return Err(anyhow!(
-                "Environment '{}' has no custom connection_type.",
-                env_name
+                "Service '{}.{}' has no custom limits.",
+                domain_name,
+                service_name
             ));
         }
-
-        env.connection_type = None;
         Ok(())
     }
 }
 
 fn maybe_migrate(path: &Path) -> Result<()> {
     let data = fs::read(path)?;
-    let mut value: serde_json::Value = serde_json::from_slice(&data).unwrap_or_default();
+    // A file that doesn't parse is left alone; loading it reports the error.
+    let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(&data) else {
+        return Ok(());
+    };
     let mut changed = false;
 
     // Migration 1: path-keyed domains → name-keyed domains with location field
@@ -3640,8 +6437,8 @@ fn maybe_migrate(path: &Path) -> Result<()> {
 
     if changed {
         let data = serde_json::to_vec_pretty(&value)?;
-        fs::write(path, data)?;
-        eprintln!("Migrated config at {} to new format.", path.display());
+        write_atomic(path, &data)?;
+        tracing::info!("Migrated config at {} to new format.", path.display());
     }
 
     Ok(())
@@ -3801,11 +6598,13 @@ impl Config {
         if !leaf_path.exists() {
             return Config::load(leaf_path);
         }
+        let _timing = timings::phase("config load");
 
         maybe_migrate(leaf_path)?;
 
         let leaf_data = fs::read(leaf_path)?;
-        let leaf_val: serde_json::Value = serde_json::from_slice(&leaf_data).unwrap_or_default();
+        let leaf_val: serde_json::Value =
+            serde_json::from_slice(&leaf_data).map_err(|e| unreadable_config(leaf_path, e))?;
 
         // 2. Extract pre_config array from leaf
         let pre_configs = leaf_val
@@ -3827,8 +6626,8 @@ impl Config {
 
             let resolved = resolve_location(location)?;
             if !resolved.exists() {
-                eprintln!(
-                    "Warning: pre_config '{}' does not exist, skipping.",
+                tracing::warn!(
+                    "pre_config '{}' does not exist, skipping.",
                     resolved.display()
                 );
                 continue;
@@ -3837,7 +6636,13 @@ impl Config {
             maybe_migrate(&resolved)?;
 
             let data = fs::read(&resolved)?;
-            let val: serde_json::Value = serde_json::from_slice(&data).unwrap_or_default();
+            let val: serde_json::Value = serde_json::from_slice(&data).map_err(|e| {
+                anyhow!(
+                    "pre_config '{}' is not valid JSON: {}",
+                    resolved.display(),
+                    e
+                )
+            })?;
 
             // Check for domain conflicts between pre_configs
             if let Some(domains) = val.get("domains").and_then(|d| d.as_object()) {
@@ -3872,8 +6677,14 @@ impl Config {
             obj.remove("pre_config");
         }
 
-        let cfg: Config = serde_json::from_value(merged)?;
+        let mut cfg: Config =
+            serde_json::from_value(merged).map_err(|e| unreadable_config(leaf_path, e))?;
         Self::validate_no_double_declarations(&cfg)?;
+        // An engine aimed at a server through its own environment variable is remote too;
+        // a configured `remote` wins.
+        if cfg.remote.is_none() {
+            cfg.remote = Remote::from_engine_env(cfg.engine.as_deref());
+        }
         Ok(cfg)
     }
 }
@@ -4009,7 +6820,7 @@ mod tests {
     fn add_group_volume_creates_group() {
         let mut config = config_with_domain("d", "/tmp/d");
         config
-            .add_group_volume("d", "g", "/app", "/host/app")
+            .add_group_volume("d", "g", "/app", "/host/app", false)
             .unwrap();
 
         let group = &config.domains.as_ref().unwrap()["d"]
@@ -4077,7 +6888,7 @@ mod tests {
     fn add_service_volume_creates_group_and_service() {
         let mut config = config_with_domain("d", "/tmp/d");
         config
-            .add_service_volume("d", "g", "svc", "/app", "/host")
+            .add_service_volume("d", "g", "svc", "/app", "/host", false)
             .unwrap();
 
         let svc = &config.domains.as_ref().unwrap()["d"]
@@ -4179,11 +6990,13 @@ mod tests {
         .unwrap();
 
         // Create a leaf config with a pre_config pointing to the parent
-        let mut config = Config::default();
-        config.pre_config = Some(vec![PreConfig {
-            location: parent_path.to_string_lossy().into_owned(),
-            repo_location: None,
-        }]);
+        let mut config = Config {
+            pre_config: Some(vec![PreConfig {
+                location: parent_path.to_string_lossy().into_owned(),
+                repo_location: None,
+            }]),
+            ..Default::default()
+        };
 
         // Should find the domain from the pre_config without -l
         config.ensure_domain_exists("parent-dom", None).unwrap();
@@ -4215,11 +7028,13 @@ mod tests {
         )
         .unwrap();
 
-        let mut config = Config::default();
-        config.pre_config = Some(vec![PreConfig {
-            location: parent_path.to_string_lossy().into_owned(),
-            repo_location: None,
-        }]);
+        let mut config = Config {
+            pre_config: Some(vec![PreConfig {
+                location: parent_path.to_string_lossy().into_owned(),
+                repo_location: None,
+            }]),
+            ..Default::default()
+        };
 
         // Domain comes from pre_config, group and service auto-created
         config.ensure_domain_exists("parent-dom", None).unwrap();
@@ -4333,7 +7148,7 @@ mod tests {
         let mut config = Config::default();
         config.ensure_domain_exists("d", Some("/tmp/d")).unwrap();
         config
-            .add_service_volume("d", "g", "svc", "/app", "/host")
+            .add_service_volume("d", "g", "svc", "/app", "/host", false)
             .unwrap();
 
         let svc = &config.domains.as_ref().unwrap()["d"]
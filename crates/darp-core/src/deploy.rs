@@ -0,0 +1,1071 @@
+// deploy.rs
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use tracing::warn;
+
+use crate::config::{self, Config, CustomDomain, DarpPaths, Domain, Stream};
+use crate::engine::{self, Engine};
+use crate::livereload;
+use crate::os::{self, OsIntegration};
+use crate::scan::ScanManifest;
+
+/// Build the contents of `~/.darp/hosts_container` — loopback + host-gateway +
+/// one `0.0.0.0 <url>` line per configured service URL, then any `extra_hosts`.
+pub fn build_container_hosts(gateway_ip: &str, gateway_name: &str, url_lines: &[String]) -> String {
+    let mut out = String::new();
+    out.push_str("127.0.0.1\tlocalhost\n");
+    out.push_str("::1\tlocalhost ip6-localhost ip6-loopback\n");
+    out.push_str(&format!("{gateway_ip}\t{gateway_name}\n"));
+    out.push_str(&url_lines.join(""));
+    out
+}
+
+/// Collect every host port declared in a `host_portmappings` anywhere in the config
+/// (domain/group/service/environment). Debug-port assignment skips these so a debug
+/// listener never clashes with a port darp publishes via `-p`. Templated keys (e.g.
+/// `{debug_port}`) don't parse as numbers and are ignored.
+pub fn collect_host_portmap_ports(config: &Config) -> std::collections::HashSet<u16> {
+    let mut set = std::collections::HashSet::new();
+    let mut add = |pm: &Option<std::collections::BTreeMap<String, String>>| {
+        if let Some(pm) = pm {
+            for host in pm.keys() {
+                if let Ok(p) = host.parse::<u16>() {
+                    set.insert(p);
+                }
+            }
+        }
+    };
+    if let Some(domains) = &config.domains {
+        for domain in domains.values() {
+            add(&domain.host_portmappings);
+            if let Some(groups) = &domain.groups {
+                for group in groups.values() {
+                    add(&group.host_portmappings);
+                    if let Some(services) = &group.services {
+                        for svc in services.values() {
+                            add(&svc.host_portmappings);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if let Some(envs) = &config.environments {
+        for env in envs.values() {
+            add(&env.host_portmappings);
+        }
+    }
+    set
+}
+
+/// Collect every already-assigned `debug_port` from a previously-written portmap so
+/// re-deploys keep each service's port stable (assignment order from `read_dir` is
+/// otherwise unstable). Removed services free their port on the next deploy.
+pub fn collect_debug_ports(portmap: &serde_json::Value) -> std::collections::HashSet<u16> {
+    let mut set = std::collections::HashSet::new();
+    if let Some(domains) = portmap.as_object() {
+        for group_obj in domains.values() {
+            if let Some(groups) = group_obj.as_object() {
+                for svc_obj in groups.values() {
+                    if let Some(services) = svc_obj.as_object() {
+                        for entry in services.values() {
+                            if let Some(p) = entry.get("debug_port").and_then(|v| v.as_u64()) {
+                                set.insert(p as u16);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    set
+}
+
+/// Resolve connection_type by cascading service → group → domain. Environment-layer
+/// overrides are not applied at deploy time (deploy does not operate within an environment).
+/// Returns None if no layer sets it, in which case callers should treat as "http".
+pub fn resolve_deploy_connection_type(
+    domain: &Domain,
+    group_name: &str,
+    service_name: &str,
+) -> Option<String> {
+    let group = domain.groups.as_ref().and_then(|g| g.get(group_name));
+    let service = group
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name));
+
+    service
+        .and_then(|s| s.connection_type.clone())
+        .or_else(|| group.and_then(|g| g.connection_type.clone()))
+        .or_else(|| domain.connection_type.clone())
+}
+
+/// A service's portmap.json entry: proxy port, connection type and debug port.
+pub fn portmap_entry(port: u16, connection_type: &str, debug_port: u16) -> serde_json::Value {
+    serde_json::json!({
+        "port": port,
+        "type": connection_type,
+        "debug_port": debug_port,
+    })
+}
+
+/// `darp.test` vhost: serves `services.json` for the companion browser extension.
+/// CORS is open because the extension fetches it from its own origin.
+pub const DARP_VHOST: &str = r#"server {
+    listen 80;
+    server_name darp.test;
+    location = /services.json {
+        root /usr/share/nginx/darp;
+        default_type application/json;
+        add_header Cache-Control no-store;
+        add_header Access-Control-Allow-Origin *;
+    }
+}
+"#;
+
+/// HTTP / WebSocket vhost. The Upgrade + Connection headers are harmless for plain HTTP
+/// and allow WebSocket clients (ws://{svc}.{dom}.test) to reach the upstream. The
+/// $connection_upgrade variable is defined in assets/nginx.conf. The long timeouts keep
+/// idle sockets (dev-server HMR between edits) from being cut after nginx's default 60s.
+const HOST_PROXY_VHOST: &str = r#"server {
+    listen 80;
+    server_name {url};
+    location / {
+        proxy_pass http://{host_gateway}:{port}/;
+        proxy_set_header Host $host;
+        proxy_http_version 1.1;
+        proxy_set_header Upgrade $http_upgrade;
+        proxy_set_header Connection $connection_upgrade;
+        proxy_read_timeout 1h;
+        proxy_send_timeout 1h;
+    }
+}
+"#;
+
+/// Same vhost, but proxying straight to the service container over the darp network.
+/// The upstream goes through a variable so nginx resolves it per request: the proxy
+/// must start even while most service containers aren't running.
+const NETWORK_PROXY_VHOST: &str = r#"server {
+    listen 80;
+    server_name {url};
+    resolver {resolver} valid=10s;
+    location / {
+        set $darp_upstream http://{container}:{container_port};
+        proxy_pass $darp_upstream;
+        proxy_set_header Host $host;
+        proxy_http_version 1.1;
+        proxy_set_header Upgrade $http_upgrade;
+        proxy_set_header Connection $connection_upgrade;
+        proxy_read_timeout 1h;
+        proxy_send_timeout 1h;
+    }
+}
+"#;
+
+/// Routes the live reload script and websocket to `darp live-reload` on the host.
+const LIVE_RELOAD_LOCATION: &str = r#"    location /__darp/ {
+        proxy_pass http://{host_gateway}:{port};
+        proxy_set_header Host $host;
+        proxy_http_version 1.1;
+        proxy_set_header Upgrade $http_upgrade;
+        proxy_set_header Connection $connection_upgrade;
+        proxy_read_timeout 1h;
+    }
+"#;
+
+/// Added to `location /`: sub_filter can't rewrite compressed bodies, so the upstream
+/// is asked for identity encoding.
+const LIVE_RELOAD_INJECT: &str = r#"        proxy_set_header Accept-Encoding "";
+        sub_filter '</body>' '<script src="/__darp/livereload.js"></script></body>';
+        sub_filter_once on;
+"#;
+
+/// A service vhost with the live reload script injected into its HTML responses.
+pub fn live_reload_vhost(vhost: &str, host_gateway: &str) -> String {
+    let location = LIVE_RELOAD_LOCATION
+        .replace("{host_gateway}", host_gateway)
+        .replace("{port}", &livereload::LIVE_RELOAD_PORT.to_string());
+    vhost.replacen(
+        "    location / {\n",
+        &format!("{location}    location / {{\n{LIVE_RELOAD_INJECT}"),
+        1,
+    )
+}
+
+/// Sends a copy of each request back through the proxy under the mirror target's host.
+/// Mirror subrequests' responses are discarded by nginx.
+const MIRROR_LOCATION: &str = r#"    location = /__darp_mirror {
+        internal;
+        proxy_pass http://127.0.0.1:80$request_uri;
+        proxy_set_header Host {target_host};
+        proxy_set_header X-Darp-Mirror-Of $host;
+    }
+"#;
+
+/// A service vhost that mirrors every request to `target_host` ({svc}.{dom}.test).
+pub fn mirror_vhost(vhost: &str, target_host: &str) -> String {
+    let location = MIRROR_LOCATION.replace("{target_host}", target_host);
+    vhost.replacen(
+        "    location / {\n",
+        &format!(
+            "{location}    location / {{\n        mirror /__darp_mirror;\n        mirror_request_body on;\n"
+        ),
+        1,
+    )
+}
+
+/// The host a service's `mirror` setting points at: `service` is in the same domain,
+/// `domain.service` names another one.
+pub fn mirror_target_host(domain_name: &str, target: &str) -> String {
+    match target.split_once('.') {
+        Some((domain, service)) => format!("{}.{}.test", service, domain),
+        None => format!("{}.{}.test", target, domain_name),
+    }
+}
+
+/// The service's `mirror` target from config.
+pub fn service_mirror<'a>(
+    domain: &'a Domain,
+    group_name: &str,
+    service_name: &str,
+) -> Option<&'a str> {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.mirror.as_deref())
+}
+
+/// Whether the service asked for live reload in config.
+pub fn live_reload_enabled(domain: &Domain, group_name: &str, service_name: &str) -> bool {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.live_reload)
+        .unwrap_or(false)
+}
+
+/// The service's stream port from config.
+pub fn service_stream<'a>(
+    domain: &'a Domain,
+    group_name: &str,
+    service_name: &str,
+) -> Option<&'a Stream> {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.stream.as_ref())
+}
+
+/// The service's `protocol` from config ("http" when unset).
+pub fn service_protocol<'a>(domain: &'a Domain, group_name: &str, service_name: &str) -> &'a str {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.protocol.as_deref())
+        .unwrap_or("http")
+}
+
+/// The service's `replicas` from config (1 when unset).
+pub fn service_replicas(domain: &Domain, group_name: &str, service_name: &str) -> u32 {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.replicas)
+        .unwrap_or(1)
+}
+
+/// The service's `host_port` from config, set when a process on the host serves it.
+pub fn service_host_port(domain: &Domain, group_name: &str, service_name: &str) -> Option<u16> {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.host_port)
+}
+
+/// The service's hostname `aliases` from config.
+pub fn service_aliases<'a>(
+    domain: &'a Domain,
+    group_name: &str,
+    service_name: &str,
+) -> &'a [String] {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.aliases.as_deref())
+        .unwrap_or_default()
+}
+
+/// A service vhost that also answers on `aliases`, added to its `server_name`.
+pub fn aliased_vhost(vhost: &str, aliases: &[String]) -> String {
+    let Some(start) = vhost.find("    server_name ") else {
+        return vhost.to_string();
+    };
+    let Some(end) = vhost[start..].find(';').map(|i| start + i) else {
+        return vhost.to_string();
+    };
+    let mut out = vhost.to_string();
+    for alias in aliases.iter().rev() {
+        out.insert_str(end, &format!(" {}", alias));
+    }
+    out
+}
+
+/// The service's `custom_domains` from config.
+pub fn service_custom_domains<'a>(
+    domain: &'a Domain,
+    group_name: &str,
+    service_name: &str,
+) -> Option<&'a BTreeMap<String, CustomDomain>> {
+    domain
+        .groups
+        .as_ref()
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .and_then(|s| s.custom_domains.as_ref())
+}
+
+/// Takes the place of a vhost's `listen` and `server_name` lines to serve a custom
+/// domain with its certificate. nginx picks the certificate by SNI.
+const TLS_SERVER_NAME: &str = r#"    listen {port} ssl;
+    server_name {hostname};
+    ssl_certificate /etc/nginx/certs/{hostname}.crt;
+    ssl_certificate_key /etc/nginx/certs/{hostname}.key;
+"#;
+
+/// The server block of a service vhost, answering on `hostname` over TLS instead.
+/// Upstreams see `X-Forwarded-Proto: https`, so apps build https redirect URIs.
+pub fn tls_vhost(vhost: &str, hostname: &str) -> String {
+    let server = vhost.rfind("server {\n").map_or(vhost, |at| &vhost[at..]);
+    let mut out = String::new();
+    for line in server.lines() {
+        if line.starts_with("    listen ") {
+            continue;
+        }
+        if line.starts_with("    server_name ") {
+            out.push_str(
+                &TLS_SERVER_NAME
+                    .replace("{port}", &engine::TLS_PROXY_PORT.to_string())
+                    .replace("{hostname}", hostname),
+            );
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+        if line == "        proxy_set_header Host $host;" {
+            out.push_str("        proxy_set_header X-Forwarded-Proto https;\n");
+        }
+    }
+    out
+}
+
+/// A service vhost that also answers on its custom domains: over HTTP through its
+/// `server_name`, and over TLS in a server block of its own for each one with a
+/// certificate.
+pub fn custom_domain_vhost(vhost: &str, custom_domains: &BTreeMap<String, CustomDomain>) -> String {
+    let hostnames: Vec<String> = custom_domains.keys().cloned().collect();
+    let mut out = aliased_vhost(vhost, &hostnames);
+    for (hostname, tls) in custom_domains {
+        if tls.cert.is_some() && tls.key.is_some() {
+            out.push_str(&tls_vhost(vhost, hostname));
+        }
+    }
+    out
+}
+
+/// The URLs a portmap entry records for a service's custom domains.
+pub fn custom_domain_urls(custom_domains: &BTreeMap<String, CustomDomain>) -> Vec<String> {
+    custom_domains
+        .iter()
+        .map(|(hostname, tls)| {
+            let scheme = if tls.cert.is_some() { "https" } else { "http" };
+            format!("{}://{}", scheme, hostname)
+        })
+        .collect()
+}
+
+/// How many containers deploy gives `service_name` proxy ports for: its `replicas`, or 1
+/// (with a warning) when the proxy can't balance it.
+pub fn deploy_replicas(
+    engine: &Engine,
+    domain_name: &str,
+    domain: &Domain,
+    group_name: &str,
+    service_name: &str,
+    connection_type: &str,
+) -> u32 {
+    let replicas = service_replicas(domain, group_name, service_name);
+    if replicas <= 1 {
+        return 1;
+    }
+    let reason = if connection_type == "tcp" {
+        Some("tcp services")
+    } else if connection_type == "static" {
+        Some("static services")
+    } else if service_host_port(domain, group_name, service_name).is_some() {
+        Some("host-run services")
+    } else if service_protocol(domain, group_name, service_name) == "grpc" {
+        Some("grpc services")
+    } else if in_podman_pod(engine, domain) {
+        Some("services in a pod")
+    } else if domain
+        .canaries
+        .as_ref()
+        .is_some_and(|c| c.contains_key(service_name))
+    {
+        Some("canary-split services")
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => {
+            warn!(
+                "{}.{} runs one container; replicas don't apply to {}",
+                service_name, domain_name, reason
+            );
+            1
+        }
+        None => replicas,
+    }
+}
+
+/// Balances a service's replicas round-robin. A replica that refuses a connection is
+/// skipped for a few seconds, so stopping one doesn't break the URL. nginx resolves
+/// `upstream` servers when it loads its config, so replicas are always reached through
+/// the host gateway, even on the darp network.
+const REPLICAS_UPSTREAM: &str = r#"upstream {upstream} {
+{servers}}
+
+"#;
+
+/// The vhost balancing `{service}.{domain}.test` across replicas listening on `ports`.
+/// Responses carry the replica that served them in `X-Darp-Upstream`.
+pub fn replicas_vhost(
+    domain_name: &str,
+    service_name: &str,
+    host_gateway: &str,
+    ports: &[u16],
+) -> String {
+    let upstream: String = format!("darp_replicas_{}_{}", domain_name, service_name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let servers: String = ports
+        .iter()
+        .map(|port| {
+            format!(
+                "    server {}:{} max_fails=1 fail_timeout=5s;\n",
+                host_gateway, port
+            )
+        })
+        .collect();
+    let vhost = HOST_PROXY_VHOST
+        .replace("{url}", &format!("{}.{}.test", service_name, domain_name))
+        .replace("{host_gateway}:{port}", &upstream)
+        .replacen(
+            "        proxy_send_timeout 1h;\n",
+            "        proxy_send_timeout 1h;\n        add_header X-Darp-Upstream $upstream_addr always;\n",
+            1,
+        );
+    REPLICAS_UPSTREAM
+        .replace("{upstream}", &upstream)
+        .replace("{servers}", &servers)
+        + &vhost
+}
+
+/// Where a service answers on localhost without DNS, for `darp urls --direct`, and
+/// whether that's a listener of the proxy's own. `routed` services are reached by the
+/// proxy over the darp network and publish no port, so the proxy listens on theirs; it
+/// can't for canary-split services or on a shared host. Static services have no port.
+pub fn direct_port(
+    connection_type: &str,
+    host_port: Option<u16>,
+    port: u16,
+    routed: bool,
+    split: bool,
+    shared: bool,
+) -> Option<(u16, bool)> {
+    if connection_type == "static" {
+        return None;
+    }
+    match host_port {
+        Some(host_port) => Some((host_port, false)),
+        None if !routed => Some((port, false)),
+        None if split || shared => None,
+        None => Some((port, true)),
+    }
+}
+
+/// A copy of a service's vhost that listens on `port` and answers whatever the Host
+/// header, so the service is still reachable as `localhost:<port>` when `.test` names
+/// don't resolve. Takes the vhost before aliases and custom domains are added.
+pub fn direct_vhost(vhost: &str, port: u16) -> String {
+    vhost
+        .lines()
+        .filter(|line| !line.starts_with("    server_name "))
+        .map(|line| {
+            if line.starts_with("    listen ") {
+                format!("    listen {};\n", port)
+            } else {
+                format!("{}\n", line)
+            }
+        })
+        .collect()
+}
+
+/// A service vhost turned into a gRPC one: served on the proxy's HTTP/2 listener and
+/// forwarded with `grpc_pass`. The WebSocket headers have no gRPC equivalent and go.
+pub fn grpc_vhost(vhost: &str) -> String {
+    vhost
+        .lines()
+        .filter(|line| {
+            !line.contains("proxy_http_version")
+                && !line.contains("$connection_upgrade")
+                && !line.contains("Upgrade $http_upgrade")
+        })
+        .map(|line| {
+            let line = if line.trim() == "listen 80;" {
+                format!("    listen {};\n    http2 on;", engine::GRPC_PROXY_PORT)
+            } else if let Some(upstream) = line
+                .trim()
+                .strip_prefix("proxy_pass http://")
+                .and_then(|u| u.strip_suffix("/;"))
+            {
+                format!("        grpc_pass grpc://{};", upstream)
+            } else {
+                line.replace("set $darp_upstream http://", "set $darp_upstream grpc://")
+                    .replace("proxy_pass $darp_upstream", "grpc_pass $darp_upstream")
+                    .replace("proxy_set_header", "grpc_set_header")
+                    .replace("proxy_read_timeout", "grpc_read_timeout")
+                    .replace("proxy_send_timeout", "grpc_send_timeout")
+            };
+            format!("{}\n", line)
+        })
+        .collect()
+}
+
+/// One stream port's server. nginx can't route plain TCP/UDP by hostname, so each
+/// service gets a listener of its own. Over the darp network the upstream goes through a
+/// variable, resolved per connection, so the proxy starts while the service is down.
+const STREAM_SERVER: &str = r#"    server {
+        listen {port}{udp};
+{upstream}    }
+"#;
+
+/// A `server` for the proxy's `stream {}` block, forwarding `port` to `upstream`
+/// (`host:port`).
+pub fn stream_server(port: u16, udp: bool, upstream: &str, resolver: Option<&str>) -> String {
+    let upstream = match resolver {
+        Some(resolver) => format!(
+            "        resolver {} valid=10s;\n        set $darp_upstream {};\n        proxy_pass $darp_upstream;\n",
+            resolver, upstream
+        ),
+        None => format!("        proxy_pass {};\n", upstream),
+    };
+    STREAM_SERVER
+        .replace("{port}", &port.to_string())
+        .replace("{udp}", if udp { " udp" } else { "" })
+        .replace("{upstream}", &upstream)
+}
+
+/// Write the proxy's `stream {}` block: a server per configured stream port whose service
+/// is a `tcp` service folder in `portmap`.
+pub fn write_stream_conf(
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+    network_resolver: Option<&str>,
+    portmap: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let mut conf = String::from("stream {\n");
+    for ((domain_name, service_name), port, udp) in config.stream_ports() {
+        let Some(domain) = config.domains.as_ref().and_then(|d| d.get(domain_name)) else {
+            continue;
+        };
+        let Some((group_name, entry)) = portmap
+            .get(domain_name)
+            .and_then(|d| d.as_object())
+            .and_then(|groups| {
+                groups
+                    .iter()
+                    .find_map(|(group_name, g)| Some((group_name, g.get(service_name)?)))
+            })
+        else {
+            continue;
+        };
+        if entry.get("type").and_then(|t| t.as_str()) != Some("tcp") {
+            warn!(
+                "{}.{} has a stream port but isn't a tcp service; set its connection_type to tcp",
+                service_name, domain_name
+            );
+            continue;
+        }
+        // A host-run service is only reachable through the gateway.
+        let host_port = entry.get("host_port").and_then(|p| p.as_u64());
+        let network_resolver = service_network_resolver(
+            config,
+            engine,
+            network_resolver,
+            domain,
+            group_name,
+            service_name,
+        );
+        let resolver =
+            network_route(engine, network_resolver, domain).filter(|_| host_port.is_none());
+        let upstream = match resolver {
+            Some(_) => format!(
+                "{}:8002",
+                engine.service_container_name(domain_name, service_name)
+            ),
+            None => format!(
+                "{}:{}",
+                engine.host_gateway(),
+                host_port
+                    .or_else(|| entry.get("port").and_then(|p| p.as_u64()))
+                    .unwrap_or(0)
+            ),
+        };
+        conf.push_str(&stream_server(port, udp, &upstream, resolver));
+    }
+    conf.push_str("}\n");
+    std::fs::write(&paths.stream_container_conf, conf)?;
+    Ok(())
+}
+
+/// The resolver to reach a domain's containers by name over the darp network, or None
+/// when the proxy goes through the host gateway. Pods publish their ports on the host, so
+/// they keep the gateway route.
+pub fn network_route<'a>(
+    engine: &Engine,
+    network_resolver: Option<&'a str>,
+    domain: &Domain,
+) -> Option<&'a str> {
+    network_resolver.filter(|_| !in_podman_pod(engine, domain))
+}
+
+/// `network_resolver` for one service: None when its domain or default environment puts it
+/// on another engine than the proxy's, whose containers are off the darp network and are
+/// reached through the host gateway instead.
+pub fn service_network_resolver<'a>(
+    config: &Config,
+    engine: &Engine,
+    network_resolver: Option<&'a str>,
+    domain: &Domain,
+    group_name: &str,
+    service_name: &str,
+) -> Option<&'a str> {
+    let engine_override = config.default_engine_override(domain, group_name, service_name);
+    network_resolver
+        .filter(|_| engine::EngineKind::for_service(config, engine_override) == engine.kind)
+}
+
+/// Whether the domain's services run in a Podman pod.
+fn in_podman_pod(engine: &Engine, domain: &Domain) -> bool {
+    domain.pod == Some(true) && matches!(engine.kind, engine::EngineKind::Podman)
+}
+
+/// Splits one URL between two upstreams. `split_clients` hashes the request id, so each
+/// request lands on a side at random, in proportion. Upstreams are IPs or names the
+/// server's resolver knows, since `proxy_pass` takes a variable here.
+const CANARY_VHOST: &str = r#"split_clients "${request_id}" ${var} {
+    {percent}% {canary_upstream};
+    * {primary_upstream};
+}
+
+server {
+    listen 80;
+    server_name {url};
+{resolver}    location / {
+        proxy_pass ${var};
+        proxy_set_header Host $host;
+        proxy_http_version 1.1;
+        proxy_set_header Upgrade $http_upgrade;
+        proxy_set_header Connection $connection_upgrade;
+        proxy_read_timeout 1h;
+        proxy_send_timeout 1h;
+        add_header X-Darp-Upstream ${var} always;
+    }
+}
+"#;
+
+/// The vhost sending `percent` of `{service}.{domain}.test`'s requests to
+/// `canary_upstream` and the rest to `primary_upstream`.
+pub fn canary_vhost(
+    domain_name: &str,
+    service_name: &str,
+    primary_upstream: &str,
+    canary_upstream: &str,
+    percent: u8,
+    resolver: Option<&str>,
+) -> String {
+    let var: String = format!("darp_canary_{}_{}", domain_name, service_name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    CANARY_VHOST
+        .replace("{var}", &var)
+        .replace("{percent}", &percent.to_string())
+        .replace("{canary_upstream}", canary_upstream)
+        .replace("{primary_upstream}", primary_upstream)
+        .replace("{url}", &format!("{}.{}.test", service_name, domain_name))
+        .replace(
+            "{resolver}",
+            &resolver.map_or(String::new(), |r| {
+                format!("    resolver {} valid=10s;\n", r)
+            }),
+        )
+}
+
+/// Where the proxy reaches a service, for `canary_vhost`: its container over the darp
+/// network, or its proxy port (host port, for a host-run service) on the host gateway's IP.
+pub fn service_upstream(
+    engine: &Engine,
+    network_resolver: Option<&str>,
+    domain_name: &str,
+    domain: &Domain,
+    service_name: &str,
+    entry: &serde_json::Value,
+    gateway_ip: &str,
+) -> String {
+    if let Some(host_port) = entry.get("host_port").and_then(|p| p.as_u64()) {
+        return format!("http://{}:{}", gateway_ip, host_port);
+    }
+    let port = entry
+        .get("port")
+        .and_then(|p| p.as_u64())
+        .or_else(|| entry.as_u64())
+        .unwrap_or(0);
+    match network_route(engine, network_resolver, domain) {
+        Some(_) => {
+            let container_port = match entry.get("type").and_then(|t| t.as_str()) {
+                Some("websocket") => 8001,
+                _ => 8000,
+            };
+            format!(
+                "http://{}:{}",
+                engine.service_container_name(domain_name, service_name),
+                container_port
+            )
+        }
+        None => format!("http://{}:{}", gateway_ip, port),
+    }
+}
+
+/// The nginx server block for one service, or None for `tcp` services — nginx can't
+/// route plain TCP by hostname. Those are reached as {svc}.{dom}.test:{auto_port}, with
+/// the port resolving via the service container's -p {auto_port}:8002 mapping.
+pub fn service_vhost(
+    engine: &Engine,
+    network_resolver: Option<&str>,
+    domain_name: &str,
+    domain: &Domain,
+    service_name: &str,
+    connection_type: &str,
+    port: u16,
+) -> Option<String> {
+    if connection_type == "tcp" {
+        return None;
+    }
+
+    let url = format!("{}.{}.test", service_name, domain_name);
+    Some(match network_route(engine, network_resolver, domain) {
+        Some(resolver) => NETWORK_PROXY_VHOST
+            .replace("{url}", &url)
+            .replace("{resolver}", resolver)
+            .replace(
+                "{container}",
+                &engine.service_container_name(domain_name, service_name),
+            )
+            .replace(
+                "{container_port}",
+                if connection_type == "websocket" {
+                    "8001"
+                } else {
+                    "8000"
+                },
+            ),
+        None => HOST_PROXY_VHOST
+            .replace("{url}", &url)
+            .replace("{host_gateway}", engine.host_gateway())
+            .replace("{port}", &port.to_string()),
+    })
+}
+
+/// Serves a static service's folder straight from the proxy's read-only mount of its
+/// domain. Folders without an index.html get a file listing.
+const STATIC_VHOST: &str = r#"server {
+    listen 80;
+    server_name {url};
+    root {root};
+    index index.html index.htm;
+    autoindex on;
+    location / {
+        try_files $uri $uri/ =404;
+        add_header Cache-Control no-cache;
+    }
+}
+"#;
+
+/// The vhost for a `static` service, which runs no container.
+pub fn static_vhost(domain_name: &str, group_name: &str, service_name: &str) -> String {
+    let root = if group_name == "." {
+        format!("{}/{}/{}", engine::STATIC_ROOT, domain_name, service_name)
+    } else {
+        format!(
+            "{}/{}/{}/{}",
+            engine::STATIC_ROOT,
+            domain_name,
+            group_name,
+            service_name
+        )
+    };
+    STATIC_VHOST
+        .replace("{url}", &format!("{}.{}.test", service_name, domain_name))
+        .replace("{root}", &root)
+}
+
+/// `conf` answering on a shared host user's names: the first name of each `server_name`,
+/// and a mirror's `Host`, go from `{svc}.{dom}.test` to `{svc}.{dom}.{user}.test`.
+/// Aliases and custom domains are left alone. Replica upstreams and canary variables are
+/// global to nginx, so they get the user in their names too.
+pub fn user_vhosts(conf: &str, user: &str) -> String {
+    let prefix = user.replace('-', "_");
+    let namespaced = |name: &str| match name.strip_suffix(".test") {
+        Some(base) => format!("{}.{}.test", base, user),
+        None => name.to_string(),
+    };
+    conf.split_inclusive('\n')
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let indent = &line[..line.len() - trimmed.len()];
+            for directive in ["server_name ", "proxy_set_header Host "] {
+                if let Some(rest) = trimmed.strip_prefix(directive) {
+                    let end = rest.find([' ', ';']).unwrap_or(rest.len());
+                    return format!(
+                        "{}{}{}{}",
+                        indent,
+                        directive,
+                        namespaced(&rest[..end]),
+                        &rest[end..]
+                    );
+                }
+            }
+            line.to_string()
+        })
+        .collect::<String>()
+        .replace("darp_replicas_", &format!("darp_replicas_{}_", prefix))
+        .replace("darp_canary_", &format!("darp_canary_{}_", prefix))
+}
+
+/// On a shared host, put this user's vhosts into the shared proxy directory as
+/// `http.d/<user>.conf`, next to the nginx.conf every user's proxy mounts. The
+/// directories are world-writable (and sticky) so each user can add their own file.
+pub fn publish_shared_vhosts(paths: &DarpPaths, config: &Config) -> anyhow::Result<()> {
+    let Some(shared) = &config.shared_host else {
+        return Ok(());
+    };
+    let dir = shared.proxy_dir();
+    let http_dir = dir.join("http.d");
+    for d in [&dir, &http_dir] {
+        if !d.is_dir() {
+            std::fs::create_dir_all(d)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(d, std::fs::Permissions::from_mode(0o1777))?;
+            }
+        }
+    }
+    std::fs::write(
+        dir.join("nginx.conf"),
+        std::fs::read(&paths.nginx_conf_path)?,
+    )?;
+    let conf = std::fs::read_to_string(&paths.vhost_container_conf)?;
+    std::fs::write(
+        http_dir.join(format!("{}.conf", shared.user)),
+        user_vhosts(&conf, &shared.user),
+    )?;
+    Ok(())
+}
+
+/// The engine's host-gateway IP, from the cache `darp install` writes, probing (and
+/// re-caching) when it's missing or was written for another engine.
+pub fn container_gateway_ip(paths: &DarpPaths, engine: &Engine) -> anyhow::Result<String> {
+    if let Some(ip) = engine::read_container_host_ip(&paths.container_host_ip_path, &engine.kind) {
+        return Ok(ip);
+    }
+    let ip = engine.probe_host_gateway_ip()?;
+    engine::write_container_host_ip(&paths.container_host_ip_path, &engine.kind, &ip)?;
+    Ok(ip)
+}
+
+/// Mirror the `0.0.0.0 <url>` lines into /etc/hosts (and the Windows hosts file under
+/// WSL) when `urls_in_hosts` is enabled. Custom domains' lines are mirrored either way,
+/// since nothing else points those names at the proxy.
+pub fn sync_url_hosts(
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+    gateway_ip: &str,
+    hosts_container_lines: &[String],
+) -> anyhow::Result<()> {
+    let hosts_container_lines: Vec<String> = if config.urls_in_hosts.unwrap_or(false) {
+        hosts_container_lines.to_vec()
+    } else {
+        let custom_hosts: Vec<&str> = config
+            .custom_domains()
+            .into_iter()
+            .map(|(_, hostname, _)| hostname)
+            .collect();
+        hosts_container_lines
+            .iter()
+            .filter(|l| {
+                l.split_whitespace()
+                    .nth(1)
+                    .is_some_and(|host| custom_hosts.contains(&host))
+            })
+            .cloned()
+            .collect()
+    };
+    if hosts_container_lines.is_empty() {
+        // A removed custom domain would otherwise keep pointing the real site at the proxy.
+        let stale = std::fs::read_to_string("/etc/hosts").is_ok_and(|hosts| {
+            os::darp_block_hostnames(&hosts)
+                .iter()
+                .any(|host| !host.ends_with(".test"))
+        });
+        if !stale {
+            return Ok(());
+        }
+    }
+
+    let os = OsIntegration::new(paths, config, &engine.kind);
+    // Inside a sandbox the proxy's port 80 is on the engine's host, and with a remote
+    // it's on the server, not localhost.
+    let proxy_ip = match (&config.remote, os.sandbox) {
+        (Some(remote), _) => Some(remote.resolved_address()),
+        (None, Some(_)) => Some(gateway_ip.to_string()),
+        (None, None) => None,
+    };
+    let system_hosts_lines: Vec<String> = match proxy_ip {
+        Some(ip) => hosts_container_lines
+            .iter()
+            .map(|l| l.replacen("0.0.0.0", &ip, 1))
+            .collect(),
+        None => hosts_container_lines.to_vec(),
+    };
+    os.sync_system_hosts(&system_hosts_lines)?;
+
+    if config.wsl.unwrap_or(false) {
+        os.sync_windows_hosts(&hosts_container_lines)?;
+    }
+    Ok(())
+}
+
+/// A domain whose folder `darp deploy` can't scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationProblem {
+    pub domain: String,
+    pub location: PathBuf,
+    /// Why, e.g. `does not exist`.
+    pub reason: String,
+}
+
+/// Every domain whose location is missing, not a directory, or can't be listed, so
+/// `darp deploy` can report them together before changing anything.
+pub fn domain_location_problems(config: &Config) -> anyhow::Result<Vec<LocationProblem>> {
+    let mut problems = Vec::new();
+    for (domain_name, domain) in config.domains.iter().flatten() {
+        let location = config::resolve_location(&domain.location)?;
+        let reason = match std::fs::metadata(&location) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Some("does not exist".to_string())
+            }
+            Err(e) => Some(format!("is not readable ({})", e)),
+            Ok(meta) if !meta.is_dir() => Some("is not a directory".to_string()),
+            Ok(_) => std::fs::read_dir(&location)
+                .err()
+                .map(|e| format!("is not readable ({})", e)),
+        };
+        if let Some(reason) = reason {
+            problems.push(LocationProblem {
+                domain: domain_name.clone(),
+                location,
+                reason,
+            });
+        }
+    }
+    Ok(problems)
+}
+
+/// `(domain, group, service)` for every folder `darp deploy` would register, sorted.
+/// Domains whose location can't be scanned are left out, as `--skip-missing` does.
+/// Folders listed in `manifest` and unchanged since aren't read again.
+pub fn scanned_services(
+    config: &Config,
+    manifest: &mut ScanManifest,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    let problems = domain_location_problems(config)?;
+    let mut services = Vec::new();
+    for (domain_name, domain) in config.domains.iter().flatten() {
+        if problems.iter().any(|p| &p.domain == domain_name) {
+            continue;
+        }
+        let location = config::resolve_location(&domain.location)?;
+        let group_names: std::collections::HashSet<&String> = domain
+            .groups
+            .iter()
+            .flat_map(|g| g.keys())
+            .filter(|k| k.as_str() != ".")
+            .collect();
+        if domain.groups.as_ref().is_none_or(|g| g.contains_key(".")) {
+            for folder in manifest.folders(&location)? {
+                if !group_names.contains(&folder) {
+                    services.push((domain_name.clone(), ".".to_string(), folder));
+                }
+            }
+        }
+        for group_name in group_names {
+            for folder in manifest.folders(&location.join(group_name))? {
+                services.push((domain_name.clone(), group_name.clone(), folder));
+            }
+        }
+    }
+    services.sort();
+    Ok(services)
+}
+
+/// Whether service folders were added or removed since the last deploy (always true
+/// before the first), for `darp deploy --if-changed`.
+pub fn service_folders_changed(paths: &DarpPaths, config: &Config) -> anyhow::Result<bool> {
+    let Ok(portmap) = config::read_json::<serde_json::Value>(&paths.portmap_path) else {
+        return Ok(true);
+    };
+    let mut manifest = ScanManifest::load(&paths.scan_manifest_path);
+    let services = scanned_services(config, &mut manifest)?;
+    manifest.save(&paths.scan_manifest_path)?;
+    Ok(services != config::portmap_services(&portmap))
+}
@@ -0,0 +1,1856 @@
+// engine/mod.rs
+
+use crate::config::Config;
+use crate::config::DarpPaths;
+use crate::config::Remote;
+use crate::config::{Domain, Environment};
+use crate::logging::Logged;
+use crate::timings;
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use tracing::warn;
+
+mod slots;
+
+pub use slots::EngineSlot;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineKind {
+    Podman,
+    Docker,
+    None,
+}
+
+impl EngineKind {
+    pub fn from_config(config: &Config) -> Self {
+        match config.engine.as_deref().map(|s| s.to_lowercase()) {
+            Some(ref e) if e == "docker" => EngineKind::Docker,
+            Some(ref e) if e == "podman" => EngineKind::Podman,
+            _ => EngineKind::None,
+        }
+    }
+
+    /// The engine for a service with `engine_override` (see `config::engine_override`),
+    /// else the global one.
+    pub fn for_service(config: &Config, engine_override: Option<&str>) -> Self {
+        engine_override
+            .and_then(Self::parse)
+            .unwrap_or_else(|| Self::from_config(config))
+    }
+
+    pub fn bin(&self) -> Option<&'static str> {
+        match self {
+            EngineKind::Podman => Some("podman"),
+            EngineKind::Docker => Some("docker"),
+            EngineKind::None => None,
+        }
+    }
+
+    /// Parse an engine name as accepted by `darp config set engine`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "podman" => Some(EngineKind::Podman),
+            "docker" => Some(EngineKind::Docker),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineKind::Podman => "podman",
+            EngineKind::Docker => "docker",
+            EngineKind::None => "none",
+        }
+    }
+}
+
+/// Read the cached host-gateway IP. Returns `None` if the file is missing, malformed,
+/// or was written for a different engine (so the caller re-probes after an engine switch).
+pub fn read_container_host_ip(path: &std::path::Path, kind: &EngineKind) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let mut lines = content.lines();
+    let cached_kind = lines.next()?.trim();
+    let cached_ip = lines.next()?.trim();
+    if cached_kind == kind.as_str() && !cached_ip.is_empty() {
+        Some(cached_ip.to_string())
+    } else {
+        None
+    }
+}
+
+pub fn write_container_host_ip(path: &std::path::Path, kind: &EngineKind, ip: &str) -> Result<()> {
+    std::fs::write(path, format!("{}\n{}\n", kind.as_str(), ip))
+        .map_err(|e| anyhow!("failed to write {}: {}", path.display(), e))
+}
+
+pub fn is_on_path(bin: &str) -> bool {
+    Command::new("which")
+        .arg(bin)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// The uid and gid darp runs as, from `id`.
+#[cfg(unix)]
+pub fn host_user_ids() -> Result<(u32, u32)> {
+    let id = |flag: &str| -> Result<u32> {
+        let output = Command::new("id")
+            .arg(flag)
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("could not run id: {}", e))?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("could not read the host user's id ({})", flag))
+    };
+    Ok((id("-u")?, id("-g")?))
+}
+
+/// Windows has no uid and gid for `map_user` to run containers as.
+#[cfg(not(unix))]
+pub fn host_user_ids() -> Result<(u32, u32)> {
+    Err(anyhow!(
+        "map_user runs containers as the host user's uid and gid, which only Linux and macOS have; turn it off with 'darp config set env map-user <env> false'"
+    ))
+}
+
+/// A container engine binary found on PATH, and whether its daemon/machine answered.
+#[derive(Clone, Debug)]
+pub struct DetectedEngine {
+    pub kind: EngineKind,
+    pub reachable: bool,
+}
+
+/// Probe PATH for supported engines (podman first, then docker) and check which are
+/// actually reachable. Reachable engines sort ahead of merely-installed ones.
+pub fn detect_engines(config: &Config) -> Vec<DetectedEngine> {
+    let mut found: Vec<DetectedEngine> = [EngineKind::Podman, EngineKind::Docker]
+        .into_iter()
+        .filter(|kind| kind.bin().is_some_and(is_on_path))
+        .map(|kind| DetectedEngine {
+            reachable: Engine::new(kind.clone(), config)
+                .is_ok_and(|engine| engine.require_ready().is_ok()),
+            kind,
+        })
+        .collect();
+    found.sort_by_key(|d| !d.reachable);
+    found
+}
+
+pub struct Engine {
+    pub kind: EngineKind,
+    pub bin: Option<&'static str>,
+    pub podman_machine: Option<String>,
+    pub reverse_proxy_args: Vec<String>,
+    pub dnsmasq_args: Vec<String>,
+    /// `engine_args` from config, added to every `run`.
+    pub engine_args: Vec<String>,
+    /// Whether the reverse proxy and service containers join `DARP_NETWORK`.
+    pub darp_network: bool,
+    /// Server whose engine every command targets instead of the local one.
+    pub remote: Option<Remote>,
+    /// Whether the reverse proxy publishes `GRPC_PROXY_PORT` for gRPC services.
+    pub grpc_listener: bool,
+    /// `(port, udp)` the reverse proxy publishes for services' stream ports.
+    pub stream_ports: Vec<(u16, bool)>,
+    /// `(hostname, cert, key)` the reverse proxy serves on `TLS_PROXY_PORT`.
+    pub tls_certs: Vec<(String, PathBuf, PathBuf)>,
+    /// `(domain, location)` the reverse proxy mounts under `STATIC_ROOT` for static services.
+    pub static_mounts: Vec<(String, PathBuf)>,
+    /// Template service containers are named from; see `container_name`. A shared host
+    /// user's name is already filled in.
+    pub container_name: String,
+    /// The directory every user's vhosts are collected in, on a shared host.
+    pub shared_proxy_dir: Option<PathBuf>,
+    /// Engine operations that may run at once across darp processes; 0 for no limit.
+    /// See `acquire_slot`.
+    pub concurrency: usize,
+}
+
+pub const REVERSE_PROXY: &str = "darp-reverse-proxy";
+/// Container starts, stops, pulls and builds that may run at once when
+/// `engine_concurrency` isn't set.
+pub const DEFAULT_ENGINE_CONCURRENCY: usize = 4;
+pub const DNSMASQ: &str = "darp-masq";
+/// Bridge network shared by the reverse proxy and service containers, so the proxy can
+/// reach services by container name instead of through published host ports.
+pub const DARP_NETWORK: &str = "darp";
+/// Port of the reverse proxy's cleartext HTTP/2 listener, which fronts services whose
+/// `protocol` is `grpc`.
+pub const GRPC_PROXY_PORT: u16 = 50051;
+/// Port the reverse proxy serves custom domains' TLS certificates on.
+pub const TLS_PROXY_PORT: u16 = 443;
+/// Where domain folders with `static` services are mounted (read-only) in the reverse
+/// proxy, one directory per domain.
+pub const STATIC_ROOT: &str = "/srv/darp";
+/// Lowest port rootless Podman must bind: dnsmasq's 53, which covers the proxy's 80 too.
+pub const UNPRIVILEGED_PORT_START: u16 = 53;
+/// The kernel setting that lets unprivileged processes bind ports from its value up.
+const UNPRIVILEGED_PORT_SYSCTL: &str = "net.ipv4.ip_unprivileged_port_start";
+/// Drop-in that keeps `UNPRIVILEGED_PORT_SYSCTL` across reboots.
+pub const UNPRIVILEGED_PORT_DROP_IN: &str = "/etc/sysctl.d/99-darp-unprivileged-ports.conf";
+
+/// Label recording the run spec a helper container was created with, so a config
+/// change (or a darp upgrade that changes mounts) can be detected and the helper recreated.
+const HELPER_ARGS_LABEL: &str = "darp.run-args";
+
+/// Labels put on every container darp runs. Discovery goes through these rather than
+/// the `darp_<domain>_<service>` naming convention, so a renamed container is still found.
+pub const MANAGED_LABEL: &str = "darp.managed";
+pub const DOMAIN_LABEL: &str = "darp.domain";
+pub const SERVICE_LABEL: &str = "darp.service";
+/// Marks a `darp shell --persist` container with its service, in place of
+/// `SERVICE_LABEL`, so it isn't taken for the running service.
+pub const SHELL_LABEL: &str = "darp.shell";
+
+/// A container's healthcheck status (`starting`, `healthy`, `unhealthy`) and the output
+/// of its most recent probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthState {
+    pub status: String,
+    pub last_output: Option<String>,
+}
+
+/// Parse `inspect --format '{{json .State.Health}}'`; Docker and Podman share the shape.
+pub fn parse_health(json: &str) -> Option<HealthState> {
+    let value: serde_json::Value = serde_json::from_str(json.trim()).ok()?;
+    let status = value.get("Status")?.as_str()?.to_string();
+    let last_output = value
+        .get("Log")
+        .and_then(|l| l.as_array())
+        .and_then(|l| l.last())
+        .and_then(|entry| entry.get("Output"))
+        .and_then(|o| o.as_str())
+        .map(|o| o.trim().to_string())
+        .filter(|o| !o.is_empty());
+    Some(HealthState {
+        status,
+        last_output,
+    })
+}
+
+/// A running serve/shell container, as listed by `darp ps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceContainer {
+    pub name: String,
+    pub domain: String,
+    pub service: String,
+    /// The engine's status column, e.g. `Up 5 minutes`.
+    pub status: String,
+    /// Published `(host port, container port/proto)` pairs.
+    pub ports: Vec<(u16, String)>,
+}
+
+/// Parse `inspect --format '{{json .NetworkSettings.Ports}}'` into `(host, container)`
+/// pairs, sorted by host port. Unpublished ports (`null`) are skipped, and a port
+/// published on both IPv4 and IPv6 is listed once.
+pub fn parse_published_ports(json: &str) -> Vec<(u16, String)> {
+    let Ok(serde_json::Value::Object(ports)) = serde_json::from_str(json.trim()) else {
+        return Vec::new();
+    };
+    let mut out: Vec<(u16, String)> = ports
+        .iter()
+        .flat_map(|(container, bindings)| {
+            bindings
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|b| b.get("HostPort")?.as_str()?.parse().ok())
+                .map(move |host| (host, container.clone()))
+        })
+        .collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
+/// Name of the Podman pod shared by a domain's services when `pod` is enabled.
+pub fn pod_name(domain: &str) -> String {
+    sanitize_container_name(&format!("darp_{}", domain))
+}
+
+/// `line` of a service's output with the time (UTC, to the millisecond) and `label` in
+/// front, as `darp serve --prefix` prints it.
+pub fn prefixed_line(time: std::time::SystemTime, label: &str, line: &str) -> String {
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs() % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03} {} | {}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis(),
+        label,
+        line.trim_end_matches(['\r', '\n'])
+    )
+}
+
+/// Copy `from` line by line to stdout (or stderr), each line through `prefixed_line`.
+fn relay_prefixed(
+    from: impl std::io::Read + Send + 'static,
+    label: String,
+    to_stderr: bool,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        use std::io::{BufRead, Write};
+        let mut from = std::io::BufReader::new(from);
+        let mut line = Vec::new();
+        while from.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+            let out = prefixed_line(
+                std::time::SystemTime::now(),
+                &label,
+                &String::from_utf8_lossy(&line),
+            );
+            let _ = if to_stderr {
+                writeln!(std::io::stderr(), "{}", out)
+            } else {
+                writeln!(std::io::stdout(), "{}", out)
+            };
+            line.clear();
+        }
+    })
+}
+
+/// Container naming template used when `container_name` isn't set in config.
+pub const DEFAULT_CONTAINER_NAME: &str = "darp_{domain}_{service}";
+
+/// The default on a shared host, where several users' containers share one engine.
+pub const SHARED_CONTAINER_NAME: &str = "darp_{user}_{domain}_{service}";
+
+/// `name` made valid for Docker and Podman, which take `[a-zA-Z0-9][a-zA-Z0-9_.-]*`:
+/// other characters become `_`, and leading ones that can't start a name are dropped.
+pub fn sanitize_container_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.trim_start_matches(|c: char| !c.is_ascii_alphanumeric()) {
+        "" => "darp".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// A service's container name: `template` with `{domain}` and `{service}` filled in,
+/// sanitized, since both come from folder names that may hold anything.
+pub fn container_name(template: &str, domain: &str, service: &str) -> String {
+    sanitize_container_name(
+        &template
+            .replace("{domain}", domain)
+            .replace("{service}", service),
+    )
+}
+
+/// The long-lived container `darp shell --persist` keeps for a service.
+pub fn persistent_shell_name(container_name: &str) -> String {
+    format!("{}_shell", container_name)
+}
+
+/// `--label` arguments identifying a serve/shell container.
+pub fn service_labels(domain: &str, service: &str) -> Vec<String> {
+    vec![
+        "--label".into(),
+        format!("{}=true", MANAGED_LABEL),
+        "--label".into(),
+        format!("{}={}", DOMAIN_LABEL, domain),
+        "--label".into(),
+        format!("{}={}", SERVICE_LABEL, service),
+    ]
+}
+
+/// `cmd` as a shell command line, for `--dry-run` and `-v` logging.
+pub fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(shell_escape)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether an `ip_unprivileged_port_start` value (as read from `/proc`) already lets
+/// rootless containers bind `UNPRIVILEGED_PORT_START`.
+pub fn unprivileged_ports_allowed(current: &str) -> bool {
+    current
+        .trim()
+        .parse::<u16>()
+        .is_ok_and(|start| start <= UNPRIVILEGED_PORT_START)
+}
+
+/// The root shell script that lowers `ip_unprivileged_port_start` now and writes the
+/// sysctl drop-in that keeps it lowered after a reboot.
+pub fn unprivileged_ports_script() -> String {
+    format!(
+        "mkdir -p /etc/sysctl.d && echo '{key} = {start}' > {drop_in} && sysctl -w {key}={start}",
+        key = UNPRIVILEGED_PORT_SYSCTL,
+        start = UNPRIVILEGED_PORT_START,
+        drop_in = UNPRIVILEGED_PORT_DROP_IN,
+    )
+}
+
+/// Quote an argument for a POSIX shell, leaving plainly safe ones bare.
+pub fn shell_escape(s: &OsStr) -> String {
+    let s = s.to_string_lossy();
+
+    let safe = s.chars().all(|c| {
+        c.is_ascii_alphanumeric()
+            || matches!(c, '_' | '-' | '.' | '/' | ':' | '=' | ',' | '@' | '+')
+    });
+
+    if safe {
+        s.into_owned()
+    } else {
+        // Single-quote wrap; escape internal single quotes: ' -> '\''
+        let mut out = String::from("'");
+        for ch in s.chars() {
+            if ch == '\'' {
+                out.push_str("'\\''");
+            } else {
+                out.push(ch);
+            }
+        }
+        out.push('\'');
+        out
+    }
+}
+
+/// Helpers default to `--restart=always`; a user-supplied `--restart` replaces it.
+fn has_restart_policy(args: &[String]) -> bool {
+    args.iter()
+        .any(|a| a == "--restart" || a.starts_with("--restart="))
+}
+
+impl Engine {
+    /// The engine a service with `domain` and `environment` runs on: its own when either
+    /// sets one (see `config::engine_override`), else the global one.
+    pub fn for_service(
+        config: &Config,
+        domain: &Domain,
+        environment: Option<&Environment>,
+    ) -> Result<Self> {
+        let engine_override = crate::config::engine_override(domain, environment);
+        Self::new(EngineKind::for_service(config, engine_override), config)
+    }
+
+    /// The engines other than the global one that a domain or environment runs its
+    /// services on, each once, for commands that look across every service.
+    pub fn overrides(config: &Config) -> Result<Vec<Self>> {
+        let global = EngineKind::from_config(config);
+        let names = config
+            .domains
+            .iter()
+            .flat_map(|d| d.values().filter_map(|d| d.engine.as_deref()))
+            .chain(
+                config
+                    .environments
+                    .iter()
+                    .flat_map(|e| e.values().filter_map(|e| e.engine.as_deref())),
+            );
+        let mut kinds: Vec<EngineKind> = Vec::new();
+        for kind in names.filter_map(EngineKind::parse) {
+            if kind != global && !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        kinds.into_iter().map(|k| Self::new(k, config)).collect()
+    }
+
+    pub fn new(kind: EngineKind, config: &Config) -> Result<Self> {
+        let podman_machine = config.podman_machine.clone();
+        let helper_args = |h: &Option<crate::config::HelperContainer>| {
+            h.as_ref()
+                .and_then(|h| h.run_args.clone())
+                .unwrap_or_default()
+        };
+        // Only the global engine's containers can share a network with the reverse proxy.
+        let on_proxy_engine = kind == EngineKind::from_config(config);
+
+        Ok(Self {
+            bin: kind.bin(),
+            kind,
+            podman_machine,
+            reverse_proxy_args: helper_args(&config.reverse_proxy),
+            dnsmasq_args: helper_args(&config.dnsmasq),
+            engine_args: config.engine_args.clone().unwrap_or_default(),
+            darp_network: config.darp_network.unwrap_or(true) && on_proxy_engine,
+            remote: config.remote.clone(),
+            grpc_listener: config.uses_grpc(),
+            stream_ports: config
+                .stream_ports()
+                .into_iter()
+                .map(|(_, port, udp)| (port, udp))
+                .collect(),
+            tls_certs: config.tls_certs()?,
+            static_mounts: config.static_domain_locations()?,
+            container_name: config
+                .container_name
+                .as_deref()
+                .unwrap_or(if config.shared_host.is_some() {
+                    SHARED_CONTAINER_NAME
+                } else {
+                    DEFAULT_CONTAINER_NAME
+                })
+                .replace("{user}", config.user().unwrap_or_default()),
+            shared_proxy_dir: config.shared_host.as_ref().map(|s| s.proxy_dir()),
+            concurrency: config
+                .engine_concurrency
+                .map_or(DEFAULT_ENGINE_CONCURRENCY, |n| n as usize),
+        })
+    }
+
+    /// The container name of `service` in `domain`, from the configured template.
+    pub fn service_container_name(&self, domain: &str, service: &str) -> String {
+        container_name(&self.container_name, domain, service)
+    }
+
+    /// Global engine flags that point it at `remote`: `-H <url>` for Docker,
+    /// `--remote --url <url>` for Podman.
+    fn remote_args(&self) -> Vec<String> {
+        let Some(remote) = &self.remote else {
+            return Vec::new();
+        };
+        match self.kind {
+            EngineKind::Docker => vec!["-H".into(), remote.engine_url(false)],
+            EngineKind::Podman => vec!["--remote".into(), "--url".into(), remote.engine_url(true)],
+            EngineKind::None => Vec::new(),
+        }
+    }
+
+    /// A command for the engine binary, aimed at the remote server when one is set.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(self.bin.expect("engine bin not set"));
+        cmd.args(self.remote_args());
+        cmd
+    }
+
+    pub fn host_gateway(&self) -> &'static str {
+        match self.kind {
+            EngineKind::Podman => "host.containers.internal",
+            EngineKind::Docker => "host.docker.internal",
+            EngineKind::None => "localhost",
+        }
+    }
+
+    pub fn is_docker(&self) -> bool {
+        matches!(self.kind, EngineKind::Docker)
+    }
+
+    pub fn require_ready(&self) -> Result<()> {
+        let _timing = timings::phase("engine readiness");
+        if let (Some(remote), Some(bin)) = (&self.remote, self.bin) {
+            let reachable = self
+                .command()
+                .arg("info")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .logged()
+                .status()
+                .is_ok_and(|s| s.success());
+            return if reachable {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "{} on {} is not reachable ({})",
+                    bin,
+                    remote.host,
+                    format!("ssh {} {} info", remote.host, bin).red()
+                ))
+            };
+        }
+        match self.kind {
+            EngineKind::Docker => Command::new("docker")
+                .arg("info")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .logged()
+                .status()
+                .map_err(|e| anyhow!("failed to run docker info: {}", e))
+                .and_then(|s| {
+                    if s.success() {
+                        Ok(())
+                    } else {
+                        Err(anyhow!(
+                            "Docker does not appear to be running ({})",
+                            "docker info".red()
+                        ))
+                    }
+                }),
+            EngineKind::Podman => {
+                if cfg!(target_os = "linux") {
+                    // On Linux, Podman runs natively without a VM/machine.
+                    Command::new("podman")
+                        .arg("info")
+                        .stdout(Stdio::null())
+                        .stderr(Stdio::null())
+                        .logged()
+                        .status()
+                        .map_err(|e| anyhow!("failed to run podman info: {}", e))
+                        .and_then(|s| {
+                            if s.success() {
+                                Ok(())
+                            } else {
+                                Err(anyhow!(
+                                    "Podman does not appear to be running ({})",
+                                    "podman info".red()
+                                ))
+                            }
+                        })
+                } else {
+                    // On macOS/Windows, Podman requires a running machine/VM.
+                    let output = Command::new("podman")
+                        .arg("machine")
+                        .arg("list")
+                        .arg("--format")
+                        .arg("{{.Name}} {{.Running}}")
+                        .logged()
+                        .output()?;
+
+                    if !output.status.success() {
+                        return Err(anyhow!(
+                            "Failed to run 'podman machine list': exit {}",
+                            output.status
+                        ));
+                    }
+
+                    let text = String::from_utf8_lossy(&output.stdout);
+                    let machine_env = self
+                        .podman_machine
+                        .as_deref()
+                        .unwrap_or("podman-machine-default");
+
+                    for line in text.lines() {
+                        let parts: Vec<_> = line.split_whitespace().collect();
+                        if parts.len() != 2 {
+                            continue;
+                        }
+                        let name = parts[0].trim_end_matches('*');
+                        let running = parts[1];
+                        if name == machine_env && running.eq_ignore_ascii_case("true") {
+                            return Ok(());
+                        }
+                    }
+
+                    Err(anyhow!(
+                        "Podman machine '{}' appears to be down ({})",
+                        machine_env,
+                        format!("podman machine start {}", machine_env).red()
+                    ))
+                }
+            }
+            EngineKind::None => Err(anyhow!(
+                "No container engine is configured.\nUse 'darp set engine podman' or 'darp set engine docker'."
+            )),
+        }
+    }
+
+    /// Name of the Podman machine darp manages (falls back to Podman's default).
+    pub fn machine_name(&self) -> &str {
+        self.podman_machine
+            .as_deref()
+            .unwrap_or("podman-machine-default")
+    }
+
+    /// Build the engine's own VM lifecycle command for `action` (start|stop|status).
+    ///
+    /// Podman maps to `podman machine <action> <name>` (status uses `inspect`);
+    /// Docker maps to the Docker Desktop CLI (`docker desktop <action>`).
+    pub fn machine_command(&self, action: &str) -> Result<Command> {
+        if let Some(remote) = &self.remote {
+            return Err(anyhow!(
+                "the engine runs on {}; 'darp machine' only manages a local VM",
+                remote.host
+            ));
+        }
+        match self.kind {
+            EngineKind::Podman => {
+                let mut cmd = Command::new("podman");
+                cmd.arg("machine");
+                if action == "status" {
+                    cmd.arg("inspect")
+                        .arg("--format")
+                        .arg("{{.Name}}: {{.State}}");
+                } else {
+                    cmd.arg(action);
+                }
+                cmd.arg(self.machine_name());
+                Ok(cmd)
+            }
+            EngineKind::Docker => {
+                let mut cmd = Command::new("docker");
+                cmd.arg("desktop").arg(action);
+                Ok(cmd)
+            }
+            EngineKind::None => Err(anyhow!(
+                "No container engine is configured.\nUse 'darp set engine podman' or 'darp set engine docker'."
+            )),
+        }
+    }
+
+    /// `run` options for a foreground container removed on exit, besides its name.
+    pub fn run_options_interactive(&self, domain: &str, service: &str) -> Vec<String> {
+        let mut options = vec!["--rm".to_string(), "-it".to_string()];
+        options.extend(service_labels(domain, service));
+        options.extend(self.engine_args.iter().cloned());
+        options
+    }
+
+    /// `restart` replaces the default `--rm` with a `--restart` policy (unless it's `no`),
+    /// leaving the container behind after it exits so the engine can restart it.
+    /// `detach` starts it in the background (`-d`).
+    pub fn run_options_noninteractive(
+        &self,
+        domain: &str,
+        service: &str,
+        restart: Option<&str>,
+        detach: bool,
+    ) -> Vec<String> {
+        let mut options = Vec::new();
+        if detach {
+            options.push("-d".to_string());
+        }
+        match restart.filter(|p| *p != "no") {
+            Some(policy) => options.push(format!("--restart={}", policy)),
+            None => options.push("--rm".to_string()),
+        }
+        options.extend(service_labels(domain, service));
+        options.extend(self.engine_args.iter().cloned());
+        options
+    }
+
+    /// `run` options for a `darp shell --persist` container: kept after it stops (no
+    /// `--rm`) and started in the background, to be entered with `exec`.
+    pub fn run_options_persistent_shell(&self, domain: &str, service: &str) -> Vec<String> {
+        let mut options = vec![
+            "-d".to_string(),
+            "--label".to_string(),
+            format!("{}=true", MANAGED_LABEL),
+            "--label".to_string(),
+            format!("{}={}", DOMAIN_LABEL, domain),
+            "--label".to_string(),
+            format!("{}={}", SHELL_LABEL, service),
+        ];
+        options.extend(self.engine_args.iter().cloned());
+        options
+    }
+
+    /// `run` options that run a container's processes as the host user `uid:gid`, so
+    /// files they write to bind mounts belong to that user. Podman also maps the user to
+    /// itself on the host (`--userns=keep-id`), which a container in a pod can't set.
+    pub fn map_user_options(&self, uid: u32, gid: u32, in_pod: bool) -> Vec<String> {
+        let mut options = Vec::new();
+        if self.kind == EngineKind::Podman && !in_pod {
+            options.push("--userns=keep-id".to_string());
+        }
+        options.push("--user".to_string());
+        options.push(format!("{}:{}", uid, gid));
+        options
+    }
+
+    pub fn command_to_string(&self, cmd: &Command) -> String {
+        command_line(cmd)
+    }
+
+    /// Names of all running containers (empty when the engine is unavailable).
+    pub fn running_container_names(&self) -> std::collections::HashSet<String> {
+        if self.bin.is_none() {
+            return Default::default();
+        }
+        let output = self
+            .command()
+            .arg("ps")
+            .arg("--format")
+            .arg("{{.Names}}")
+            .logged()
+            .output();
+        match output {
+            Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect(),
+            _ => Default::default(),
+        }
+    }
+
+    /// Exact-name lookup, for the helpers whose names darp fixes. Service containers
+    /// should be found with `running_service_container` instead.
+    pub fn is_container_running(&self, name: &str) -> bool {
+        self.running_container_names().contains(name)
+    }
+
+    /// Names of running containers carrying `darp.managed=true` plus every extra
+    /// `label` filter (engines AND repeated label filters together).
+    fn running_managed_names(&self, label_filters: &[String]) -> Vec<String> {
+        if self.bin.is_none() {
+            return Vec::new();
+        }
+        let mut cmd = self.command();
+        cmd.arg("ps")
+            .arg("--filter")
+            .arg(format!("label={}=true", MANAGED_LABEL));
+        for filter in label_filters {
+            cmd.arg("--filter").arg(format!("label={}", filter));
+        }
+        match cmd.arg("--format").arg("{{.Names}}").logged().output() {
+            Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Every running serve/shell container with its labels, status and published ports.
+    pub fn service_containers(&self) -> Vec<ServiceContainer> {
+        if self.bin.is_none() {
+            return Vec::new();
+        }
+        let Ok(ps) = self
+            .command()
+            .arg("ps")
+            .arg("--filter")
+            .arg(format!("label={}=true", MANAGED_LABEL))
+            .arg("--filter")
+            .arg(format!("label={}", SERVICE_LABEL))
+            .arg("--format")
+            .arg("{{.Names}}\t{{.Status}}")
+            .logged()
+            .output()
+        else {
+            return Vec::new();
+        };
+        let statuses: Vec<(String, String)> = String::from_utf8_lossy(&ps.stdout)
+            .lines()
+            .filter_map(|l| {
+                let (name, status) = l.split_once('\t')?;
+                Some((name.trim().to_string(), status.trim().to_string()))
+            })
+            .filter(|(name, _)| !name.is_empty())
+            .collect();
+        if statuses.is_empty() {
+            return Vec::new();
+        }
+
+        // `.Config.Labels` and `.NetworkSettings.Ports` have the same shape in Docker's
+        // and Podman's inspect output; their `ps` formats differ. Docker prefixes `.Name`
+        // with a slash.
+        let format = format!(
+            "{{{{.Name}}}}\t{{{{index .Config.Labels \"{}\"}}}}\t{{{{index .Config.Labels \"{}\"}}}}\t{{{{json .NetworkSettings.Ports}}}}",
+            DOMAIN_LABEL, SERVICE_LABEL
+        );
+        let inspect = self
+            .command()
+            .arg("inspect")
+            .arg("--format")
+            .arg(format)
+            .args(statuses.iter().map(|(name, _)| name))
+            .logged()
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+            .unwrap_or_default();
+        let details: std::collections::HashMap<&str, Vec<&str>> = inspect
+            .lines()
+            .filter_map(|l| {
+                let fields: Vec<&str> = l.splitn(4, '\t').collect();
+                Some((fields.first()?.trim_start_matches('/'), fields))
+            })
+            .collect();
+
+        statuses
+            .into_iter()
+            .filter_map(|(name, status)| {
+                let fields = details.get(name.as_str())?;
+                Some(ServiceContainer {
+                    domain: fields.get(1)?.to_string(),
+                    service: fields.get(2)?.to_string(),
+                    status,
+                    ports: parse_published_ports(fields.get(3).copied().unwrap_or_default()),
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    /// Name of the running container serving `service` in `domain`, whatever it's called.
+    /// For a replicated service, that's its first replica.
+    pub fn running_service_container(&self, domain: &str, service: &str) -> Option<String> {
+        self.running_service_containers(domain, service)
+            .into_iter()
+            .next()
+    }
+
+    /// Every running container serving `service` in `domain`, shortest name first so a
+    /// replicated service's first replica leads its `_rN` siblings.
+    pub fn running_service_containers(&self, domain: &str, service: &str) -> Vec<String> {
+        let mut names = self.running_managed_names(&[
+            format!("{}={}", DOMAIN_LABEL, domain),
+            format!("{}={}", SERVICE_LABEL, service),
+        ]);
+        names.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        names
+    }
+
+    /// Current healthcheck state of a container, or None if it has no healthcheck (or
+    /// doesn't exist yet).
+    pub fn container_health(&self, name: &str) -> Option<HealthState> {
+        self.bin?;
+        let output = self
+            .command()
+            .args(["inspect", "--format", "{{json .State.Health}}", name])
+            .logged()
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_health(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    pub fn is_process_running_in_container(&self, container_name: &str, process: &str) -> bool {
+        if self.bin.is_none() {
+            return false;
+        }
+        let output = self
+            .command()
+            .arg("top")
+            .arg(container_name)
+            .logged()
+            .output();
+        if let Ok(out) = output {
+            if out.status.success() {
+                let text = String::from_utf8_lossy(&out.stdout);
+                return text.lines().skip(1).any(|line| line.contains(process));
+            }
+        }
+        false
+    }
+
+    pub fn is_engine_installed(&self) -> bool {
+        self.bin.is_some_and(is_on_path)
+    }
+
+    /// Ask the engine to expand `host-gateway` in a throwaway container and return the IP.
+    ///
+    /// Docker Desktop and Docker Engine / Podman each map `host-gateway` to a different
+    /// IP (VM-internal bridge vs. host-routable magic address), so this is the only way
+    /// to learn the platform-correct value without guessing.
+    pub fn probe_host_gateway_ip(&self) -> Result<String> {
+        self.bin
+            .ok_or_else(|| anyhow!("no container engine configured"))?;
+
+        const PROBE_HOST: &str = "_darp_probe_";
+
+        let output = self
+            .command()
+            .arg("run")
+            .arg("--rm")
+            .args(&self.engine_args)
+            .arg("--add-host")
+            .arg(format!("{PROBE_HOST}:host-gateway"))
+            .arg("nginx")
+            .arg("cat")
+            .arg("/etc/hosts")
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("failed to run probe container: {}", e))?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "probe container exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for line in stdout.lines() {
+            let mut parts = line.split_whitespace();
+            let ip = parts.next();
+            let name = parts.next();
+            if name == Some(PROBE_HOST) {
+                if let Some(ip) = ip {
+                    return Ok(ip.to_string());
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "probe container did not return a {} entry in /etc/hosts",
+            PROBE_HOST
+        ))
+    }
+
+    /// Run spec the helper was created with, read back from its label. `None` when the
+    /// container doesn't exist at all (running or stopped).
+    fn helper_fingerprint(&self, name: &str) -> Option<String> {
+        self.bin?;
+        let output = self
+            .command()
+            .arg("inspect")
+            .arg("--format")
+            .arg(format!(
+                "{{{{index .Config.Labels \"{}\"}}}}",
+                HELPER_ARGS_LABEL
+            ))
+            .arg(name)
+            .logged()
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let label = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Some(if label == "<no value>" {
+            String::new()
+        } else {
+            label
+        })
+    }
+
+    /// Bring a helper container to its desired state. A running helper created from the
+    /// same run spec is left alone; a stopped one is started again; one created from a
+    /// different spec (changed run args, a newer darp, or no label at all) is replaced.
+    fn reconcile_helper(&self, name: &str, image: &str, spec: &[String]) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        let fingerprint = spec.join(" ");
+
+        match self.helper_fingerprint(name) {
+            Some(existing) if existing == fingerprint => {
+                if self.is_container_running(name) {
+                    return Ok(());
+                }
+                println!("starting {}", name.green());
+                self.command()
+                    .arg("start")
+                    .arg(name)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .logged()
+                    .status()?;
+                return Ok(());
+            }
+            Some(_) => {
+                println!("recreating {} (run settings changed)", name.green());
+                self.remove_named_container(name)?;
+            }
+            None => println!("starting {}", name.green()),
+        }
+
+        self.command()
+            .arg("run")
+            .arg("-d")
+            .arg("--name")
+            .arg(name)
+            .arg("--label")
+            .arg(format!("{}={}", HELPER_ARGS_LABEL, fingerprint))
+            .args(spec)
+            .arg(image)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .spawn()?;
+        Ok(())
+    }
+
+    /// Everything after `run -d --name` for a helper: darp's own ports/mounts, the
+    /// managed label, the default restart policy, then `engine_args` and the helper's
+    /// own run args.
+    fn helper_spec(&self, mut args: Vec<String>, user_args: &[String]) -> Vec<String> {
+        args.push("--label".into());
+        args.push(format!("{}=true", MANAGED_LABEL));
+        if !has_restart_policy(&self.engine_args) && !has_restart_policy(user_args) {
+            args.push("--restart=always".into());
+        }
+        if self.is_docker() {
+            args.push("--add-host".into());
+            args.push("host.docker.internal:host-gateway".into());
+        }
+        args.extend(self.engine_args.iter().cloned());
+        args.extend(user_args.iter().cloned());
+        args
+    }
+
+    /// The files the reverse proxy mounts: this user's own, or on a shared host the
+    /// shared directory's, so every user's deploy starts the same proxy.
+    fn reverse_proxy_mounts(&self, paths: &DarpPaths) -> Vec<String> {
+        match &self.shared_proxy_dir {
+            Some(dir) => vec![
+                "-v".into(),
+                format!("{}:/etc/nginx/nginx.conf", dir.join("nginx.conf").display()),
+                "-v".into(),
+                format!("{}:/etc/nginx/http.d", dir.join("http.d").display()),
+            ],
+            None => vec![
+                "-v".into(),
+                format!("{}:/etc/nginx/nginx.conf", paths.nginx_conf_path.display()),
+                "-v".into(),
+                format!(
+                    "{}:/etc/nginx/http.d/vhost_container.conf",
+                    paths.vhost_container_conf.display()
+                ),
+                "-v".into(),
+                format!("{}:/usr/share/nginx/darp:ro", paths.www_dir.display()),
+            ],
+        }
+    }
+
+    fn reverse_proxy_spec(&self, paths: &DarpPaths) -> Vec<String> {
+        self.helper_spec(
+            ["-p".into(), "80:80".into()]
+                .into_iter()
+                .chain(self.reverse_proxy_mounts(paths))
+                .chain(
+                    self.grpc_listener
+                        .then(|| ["-p".into(), format!("{0}:{0}", GRPC_PROXY_PORT)])
+                        .into_iter()
+                        .flatten(),
+                )
+                .chain(
+                    crate::config::read_json::<serde_json::Value>(&paths.portmap_path)
+                        .map(|portmap| crate::config::portmap_direct_proxy_ports(&portmap))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flat_map(|port| ["-p".into(), format!("{0}:{0}", port)]),
+                )
+                .chain(self.stream_ports.iter().flat_map(|(port, udp)| {
+                    let suffix = if *udp { "/udp" } else { "" };
+                    ["-p".into(), format!("{port}:{port}{suffix}")]
+                }))
+                .chain(
+                    (!self.stream_ports.is_empty())
+                        .then(|| {
+                            [
+                                "-v".into(),
+                                format!(
+                                    "{}:/etc/nginx/stream.d/stream_container.conf",
+                                    paths.stream_container_conf.display()
+                                ),
+                            ]
+                        })
+                        .into_iter()
+                        .flatten(),
+                )
+                .chain(
+                    (!self.tls_certs.is_empty())
+                        .then(|| ["-p".into(), format!("{0}:{0}", TLS_PROXY_PORT)])
+                        .into_iter()
+                        .flatten(),
+                )
+                .chain(self.tls_certs.iter().flat_map(|(host, cert, key)| {
+                    [
+                        "-v".into(),
+                        format!("{}:/etc/nginx/certs/{}.crt:ro", cert.display(), host),
+                        "-v".into(),
+                        format!("{}:/etc/nginx/certs/{}.key:ro", key.display(), host),
+                    ]
+                }))
+                .chain(
+                    self.static_mounts
+                        .iter()
+                        .flat_map(|(domain_name, location)| {
+                            [
+                                "-v".into(),
+                                format!(
+                                    "{}:{}/{}:ro",
+                                    location.display(),
+                                    STATIC_ROOT,
+                                    domain_name
+                                ),
+                            ]
+                        }),
+                )
+                .chain(
+                    self.darp_network
+                        .then(|| format!("--network={}", DARP_NETWORK)),
+                )
+                .collect(),
+            &self.reverse_proxy_args,
+        )
+    }
+
+    pub fn start_reverse_proxy(&self, paths: &DarpPaths) -> Result<()> {
+        self.reconcile_helper(
+            REVERSE_PROXY,
+            "nginx:alpine",
+            &self.reverse_proxy_spec(paths),
+        )
+    }
+
+    pub fn restart_reverse_proxy(&self, paths: &DarpPaths) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+
+        let current = self.helper_fingerprint(REVERSE_PROXY);
+        if !self.is_container_running(REVERSE_PROXY)
+            || current.as_deref() != Some(self.reverse_proxy_spec(paths).join(" ").as_str())
+        {
+            return self.start_reverse_proxy(paths);
+        }
+
+        println!("restarting {}", REVERSE_PROXY.green());
+
+        self.command()
+            .arg("restart")
+            .arg(REVERSE_PROXY)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .spawn()?;
+
+        Ok(())
+    }
+
+    /// Have the running proxy re-read its vhosts (`nginx -s reload`) without dropping
+    /// connections. Falls back to starting it when it's stopped or its run settings changed.
+    pub fn reload_reverse_proxy(&self, paths: &DarpPaths) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+
+        let current = self.helper_fingerprint(REVERSE_PROXY);
+        if !self.is_container_running(REVERSE_PROXY)
+            || current.as_deref() != Some(self.reverse_proxy_spec(paths).join(" ").as_str())
+        {
+            return self.start_reverse_proxy(paths);
+        }
+
+        println!("reloading {}", REVERSE_PROXY.green());
+
+        let output = self
+            .command()
+            .arg("exec")
+            .arg(REVERSE_PROXY)
+            .args(["nginx", "-s", "reload"])
+            .logged()
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "could not reload {}: {}",
+                REVERSE_PROXY,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn start_darp_masq(&self, paths: &DarpPaths) -> Result<()> {
+        let spec = self.helper_spec(
+            vec![
+                "-p".into(),
+                "53:53/udp".into(),
+                "-p".into(),
+                "53:53/tcp".into(),
+                "-v".into(),
+                format!("{}:/etc/dnsmasq.d", paths.dnsmasq_dir.display()),
+                "--cap-add=NET_ADMIN".into(),
+            ],
+            &self.dnsmasq_args,
+        );
+        self.reconcile_helper(DNSMASQ, "dockurr/dnsmasq", &spec)
+    }
+
+    /// Restart darp-masq so it re-reads dnsmasq.d: a SIGHUP only makes dnsmasq reload its
+    /// hosts files, not `address=` rules. Nothing to do when it isn't running.
+    pub fn restart_darp_masq(&self) -> Result<()> {
+        if self.bin.is_none() || !self.is_container_running(DNSMASQ) {
+            return Ok(());
+        }
+        println!("restarting {}", DNSMASQ.green());
+        let status = self
+            .command()
+            .args(["restart", DNSMASQ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()?;
+        if !status.success() {
+            return Err(anyhow!("could not restart {}", DNSMASQ));
+        }
+        Ok(())
+    }
+
+    /// The engine's state for a container (`running`, `exited`, `created`, …), or None
+    /// when there's no container by that name.
+    pub fn container_status(&self, name: &str) -> Option<String> {
+        self.bin?;
+        let output = self
+            .command()
+            .args(["inspect", "--format", "{{.State.Status}}", name])
+            .stderr(Stdio::null())
+            .logged()
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Force-remove a container, running or not. Helpers aren't started with `--rm`
+    /// (their restart policy keeps them around), so stopping alone leaves them behind.
+    pub fn remove_named_container(&self, name: &str) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        self.command()
+            .arg("rm")
+            .arg("-f")
+            .arg(name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()?;
+        Ok(())
+    }
+
+    /// Copy darp's generated files (nginx config, vhosts, dnsmasq config, the containers'
+    /// hosts file, the service index) to the same paths on the remote server, where the
+    /// containers mount them from. No-op without a remote.
+    pub fn sync_remote_files(&self, paths: &DarpPaths) -> Result<()> {
+        let Some(remote) = &self.remote else {
+            return Ok(());
+        };
+        let root = &paths.darp_root;
+        let entries: Vec<&OsStr> = [
+            &paths.nginx_conf_path,
+            &paths.vhost_container_conf,
+            &paths.stream_container_conf,
+            &paths.hosts_container_path,
+            &paths.dnsmasq_dir,
+            &paths.www_dir,
+        ]
+        .into_iter()
+        .filter(|p| p.exists())
+        .filter_map(|p| p.file_name())
+        .collect();
+
+        let mut tar = Command::new("tar")
+            .arg("-C")
+            .arg(root)
+            .arg("-cf")
+            .arg("-")
+            .args(&entries)
+            .stdout(Stdio::piped())
+            .logged()
+            .spawn()
+            .map_err(|e| anyhow!("failed to run tar: {}", e))?;
+        let archive = tar
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Could not open tar output"))?;
+        let root = shell_escape(root.as_os_str());
+        let status = Command::new("ssh")
+            .arg(&remote.host)
+            .arg(format!("mkdir -p {root} && tar -C {root} -xf -"))
+            .stdin(archive)
+            .logged()
+            .status()
+            .map_err(|e| anyhow!("failed to run ssh: {}", e))?;
+        tar.wait()?;
+        if !status.success() {
+            return Err(anyhow!(
+                "failed to copy darp files to {} (ssh exited with {})",
+                remote.host,
+                status
+            ));
+        }
+        Ok(())
+    }
+
+    /// Those of `paths` that don't exist on the remote server, where a bind mount would
+    /// silently be an empty folder. Empty without a remote.
+    pub fn missing_remote_paths(&self, paths: &[String]) -> Result<Vec<String>> {
+        let Some(remote) = &self.remote else {
+            return Ok(Vec::new());
+        };
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+        let quoted: Vec<String> = paths.iter().map(|p| shell_escape(OsStr::new(p))).collect();
+        let output = Command::new("ssh")
+            .arg(&remote.host)
+            .arg(format!(
+                "for p in {}; do [ -e \"$p\" ] || echo \"$p\"; done",
+                quoted.join(" ")
+            ))
+            .stdin(Stdio::null())
+            .logged()
+            .output()
+            .map_err(|e| anyhow!("failed to run ssh: {}", e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "could not check the mounted folders on {}: {}",
+                remote.host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Let rootless Podman restart `--restart=always` containers after a reboot; Podman
+    /// has no daemon, so that job falls to the `podman-restart` user service.
+    pub fn enable_restart_on_boot(&self) -> Result<()> {
+        if !matches!(self.kind, EngineKind::Podman)
+            || !cfg!(target_os = "linux")
+            || self.remote.is_some()
+        {
+            return Ok(());
+        }
+        let status = Command::new("systemctl")
+            .args(["--user", "enable", "podman-restart.service"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status();
+        if !matches!(status, Ok(s) if s.success()) {
+            warn!(
+                "could not enable podman-restart.service; helper containers won't come back after a reboot until 'darp deploy'"
+            );
+        }
+        Ok(())
+    }
+
+    /// Create `DARP_NETWORK` unless it already exists (no-op when disabled).
+    pub fn ensure_darp_network(&self) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        if !self.darp_network || self.network_exists(DARP_NETWORK) {
+            return Ok(());
+        }
+        println!("creating network {}", DARP_NETWORK.green());
+        let output = self
+            .command()
+            .args(["network", "create", "--label"])
+            .arg(format!("{}=true", MANAGED_LABEL))
+            .arg(DARP_NETWORK)
+            .logged()
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to create network {}: {}",
+                DARP_NETWORK,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn remove_darp_network(&self) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        if !self.network_exists(DARP_NETWORK) {
+            return Ok(());
+        }
+        self.command()
+            .args(["network", "rm", DARP_NETWORK])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()?;
+        Ok(())
+    }
+
+    fn network_exists(&self, name: &str) -> bool {
+        if self.bin.is_none() {
+            return false;
+        }
+        self.command()
+            .args(["network", "inspect", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// DNS server that resolves container names on `DARP_NETWORK`, for nginx's
+    /// `resolver`. Docker answers on 127.0.0.11 inside every container; Podman's
+    /// aardvark-dns listens on the network's gateway.
+    pub fn darp_network_dns(&self) -> Result<String> {
+        match self.kind {
+            EngineKind::Docker => Ok("127.0.0.11".to_string()),
+            EngineKind::Podman => {
+                let output = self
+                    .command()
+                    .args(["network", "inspect", DARP_NETWORK, "--format"])
+                    .arg("{{range .Subnets}}{{.Gateway}} {{end}}")
+                    .logged()
+                    .output()?;
+                String::from_utf8_lossy(&output.stdout)
+                    .split_whitespace()
+                    .next()
+                    .map(str::to_string)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "could not read the gateway of the {} network; run 'darp config set darp-network false' to proxy through host ports instead",
+                            DARP_NETWORK
+                        )
+                    })
+            }
+            EngineKind::None => Err(anyhow!("no container engine configured")),
+        }
+    }
+
+    /// Create the domain's pod unless it already exists. Ports can only be published
+    /// when a pod is created, so `ports` must cover every service that may join it.
+    pub fn ensure_pod(&self, domain: &str, ports: &[u16]) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        let name = pod_name(domain);
+        let exists = self
+            .command()
+            .args(["pod", "exists", &name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()
+            .is_ok_and(|s| s.success());
+        if exists {
+            return Ok(());
+        }
+
+        println!("creating pod {}", name.green());
+        let mut cmd = self.command();
+        cmd.args(["pod", "create", "--name", &name])
+            .arg("--label")
+            .arg(format!("{}=true", MANAGED_LABEL))
+            .arg("--label")
+            .arg(format!("{}={}", DOMAIN_LABEL, domain));
+        for port in ports {
+            cmd.arg("-p").arg(format!("{port}:{port}"));
+        }
+        let output = cmd.logged().output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to create pod {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Create the named volume `name`, labelled as darp's, unless it already exists.
+    /// The engine would create a missing one on `run` too, but without the label
+    /// `darp volume ls` lists by.
+    pub fn ensure_volume(&self, name: &str) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        let exists = self
+            .command()
+            .args(["volume", "inspect", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .status()
+            .is_ok_and(|s| s.success());
+        if exists {
+            return Ok(());
+        }
+
+        println!("creating volume {}", name.green());
+        let output = self
+            .command()
+            .args(["volume", "create", "--label"])
+            .arg(format!("{}=true", MANAGED_LABEL))
+            .arg(name)
+            .logged()
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to create volume {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The named volumes darp created, sorted.
+    pub fn darp_volumes(&self) -> Vec<String> {
+        if self.bin.is_none() {
+            return Vec::new();
+        }
+        let output = self
+            .command()
+            .args(["volume", "ls", "--format", "{{.Name}}", "--filter"])
+            .arg(format!("label={}=true", MANAGED_LABEL))
+            .logged()
+            .output();
+        let mut names: Vec<String> = output
+            .map(|o| {
+                String::from_utf8_lossy(&o.stdout)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// Remove the named volume `name` and its data. Fails while a container uses it.
+    pub fn remove_volume(&self, name: &str) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        let output = self
+            .command()
+            .args(["volume", "rm", name])
+            .logged()
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "could not remove volume {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Remove every pod darp created. Run on deploy, since a pod's published ports are
+    /// fixed at creation and the portmap may have changed.
+    pub fn remove_darp_pods(&self) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        if !matches!(self.kind, EngineKind::Podman) {
+            return Ok(());
+        }
+        let output = self
+            .command()
+            .args(["pod", "ps", "-q", "--filter"])
+            .arg(format!("label={}=true", MANAGED_LABEL))
+            .logged()
+            .output()?;
+        for id in String::from_utf8_lossy(&output.stdout).lines() {
+            let id = id.trim();
+            if id.is_empty() {
+                continue;
+            }
+            self.command()
+                .args(["pod", "rm", "-f", id])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .logged()
+                .status()?;
+        }
+        Ok(())
+    }
+
+    /// Stop every serve/shell container darp started. Helpers carry the managed label
+    /// too but no service label, so they're left running. The stops run side by side, and
+    /// return once all of them are done, so a caller's engine slot covers them.
+    pub fn stop_running_darps(&self) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        let mut stops = Vec::new();
+        for name in self.running_managed_names(&[SERVICE_LABEL.to_string()]) {
+            println!("stopping {}", name.cyan());
+            stops.push(
+                self.command()
+                    .arg("stop")
+                    .arg(&name)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .logged()
+                    .spawn()?,
+            );
+        }
+        for mut stop in stops {
+            stop.wait()?;
+        }
+        Ok(())
+    }
+
+    pub fn stop_named_container(&self, name: &str) -> Result<()> {
+        if self.bin.is_none() {
+            return Ok(());
+        }
+        if !self.is_container_running(name) {
+            return Ok(());
+        }
+        println!("stopping {}", name.cyan());
+        self.command()
+            .arg("stop")
+            .arg(name)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .logged()
+            .spawn()?;
+        Ok(())
+    }
+
+    pub fn run_container_interactive(
+        &self,
+        mut cmd: Command,
+        container_name: &str,
+        restart_on: &[i32],
+        prefix: Option<&str>,
+    ) -> Result<()> {
+        let restart_on: Vec<i32> = restart_on.to_vec();
+        let bin = self.bin.expect("engine bin not set").to_string();
+        let remote_args = self.remote_args();
+        if prefix.is_some() {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+
+        loop {
+            let mut child = cmd.logged().spawn()?;
+            let relays: Vec<_> = prefix
+                .into_iter()
+                .flat_map(|label| {
+                    let stdout = child
+                        .stdout
+                        .take()
+                        .map(|out| relay_prefixed(out, label.to_string(), false));
+                    let stderr = child
+                        .stderr
+                        .take()
+                        .map(|err| relay_prefixed(err, label.to_string(), true));
+                    stdout.into_iter().chain(stderr)
+                })
+                .collect();
+
+            let container_name_for_handler = container_name.to_string();
+            let bin_clone = bin.clone();
+            let remote_args = remote_args.clone();
+
+            ctrlc::set_handler(move || {
+                eprintln!("\nStopping {} (Ctrl+C)", container_name_for_handler.cyan());
+                // Best-effort stop
+                let _ = Command::new(&bin_clone)
+                    .args(&remote_args)
+                    .arg("stop")
+                    .arg(&container_name_for_handler)
+                    .logged()
+                    .status();
+            })?;
+
+            let status = child.wait()?;
+            for relay in relays {
+                let _ = relay.join();
+            }
+
+            if let Some(code) = status.code() {
+                if restart_on.contains(&code) {
+                    println!("restarting {} with code {}", container_name.cyan(), &code);
+                    continue;
+                }
+                println!("exiting with status code {}", &code);
+            }
+
+            // Normal exit or non-restartable error
+            break;
+        }
+
+        Ok(())
+    }
+
+    /// Let rootless Podman bind the proxy's port 80 and dnsmasq's 53 by lowering
+    /// `ip_unprivileged_port_start`: with sudo on Linux, inside the Podman machine (over
+    /// `podman machine ssh`) elsewhere. Docker and remote engines are left alone. A failure
+    /// only warns, with the command to run by hand.
+    pub fn configure_unprivileged_ports_if_needed(&self) -> Result<()> {
+        if !matches!(self.kind, EngineKind::Podman) || self.remote.is_some() {
+            return Ok(());
+        }
+        let proc_path = format!("/proc/sys/{}", UNPRIVILEGED_PORT_SYSCTL.replace('.', "/"));
+        let script = unprivileged_ports_script();
+
+        if cfg!(target_os = "linux") {
+            let current = std::fs::read_to_string(&proc_path).unwrap_or_default();
+            if unprivileged_ports_allowed(&current) {
+                return Ok(());
+            }
+            println!(
+                "Allowing rootless containers to bind ports from {} (sudo)",
+                UNPRIVILEGED_PORT_START
+            );
+            let mut sudo = Command::new("sudo");
+            if crate::os::assume_yes() {
+                sudo.arg("-n");
+            }
+            let status = sudo.args(["sh", "-c", &script]).logged().status();
+            if !matches!(status, Ok(s) if s.success()) {
+                warn!(
+                    "could not set {}; rootless podman can't bind ports 53 and 80 until you run: sudo sh -c {}",
+                    UNPRIVILEGED_PORT_SYSCTL,
+                    shell_escape(OsStr::new(&script))
+                );
+            }
+            return Ok(());
+        }
+
+        let machine = self.machine_name();
+        let current = Command::new("podman")
+            .args(["machine", "ssh", machine, "cat", &proc_path])
+            .stderr(Stdio::null())
+            .logged()
+            .output();
+        match current {
+            Ok(out) if out.status.success() => {
+                if unprivileged_ports_allowed(&String::from_utf8_lossy(&out.stdout)) {
+                    return Ok(());
+                }
+            }
+            _ => {
+                warn!(
+                    "could not reach podman machine '{}'; start it and run 'darp install' again so rootless podman can bind ports 53 and 80",
+                    machine
+                );
+                return Ok(());
+            }
+        }
+        println!(
+            "Allowing rootless containers in podman machine '{}' to bind ports from {}",
+            machine, UNPRIVILEGED_PORT_START
+        );
+        // `machine ssh` hands its arguments to the machine's shell as one command line.
+        let remote_command = format!("sudo sh -c {}", shell_escape(OsStr::new(&script)));
+        let status = Command::new("podman")
+            .args(["machine", "ssh", machine, &remote_command])
+            .stdout(Stdio::null())
+            .logged()
+            .status();
+        if !matches!(status, Ok(s) if s.success()) {
+            warn!(
+                "could not set {} in podman machine '{}'; run: podman machine ssh {} {}",
+                UNPRIVILEGED_PORT_SYSCTL,
+                machine,
+                machine,
+                shell_escape(OsStr::new(&remote_command))
+            );
+        }
+        Ok(())
+    }
+}
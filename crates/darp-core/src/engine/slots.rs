@@ -0,0 +1,110 @@
+// engine/slots.rs
+
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use fs4::fs_std::FileExt;
+use tracing::info;
+
+use super::Engine;
+use crate::timings;
+
+/// How often a queued operation checks for a free slot.
+const SLOT_POLL: Duration = Duration::from_millis(200);
+
+/// One of the engine slots, held until dropped. Slots are locked files shared by every
+/// darp process, so the lock goes away with the process too.
+#[derive(Debug)]
+pub struct EngineSlot {
+    _lock: Option<File>,
+}
+
+/// `path` opened for locking, created if need be.
+fn lock_file(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?)
+}
+
+/// How many live tickets in `queue` sort before `ticket`. A ticket nobody holds locked
+/// was left by a process that died while queued, and is removed.
+fn tickets_ahead(queue: &Path, ticket: &str) -> usize {
+    let mut ahead = 0;
+    for entry in fs::read_dir(queue).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.as_str() >= ticket {
+            continue;
+        }
+        match lock_file(&entry.path()).map(|f| f.try_lock_exclusive()) {
+            Ok(Ok(true)) => {
+                let _ = fs::remove_file(entry.path());
+            }
+            _ => ahead += 1,
+        }
+    }
+    ahead
+}
+
+impl Engine {
+    /// A free slot in `dir`, or None when `concurrency` operations already hold one.
+    /// With no limit, every caller gets a slot.
+    pub fn try_acquire_slot(&self, dir: &Path) -> Result<Option<EngineSlot>> {
+        if self.concurrency == 0 {
+            return Ok(Some(EngineSlot { _lock: None }));
+        }
+        fs::create_dir_all(dir)?;
+        for i in 0..self.concurrency {
+            let file = lock_file(&dir.join(format!("slot-{}.lock", i)))?;
+            if file.try_lock_exclusive()? {
+                return Ok(Some(EngineSlot { _lock: Some(file) }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Wait for a slot in `dir` before an engine operation (`what`, e.g. "start
+    /// darp_shop_api"), so a burst of starts, stops and pulls from any number of darp
+    /// processes can't wedge a small engine VM. Waiters queue in arrival order, and say
+    /// how many are ahead of them while they wait.
+    pub fn acquire_slot(&self, dir: &Path, what: &str) -> Result<EngineSlot> {
+        if let Some(slot) = self.try_acquire_slot(dir)? {
+            return Ok(slot);
+        }
+
+        let _timing = timings::phase("engine queue");
+        let queue = dir.join("queue");
+        fs::create_dir_all(&queue)?;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let name = format!("{:024}-{}", nanos, std::process::id());
+        let ticket_path = queue.join(&name);
+        let ticket = lock_file(&ticket_path)?;
+        ticket.try_lock_exclusive()?;
+
+        let mut shown = None;
+        let slot = loop {
+            let ahead = tickets_ahead(&queue, &name);
+            if ahead == 0 {
+                if let Some(slot) = self.try_acquire_slot(dir)? {
+                    break slot;
+                }
+            }
+            if shown != Some(ahead) {
+                info!(
+                    "waiting to {}: {} engine operation(s) running, {} queued ahead (engine_concurrency)",
+                    what, self.concurrency, ahead
+                );
+                shown = Some(ahead);
+            }
+            std::thread::sleep(SLOT_POLL);
+        };
+        drop(ticket);
+        let _ = fs::remove_file(&ticket_path);
+        Ok(slot)
+    }
+}
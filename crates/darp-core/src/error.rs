@@ -0,0 +1,94 @@
+// error.rs
+
+use thiserror::Error;
+
+/// Failures darp reports to the user with guidance on fixing them. Commands return them
+/// through `anyhow` like any other error; `main` prints them and picks the exit code.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DarpError {
+    #[error("engine must be 'podman' or 'docker', got '{0}'")]
+    UnknownEngine(String),
+
+    #[error("Please configure a domain: darp config add domain <name> <path>")]
+    NoDomains,
+
+    #[error("Environment '{0}' does not exist.")]
+    EnvironmentNotFound(String),
+
+    #[error(
+        "Environment is required for 'darp serve' in domain '{domain}'.\n\
+Either pass an explicit environment:\n  darp serve --environment <env>\n\
+or configure a default_environment for this domain:\n  darp config set dom default-environment {domain} <env>"
+    )]
+    EnvironmentRequired { domain: String },
+
+    #[error(
+        "Neither service '{domain}.{service}', domain '{domain}', nor environment '{environment}' has a serve_command configured.\n\
+Use 'darp config set svc serve-command {domain} {service} <cmd>' or \
+'darp config set dom serve-command {domain} <cmd>' or \
+'darp config set env serve-command {environment} <cmd>' first."
+    )]
+    NoServeCommand {
+        domain: String,
+        service: String,
+        environment: String,
+    },
+
+    #[error(
+        "No container image provided for '{domain}.{service}' in environment '{environment}'.\n\
+Either pass an explicit image to 'darp serve' or configure a default_container_image:\n  \
+darp config set svc default-container-image {domain} {service} <image>\n\
+or\n  \
+darp config set env default-container-image {environment} <image>"
+    )]
+    NoServeImage {
+        domain: String,
+        service: String,
+        environment: String,
+    },
+
+    #[error(
+        "No container image provided for '{domain}.{service}'.\n\
+Either pass an explicit image to 'darp shell' or configure a shell_image or default_container_image:\n  \
+darp config set svc default-container-image {domain} {service} <image>\n\
+or\n  \
+darp config set env default-container-image <env> <image>"
+    )]
+    NoShellImage { domain: String, service: String },
+
+    #[error(
+        "No image specified and none could be resolved from current directory.\nUsage: darp check-image <image>"
+    )]
+    NoImageToCheck,
+
+    #[error("Volume {0} does not appear to exist.")]
+    VolumeNotFound(String),
+
+    #[error(
+        "{0} doesn't exist on {1}; the engine runs there, so mounted folders must be at the same path on it (a shared mount or a synced copy)"
+    )]
+    RemoteVolumeNotFound(String, String),
+
+    #[error("port not yet assigned to {0}, run 'darp deploy'")]
+    PortNotAssigned(String),
+}
+
+impl DarpError {
+    /// The exit code for this error, matching `darp check`'s: 1 for configuration
+    /// problems, 4 when the service hasn't been deployed.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DarpError::PortNotAssigned(_) => 4,
+            _ => 1,
+        }
+    }
+}
+
+/// The exit code for an error returned by a command: that of the first `DarpError` in its
+/// chain, else 1.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    error
+        .chain()
+        .find_map(|e| e.downcast_ref::<DarpError>())
+        .map_or(1, DarpError::exit_code)
+}
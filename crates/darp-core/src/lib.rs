@@ -0,0 +1,25 @@
+//! The library behind the `darp` command: everything that reads and writes darp's
+//! configuration, drives the container engine, plans a deploy and builds service URLs.
+//! Editor extensions, GUIs and test harnesses can use it to do what the CLI does without
+//! shelling out to it.
+//!
+//! Start with [`config::DarpPaths::from_args`] and [`config::Config::load_merged`], then
+//! [`engine::Engine::new`]. [`urls`] turns a portmap into service URLs, and [`deploy`]
+//! holds the pieces `darp deploy` assembles its nginx configuration from.
+
+pub mod alias;
+pub mod backups;
+pub mod config;
+pub mod deploy;
+pub mod engine;
+pub mod error;
+pub mod livereload;
+pub mod logging;
+pub mod mdns;
+pub mod os;
+pub mod resolver;
+pub mod scan;
+pub mod secrets;
+pub mod services_index;
+pub mod timings;
+pub mod urls;
@@ -0,0 +1,258 @@
+// livereload.rs
+
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Port `darp live-reload` listens on; the reverse proxy forwards `/__darp/` here.
+pub const LIVE_RELOAD_PORT: u16 = 35729;
+
+/// Path prefix the proxy routes to the live reload server instead of the service.
+pub const LIVE_RELOAD_PATH: &str = "/__darp/";
+
+/// Script injected before `</body>`: it reloads the page on a `reload` message and
+/// reconnects whenever `darp live-reload` restarts.
+pub const LIVE_RELOAD_SCRIPT: &str = r#"(function () {
+  var url = (location.protocol === "https:" ? "wss://" : "ws://") + location.host + "/__darp/livereload";
+  function connect() {
+    var ws = new WebSocket(url);
+    ws.onmessage = function (e) { if (e.data === "reload") location.reload(); };
+    ws.onclose = function () { setTimeout(connect, 1000); };
+  }
+  connect();
+})();
+"#;
+
+/// Folders whose churn (installs, builds, VCS metadata) shouldn't reload the page.
+const IGNORED_DIRS: &[&str] = &[".git", "node_modules", "target", "vendor", ".cache"];
+
+/// The `Sec-WebSocket-Accept` value answering a client's `Sec-WebSocket-Key` (RFC 6455).
+pub fn websocket_accept(key: &str) -> String {
+    let mut input = key.trim().as_bytes().to_vec();
+    input.extend_from_slice(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+    base64(&sha1(&input))
+}
+
+/// An unmasked, single-frame text message, as servers send them.
+pub fn text_frame(message: &str) -> Vec<u8> {
+    let payload = message.as_bytes();
+    let mut frame = vec![0x81];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// File count and newest modification time under `dir`, skipping `IGNORED_DIRS`.
+/// Any edit, addition or removal changes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fingerprint {
+    files: usize,
+    newest: Option<SystemTime>,
+}
+
+pub fn tree_fingerprint(dir: &Path) -> Fingerprint {
+    let mut fingerprint = Fingerprint::default();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !IGNORED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                    pending.push(entry.path());
+                }
+                continue;
+            }
+            fingerprint.files += 1;
+            if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                fingerprint.newest = fingerprint.newest.max(Some(modified));
+            }
+        }
+    }
+    fingerprint
+}
+
+type Clients = Arc<Mutex<HashMap<String, Vec<TcpStream>>>>;
+
+/// Serve the reload script and websocket on `LIVE_RELOAD_PORT`, and tell the pages of
+/// each `(host, dir)` pair to reload when `dir` changes. Runs until the process exits.
+pub fn serve(watched: Vec<(String, PathBuf)>) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", LIVE_RELOAD_PORT))
+        .map_err(|e| anyhow!("could not listen on port {}: {}", LIVE_RELOAD_PORT, e))?;
+    let clients: Clients = Default::default();
+
+    let watcher_clients = Arc::clone(&clients);
+    std::thread::spawn(move || {
+        let mut last: Vec<Fingerprint> = watched
+            .iter()
+            .map(|(_, dir)| tree_fingerprint(dir))
+            .collect();
+        loop {
+            std::thread::sleep(Duration::from_millis(500));
+            for ((host, dir), last) in watched.iter().zip(last.iter_mut()) {
+                let current = tree_fingerprint(dir);
+                if current == *last {
+                    continue;
+                }
+                *last = current;
+                let mut clients = watcher_clients.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(streams) = clients.get_mut(host) {
+                    let frame = text_frame("reload");
+                    streams.retain_mut(|s| s.write_all(&frame).is_ok());
+                    println!(
+                        "{} changed, reloaded {} page(s)",
+                        host.cyan(),
+                        streams.len()
+                    );
+                }
+            }
+        }
+    });
+
+    for stream in listener.incoming().flatten() {
+        let clients = Arc::clone(&clients);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &clients) {
+                tracing::warn!("live reload: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, clients: &Clients) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let host = headers
+        .get("host")
+        .map(|h| h.split(':').next().unwrap_or_default().to_string())
+        .unwrap_or_default();
+
+    if path == format!("{}livereload.js", LIVE_RELOAD_PATH) {
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/javascript\r\nCache-Control: no-store\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            LIVE_RELOAD_SCRIPT.len(),
+            LIVE_RELOAD_SCRIPT
+        )?;
+        return Ok(());
+    }
+
+    match headers.get("sec-websocket-key") {
+        Some(key) if path == format!("{}livereload", LIVE_RELOAD_PATH) => {
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                websocket_accept(key)
+            )?;
+            clients
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(host)
+                .or_default()
+                .push(stream);
+        }
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )?,
+    }
+    Ok(())
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (chunk, word) in out.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
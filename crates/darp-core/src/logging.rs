@@ -0,0 +1,81 @@
+// logging.rs
+
+use std::fmt;
+use std::process::Command;
+
+use colored::*;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::engine::command_line;
+
+/// Environment variable holding a filter (`warn`, `debug`, `darp::exec=debug`, …) that
+/// replaces the one `-v` / `-q` pick.
+pub const LOG_ENV: &str = "DARP_LOG";
+
+/// The filter for `-q` (`quiet`) or `-v` repeated `verbose` times: errors only, notes and
+/// warnings (the default), engine commands, or everything.
+pub fn level_filter(verbose: u8, quiet: bool) -> &'static str {
+    match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    }
+}
+
+/// Send darp's diagnostics to stderr. `DARP_LOG`, when set, wins over the flags.
+pub fn init(verbose: u8, quiet: bool) {
+    let filter = match std::env::var(LOG_ENV) {
+        Ok(value) if !value.trim().is_empty() => EnvFilter::new(value),
+        _ => EnvFilter::new(level_filter(verbose, quiet)),
+    };
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .event_format(Prefixed)
+        .try_init();
+}
+
+/// Each event on its own line after the prefix darp has always used for it (`warning:`,
+/// `note:`), without timestamps or targets.
+struct Prefixed;
+
+impl<S, N> FormatEvent<S, N> for Prefixed
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => "error:".red(),
+            Level::WARN => "warning:".yellow(),
+            Level::INFO => "note:".yellow(),
+            Level::DEBUG => "debug:".dimmed(),
+            Level::TRACE => "trace:".dimmed(),
+        };
+        write!(writer, "{} ", prefix)?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Logs a command at debug level (`-v`) just before it runs.
+pub trait Logged {
+    fn logged(&mut self) -> &mut Self;
+}
+
+impl Logged for Command {
+    fn logged(&mut self) -> &mut Self {
+        tracing::debug!(target: "darp::exec", "$ {}", command_line(self));
+        self
+    }
+}
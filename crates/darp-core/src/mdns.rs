@@ -0,0 +1,121 @@
+// mdns.rs
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Result, anyhow};
+use colored::*;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+use crate::config;
+
+/// DNS-SD type the services are advertised under, so Bonjour browsers list them too.
+const SERVICE_TYPE: &str = "_http._tcp.local.";
+
+/// How often the portmap is checked for a new deploy.
+const POLL: Duration = Duration::from_secs(1);
+
+/// The `.local` name a service is advertised as: `<service>-<domain>.local`, lower-cased,
+/// with anything but letters, digits and `-` as `-` (mDNS names are a single label).
+pub fn mdns_host(domain: &str, service: &str) -> String {
+    let label: String = format!("{}-{}", service, domain)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("{}.local", label)
+}
+
+/// Every `mdns_host` recorded in `portmap` by a deploy with `mdns` on, sorted.
+pub fn mdns_hosts(portmap: &serde_json::Value) -> Vec<String> {
+    let mut hosts: Vec<String> = portmap
+        .as_object()
+        .into_iter()
+        .flat_map(|domains| domains.values())
+        .flat_map(|groups| groups.as_object().into_iter().flat_map(|g| g.values()))
+        .flat_map(|services| services.as_object().into_iter().flat_map(|s| s.values()))
+        .filter_map(|entry| entry.get("mdns_host").and_then(|h| h.as_str()))
+        .map(str::to_string)
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Answer mDNS queries for the `.local` names in the portmap at `portmap_path` with this
+/// machine's addresses, until `stop` is set. Each name is also announced as an `_http._tcp`
+/// service on port 80, where the reverse proxy answers for it. A deploy that changes the
+/// names is picked up without a restart.
+pub fn advertise(portmap_path: &Path, stop: &AtomicBool) -> Result<()> {
+    let daemon =
+        ServiceDaemon::new().map_err(|e| anyhow!("could not start the mDNS responder: {}", e))?;
+
+    // Host name → the registered service's full name.
+    let mut registered: BTreeMap<String, String> = BTreeMap::new();
+    let mut last_modified = None;
+    while !stop.load(Ordering::Relaxed) {
+        let current = modified(portmap_path);
+        if current != last_modified {
+            last_modified = current;
+            let portmap: serde_json::Value =
+                config::read_json(portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+            let hosts = mdns_hosts(&portmap);
+
+            let gone: Vec<String> = registered
+                .keys()
+                .filter(|h| !hosts.contains(h))
+                .cloned()
+                .collect();
+            for host in gone {
+                if let Some(fullname) = registered.remove(&host) {
+                    let _ = daemon.unregister(&fullname);
+                    println!("Stopped advertising {}", host);
+                }
+            }
+            for host in hosts {
+                if registered.contains_key(&host) {
+                    continue;
+                }
+                let instance = host.trim_end_matches(".local");
+                let info = ServiceInfo::new(
+                    SERVICE_TYPE,
+                    instance,
+                    &format!("{}.", host),
+                    "",
+                    80,
+                    [("path", "/")].as_slice(),
+                )?
+                .enable_addr_auto();
+                let fullname = info.get_fullname().to_string();
+                daemon.register(info)?;
+                println!("Advertising {}", format!("http://{}", host).green());
+                registered.insert(host, fullname);
+            }
+            if registered.is_empty() {
+                println!(
+                    "Nothing to advertise; turn mdns on with 'darp config set mdns true', then run 'darp deploy'."
+                );
+            }
+        }
+        std::thread::sleep(POLL);
+    }
+
+    for fullname in registered.values() {
+        if let Ok(done) = daemon.unregister(fullname) {
+            let _ = done.recv_timeout(Duration::from_secs(1));
+        }
+    }
+    let _ = daemon.shutdown();
+    Ok(())
+}
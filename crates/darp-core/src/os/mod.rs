@@ -0,0 +1,486 @@
+use crate::config::{Config, DarpPaths, Remote};
+use crate::engine::EngineKind;
+use crate::logging::Logged;
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const NGINX_CONF: &str = include_str!("../../assets/nginx.conf");
+
+/// Environment variable that, when set to anything but `0`, `false` or `no`, turns on
+/// non-interactive mode like `--yes`.
+pub const NONINTERACTIVE_ENV: &str = "DARP_NONINTERACTIVE";
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Turn on non-interactive mode for the rest of the run (`--yes`).
+pub fn set_assume_yes(yes: bool) {
+    if yes {
+        ASSUME_YES.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether `value` of `DARP_NONINTERACTIVE` turns non-interactive mode on.
+pub fn truthy_env_value(value: &str) -> bool {
+    !matches!(
+        value.trim().to_lowercase().as_str(),
+        "" | "0" | "false" | "no"
+    )
+}
+
+/// Non-interactive mode: questions take their default answer and sudo fails instead of
+/// asking for a password, so scripts can drive darp unattended.
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+        || std::env::var(NONINTERACTIVE_ENV).is_ok_and(|v| truthy_env_value(&v))
+}
+
+pub const HOSTS_HEADER: &str = "# --- DARP HOSTS START ---";
+pub const HOSTS_FOOTER: &str = "# --- DARP HOSTS END ---";
+
+/// Parse an existing hosts file, replace the DARP block with new entries,
+/// and return the full new file content. `0.0.0.0` entries are written as 127.0.0.1;
+/// any other address is kept.
+pub fn build_hosts_content(current_raw: &str, hosts_container_lines: &[String]) -> String {
+    let current = current_raw.replace("\r\n", "\n");
+
+    let (before, after) = if let Some(s) = current.find(HOSTS_HEADER) {
+        if let Some(e) = current[s..].find(HOSTS_FOOTER) {
+            let end = s + e + HOSTS_FOOTER.len();
+            (
+                current[..s].trim_end_matches('\n').to_string(),
+                current[end..].trim_start_matches('\n').to_string(),
+            )
+        } else {
+            (current.trim_end_matches('\n').to_string(), String::new())
+        }
+    } else {
+        (current.trim_end_matches('\n').to_string(), String::new())
+    };
+
+    let mut block = String::new();
+    block.push_str(HOSTS_HEADER);
+    block.push('\n');
+    for line in hosts_container_lines {
+        let parts: Vec<_> = line.split_whitespace().collect();
+        if parts.len() >= 2 {
+            // 0.0.0.0 is the containers' view; the host reaches the proxy on loopback.
+            let ip = if parts[0] == "0.0.0.0" {
+                "127.0.0.1"
+            } else {
+                parts[0]
+            };
+            block.push_str(&format!("{}   {}\n", ip, parts[1]));
+        }
+    }
+    block.push_str(HOSTS_FOOTER);
+    block.push('\n');
+
+    let mut new_contents = String::new();
+    if !before.is_empty() {
+        new_contents.push_str(before.trim_end_matches('\n'));
+        new_contents.push('\n');
+    }
+    new_contents.push('\n');
+    new_contents.push_str(block.trim_end_matches('\n'));
+    new_contents.push('\n');
+    if !after.is_empty() {
+        new_contents.push('\n');
+        new_contents.push_str(after.trim_start_matches('\n'));
+        new_contents.push('\n');
+    }
+
+    new_contents
+}
+
+/// The hostnames in a hosts file's DARP block.
+pub fn darp_block_hostnames(current_raw: &str) -> Vec<String> {
+    let Some((_, block)) = current_raw.split_once(HOSTS_HEADER) else {
+        return Vec::new();
+    };
+    let block = block.split_once(HOSTS_FOOTER).map_or(block, |(b, _)| b);
+    block
+        .lines()
+        .filter_map(|l| l.split_whitespace().nth(1))
+        .map(String::from)
+        .collect()
+}
+
+/// An environment darp can run in that isn't the developer's own machine: the container
+/// engine it talks to (through a mounted socket or a remote host) lives outside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    Codespaces,
+    Devcontainer,
+    Container,
+}
+
+impl Sandbox {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Sandbox::Codespaces => "a GitHub Codespace",
+            Sandbox::Devcontainer => "a dev container",
+            Sandbox::Container => "a container",
+        }
+    }
+}
+
+/// What doesn't work, or works differently, when darp runs inside a `Sandbox`:
+///
+/// - `/etc/resolver/test` is never consulted by the host's browser, and `sudo` often
+///   doesn't exist, so `darp install` skips it.
+/// - Published ports (the reverse proxy's 80, service ports) open on the engine's host,
+///   not on the sandbox's localhost, so `urls_in_hosts` points `.test` names at the host
+///   gateway instead of 0.0.0.0.
+/// - Volume paths are passed to the engine as-is, so they must exist at the same path
+///   on the engine's host (true for bind-mounted workspaces, not for sandbox-only files).
+pub const SANDBOX_LIMITATIONS: &[&str] = &[
+    "/etc/resolver is skipped; reach .test names with urls_in_hosts or from the host",
+    "ports are published on the engine's host, reached from here through the host gateway",
+    "volume paths must exist at the same path on the engine's host",
+];
+
+/// Detect whether darp is running inside a container. `env` and `exists` are injected
+/// so detection can be tested without the real environment.
+pub fn detect_sandbox_with(
+    env: impl Fn(&str) -> Option<String>,
+    exists: impl Fn(&str) -> bool,
+) -> Option<Sandbox> {
+    let is_true = |name: &str| env(name).is_some_and(|v| v == "true");
+    if is_true("CODESPACES") {
+        Some(Sandbox::Codespaces)
+    } else if is_true("REMOTE_CONTAINERS") || env("DEVCONTAINER").is_some() {
+        Some(Sandbox::Devcontainer)
+    } else if exists("/.dockerenv") || exists("/run/.containerenv") {
+        Some(Sandbox::Container)
+    } else {
+        None
+    }
+}
+
+pub fn detect_sandbox() -> Option<Sandbox> {
+    detect_sandbox_with(
+        |name| std::env::var(name).ok(),
+        |path| std::path::Path::new(path).exists(),
+    )
+}
+
+/// Nameserver `/etc/resolver/test` should point at: the remote server's dnsmasq when
+/// `remote.resolver` is on, else the local one.
+pub fn resolver_nameserver(config: &Config) -> String {
+    match &config.remote {
+        Some(remote) if remote.resolver == Some(true) => remote.resolved_address(),
+        _ => "127.0.0.1".to_string(),
+    }
+}
+
+/// The program (and leading arguments) that opens a URL in the default browser. Under
+/// WSL the Windows side's browser is the one the developer looks at.
+pub fn url_opener(wsl: bool) -> (&'static str, &'static [&'static str]) {
+    if wsl || cfg!(windows) {
+        ("cmd.exe", &["/c", "start", ""])
+    } else if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else {
+        ("xdg-open", &[])
+    }
+}
+
+pub fn open_url(url: &str, wsl: bool) -> Result<()> {
+    let (program, args) = url_opener(wsl);
+    let status = Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status()
+        .map_err(|e| anyhow!("failed to run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(anyhow!("{} could not open {}", program, url));
+    }
+    Ok(())
+}
+
+pub struct OsIntegration<'a> {
+    paths: &'a DarpPaths,
+    resolver_file: &'static str,
+    nameserver: String,
+    remote: Option<Remote>,
+    pub sandbox: Option<Sandbox>,
+    /// Run sudo with `-n`; see `assume_yes`.
+    pub non_interactive: bool,
+}
+
+impl<'a> OsIntegration<'a> {
+    pub fn new(paths: &'a DarpPaths, config: &Config, _engine_kind: &'a EngineKind) -> Self {
+        // In your Python version this is hard-coded to /etc/resolver/test
+        Self {
+            paths,
+            resolver_file: "/etc/resolver/test",
+            nameserver: resolver_nameserver(config).to_string(),
+            remote: config.remote.clone(),
+            sandbox: detect_sandbox(),
+            non_interactive: assume_yes(),
+        }
+    }
+
+    /// `sudo`, told not to prompt in non-interactive mode.
+    #[cfg(unix)]
+    fn sudo(&self) -> Command {
+        let mut cmd = Command::new("sudo");
+        if self.non_interactive {
+            cmd.arg("-n");
+        }
+        cmd
+    }
+
+    /// The error for a sudo command that failed while doing `what`.
+    #[cfg(unix)]
+    fn sudo_failed(&self, what: &str) -> anyhow::Error {
+        if self.non_interactive {
+            anyhow!(
+                "could not {} without a sudo password (non-interactive mode); run it interactively or allow passwordless sudo",
+                what
+            )
+        } else {
+            anyhow!("could not {} with sudo", what)
+        }
+    }
+
+    pub fn init_resolver(&self) -> Result<()> {
+        if let Some(sandbox) = self.sandbox {
+            println!(
+                "Skipping {}: darp is running inside {}",
+                self.resolver_file,
+                sandbox.describe()
+            );
+            return Ok(());
+        }
+        if let Some(remote) = self.remote.as_ref().filter(|r| r.resolver != Some(true)) {
+            println!(
+                "Skipping {}: the engine runs on {} and remote.resolver is off",
+                self.resolver_file, remote.host
+            );
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        {
+            let what = format!("write {}", self.resolver_file);
+            let created = self
+                .sudo()
+                .arg("mkdir")
+                .arg("-p")
+                .arg("/etc/resolver")
+                .logged()
+                .status()?;
+            if !created.success() {
+                return Err(self.sudo_failed(&what));
+            }
+
+            let mut child = self
+                .sudo()
+                .arg("tee")
+                .arg(self.resolver_file)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::inherit())
+                .logged()
+                .spawn()?;
+
+            {
+                let stdin = child
+                    .stdin
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("Could not open stdin"))?;
+                stdin.write_all(format!("nameserver {}\n", self.nameserver).as_bytes())?;
+            }
+
+            if !child.wait()?.success() {
+                return Err(self.sudo_failed(&what));
+            }
+            println!("\n{} created", self.resolver_file.green());
+            Ok(())
+        }
+
+        #[cfg(not(unix))]
+        {
+            Err(anyhow!(
+                "resolver initialization is currently implemented only on Unix-like systems"
+            ))
+        }
+    }
+
+    pub fn ensure_dnsmasq_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.paths.dnsmasq_dir)?;
+        Ok(())
+    }
+
+    pub fn copy_nginx_conf(&self) -> Result<()> {
+        // Write embedded nginx.conf to $DARP_ROOT/nginx.conf
+        if let Some(parent) = self.paths.nginx_conf_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&self.paths.nginx_conf_path, NGINX_CONF)?;
+        println!(
+            "nginx.conf written to {}",
+            self.paths.nginx_conf_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// What dnsmasq.d/test.conf should hold. On a remote server, dnsmasq answers other
+    /// machines, so it hands out the server's own address.
+    pub fn test_conf(&self) -> String {
+        let address = self
+            .remote
+            .as_ref()
+            .map_or_else(|| "127.0.0.1".to_string(), Remote::resolved_address);
+        format!("address=/.test/{}\n", address)
+    }
+
+    pub fn write_test_conf(&self) -> Result<()> {
+        let test_conf = self.paths.dnsmasq_dir.join("test.conf");
+        let mut file = fs::File::create(&test_conf)?;
+        file.write_all(self.test_conf().as_bytes())?;
+        println!("{} created", test_conf.display().to_string().green());
+        Ok(())
+    }
+
+    /// Rewrite dnsmasq.d/test.conf when it no longer matches the config (e.g. `remote`
+    /// was set or removed since install), returning whether it changed. darp-masq only
+    /// picks the change up once restarted.
+    pub fn sync_test_conf(&self) -> Result<bool> {
+        let test_conf = self.paths.dnsmasq_dir.join("test.conf");
+        let wanted = self.test_conf();
+        if fs::read_to_string(&test_conf).is_ok_and(|current| current == wanted) {
+            return Ok(false);
+        }
+        fs::create_dir_all(&self.paths.dnsmasq_dir)?;
+        fs::write(&test_conf, wanted)?;
+        Ok(true)
+    }
+
+    pub fn sync_system_hosts(&self, hosts_container_lines: &[String]) -> Result<()> {
+        #[cfg(unix)]
+        {
+            let hosts_path = "/etc/hosts";
+
+            let output = self
+                .sudo()
+                .arg("cat")
+                .arg(hosts_path)
+                .logged()
+                .output()
+                .map_err(|e| anyhow!("unable to read {} via sudo: {}", hosts_path, e))?;
+            if !output.status.success() {
+                return Err(self.sudo_failed(&format!("read {}", hosts_path)));
+            }
+
+            let current = String::from_utf8_lossy(&output.stdout).into_owned();
+            let new_contents = build_hosts_content(&current, hosts_container_lines);
+
+            let mut child = self
+                .sudo()
+                .arg("tee")
+                .arg(hosts_path)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .logged()
+                .spawn()?;
+
+            {
+                let stdin = child
+                    .stdin
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("Could not open stdin"))?;
+                stdin.write_all(new_contents.as_bytes())?;
+            }
+
+            if !child.wait()?.success() {
+                return Err(self.sudo_failed(&format!("write {}", hosts_path)));
+            }
+            println!(
+                "{} updated with Darp URL mappings (127.0.0.1).",
+                hosts_path.green()
+            );
+            Ok(())
+        }
+
+        #[cfg(not(unix))]
+        {
+            Err(anyhow!(
+                "/etc/hosts sync is only implemented for Unix-like systems right now"
+            ))
+        }
+    }
+
+    pub fn sync_windows_hosts(&self, hosts_container_lines: &[String]) -> Result<()> {
+        let hosts_path = "/mnt/c/Windows/System32/drivers/etc/hosts";
+
+        let current = fs::read_to_string(hosts_path).map_err(|e| {
+            anyhow!(
+                "Unable to read Windows hosts file at {}: {}. Ensure WSL is running as Administrator.",
+                hosts_path,
+                e
+            )
+        })?;
+
+        let new_contents = build_hosts_content(&current, hosts_container_lines);
+
+        fs::write(hosts_path, new_contents.as_bytes()).map_err(|e| {
+            anyhow!(
+                "Unable to write Windows hosts file at {}: {}. Ensure WSL is running as Administrator.",
+                hosts_path,
+                e
+            )
+        })?;
+
+        println!(
+            "{} updated with Darp URL mappings (127.0.0.1).",
+            hosts_path.green()
+        );
+        Ok(())
+    }
+
+    pub fn uninstall(&self) -> Result<()> {
+        if self.sandbox.is_some() {
+            // install never created the resolver file here.
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        {
+            // Remove resolver file; leave Darp config directory intact.
+            let removed = self
+                .sudo()
+                .arg("rm")
+                .arg("-f")
+                .arg(self.resolver_file)
+                .logged()
+                .status()
+                .map_err(|e| anyhow!("failed to remove resolver file: {}", e))?;
+            if !removed.success() {
+                return Err(self.sudo_failed(&format!("remove {}", self.resolver_file)));
+            }
+            println!("{} removed", self.resolver_file.green());
+            println!(
+                "Darp resolver removed. Config and data under $DARP_ROOT were left untouched."
+            );
+            Ok(())
+        }
+
+        #[cfg(not(unix))]
+        {
+            Err(anyhow!(
+                "Uninstall is currently implemented only on Unix-like systems"
+            ))
+        }
+    }
+}
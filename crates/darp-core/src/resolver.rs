@@ -0,0 +1,195 @@
+// resolver.rs
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A configured domain as `locate` sees it: its folder and its named groups (every
+/// other subfolder is a service of the `.` group).
+#[derive(Debug, Clone)]
+pub struct DomainDir<'a> {
+    pub name: &'a str,
+    pub location: PathBuf,
+    pub groups: Vec<&'a str>,
+}
+
+/// Where a directory sits relative to the configured domain folders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placement<'a> {
+    /// In a service folder (`dir`), or anywhere below it: a direct subfolder of the
+    /// domain (group `.`) or of a named group's folder.
+    Service {
+        domain: &'a str,
+        group: String,
+        service: String,
+        dir: PathBuf,
+    },
+    /// The domain folder itself.
+    DomainRoot { domain: &'a str },
+    /// A named group's folder itself.
+    GroupRoot { domain: &'a str, group: String },
+    /// Not inside any domain folder.
+    Outside,
+}
+
+/// `path` with `.` and `..` components and trailing separators dropped, without touching
+/// the filesystem (symlinks are kept as written).
+pub fn lexical(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// The physical directory `path` names: symlinks resolved, or `lexical(path)` when it
+/// doesn't exist.
+pub fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| lexical(path))
+}
+
+/// Whether two existing paths are the same file, by device and inode where available.
+fn same_file(a: &Path, b: &Path) -> bool {
+    let (Ok(a), Ok(b)) = (fs::metadata(a), fs::metadata(b)) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        a.dev() == b.dev() && a.ino() == b.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        a.is_dir() == b.is_dir()
+    }
+}
+
+/// Whether `a` and `b` name the same directory, however they're spelled: through
+/// symlinks, with trailing slashes, or in another case on a case-insensitive filesystem.
+pub fn same_dir(a: &Path, b: &Path) -> bool {
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return true;
+    }
+    // macOS and Windows hand back whichever spelling was asked for; only the filesystem
+    // can say whether two spellings are one folder.
+    a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase() && same_file(&a, &b)
+}
+
+/// The working directory as the user sees it. Shells keep the path as typed, symlinks
+/// included, in `$PWD`, while `getcwd` resolves them; a service folder symlinked into a
+/// domain only maps through the former.
+pub fn current_dir() -> std::io::Result<PathBuf> {
+    let physical = std::env::current_dir()?;
+    Ok(std::env::var_os("PWD")
+        .map(PathBuf::from)
+        .filter(|pwd| pwd.is_absolute() && same_dir(pwd, &physical))
+        .map(|pwd| lexical(&pwd))
+        .unwrap_or(physical))
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    Some(path.file_name()?.to_string_lossy().to_string())
+}
+
+/// Where `dir` sits relative to `domain`: found by walking up from `dir` until an
+/// ancestor is the domain folder.
+fn place<'a>(dir: &Path, domain: &DomainDir<'a>) -> Option<Placement<'a>> {
+    let root = dir.ancestors().find(|a| same_dir(a, &domain.location))?;
+    let mut below = dir.strip_prefix(root).ok()?.components();
+    let Some(first) = below.next() else {
+        return Some(Placement::DomainRoot {
+            domain: domain.name,
+        });
+    };
+    let first = first.as_os_str().to_string_lossy().to_string();
+    if !domain.groups.contains(&first.as_str()) {
+        return Some(Placement::Service {
+            domain: domain.name,
+            group: ".".to_string(),
+            dir: root.join(&first),
+            service: first,
+        });
+    }
+    Some(match below.next() {
+        Some(service) => {
+            let service = service.as_os_str().to_string_lossy().to_string();
+            Placement::Service {
+                domain: domain.name,
+                dir: root.join(&first).join(&service),
+                group: first,
+                service,
+            }
+        }
+        None => Placement::GroupRoot {
+            domain: domain.name,
+            group: first,
+        },
+    })
+}
+
+/// Where `dir` sits among `domains`, so darp commands work from a service folder or any
+/// folder below it. `dir` is matched by its path as given, then by its physical path,
+/// then by looking for a symlink in a domain (or group) folder that points at it or one
+/// of its ancestors.
+pub fn locate<'a>(dir: &Path, domains: &[DomainDir<'a>]) -> Placement<'a> {
+    let mut spellings = vec![lexical(dir)];
+    let physical = normalize(dir);
+    if physical != spellings[0] {
+        spellings.push(physical.clone());
+    }
+
+    for dir in &spellings {
+        for domain in domains {
+            if let Some(placement) = place(dir, domain) {
+                return placement;
+            }
+        }
+    }
+    domains
+        .iter()
+        .find_map(|domain| find_symlinked(&physical, domain))
+        .unwrap_or(Placement::Outside)
+}
+
+/// A service folder of `domain` that is a symlink to `physical` or one of its ancestors.
+fn find_symlinked<'a>(physical: &Path, domain: &DomainDir<'a>) -> Option<Placement<'a>> {
+    let entries = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect()
+    };
+    let points_here = |dir: &Path| physical.starts_with(normalize(dir));
+    for child in entries(&domain.location) {
+        let name = file_name(&child)?;
+        if domain.groups.contains(&name.as_str()) {
+            for service_dir in entries(&child) {
+                if points_here(&service_dir) {
+                    return Some(Placement::Service {
+                        domain: domain.name,
+                        group: name,
+                        service: file_name(&service_dir)?,
+                        dir: service_dir,
+                    });
+                }
+            }
+        } else if points_here(&child) {
+            return Some(Placement::Service {
+                domain: domain.name,
+                group: ".".to_string(),
+                service: name,
+                dir: child,
+            });
+        }
+    }
+    None
+}
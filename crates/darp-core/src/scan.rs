@@ -0,0 +1,125 @@
+// scan.rs
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Bumped whenever the manifest's layout changes; an older manifest is ignored.
+const MANIFEST_VERSION: u32 = 1;
+
+/// A folder modified this recently isn't cached: on file systems with coarse timestamps a
+/// folder added a moment later could leave its mtime unchanged.
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// One scanned folder: its mtime when scanned and the folders in it, in `read_dir` order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScannedDir {
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    pub folders: Vec<String>,
+}
+
+/// What the last deploy found in each domain and group folder (`scan_manifest.json`).
+/// Adding or removing a service folder changes its parent's mtime, so a parent whose
+/// mtime still matches is known to hold the same folders without listing it again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanManifest {
+    version: u32,
+    dirs: BTreeMap<String, ScannedDir>,
+    #[serde(skip)]
+    seen: HashSet<String>,
+}
+
+/// `dir`'s mtime, or None when it doesn't exist.
+fn mtime(dir: &Path) -> Result<Option<Duration>> {
+    match fs::metadata(dir) {
+        Ok(metadata) => Ok(metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow!("could not read {}: {}", dir.display(), e)),
+    }
+}
+
+/// The folders directly in `dir`, in `read_dir` order.
+fn list_folders(dir: &Path) -> Result<Vec<String>> {
+    let read_error = |e: std::io::Error| anyhow!("could not read {}: {}", dir.display(), e);
+    let mut folders = Vec::new();
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        if entry.file_type().map_err(read_error)?.is_dir() {
+            folders.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    Ok(folders)
+}
+
+impl ScanManifest {
+    /// The manifest at `path`, or an empty one when it's missing, unreadable or from
+    /// another version of darp.
+    pub fn load(path: &Path) -> Self {
+        match config::read_json::<ScanManifest>(path) {
+            Ok(manifest) if manifest.version == MANIFEST_VERSION => manifest,
+            _ => Self::default(),
+        }
+    }
+
+    /// Write the manifest to `path`, keeping only the folders looked up since it was
+    /// loaded so removed domains and groups drop out.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.version = MANIFEST_VERSION;
+        let seen = &self.seen;
+        self.dirs.retain(|dir, _| seen.contains(dir));
+        config::write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// The folders directly in `dir`: from the manifest when `dir`'s mtime hasn't changed
+    /// since it was listed, otherwise listed again and remembered. A missing `dir` has
+    /// none; one that can't be read is an error, so no service goes missing unnoticed.
+    pub fn folders(&mut self, dir: &Path) -> Result<Vec<String>> {
+        let key = dir.to_string_lossy().to_string();
+        self.seen.insert(key.clone());
+        let Some(modified) = mtime(dir)? else {
+            self.dirs.remove(&key);
+            return Ok(Vec::new());
+        };
+        if let Some(cached) = self.dirs.get(&key) {
+            if cached.mtime_secs == modified.as_secs()
+                && cached.mtime_nanos == modified.subsec_nanos()
+            {
+                return Ok(cached.folders.clone());
+            }
+        }
+
+        let folders = list_folders(dir)?;
+        let settled = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .is_ok_and(|now| now.saturating_sub(modified) >= SETTLE);
+        if settled {
+            self.dirs.insert(
+                key,
+                ScannedDir {
+                    mtime_secs: modified.as_secs(),
+                    mtime_nanos: modified.subsec_nanos(),
+                    folders: folders.clone(),
+                },
+            );
+        } else {
+            self.dirs.remove(&key);
+        }
+        Ok(folders)
+    }
+
+    /// The cached entry for `dir`, if any.
+    pub fn get(&self, dir: &Path) -> Option<&ScannedDir> {
+        self.dirs.get(dir.to_string_lossy().as_ref())
+    }
+}
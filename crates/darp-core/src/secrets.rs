@@ -0,0 +1,105 @@
+// secrets.rs
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Scope key for secrets that apply to every domain.
+pub const GLOBAL_SCOPE: &str = "*";
+
+/// Secret values kept out of config.json, in a file only the owner can read.
+///
+/// Layout: `{ "<scope>": { "NAME": "value" } }`, where scope is `*` (every domain) or a
+/// domain name. Domain-scoped values win over global ones with the same name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretStore {
+    scopes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl SecretStore {
+    /// Load the store, treating a missing file as empty.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data)
+            .map_err(|e| anyhow!("failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Write the store with owner-only permissions (0600 on Unix).
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(self)?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        // `mode` only applies on creation; tighten a file created some other way too.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        std::io::Write::write_all(&mut file, &data)?;
+        Ok(())
+    }
+
+    pub fn set(&mut self, scope: &str, name: &str, value: &str) {
+        self.scopes
+            .entry(scope.to_string())
+            .or_default()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    pub fn rm(&mut self, scope: &str, name: &str) -> Result<()> {
+        let entries = self
+            .scopes
+            .get_mut(scope)
+            .ok_or_else(|| anyhow!("No secrets stored for scope '{}'", scope))?;
+        if entries.remove(name).is_none() {
+            return Err(anyhow!(
+                "secret '{}' does not exist in scope '{}'",
+                name,
+                scope
+            ));
+        }
+        if entries.is_empty() {
+            self.scopes.remove(scope);
+        }
+        Ok(())
+    }
+
+    /// `(scope, name)` pairs, without values.
+    pub fn list(&self) -> Vec<(&str, &str)> {
+        self.scopes
+            .iter()
+            .flat_map(|(scope, entries)| {
+                entries
+                    .keys()
+                    .map(move |name| (scope.as_str(), name.as_str()))
+            })
+            .collect()
+    }
+
+    /// Secrets visible to containers in `domain`: global ones overlaid with the domain's.
+    pub fn for_domain(&self, domain: &str) -> BTreeMap<String, String> {
+        let mut out = BTreeMap::new();
+        for scope in [GLOBAL_SCOPE, domain] {
+            if let Some(entries) = self.scopes.get(scope) {
+                out.extend(entries.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,81 @@
+// services_index.rs
+
+use std::collections::HashSet;
+
+use crate::config::{self, DarpPaths};
+use crate::engine::{self, Engine};
+
+/// Version of the `services.json` layout served at `http://darp.test/services.json`.
+pub const SERVICES_INDEX_FORMAT_VERSION: u32 = 1;
+
+/// Build the service switcher index from a portmap: one entry per service with its
+/// URL, connection type, proxy port, and whether its container (named from the
+/// `container_name` template) is in `running`, plus its `direct_url` when it has one.
+pub fn build_services_index(
+    portmap: &serde_json::Value,
+    running: &HashSet<String>,
+    container_name: &str,
+) -> serde_json::Value {
+    let mut services = Vec::new();
+    if let Some(domains) = portmap.as_object() {
+        for (domain_name, groups) in domains {
+            let Some(groups) = groups.as_object() else {
+                continue;
+            };
+            for (group_name, entries) in groups {
+                let Some(entries) = entries.as_object() else {
+                    continue;
+                };
+                for (service_name, entry) in entries {
+                    let port =
+                        config::portmap_proxy_port(portmap, domain_name, group_name, service_name);
+                    let conn_type = entry.get("type").and_then(|t| t.as_str()).unwrap_or("http");
+                    let host = config::portmap_host(entry, domain_name, service_name);
+                    let url = match conn_type {
+                        "tcp" => format!("tcp://{}:{}", host, config::portmap_tcp_port(entry)),
+                        "websocket" => format!("ws://{}", host),
+                        _ => format!("http://{}", host),
+                    };
+                    let mut service = serde_json::json!({
+                        "domain": domain_name,
+                        "group": group_name,
+                        "service": service_name,
+                        "url": url,
+                        "type": conn_type,
+                        "port": port,
+                        "running": running.contains(&engine::container_name(container_name, domain_name, service_name)),
+                    });
+                    if let Some(direct_url) = entry.get("direct_url") {
+                        service["direct_url"] = direct_url.clone();
+                    }
+                    services.push(service);
+                }
+            }
+        }
+    }
+
+    serde_json::json!({
+        "format_version": SERVICES_INDEX_FORMAT_VERSION,
+        "services": services,
+    })
+}
+
+/// Rewrite `~/.darp/www/services.json` from the current portmap.
+pub fn write_services_index(
+    paths: &DarpPaths,
+    engine: &Engine,
+    running: &HashSet<String>,
+) -> anyhow::Result<()> {
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    std::fs::create_dir_all(&paths.www_dir)?;
+    std::fs::write(
+        &paths.services_index_path,
+        serde_json::to_vec_pretty(&build_services_index(
+            &portmap,
+            running,
+            &engine.container_name,
+        ))?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,96 @@
+// timings.rs
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PHASES: Mutex<Vec<(String, Duration)>> = Mutex::new(Vec::new());
+
+/// Start recording phases for the rest of the run (`--timings`).
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// A phase being timed; it's recorded when dropped. Does nothing unless timings are on.
+pub struct Phase {
+    name: Option<String>,
+    start: Instant,
+}
+
+/// Time `name` (config load, engine readiness, scan, …) until the returned guard drops.
+pub fn phase(name: &str) -> Phase {
+    Phase {
+        name: enabled().then(|| name.to_string()),
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        if let Some(name) = self.name.take() {
+            if let Ok(mut phases) = PHASES.lock() {
+                phases.push((name, self.start.elapsed()));
+            }
+        }
+    }
+}
+
+/// Every phase recorded so far, in the order they finished.
+pub fn recorded() -> Vec<(String, Duration)> {
+    PHASES.lock().map(|p| p.clone()).unwrap_or_default()
+}
+
+fn format_duration(d: Duration) -> String {
+    if d.as_secs() >= 1 {
+        format!("{:.2}s", d.as_secs_f64())
+    } else {
+        format!("{}ms", d.as_millis())
+    }
+}
+
+/// The `--timings` report: each phase name once, in order of first appearance, with its
+/// total time and how often it ran when more than once, then the whole run's time.
+/// Phases can nest, so they needn't add up to the total.
+pub fn render(phases: &[(String, Duration)], total: Duration) -> String {
+    let mut rows: Vec<(&str, Duration, usize)> = Vec::new();
+    for (name, took) in phases {
+        match rows.iter_mut().find(|(n, _, _)| n == name) {
+            Some(row) => {
+                row.1 += *took;
+                row.2 += 1;
+            }
+            None => rows.push((name, *took, 1)),
+        }
+    }
+    let width = rows
+        .iter()
+        .map(|(n, _, _)| n.len())
+        .chain(["total".len()])
+        .max()
+        .unwrap_or_default();
+    let mut out = String::from("timings:\n");
+    for (name, took, count) in rows {
+        let times = if count > 1 {
+            format!("  ({}×)", count)
+        } else {
+            String::new()
+        };
+        out.push_str(&format!(
+            "  {:<width$}  {:>8}{}\n",
+            name,
+            format_duration(took),
+            times
+        ));
+    }
+    out.push_str(&format!(
+        "  {:<width$}  {:>8}\n",
+        "total",
+        format_duration(total)
+    ));
+    out
+}
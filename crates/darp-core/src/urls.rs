@@ -0,0 +1,308 @@
+// urls.rs
+
+use std::net::{IpAddr, UdpSocket};
+
+use anyhow::Result;
+use qrcode::QrCode;
+use qrcode::render::unicode::Dense1x2;
+
+use crate::config::{self, Config};
+
+/// A service as listed in an exported handbook.
+struct Row {
+    service: String,
+    url: String,
+    description: Option<String>,
+}
+
+/// `(group, rows)` pairs for one domain.
+type GroupRows = Vec<(String, Vec<Row>)>;
+
+/// The proxy port and URL of a portmap entry (a bare number in legacy portmaps, or
+/// `{"port": N, "type": "..."}`).
+pub fn entry_url(
+    domain_name: &str,
+    service_name: &str,
+    entry: &serde_json::Value,
+) -> (u64, String) {
+    let port = entry
+        .get("port")
+        .and_then(|p| p.as_u64())
+        .or_else(|| entry.as_u64())
+        .unwrap_or(0);
+    let host = config::portmap_host(entry, domain_name, service_name);
+    let url = match entry.get("type").and_then(|t| t.as_str()) {
+        Some("tcp") => format!("tcp://{}:{}", host, config::portmap_tcp_port(entry)),
+        Some("websocket") => format!("ws://{}", host),
+        _ => format!("http://{}", host),
+    };
+    (port, url)
+}
+
+/// The URL of a service published on `port` of `host` (`localhost`, or a remote
+/// server's address), which works without DNS.
+pub fn direct_url(connection_type: &str, host: &str, port: u16) -> String {
+    match connection_type {
+        "tcp" => format!("tcp://{}:{}", host, port),
+        "websocket" => format!("ws://{}:{}", host, port),
+        _ => format!("http://{}:{}", host, port),
+    }
+}
+
+/// Services from the portmap grouped by domain, then group (`.` first), each with its
+/// URL and the `description` from config.
+fn handbook_rows(portmap: &serde_json::Value, config: &Config) -> Vec<(String, GroupRows)> {
+    let mut domains = Vec::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        let mut group_entries: Vec<_> = groups.as_object().into_iter().flatten().collect();
+        group_entries.sort_by(|(a, _), (b, _)| match (a.as_str(), b.as_str()) {
+            (".", _) => std::cmp::Ordering::Less,
+            (_, ".") => std::cmp::Ordering::Greater,
+            _ => a.cmp(b),
+        });
+
+        let mut out_groups = Vec::new();
+        for (group_name, services) in group_entries {
+            let mut rows: Vec<Row> = services
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(service_name, entry)| {
+                    let (_, url) = entry_url(domain_name, service_name, entry);
+                    let description = config
+                        .domains
+                        .as_ref()
+                        .and_then(|d| d.get(domain_name))
+                        .and_then(|d| d.groups.as_ref())
+                        .and_then(|g| g.get(group_name))
+                        .and_then(|g| g.services.as_ref())
+                        .and_then(|s| s.get(service_name))
+                        .and_then(|s| s.description.clone());
+                    Row {
+                        service: service_name.clone(),
+                        url,
+                        description,
+                    }
+                })
+                .collect();
+            rows.sort_by(|a, b| a.service.cmp(&b.service));
+            out_groups.push((group_name.clone(), rows));
+        }
+        domains.push((domain_name.clone(), out_groups));
+    }
+    domains
+}
+
+/// Render every registered service as Markdown: a section per domain (and named group)
+/// with a Service / URL / Description table.
+pub fn render_urls_markdown(portmap: &serde_json::Value, config: &Config) -> String {
+    let cell = |s: &str| s.replace('|', "\\|").replace('\n', " ");
+    let mut out = String::from("# darp services\n");
+    for (domain_name, groups) in handbook_rows(portmap, config) {
+        out.push_str(&format!("\n## {}\n", domain_name));
+        for (group_name, rows) in groups {
+            if group_name != "." {
+                out.push_str(&format!("\n### {}\n", group_name));
+            }
+            out.push_str("\n| Service | URL | Description |\n|---|---|---|\n");
+            for row in rows {
+                out.push_str(&format!(
+                    "| {} | <{}> | {} |\n",
+                    cell(&row.service),
+                    row.url,
+                    cell(row.description.as_deref().unwrap_or_default())
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The same handbook as `render_urls_markdown`, as an HTML fragment.
+pub fn render_urls_html(portmap: &serde_json::Value, config: &Config) -> String {
+    let mut out = String::from("<h1>darp services</h1>\n");
+    for (domain_name, groups) in handbook_rows(portmap, config) {
+        out.push_str(&format!("<h2>{}</h2>\n", html_escape(&domain_name)));
+        for (group_name, rows) in groups {
+            if group_name != "." {
+                out.push_str(&format!("<h3>{}</h3>\n", html_escape(&group_name)));
+            }
+            out.push_str("<table>\n<tr><th>Service</th><th>URL</th><th>Description</th></tr>\n");
+            for row in rows {
+                let url = html_escape(&row.url);
+                // Browsers can't open tcp:// links, so those are listed as text.
+                let link = if row.url.starts_with("tcp://") {
+                    url
+                } else {
+                    format!("<a href=\"{url}\">{url}</a>")
+                };
+                out.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    html_escape(&row.service),
+                    link,
+                    html_escape(row.description.as_deref().unwrap_or_default())
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+    }
+    out
+}
+
+/// `{domain: {service: {url, port}}}` for every service in the portmap, for editor
+/// plugins and scripts, with `aliases` and `custom_urls` for services answering on other
+/// hostnames too, and `direct_url` for those reachable on localhost. Groups are flattened away.
+pub fn render_urls_json(portmap: &serde_json::Value) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        let mut services_out = serde_json::Map::new();
+        for (_, services) in groups.as_object().into_iter().flatten() {
+            for (service_name, entry) in services.as_object().into_iter().flatten() {
+                let (port, url) = entry_url(domain_name, service_name, entry);
+                let mut service = serde_json::json!({ "url": url, "port": port });
+                let aliases = config::portmap_aliases(entry);
+                if !aliases.is_empty() {
+                    service["aliases"] = aliases.into();
+                }
+                let custom_urls = config::portmap_custom_urls(entry);
+                if !custom_urls.is_empty() {
+                    service["custom_urls"] = custom_urls.into();
+                }
+                if let Some(direct_url) = entry.get("direct_url") {
+                    service["direct_url"] = direct_url.clone();
+                }
+                services_out.insert(service_name.clone(), service);
+            }
+        }
+        out.insert(domain_name.clone(), serde_json::Value::Object(services_out));
+    }
+    serde_json::Value::Object(out)
+}
+
+/// One `<url> <port>` line per service, sorted, without colours or headers.
+pub fn render_urls_plain(portmap: &serde_json::Value) -> String {
+    let mut lines: Vec<String> = render_urls_json(portmap)
+        .as_object()
+        .into_iter()
+        .flatten()
+        .flat_map(|(_, services)| services.as_object().into_iter().flatten())
+        .map(|(_, s)| format!("{} {}", s["url"].as_str().unwrap_or_default(), s["port"]))
+        .collect();
+    lines.sort();
+    lines.iter().map(|l| format!("{}\n", l)).collect()
+}
+
+/// One `<host> <direct url>` line per service that has a `localhost` URL, sorted, for
+/// `darp urls --direct` when `.test` names don't resolve.
+pub fn render_urls_direct(portmap: &serde_json::Value) -> String {
+    let mut rows: Vec<(String, &str)> = Vec::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        for (_, services) in groups.as_object().into_iter().flatten() {
+            for (service_name, entry) in services.as_object().into_iter().flatten() {
+                if let Some(direct_url) = entry.get("direct_url").and_then(|u| u.as_str()) {
+                    let host = config::portmap_host(entry, domain_name, service_name);
+                    rows.push((host, direct_url));
+                }
+            }
+        }
+    }
+    rows.sort();
+    let width = rows
+        .iter()
+        .map(|(host, _)| host.len())
+        .max()
+        .unwrap_or_default();
+    rows.iter()
+        .map(|(host, url)| format!("{:<width$}  {}\n", host, url))
+        .collect()
+}
+
+/// `text` as a QR code drawn with half-block characters, two modules per line. Dark and
+/// light are swapped so the code reads as dark-on-light on a dark terminal, which is
+/// what most phone cameras expect; the quiet zone keeps it scannable on a light one too.
+pub fn qr_code(text: &str) -> Result<String> {
+    let code = QrCode::new(text.as_bytes())?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build()
+        + "\n")
+}
+
+/// This machine's address on the local network: the one the OS would send from to reach
+/// the internet. Connecting a UDP socket only picks the route; nothing is sent.
+pub fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
+
+/// A URL another device on the network, like a phone, can open for a portmap entry. The
+/// `.test` name only resolves on this machine, so it's the service's mDNS `.local` name
+/// when a deploy with `mdns` on gave it one, else its direct URL with `localhost`
+/// swapped for `lan_ip`. None for tcp services and when there's neither.
+pub fn phone_url(entry: &serde_json::Value, lan_ip: Option<IpAddr>) -> Option<String> {
+    if entry.get("type").and_then(|t| t.as_str()) == Some("tcp") {
+        return None;
+    }
+    if let Some(host) = entry.get("mdns_host").and_then(|h| h.as_str()) {
+        return Some(format!("http://{}", host));
+    }
+    let direct = entry.get("direct_url").and_then(|u| u.as_str())?;
+    let (host, port) = direct.split_once("://")?.1.rsplit_once(':')?;
+    let host = match host {
+        "localhost" | "127.0.0.1" => lan_ip?.to_string(),
+        _ => host.to_string(),
+    };
+    Some(format!("http://{}:{}", host, port))
+}
+
+/// Each browser-openable service in `portmap` as its URL and the `phone_url` a QR code
+/// under it holds, sorted by URL. tcp services are left out: a phone can't open them.
+/// Services with no `phone_url` are listed after the codes.
+pub fn render_urls_qr(portmap: &serde_json::Value, lan_ip: Option<IpAddr>) -> Result<String> {
+    let mut urls: Vec<(String, Option<String>)> = Vec::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        for (_, services) in groups.as_object().into_iter().flatten() {
+            for (service_name, entry) in services.as_object().into_iter().flatten() {
+                let (_, url) = entry_url(domain_name, service_name, entry);
+                if !url.starts_with("tcp://") {
+                    urls.push((
+                        url.replacen("ws://", "http://", 1),
+                        phone_url(entry, lan_ip),
+                    ));
+                }
+            }
+        }
+    }
+    urls.sort();
+    let mut out = String::new();
+    let mut unreachable = Vec::new();
+    for (url, phone_url) in urls {
+        match phone_url {
+            Some(phone_url) => out.push_str(&format!(
+                "{}  {}\n{}\n",
+                url,
+                phone_url,
+                qr_code(&phone_url)?
+            )),
+            None => unreachable.push(url),
+        }
+    }
+    if !unreachable.is_empty() {
+        out.push_str(&format!(
+            "No address another device can reach for: {}\nTurn on mdns ('darp config set mdns true') or run 'darp deploy' to give them one.\n",
+            unreachable.join(", ")
+        ));
+    }
+    Ok(out)
+}
@@ -0,0 +1,87 @@
+// alias.rs
+
+use anyhow::Result;
+use clap::CommandFactory;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+use crate::cli::Cli;
+pub use darp_core::alias::split_words;
+
+/// Names (and visible aliases) of darp's own subcommands, which aliases can't shadow.
+pub fn builtin_names() -> Vec<String> {
+    Cli::command()
+        .get_subcommands()
+        .flat_map(|c| std::iter::once(c.get_name()).chain(c.get_all_aliases()))
+        .map(|n| n.to_string())
+        .chain(["help".to_string()])
+        .collect()
+}
+
+/// Check an alias before it goes into config; see `darp_core::alias::validate_alias`.
+pub fn validate_alias(name: &str, expansion: &str) -> Result<()> {
+    darp_core::alias::validate_alias(name, expansion, &builtin_names())
+}
+
+/// `args` (argv, program name first) with a leading alias replaced by its expansion;
+/// arguments after it are appended. darp's own subcommands always win over aliases,
+/// and expansions aren't expanded again.
+pub fn expand_args(
+    args: Vec<OsString>,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<OsString>> {
+    let Some(first) = args.get(1).and_then(|a| a.to_str()) else {
+        return Ok(args);
+    };
+    let Some(expansion) = aliases.get(first) else {
+        return Ok(args);
+    };
+    if builtin_names().iter().any(|b| b == first) {
+        return Ok(args);
+    }
+    let mut expanded = vec![args[0].clone()];
+    expanded.extend(split_words(expansion)?.into_iter().map(OsString::from));
+    expanded.extend(args.into_iter().skip(2));
+    Ok(expanded)
+}
+
+/// Whether `darp <first>` could be an alias, so main only reads config when it might be.
+pub fn may_be_alias(first: Option<&OsString>) -> bool {
+    first
+        .and_then(|a| a.to_str())
+        .is_some_and(|a| !a.starts_with('-') && !builtin_names().iter().any(|b| b == a))
+}
+
+/// darp's clap command with a subcommand per alias, for shell completions. An alias
+/// takes the flags and arguments of the subcommand it expands to (`s` → `serve -e dev`
+/// completes like `serve`).
+pub fn command_with_aliases(aliases: &BTreeMap<String, String>) -> clap::Command {
+    let mut cmd = Cli::command();
+    let builtins = builtin_names();
+    for (name, expansion) in aliases {
+        if builtins.contains(name) {
+            continue;
+        }
+        let words = split_words(expansion).unwrap_or_default();
+        let mut target: Option<clap::Command> = None;
+        let mut scope = Cli::command();
+        for word in &words {
+            let Some(sub) = scope.find_subcommand(word).cloned() else {
+                break;
+            };
+            target = Some(sub.clone());
+            scope = sub;
+        }
+        let about = format!("Alias for 'darp {}'", expansion);
+        let alias = match target {
+            Some(target) => target
+                .name(name.clone())
+                .alias(None)
+                .visible_alias(None)
+                .about(about),
+            None => clap::Command::new(name.clone()).about(about),
+        };
+        cmd = cmd.subcommand(alias);
+    }
+    cmd
+}
@@ -1,4 +1,6 @@
-use clap::{Parser, Subcommand};
+use std::ffi::OsString;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 /// Your directories auto-reverse proxied.
 #[derive(Parser, Debug)]
@@ -9,10 +11,106 @@ use clap::{Parser, Subcommand};
     disable_help_subcommand = true
 )]
 pub struct Cli {
+    #[command(flatten)]
+    pub paths: PathArgs,
+    #[command(flatten)]
+    pub log: LogArgs,
+    /// Output of read commands (urls, which, ps, artifacts, doctor, next, secret list, config show, config rollback --list)
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    pub output: OutputFormat,
+    /// Never prompt: take the default answer, and fail instead of asking for a sudo password (also DARP_NONINTERACTIVE=1)
+    #[arg(short = 'y', long, global = true)]
+    pub yes: bool,
+    /// Report on stderr how long each phase took (config load, engine readiness, scan, …)
+    #[arg(long, global = true)]
+    pub timings: bool,
     #[command(subcommand)]
     pub command: Option<Command>,
 }
 
+/// How read commands print their results: for people, or as JSON for scripts.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Program `darp share` opens its tunnel with.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelKind {
+    Cloudflared,
+    Ngrok,
+    Localtunnel,
+}
+
+/// How much darp reports on stderr. `DARP_LOG` overrides both.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogArgs {
+    /// Show the engine commands darp runs (-v), or everything it does (-vv)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Only report errors; hide notes and warnings
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+}
+
+/// Options choosing the files an invocation works on, accepted before or after any
+/// subcommand.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathArgs {
+    /// Directory darp keeps its files in (instead of DARP_ROOT, DARP_CONFIG_DIR and DARP_STATE_DIR)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub darp_root: Option<String>,
+    /// config.json to read and change (instead of the one in the darp root or DARP_CONFIG_DIR)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<String>,
+}
+
+impl PathArgs {
+    /// The path flags in raw `args` (program name first), read before clap parses them so
+    /// main can find the config that holds the aliases. Also returns how many arguments
+    /// right after the program name are path flags and their values.
+    pub fn scan(args: &[OsString]) -> (PathArgs, usize) {
+        let mut found = PathArgs::default();
+        let mut leading = 0;
+        let mut in_lead = true;
+        let mut i = 1;
+        while let Some(arg) = args.get(i) {
+            let arg = arg.to_str().unwrap_or_default();
+            if arg == "--" {
+                break;
+            }
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg, None),
+            };
+            let slot = match flag {
+                "--darp-root" => &mut found.darp_root,
+                "--config" => &mut found.config,
+                _ => {
+                    in_lead = false;
+                    i += 1;
+                    continue;
+                }
+            };
+            let taken = if inline.is_some() { 1 } else { 2 };
+            *slot = inline.or_else(|| args.get(i + 1).and_then(|v| v.to_str()).map(String::from));
+            if in_lead {
+                leading += taken;
+            }
+            i += taken;
+        }
+        (found, leading.min(args.len().saturating_sub(1)))
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Configuration commands that modify config.json
@@ -21,37 +119,238 @@ pub enum Command {
         cmd: ConfigCommand,
     },
     /// Generates domains and starts reverse proxy
-    Deploy,
+    Deploy {
+        /// Afterwards, request every URL and report those that don't reach the proxy
+        #[arg(long)]
+        verify: bool,
+        /// Deploy without domains whose folder is missing or unreadable instead of failing
+        #[arg(long)]
+        skip_missing: bool,
+        /// Do nothing unless service folders were added or removed since the last deploy
+        #[arg(long)]
+        if_changed: bool,
+    },
+    /// Registers the service in the current directory without a full deploy
+    AddService {
+        /// Register every service folder that has no port yet instead
+        #[arg(long)]
+        new: bool,
+    },
     /// Runs the environment serve_command (uses domain default_environment if set)
     Serve {
-        /// Environment name (optional; falls back to domain default_environment if configured)
+        #[command(flatten)]
+        run: RunArgs,
+        /// Report the URL once the service's healthcheck passes
+        #[arg(long)]
+        wait: bool,
+        /// Seconds --wait allows the service to become healthy
+        #[arg(long, value_name = "SECS", default_value_t = 60, requires = "wait")]
+        wait_timeout: u64,
+        /// Restart policy for this run (no|on-failure[:N]|always|unless-stopped)
+        #[arg(long, value_name = "POLICY")]
+        restart: Option<String>,
+        /// Start the container in the background and return once it's up
+        #[arg(short = 'd', long)]
+        detach: bool,
+        /// Prefix each output line with the time (UTC) and the service name
+        #[arg(long, conflicts_with = "detach")]
+        prefix: bool,
+    },
+    /// Shows the output of the current directory's service container
+    Logs {
+        /// Keep streaming new output
+        #[arg(short = 'f', long)]
+        follow: bool,
+        /// Number of lines to show from the end of the output
+        #[arg(long, value_name = "LINES")]
+        tail: Option<String>,
+    },
+    /// Stops the current directory's service container
+    Stop,
+    /// Starts every service of a domain in the background (like `darp serve -d` in each)
+    Up {
+        /// Domain to start (defaults to the one the current directory belongs to)
+        #[arg(short, long)]
+        domain: Option<String>,
+        /// Only start services with this tag (repeatable; any tag matches)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Only start these services (comma-separated)
+        #[arg(long, value_delimiter = ',', value_name = "SERVICES")]
+        only: Vec<String>,
+        /// Skip these services (comma-separated)
+        #[arg(long, value_delimiter = ',', value_name = "SERVICES")]
+        except: Vec<String>,
+        /// Environment name (optional; falls back to each service's default_environment)
         #[arg(short, long)]
         environment: Option<String>,
-        /// Print the generated container command and exit without running it
-        #[arg(long)]
-        dry_run: bool,
-        /// Container image to use (optional if default_container_image is configured)
-        container_image: Option<String>,
     },
     /// Starts a shell instance (uses service/environment shell_command if set, otherwise 'sh')
     Shell {
-        /// Environment name (optional)
-        #[arg(short, long)]
-        environment: Option<String>,
-        /// Print the generated container command and exit without running it
+        #[command(flatten)]
+        run: RunArgs,
+        /// Use a long-lived container for the service that keeps installed packages between sessions
         #[arg(long)]
-        dry_run: bool,
-        /// Container image to use (optional if default_container_image is configured)
-        container_image: Option<String>,
+        persist: bool,
     },
     /// List Darp URLs
-    Urls,
+    Urls {
+        /// Only list this domain's services
+        domain: Option<String>,
+        /// Only list services whose name or host matches this glob (e.g. 'api*', '*.shop.test')
+        #[arg(long, value_name = "GLOB")]
+        filter: Option<String>,
+        /// Print a Markdown handbook (with service descriptions) for a team wiki
+        #[arg(long, conflicts_with_all = ["html", "json", "plain"])]
+        markdown: bool,
+        /// Print the handbook as an HTML fragment
+        #[arg(long, conflicts_with_all = ["json", "plain"])]
+        html: bool,
+        /// Print `{domain: {service: {url, port}}}` as JSON
+        #[arg(long, conflicts_with = "plain")]
+        json: bool,
+        /// Print one uncoloured `<url> <port>` line per service
+        #[arg(long)]
+        plain: bool,
+        /// Print each service's http://localhost:<port> URL, which works without DNS
+        #[arg(long, conflicts_with_all = ["markdown", "html", "json", "plain"])]
+        direct: bool,
+        /// Print a QR code of each URL, or of one service's ('domain.service' or a unique name), to open on a phone
+        #[arg(
+            long,
+            value_name = "SERVICE",
+            num_args = 0..=1,
+            conflicts_with_all = ["markdown", "html", "json", "plain", "direct"]
+        )]
+        qr: Option<Option<String>>,
+        /// Only list services with this tag (repeatable; any tag matches)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Show the domain, service, port, URL and container the current directory maps to
+    Which {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print shell integration for the current directory's service
+    Hook {
+        #[command(subcommand)]
+        cmd: HookCommand,
+    },
+    /// Write editor tasks that run `darp serve` and `darp shell` for the current service
+    Generate {
+        #[command(subcommand)]
+        cmd: GenerateCommand,
+    },
+    /// Add darp's direnv block to the current service's .envrc (or every service's in a domain)
+    Envrc {
+        /// Update every deployed service folder of this domain instead
+        #[arg(long, value_name = "DOMAIN")]
+        domain: Option<String>,
+        /// Remove darp's block instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Open a service's URL in the browser (the current directory's service by default)
+    Open {
+        /// Service as 'domain.service', or a service name unique across domains
+        service: Option<String>,
+    },
+    /// Open a public tunnel to a service (the current directory's by default) and print its URL
+    Share {
+        /// Service as 'domain.service', or a service name unique across domains
+        service: Option<String>,
+        /// Tunnel program to use (default: the first of cloudflared, ngrok and lt on PATH)
+        #[arg(long, value_enum)]
+        via: Option<TunnelKind>,
+    },
+    /// Reload browser tabs of live_reload services when their folders change (runs until Ctrl-C)
+    LiveReload,
+    /// Advertise every deployed service as <service>-<domain>.local over mDNS (runs until Ctrl-C)
+    Mdns,
+    /// List running darp containers with their URLs and ports
+    Ps {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the paths and formats of files generated by `darp deploy`
+    Artifacts {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+        /// Keep running and report each time an artifact changes
+        #[arg(long)]
+        watch: bool,
+    },
     /// Install darp system installation
-    Install,
+    Install {
+        /// Container engine to configure (podman|docker); skips auto-detection
+        #[arg(long)]
+        engine: Option<String>,
+        /// Write the completion block to this file instead of ~/.zshrc / ~/.bashrc
+        #[arg(long, value_name = "PATH", conflicts_with = "no_rc")]
+        rc_file: Option<String>,
+        /// Don't touch any rc file; print the completion block instead
+        #[arg(long)]
+        no_rc: bool,
+    },
     /// Uninstall darp system integration
-    Uninstall,
+    Uninstall {
+        /// Also remove the completion block from this file (as passed to install)
+        #[arg(long, value_name = "PATH", conflicts_with = "no_rc")]
+        rc_file: Option<String>,
+        /// Don't touch any rc file
+        #[arg(long)]
+        no_rc: bool,
+    },
     /// Check system health and configuration
     Doctor,
+    /// Exit 0 if darp is ready to use (engine, proxy, DNS, config), non-zero if not; prints nothing
+    Check,
+    /// Show the getting-started steps still to do (engine, install, domain, deploy, serve)
+    Next,
+    /// Manage secrets injected as environment variables (stored outside config.json)
+    Secret {
+        #[command(subcommand)]
+        cmd: SecretCommand,
+    },
+    /// Inspect the reverse proxy container
+    Proxy {
+        #[command(subcommand)]
+        cmd: ProxyCommand,
+    },
+    /// Start, stop, or inspect the container engine's VM (podman machine / Docker Desktop)
+    Machine {
+        #[command(subcommand)]
+        cmd: MachineCommand,
+    },
+    /// List or remove the named volumes darp created for services
+    Volume {
+        #[command(subcommand)]
+        cmd: VolumeCommand,
+    },
+    /// Generate a unit or compose file that runs services' serve configuration without darp
+    Export {
+        #[command(subcommand)]
+        cmd: ExportCommand,
+    },
+    /// Print the shell completion script, for wiring completions up yourself
+    Completion {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
+    /// Write man pages for every command (run by `darp install`)
+    #[command(hide = true)]
+    Man {
+        /// Directory to write the pages to (default: ~/.local/share/man/man1)
+        #[arg(long, value_name = "DIR")]
+        dir: Option<String>,
+        /// Remove the pages instead
+        #[arg(long)]
+        remove: bool,
+    },
     /// Validate a container image works with darp
     CheckImage {
         /// Container image to check (if omitted, resolves from current directory context)
@@ -62,6 +361,187 @@ pub enum Command {
     },
 }
 
+/// Options shared by `darp serve` and `darp shell`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct RunArgs {
+    /// Environment name (optional; falls back to the service/group/domain default_environment)
+    #[arg(short, long)]
+    pub environment: Option<String>,
+    /// Print the generated container command and exit without running it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Print what would be run (image, options, mounts, ports, env, command) as JSON and exit
+    #[arg(long, conflicts_with = "dry_run")]
+    pub print_plan: bool,
+    /// Load environment variables from this file (repeatable; added after the service's env_file)
+    #[arg(long = "env-file", value_name = "PATH")]
+    pub env_files: Vec<String>,
+    /// Container network to join (overrides the domain's network)
+    #[arg(long)]
+    pub network: Option<String>,
+    /// Container image to use (optional if default_container_image is configured)
+    pub container_image: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportCommand {
+    /// Podman Quadlet `.container` unit
+    Quadlet(ExportArgs),
+    /// systemd `.service` unit that runs the configured engine (e.g. docker) directly
+    Systemd(ExportArgs),
+    /// docker-compose.yml that runs every service of a domain without darp
+    Compose(ComposeArgs),
+}
+
+/// Options for `darp export compose`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ComposeArgs {
+    /// Domain whose services to export
+    pub domain: String,
+    /// Environment name (optional; falls back to each service's default_environment)
+    #[arg(short, long)]
+    pub environment: Option<String>,
+    /// Env file to reference from every service (repeatable; added after each service's env_file)
+    #[arg(long = "env-file", value_name = "PATH")]
+    pub env_files: Vec<String>,
+    /// Write docker-compose.yml into this directory instead of printing it
+    #[arg(short = 'o', long, value_name = "DIR")]
+    pub output_dir: Option<String>,
+    /// Keep Podman-only settings (userns_mode: keep-id) for podman-compose
+    #[arg(long)]
+    pub podman: bool,
+}
+
+/// Options shared by the `darp export` formats.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ExportArgs {
+    /// Service folder name, as listed by `darp urls`
+    pub service: String,
+    /// Domain of the service (needed when several domains have a service by that name)
+    #[arg(short, long)]
+    pub domain: Option<String>,
+    /// Environment name (optional; falls back to the service/group/domain default_environment)
+    #[arg(short, long)]
+    pub environment: Option<String>,
+    /// Env file to reference from the unit (repeatable; added after the service's env_file)
+    #[arg(long = "env-file", value_name = "PATH")]
+    pub env_files: Vec<String>,
+    /// Container network to join (overrides the domain's network)
+    #[arg(long)]
+    pub network: Option<String>,
+    /// Write the unit into this directory instead of printing it
+    #[arg(short = 'o', long, value_name = "DIR")]
+    pub output_dir: Option<String>,
+    /// Container image to use (optional if default_container_image is configured)
+    pub container_image: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SecretCommand {
+    /// Store a secret (prompts for the value on stdin when VALUE is omitted)
+    Set {
+        name: String,
+        value: Option<String>,
+        /// Only inject into containers of this domain (default: every domain)
+        #[arg(short, long)]
+        domain: Option<String>,
+    },
+    /// Remove a stored secret
+    Rm {
+        name: String,
+        /// Scope the secret was stored under
+        #[arg(short, long)]
+        domain: Option<String>,
+    },
+    /// List stored secret names (values are never printed)
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VolumeCommand {
+    /// List darp's named volumes and where each is configured
+    Ls {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Remove named volumes darp created, and their data
+    Rm {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MachineCommand {
+    /// Start the configured podman machine (or Docker Desktop)
+    Start,
+    /// Stop the configured podman machine (or Docker Desktop)
+    Stop,
+    /// Show whether the configured podman machine (or Docker Desktop) is running
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GitHookCommand {
+    /// Add a post-checkout hook that re-deploys when a branch switch adds or removes service folders
+    Install,
+    /// Remove darp's post-checkout hook
+    Uninstall,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HookCommand {
+    /// Print .envrc lines exporting DARP_SERVICE, DARP_URL, DARP_PORT and sibling URLs (use: eval "$(darp hook direnv)")
+    Direnv,
+    /// Manage the current repository's git hooks
+    Git {
+        #[command(subcommand)]
+        cmd: GitHookCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GenerateCommand {
+    /// .vscode/tasks.json with "darp: serve" and "darp: shell" tasks
+    Vscode(GenerateArgs),
+    /// .run/ shell run configurations for JetBrains IDEs
+    Jetbrains(GenerateArgs),
+}
+
+/// Options for `darp generate`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct GenerateArgs {
+    /// Environment the tasks pass to `-e` (optional; falls back to the service's default_environment)
+    #[arg(short, long)]
+    pub environment: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProxyCommand {
+    /// Show the reverse proxy's nginx access and error logs
+    Logs {
+        /// Keep streaming new log lines
+        #[arg(short = 'f', long)]
+        follow: bool,
+        /// Number of lines to show from the end of the log
+        #[arg(long, value_name = "LINES")]
+        tail: Option<String>,
+        /// Only show the error log (upstream failures, 502s)
+        #[arg(long, conflicts_with = "access")]
+        errors: bool,
+        /// Only show the access log
+        #[arg(long)]
+        access: bool,
+    },
+    /// Have nginx re-read its configuration without dropping connections
+    Reload,
+    /// Restart the reverse proxy container
+    Restart,
+    /// Show whether the proxy runs, its nginx config test and its published ports
+    Status,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum ConfigCommand {
     /// Set values in config
@@ -87,6 +567,53 @@ pub enum ConfigCommand {
     },
     /// Pull latest changes for all pre_config repos
     Pull,
+    /// Restore config.json from one of the backups taken before each change
+    Rollback {
+        /// Which backup to restore, 1 being the newest
+        #[arg(default_value_t = 1)]
+        n: usize,
+        /// List the backups instead of restoring one
+        #[arg(long, conflicts_with = "n")]
+        list: bool,
+    },
+    /// Service-level tools
+    Svc {
+        #[command(subcommand)]
+        cmd: ConfigSvcCommand,
+    },
+    /// Update config after moving a folder
+    Move {
+        #[command(subcommand)]
+        cmd: MoveCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MoveCommand {
+    /// Point a domain at its new folder, keeping its services and ports, and redeploy
+    Domain {
+        name: String,
+        /// Where the domain's folder is now
+        new_location: String,
+        /// Only update config.json; run 'darp deploy' yourself later
+        #[arg(long)]
+        no_deploy: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigSvcCommand {
+    /// Set up a service interactively: image, serve command, ports, volumes and variables
+    Wizard {
+        domain_name: String,
+        service_name: String,
+        /// Group the service folder is in
+        #[arg(short = 'g', long = "group", default_value = ".")]
+        group_name: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -122,10 +649,58 @@ pub enum SetCommand {
     UrlsInHosts { value: String },
     /// Enable/disable WSL mode (syncs Windows hosts file and adds doctor checks)
     Wsl { value: String },
+    /// Enable/disable `<service>-<domain>.local` names for `darp mdns` to advertise
+    Mdns { value: String },
+    /// Enable/disable the dedicated `darp` container network (default: enabled)
+    DarpNetwork { value: String },
+    /// Keep darp's state and generated files in another directory than config.json
+    StateDir {
+        /// Directory, relative to config.json's (e.g. '{home}/.local/state/darp')
+        path: String,
+    },
+    /// Name service containers from a template (default: 'darp_{domain}_{service}')
+    ContainerName {
+        /// Template with {domain} and {service} tokens (e.g. 'dev-{service}-{domain}')
+        template: String,
+    },
+    /// Limit how many container starts, stops, pulls and builds run at once (default: 4)
+    EngineConcurrency {
+        /// Operations at once across every darp process; 0 removes the limit
+        limit: u32,
+    },
+    /// Run the container engine on a development server over SSH
+    Remote {
+        /// SSH destination (e.g. 'me@devbox' or a ~/.ssh/config alias)
+        host: String,
+        /// IP address .test names should resolve to (the server's address)
+        address: String,
+        /// Engine URL to use instead of the default ssh://<host>
+        #[arg(long)]
+        url: Option<String>,
+        /// Point /etc/resolver/test at the server's dnsmasq on 'darp install'
+        #[arg(long)]
+        resolver: bool,
+    },
+    /// Share this machine with other darp users: namespaced hostnames, containers and ports
+    SharedHost {
+        /// Name to namespace under; services become <service>.<domain>.<user>.test
+        user: String,
+        /// First of the 1000 ports set aside for this user (e.g. 20000)
+        port_base: u16,
+        /// Directory every user's vhosts are collected in (default: /tmp/darp-proxy)
+        #[arg(long)]
+        proxy_dir: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum SetDomCommand {
+    /// Point a domain at a new folder (e.g. after moving it)
+    Location {
+        domain_name: String,
+        /// Folder holding the domain's services
+        path: String,
+    },
     /// Set default_environment on a domain
     DefaultEnvironment {
         /// Logical domain name (e.g. 'my-domain')
@@ -176,16 +751,54 @@ pub enum SetDomCommand {
         #[arg(short = 'l', long)]
         location: Option<String>,
     },
-    /// Set connection_type (http|websocket|tcp) on a domain. Controls how the darp reverse
+    /// Set connection_type (http|websocket|tcp|static) on a domain. Controls how the darp reverse
     /// proxy forwards traffic to services under this domain.
     ConnectionType {
         domain_name: String,
-        /// One of: http, websocket, tcp
+        /// One of: http, websocket, tcp, static
         connection_type: String,
         /// Create the domain at this path if it doesn't exist
         #[arg(short = 'l', long)]
         location: Option<String>,
     },
+    /// Set the container network serve/shell containers in a domain join
+    Network {
+        domain_name: String,
+        /// Name of an existing network (e.g. one created by docker compose)
+        network: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Run the domain's services in one shared Podman pod (true/false)
+    Pod {
+        domain_name: String,
+        value: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Send a percentage of a service's requests to another service in the domain
+    Canary {
+        domain_name: String,
+        /// Service whose URL is split
+        service_name: String,
+        /// Service that receives the canary share
+        canary_service: String,
+        /// Share of requests for the canary (1-99)
+        percent: u8,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Run the domain's services on another container engine (podman|docker)
+    Engine {
+        domain_name: String,
+        engine: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -244,11 +857,11 @@ pub enum SetGrpCommand {
         #[arg(short = 'l', long)]
         location: Option<String>,
     },
-    /// Set connection_type (http|websocket|tcp) on a group
+    /// Set connection_type (http|websocket|tcp|static) on a group
     ConnectionType {
         domain_name: String,
         group_name: String,
-        /// One of: http, websocket, tcp
+        /// One of: http, websocket, tcp, static
         connection_type: String,
         /// Create the domain at this path if it doesn't exist
         #[arg(short = 'l', long)]
@@ -283,12 +896,32 @@ pub enum SetEnvCommand {
         environment: String,
         default_container_image: String,
     },
-    /// Set connection_type (http|websocket|tcp) on an environment
+    /// Set connection_type (http|websocket|tcp|static) on an environment
     ConnectionType {
         environment: String,
-        /// One of: http, websocket, tcp
+        /// One of: http, websocket, tcp, static
         connection_type: String,
     },
+    /// Set CPU/memory limits on an environment (passed as --cpus/--memory)
+    Limits {
+        environment: String,
+        /// Number of CPUs (e.g. '2' or '1.5')
+        #[arg(long)]
+        cpus: Option<String>,
+        /// Memory limit (e.g. '2g' or '512m')
+        #[arg(long)]
+        memory: Option<String>,
+    },
+    /// Set the restart policy for served containers (no|on-failure[:N]|always|unless-stopped)
+    Restart { environment: String, policy: String },
+    /// Run served and shell containers as the host user, so files in /app aren't root's (true/false)
+    MapUser { environment: String, value: String },
+    /// Mount the service folder at /app read-only (true/false)
+    ReadOnlyApp { environment: String, value: String },
+    /// Set the image 'darp shell' uses (instead of default_container_image)
+    ShellImage { environment: String, image: String },
+    /// Run this environment's services on another container engine (podman|docker)
+    Engine { environment: String, engine: String },
 }
 
 #[derive(Subcommand, Debug)]
@@ -353,20 +986,209 @@ pub enum SetSvcCommand {
         #[arg(short = 'l', long)]
         location: Option<String>,
     },
-    /// Set connection_type (http|websocket|tcp) on a service. 'http' and 'websocket' both
+    /// Set connection_type (http|websocket|tcp|static) on a service. 'http' and 'websocket' both
     /// generate an nginx vhost at {service}.{domain}.test with WebSocket upgrade support;
     /// they differ only in the URL scheme shown by `darp urls`. 'tcp' skips the nginx vhost
     /// entirely and exposes the service on localhost:{auto_port} as a raw TCP socket.
+    /// 'static' runs no container: the proxy serves the service's folder as files.
     ConnectionType {
         domain_name: String,
         group_name: String,
         service_name: String,
-        /// One of: http, websocket, tcp
+        /// One of: http, websocket, tcp, static
         connection_type: String,
         /// Create the domain at this path if it doesn't exist
         #[arg(short = 'l', long)]
         location: Option<String>,
     },
+    /// Set a default env file for a service (relative to the project dir; supports {pwd}/{home})
+    EnvFile {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        env_file: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set the command (or http(s) URL) the engine polls to check the service is healthy
+    Healthcheck {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        healthcheck: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set the one-line description shown by `darp urls --markdown/--html`
+    Description {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        description: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Inject a reload script into the service's pages, served by `darp live-reload` (true/false)
+    LiveReload {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        value: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Copy every request to another darp service ('service' or 'domain.service'); its responses are ignored
+    Mirror {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        target: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set how the proxy talks to the service: http (default) or grpc (HTTP/2 listener, grpc_pass)
+    Protocol {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        value: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Give a tcp service a dedicated port on the proxy, e.g. 5432 for {svc}.{dom}.test:5432
+    Stream {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        port: u16,
+        /// Forward UDP instead of TCP
+        #[arg(long)]
+        udp: bool,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Proxy the service to a process run on the host instead of a darp container
+    HostPort {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        /// Port the process listens on (e.g. 3000)
+        port: u16,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Run several containers of the service behind one URL, balanced by the proxy
+    Replicas {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        count: u32,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set when `darp up` starts the service (lower first; services sharing a value start together)
+    StartOrder {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        #[arg(allow_negative_numbers = true)]
+        start_order: i32,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set the restart policy for the served container (overrides the environment's)
+    Restart {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        /// One of: no, on-failure[:N], always, unless-stopped
+        policy: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Run the service's containers as the host user (true/false; overrides the environment's)
+    MapUser {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        value: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Mount the service folder at /app read-only (true/false; overrides the environment's)
+    ReadOnlyApp {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        value: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Start the service's containers with this entrypoint in place of 'sh' (given '-c <script>')
+    Entrypoint {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        path: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set the image 'darp shell' uses for a service (overrides the environment's)
+    ShellImage {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        image: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Set CPU/memory limits on a service (overrides the environment's per key)
+    Limits {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        /// Number of CPUs (e.g. '2' or '1.5')
+        #[arg(long)]
+        cpus: Option<String>,
+        /// Memory limit (e.g. '2g' or '512m')
+        #[arg(long)]
+        memory: Option<String>,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Build the service's image from a base image plus packages and commands
+    ImageBuild {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        /// Image to start from (e.g. 'php:8.3-cli')
+        base: String,
+        /// Package to install with the base image's package manager (repeatable)
+        #[arg(long = "package", value_name = "PACKAGE")]
+        packages: Vec<String>,
+        /// Command to run on top, one RUN step each (repeatable)
+        #[arg(long = "run", value_name = "COMMAND")]
+        run: Vec<String>,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -379,6 +1201,28 @@ pub enum AddCommand {
         #[arg(short, long)]
         repo_location: Option<String>,
     },
+    /// Add a shortcut: `darp <name> [args]` runs `darp <command> [args]`
+    Alias {
+        name: String,
+        /// The darp invocation it stands for, quoted (e.g. 'serve -e dev')
+        #[arg(allow_hyphen_values = true)]
+        command: String,
+    },
+    /// Add a static hosts entry for every domain (e.g. cdn.myapp.test -> 10.0.0.5)
+    ExtraHost { host: String, ip: String },
+    /// Add an extra `run` argument for a helper container (reverse-proxy|dnsmasq)
+    HelperArg {
+        /// One of: reverse-proxy, dnsmasq
+        helper: String,
+        /// Argument passed to the engine's `run` (e.g. '--network=devnet')
+        #[arg(allow_hyphen_values = true)]
+        arg: String,
+    },
+    /// Add an argument to every `run` the engine issues (e.g. '--pull=never')
+    EngineArg {
+        #[arg(allow_hyphen_values = true)]
+        arg: String,
+    },
     /// Add domain-scoped configuration (volumes, port mappings, variables)
     Dom {
         #[command(subcommand)]
@@ -413,7 +1257,17 @@ pub enum AddDomCommand {
         #[arg(short = 'l', long)]
         location: Option<String>,
     },
+    /// Add a static hosts entry alongside the domain's service URLs
+    ExtraHost {
+        domain_name: String,
+        host: String,
+        ip: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
     /// Add variable to a domain
+    #[command(visible_alias = "envvar")]
     Variable {
         domain_name: String,
         name: String,
@@ -427,6 +1281,9 @@ pub enum AddDomCommand {
         domain_name: String,
         container_dir: String,
         host_dir: String,
+        /// Mount it read-only
+        #[arg(long)]
+        read_only: bool,
         /// Create the domain at this path if it doesn't exist
         #[arg(short = 'l', long)]
         location: Option<String>,
@@ -446,6 +1303,7 @@ pub enum AddGrpCommand {
         location: Option<String>,
     },
     /// Add variable to a group
+    #[command(visible_alias = "envvar")]
     Variable {
         domain_name: String,
         group_name: String,
@@ -461,6 +1319,9 @@ pub enum AddGrpCommand {
         group_name: String,
         container_dir: String,
         host_dir: String,
+        /// Mount it read-only
+        #[arg(long)]
+        read_only: bool,
         /// Create the domain at this path if it doesn't exist
         #[arg(short = 'l', long)]
         location: Option<String>,
@@ -476,6 +1337,7 @@ pub enum AddEnvCommand {
         container_port: String,
     },
     /// Add variable to an environment (auto-creates environment if needed)
+    #[command(visible_alias = "envvar")]
     Variable {
         environment: String,
         name: String,
@@ -486,6 +1348,9 @@ pub enum AddEnvCommand {
         environment: String,
         container_dir: String,
         host_dir: String,
+        /// Mount it read-only
+        #[arg(long)]
+        read_only: bool,
     },
 }
 
@@ -503,6 +1368,7 @@ pub enum AddSvcCommand {
         location: Option<String>,
     },
     /// Add variable to a service
+    #[command(visible_alias = "envvar")]
     Variable {
         domain_name: String,
         group_name: String,
@@ -520,6 +1386,76 @@ pub enum AddSvcCommand {
         service_name: String,
         container_dir: String,
         host_dir: String,
+        /// Mount it read-only
+        #[arg(long)]
+        read_only: bool,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Tag a service (for `darp up --tag` / `darp urls --tag`)
+    Tag {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        tag: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Add a hostname the service also answers on (e.g. api.myapp.test)
+    Alias {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        hostname: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Route a real hostname (e.g. login.mycompany.dev) to the service, optionally over TLS
+    CustomDomain {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        hostname: String,
+        /// Certificate for the hostname (PEM), served on port 443
+        #[arg(long, requires = "key")]
+        cert: Option<String>,
+        /// Private key for the certificate (PEM)
+        #[arg(long, requires = "cert")]
+        key: Option<String>,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Add a command `darp serve` runs before the serve command
+    PreServe {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        command: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Add a command run after the serve command exits
+    PostServe {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        command: String,
+        /// Create the domain at this path if it doesn't exist
+        #[arg(short = 'l', long)]
+        location: Option<String>,
+    },
+    /// Add an argument to the engine's `run` for the service (e.g. '--shm-size=1g')
+    RunArg {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        #[arg(allow_hyphen_values = true)]
+        arg: String,
         /// Create the domain at this path if it doesn't exist
         #[arg(short = 'l', long)]
         location: Option<String>,
@@ -548,6 +1484,32 @@ pub enum RmCommand {
     },
     /// Remove PODMAN_MACHINE from config
     PodmanMachine {},
+    /// Go back to the local container engine
+    Remote {},
+    /// Stop namespacing this user on a shared machine
+    SharedHost {},
+    /// Keep darp's state and generated files next to config.json again
+    StateDir {},
+    /// Go back to the default container name template
+    ContainerName {},
+    /// Go back to the default engine concurrency limit
+    EngineConcurrency {},
+    /// Remove a shortcut added with `darp config add alias`
+    Alias { name: String },
+    /// Remove a static hosts entry added with `darp config add extra-host`
+    ExtraHost { host: String },
+    /// Remove an extra `run` argument from a helper container (reverse-proxy|dnsmasq)
+    HelperArg {
+        /// One of: reverse-proxy, dnsmasq
+        helper: String,
+        #[arg(allow_hyphen_values = true)]
+        arg: String,
+    },
+    /// Remove an argument added with `darp config add engine-arg`
+    EngineArg {
+        #[arg(allow_hyphen_values = true)]
+        arg: String,
+    },
     /// Remove domain-level configuration
     Dom {
         #[command(subcommand)]
@@ -582,7 +1544,10 @@ pub enum RmDomCommand {
         domain_name: String,
         host_port: String,
     },
+    /// Remove a static hosts entry from a domain
+    ExtraHost { domain_name: String, host: String },
     /// Remove variable from a domain
+    #[command(visible_alias = "envvar")]
     Variable { domain_name: String, name: String },
     /// Remove volume from a domain
     Volume {
@@ -602,6 +1567,17 @@ pub enum RmDomCommand {
     DefaultContainerImage { domain_name: String },
     /// Remove connection_type override from a domain
     ConnectionType { domain_name: String },
+    /// Remove the custom network from a domain
+    Network { domain_name: String },
+    /// Remove the pod setting from a domain
+    Pod { domain_name: String },
+    /// Stop splitting a service's traffic
+    Canary {
+        domain_name: String,
+        service_name: String,
+    },
+    /// Remove the engine override from a domain
+    Engine { domain_name: String },
 }
 
 #[derive(Subcommand, Debug)]
@@ -618,6 +1594,7 @@ pub enum RmGrpCommand {
         host_port: String,
     },
     /// Remove variable from a group
+    #[command(visible_alias = "envvar")]
     Variable {
         domain_name: String,
         group_name: String,
@@ -670,6 +1647,7 @@ pub enum RmEnvCommand {
         host_port: String,
     },
     /// Remove variable from an environment
+    #[command(visible_alias = "envvar")]
     Variable { environment: String, name: String },
     /// Remove volume from an environment
     Volume {
@@ -689,6 +1667,18 @@ pub enum RmEnvCommand {
     DefaultContainerImage { environment: String },
     /// Remove connection_type override from an environment
     ConnectionType { environment: String },
+    /// Remove CPU/memory limits from an environment
+    Limits { environment: String },
+    /// Remove the restart policy from an environment
+    Restart { environment: String },
+    /// Remove the map_user setting from an environment
+    MapUser { environment: String },
+    /// Remove the read_only_app setting from an environment
+    ReadOnlyApp { environment: String },
+    /// Remove the shell image from an environment
+    ShellImage { environment: String },
+    /// Remove the engine override from an environment
+    Engine { environment: String },
 }
 
 #[derive(Subcommand, Debug)]
@@ -707,6 +1697,7 @@ pub enum RmSvcCommand {
         host_port: String,
     },
     /// Remove variable from a service
+    #[command(visible_alias = "envvar")]
     Variable {
         domain_name: String,
         group_name: String,
@@ -721,6 +1712,49 @@ pub enum RmSvcCommand {
         container_dir: String,
         host_dir: String,
     },
+    /// Remove a tag from a service
+    Tag {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        tag: String,
+    },
+    /// Remove a hostname alias from a service
+    Alias {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        hostname: String,
+    },
+    /// Remove a custom domain from a service
+    CustomDomain {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        hostname: String,
+    },
+    /// Remove a pre_serve command from a service
+    PreServe {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        command: String,
+    },
+    /// Remove a post_serve command from a service
+    PostServe {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        command: String,
+    },
+    /// Remove a run argument from a service
+    RunArg {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+        #[arg(allow_hyphen_values = true)]
+        arg: String,
+    },
     /// Remove serve_command from a service
     ServeCommand {
         domain_name: String,
@@ -757,4 +1791,106 @@ pub enum RmSvcCommand {
         group_name: String,
         service_name: String,
     },
+    /// Remove the default env file from a service
+    EnvFile {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the healthcheck from a service
+    Healthcheck {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the description from a service
+    Description {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the live reload setting from a service
+    LiveReload {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Stop mirroring a service's requests
+    Mirror {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the protocol from a service (back to http)
+    Protocol {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove a service's dedicated stream port
+    Stream {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Go back to running the service in a darp container
+    HostPort {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Go back to a single container for a service
+    Replicas {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the start order from a service
+    StartOrder {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the restart policy from a service
+    Restart {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the map_user setting from a service
+    MapUser {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the read_only_app setting from a service
+    ReadOnlyApp {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the entrypoint from a service
+    Entrypoint {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the shell image from a service
+    ShellImage {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove the image_build recipe from a service
+    ImageBuild {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
+    /// Remove CPU/memory limits from a service
+    Limits {
+        domain_name: String,
+        group_name: String,
+        service_name: String,
+    },
 }
@@ -0,0 +1,341 @@
+use std::io::Write;
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::config::{self, Config, DarpPaths, ServiceContext};
+use crate::deploy::{
+    aliased_vhost, collect_debug_ports, collect_host_portmap_ports, container_gateway_ip,
+    custom_domain_urls, custom_domain_vhost, deploy_replicas, direct_port, direct_vhost,
+    grpc_vhost, live_reload_vhost, mirror_target_host, mirror_vhost, network_route, portmap_entry,
+    publish_shared_vhosts, replicas_vhost, resolve_deploy_connection_type, scanned_services,
+    service_network_resolver, service_vhost, static_vhost, sync_url_hosts, write_stream_conf,
+};
+use crate::engine::Engine;
+use crate::mdns;
+use crate::scan::ScanManifest;
+use crate::services_index::write_services_index;
+use crate::urls::direct_url;
+
+/// Register the service in the current directory without a full `darp deploy`: assign
+/// its ports, append its vhost and hosts line, and reload the proxy. Running service
+/// containers and every other service's ports are left alone. `new` registers every
+/// scanned service folder that has no port yet instead.
+pub fn cmd_add_service(
+    new: bool,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    engine.require_ready()?;
+
+    if new {
+        return register_new_services(paths, config, engine);
+    }
+    let ctx = config.require_service_context(None)?;
+    register_service(&ctx, paths, config, engine)
+}
+
+/// `darp add-service --new`: register each folder `darp deploy` would pick up that isn't
+/// in the portmap yet. Folders that were removed keep their entry until the next deploy.
+fn register_new_services(
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)
+        .map_err(|_| anyhow!("No deploy found. Run 'darp deploy' first."))?;
+    let registered = config::portmap_services(&portmap);
+    let mut manifest = ScanManifest::load(&paths.scan_manifest_path);
+    let services = scanned_services(config, &mut manifest)?;
+    manifest.save(&paths.scan_manifest_path)?;
+
+    let mut added = 0;
+    for (domain_name, group_name, service_name) in services {
+        if registered
+            .iter()
+            .any(|(d, g, s)| (d, g, s) == (&domain_name, &group_name, &service_name))
+        {
+            continue;
+        }
+        let Some(domain) = config.domains.as_ref().and_then(|d| d.get(&domain_name)) else {
+            continue;
+        };
+        let mut dir = config::resolve_location(&domain.location)?;
+        if group_name != "." {
+            dir.push(&group_name);
+        }
+        dir.push(&service_name);
+        let ctx = config
+            .service_context_for_dir(dir.clone(), None)
+            .ok_or_else(|| anyhow!("{} is not inside any darp domain", dir.display()))?;
+        register_service(&ctx, paths, config, engine)?;
+        added += 1;
+    }
+    if added == 0 {
+        println!("No new service folders to register.");
+    }
+    Ok(())
+}
+
+/// `darp add-service` for the service `ctx` describes, wherever it was found.
+pub(crate) fn register_service(
+    ctx: &ServiceContext<'_>,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let domain_name = ctx.domain_name.as_str();
+    let group_name = ctx.group_name.as_str();
+    let service_name = ctx.current_directory_name.as_str();
+
+    // Only register folders deploy would pick up, so the next deploy agrees with us.
+    let groups = ctx.domain.groups.as_ref();
+    if group_name == "." {
+        if groups.is_some_and(|g| !g.contains_key(".")) {
+            return Err(anyhow!(
+                "Domain '{}' has no '.' group, so deploy doesn't register services in its root.",
+                domain_name
+            ));
+        }
+        if groups.is_some_and(|g| g.contains_key(service_name)) {
+            return Err(anyhow!(
+                "'{}' is a group of domain '{}', not a service.",
+                service_name,
+                domain_name
+            ));
+        }
+    } else if ctx.group.is_none() {
+        return Err(anyhow!(
+            "'{}' is not a group of domain '{}'. Add it with 'darp config add grp group {} {}'.",
+            group_name,
+            domain_name,
+            domain_name,
+            group_name
+        ));
+    }
+
+    if !paths.portmap_path.exists()
+        || !paths.vhost_container_conf.exists()
+        || !paths.hosts_container_path.exists()
+    {
+        return Err(anyhow!("No deploy found. Run 'darp deploy' first."));
+    }
+    let mut portmap: serde_json::Value = config::read_json(&paths.portmap_path)?;
+    let url = config.service_host(service_name, domain_name);
+    if config::portmap_proxy_port(&portmap, domain_name, group_name, service_name).is_some() {
+        println!("{} is already registered.", url.green());
+        return Ok(());
+    }
+
+    let connection_type = resolve_deploy_connection_type(ctx.domain, group_name, service_name)
+        .unwrap_or_else(|| "http".to_string());
+    let port = config::next_proxy_port(&portmap, config.first_proxy_port());
+
+    let debug_base = config.first_debug_port();
+    let mut skip_debug_ports = config::well_known_skip_ports();
+    skip_debug_ports.extend(collect_host_portmap_ports(config));
+    let mut reserved_debug_ports = collect_debug_ports(&portmap);
+    let mut next_debug_port = debug_base;
+    let debug_port = config::choose_debug_port(
+        None,
+        debug_base,
+        &skip_debug_ports,
+        &mut reserved_debug_ports,
+        &mut next_debug_port,
+    );
+
+    let domain_map = portmap
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("{} is not a JSON object", paths.portmap_path.display()))?
+        .entry(domain_name.to_string())
+        .or_insert_with(|| serde_json::json!({}));
+    let group_map = domain_map
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("portmap entry for '{}' is not an object", domain_name))?
+        .entry(group_name.to_string())
+        .or_insert_with(|| serde_json::json!({}));
+    let replicas = deploy_replicas(
+        engine,
+        domain_name,
+        ctx.domain,
+        group_name,
+        service_name,
+        &connection_type,
+    ) as u16;
+    let replica_ports: Vec<u16> = (port..port + replicas).collect();
+    let mut entry = portmap_entry(port, &connection_type, debug_port);
+    if config.user().is_some() {
+        entry["host"] = url.clone().into();
+    }
+    let stream = ctx.service.and_then(|s| s.stream.as_ref());
+    if let Some(stream) = stream {
+        entry["stream_port"] = stream.port.into();
+    }
+    if replicas > 1 {
+        entry["replica_ports"] = replica_ports[1..].into();
+    }
+    let aliases = ctx
+        .service
+        .and_then(|s| s.aliases.as_deref())
+        .unwrap_or_default();
+    if !aliases.is_empty() {
+        entry["aliases"] = aliases.into();
+    }
+    let custom_domains = ctx.service.and_then(|s| s.custom_domains.as_ref());
+    if let Some(custom_domains) = custom_domains {
+        entry["custom_urls"] = custom_domain_urls(custom_domains).into();
+    }
+    let mdns_host = (config.mdns == Some(true) && connection_type != "tcp")
+        .then(|| mdns::mdns_host(domain_name, service_name));
+    if let Some(mdns_host) = &mdns_host {
+        entry["mdns_host"] = mdns_host.clone().into();
+    }
+    let host_port = ctx
+        .service
+        .and_then(|s| s.host_port)
+        .filter(|_| connection_type != "static");
+    if let Some(host_port) = host_port {
+        entry["host_port"] = host_port.into();
+    }
+    let network_resolver = if engine.darp_network {
+        Some(engine.darp_network_dns()?)
+    } else {
+        None
+    };
+    let service_resolver = service_network_resolver(
+        config,
+        engine,
+        network_resolver.as_deref(),
+        ctx.domain,
+        group_name,
+        service_name,
+    );
+    let routed = connection_type != "tcp"
+        && replicas <= 1
+        && network_route(engine, service_resolver, ctx.domain).is_some();
+    let split = ctx
+        .domain
+        .canaries
+        .as_ref()
+        .is_some_and(|c| c.contains_key(service_name));
+    let direct = direct_port(
+        &connection_type,
+        host_port,
+        port,
+        routed,
+        split,
+        config.shared_host.is_some(),
+    );
+    if let Some((port, _)) = direct {
+        entry["direct_url"] = direct_url(&connection_type, &config.direct_host(), port).into();
+    }
+    let direct_via_proxy = direct.is_some_and(|(_, via_proxy)| via_proxy);
+    if direct_via_proxy {
+        entry["direct_via_proxy"] = true.into();
+    }
+    if let Some(group_map) = group_map.as_object_mut() {
+        group_map.insert(service_name.to_string(), entry);
+    }
+    std::fs::write(&paths.portmap_path, serde_json::to_vec_pretty(&portmap)?)?;
+
+    let vhost = if connection_type == "static" {
+        Some(static_vhost(domain_name, group_name, service_name))
+    } else if let Some(host_port) = host_port {
+        service_vhost(
+            engine,
+            None,
+            domain_name,
+            ctx.domain,
+            service_name,
+            &connection_type,
+            host_port,
+        )
+    } else if replicas > 1 {
+        Some(replicas_vhost(
+            domain_name,
+            service_name,
+            engine.host_gateway(),
+            &replica_ports,
+        ))
+    } else {
+        service_vhost(
+            engine,
+            service_resolver,
+            domain_name,
+            ctx.domain,
+            service_name,
+            &connection_type,
+            port,
+        )
+    };
+    if let Some(vhost) = vhost {
+        let grpc = ctx.service.and_then(|s| s.protocol.as_deref()) == Some("grpc");
+        let vhost = if grpc {
+            grpc_vhost(&vhost)
+        } else if ctx.service.and_then(|s| s.live_reload) == Some(true) {
+            live_reload_vhost(&vhost, engine.host_gateway())
+        } else {
+            vhost
+        };
+        let vhost = match ctx.service.and_then(|s| s.mirror.as_deref()) {
+            Some(target) if !grpc => mirror_vhost(&vhost, &mirror_target_host(domain_name, target)),
+            _ => vhost,
+        };
+        let direct = direct_via_proxy.then(|| direct_vhost(&vhost, port));
+        let vhost = aliased_vhost(&vhost, aliases);
+        let vhost = aliased_vhost(&vhost, mdns_host.as_slice());
+        let vhost = match custom_domains {
+            Some(custom_domains) if !grpc => custom_domain_vhost(&vhost, custom_domains),
+            _ => vhost,
+        };
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&paths.vhost_container_conf)?
+            .write_all((vhost + direct.as_deref().unwrap_or_default()).as_bytes())?;
+    }
+
+    if stream.is_some() {
+        write_stream_conf(paths, config, engine, network_resolver.as_deref(), &portmap)?;
+    }
+
+    // Append rather than rewrite: running containers bind-mount this file.
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&paths.hosts_container_path)?
+        .write_all(
+            std::iter::once(&url)
+                .chain(aliases)
+                .chain(custom_domains.into_iter().flat_map(|d| d.keys()))
+                .map(|host| format!("0.0.0.0   {host}\n"))
+                .collect::<String>()
+                .as_bytes(),
+        )?;
+
+    write_services_index(paths, engine, &engine.running_container_names())?;
+    engine.sync_remote_files(paths)?;
+    publish_shared_vhosts(paths, config)?;
+    engine.reload_reverse_proxy(paths)?;
+
+    let mut hosts_container_lines: Vec<String> =
+        std::fs::read_to_string(&paths.hosts_container_path)?
+            .lines()
+            .filter(|l| l.starts_with("0.0.0.0"))
+            .map(|l| format!("{l}\n"))
+            .collect();
+    for line in config.extra_host_lines() {
+        if !hosts_container_lines.contains(&line) {
+            hosts_container_lines.push(line);
+        }
+    }
+    let gateway_ip = container_gateway_ip(paths, engine)?;
+    sync_url_hosts(paths, config, engine, &gateway_ip, &hosts_container_lines)?;
+
+    println!(
+        "Registered {} (proxy port {}, debug port {})",
+        url.green(),
+        port,
+        debug_port
+    );
+    Ok(())
+}
@@ -0,0 +1,122 @@
+// commands/artifacts.rs
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use colored::*;
+
+use crate::config::DarpPaths;
+
+/// Version of the artifact formats described by `darp artifacts`. Bumped only on a
+/// breaking change to one of the files below; additive changes (new keys in a
+/// portmap entry, extra vhost blocks) keep the version.
+pub const ARTIFACTS_FORMAT_VERSION: u32 = 1;
+
+struct Artifact {
+    name: &'static str,
+    path: PathBuf,
+    format: &'static str,
+    description: &'static str,
+}
+
+fn artifacts(paths: &DarpPaths) -> Vec<Artifact> {
+    vec![
+        Artifact {
+            name: "portmap",
+            path: paths.portmap_path.clone(),
+            format: "json",
+            description: "{domain: {group: {service: {port, type, debug_port}}}}",
+        },
+        Artifact {
+            name: "vhost_container",
+            path: paths.vhost_container_conf.clone(),
+            format: "nginx",
+            description: "one `server` block per http/websocket service",
+        },
+        Artifact {
+            name: "stream_container",
+            path: paths.stream_container_conf.clone(),
+            format: "nginx",
+            description: "`stream {}` block with a `server` per service stream port",
+        },
+        Artifact {
+            name: "hosts_container",
+            path: paths.hosts_container_path.clone(),
+            format: "hosts",
+            description: "hosts(5) file mounted at /etc/hosts in darp containers",
+        },
+        Artifact {
+            name: "services_index",
+            path: paths.services_index_path.clone(),
+            format: "json",
+            description: "service switcher index, also served at http://darp.test/services.json",
+        },
+    ]
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+pub fn cmd_artifacts(paths: &DarpPaths, json: bool, watch: bool) -> anyhow::Result<()> {
+    let list = artifacts(paths);
+
+    if json {
+        let files: Vec<serde_json::Value> = list
+            .iter()
+            .map(|a| {
+                serde_json::json!({
+                    "name": a.name,
+                    "path": a.path,
+                    "format": a.format,
+                    "exists": a.path.exists(),
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "format_version": ARTIFACTS_FORMAT_VERSION,
+                "artifacts": files,
+            }))?
+        );
+    } else {
+        println!("artifact format version {}", ARTIFACTS_FORMAT_VERSION);
+        for a in &list {
+            let path = a.path.display().to_string();
+            let path = if a.path.exists() {
+                path.normal()
+            } else {
+                format!("{} (not yet written — run 'darp deploy')", path).yellow()
+            };
+            println!("{} [{}] {}", a.name.green(), a.format, path);
+            println!("    {}", a.description);
+        }
+    }
+
+    if !watch {
+        return Ok(());
+    }
+
+    if !json {
+        println!("\nwatching for changes (Ctrl+C to stop)");
+    }
+    let mut last: Vec<Option<SystemTime>> = list.iter().map(|a| modified(&a.path)).collect();
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        for (a, seen) in list.iter().zip(last.iter_mut()) {
+            let now = modified(&a.path);
+            if now != *seen {
+                *seen = now;
+                if json {
+                    println!(
+                        "{}",
+                        serde_json::json!({"event": "changed", "name": a.name, "path": a.path})
+                    );
+                } else {
+                    println!("{} changed", a.name.cyan());
+                }
+            }
+        }
+    }
+}
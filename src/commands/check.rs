@@ -0,0 +1,133 @@
+// commands/check.rs
+
+use std::net::ToSocketAddrs;
+
+use tracing::debug;
+
+use crate::config::{self, Config, DarpPaths};
+use crate::deploy::domain_location_problems;
+use crate::engine::{self, Engine, EngineKind};
+
+/// The part of the toolchain `darp check` found broken. Each has an exit code of its own,
+/// so a script can tell them apart without parsing output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckFailure {
+    /// config.json doesn't load, names no engine or domain, or a domain's folder is gone.
+    Config,
+    /// The engine isn't installed or isn't running.
+    Engine,
+    /// Nothing is deployed, or the reverse proxy isn't running.
+    Proxy,
+    /// dnsmasq isn't running, or deployed hostnames don't resolve.
+    Dns,
+}
+
+impl CheckFailure {
+    /// 1, 3, 4 and 5: clap already exits with 2 for a bad command line.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            CheckFailure::Config => 1,
+            CheckFailure::Engine => 3,
+            CheckFailure::Proxy => 4,
+            CheckFailure::Dns => 5,
+        }
+    }
+}
+
+/// Why `config` isn't usable, checking only what needs no engine.
+pub fn config_problem(config: &Config) -> Option<String> {
+    match config.engine.as_deref() {
+        Some("docker" | "podman") => {}
+        Some(other) => return Some(format!("engine '{}' is not docker or podman", other)),
+        None => return Some("no engine configured".to_string()),
+    }
+    if config.domains.as_ref().is_none_or(|d| d.is_empty()) {
+        return Some("no domains configured".to_string());
+    }
+    match domain_location_problems(config) {
+        Ok(problems) => problems.first().map(|p| {
+            format!(
+                "domain '{}': {} {}",
+                p.domain,
+                p.location.display(),
+                p.reason
+            )
+        }),
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+/// The first deployed service's hostname, to try resolving.
+fn deployed_host(portmap: &serde_json::Value) -> Option<String> {
+    portmap
+        .as_object()?
+        .iter()
+        .flat_map(|(domain, groups)| {
+            groups
+                .as_object()
+                .into_iter()
+                .flatten()
+                .flat_map(|(_, services)| services.as_object().into_iter().flatten())
+                .map(move |(service, entry)| config::portmap_host(entry, domain, service))
+        })
+        .next()
+}
+
+/// Every check in order, stopping at the first failure.
+pub fn run_checks(paths: &DarpPaths) -> Result<(), (CheckFailure, String)> {
+    let config = Config::load_merged(&paths.config_path)
+        .map_err(|e| (CheckFailure::Config, e.to_string()))?;
+    if let Some(problem) = config_problem(&config) {
+        return Err((CheckFailure::Config, problem));
+    }
+
+    let engine = Engine::new(EngineKind::from_config(&config), &config)
+        .map_err(|e| (CheckFailure::Config, e.to_string()))?;
+    if !engine.is_engine_installed() {
+        return Err((
+            CheckFailure::Engine,
+            format!("{} is not on PATH", engine.bin.unwrap_or("engine")),
+        ));
+    }
+    engine
+        .require_ready()
+        .map_err(|e| (CheckFailure::Engine, e.to_string()))?;
+
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)
+        .map_err(|_| (CheckFailure::Proxy, "nothing deployed".to_string()))?;
+    let running = engine.running_container_names();
+    if !running.contains(engine::REVERSE_PROXY) {
+        return Err((
+            CheckFailure::Proxy,
+            format!("{} is not running", engine::REVERSE_PROXY),
+        ));
+    }
+
+    if !running.contains(engine::DNSMASQ) {
+        return Err((
+            CheckFailure::Dns,
+            format!("{} is not running", engine::DNSMASQ),
+        ));
+    }
+    // A sandbox has no resolver of its own to point at dnsmasq; see `darp doctor`.
+    if crate::os::detect_sandbox().is_none() {
+        if let Some(host) = deployed_host(&portmap) {
+            if (host.as_str(), 80).to_socket_addrs().is_err() {
+                return Err((CheckFailure::Dns, format!("{} does not resolve", host)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `darp check`: no output, just an exit code (0 when everything works). `-v` logs why
+/// it failed.
+pub fn cmd_check(paths: &DarpPaths) -> i32 {
+    match run_checks(paths) {
+        Ok(()) => 0,
+        Err((failure, reason)) => {
+            debug!("check failed ({:?}): {}", failure, reason);
+            failure.exit_code()
+        }
+    }
+}
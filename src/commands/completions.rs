@@ -1,10 +1,11 @@
-use clap::CommandFactory;
-use clap_complete::{generate, shells};
+use clap_complete::{Shell, generate, shells};
 use dirs::home_dir;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::cli::Cli;
+use crate::alias;
 
 pub const RC_START_MARKER: &str = "# >>> darp completion start >>>";
 const RC_END_MARKER: &str = "# <<< darp completion end <<<";
@@ -29,32 +30,63 @@ pub fn detect_shell() -> Option<&'static str> {
     }
 }
 
-fn ensure_rc_block(rc_path: &Path, body: &str) -> anyhow::Result<()> {
-    let contents = fs::read_to_string(rc_path).unwrap_or_default();
+/// Byte range of darp's marker block in `contents`, end marker and its newline
+/// included. A block missing its end marker runs to the end of the file.
+fn find_rc_block(contents: &str) -> Option<(usize, usize)> {
+    let start = contents.find(RC_START_MARKER)?;
+    let end = match contents[start..].find(RC_END_MARKER) {
+        Some(e) => {
+            let end = start + e + RC_END_MARKER.len();
+            if contents[end..].starts_with('\n') {
+                end + 1
+            } else {
+                end
+            }
+        }
+        None => contents.len(),
+    };
+    Some((start, end))
+}
 
-    if contents.contains(RC_START_MARKER) {
-        return Ok(());
+/// `contents` with darp's marker block set to `body`: a block left by an older darp
+/// is replaced where it stands, otherwise a new one is appended.
+pub fn with_rc_block(contents: &str, body: &str) -> String {
+    let mut block = String::new();
+    block.push_str(RC_START_MARKER);
+    block.push('\n');
+    block.push_str(body);
+    if !body.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(RC_END_MARKER);
+    block.push('\n');
+
+    if let Some((start, end)) = find_rc_block(contents) {
+        return format!("{}{}{}", &contents[..start], block, &contents[end..]);
     }
 
-    let mut new_contents = contents;
+    let mut new_contents = contents.to_string();
     if !new_contents.is_empty() && !new_contents.ends_with('\n') {
         new_contents.push('\n');
     }
+    new_contents.push_str(&block);
+    new_contents
+}
 
-    new_contents.push_str(RC_START_MARKER);
-    new_contents.push('\n');
-    new_contents.push_str(body);
-    if !body.ends_with('\n') {
-        new_contents.push('\n');
+/// Write darp's block into `rc_path`. Returns false when it was already current, so
+/// re-running `darp install` leaves the file untouched.
+fn ensure_rc_block(rc_path: &Path, body: &str) -> anyhow::Result<bool> {
+    let contents = fs::read_to_string(rc_path).unwrap_or_default();
+    let new_contents = with_rc_block(&contents, body);
+    if new_contents == contents {
+        return Ok(false);
     }
-    new_contents.push_str(RC_END_MARKER);
-    new_contents.push('\n');
 
     if let Some(parent) = rc_path.parent() {
         fs::create_dir_all(parent)?;
     }
     fs::write(rc_path, new_contents)?;
-    Ok(())
+    Ok(true)
 }
 
 fn remove_rc_block(rc_path: &Path) -> anyhow::Result<()> {
@@ -64,21 +96,10 @@ fn remove_rc_block(rc_path: &Path) -> anyhow::Result<()> {
         Err(e) => return Err(e.into()),
     };
 
-    let start = if let Some(s) = contents.find(RC_START_MARKER) {
-        s
-    } else {
+    let Some((start, end)) = find_rc_block(&contents) else {
         return Ok(());
     };
 
-    // Find end after start
-    let after_start = &contents[start..];
-    let end_rel = if let Some(e) = after_start.find(RC_END_MARKER) {
-        e + RC_END_MARKER.len()
-    } else {
-        contents.len() - start
-    };
-    let end = start + end_rel;
-
     let mut new_contents = String::new();
     new_contents.push_str(contents[..start].trim_end_matches('\n'));
     if !new_contents.is_empty() {
@@ -97,9 +118,55 @@ fn remove_rc_block(rc_path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Where `darp install` / `darp uninstall` put the block that loads completions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RcTarget {
+    /// The shell's usual rc file (see `default_rc_path`).
+    Default,
+    /// A file picked with `--rc-file`, e.g. one sourced from managed dotfiles.
+    File(PathBuf),
+    /// `--no-rc`: leave rc files alone and only write the completion script.
+    Skip,
+}
+
+impl RcTarget {
+    /// From the `--rc-file` / `--no-rc` flags (clap keeps them mutually exclusive).
+    pub fn from_flags(rc_file: Option<String>, no_rc: bool) -> Self {
+        match (rc_file, no_rc) {
+            (_, true) => RcTarget::Skip,
+            (Some(path), false) => RcTarget::File(PathBuf::from(path)),
+            (None, false) => RcTarget::Default,
+        }
+    }
+}
+
+/// The rc file darp edits for `shell` unless told otherwise: `$ZDOTDIR/.zshrc` for zsh
+/// and `$BASH_ENV` for bash when those are set, else `~/.zshrc` / `~/.bashrc`. `None`
+/// for shells that load completions without an rc block. `env` is injected for tests.
+pub fn default_rc_path_with(
+    shell: &str,
+    home: &Path,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<PathBuf> {
+    let var = |name: &str| env(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    match shell {
+        "zsh" => Some(
+            var("ZDOTDIR")
+                .unwrap_or_else(|| home.to_path_buf())
+                .join(".zshrc"),
+        ),
+        "bash" => Some(var("BASH_ENV").unwrap_or_else(|| home.join(".bashrc"))),
+        _ => None,
+    }
+}
+
+pub fn default_rc_path(shell: &str, home: &Path) -> Option<PathBuf> {
+    default_rc_path_with(shell, home, |name| std::env::var(name).ok())
+}
+
 struct ShellCompletionConfig {
     completion_file: &'static str,
-    rc: Option<(&'static str, &'static str)>,
+    rc_body: Option<&'static str>,
     generate: fn(cmd: &mut clap::Command, name: String, file: &mut fs::File),
 }
 
@@ -117,36 +184,85 @@ fn shell_completion_config(shell: &str) -> Option<ShellCompletionConfig> {
     match shell {
         "bash" => Some(ShellCompletionConfig {
             completion_file: ".local/share/bash-completion/completions/darp",
-            rc: Some((
-                ".bashrc",
+            rc_body: Some(
                 r#"if command -v darp >/dev/null 2>&1; then
   source "${XDG_DATA_HOME:-$HOME/.local/share}/bash-completion/completions/darp"
 fi"#,
-            )),
+            ),
             generate: gen_bash,
         }),
         "zsh" => Some(ShellCompletionConfig {
             completion_file: ".zfunc/_darp",
-            rc: Some((
-                ".zshrc",
+            rc_body: Some(
                 r#"if command -v darp >/dev/null 2>&1; then
   fpath+=("$HOME/.zfunc")
   autoload -Uz compinit
   compinit
 fi"#,
-            )),
+            ),
             generate: gen_zsh,
         }),
         "fish" => Some(ShellCompletionConfig {
             completion_file: ".config/fish/completions/darp.fish",
-            rc: None,
+            rc_body: None,
             generate: gen_fish,
         }),
         _ => None,
     }
 }
 
-pub fn install_shell_completions() -> anyhow::Result<()> {
+/// Write the completion script for `cfg`'s shell to `path`, with `aliases` completing
+/// like the commands they expand to.
+fn write_completions(
+    cfg: &ShellCompletionConfig,
+    path: &Path,
+    aliases: &BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::File::create(path)?;
+    let mut cmd = alias::command_with_aliases(aliases);
+    let name = cmd.get_name().to_string();
+    (cfg.generate)(&mut cmd, name, &mut file);
+    Ok(())
+}
+
+/// The completion script for `shell`, with `aliases` completing like the commands they
+/// expand to.
+pub fn completion_script(shell: Shell, aliases: &BTreeMap<String, String>) -> Vec<u8> {
+    let mut cmd = alias::command_with_aliases(aliases);
+    let name = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    generate(shell, &mut cmd, name, &mut script);
+    script
+}
+
+/// `darp completion <shell>`: print the script instead of installing it, for dotfile
+/// managers that keep rc files under their own control.
+pub fn cmd_completion(shell: Shell, aliases: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    std::io::stdout().write_all(&completion_script(shell, aliases))?;
+    Ok(())
+}
+
+/// Regenerate an installed completion script after the aliases changed. Does nothing
+/// when completions were never installed for the current shell.
+pub fn refresh_shell_completions(aliases: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let (Some(cfg), Some(home)) = (detect_shell().and_then(shell_completion_config), home_dir())
+    else {
+        return Ok(());
+    };
+    let path = home.join(cfg.completion_file);
+    if path.exists() {
+        write_completions(&cfg, &path, aliases)?;
+    }
+    Ok(())
+}
+
+pub fn install_shell_completions(
+    rc: &RcTarget,
+    aliases: &BTreeMap<String, String>,
+) -> anyhow::Result<()> {
     let Some(shell) = detect_shell() else {
         println!("Could not detect shell from $SHELL; skipping shell completion install.");
         return Ok(());
@@ -166,25 +282,40 @@ pub fn install_shell_completions() -> anyhow::Result<()> {
     };
 
     let path = home.join(cfg.completion_file);
-    if let Some(dir) = path.parent() {
-        fs::create_dir_all(dir)?;
-    }
-    let mut file = fs::File::create(&path)?;
-    let mut cmd = Cli::command();
-    let name = cmd.get_name().to_string();
-    (cfg.generate)(&mut cmd, name, &mut file);
+    write_completions(&cfg, &path, aliases)?;
     println!("Installed {} completions to {}", shell, path.display());
 
-    if let Some((rc_rel, body)) = cfg.rc {
-        let rc_path = home.join(rc_rel);
-        ensure_rc_block(&rc_path, body)?;
-        println!("Updated {} with darp completion block", rc_path.display());
+    let Some(body) = cfg.rc_body else {
+        return Ok(());
+    };
+    let rc_path = match rc {
+        RcTarget::Default => default_rc_path(shell, &home),
+        RcTarget::File(path) => Some(path.clone()),
+        RcTarget::Skip => None,
+    };
+    match rc_path {
+        Some(rc_path) => {
+            if ensure_rc_block(&rc_path, body)? {
+                println!("Updated {} with darp completion block", rc_path.display());
+            } else {
+                println!(
+                    "{} already has the current darp completion block",
+                    rc_path.display()
+                );
+            }
+        }
+        None => println!(
+            "Left your rc files alone. To load completions, add this to one:\n{}",
+            body
+        ),
     }
 
     Ok(())
 }
 
-pub fn uninstall_shell_completions() -> anyhow::Result<()> {
+/// Remove the completion script and the rc block. The block is removed from the default
+/// rc file and, when `rc` names one, from that file too.
+pub fn uninstall_shell_completions(rc: &RcTarget) -> anyhow::Result<()> {
     let Some(shell) = detect_shell() else {
         println!("Could not detect shell from $SHELL; skipping shell completion removal.");
         return Ok(());
@@ -210,8 +341,14 @@ pub fn uninstall_shell_completions() -> anyhow::Result<()> {
         Err(e) => return Err(e.into()),
     }
 
-    if let Some((rc_rel, _)) = cfg.rc {
-        remove_rc_block(&home.join(rc_rel))?;
+    if cfg.rc_body.is_none() || *rc == RcTarget::Skip {
+        return Ok(());
+    }
+    if let Some(rc_path) = default_rc_path(shell, &home) {
+        remove_rc_block(&rc_path)?;
+    }
+    if let RcTarget::File(rc_path) = rc {
+        remove_rc_block(rc_path)?;
     }
 
     Ok(())
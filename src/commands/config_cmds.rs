@@ -1,17 +1,30 @@
 use colored::*;
 
+use super::completions::refresh_shell_completions;
+use super::deploy::cmd_deploy;
+use super::image_build::image_build_tag;
+use super::open::{find_portmap_service, service_browser_url};
+use crate::alias;
+use crate::backups;
 use crate::cli::*;
-use crate::config::{self, Config, DarpPaths, ResolvedSettings};
-use crate::engine::EngineKind;
+use crate::config::{self, Config, DarpPaths, ImageBuild, ResolvedSettings, ResourceLimits};
+use crate::engine::{Engine, EngineKind};
+use crate::error::DarpError;
+use crate::logging::Logged;
+use crate::os::OsIntegration;
+use crate::urls::{
+    lan_ip, phone_url, qr_code, render_urls_direct, render_urls_html, render_urls_json,
+    render_urls_markdown, render_urls_plain, render_urls_qr,
+};
 
 fn config_mutate(
     config: &mut Config,
-    path: &std::path::Path,
+    paths: &DarpPaths,
     f: impl FnOnce(&mut Config) -> anyhow::Result<()>,
     msg: Option<String>,
 ) -> anyhow::Result<()> {
     f(config)?;
-    config.save(path)?;
+    config.save_with_backup(paths)?;
     if let Some(msg) = msg {
         println!("{}", msg);
     }
@@ -29,7 +42,7 @@ pub fn cmd_set(
         SetCommand::PodmanMachine { new_podman_machine } => {
             config_mutate(
                 config,
-                p,
+                paths,
                 |c| {
                     c.podman_machine = Some(new_podman_machine.clone());
                     Ok(())
@@ -44,12 +57,11 @@ pub fn cmd_set(
         SetCommand::Engine { engine } => {
             let engine_lc = engine.to_lowercase();
             if engine_lc != "podman" && engine_lc != "docker" {
-                eprintln!("engine must be 'podman' or 'docker'");
-                std::process::exit(1);
+                return Err(DarpError::UnknownEngine(engine).into());
             }
             config_mutate(
                 config,
-                p,
+                paths,
                 |c| {
                     c.engine = Some(engine_lc);
                     Ok(())
@@ -64,7 +76,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| c.set_image_repository(&environment, &image_repository),
                     Some(format!(
                         "Set image_repository for environment '{}' to:\n  {}",
@@ -78,7 +90,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| c.set_serve_command(&environment, &serve_command),
                     Some(format!(
                         "Set serve_command for environment '{}' to:\n  {}",
@@ -92,7 +104,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| c.set_shell_command(&environment, &shell_command),
                     Some(format!(
                         "Set shell_command for environment '{}' to:\n  {}",
@@ -106,7 +118,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| c.set_platform(&environment, &platform),
                     Some(format!(
                         "Set platform for environment '{}' to:\n  {}",
@@ -120,7 +132,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| c.set_default_container_image(&environment, &default_container_image),
                     Some(format!(
                         "Set default_container_image for environment '{}' to:\n  {}",
@@ -134,7 +146,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| c.set_environment_connection_type(&environment, &connection_type),
                     Some(format!(
                         "Set connection_type for environment '{}' to:\n  {}",
@@ -142,6 +154,82 @@ pub fn cmd_set(
                     )),
                 )?;
             }
+            SetEnvCommand::Limits {
+                environment,
+                cpus,
+                memory,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_environment_limits(&environment, &ResourceLimits { cpus, memory }),
+                    Some(format!("Set limits for environment '{}'", environment)),
+                )?;
+            }
+            SetEnvCommand::Restart {
+                environment,
+                policy,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_environment_restart(&environment, &policy),
+                    Some(format!(
+                        "Set restart policy for environment '{}' to:\n  {}",
+                        environment, policy
+                    )),
+                )?;
+            }
+            SetEnvCommand::MapUser { environment, value } => {
+                let v = config.parse_bool(&value)?;
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_environment_map_user(&environment, v),
+                    Some(format!(
+                        "Set map_user for environment '{}' to {}",
+                        environment, v
+                    )),
+                )?;
+            }
+            SetEnvCommand::ReadOnlyApp { environment, value } => {
+                let v = config.parse_bool(&value)?;
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_environment_read_only_app(&environment, v),
+                    Some(format!(
+                        "Set read_only_app for environment '{}' to {}",
+                        environment, v
+                    )),
+                )?;
+            }
+            SetEnvCommand::ShellImage { environment, image } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_environment_shell_image(&environment, &image),
+                    Some(format!(
+                        "Set shell_image for environment '{}' to:\n  {}",
+                        environment, image
+                    )),
+                )?;
+            }
+            SetEnvCommand::Engine {
+                environment,
+                engine,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_environment_engine(&environment, &engine),
+                    Some(format!(
+                        "Set engine for environment '{}' to {}. Run 'darp deploy' so the reverse proxy reaches its services.",
+                        environment,
+                        engine.to_lowercase()
+                    )),
+                )?;
+            }
         },
         SetCommand::Svc { cmd } => match cmd {
             SetSvcCommand::DefaultEnvironment {
@@ -153,7 +241,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_default_environment(
@@ -178,7 +266,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_image_repository(
@@ -203,7 +291,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_serve_command(
@@ -228,7 +316,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_shell_command(
@@ -253,7 +341,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_platform(&domain_name, &group_name, &service_name, &platform)
@@ -273,7 +361,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_default_container_image(
@@ -298,7 +386,7 @@ pub fn cmd_set(
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
                         c.set_service_connection_type(
@@ -314,977 +402,2242 @@ pub fn cmd_set(
                     )),
                 )?;
             }
-        },
-        SetCommand::Dom { cmd } => match cmd {
-            SetDomCommand::DefaultEnvironment {
+            SetSvcCommand::EnvFile {
                 domain_name,
-                default_environment,
+                group_name,
+                service_name,
+                env_file,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_default_environment(&domain_name, &default_environment)
+                        c.set_service_env_file(&domain_name, &group_name, &service_name, &env_file)
                     },
                     Some(format!(
-                        "Set default_environment for domain '{}' to environment '{}'",
-                        domain_name, default_environment
+                        "Set env_file for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, env_file
                     )),
                 )?;
             }
-            SetDomCommand::ImageRepository {
+            SetSvcCommand::Healthcheck {
                 domain_name,
-                image_repository,
+                group_name,
+                service_name,
+                healthcheck,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_image_repository(&domain_name, &image_repository)
+                        c.set_service_healthcheck(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &healthcheck,
+                        )
                     },
                     Some(format!(
-                        "Set image_repository for domain '{}' to:\n  {}",
-                        domain_name, image_repository
+                        "Set healthcheck for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, healthcheck
                     )),
                 )?;
             }
-            SetDomCommand::ServeCommand {
+            SetSvcCommand::Description {
                 domain_name,
-                serve_command,
+                group_name,
+                service_name,
+                description,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_serve_command(&domain_name, &serve_command)
+                        c.set_service_description(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &description,
+                        )
                     },
                     Some(format!(
-                        "Set serve_command for domain '{}' to:\n  {}",
-                        domain_name, serve_command
+                        "Set description for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, description
                     )),
                 )?;
             }
-            SetDomCommand::ShellCommand {
+            SetSvcCommand::LiveReload {
                 domain_name,
-                shell_command,
+                group_name,
+                service_name,
+                value,
                 location,
             } => {
+                let v = config.parse_bool(&value)?;
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_shell_command(&domain_name, &shell_command)
+                        c.set_service_live_reload(&domain_name, &group_name, &service_name, v)
                     },
                     Some(format!(
-                        "Set shell_command for domain '{}' to:\n  {}",
-                        domain_name, shell_command
+                        "Set live_reload for service '{}.{}' to {}. Run 'darp deploy' to update the proxy.",
+                        domain_name, service_name, v
                     )),
                 )?;
             }
-            SetDomCommand::Platform {
+            SetSvcCommand::Mirror {
                 domain_name,
-                platform,
+                group_name,
+                service_name,
+                target,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_platform(&domain_name, &platform)
+                        c.set_service_mirror(&domain_name, &group_name, &service_name, &target)
                     },
                     Some(format!(
-                        "Set platform for domain '{}' to:\n  {}",
-                        domain_name, platform
+                        "Set mirror for service '{}.{}' to '{}'. Run 'darp deploy' to update the proxy.",
+                        domain_name, service_name, target
                     )),
                 )?;
             }
-            SetDomCommand::DefaultContainerImage {
+            SetSvcCommand::Protocol {
                 domain_name,
-                default_container_image,
+                group_name,
+                service_name,
+                value,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_default_container_image(&domain_name, &default_container_image)
+                        c.set_service_protocol(&domain_name, &group_name, &service_name, &value)
                     },
                     Some(format!(
-                        "Set default_container_image for domain '{}' to:\n  {}",
-                        domain_name, default_container_image
+                        "Set protocol for service '{}.{}' to '{}'. Run 'darp deploy' to update the proxy.",
+                        domain_name, service_name, value
                     )),
                 )?;
             }
-            SetDomCommand::ConnectionType {
+            SetSvcCommand::Stream {
                 domain_name,
-                connection_type,
+                group_name,
+                service_name,
+                port,
+                udp,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_domain_connection_type(&domain_name, &connection_type)
+                        c.set_service_stream(&domain_name, &group_name, &service_name, port, udp)
                     },
                     Some(format!(
-                        "Set connection_type for domain '{}' to:\n  {}",
-                        domain_name, connection_type
+                        "Set stream port for service '{}.{}' to {}. Run 'darp deploy' to update the proxy.",
+                        domain_name, service_name, port
                     )),
                 )?;
             }
-        },
-        SetCommand::Grp { cmd } => match cmd {
-            SetGrpCommand::DefaultEnvironment {
+            SetSvcCommand::HostPort {
                 domain_name,
                 group_name,
-                default_environment,
+                service_name,
+                port,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_default_environment(
-                            &domain_name,
-                            &group_name,
-                            &default_environment,
-                        )
+                        c.set_service_host_port(&domain_name, &group_name, &service_name, port)
                     },
                     Some(format!(
-                        "Set default_environment for group '{}' in domain '{}' to '{}'",
-                        group_name, domain_name, default_environment
+                        "Service '{}.{}' now runs on the host at port {}. Run 'darp deploy' to update the proxy.",
+                        domain_name, service_name, port
                     )),
                 )?;
             }
-            SetGrpCommand::ImageRepository {
+            SetSvcCommand::Replicas {
                 domain_name,
                 group_name,
-                image_repository,
+                service_name,
+                count,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_image_repository(&domain_name, &group_name, &image_repository)
+                        c.set_service_replicas(&domain_name, &group_name, &service_name, count)
                     },
                     Some(format!(
-                        "Set image_repository for group '{}' in domain '{}' to:\n  {}",
-                        group_name, domain_name, image_repository
+                        "Set replicas for service '{}.{}' to {}. Run 'darp deploy' to update the proxy.",
+                        domain_name, service_name, count
                     )),
                 )?;
             }
-            SetGrpCommand::ServeCommand {
+            SetSvcCommand::StartOrder {
                 domain_name,
                 group_name,
-                serve_command,
+                service_name,
+                start_order,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_serve_command(&domain_name, &group_name, &serve_command)
+                        c.set_service_start_order(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            start_order,
+                        )
                     },
                     Some(format!(
-                        "Set serve_command for group '{}' in domain '{}' to:\n  {}",
-                        group_name, domain_name, serve_command
+                        "Set start_order for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, start_order
                     )),
                 )?;
             }
-            SetGrpCommand::ShellCommand {
+            SetSvcCommand::ShellImage {
                 domain_name,
                 group_name,
-                shell_command,
+                service_name,
+                image,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_shell_command(&domain_name, &group_name, &shell_command)
+                        c.set_service_shell_image(&domain_name, &group_name, &service_name, &image)
                     },
                     Some(format!(
-                        "Set shell_command for group '{}' in domain '{}' to:\n  {}",
-                        group_name, domain_name, shell_command
+                        "Set shell_image for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, image
                     )),
                 )?;
             }
-            SetGrpCommand::Platform {
+            SetSvcCommand::Restart {
                 domain_name,
                 group_name,
-                platform,
+                service_name,
+                policy,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_platform(&domain_name, &group_name, &platform)
+                        c.set_service_restart(&domain_name, &group_name, &service_name, &policy)
                     },
                     Some(format!(
-                        "Set platform for group '{}' in domain '{}' to:\n  {}",
-                        group_name, domain_name, platform
+                        "Set restart policy for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, policy
                     )),
                 )?;
             }
-            SetGrpCommand::DefaultContainerImage {
+            SetSvcCommand::MapUser {
                 domain_name,
                 group_name,
-                default_container_image,
+                service_name,
+                value,
                 location,
             } => {
+                let v = config.parse_bool(&value)?;
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_default_container_image(
-                            &domain_name,
-                            &group_name,
-                            &default_container_image,
-                        )
+                        c.set_service_map_user(&domain_name, &group_name, &service_name, v)
                     },
                     Some(format!(
-                        "Set default_container_image for group '{}' in domain '{}' to:\n  {}",
-                        group_name, domain_name, default_container_image
+                        "Set map_user for service '{}.{}' to {}",
+                        domain_name, service_name, v
                     )),
                 )?;
             }
-            SetGrpCommand::ConnectionType {
+            SetSvcCommand::ReadOnlyApp {
                 domain_name,
                 group_name,
-                connection_type,
+                service_name,
+                value,
                 location,
             } => {
+                let v = config.parse_bool(&value)?;
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.set_group_connection_type(&domain_name, &group_name, &connection_type)
+                        c.set_service_read_only_app(&domain_name, &group_name, &service_name, v)
                     },
                     Some(format!(
-                        "Set connection_type for group '{}' in domain '{}' to:\n  {}",
-                        group_name, domain_name, connection_type
+                        "Set read_only_app for service '{}.{}' to {}",
+                        domain_name, service_name, v
                     )),
                 )?;
             }
-        },
-        SetCommand::UrlsInHosts { value } => {
-            let v = config.parse_bool(&value)?;
-            config_mutate(
-                config,
-                p,
-                |c| {
-                    c.urls_in_hosts = Some(v);
-                    Ok(())
-                },
-                Some(format!(
-                    "urls_in_hosts has been {} (stored in {}). Next 'darp deploy' will sync /etc/hosts accordingly.",
-                    if v { "enabled" } else { "disabled" },
-                    p.display()
-                )),
-            )?;
-        }
-        SetCommand::Wsl { value } => {
-            let v = config.parse_bool(&value)?;
-            config_mutate(
-                config,
-                p,
-                |c| {
-                    c.wsl = Some(v);
-                    Ok(())
-                },
-                Some(format!(
-                    "WSL mode has been {} (stored in {}). When enabled alongside urls_in_hosts, 'darp deploy' will also sync /mnt/c/Windows/System32/drivers/etc/hosts.",
-                    if v { "enabled" } else { "disabled" },
-                    p.display()
-                )),
-            )?;
-        }
-    }
-
-    Ok(())
-}
-
-pub fn cmd_add(cmd: AddCommand, paths: &DarpPaths, config: &mut Config) -> anyhow::Result<()> {
-    let p = &paths.config_path;
-    match cmd {
-        AddCommand::PreConfig {
-            location,
-            repo_location,
-        } => {
-            config_mutate(
-                config,
-                p,
-                |c| c.add_pre_config(&location, repo_location.as_deref()),
-                Some(format!("Added pre_config '{}'", location)),
-            )?;
-        }
-        AddCommand::Dom { cmd } => match cmd {
-            AddDomCommand::Portmap {
+            SetSvcCommand::Entrypoint {
                 domain_name,
-                host_port,
-                container_port,
+                group_name,
+                service_name,
+                path,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_domain_portmap(&domain_name, &host_port, &container_port)
+                        c.set_service_entrypoint(&domain_name, &group_name, &service_name, &path)
                     },
-                    None,
+                    Some(format!(
+                        "Set entrypoint for service '{}.{}' to:\n  {}",
+                        domain_name, service_name, path
+                    )),
                 )?;
             }
-            AddDomCommand::Variable {
+            SetSvcCommand::ImageBuild {
                 domain_name,
-                name,
-                value,
+                group_name,
+                service_name,
+                base,
+                packages,
+                run,
                 location,
             } => {
+                let build = ImageBuild {
+                    base,
+                    packages,
+                    run,
+                };
+                let tag = image_build_tag(&domain_name, &service_name, &build);
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_domain_variable(&domain_name, &name, &value)
+                        c.set_service_image_build(&domain_name, &group_name, &service_name, build)
                     },
-                    None,
+                    Some(format!(
+                        "Set image_build for service '{}.{}'; it builds to:\n  {}",
+                        domain_name, service_name, tag
+                    )),
                 )?;
             }
-            AddDomCommand::Volume {
+            SetSvcCommand::Limits {
                 domain_name,
-                container_dir,
-                host_dir,
+                group_name,
+                service_name,
+                cpus,
+                memory,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_domain_volume(&domain_name, &container_dir, &host_dir)
+                        c.set_service_limits(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &ResourceLimits { cpus, memory },
+                        )
                     },
-                    None,
+                    Some(format!(
+                        "Set limits for service '{}.{}'",
+                        domain_name, service_name
+                    )),
                 )?;
             }
         },
-        AddCommand::Grp { cmd } => match cmd {
-            AddGrpCommand::Portmap {
+        SetCommand::Dom { cmd } => match cmd {
+            SetDomCommand::DefaultEnvironment {
                 domain_name,
-                group_name,
-                host_port,
-                container_port,
+                default_environment,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_group_portmap(&domain_name, &group_name, &host_port, &container_port)
+                        c.set_domain_default_environment(&domain_name, &default_environment)
                     },
-                    None,
+                    Some(format!(
+                        "Set default_environment for domain '{}' to environment '{}'",
+                        domain_name, default_environment
+                    )),
                 )?;
             }
-            AddGrpCommand::Variable {
+            SetDomCommand::ImageRepository {
                 domain_name,
-                group_name,
-                name,
-                value,
+                image_repository,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_group_variable(&domain_name, &group_name, &name, &value)
+                        c.set_domain_image_repository(&domain_name, &image_repository)
                     },
-                    None,
+                    Some(format!(
+                        "Set image_repository for domain '{}' to:\n  {}",
+                        domain_name, image_repository
+                    )),
                 )?;
             }
-            AddGrpCommand::Volume {
+            SetDomCommand::ServeCommand {
                 domain_name,
-                group_name,
-                container_dir,
-                host_dir,
+                serve_command,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_group_volume(&domain_name, &group_name, &container_dir, &host_dir)
+                        c.set_domain_serve_command(&domain_name, &serve_command)
                     },
-                    None,
+                    Some(format!(
+                        "Set serve_command for domain '{}' to:\n  {}",
+                        domain_name, serve_command
+                    )),
                 )?;
             }
-        },
-        AddCommand::Env { cmd } => match cmd {
-            AddEnvCommand::Portmap {
-                environment,
-                host_port,
-                container_port,
+            SetDomCommand::ShellCommand {
+                domain_name,
+                shell_command,
+                location,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.add_env_portmap(&environment, &host_port, &container_port),
-                    None,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_shell_command(&domain_name, &shell_command)
+                    },
+                    Some(format!(
+                        "Set shell_command for domain '{}' to:\n  {}",
+                        domain_name, shell_command
+                    )),
                 )?;
             }
-            AddEnvCommand::Variable {
-                environment,
-                name,
+            SetDomCommand::Platform {
+                domain_name,
+                platform,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_platform(&domain_name, &platform)
+                    },
+                    Some(format!(
+                        "Set platform for domain '{}' to:\n  {}",
+                        domain_name, platform
+                    )),
+                )?;
+            }
+            SetDomCommand::DefaultContainerImage {
+                domain_name,
+                default_container_image,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_default_container_image(&domain_name, &default_container_image)
+                    },
+                    Some(format!(
+                        "Set default_container_image for domain '{}' to:\n  {}",
+                        domain_name, default_container_image
+                    )),
+                )?;
+            }
+            SetDomCommand::ConnectionType {
+                domain_name,
+                connection_type,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_connection_type(&domain_name, &connection_type)
+                    },
+                    Some(format!(
+                        "Set connection_type for domain '{}' to:\n  {}",
+                        domain_name, connection_type
+                    )),
+                )?;
+            }
+            SetDomCommand::Location { domain_name, path } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.set_domain_location(&domain_name, &path),
+                    Some(format!(
+                        "Set location for domain '{}' to:\n  {}",
+                        domain_name, path
+                    )),
+                )?;
+            }
+            SetDomCommand::Network {
+                domain_name,
+                network,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_network(&domain_name, &network)
+                    },
+                    Some(format!(
+                        "Set network for domain '{}' to:\n  {}",
+                        domain_name, network
+                    )),
+                )?;
+            }
+            SetDomCommand::Pod {
+                domain_name,
                 value,
+                location,
             } => {
+                let v = config.parse_bool(&value)?;
                 config_mutate(
                     config,
-                    p,
-                    |c| c.add_env_variable(&environment, &name, &value),
-                    None,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_pod(&domain_name, v)
+                    },
+                    Some(format!("Set pod for domain '{}' to {}", domain_name, v)),
                 )?;
             }
-            AddEnvCommand::Volume {
-                environment,
-                container_dir,
-                host_dir,
+            SetDomCommand::Engine {
+                domain_name,
+                engine,
+                location,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.add_volume(&environment, &container_dir, &host_dir),
-                    None,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_engine(&domain_name, &engine)
+                    },
+                    Some(format!(
+                        "Set engine for domain '{}' to {}. Run 'darp deploy' so the reverse proxy reaches its services.",
+                        domain_name,
+                        engine.to_lowercase()
+                    )),
+                )?;
+            }
+            SetDomCommand::Canary {
+                domain_name,
+                service_name,
+                canary_service,
+                percent,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_domain_canary(&domain_name, &service_name, &canary_service, percent)
+                    },
+                    Some(format!(
+                        "Sending {}% of {} to '{}'. Run 'darp deploy' to update the proxy.",
+                        percent,
+                        config.service_host(&service_name, &domain_name),
+                        canary_service
+                    )),
                 )?;
             }
         },
-        AddCommand::Svc { cmd } => match cmd {
-            AddSvcCommand::Portmap {
+        SetCommand::Grp { cmd } => match cmd {
+            SetGrpCommand::DefaultEnvironment {
                 domain_name,
                 group_name,
-                service_name,
-                host_port,
-                container_port,
+                default_environment,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_portmap(
+                        c.set_group_default_environment(
                             &domain_name,
                             &group_name,
-                            &service_name,
-                            &host_port,
-                            &container_port,
+                            &default_environment,
                         )
                     },
-                    None,
+                    Some(format!(
+                        "Set default_environment for group '{}' in domain '{}' to '{}'",
+                        group_name, domain_name, default_environment
+                    )),
                 )?;
             }
-            AddSvcCommand::Variable {
+            SetGrpCommand::ImageRepository {
                 domain_name,
                 group_name,
-                service_name,
-                name,
-                value,
+                image_repository,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_variable(&domain_name, &group_name, &service_name, &name, &value)
+                        c.set_group_image_repository(&domain_name, &group_name, &image_repository)
                     },
-                    None,
+                    Some(format!(
+                        "Set image_repository for group '{}' in domain '{}' to:\n  {}",
+                        group_name, domain_name, image_repository
+                    )),
                 )?;
             }
-            AddSvcCommand::Volume {
+            SetGrpCommand::ServeCommand {
                 domain_name,
                 group_name,
-                service_name,
-                container_dir,
-                host_dir,
+                serve_command,
                 location,
             } => {
                 config_mutate(
                     config,
-                    p,
+                    paths,
                     |c| {
                         c.ensure_domain_exists(&domain_name, location.as_deref())?;
-                        c.add_service_volume(
+                        c.set_group_serve_command(&domain_name, &group_name, &serve_command)
+                    },
+                    Some(format!(
+                        "Set serve_command for group '{}' in domain '{}' to:\n  {}",
+                        group_name, domain_name, serve_command
+                    )),
+                )?;
+            }
+            SetGrpCommand::ShellCommand {
+                domain_name,
+                group_name,
+                shell_command,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_group_shell_command(&domain_name, &group_name, &shell_command)
+                    },
+                    Some(format!(
+                        "Set shell_command for group '{}' in domain '{}' to:\n  {}",
+                        group_name, domain_name, shell_command
+                    )),
+                )?;
+            }
+            SetGrpCommand::Platform {
+                domain_name,
+                group_name,
+                platform,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_group_platform(&domain_name, &group_name, &platform)
+                    },
+                    Some(format!(
+                        "Set platform for group '{}' in domain '{}' to:\n  {}",
+                        group_name, domain_name, platform
+                    )),
+                )?;
+            }
+            SetGrpCommand::DefaultContainerImage {
+                domain_name,
+                group_name,
+                default_container_image,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_group_default_container_image(
                             &domain_name,
                             &group_name,
-                            &service_name,
-                            &container_dir,
-                            &host_dir,
+                            &default_container_image,
                         )
                     },
-                    None,
+                    Some(format!(
+                        "Set default_container_image for group '{}' in domain '{}' to:\n  {}",
+                        group_name, domain_name, default_container_image
+                    )),
                 )?;
             }
-        },
-    }
-
-    Ok(())
-}
-
-pub fn cmd_rm(cmd: RmCommand, paths: &DarpPaths, config: &mut Config) -> anyhow::Result<()> {
-    let p = &paths.config_path;
-    match cmd {
-        RmCommand::PodmanMachine {} => {
+            SetGrpCommand::ConnectionType {
+                domain_name,
+                group_name,
+                connection_type,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.set_group_connection_type(&domain_name, &group_name, &connection_type)
+                    },
+                    Some(format!(
+                        "Set connection_type for group '{}' in domain '{}' to:\n  {}",
+                        group_name, domain_name, connection_type
+                    )),
+                )?;
+            }
+        },
+        SetCommand::UrlsInHosts { value } => {
+            let v = config.parse_bool(&value)?;
             config_mutate(
                 config,
-                p,
+                paths,
                 |c| {
-                    c.podman_machine = None;
+                    c.urls_in_hosts = Some(v);
                     Ok(())
                 },
-                None,
+                Some(format!(
+                    "urls_in_hosts has been {} (stored in {}). Next 'darp deploy' will sync /etc/hosts accordingly.",
+                    if v { "enabled" } else { "disabled" },
+                    p.display()
+                )),
             )?;
         }
-        RmCommand::PreConfig { location } => {
+        SetCommand::Wsl { value } => {
+            let v = config.parse_bool(&value)?;
             config_mutate(
                 config,
-                p,
-                |c| c.rm_pre_config(&location),
-                Some(format!("Removed pre_config '{}'", location)),
+                paths,
+                |c| {
+                    c.wsl = Some(v);
+                    Ok(())
+                },
+                Some(format!(
+                    "WSL mode has been {} (stored in {}). When enabled alongside urls_in_hosts, 'darp deploy' will also sync /mnt/c/Windows/System32/drivers/etc/hosts.",
+                    if v { "enabled" } else { "disabled" },
+                    p.display()
+                )),
             )?;
         }
-        RmCommand::Domain { name } => {
-            config_mutate(config, p, |c| c.rm_domain(&name), None)?;
+        SetCommand::Mdns { value } => {
+            let v = config.parse_bool(&value)?;
+            config_mutate(
+                config,
+                paths,
+                |c| {
+                    c.mdns = Some(v);
+                    Ok(())
+                },
+                Some(format!(
+                    "mDNS names have been {} (stored in {}). Run 'darp deploy', then 'darp mdns' to advertise them.",
+                    if v { "enabled" } else { "disabled" },
+                    p.display()
+                )),
+            )?;
         }
-        RmCommand::Group {
-            domain_name,
-            group_name,
+        SetCommand::DarpNetwork { value } => {
+            let v = config.parse_bool(&value)?;
+            config_mutate(
+                config,
+                paths,
+                |c| {
+                    c.darp_network = Some(v);
+                    Ok(())
+                },
+                Some(format!(
+                    "darp_network has been {} (stored in {}). Run 'darp deploy' to switch the reverse proxy over.",
+                    if v { "enabled" } else { "disabled" },
+                    p.display()
+                )),
+            )?;
+        }
+        SetCommand::StateDir { path } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.set_state_dir(&path),
+                Some(format!(
+                    "darp will keep its state in {} (stored in {}). Move secrets.json and backups/ over from {}, then run 'darp install' and 'darp deploy' to write the rest there.",
+                    path,
+                    p.display(),
+                    paths.darp_root.display()
+                )),
+            )?;
+        }
+        SetCommand::ContainerName { template } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.set_container_name(&template),
+                Some(format!(
+                    "Service containers will be named '{}' (stored in {}). Stop running services before switching; new names apply from their next 'darp serve'.",
+                    template,
+                    p.display()
+                )),
+            )?;
+        }
+        SetCommand::EngineConcurrency { limit } => {
+            let message = if limit == 0 {
+                "Engine operations are no longer limited".to_string()
+            } else {
+                format!("At most {} engine operation(s) will run at once", limit)
+            };
+            config_mutate(
+                config,
+                paths,
+                |c| c.set_engine_concurrency(limit),
+                Some(message),
+            )?;
+        }
+        SetCommand::Remote {
+            host,
+            address,
+            url,
+            resolver,
         } => {
-            config_mutate(config, p, |c| c.rm_group(&domain_name, &group_name), None)?;
+            config_mutate(
+                config,
+                paths,
+                |c| {
+                    c.remote = Some(config::Remote {
+                        host: host.clone(),
+                        address: address.clone(),
+                        url,
+                        resolver: resolver.then_some(true),
+                    });
+                    Ok(())
+                },
+                Some(format!(
+                    "Remote engine set to {} ({}). Run 'darp install' then 'darp deploy' to move darp there.",
+                    host, address
+                )),
+            )?;
         }
-        RmCommand::Service {
-            domain_name,
-            group_name,
-            service_name,
+        SetCommand::SharedHost {
+            user,
+            port_base,
+            proxy_dir,
         } => {
             config_mutate(
                 config,
-                p,
-                |c| c.rm_service(&domain_name, &group_name, &service_name),
-                None,
+                paths,
+                |c| c.set_shared_host(&user, port_base, proxy_dir.as_deref()),
+                Some(format!(
+                    "Services will be served as <service>.<domain>.{}.test on ports {}-{}. Stop running services, then run 'darp deploy'.",
+                    user,
+                    port_base,
+                    port_base + (config::SHARED_PORT_RANGE - 1)
+                )),
             )?;
         }
-        RmCommand::Dom { cmd } => match cmd {
-            RmDomCommand::DefaultEnvironment { domain_name } => {
+    }
+
+    Ok(())
+}
+
+pub fn cmd_add(cmd: AddCommand, paths: &DarpPaths, config: &mut Config) -> anyhow::Result<()> {
+    let p = &paths.config_path;
+    match cmd {
+        AddCommand::PreConfig {
+            location,
+            repo_location,
+        } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.add_pre_config(&location, repo_location.as_deref()),
+                Some(format!("Added pre_config '{}'", location)),
+            )?;
+        }
+        AddCommand::Alias { name, command } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.add_alias(&name, &command, &alias::builtin_names()),
+                Some(format!("Added alias '{}' for 'darp {}'", name, command)),
+            )?;
+            refresh_shell_completions(&Config::load_merged(p)?.aliases.unwrap_or_default())?;
+        }
+        AddCommand::ExtraHost { host, ip } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.add_extra_host(&host, &ip),
+                Some(format!(
+                    "Added hosts entry {} -> {}. Run 'darp deploy' to apply it.",
+                    host, ip
+                )),
+            )?;
+        }
+        AddCommand::HelperArg { helper, arg } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.add_helper_run_arg(&helper, &arg),
+                Some(format!(
+                    "Added run arg '{}' to {}. Run 'darp deploy' to apply it.",
+                    arg, helper
+                )),
+            )?;
+        }
+        AddCommand::EngineArg { arg } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.add_engine_arg(&arg),
+                Some(format!(
+                    "Added engine arg '{}'. Containers started from now on get it; run 'darp deploy' to recreate the helpers.",
+                    arg
+                )),
+            )?;
+        }
+        AddCommand::Dom { cmd } => match cmd {
+            AddDomCommand::Portmap {
+                domain_name,
+                host_port,
+                container_port,
+                location,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_default_environment(&domain_name),
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_domain_portmap(&domain_name, &host_port, &container_port)
+                    },
+                    None,
+                )?;
+            }
+            AddDomCommand::ExtraHost {
+                domain_name,
+                host,
+                ip,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_domain_extra_host(&domain_name, &host, &ip)
+                    },
+                    Some(format!(
+                        "Added hosts entry {} -> {} to domain '{}'. Run 'darp deploy' to apply it.",
+                        host, ip, domain_name
+                    )),
+                )?;
+            }
+            AddDomCommand::Variable {
+                domain_name,
+                name,
+                value,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_domain_variable(&domain_name, &name, &value)
+                    },
+                    None,
+                )?;
+            }
+            AddDomCommand::Volume {
+                domain_name,
+                container_dir,
+                host_dir,
+                read_only,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_domain_volume(&domain_name, &container_dir, &host_dir, read_only)
+                    },
+                    None,
+                )?;
+            }
+        },
+        AddCommand::Grp { cmd } => match cmd {
+            AddGrpCommand::Portmap {
+                domain_name,
+                group_name,
+                host_port,
+                container_port,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_group_portmap(&domain_name, &group_name, &host_port, &container_port)
+                    },
+                    None,
+                )?;
+            }
+            AddGrpCommand::Variable {
+                domain_name,
+                group_name,
+                name,
+                value,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_group_variable(&domain_name, &group_name, &name, &value)
+                    },
+                    None,
+                )?;
+            }
+            AddGrpCommand::Volume {
+                domain_name,
+                group_name,
+                container_dir,
+                host_dir,
+                read_only,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_group_volume(
+                            &domain_name,
+                            &group_name,
+                            &container_dir,
+                            &host_dir,
+                            read_only,
+                        )
+                    },
+                    None,
+                )?;
+            }
+        },
+        AddCommand::Env { cmd } => match cmd {
+            AddEnvCommand::Portmap {
+                environment,
+                host_port,
+                container_port,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.add_env_portmap(&environment, &host_port, &container_port),
+                    None,
+                )?;
+            }
+            AddEnvCommand::Variable {
+                environment,
+                name,
+                value,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.add_env_variable(&environment, &name, &value),
+                    None,
+                )?;
+            }
+            AddEnvCommand::Volume {
+                environment,
+                container_dir,
+                host_dir,
+                read_only,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.add_volume(&environment, &container_dir, &host_dir, read_only),
+                    None,
+                )?;
+            }
+        },
+        AddCommand::Svc { cmd } => match cmd {
+            AddSvcCommand::Portmap {
+                domain_name,
+                group_name,
+                service_name,
+                host_port,
+                container_port,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_portmap(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &host_port,
+                            &container_port,
+                        )
+                    },
+                    None,
+                )?;
+            }
+            AddSvcCommand::Variable {
+                domain_name,
+                group_name,
+                service_name,
+                name,
+                value,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_variable(&domain_name, &group_name, &service_name, &name, &value)
+                    },
+                    None,
+                )?;
+            }
+            AddSvcCommand::Volume {
+                domain_name,
+                group_name,
+                service_name,
+                container_dir,
+                host_dir,
+                read_only,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_volume(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &container_dir,
+                            &host_dir,
+                            read_only,
+                        )
+                    },
+                    None,
+                )?;
+            }
+            AddSvcCommand::Tag {
+                domain_name,
+                group_name,
+                service_name,
+                tag,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_tag(&domain_name, &group_name, &service_name, &tag)
+                    },
+                    None,
+                )?;
+            }
+            AddSvcCommand::Alias {
+                domain_name,
+                group_name,
+                service_name,
+                hostname,
+                location,
+            } => {
+                let msg = format!(
+                    "{}.{} also answers on {}. Run 'darp deploy' to apply it.",
+                    service_name, domain_name, hostname
+                );
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_alias(&domain_name, &group_name, &service_name, &hostname)
+                    },
+                    Some(msg),
+                )?;
+            }
+            AddSvcCommand::CustomDomain {
+                domain_name,
+                group_name,
+                service_name,
+                hostname,
+                cert,
+                key,
+                location,
+            } => {
+                let msg = format!(
+                    "{} now routes to {}.{}. Run 'darp deploy' to apply it.",
+                    hostname, service_name, domain_name
+                );
+                // The proxy mounts these, so relative paths can't stay relative.
+                let absolute = |path: Option<String>| -> anyhow::Result<Option<String>> {
+                    match path {
+                        Some(p) if !p.contains("{home}") => Ok(Some(
+                            std::path::absolute(&p)?.to_string_lossy().into_owned(),
+                        )),
+                        other => Ok(other),
+                    }
+                };
+                let (cert, key) = (absolute(cert)?, absolute(key)?);
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_custom_domain(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &hostname,
+                            cert.as_deref(),
+                            key.as_deref(),
+                        )
+                    },
+                    Some(msg),
+                )?;
+            }
+            AddSvcCommand::PreServe {
+                domain_name,
+                group_name,
+                service_name,
+                command,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_pre_serve(&domain_name, &group_name, &service_name, &command)
+                    },
+                    None,
+                )?;
+            }
+            AddSvcCommand::RunArg {
+                domain_name,
+                group_name,
+                service_name,
+                arg,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_run_arg(&domain_name, &group_name, &service_name, &arg)
+                    },
+                    Some(format!(
+                        "Added run arg '{}' to service '{}.{}'",
+                        arg, domain_name, service_name
+                    )),
+                )?;
+            }
+            AddSvcCommand::PostServe {
+                domain_name,
+                group_name,
+                service_name,
+                command,
+                location,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| {
+                        c.ensure_domain_exists(&domain_name, location.as_deref())?;
+                        c.add_service_post_serve(&domain_name, &group_name, &service_name, &command)
+                    },
+                    None,
+                )?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// `darp config move`: record where a folder went, then redeploy so the proxy and hosts
+/// files follow it.
+pub fn cmd_move(cmd: MoveCommand, paths: &DarpPaths) -> anyhow::Result<()> {
+    match cmd {
+        MoveCommand::Domain {
+            name,
+            new_location,
+            no_deploy,
+        } => {
+            let mut config = Config::load(&paths.config_path)?;
+            let cwd = std::env::current_dir()?;
+            config_mutate(
+                &mut config,
+                paths,
+                |c| c.move_domain(&name, &new_location, &cwd),
+                None,
+            )?;
+            let location = &config.domains.as_ref().unwrap()[&name].location;
+            println!("Moved domain '{}' to:\n  {}", name, location);
+            if no_deploy {
+                return Ok(());
+            }
+
+            let config = Config::load_merged(&paths.config_path)?;
+            let engine_kind = EngineKind::from_config(&config);
+            let engine = Engine::new(engine_kind.clone(), &config)?;
+            if let Err(e) = engine.require_ready() {
+                println!("Not redeploying ({}); run 'darp deploy' once it's up.", e);
+                return Ok(());
+            }
+            let os = OsIntegration::new(paths, &config, &engine_kind);
+            cmd_deploy(paths, &config, &os, &engine, false)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn cmd_rm(cmd: RmCommand, paths: &DarpPaths, config: &mut Config) -> anyhow::Result<()> {
+    let p = &paths.config_path;
+    match cmd {
+        RmCommand::PodmanMachine {} => {
+            config_mutate(
+                config,
+                paths,
+                |c| {
+                    c.podman_machine = None;
+                    Ok(())
+                },
+                None,
+            )?;
+        }
+        RmCommand::Remote {} => {
+            config_mutate(
+                config,
+                paths,
+                |c| {
+                    if c.remote.take().is_none() {
+                        return Err(anyhow::anyhow!("No remote is configured."));
+                    }
+                    Ok(())
+                },
+                Some(
+                    "Remote removed; darp uses the local engine again. Run 'darp install' then 'darp deploy'."
+                        .to_string(),
+                ),
+            )?;
+        }
+        RmCommand::StateDir {} => {
+            config_mutate(config, paths, |c| c.rm_state_dir(), None)?;
+        }
+        RmCommand::SharedHost {} => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_shared_host(),
+                Some(
+                    "Shared host settings removed. Stop running services, then run 'darp deploy'."
+                        .to_string(),
+                ),
+            )?;
+        }
+        RmCommand::ContainerName {} => {
+            config_mutate(config, paths, |c| c.rm_container_name(), None)?;
+        }
+        RmCommand::EngineConcurrency {} => {
+            config_mutate(config, paths, |c| c.rm_engine_concurrency(), None)?;
+        }
+        RmCommand::Alias { name } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_alias(&name),
+                Some(format!("Removed alias '{}'", name)),
+            )?;
+            refresh_shell_completions(&Config::load_merged(p)?.aliases.unwrap_or_default())?;
+        }
+        RmCommand::ExtraHost { host } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_extra_host(&host),
+                Some(format!("Removed hosts entry {}", host)),
+            )?;
+        }
+        RmCommand::HelperArg { helper, arg } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_helper_run_arg(&helper, &arg),
+                Some(format!("Removed run arg '{}' from {}", arg, helper)),
+            )?;
+        }
+        RmCommand::EngineArg { arg } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_engine_arg(&arg),
+                Some(format!("Removed engine arg '{}'", arg)),
+            )?;
+        }
+        RmCommand::PreConfig { location } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_pre_config(&location),
+                Some(format!("Removed pre_config '{}'", location)),
+            )?;
+        }
+        RmCommand::Domain { name } => {
+            config_mutate(config, paths, |c| c.rm_domain(&name), None)?;
+        }
+        RmCommand::Group {
+            domain_name,
+            group_name,
+        } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_group(&domain_name, &group_name),
+                None,
+            )?;
+        }
+        RmCommand::Service {
+            domain_name,
+            group_name,
+            service_name,
+        } => {
+            config_mutate(
+                config,
+                paths,
+                |c| c.rm_service(&domain_name, &group_name, &service_name),
+                None,
+            )?;
+        }
+        RmCommand::Dom { cmd } => match cmd {
+            RmDomCommand::DefaultEnvironment { domain_name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_default_environment(&domain_name),
+                    Some(format!(
+                        "Removed default_environment for domain '{}'",
+                        domain_name
+                    )),
+                )?;
+            }
+            RmDomCommand::Portmap {
+                domain_name,
+                host_port,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_portmap(&domain_name, &host_port),
+                    None,
+                )?;
+            }
+            RmDomCommand::ExtraHost { domain_name, host } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_extra_host(&domain_name, &host),
+                    None,
+                )?;
+            }
+            RmDomCommand::Variable { domain_name, name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_variable(&domain_name, &name),
+                    None,
+                )?;
+            }
+            RmDomCommand::Volume {
+                domain_name,
+                container_dir,
+                host_dir,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_volume(&domain_name, &container_dir, &host_dir),
+                    None,
+                )?;
+            }
+            RmDomCommand::ServeCommand { domain_name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_serve_command(&domain_name),
+                    None,
+                )?;
+            }
+            RmDomCommand::ShellCommand { domain_name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_shell_command(&domain_name),
+                    None,
+                )?;
+            }
+            RmDomCommand::ImageRepository { domain_name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_image_repository(&domain_name),
+                    None,
+                )?;
+            }
+            RmDomCommand::Platform { domain_name } => {
+                config_mutate(config, paths, |c| c.rm_domain_platform(&domain_name), None)?;
+            }
+            RmDomCommand::DefaultContainerImage { domain_name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_default_container_image(&domain_name),
+                    None,
+                )?;
+            }
+            RmDomCommand::ConnectionType { domain_name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_connection_type(&domain_name),
+                    None,
+                )?;
+            }
+            RmDomCommand::Network { domain_name } => {
+                config_mutate(config, paths, |c| c.rm_domain_network(&domain_name), None)?;
+            }
+            RmDomCommand::Pod { domain_name } => {
+                config_mutate(config, paths, |c| c.rm_domain_pod(&domain_name), None)?;
+            }
+            RmDomCommand::Engine { domain_name } => {
+                config_mutate(config, paths, |c| c.rm_domain_engine(&domain_name), None)?;
+            }
+            RmDomCommand::Canary {
+                domain_name,
+                service_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_domain_canary(&domain_name, &service_name),
+                    None,
+                )?;
+            }
+        },
+        RmCommand::Grp { cmd } => match cmd {
+            RmGrpCommand::DefaultEnvironment {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_default_environment(&domain_name, &group_name),
+                    Some(format!(
+                        "Removed default_environment for group '{}' in domain '{}'",
+                        group_name, domain_name
+                    )),
+                )?;
+            }
+            RmGrpCommand::Portmap {
+                domain_name,
+                group_name,
+                host_port,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_portmap(&domain_name, &group_name, &host_port),
+                    None,
+                )?;
+            }
+            RmGrpCommand::Variable {
+                domain_name,
+                group_name,
+                name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_variable(&domain_name, &group_name, &name),
+                    None,
+                )?;
+            }
+            RmGrpCommand::Volume {
+                domain_name,
+                group_name,
+                container_dir,
+                host_dir,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_volume(&domain_name, &group_name, &container_dir, &host_dir),
+                    None,
+                )?;
+            }
+            RmGrpCommand::ServeCommand {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_serve_command(&domain_name, &group_name),
+                    None,
+                )?;
+            }
+            RmGrpCommand::ShellCommand {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_shell_command(&domain_name, &group_name),
+                    None,
+                )?;
+            }
+            RmGrpCommand::ImageRepository {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_image_repository(&domain_name, &group_name),
+                    None,
+                )?;
+            }
+            RmGrpCommand::Platform {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_platform(&domain_name, &group_name),
+                    None,
+                )?;
+            }
+            RmGrpCommand::DefaultContainerImage {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_default_container_image(&domain_name, &group_name),
+                    None,
+                )?;
+            }
+            RmGrpCommand::ConnectionType {
+                domain_name,
+                group_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_group_connection_type(&domain_name, &group_name),
+                    None,
+                )?;
+            }
+        },
+        RmCommand::Env { cmd } => match cmd {
+            RmEnvCommand::Portmap {
+                environment,
+                host_port,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_env_portmap(&environment, &host_port),
+                    None,
+                )?;
+            }
+            RmEnvCommand::Variable { environment, name } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_env_variable(&environment, &name),
+                    None,
+                )?;
+            }
+            RmEnvCommand::Volume {
+                environment,
+                container_dir,
+                host_dir,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_volume(&environment, &container_dir, &host_dir),
+                    None,
+                )?;
+            }
+            RmEnvCommand::ServeCommand { environment } => {
+                config_mutate(config, paths, |c| c.rm_serve_command(&environment), None)?;
+            }
+            RmEnvCommand::ShellCommand { environment } => {
+                config_mutate(config, paths, |c| c.rm_shell_command(&environment), None)?;
+            }
+            RmEnvCommand::ImageRepository { environment } => {
+                config_mutate(config, paths, |c| c.rm_image_repository(&environment), None)?;
+            }
+            RmEnvCommand::Platform { environment } => {
+                config_mutate(config, paths, |c| c.rm_platform(&environment), None)?;
+            }
+            RmEnvCommand::DefaultContainerImage { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_default_container_image(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::ConnectionType { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_connection_type(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::Limits { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_limits(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::Restart { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_restart(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::MapUser { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_map_user(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::ReadOnlyApp { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_read_only_app(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::ShellImage { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_shell_image(&environment),
+                    None,
+                )?;
+            }
+            RmEnvCommand::Engine { environment } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_environment_engine(&environment),
+                    None,
+                )?;
+            }
+        },
+        RmCommand::Svc { cmd } => match cmd {
+            RmSvcCommand::DefaultEnvironment {
+                domain_name,
+                group_name,
+                service_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_service_default_environment(&domain_name, &group_name, &service_name),
                     Some(format!(
-                        "Removed default_environment for domain '{}'",
-                        domain_name
+                        "Removed default_environment for service '{}.{}'",
+                        domain_name, service_name
                     )),
                 )?;
             }
-            RmDomCommand::Portmap {
+            RmSvcCommand::Portmap {
                 domain_name,
+                group_name,
+                service_name,
                 host_port,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_portmap(&domain_name, &host_port),
+                    paths,
+                    |c| c.rm_portmap(&domain_name, &group_name, &service_name, &host_port),
                     None,
                 )?;
             }
-            RmDomCommand::Variable { domain_name, name } => {
+            RmSvcCommand::Variable {
+                domain_name,
+                group_name,
+                service_name,
+                name,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_variable(&domain_name, &name),
+                    paths,
+                    |c| c.rm_variable(&domain_name, &group_name, &service_name, &name),
                     None,
                 )?;
             }
-            RmDomCommand::Volume {
+            RmSvcCommand::Volume {
                 domain_name,
+                group_name,
+                service_name,
                 container_dir,
                 host_dir,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_volume(&domain_name, &container_dir, &host_dir),
+                    paths,
+                    |c| {
+                        c.rm_service_volume(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &container_dir,
+                            &host_dir,
+                        )
+                    },
                     None,
                 )?;
             }
-            RmDomCommand::ServeCommand { domain_name } => {
-                config_mutate(config, p, |c| c.rm_domain_serve_command(&domain_name), None)?;
-            }
-            RmDomCommand::ShellCommand { domain_name } => {
-                config_mutate(config, p, |c| c.rm_domain_shell_command(&domain_name), None)?;
-            }
-            RmDomCommand::ImageRepository { domain_name } => {
+            RmSvcCommand::Tag {
+                domain_name,
+                group_name,
+                service_name,
+                tag,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_image_repository(&domain_name),
+                    paths,
+                    |c| c.rm_service_tag(&domain_name, &group_name, &service_name, &tag),
                     None,
                 )?;
             }
-            RmDomCommand::Platform { domain_name } => {
-                config_mutate(config, p, |c| c.rm_domain_platform(&domain_name), None)?;
-            }
-            RmDomCommand::DefaultContainerImage { domain_name } => {
+            RmSvcCommand::Alias {
+                domain_name,
+                group_name,
+                service_name,
+                hostname,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_default_container_image(&domain_name),
+                    paths,
+                    |c| c.rm_service_alias(&domain_name, &group_name, &service_name, &hostname),
                     None,
                 )?;
             }
-            RmDomCommand::ConnectionType { domain_name } => {
+            RmSvcCommand::CustomDomain {
+                domain_name,
+                group_name,
+                service_name,
+                hostname,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_domain_connection_type(&domain_name),
+                    paths,
+                    |c| {
+                        c.rm_service_custom_domain(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                            &hostname,
+                        )
+                    },
                     None,
                 )?;
             }
-        },
-        RmCommand::Grp { cmd } => match cmd {
-            RmGrpCommand::DefaultEnvironment {
+            RmSvcCommand::PreServe {
                 domain_name,
                 group_name,
+                service_name,
+                command,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_default_environment(&domain_name, &group_name),
-                    Some(format!(
-                        "Removed default_environment for group '{}' in domain '{}'",
-                        group_name, domain_name
-                    )),
+                    paths,
+                    |c| c.rm_service_pre_serve(&domain_name, &group_name, &service_name, &command),
+                    None,
                 )?;
             }
-            RmGrpCommand::Portmap {
+            RmSvcCommand::RunArg {
                 domain_name,
                 group_name,
-                host_port,
+                service_name,
+                arg,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_portmap(&domain_name, &group_name, &host_port),
+                    paths,
+                    |c| c.rm_service_run_arg(&domain_name, &group_name, &service_name, &arg),
                     None,
                 )?;
             }
-            RmGrpCommand::Variable {
+            RmSvcCommand::PostServe {
                 domain_name,
                 group_name,
-                name,
+                service_name,
+                command,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_variable(&domain_name, &group_name, &name),
+                    paths,
+                    |c| c.rm_service_post_serve(&domain_name, &group_name, &service_name, &command),
                     None,
                 )?;
             }
-            RmGrpCommand::Volume {
+            RmSvcCommand::ServeCommand {
                 domain_name,
                 group_name,
-                container_dir,
-                host_dir,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_volume(&domain_name, &group_name, &container_dir, &host_dir),
+                    paths,
+                    |c| c.rm_service_serve_command(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmGrpCommand::ServeCommand {
+            RmSvcCommand::ShellCommand {
                 domain_name,
                 group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_serve_command(&domain_name, &group_name),
+                    paths,
+                    |c| c.rm_service_shell_command(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmGrpCommand::ShellCommand {
+            RmSvcCommand::ImageRepository {
                 domain_name,
                 group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_shell_command(&domain_name, &group_name),
+                    paths,
+                    |c| c.rm_service_image_repository(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmGrpCommand::ImageRepository {
+            RmSvcCommand::Platform {
                 domain_name,
                 group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_image_repository(&domain_name, &group_name),
+                    paths,
+                    |c| c.rm_service_platform(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmGrpCommand::Platform {
+            RmSvcCommand::DefaultContainerImage {
                 domain_name,
                 group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_platform(&domain_name, &group_name),
+                    paths,
+                    |c| {
+                        c.rm_service_default_container_image(
+                            &domain_name,
+                            &group_name,
+                            &service_name,
+                        )
+                    },
                     None,
                 )?;
             }
-            RmGrpCommand::DefaultContainerImage {
+            RmSvcCommand::ConnectionType {
                 domain_name,
                 group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_default_container_image(&domain_name, &group_name),
+                    paths,
+                    |c| c.rm_service_connection_type(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmGrpCommand::ConnectionType {
+            RmSvcCommand::EnvFile {
                 domain_name,
                 group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_group_connection_type(&domain_name, &group_name),
+                    paths,
+                    |c| c.rm_service_env_file(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-        },
-        RmCommand::Env { cmd } => match cmd {
-            RmEnvCommand::Portmap {
-                environment,
-                host_port,
+            RmSvcCommand::Healthcheck {
+                domain_name,
+                group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_env_portmap(&environment, &host_port),
+                    paths,
+                    |c| c.rm_service_healthcheck(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmEnvCommand::Variable { environment, name } => {
-                config_mutate(config, p, |c| c.rm_env_variable(&environment, &name), None)?;
-            }
-            RmEnvCommand::Volume {
-                environment,
-                container_dir,
-                host_dir,
+            RmSvcCommand::Description {
+                domain_name,
+                group_name,
+                service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_volume(&environment, &container_dir, &host_dir),
+                    paths,
+                    |c| c.rm_service_description(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmEnvCommand::ServeCommand { environment } => {
-                config_mutate(config, p, |c| c.rm_serve_command(&environment), None)?;
-            }
-            RmEnvCommand::ShellCommand { environment } => {
-                config_mutate(config, p, |c| c.rm_shell_command(&environment), None)?;
-            }
-            RmEnvCommand::ImageRepository { environment } => {
-                config_mutate(config, p, |c| c.rm_image_repository(&environment), None)?;
+            RmSvcCommand::LiveReload {
+                domain_name,
+                group_name,
+                service_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_service_live_reload(&domain_name, &group_name, &service_name),
+                    None,
+                )?;
             }
-            RmEnvCommand::Platform { environment } => {
-                config_mutate(config, p, |c| c.rm_platform(&environment), None)?;
+            RmSvcCommand::Mirror {
+                domain_name,
+                group_name,
+                service_name,
+            } => {
+                config_mutate(
+                    config,
+                    paths,
+                    |c| c.rm_service_mirror(&domain_name, &group_name, &service_name),
+                    None,
+                )?;
             }
-            RmEnvCommand::DefaultContainerImage { environment } => {
+            RmSvcCommand::Protocol {
+                domain_name,
+                group_name,
+                service_name,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_default_container_image(&environment),
+                    paths,
+                    |c| c.rm_service_protocol(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmEnvCommand::ConnectionType { environment } => {
+            RmSvcCommand::Stream {
+                domain_name,
+                group_name,
+                service_name,
+            } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_environment_connection_type(&environment),
+                    paths,
+                    |c| c.rm_service_stream(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-        },
-        RmCommand::Svc { cmd } => match cmd {
-            RmSvcCommand::DefaultEnvironment {
+            RmSvcCommand::HostPort {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_service_default_environment(&domain_name, &group_name, &service_name),
-                    Some(format!(
-                        "Removed default_environment for service '{}.{}'",
-                        domain_name, service_name
-                    )),
+                    paths,
+                    |c| c.rm_service_host_port(&domain_name, &group_name, &service_name),
+                    None,
                 )?;
             }
-            RmSvcCommand::Portmap {
+            RmSvcCommand::Replicas {
                 domain_name,
                 group_name,
                 service_name,
-                host_port,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_portmap(&domain_name, &group_name, &service_name, &host_port),
+                    paths,
+                    |c| c.rm_service_replicas(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::Variable {
+            RmSvcCommand::StartOrder {
                 domain_name,
                 group_name,
                 service_name,
-                name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_variable(&domain_name, &group_name, &service_name, &name),
+                    paths,
+                    |c| c.rm_service_start_order(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::Volume {
+            RmSvcCommand::Restart {
                 domain_name,
                 group_name,
                 service_name,
-                container_dir,
-                host_dir,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| {
-                        c.rm_service_volume(
-                            &domain_name,
-                            &group_name,
-                            &service_name,
-                            &container_dir,
-                            &host_dir,
-                        )
-                    },
+                    paths,
+                    |c| c.rm_service_restart(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::ServeCommand {
+            RmSvcCommand::MapUser {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_service_serve_command(&domain_name, &group_name, &service_name),
+                    paths,
+                    |c| c.rm_service_map_user(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::ShellCommand {
+            RmSvcCommand::ReadOnlyApp {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_service_shell_command(&domain_name, &group_name, &service_name),
+                    paths,
+                    |c| c.rm_service_read_only_app(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::ImageRepository {
+            RmSvcCommand::Entrypoint {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_service_image_repository(&domain_name, &group_name, &service_name),
+                    paths,
+                    |c| c.rm_service_entrypoint(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::Platform {
+            RmSvcCommand::ShellImage {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_service_platform(&domain_name, &group_name, &service_name),
+                    paths,
+                    |c| c.rm_service_shell_image(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::DefaultContainerImage {
+            RmSvcCommand::Limits {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| {
-                        c.rm_service_default_container_image(
-                            &domain_name,
-                            &group_name,
-                            &service_name,
-                        )
-                    },
+                    paths,
+                    |c| c.rm_service_limits(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
-            RmSvcCommand::ConnectionType {
+            RmSvcCommand::ImageBuild {
                 domain_name,
                 group_name,
                 service_name,
             } => {
                 config_mutate(
                     config,
-                    p,
-                    |c| c.rm_service_connection_type(&domain_name, &group_name, &service_name),
+                    paths,
+                    |c| c.rm_service_image_build(&domain_name, &group_name, &service_name),
                     None,
                 )?;
             }
@@ -1295,17 +2648,11 @@ pub fn cmd_rm(cmd: RmCommand, paths: &DarpPaths, config: &mut Config) -> anyhow:
 }
 
 pub fn cmd_show(environment_cli: Option<String>, config: &Config) -> anyhow::Result<()> {
-    let ctx = config
-        .service_context_from_cwd(environment_cli)
-        .unwrap_or_else(|| {
-            eprintln!("Current directory does not exist in any darp domain configuration.");
-            std::process::exit(1);
-        });
+    let ctx = config.require_service_context(environment_cli)?;
 
     if let Some(ref env_name) = ctx.environment_name {
         if ctx.environment.is_none() {
-            eprintln!("Environment '{}' does not exist.", env_name);
-            std::process::exit(1);
+            return Err(DarpError::EnvironmentNotFound(env_name.clone()).into());
         }
     }
 
@@ -1324,6 +2671,44 @@ pub fn cmd_show(environment_cli: Option<String>, config: &Config) -> anyhow::Res
     Ok(())
 }
 
+/// `darp config rollback`: put the `n`th newest backup back as config.json, or list the
+/// backups. The config being replaced is backed up too, so a rollback can be undone.
+pub fn cmd_rollback(n: usize, list: bool, json: bool, paths: &DarpPaths) -> anyhow::Result<()> {
+    let config_path = &paths.config_path;
+    if list {
+        let backups = backups::list_backups(&paths.backups_dir)?;
+        if json {
+            // Newest first, so an entry's position + 1 is the `n` that restores it.
+            println!("{}", serde_json::to_string_pretty(&backups)?);
+            return Ok(());
+        }
+        if backups.is_empty() {
+            println!("No config backups in {}.", paths.backups_dir.display());
+        }
+        for (i, backup) in backups.iter().enumerate() {
+            println!(
+                "{:>3}) {}",
+                i + 1,
+                backup.file_name().unwrap_or_default().to_string_lossy()
+            );
+        }
+        return Ok(());
+    }
+
+    let backup = backups::nth_backup(&paths.backups_dir, n)?;
+    let data = std::fs::read(&backup)?;
+    serde_json::from_slice::<Config>(&data)
+        .map_err(|e| anyhow::anyhow!("{} is not a valid config: {}", backup.display(), e))?;
+    backups::back_up(&paths.backups_dir, config_path, &data)?;
+    config::write_atomic(config_path, &data)?;
+    println!(
+        "Restored {} from {}. Run 'darp deploy' to apply it; 'darp config rollback' again undoes this.",
+        config_path.display(),
+        backup.display()
+    );
+    Ok(())
+}
+
 pub fn cmd_pull(config: &Config) -> anyhow::Result<()> {
     let entries = match &config.pre_config {
         Some(entries) if !entries.is_empty() => entries,
@@ -1349,6 +2734,7 @@ pub fn cmd_pull(config: &Config) -> anyhow::Result<()> {
             .arg("-C")
             .arg(&resolved)
             .arg("pull")
+            .logged()
             .output();
 
         match output {
@@ -1374,8 +2760,121 @@ pub fn cmd_pull(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn cmd_urls(paths: &DarpPaths, _config: &Config) -> anyhow::Result<()> {
-    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)?;
+/// What `darp urls` lists and how.
+#[derive(Debug, Clone, Default)]
+pub struct UrlsOptions {
+    /// Only this domain.
+    pub domain: Option<String>,
+    /// Only services whose name or host matches this glob (`--filter`).
+    pub filter: Option<String>,
+    /// Only services carrying one of these tags (`--tag`).
+    pub tags: Vec<String>,
+    pub markdown: bool,
+    pub html: bool,
+    pub json: bool,
+    pub plain: bool,
+    /// `localhost` URLs instead of `.test` ones (`--direct`).
+    pub direct: bool,
+    /// QR codes of the URLs, or of one service's (`--qr [service]`).
+    pub qr: Option<Option<String>>,
+}
+
+pub fn cmd_urls(
+    options: UrlsOptions,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let raw_portmap: serde_json::Value = config::read_json(&paths.portmap_path)?;
+    if let Some(domain) = &options.domain {
+        if raw_portmap.get(domain).is_none() {
+            return Err(anyhow::anyhow!(
+                "No services are registered for domain '{}'. Run 'darp deploy' if it was added recently.",
+                domain
+            ));
+        }
+    }
+    let narrow = |portmap: &serde_json::Value| {
+        config.filter_portmap_by_tags(
+            &config::filter_portmap(
+                portmap,
+                options.domain.as_deref(),
+                options.filter.as_deref(),
+            ),
+            &options.tags,
+        )
+    };
+
+    let portmap = narrow(&raw_portmap);
+    if options.markdown {
+        print!("{}", render_urls_markdown(&portmap, config));
+        return Ok(());
+    }
+    if options.html {
+        print!("{}", render_urls_html(&portmap, config));
+        return Ok(());
+    }
+    if options.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&render_urls_json(&portmap))?
+        );
+        return Ok(());
+    }
+    if options.plain {
+        print!("{}", render_urls_plain(&portmap));
+        return Ok(());
+    }
+    if let Some(target) = &options.qr {
+        let lan_ip = lan_ip();
+        match target {
+            Some(target) => {
+                let url = service_browser_url(&portmap, Some(target), None)?;
+                let (_, _, entry) = find_portmap_service(&portmap, Some(target), None)?;
+                let phone_url = phone_url(entry, lan_ip).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{} has no address another device can reach; turn on mdns ('darp config set mdns true') or run 'darp deploy'",
+                        url
+                    )
+                })?;
+                print!("{}  {}\n{}", url, phone_url, qr_code(&phone_url)?);
+            }
+            None => print!("{}", render_urls_qr(&portmap, lan_ip)?),
+        }
+        return Ok(());
+    }
+    if options.direct {
+        // Portmaps from before direct URLs have none; static services never do.
+        let direct = render_urls_direct(&portmap);
+        let undeployed = portmap
+            .as_object()
+            .into_iter()
+            .flat_map(|d| d.values())
+            .flat_map(|g| g.as_object().into_iter().flat_map(|s| s.values()))
+            .any(|entry| entry.get("type").and_then(|t| t.as_str()) != Some("static"));
+        if direct.is_empty() && undeployed {
+            return Err(anyhow::anyhow!(
+                "No direct URLs recorded yet; run 'darp deploy' to assign them"
+            ));
+        }
+        print!("{}", direct);
+        return Ok(());
+    }
+
+    // One `ps` per engine services run on; without a reachable global engine the state
+    // is left out.
+    let running = engine.require_ready().ok().map(|_| {
+        let mut names = engine.running_container_names();
+        for other in Engine::overrides(config).unwrap_or_default() {
+            if other.require_ready().is_ok() {
+                names.extend(other.running_container_names());
+            }
+        }
+        names
+    });
+    let portmap = narrow(&config.with_unassigned_services(&raw_portmap));
+    // `.<user>` before `.test` on a shared host.
+    let user_part = config.user().map(|u| format!(".{}", u)).unwrap_or_default();
     println!();
     if let Some(obj) = portmap.as_object() {
         for (domain_name, domain) in obj.iter() {
@@ -1400,6 +2899,34 @@ pub fn cmd_urls(paths: &DarpPaths, _config: &Config) -> anyhow::Result<()> {
                         let mut entries: Vec<_> = services.iter().collect();
                         entries.sort_by_key(|(k, _)| *k);
                         for (service_name, entry) in entries {
+                            if entry.is_null() {
+                                println!(
+                                    "{}{}.{}{}.test  {}",
+                                    indent,
+                                    service_name.blue(),
+                                    domain_name.green(),
+                                    user_part,
+                                    "[port-unassigned]".yellow()
+                                );
+                                continue;
+                            }
+                            let static_service =
+                                entry.get("type").and_then(|t| t.as_str()) == Some("static");
+                            let state_suffix = match &running {
+                                _ if static_service => format!("  {}", "[static]".dimmed()),
+                                _ if entry.get("host_port").is_some() => {
+                                    format!("  {}", "[host]".dimmed())
+                                }
+                                Some(running)
+                                    if running.contains(
+                                        &engine.service_container_name(domain_name, service_name),
+                                    ) =>
+                                {
+                                    format!("  {}", "[running]".green())
+                                }
+                                Some(_) => format!("  {}", "[stopped]".dimmed()),
+                                None => String::new(),
+                            };
                             // Portmap entries are either a bare number (legacy) or
                             // an object {"port": N, "type": "..."}.
                             let port = entry
@@ -1418,35 +2945,56 @@ pub fn cmd_urls(paths: &DarpPaths, _config: &Config) -> anyhow::Result<()> {
                             match conn_type {
                                 "tcp" => {
                                     println!(
-                                        "{}tcp://{}.{}.test:{}{}",
+                                        "{}tcp://{}.{}{}.test:{}{}{}",
                                         indent,
                                         service_name.blue(),
                                         domain_name.green(),
-                                        port,
-                                        debug_suffix
+                                        user_part,
+                                        config::portmap_tcp_port(entry),
+                                        debug_suffix,
+                                        state_suffix
                                     );
                                 }
                                 "websocket" => {
                                     println!(
-                                        "{}ws://{}.{}.test ({}){}",
+                                        "{}ws://{}.{}{}.test ({}){}{}",
                                         indent,
                                         service_name.blue(),
                                         domain_name.green(),
+                                        user_part,
                                         port,
-                                        debug_suffix
+                                        debug_suffix,
+                                        state_suffix
                                     );
                                 }
                                 _ => {
                                     println!(
-                                        "{}http://{}.{}.test ({}){}",
+                                        "{}http://{}.{}{}.test ({}){}{}",
                                         indent,
                                         service_name.blue(),
                                         domain_name.green(),
+                                        user_part,
                                         port,
-                                        debug_suffix
+                                        debug_suffix,
+                                        state_suffix
                                     );
                                 }
                             }
+                            for alias in config::portmap_aliases(entry) {
+                                let alias_url = match conn_type {
+                                    "tcp" => format!(
+                                        "tcp://{}:{}",
+                                        alias,
+                                        config::portmap_tcp_port(entry)
+                                    ),
+                                    "websocket" => format!("ws://{}", alias),
+                                    _ => format!("http://{}", alias),
+                                };
+                                println!("{}  {} {}", indent, "also".dimmed(), alias_url);
+                            }
+                            for url in config::portmap_custom_urls(entry) {
+                                println!("{}  {} {}", indent, "also".dimmed(), url);
+                            }
                         }
                     }
                 }
@@ -1,106 +1,58 @@
+// commands/deploy.rs
+
 use std::io::Write;
 
-use crate::config::{self, Config, DarpPaths, Domain};
-use crate::engine::{self, Engine};
+use anyhow::anyhow;
+use tracing::warn;
+
+use crate::config::{self, Config, DarpPaths};
+use crate::deploy::{
+    DARP_VHOST, aliased_vhost, build_container_hosts, canary_vhost, collect_debug_ports,
+    collect_host_portmap_ports, container_gateway_ip, custom_domain_urls, custom_domain_vhost,
+    deploy_replicas, direct_port, direct_vhost, domain_location_problems, grpc_vhost,
+    live_reload_enabled, live_reload_vhost, mirror_target_host, mirror_vhost, network_route,
+    portmap_entry, publish_shared_vhosts, replicas_vhost, resolve_deploy_connection_type,
+    service_aliases, service_custom_domains, service_host_port, service_mirror,
+    service_network_resolver, service_protocol, service_stream, service_upstream, service_vhost,
+    static_vhost, sync_url_hosts, write_stream_conf,
+};
+use crate::engine::Engine;
+use crate::error::DarpError;
+use crate::mdns;
 use crate::os::OsIntegration;
-
-/// Build the contents of `~/.darp/hosts_container` — loopback + host-gateway +
-/// one `0.0.0.0 <url>` line per configured service URL.
-pub fn build_container_hosts(gateway_ip: &str, gateway_name: &str, url_lines: &[String]) -> String {
-    let mut out = String::new();
-    out.push_str("127.0.0.1\tlocalhost\n");
-    out.push_str("::1\tlocalhost ip6-localhost ip6-loopback\n");
-    out.push_str(&format!("{gateway_ip}\t{gateway_name}\n"));
-    out.push_str(&url_lines.join(""));
-    out
-}
-
-/// Collect every host port declared in a `host_portmappings` anywhere in the config
-/// (domain/group/service/environment). Debug-port assignment skips these so a debug
-/// listener never clashes with a port darp publishes via `-p`. Templated keys (e.g.
-/// `{debug_port}`) don't parse as numbers and are ignored.
-fn collect_host_portmap_ports(config: &Config) -> std::collections::HashSet<u16> {
-    let mut set = std::collections::HashSet::new();
-    let mut add = |pm: &Option<std::collections::BTreeMap<String, String>>| {
-        if let Some(pm) = pm {
-            for host in pm.keys() {
-                if let Ok(p) = host.parse::<u16>() {
-                    set.insert(p);
-                }
-            }
-        }
-    };
-    if let Some(domains) = &config.domains {
-        for domain in domains.values() {
-            add(&domain.host_portmappings);
-            if let Some(groups) = &domain.groups {
-                for group in groups.values() {
-                    add(&group.host_portmappings);
-                    if let Some(services) = &group.services {
-                        for svc in services.values() {
-                            add(&svc.host_portmappings);
-                        }
-                    }
-                }
-            }
-        }
-    }
-    if let Some(envs) = &config.environments {
-        for env in envs.values() {
-            add(&env.host_portmappings);
-        }
-    }
-    set
-}
-
-/// Collect every already-assigned `debug_port` from a previously-written portmap so
-/// re-deploys keep each service's port stable (assignment order from `read_dir` is
-/// otherwise unstable). Removed services free their port on the next deploy.
-fn collect_debug_ports(portmap: &serde_json::Value) -> std::collections::HashSet<u16> {
-    let mut set = std::collections::HashSet::new();
-    if let Some(domains) = portmap.as_object() {
-        for group_obj in domains.values() {
-            if let Some(groups) = group_obj.as_object() {
-                for svc_obj in groups.values() {
-                    if let Some(services) = svc_obj.as_object() {
-                        for entry in services.values() {
-                            if let Some(p) = entry.get("debug_port").and_then(|v| v.as_u64()) {
-                                set.insert(p as u16);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    set
-}
-
-/// Resolve connection_type by cascading service → group → domain. Environment-layer
-/// overrides are not applied at deploy time (deploy does not operate within an environment).
-/// Returns None if no layer sets it, in which case callers should treat as "http".
-fn resolve_deploy_connection_type(
-    domain: &Domain,
-    group_name: &str,
-    service_name: &str,
-) -> Option<String> {
-    let group = domain.groups.as_ref().and_then(|g| g.get(group_name));
-    let service = group
-        .and_then(|g| g.services.as_ref())
-        .and_then(|s| s.get(service_name));
-
-    service
-        .and_then(|s| s.connection_type.clone())
-        .or_else(|| group.and_then(|g| g.connection_type.clone()))
-        .or_else(|| domain.connection_type.clone())
-}
+use crate::scan::ScanManifest;
+use crate::services_index::write_services_index;
+use crate::timings;
+use crate::urls::direct_url;
 
 pub fn cmd_deploy(
     paths: &DarpPaths,
     config: &Config,
     os: &OsIntegration,
     engine: &Engine,
+    skip_missing: bool,
 ) -> anyhow::Result<()> {
+    let problems = domain_location_problems(config)?;
+    if !problems.is_empty() {
+        let report: Vec<String> = problems
+            .iter()
+            .map(|p| format!("  {}: {} {}", p.domain, p.location.display(), p.reason))
+            .collect();
+        if !skip_missing {
+            return Err(anyhow!(
+                "cannot scan {} domain location(s):\n{}\n\
+Update a moved domain with 'darp config move domain <domain> <path>', remove it with \
+'darp config rm domain <domain>', or pass --skip-missing to deploy without it.",
+                problems.len(),
+                report.join("\n")
+            ));
+        }
+        warn!(
+            "skipping domain(s) that can't be scanned:\n{}",
+            report.join("\n")
+        );
+    }
+
     engine.require_ready()?;
 
     println!("Deploying Container Development\n");
@@ -109,25 +61,20 @@ pub fn cmd_deploy(
     // reach the reverse-proxy without a separate `darp install`.
     os.copy_nginx_conf()?;
 
-    let host_gateway = engine.host_gateway();
-
     let domains = match &config.domains {
         Some(d) if !d.is_empty() => d,
-        _ => {
-            eprintln!("Please configure a domain.");
-            std::process::exit(1);
-        }
+        _ => return Err(DarpError::NoDomains.into()),
     };
 
     let mut hosts_container_lines = Vec::<String>::new();
     let mut portmap = serde_json::Map::new();
 
-    let mut port_number = 50100u16;
+    let mut port_number = config.first_proxy_port();
 
     // Assign a stable, unique debug port per service.
     let old_portmap: serde_json::Value =
         config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
-    let debug_base = config.debug_port_base.unwrap_or(config::DEBUG_PORT_BASE);
+    let debug_base = config.first_debug_port();
     // Ports never handed out: well-known services + any host port darp itself publishes.
     let mut skip_debug_ports = config::well_known_skip_ports();
     skip_debug_ports.extend(collect_host_portmap_ports(config));
@@ -142,27 +89,34 @@ pub fn cmd_deploy(
             .collect();
     let mut next_debug_port = debug_base;
 
-    // HTTP / WebSocket vhost. The Upgrade + Connection headers are harmless for plain HTTP
-    // and allow WebSocket clients (ws://{svc}.{dom}.test) to reach the upstream. The
-    // $connection_upgrade variable is defined in assets/nginx.conf.
-    let host_proxy_template = r#"server {
-    listen 80;
-    server_name {url};
-    location / {
-        proxy_pass http://{host_gateway}:{port}/;
-        proxy_set_header Host $host;
-        proxy_http_version 1.1;
-        proxy_set_header Upgrade $http_upgrade;
-        proxy_set_header Connection $connection_upgrade;
-    }
-}
-"#;
+    let network_timing = timings::phase("network setup");
+    engine.ensure_darp_network()?;
+    let network_resolver = if engine.darp_network {
+        Some(engine.darp_network_dns()?)
+    } else {
+        None
+    };
 
-    // Truncate vhost_container.conf at the start of each deploy so we don't
-    // keep appending duplicate server blocks.
-    std::fs::write(&paths.vhost_container_conf, b"")?;
+    let gateway_ip = container_gateway_ip(paths, engine)?;
+    drop(network_timing);
+
+    // Start vhost_container.conf fresh on each deploy (so server blocks don't pile up)
+    // with darp's own vhost, which serves the service switcher index from www/. A shared
+    // proxy has no one user's www/ to serve, so it goes without.
+    std::fs::create_dir_all(&paths.www_dir)?;
+    let darp_vhost = if config.shared_host.is_some() {
+        ""
+    } else {
+        DARP_VHOST
+    };
+    std::fs::write(&paths.vhost_container_conf, darp_vhost)?;
 
+    let scan_timing = timings::phase("scan");
+    let mut manifest = ScanManifest::load(&paths.scan_manifest_path);
     for (domain_name, domain) in domains.iter() {
+        if problems.iter().any(|p| &p.domain == domain_name) {
+            continue;
+        }
         let location = config::resolve_location(&domain.location)?;
         let mut domain_map = serde_json::Map::new();
 
@@ -186,6 +140,14 @@ pub fn cmd_deploy(
          -> anyhow::Result<()> {
             let connection_type = resolve_deploy_connection_type(domain, group_name, folder_name)
                 .unwrap_or_else(|| "http".to_string());
+            let network_resolver = service_network_resolver(
+                config,
+                engine,
+                network_resolver.as_deref(),
+                domain,
+                group_name,
+                folder_name,
+            );
 
             // Reuse this service's previously-assigned debug port when still valid,
             // else assign the next free one (skipping reserved + well-known ports).
@@ -198,80 +160,193 @@ pub fn cmd_deploy(
             );
 
             // Record port (and type) in portmap.json. run.rs and cmd_urls read this back.
-            let mut entry = serde_json::Map::new();
-            entry.insert(
-                "port".to_string(),
-                serde_json::Value::Number((*port_number).into()),
-            );
-            entry.insert(
-                "type".to_string(),
-                serde_json::Value::String(connection_type.clone()),
-            );
-            entry.insert(
-                "debug_port".to_string(),
-                serde_json::Value::Number(debug_port.into()),
+            let replicas = deploy_replicas(
+                engine,
+                domain_name,
+                domain,
+                group_name,
+                folder_name,
+                &connection_type,
+            ) as u16;
+            let replica_ports: Vec<u16> = (*port_number..*port_number + replicas).collect();
+            let mut entry = portmap_entry(*port_number, &connection_type, debug_port);
+            if config.user().is_some() {
+                entry["host"] = config.service_host(folder_name, domain_name).into();
+            }
+            if let Some(stream) = service_stream(domain, group_name, folder_name) {
+                entry["stream_port"] = stream.port.into();
+            }
+            if replicas > 1 {
+                entry["replica_ports"] = replica_ports[1..].into();
+            }
+            let aliases = service_aliases(domain, group_name, folder_name);
+            if !aliases.is_empty() {
+                entry["aliases"] = aliases.into();
+            }
+            let custom_domains = service_custom_domains(domain, group_name, folder_name);
+            if let Some(custom_domains) = custom_domains {
+                entry["custom_urls"] = custom_domain_urls(custom_domains).into();
+            }
+            let mdns_host = (config.mdns == Some(true) && connection_type != "tcp")
+                .then(|| mdns::mdns_host(domain_name, folder_name));
+            if let Some(mdns_host) = &mdns_host {
+                entry["mdns_host"] = mdns_host.clone().into();
+            }
+            let mut host_port = service_host_port(domain, group_name, folder_name);
+            if host_port.is_some() && connection_type == "static" {
+                warn!(
+                    "{}.{} is a static service; its host_port is ignored",
+                    folder_name, domain_name
+                );
+                host_port = None;
+            }
+            if let Some(host_port) = host_port {
+                entry["host_port"] = host_port.into();
+            }
+            let grpc = service_protocol(domain, group_name, folder_name) == "grpc";
+            let split = !grpc
+                && connection_type != "static"
+                && domain
+                    .canaries
+                    .as_ref()
+                    .is_some_and(|c| c.contains_key(folder_name));
+            let routed = connection_type != "tcp"
+                && replicas <= 1
+                && network_route(engine, network_resolver, domain).is_some();
+            let direct = direct_port(
+                &connection_type,
+                host_port,
+                *port_number,
+                routed,
+                split,
+                config.shared_host.is_some(),
             );
+            if let Some((port, _)) = direct {
+                entry["direct_url"] =
+                    direct_url(&connection_type, &config.direct_host(), port).into();
+            }
+            let direct_via_proxy = direct.is_some_and(|(_, via_proxy)| via_proxy);
+            if direct_via_proxy {
+                entry["direct_via_proxy"] = true.into();
+            }
             let group_obj = domain_map
                 .entry(group_name.to_string())
                 .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
             if let Some(group_map) = group_obj.as_object_mut() {
-                group_map.insert(folder_name.to_string(), serde_json::Value::Object(entry));
+                group_map.insert(folder_name.to_string(), entry);
             }
 
-            let url = format!(
-                "{folder}.{domain}.test",
-                folder = folder_name,
-                domain = domain_name
-            );
-
             // Every service gets a hosts entry — HTTP/WS clients reach the reverse proxy
             // on port 80 via this name; TCP clients reach localhost (the hostname is a
             // loopback alias once urls_in_hosts syncs /etc/hosts).
-            hosts_container_lines.push(format!("0.0.0.0   {url}\n"));
+            hosts_container_lines.push(format!(
+                "0.0.0.0   {}\n",
+                config.service_host(folder_name, domain_name)
+            ));
+            for alias in aliases
+                .iter()
+                .chain(custom_domains.into_iter().flat_map(|d| d.keys()))
+            {
+                hosts_container_lines.push(format!("0.0.0.0   {alias}\n"));
+            }
 
-            match connection_type.as_str() {
-                "tcp" => {
-                    // No nginx vhost — nginx can't route plain TCP by hostname. The
-                    // service is reached as {svc}.{dom}.test:{auto_port} with the port
-                    // resolving via the service container's -p {auto_port}:8002 mapping.
-                }
-                _ => {
-                    let vhost = host_proxy_template
-                        .replace("{url}", &url)
-                        .replace("{host_gateway}", host_gateway)
-                        .replace("{port}", &port_number.to_string());
-
-                    std::fs::OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(&paths.vhost_container_conf)?
-                        .write_all(vhost.as_bytes())?;
-                }
+            // Split services get their vhost once the whole domain has ports.
+            if split {
+                *port_number += 1;
+                return Ok(());
+            }
+
+            let vhost = if connection_type == "static" {
+                Some(static_vhost(domain_name, group_name, folder_name))
+            } else if let Some(host_port) = host_port {
+                // No resolver: the process is on the host, never on the darp network.
+                service_vhost(
+                    engine,
+                    None,
+                    domain_name,
+                    domain,
+                    folder_name,
+                    &connection_type,
+                    host_port,
+                )
+            } else if replicas > 1 {
+                Some(replicas_vhost(
+                    domain_name,
+                    folder_name,
+                    engine.host_gateway(),
+                    &replica_ports,
+                ))
+            } else {
+                service_vhost(
+                    engine,
+                    network_resolver,
+                    domain_name,
+                    domain,
+                    folder_name,
+                    &connection_type,
+                    *port_number,
+                )
+            };
+            if let Some(vhost) = vhost {
+                let vhost = if grpc {
+                    if live_reload_enabled(domain, group_name, folder_name)
+                        || service_mirror(domain, group_name, folder_name).is_some()
+                    {
+                        warn!(
+                            "{}.{} uses grpc; live_reload and mirror only apply to http services",
+                            folder_name, domain_name
+                        );
+                    }
+                    grpc_vhost(&vhost)
+                } else if live_reload_enabled(domain, group_name, folder_name) {
+                    live_reload_vhost(&vhost, engine.host_gateway())
+                } else {
+                    vhost
+                };
+                let vhost = match service_mirror(domain, group_name, folder_name) {
+                    Some(target) if !grpc => {
+                        mirror_vhost(&vhost, &mirror_target_host(domain_name, target))
+                    }
+                    _ => vhost,
+                };
+                let direct = direct_via_proxy.then(|| direct_vhost(&vhost, *port_number));
+                let vhost = aliased_vhost(&vhost, aliases);
+                let vhost = aliased_vhost(&vhost, mdns_host.as_slice());
+                let vhost = match custom_domains {
+                    Some(_) if grpc => {
+                        warn!(
+                            "{}.{} uses grpc; custom domains only apply to http services",
+                            folder_name, domain_name
+                        );
+                        vhost
+                    }
+                    Some(custom_domains) => custom_domain_vhost(&vhost, custom_domains),
+                    None => vhost,
+                };
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&paths.vhost_container_conf)?
+                    .write_all((vhost + direct.as_deref().unwrap_or_default()).as_bytes())?;
             }
 
-            *port_number += 1;
+            *port_number += replicas;
             Ok(())
         };
 
         // Scan "." group: direct children of domain location, excluding group subdirs
         if groups.is_none_or(|g| g.contains_key(".")) {
-            if let Ok(entries) = std::fs::read_dir(&location) {
-                for entry in entries {
-                    let entry = entry?;
-                    if entry.file_type()?.is_dir() {
-                        let folder_name = entry.file_name().to_string_lossy().to_string();
-                        if !group_names.contains(&folder_name) {
-                            register_service(
-                                &folder_name,
-                                ".",
-                                &mut port_number,
-                                &mut next_debug_port,
-                                &mut reserved_debug_ports,
-                                &mut domain_map,
-                                &mut hosts_container_lines,
-                            )?;
-                        }
-                    }
+            for folder_name in manifest.folders(&location)? {
+                if !group_names.contains(&folder_name) {
+                    register_service(
+                        &folder_name,
+                        ".",
+                        &mut port_number,
+                        &mut next_debug_port,
+                        &mut reserved_debug_ports,
+                        &mut domain_map,
+                        &mut hosts_container_lines,
+                    )?;
                 }
             }
         }
@@ -279,47 +354,155 @@ pub fn cmd_deploy(
         // Scan named groups: subdirs within each group directory
         for group_name in &group_names {
             let group_path = location.join(group_name);
-            if let Ok(entries) = std::fs::read_dir(&group_path) {
-                for entry in entries {
-                    let entry = entry?;
-                    if entry.file_type()?.is_dir() {
-                        let folder_name = entry.file_name().to_string_lossy().to_string();
-                        register_service(
-                            &folder_name,
-                            group_name,
-                            &mut port_number,
-                            &mut next_debug_port,
-                            &mut reserved_debug_ports,
-                            &mut domain_map,
-                            &mut hosts_container_lines,
-                        )?;
-                    }
-                }
+            for folder_name in manifest.folders(&group_path)? {
+                register_service(
+                    &folder_name,
+                    group_name,
+                    &mut port_number,
+                    &mut next_debug_port,
+                    &mut reserved_debug_ports,
+                    &mut domain_map,
+                    &mut hosts_container_lines,
+                )?;
+            }
+        }
+
+        for (service_name, canary) in domain.canaries.iter().flatten() {
+            let find = |name: &str| {
+                domain_map
+                    .iter()
+                    .find_map(|(_, services)| services.get(name).cloned())
+            };
+            let Some(primary) = find(service_name) else {
+                continue;
+            };
+            let group_of = |name: &str| {
+                domain_map
+                    .iter()
+                    .find(|(_, services)| services.get(name).is_some())
+                    .map(|(group_name, _)| group_name.as_str())
+                    .unwrap_or(".")
+            };
+            let primary_group = group_of(service_name);
+            let resolver_of = |name: &str| {
+                service_network_resolver(
+                    config,
+                    engine,
+                    network_resolver.as_deref(),
+                    domain,
+                    group_of(name),
+                    name,
+                )
+            };
+            let primary_resolver = resolver_of(service_name);
+            if service_protocol(domain, primary_group, service_name) == "grpc" {
+                warn!(
+                    "{}.{} uses grpc; canary routing only applies to http services",
+                    service_name, domain_name
+                );
+                continue;
+            }
+            if primary.get("type").and_then(|t| t.as_str()) == Some("static") {
+                warn!(
+                    "{}.{} is static; canary routing only applies to http services",
+                    service_name, domain_name
+                );
+                continue;
             }
+            let vhost = match find(&canary.service) {
+                Some(target)
+                    if primary.get("type").and_then(|t| t.as_str()) != Some("tcp")
+                        && target.get("type").and_then(|t| t.as_str()) != Some("static") =>
+                {
+                    canary_vhost(
+                        domain_name,
+                        service_name,
+                        &service_upstream(
+                            engine,
+                            primary_resolver,
+                            domain_name,
+                            domain,
+                            service_name,
+                            &primary,
+                            &gateway_ip,
+                        ),
+                        &service_upstream(
+                            engine,
+                            resolver_of(&canary.service),
+                            domain_name,
+                            domain,
+                            &canary.service,
+                            &target,
+                            &gateway_ip,
+                        ),
+                        canary.percent,
+                        network_route(
+                            engine,
+                            primary_resolver.or(resolver_of(&canary.service)),
+                            domain,
+                        ),
+                    )
+                }
+                _ => {
+                    warn!(
+                        "canary '{}' is not an http service folder in '{}'; serving {} alone",
+                        canary.service, domain_name, service_name
+                    );
+                    service_vhost(
+                        engine,
+                        primary_resolver,
+                        domain_name,
+                        domain,
+                        service_name,
+                        primary
+                            .get("type")
+                            .and_then(|t| t.as_str())
+                            .unwrap_or("http"),
+                        primary
+                            .get("port")
+                            .and_then(|p| p.as_u64())
+                            .or_else(|| primary.as_u64())
+                            .unwrap_or(0) as u16,
+                    )
+                    .unwrap_or_default()
+                }
+            };
+            let vhost = aliased_vhost(&vhost, service_aliases(domain, primary_group, service_name));
+            let mdns_host: Option<String> = primary
+                .get("mdns_host")
+                .and_then(|h| h.as_str())
+                .map(str::to_string);
+            let vhost = aliased_vhost(&vhost, mdns_host.as_slice());
+            let vhost = match service_custom_domains(domain, primary_group, service_name) {
+                Some(custom_domains) => custom_domain_vhost(&vhost, custom_domains),
+                None => vhost,
+            };
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&paths.vhost_container_conf)?
+                .write_all(vhost.as_bytes())?;
         }
 
         portmap.insert(domain_name.clone(), serde_json::Value::Object(domain_map));
     }
+    manifest.save(&paths.scan_manifest_path)?;
+    drop(scan_timing);
 
-    let gateway_ip =
-        match engine::read_container_host_ip(&paths.container_host_ip_path, &engine.kind) {
-            Some(ip) => ip,
-            None => {
-                let ip = engine.probe_host_gateway_ip()?;
-                engine::write_container_host_ip(&paths.container_host_ip_path, &engine.kind, &ip)?;
-                ip
-            }
-        };
-
+    let files_timing = timings::phase("file generation");
+    // Static entries go after the service URLs, in both hosts files.
+    hosts_container_lines.extend(config.extra_host_lines());
     let hosts_content =
         build_container_hosts(&gateway_ip, engine.host_gateway(), &hosts_container_lines);
     std::fs::write(&paths.hosts_container_path, hosts_content)?;
+    let portmap = serde_json::Value::Object(portmap);
     std::fs::write(&paths.portmap_path, serde_json::to_vec_pretty(&portmap)?)?;
+    write_stream_conf(paths, config, engine, network_resolver.as_deref(), &portmap)?;
 
     // Report assigned debug ports so each project's .vscode/launch.json "port" can be
     // set (once — ports are persisted). Also available anytime via `darp urls`.
     let mut debug_lines: Vec<(String, u16)> = Vec::new();
-    for (domain_name, groups) in portmap.iter() {
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
         if let Some(groups) = groups.as_object() {
             for (group_name, services) in groups {
                 if let Some(services) = services.as_object() {
@@ -345,20 +528,32 @@ pub fn cmd_deploy(
         }
     }
 
-    // Restart reverse proxy and stop darp_* containers
+    // Every service container is stopped below, so nothing is running yet.
+    write_services_index(paths, engine, &Default::default())?;
+    let dns_changed = os.sync_test_conf()?;
+    // A remote engine mounts these files on the server; copy them over before any
+    // helper (re)starts.
+    engine.sync_remote_files(paths)?;
+    publish_shared_vhosts(paths, config)?;
+    drop(files_timing);
+
+    // Restart reverse proxy and stop darp-managed service containers. The whole batch
+    // takes one engine slot, so it queues behind other darp processes' starts and stops.
+    let slot = engine.acquire_slot(&paths.engine_slots_dir, "deploy")?;
+    let containers_timing = timings::phase("container operations");
     engine.restart_reverse_proxy(paths)?;
     engine.start_darp_masq(paths)?;
+    if dns_changed {
+        engine.restart_darp_masq()?;
+    }
     engine.stop_running_darps()?;
+    engine.remove_darp_pods()?;
+    drop(containers_timing);
+    drop(slot);
 
     // Optionally sync /etc/hosts if urls_in_hosts is enabled
-    if config.urls_in_hosts.unwrap_or(false) {
-        let os = OsIntegration::new(paths, config, &engine.kind);
-        os.sync_system_hosts(&hosts_container_lines)?;
-
-        if config.wsl.unwrap_or(false) {
-            os.sync_windows_hosts(&hosts_container_lines)?;
-        }
-    }
+    let _timing = timings::phase("hosts sync");
+    sync_url_hosts(paths, config, engine, &gateway_ip, &hosts_container_lines)?;
 
     Ok(())
 }
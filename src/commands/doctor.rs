@@ -3,9 +3,13 @@ use dirs::home_dir;
 use std::fs;
 use std::path::Path;
 
-use crate::commands::completions::{RC_START_MARKER, detect_shell};
+use crate::commands::completions::{RC_START_MARKER, default_rc_path, detect_shell};
 use crate::config::{self, Config, DarpPaths, ResolvedSettings};
 use crate::engine::{self, Engine};
+use crate::error::DarpError;
+use crate::logging::Logged;
+use crate::os::OsIntegration;
+use crate::timings;
 
 enum CheckResult {
     Ok(String),
@@ -58,16 +62,112 @@ impl DoctorSection {
     }
 }
 
-pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow::Result<()> {
-    println!("Darp Doctor");
+impl CheckResult {
+    fn to_json(&self) -> serde_json::Value {
+        let (status, message) = match self {
+            CheckResult::Ok(msg) => ("ok", msg),
+            CheckResult::Warn(msg) => ("warn", msg),
+            CheckResult::Fail(msg) => ("fail", msg),
+        };
+        serde_json::json!({ "status": status, "message": message })
+    }
+}
 
-    let mut issue_count = 0u32;
+/// `darp doctor`'s sections, printed as they finish, or gathered into one JSON object:
+/// `{"sections": [{"name", "passed", "checks": [{"status", "message"}]}], "issues": N}`.
+struct DoctorReport {
+    json: bool,
+    sections: Vec<serde_json::Value>,
+    issues: usize,
+}
+
+impl DoctorReport {
+    fn new(json: bool) -> Self {
+        if !json {
+            println!("Darp Doctor");
+        }
+        Self {
+            json,
+            sections: Vec::new(),
+            issues: 0,
+        }
+    }
+
+    fn add(&mut self, section: DoctorSection) {
+        if !section.passed() {
+            self.issues += 1;
+        }
+        if self.json {
+            self.sections.push(serde_json::json!({
+                "name": section.name,
+                "passed": section.passed(),
+                "checks": section.results.iter().map(CheckResult::to_json).collect::<Vec<_>>(),
+            }));
+        } else {
+            section.print();
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if self.json {
+            let report = serde_json::json!({ "sections": self.sections, "issues": self.issues });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        println!();
+        if self.issues == 0 {
+            println!("{}", "No issues found.".green());
+        } else {
+            println!(
+                "{}",
+                format!(
+                    "{} section(s) with issues. Run the suggested commands to fix them.",
+                    self.issues
+                )
+                .yellow()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Check the shell's default rc file for darp's completion block. A missing file is
+/// fine: the block may live in a file passed to `darp install --rc-file`.
+fn check_rc_block(s: &mut DoctorSection, home: &Path, shell: &str) {
+    let Some(rc) = default_rc_path(shell, home) else {
+        return;
+    };
+    if let Ok(contents) = fs::read_to_string(&rc) {
+        if contents.contains(RC_START_MARKER) {
+            s.ok(&format!("{} contains darp completion block", rc.display()));
+        } else {
+            s.warn(&format!(
+                "{} missing darp completion block — run 'darp install' (or pass --rc-file if you keep it elsewhere)",
+                rc.display()
+            ));
+        }
+    }
+}
+
+pub fn cmd_doctor(
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut report = DoctorReport::new(json);
 
     // 1. Darp root
     {
-        let mut s = DoctorSection::new(&format!("Darp root ({})", paths._darp_root.display()));
+        let mut s = DoctorSection::new(&format!("Darp root ({})", paths.darp_root.display()));
 
-        if paths._darp_root.is_dir() {
+        if paths.config_path.parent() != Some(paths.darp_root.as_path()) {
+            s.ok(&format!(
+                "config.json is read from {}",
+                paths.config_path.display()
+            ));
+        }
+        if paths.darp_root.is_dir() {
             if paths.config_path.is_file() {
                 match fs::read_to_string(&paths.config_path) {
                     Ok(contents) => {
@@ -97,11 +197,12 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
 
             let test_conf = paths.dnsmasq_dir.join("test.conf");
             if test_conf.is_file() {
+                let wanted = OsIntegration::new(paths, config, &engine.kind).test_conf();
                 match fs::read_to_string(&test_conf) {
-                    Ok(contents) if contents.contains("address=/.test/127.0.0.1") => {
+                    Ok(contents) if contents == wanted => {
                         s.ok("dnsmasq.d/test.conf has correct DNS rule");
                     }
-                    Ok(_) => s.warn("dnsmasq.d/test.conf has unexpected content"),
+                    Ok(_) => s.warn("dnsmasq.d/test.conf is out of date — run 'darp deploy'"),
                     Err(_) => s.fail("dnsmasq.d/test.conf cannot be read"),
                 }
             } else if paths.dnsmasq_dir.is_dir() {
@@ -110,14 +211,11 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
         } else {
             s.fail(&format!(
                 "{} does not exist — run 'darp install'",
-                paths._darp_root.display()
+                paths.darp_root.display()
             ));
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 2. Container engine
@@ -150,16 +248,19 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
         }
 
         engine_ready = engine.require_ready().is_ok();
-        if engine_ready {
+        if let (true, Some(remote)) = (engine_ready, &config.remote) {
+            s.ok(&format!(
+                "{} is running on {}",
+                engine.bin.unwrap_or("engine"),
+                remote.host
+            ));
+        } else if engine_ready {
             s.ok(&format!("{} is running", engine.bin.unwrap_or("engine")));
         } else if engine.bin.is_some() {
             s.warn(&format!("{} is not running", engine.bin.unwrap()));
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 3. DNS resolver
@@ -167,11 +268,21 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
         let mut s = DoctorSection::new("DNS resolver");
         let resolver_path = Path::new("/etc/resolver/test");
 
-        if resolver_path.exists() {
+        if let Some(sandbox) = crate::os::detect_sandbox() {
+            s.ok(&format!(
+                "Running inside {}; /etc/resolver is not used",
+                sandbox.describe()
+            ));
+            // Expected in a sandbox, so listed without counting as issues.
+            for limitation in crate::os::SANDBOX_LIMITATIONS {
+                s.ok(limitation);
+            }
+        } else if resolver_path.exists() {
             s.ok("/etc/resolver/test exists");
+            let nameserver = format!("nameserver {}", crate::os::resolver_nameserver(config));
             match fs::read_to_string(resolver_path) {
-                Ok(contents) if contents.contains("nameserver 127.0.0.1") => {
-                    s.ok("Contains: nameserver 127.0.0.1");
+                Ok(contents) if contents.contains(&nameserver) => {
+                    s.ok(&format!("Contains: {}", nameserver));
                 }
                 Ok(_) => {
                     s.warn("/etc/resolver/test has unexpected content");
@@ -184,10 +295,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             s.warn("/etc/resolver/test not found — run 'darp install'");
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 4. Infrastructure containers
@@ -210,10 +318,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             s.warn("Skipped — container engine is not running");
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 5. Shell completions
@@ -234,18 +339,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
                     } else {
                         s.warn("Bash completion file not found — run 'darp install'");
                     }
-                    let rc = home.join(".bashrc");
-                    if rc.is_file() {
-                        if let Ok(contents) = fs::read_to_string(&rc) {
-                            if contents.contains(RC_START_MARKER) {
-                                s.ok(".bashrc contains darp completion block");
-                            } else {
-                                s.warn(
-                                    ".bashrc missing darp completion block — run 'darp install'",
-                                );
-                            }
-                        }
-                    }
+                    check_rc_block(&mut s, &home, "bash");
                 }
                 Some("zsh") => {
                     let comp = home.join(".zfunc/_darp");
@@ -254,16 +348,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
                     } else {
                         s.warn("Zsh completion file not found — run 'darp install'");
                     }
-                    let rc = home.join(".zshrc");
-                    if rc.is_file() {
-                        if let Ok(contents) = fs::read_to_string(&rc) {
-                            if contents.contains(RC_START_MARKER) {
-                                s.ok(".zshrc contains darp completion block");
-                            } else {
-                                s.warn(".zshrc missing darp completion block — run 'darp install'");
-                            }
-                        }
-                    }
+                    check_rc_block(&mut s, &home, "zsh");
                 }
                 Some("fish") => {
                     let comp = home.join(".config/fish/completions/darp.fish");
@@ -287,10 +372,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             s.warn("Could not determine home directory");
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 6. Domains
@@ -379,10 +461,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             }
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 7. Deploy state
@@ -447,10 +526,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             s.warn("container_host_ip not cached — run 'darp install' (deploy will retry)");
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 8. Environments
@@ -485,10 +561,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             }
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
     // 9. WSL
@@ -501,7 +574,7 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
                 "-Command",
                 "[bool](([Security.Principal.WindowsPrincipal][Security.Principal.WindowsIdentity]::GetCurrent()).IsInRole([Security.Principal.WindowsBuiltInRole]::Administrator))",
             ])
-            .output()
+            .logged().output()
         {
             Ok(output) => {
                 let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -538,34 +611,16 @@ pub fn cmd_doctor(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow
             s.warn("urls_in_hosts is not enabled — WSL hosts sync requires it. Run 'darp config set urls-in-hosts true'");
         }
 
-        if !s.passed() {
-            issue_count += 1;
-        }
-        s.print();
+        report.add(s);
     }
 
-    // Summary
-    println!();
-    if issue_count == 0 {
-        println!("{}", "No issues found.".green());
-    } else {
-        println!(
-            "{}",
-            format!(
-                "{} section(s) with issues. Run the suggested commands to fix them.",
-                issue_count
-            )
-            .yellow()
-        );
-    }
-
-    Ok(())
+    report.finish()
 }
 
 pub fn cmd_check_image(
     image_cli: Option<String>,
     environment_cli: Option<String>,
-    _paths: &DarpPaths,
+    paths: &DarpPaths,
     config: &Config,
     engine: &Engine,
 ) -> anyhow::Result<()> {
@@ -594,13 +649,7 @@ pub fn cmd_check_image(
             .and_then(|r| r.resolve_full_image_name(None))
         {
             Some(img) => img,
-            None => {
-                eprintln!(
-                    "No image specified and none could be resolved from current directory.\n\
-                     Usage: darp check-image <image>"
-                );
-                std::process::exit(1);
-            }
+            None => return Err(DarpError::NoImageToCheck.into()),
         }
     };
 
@@ -637,18 +686,18 @@ pub fn cmd_check_image(
 
     let probe_script = probe_lines.join("; ");
 
-    let bin = engine.bin.expect("engine bin not set");
-
     // Check image availability
     {
         let mut s = DoctorSection::new("Image");
 
-        let inspect = std::process::Command::new(bin)
+        let inspect = engine
+            .command()
             .arg("image")
             .arg("inspect")
             .arg(&image_name)
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
+            .logged()
             .status();
 
         match inspect {
@@ -661,11 +710,16 @@ pub fn cmd_check_image(
                     image_name
                 ));
 
-                let pull = std::process::Command::new(bin)
+                let _slot = engine
+                    .acquire_slot(&paths.engine_slots_dir, &format!("pull {}", image_name))?;
+                let _timing = timings::phase("image pull");
+                let pull = engine
+                    .command()
                     .arg("pull")
                     .arg(&image_name)
                     .stdout(std::process::Stdio::null())
                     .stderr(std::process::Stdio::piped())
+                    .logged()
                     .status();
 
                 match pull {
@@ -689,13 +743,16 @@ pub fn cmd_check_image(
     }
 
     // Run diagnostic container
-    let output = std::process::Command::new(bin)
+    let output = engine
+        .command()
         .arg("run")
         .arg("--rm")
+        .args(&engine.engine_args)
         .arg(&image_name)
         .arg("sh")
         .arg("-c")
         .arg(&probe_script)
+        .logged()
         .output();
 
     let probe_output = match output {
@@ -0,0 +1,703 @@
+// commands/export.rs
+
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use colored::*;
+use tracing::{info, warn};
+
+use super::plan::RunPlan;
+use super::run::{build_service_image, container_port, resolve_serve, serve_run_plan};
+use crate::cli::{ComposeArgs, ExportArgs, ExportCommand};
+use crate::config::{self, Config, DarpPaths};
+use crate::engine::{self, Engine};
+
+pub fn cmd_export(
+    cmd: ExportCommand,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let (args, quadlet) = match cmd {
+        ExportCommand::Quadlet(args) => (args, true),
+        ExportCommand::Systemd(args) => (args, false),
+        ExportCommand::Compose(args) => return cmd_export_compose(args, paths, config, engine),
+    };
+    let ExportArgs {
+        service,
+        domain,
+        environment,
+        env_files,
+        network,
+        output_dir,
+        container_image,
+    } = args;
+
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let service_dir = find_service_dir(config, &portmap, &service, domain.as_deref())?;
+    let ctx = config
+        .service_context_for_dir(service_dir.clone(), environment)
+        .ok_or_else(|| anyhow!("{} is not inside any darp domain", service_dir.display()))?;
+
+    let setup = resolve_serve(&ctx, paths)?;
+    let plan = serve_run_plan(
+        &ctx,
+        &setup,
+        container_image.as_deref(),
+        &env_files,
+        network.as_deref(),
+        false,
+        0,
+        paths,
+        config,
+        engine,
+    )?;
+
+    // The unit runs the image later; build a recipe's image now if there's an engine to.
+    if engine.bin.is_some() {
+        build_service_image(
+            &ctx,
+            &setup.resolved,
+            container_image.as_deref(),
+            paths,
+            engine,
+        )?;
+    }
+
+    let run_args: Vec<String> = plan.run_args().split_off(1); // after "run"
+    let description = format!(
+        "darp service {}",
+        config.service_host(&ctx.current_directory_name, &ctx.domain_name)
+    );
+    let unit_name = engine.service_container_name(&ctx.domain_name, &ctx.current_directory_name);
+
+    let (unit, file_name) = if quadlet {
+        (
+            quadlet_unit(&description, &run_args),
+            format!("{}.container", unit_name),
+        )
+    } else {
+        let bin = engine.bin.unwrap_or("docker");
+        (
+            systemd_unit(&description, bin, &unit_name, &run_args),
+            format!("{}.service", unit_name),
+        )
+    };
+
+    let secrets = unexported_secrets(&run_args);
+    if !secrets.is_empty() {
+        info!(
+            "secrets aren't written into unit files; add {} to an --env-file",
+            secrets.join(", ")
+        );
+    }
+
+    match output_dir {
+        Some(dir) => {
+            let path = PathBuf::from(dir).join(&file_name);
+            std::fs::write(&path, unit)?;
+            println!("Wrote {}", path.display().to_string().green());
+            if quadlet {
+                println!(
+                    "Place it in ~/.config/containers/systemd/, then run 'systemctl --user daemon-reload' and 'systemctl --user start {}'",
+                    unit_name
+                );
+            } else {
+                println!(
+                    "Place it in /etc/systemd/system/, then run 'systemctl daemon-reload' and 'systemctl enable --now {}'",
+                    unit_name
+                );
+            }
+        }
+        None => print!("{}", unit),
+    }
+
+    Ok(())
+}
+
+/// `darp export compose`: every service of a domain that has a serve setup, as
+/// `darp serve` would run it, minus the files darp mounts for its own proxy.
+fn cmd_export_compose(
+    args: ComposeArgs,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let ComposeArgs {
+        domain: domain_name,
+        environment,
+        env_files,
+        output_dir,
+        podman,
+    } = args;
+    let domain = config
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(&domain_name))
+        .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+    let location = config::resolve_location(&domain.location)?;
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let Some(groups) = portmap.get(&domain_name).and_then(|g| g.as_object()) else {
+        return Err(anyhow!(
+            "Domain '{}' has no services yet; run 'darp deploy' first",
+            domain_name
+        ));
+    };
+    let darp_files = [
+        &paths.hosts_container_path,
+        &paths.nginx_conf_path,
+        &paths.vhost_container_conf,
+    ];
+
+    let mut services = Vec::new();
+    for (group_name, group) in groups {
+        for (service_name, entry) in group.as_object().into_iter().flatten() {
+            if entry.get("type").and_then(|t| t.as_str()) == Some("static") {
+                continue;
+            }
+            let mut dir = location.clone();
+            if group_name != "." {
+                dir.push(group_name);
+            }
+            dir.push(service_name);
+            let Some(ctx) = config.service_context_for_dir(dir, environment.clone()) else {
+                continue;
+            };
+            let plan = resolve_serve(&ctx, paths)
+                .map_err(anyhow::Error::from)
+                .and_then(|setup| {
+                    if engine.bin.is_some() {
+                        build_service_image(&ctx, &setup.resolved, None, paths, engine)?;
+                    }
+                    let mut plan = serve_run_plan(
+                        &ctx, &setup, None, &env_files, None, false, 0, paths, config, engine,
+                    )?;
+                    plan.mounts
+                        .retain(|m| !darp_files.iter().any(|f| Path::new(&m.host) == *f));
+                    // Without darp's proxy in front, the service is reached on its proxy port.
+                    if let Some(port) = entry.get("port").and_then(|p| p.as_u64()) {
+                        let prefix = format!("{}:", port);
+                        if !plan.ports.iter().any(|p| p.starts_with(&prefix)) {
+                            plan.ports.push(format!(
+                                "{}{}",
+                                prefix,
+                                container_port(setup.resolved.connection_type.as_deref())
+                            ));
+                        }
+                    }
+                    Ok(plan)
+                });
+            match plan {
+                Ok(plan) => services.push((service_name.clone(), plan)),
+                Err(e) => warn!("{} is left out: {}", service_name, e),
+            }
+        }
+    }
+    if services.is_empty() {
+        return Err(anyhow!(
+            "No service in domain '{}' has a serve setup to export",
+            domain_name
+        ));
+    }
+
+    let secrets: Vec<&str> = services
+        .iter()
+        .flat_map(|(_, plan)| plan.env.iter())
+        .filter(|e| !e.contains('='))
+        .map(String::as_str)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if !secrets.is_empty() {
+        info!(
+            "secrets aren't written into the compose file; set {} in the environment or an --env-file",
+            secrets.join(", ")
+        );
+    }
+
+    match output_dir {
+        Some(dir) => {
+            let dir = std::env::current_dir()?.join(dir);
+            let path = dir.join("docker-compose.yml");
+            std::fs::write(&path, compose_file(&domain_name, &services, &dir, podman))?;
+            println!("Wrote {}", path.display().to_string().green());
+        }
+        None => print!(
+            "{}",
+            compose_file(&domain_name, &services, &location, podman)
+        ),
+    }
+    Ok(())
+}
+
+/// Locate a service folder from the portmap written by `darp deploy`.
+fn find_service_dir(
+    config: &Config,
+    portmap: &serde_json::Value,
+    service: &str,
+    domain: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    let mut matches = Vec::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        if domain.is_some_and(|d| d != domain_name) {
+            continue;
+        }
+        for (group_name, services) in groups.as_object().into_iter().flatten() {
+            if services.get(service).is_some() {
+                matches.push((domain_name.clone(), group_name.clone()));
+            }
+        }
+    }
+
+    let (domain_name, group_name) = match matches.len() {
+        0 => {
+            return Err(anyhow!(
+                "Service '{}' not found; run 'darp deploy' if it was added recently",
+                service
+            ));
+        }
+        1 => matches.remove(0),
+        _ => {
+            let domains: Vec<&str> = matches.iter().map(|(d, _)| d.as_str()).collect();
+            return Err(anyhow!(
+                "Service '{}' exists in several domains ({}); pick one with -d",
+                service,
+                domains.join(", ")
+            ));
+        }
+    };
+
+    let domain = config
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(&domain_name))
+        .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+    let mut dir = config::resolve_location(&domain.location)?;
+    if group_name != "." {
+        dir.push(&group_name);
+    }
+    dir.push(service);
+    Ok(dir)
+}
+
+/// `run` arguments split into (flag, value) options, the image, and the container command.
+/// A flag in `config::RUN_VALUE_FLAGS` takes its value from after `=` (`--cap-add=SYS_PTRACE`,
+/// as `engine_args` and `run_args` are written) or else from the next argument.
+struct RunParts<'a> {
+    options: Vec<(&'a str, Option<&'a str>)>,
+    image: Option<&'a str>,
+    command: &'a [String],
+}
+
+fn split_run_args(run_args: &[String]) -> RunParts<'_> {
+    let mut options = Vec::new();
+    let mut i = 0;
+    while i < run_args.len() {
+        let arg = run_args[i].as_str();
+        if !arg.starts_with('-') {
+            return RunParts {
+                options,
+                image: Some(arg),
+                command: &run_args[i + 1..],
+            };
+        }
+        let joined = arg
+            .split_once('=')
+            .filter(|(flag, _)| flag.starts_with("--") && config::RUN_VALUE_FLAGS.contains(flag));
+        if let Some((flag, value)) = joined {
+            options.push((flag, Some(value)));
+            i += 1;
+        } else if config::RUN_VALUE_FLAGS.contains(&arg) {
+            options.push((arg, run_args.get(i + 1).map(String::as_str)));
+            i += 2;
+        } else {
+            options.push((arg, None));
+            i += 1;
+        }
+    }
+    RunParts {
+        options,
+        image: None,
+        command: &[],
+    }
+}
+
+/// Names passed as `-e NAME` with no value: secrets darp injects from its own environment.
+fn unexported_secrets(run_args: &[String]) -> Vec<&str> {
+    split_run_args(run_args)
+        .options
+        .into_iter()
+        .filter_map(|(flag, value)| match (flag, value) {
+            ("-e" | "--env", Some(v)) if !v.contains('=') => Some(v),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render a Podman Quadlet `.container` unit from `run` arguments. Options Quadlet has a
+/// key for are mapped to it; the rest go through `PodmanArgs=`.
+pub fn quadlet_unit(description: &str, run_args: &[String]) -> String {
+    let RunParts {
+        options,
+        image,
+        command,
+    } = split_run_args(run_args);
+    let mut container = Vec::new();
+    let mut podman_args = Vec::new();
+
+    for (flag, value) in options {
+        let key = match flag {
+            "--name" => Some("ContainerName"),
+            "-v" | "--volume" => Some("Volume"),
+            "-p" | "--publish" => Some("PublishPort"),
+            "-e" | "--env" => Some("Environment"),
+            "--env-file" => Some("EnvironmentFile"),
+            "-l" | "--label" => Some("Label"),
+            "--network" => Some("Network"),
+            "--add-host" => Some("AddHost"),
+            "--entrypoint" => Some("Entrypoint"),
+            "--cap-add" => Some("AddCapability"),
+            "--cap-drop" => Some("DropCapability"),
+            "--device" => Some("AddDevice"),
+            "--dns" => Some("DNS"),
+            "--group-add" => Some("GroupAdd"),
+            "-h" | "--hostname" => Some("HostName"),
+            "--mount" => Some("Mount"),
+            "--pull" => Some("Pull"),
+            "--shm-size" => Some("ShmSize"),
+            "--sysctl" => Some("Sysctl"),
+            "--tmpfs" => Some("Tmpfs"),
+            "--ulimit" => Some("Ulimit"),
+            "-w" | "--workdir" => Some("WorkingDir"),
+            "--userns" => Some("UserNS"),
+            // Quadlet takes the user and group as keys of their own.
+            "-u" | "--user" => {
+                if let Some(v) = value {
+                    let (user, group) = v.split_once(':').map_or((v, None), |(u, g)| (u, Some(g)));
+                    container.push(format!("User={}", systemd_quote(user)));
+                    container.extend(group.map(|g| format!("Group={}", systemd_quote(g))));
+                }
+                continue;
+            }
+            // --rm/-it make no sense for a unit; Quadlet manages the container's lifetime,
+            // and the unit's Restart= replaces any engine restart policy.
+            "--rm" | "-it" | "-i" | "-t" | "-d" | "--restart" => continue,
+            _ => None,
+        };
+        match (key, value) {
+            (Some("Environment"), Some(v)) if !v.contains('=') => {}
+            (Some(key), Some(v)) => container.push(format!("{}={}", key, systemd_quote(v))),
+            (_, Some(v)) if flag.starts_with("--") => {
+                podman_args.push(systemd_quote(&format!("{}={}", flag, v)))
+            }
+            (_, v) => {
+                podman_args.push(systemd_quote(flag));
+                podman_args.extend(v.map(systemd_quote));
+            }
+        }
+    }
+    if !podman_args.is_empty() {
+        container.push(format!("PodmanArgs={}", podman_args.join(" ")));
+    }
+    if !command.is_empty() {
+        let exec: Vec<String> = command.iter().map(|a| systemd_quote(a)).collect();
+        container.push(format!("Exec={}", exec.join(" ")));
+    }
+
+    format!(
+        "# Generated by `darp export quadlet`; re-run it after changing darp config.\n\
+         [Unit]\n\
+         Description={description}\n\
+         \n\
+         [Container]\n\
+         Image={image}\n\
+         {container}\n\
+         \n\
+         [Service]\n\
+         Restart=always\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        description = description,
+        image = image.unwrap_or_default(),
+        container = container.join("\n"),
+    )
+}
+
+/// Render a systemd `.service` unit that runs the container with the engine CLI.
+pub fn systemd_unit(description: &str, bin: &str, name: &str, run_args: &[String]) -> String {
+    let args: Vec<String> = run_args
+        .iter()
+        .filter(|a| !matches!(a.as_str(), "-it" | "-i" | "-t") && !a.starts_with("--restart="))
+        // ExecStart expands $VAR; Quadlet escapes what it generates itself.
+        .map(|a| systemd_quote(&a.replace('$', "$$")))
+        .collect();
+    let after = if bin == "docker" {
+        "After=docker.service\nRequires=docker.service\n"
+    } else {
+        "After=network-online.target\n"
+    };
+
+    format!(
+        "# Generated by `darp export systemd`; re-run it after changing darp config.\n\
+         [Unit]\n\
+         Description={description}\n\
+         {after}\
+         \n\
+         [Service]\n\
+         ExecStartPre=-{bin} rm -f {name}\n\
+         ExecStart={bin} run {args}\n\
+         ExecStop={bin} stop {name}\n\
+         Restart=always\n\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+        description = description,
+        after = after,
+        bin = bin,
+        name = name,
+        args = args.join(" "),
+    )
+}
+
+/// Quote one argument for a systemd command line or list setting: `%` specifiers are
+/// escaped, and anything with whitespace or quotes is double-quoted with C-style
+/// escapes (systemd unescapes `\n`, `\"`, `\\` inside quotes).
+pub fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%");
+    let needs_quotes = escaped.is_empty()
+        || escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'));
+    if !needs_quotes {
+        return escaped;
+    }
+    let mut out = String::from("\"");
+    for ch in escaped.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Quote a compose value: a JSON string is a valid YAML scalar, and a doubled `$` keeps
+/// compose from interpolating it.
+fn compose_quote(value: &str) -> String {
+    serde_json::Value::String(value.replace('$', "$$")).to_string()
+}
+
+/// `user:` for `--user`. A numeric `uid:gid` is the exporting user's own (from `map_user`),
+/// so it becomes `${DARP_UID:-uid}:${DARP_GID:-gid}` for whoever runs the file to override.
+fn compose_user(user: &str) -> String {
+    match user.split_once(':') {
+        Some((uid, gid))
+            if [uid, gid]
+                .iter()
+                .all(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())) =>
+        {
+            format!("\"${{DARP_UID:-{}}}:${{DARP_GID:-{}}}\"", uid, gid)
+        }
+        _ => compose_quote(user),
+    }
+}
+
+/// `path` relative to `base` (`./api`) when it's inside it, so the file works from any
+/// checkout of the domain.
+fn compose_path(path: &str, base: &Path) -> String {
+    match Path::new(path).strip_prefix(base) {
+        Ok(rest) if rest.as_os_str().is_empty() => ".".to_string(),
+        Ok(rest) => format!("./{}", rest.display()),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// Render a compose file for project `project` from each service's name and serve plan.
+/// Host paths are made relative to `base`, where the file is meant to live. darp's labels
+/// and network are left out, and user networks are expected to exist already. Named
+/// volumes are declared under their own names, so compose uses the ones darp created.
+/// `podman` keeps settings only podman-compose accepts, such as `userns_mode: keep-id`.
+pub fn compose_file(
+    project: &str,
+    services: &[(String, RunPlan)],
+    base: &Path,
+    podman: bool,
+) -> String {
+    let list = |out: &mut Vec<String>, key: &str, items: &[String]| {
+        if !items.is_empty() {
+            out.push(format!("    {}:", key));
+            out.extend(items.iter().map(|i| format!("      - {}", i)));
+        }
+    };
+    let mut external_networks = std::collections::BTreeSet::new();
+    let mut named_volumes = std::collections::BTreeSet::new();
+    let mut out = vec![
+        "# Generated by `darp export compose`; re-run it after changing darp config.".to_string(),
+        format!("name: {}", compose_quote(&project.to_lowercase())),
+        "services:".to_string(),
+    ];
+
+    for (name, plan) in services {
+        let mut restart = None;
+        let mut entrypoint = None;
+        let mut labels = Vec::new();
+        let mut networks = Vec::new();
+        let mut extra_hosts = Vec::new();
+        let mut environment: Vec<String> = plan.env.iter().map(|e| compose_quote(e)).collect();
+        // Lists compose has a key of the same shape for, by key.
+        let mut lists: std::collections::BTreeMap<&str, Vec<String>> = Default::default();
+        let mut settings = Vec::new();
+        let mut health = Vec::new();
+        let (mut os, mut arch, mut platform) = (None, None, None);
+        for (flag, value) in split_run_args(&plan.options).options {
+            let list = match flag {
+                "--cap-add" => Some("cap_add"),
+                "--cap-drop" => Some("cap_drop"),
+                "--device" => Some("devices"),
+                "--dns" => Some("dns"),
+                "--group-add" => Some("group_add"),
+                "--security-opt" => Some("security_opt"),
+                "--sysctl" => Some("sysctls"),
+                "--tmpfs" => Some("tmpfs"),
+                _ => None,
+            };
+            if let (Some(list), Some(v)) = (list, value) {
+                lists.entry(list).or_default().push(compose_quote(v));
+                continue;
+            }
+            match (flag, value) {
+                ("--restart", Some(v)) => restart = Some(v),
+                ("-l" | "--label", Some(v)) if !v.starts_with("darp.") => {
+                    labels.push(compose_quote(v))
+                }
+                ("--network", Some(v)) if v != engine::DARP_NETWORK => {
+                    external_networks.insert(v.to_string());
+                    networks.push(compose_quote(v));
+                }
+                ("--add-host", Some(v)) => extra_hosts.push(compose_quote(v)),
+                ("-e" | "--env", Some(v)) => environment.push(compose_quote(v)),
+                ("--entrypoint", Some(v)) => entrypoint = Some(v),
+                ("-u" | "--user", Some(v)) => settings.push(format!("user: {}", compose_user(v))),
+                ("--userns", Some(v)) if podman => {
+                    settings.push(format!("userns_mode: {}", compose_quote(v)))
+                }
+                ("--userns", Some(v)) => warn!(
+                    "{}: left out 'userns_mode: {}', which only podman-compose accepts; pass --podman to keep it",
+                    name, v
+                ),
+                ("-h" | "--hostname", Some(v)) => {
+                    settings.push(format!("hostname: {}", compose_quote(v)))
+                }
+                ("-w" | "--workdir", Some(v)) => {
+                    settings.push(format!("working_dir: {}", compose_quote(v)))
+                }
+                ("--shm-size", Some(v)) => settings.push(format!("shm_size: {}", compose_quote(v))),
+                ("--init", None) => settings.push("init: true".to_string()),
+                ("--privileged", None) => settings.push("privileged: true".to_string()),
+                ("--cpus", Some(v)) => settings.push(format!("cpus: {}", compose_quote(v))),
+                ("--memory", Some(v)) => settings.push(format!("mem_limit: {}", compose_quote(v))),
+                ("--platform", Some(v)) => platform = Some(v.to_string()),
+                ("--os", Some(v)) => os = Some(v),
+                ("--arch", Some(v)) => arch = Some(v),
+                ("--health-cmd", Some(v)) => {
+                    health.push(format!("test: [\"CMD-SHELL\", {}]", compose_quote(v)))
+                }
+                ("--health-interval", Some(v)) => {
+                    health.push(format!("interval: {}", compose_quote(v)))
+                }
+                // --rm, -d, -it, --pod and darp's own labels and network only matter to
+                // `darp serve`.
+                ("--rm" | "-d" | "-it" | "--pod" | "-l" | "--label" | "--network", _) => {}
+                (flag, value) => warn!(
+                    "{}: compose has no equivalent for '{}', left out",
+                    name,
+                    value.map_or_else(|| flag.to_string(), |v| format!("{}={}", flag, v))
+                ),
+            }
+        }
+        if let (None, Some(arch)) = (&platform, arch) {
+            platform = Some(format!("{}/{}", os.unwrap_or("linux"), arch));
+        }
+        if !networks.is_empty() {
+            networks.insert(0, compose_quote("default"));
+        }
+
+        out.push(format!("  {}:", name));
+        out.push(format!("    image: {}", compose_quote(&plan.image)));
+        if let Some(platform) = platform {
+            out.push(format!("    platform: {}", compose_quote(&platform)));
+        }
+        if let Some(restart) = restart {
+            out.push(format!("    restart: {}", compose_quote(restart)));
+        }
+        if let Some(entrypoint) = entrypoint {
+            out.push(format!("    entrypoint: [{}]", compose_quote(entrypoint)));
+        }
+        if !plan.command.is_empty() {
+            let command: Vec<String> = plan.command.iter().map(|a| compose_quote(a)).collect();
+            out.push(format!("    command: [{}]", command.join(", ")));
+        }
+        named_volumes.extend(
+            plan.mounts
+                .iter()
+                .filter(|m| config::is_volume_name(&m.host))
+                .map(|m| m.host.clone()),
+        );
+        let volumes: Vec<String> = plan
+            .mounts
+            .iter()
+            .map(|m| {
+                compose_quote(&format!(
+                    "{}:{}{}",
+                    compose_path(&m.host, base),
+                    m.container,
+                    if m.read_only { ":ro" } else { "" }
+                ))
+            })
+            .collect();
+        list(&mut out, "volumes", &volumes);
+        let ports: Vec<String> = plan.ports.iter().map(|p| compose_quote(p)).collect();
+        list(&mut out, "ports", &ports);
+        let env_files: Vec<String> = plan
+            .env_files
+            .iter()
+            .map(|f| compose_quote(&compose_path(f, base)))
+            .collect();
+        list(&mut out, "env_file", &env_files);
+        list(&mut out, "environment", &environment);
+        list(&mut out, "labels", &labels);
+        list(&mut out, "extra_hosts", &extra_hosts);
+        list(&mut out, "networks", &networks);
+        for (key, items) in &lists {
+            list(&mut out, key, items);
+        }
+        out.extend(settings.into_iter().map(|s| format!("    {}", s)));
+        if !health.is_empty() {
+            out.push("    healthcheck:".to_string());
+            out.extend(health.into_iter().map(|h| format!("      {}", h)));
+        }
+    }
+
+    if !named_volumes.is_empty() {
+        out.push("volumes:".to_string());
+        for volume in named_volumes {
+            out.push(format!("  {}:", volume));
+            out.push(format!("    name: {}", compose_quote(&volume)));
+        }
+    }
+    if !external_networks.is_empty() {
+        out.push("networks:".to_string());
+        for network in external_networks {
+            out.push(format!("  {}:", network));
+            out.push("    external: true".to_string());
+        }
+    }
+    out.join("\n") + "\n"
+}
@@ -0,0 +1,148 @@
+// commands/generate.rs
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::cli::{GenerateArgs, GenerateCommand};
+use crate::config::{self, Config};
+use crate::engine::shell_escape;
+
+/// Labels of the tasks darp writes start with this, so regenerating replaces only them.
+const TASK_PREFIX: &str = "darp: ";
+
+/// The editor tasks for a service as (name, command line): `darp serve` and
+/// `darp shell`, with `-e <environment>` when one is given.
+pub fn editor_tasks(environment: Option<&str>) -> Vec<(&'static str, String)> {
+    let env_flag = environment
+        .map(|e| format!(" -e {}", shell_escape(OsStr::new(e))))
+        .unwrap_or_default();
+    vec![
+        ("serve", format!("darp serve{}", env_flag)),
+        ("shell", format!("darp shell{}", env_flag)),
+    ]
+}
+
+/// `.vscode/tasks.json` with darp's tasks. The other tasks and settings of `existing`
+/// are kept; darp's tasks from an earlier run are replaced.
+pub fn vscode_tasks(
+    existing: Option<&serde_json::Value>,
+    environment: Option<&str>,
+) -> serde_json::Value {
+    let mut tasks_json = existing
+        .filter(|v| v.is_object())
+        .cloned()
+        .unwrap_or_else(|| serde_json::json!({"version": "2.0.0"}));
+    let mut tasks: Vec<serde_json::Value> = tasks_json["tasks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|t| {
+            !t["label"]
+                .as_str()
+                .is_some_and(|l| l.starts_with(TASK_PREFIX))
+        })
+        .cloned()
+        .collect();
+    for (name, command) in editor_tasks(environment) {
+        let mut task = serde_json::json!({
+            "label": format!("{}{}", TASK_PREFIX, name),
+            "type": "shell",
+            "command": command,
+            "problemMatcher": [],
+            "presentation": {"panel": "dedicated", "focus": name == "shell"},
+        });
+        if name == "serve" {
+            task["isBackground"] = true.into();
+        }
+        tasks.push(task);
+    }
+    tasks_json["tasks"] = tasks.into();
+    tasks_json
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A JetBrains shell-script run configuration (`.run/<name>.run.xml`) that runs
+/// `command` in the project folder, in the IDE's terminal.
+pub fn jetbrains_run_config(name: &str, command: &str) -> String {
+    format!(
+        r#"<component name="ProjectRunConfigurationManager">
+  <configuration default="false" name="{}" type="ShConfigurationType">
+    <option name="SCRIPT_TEXT" value="{}" />
+    <option name="INDEPENDENT_SCRIPT_PATH" value="true" />
+    <option name="SCRIPT_PATH" value="" />
+    <option name="SCRIPT_OPTIONS" value="" />
+    <option name="INDEPENDENT_SCRIPT_WORKING_DIRECTORY" value="true" />
+    <option name="SCRIPT_WORKING_DIRECTORY" value="$PROJECT_DIR$" />
+    <option name="INDEPENDENT_INTERPRETER_PATH" value="true" />
+    <option name="INTERPRETER_PATH" value="/bin/sh" />
+    <option name="INTERPRETER_OPTIONS" value="" />
+    <option name="EXECUTE_IN_TERMINAL" value="true" />
+    <option name="EXECUTE_SCRIPT_FILE" value="false" />
+    <envs />
+    <method v="2" />
+  </configuration>
+</component>
+"#,
+        xml_escape(name),
+        xml_escape(command)
+    )
+}
+
+fn write_file(path: &Path, contents: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    println!("Wrote {}", path.display().to_string().green());
+    Ok(())
+}
+
+/// `darp generate`: write editor tasks that run `darp serve` and `darp shell` into the
+/// current service's folder. The environment is `-e`, else the service's default.
+pub fn cmd_generate(cmd: GenerateCommand, config: &Config) -> anyhow::Result<()> {
+    let (GenerateCommand::Vscode(GenerateArgs { environment })
+    | GenerateCommand::Jetbrains(GenerateArgs { environment })) = &cmd;
+    let ctx = config.require_service_context(environment.clone())?;
+    let environment = ctx.environment_name.as_deref();
+
+    match cmd {
+        GenerateCommand::Vscode(_) => {
+            let path = ctx.current_dir.join(".vscode").join("tasks.json");
+            let existing = if path.exists() {
+                Some(config::read_json::<serde_json::Value>(&path).map_err(|e| {
+                    anyhow!(
+                        "could not read {} ({}); comments in tasks.json aren't supported",
+                        path.display(),
+                        e
+                    )
+                })?)
+            } else {
+                None
+            };
+            let tasks = vscode_tasks(existing.as_ref(), environment);
+            write_file(&path, &(serde_json::to_string_pretty(&tasks)? + "\n"))?;
+        }
+        GenerateCommand::Jetbrains(_) => {
+            let run_dir = ctx.current_dir.join(".run");
+            for (name, command) in editor_tasks(environment) {
+                let title = format!("darp {}", name);
+                write_file(
+                    &run_dir.join(format!("{}.run.xml", title)),
+                    &jetbrains_run_config(&title, &command),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,331 @@
+// commands/hook.rs
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::anyhow;
+
+use crate::cli::{GitHookCommand, HookCommand};
+use crate::config::{self, Config, DarpPaths, ServiceContext};
+use crate::engine::{Engine, shell_escape};
+use crate::urls::entry_url;
+
+use super::which_entry;
+
+/// The variable a sibling service's URL is exported as: `DARP_URL_` and the service name
+/// upper-cased, with anything but letters and digits as `_` (`web-app` → `DARP_URL_WEB_APP`).
+pub fn sibling_url_var(service: &str) -> String {
+    let name: String = service
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("DARP_URL_{}", name)
+}
+
+/// The URL of every other deployed service in `domain`, keyed by `sibling_url_var`.
+pub fn sibling_urls(
+    portmap: &serde_json::Value,
+    domain: &str,
+    service: &str,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut urls = serde_json::Map::new();
+    let groups = portmap.get(domain).and_then(|g| g.as_object());
+    for services in groups.into_iter().flat_map(|g| g.values()) {
+        for (name, entry) in services.as_object().into_iter().flatten() {
+            if name != service {
+                let (_, url) = entry_url(domain, name, entry);
+                urls.insert(sibling_url_var(name), url.into());
+            }
+        }
+    }
+    urls
+}
+
+/// `which_entry` with what `.envrc` exports on top: the service's `direct_url` and its
+/// siblings' URLs.
+fn envrc_entry(
+    ctx: &ServiceContext<'_>,
+    portmap: &serde_json::Value,
+    container_name: &str,
+) -> serde_json::Value {
+    let service = ctx.current_directory_name.as_str();
+    let mut entry = which_entry(ctx, portmap, container_name);
+    entry["direct_url"] = portmap
+        .get(&ctx.domain_name)
+        .and_then(|d| d.get(&ctx.group_name))
+        .and_then(|g| g.get(service))
+        .and_then(|e| e.get("direct_url"))
+        .cloned()
+        .unwrap_or_default();
+    entry["siblings"] = sibling_urls(portmap, &ctx.domain_name, service).into();
+    entry
+}
+
+/// `.envrc` lines for a `which_entry`: `DARP_SERVICE` (`<domain>.<service>`), plus
+/// `DARP_URL` and `DARP_PORT` (the reverse proxy's port for it) once the service has a
+/// port, `DARP_DIRECT_URL` when it has a `localhost` URL, and each of `siblings`. Each of
+/// `watch` gets a `watch_file`, so direnv reloads when a deploy moves the service.
+pub fn direnv_snippet(entry: &serde_json::Value, watch: &[&Path]) -> String {
+    let quote = |s: &str| shell_escape(OsStr::new(s));
+    let mut lines: Vec<String> = watch
+        .iter()
+        .map(|path| format!("watch_file {}", shell_escape(path.as_os_str())))
+        .collect();
+    let service = format!(
+        "{}.{}",
+        entry["domain"].as_str().unwrap_or_default(),
+        entry["service"].as_str().unwrap_or_default()
+    );
+    lines.push(format!("export DARP_SERVICE={}", quote(&service)));
+    if let (Some(url), Some(port)) = (entry["url"].as_str(), entry["port"].as_u64()) {
+        lines.push(format!("export DARP_URL={}", quote(url)));
+        lines.push(format!("export DARP_PORT={}", port));
+    }
+    if let Some(url) = entry["direct_url"].as_str() {
+        lines.push(format!("export DARP_DIRECT_URL={}", quote(url)));
+    }
+    for (var, url) in entry["siblings"].as_object().into_iter().flatten() {
+        if let Some(url) = url.as_str() {
+            lines.push(format!("export {}={}", var, quote(url)));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+const GIT_HOOK_START_MARKER: &str = "# >>> darp post-checkout >>>";
+const GIT_HOOK_END_MARKER: &str = "# <<< darp post-checkout <<<";
+
+/// The darp command the post-checkout hook runs. It only registers new service folders:
+/// a full deploy would stop every running service container on each checkout.
+pub const GIT_HOOK_COMMAND: &str = "darp --yes add-service --new";
+
+/// darp's part of a post-checkout hook. Git passes a third argument of 1 for branch
+/// checkouts (0 for single files); only those can add or remove service folders.
+fn git_hook_body() -> String {
+    format!(
+        r#"if [ "$3" = "1" ] && command -v darp >/dev/null 2>&1; then
+  {} || echo "darp: registering new services failed; run 'darp deploy'" >&2
+fi
+"#,
+        GIT_HOOK_COMMAND
+    )
+}
+
+/// Where the block between `start_marker` and `end_marker` is in `text`, including its
+/// trailing newline.
+fn find_block(text: &str, start_marker: &str, end_marker: &str) -> Option<(usize, usize)> {
+    let start = text.find(start_marker)?;
+    let end = match text[start..].find(end_marker) {
+        Some(e) => start + e + end_marker.len(),
+        None => text.len(),
+    };
+    let end = if text[end..].starts_with('\n') {
+        end + 1
+    } else {
+        end
+    };
+    Some((start, end))
+}
+
+/// Where darp's block is in `hook`, including its trailing newline.
+fn find_git_hook_block(hook: &str) -> Option<(usize, usize)> {
+    find_block(hook, GIT_HOOK_START_MARKER, GIT_HOOK_END_MARKER)
+}
+
+/// `hook` (a post-checkout script, possibly empty) with darp's block in it: replaced
+/// where an older one stands, else appended. A new script gets a `#!/bin/sh` line.
+pub fn with_git_hook_block(hook: &str) -> String {
+    let block = format!(
+        "{}\n{}{}\n",
+        GIT_HOOK_START_MARKER,
+        git_hook_body(),
+        GIT_HOOK_END_MARKER
+    );
+    if let Some((start, end)) = find_git_hook_block(hook) {
+        return format!("{}{}{}", &hook[..start], block, &hook[end..]);
+    }
+    if hook.trim().is_empty() {
+        return format!("#!/bin/sh\n{}", block);
+    }
+    let separator = if hook.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}{}", hook, separator, block)
+}
+
+/// `hook` without darp's block, or None when nothing but a shebang would be left.
+pub fn without_git_hook_block(hook: &str) -> Option<String> {
+    let rest = match find_git_hook_block(hook) {
+        Some((start, end)) => format!("{}{}", &hook[..start], &hook[end..]),
+        None => hook.to_string(),
+    };
+    let empty = rest
+        .lines()
+        .all(|l| l.trim().is_empty() || l.starts_with("#!"));
+    (!empty).then_some(rest)
+}
+
+const ENVRC_START_MARKER: &str = "# >>> darp >>>";
+const ENVRC_END_MARKER: &str = "# <<< darp <<<";
+
+/// `envrc` (a `.envrc`, possibly empty) with darp's block in it, replacing an older one.
+/// The block evaluates `darp hook direnv`, so the values follow every deploy.
+pub fn with_envrc_block(envrc: &str) -> String {
+    let block = format!(
+        "{}\neval \"$(darp hook direnv)\"\n{}\n",
+        ENVRC_START_MARKER, ENVRC_END_MARKER
+    );
+    if let Some((start, end)) = find_block(envrc, ENVRC_START_MARKER, ENVRC_END_MARKER) {
+        return format!("{}{}{}", &envrc[..start], block, &envrc[end..]);
+    }
+    if envrc.trim().is_empty() {
+        return block;
+    }
+    let separator = if envrc.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}{}", envrc, separator, block)
+}
+
+/// `envrc` without darp's block, or None when nothing would be left.
+pub fn without_envrc_block(envrc: &str) -> Option<String> {
+    let rest = match find_block(envrc, ENVRC_START_MARKER, ENVRC_END_MARKER) {
+        Some((start, end)) => format!("{}{}", &envrc[..start], &envrc[end..]),
+        None => envrc.to_string(),
+    };
+    (!rest.trim().is_empty()).then_some(rest)
+}
+
+/// The current repository's hooks folder, as git resolves it (`core.hooksPath`, worktrees).
+fn git_hooks_dir() -> anyhow::Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .map_err(|e| anyhow!("could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!("Current directory is not inside a git repository."));
+    }
+    let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(std::env::current_dir()?.join(dir))
+}
+
+fn cmd_git_hook(cmd: GitHookCommand) -> anyhow::Result<()> {
+    let hook_path = git_hooks_dir()?.join("post-checkout");
+    let contents = fs::read_to_string(&hook_path).unwrap_or_default();
+    match cmd {
+        GitHookCommand::Install => {
+            if let Some(parent) = hook_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&hook_path, with_git_hook_block(&contents))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+            }
+            println!("Installed post-checkout hook at {}", hook_path.display());
+        }
+        GitHookCommand::Uninstall => {
+            if !contents.contains(GIT_HOOK_START_MARKER) {
+                println!("No darp post-checkout hook at {}", hook_path.display());
+                return Ok(());
+            }
+            match without_git_hook_block(&contents) {
+                Some(rest) => fs::write(&hook_path, rest)?,
+                None => fs::remove_file(&hook_path)?,
+            }
+            println!("Removed post-checkout hook from {}", hook_path.display());
+        }
+    }
+    Ok(())
+}
+
+pub fn cmd_hook(
+    cmd: HookCommand,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    match cmd {
+        HookCommand::Direnv => {
+            let ctx = config.require_service_context(None)?;
+            let portmap: serde_json::Value =
+                config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+            let entry = envrc_entry(&ctx, &portmap, &engine.container_name);
+            print!(
+                "{}",
+                direnv_snippet(&entry, &[&paths.config_path, &paths.portmap_path])
+            );
+        }
+        HookCommand::Git { cmd } => cmd_git_hook(cmd)?,
+    }
+    Ok(())
+}
+
+/// Add darp's block to (or with `remove`, take it out of) one service folder's `.envrc`.
+fn update_envrc(dir: &Path, remove: bool) -> anyhow::Result<()> {
+    let path = dir.join(".envrc");
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+    if remove {
+        if !contents.contains(ENVRC_START_MARKER) {
+            return Ok(());
+        }
+        match without_envrc_block(&contents) {
+            Some(rest) => fs::write(&path, rest)?,
+            None => fs::remove_file(&path)?,
+        }
+        println!("Removed darp from {}", path.display());
+    } else {
+        let updated = with_envrc_block(&contents);
+        if updated != contents {
+            fs::write(&path, updated)?;
+            println!("Wrote {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// `darp envrc`: add darp's block to the `.envrc` of the current directory's service, or
+/// of every deployed service in `domain`. direnv asks to `direnv allow` each changed file.
+pub fn cmd_envrc(
+    domain: Option<String>,
+    remove: bool,
+    paths: &DarpPaths,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let Some(domain_name) = domain else {
+        let ctx = config.require_service_context(None)?;
+        return update_envrc(&ctx.current_dir, remove);
+    };
+
+    let domain = config
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(&domain_name))
+        .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)
+        .map_err(|_| anyhow!("No portmap found. Run 'darp deploy' first."))?;
+    let location = config::resolve_location(&domain.location)?;
+    for (group_name, services) in portmap
+        .get(&domain_name)
+        .and_then(|g| g.as_object())
+        .into_iter()
+        .flatten()
+    {
+        for service_name in services.as_object().into_iter().flatten().map(|(s, _)| s) {
+            let mut dir = location.clone();
+            if group_name != "." {
+                dir.push(group_name);
+            }
+            dir.push(service_name);
+            if dir.is_dir() {
+                update_envrc(&dir, remove)?;
+            }
+        }
+    }
+    Ok(())
+}
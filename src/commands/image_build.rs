@@ -0,0 +1,115 @@
+// commands/image_build.rs
+
+use std::fs;
+use std::process::Stdio;
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::config::{DarpPaths, ImageBuild};
+use crate::engine::{Engine, sanitize_container_name};
+use crate::logging::Logged;
+use crate::timings;
+
+/// The Dockerfile for an `image_build` recipe. Packages go in one layer, installed with
+/// the first package manager the base image has; each `run` command is a layer of its own.
+pub fn image_build_dockerfile(build: &ImageBuild) -> String {
+    let mut dockerfile = format!("FROM {}\n", build.base);
+    if !build.packages.is_empty() {
+        let packages = build.packages.join(" ");
+        let managers = [
+            ("apk", format!("apk add --no-cache {}", packages)),
+            (
+                "apt-get",
+                format!(
+                    "apt-get update && DEBIAN_FRONTEND=noninteractive apt-get install -y \
+                     --no-install-recommends {} && rm -rf /var/lib/apt/lists/*",
+                    packages
+                ),
+            ),
+            (
+                "dnf",
+                format!("dnf install -y {} && dnf clean all", packages),
+            ),
+            (
+                "microdnf",
+                format!("microdnf install -y {} && microdnf clean all", packages),
+            ),
+        ];
+        let branches: Vec<String> = managers
+            .iter()
+            .map(|(bin, install)| format!("command -v {} >/dev/null 2>&1; then {}", bin, install))
+            .collect();
+        dockerfile.push_str(&format!(
+            "RUN if {}; \\\n    else echo 'darp: no supported package manager in {}' >&2; exit 1; fi\n",
+            branches.join("; \\\n    elif "),
+            build.base
+        ));
+    }
+    for command in &build.run {
+        dockerfile.push_str(&format!("RUN {}\n", command));
+    }
+    dockerfile
+}
+
+/// FNV-1a, so a recipe hashes the same on every machine and darp version.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The tag a service's recipe builds to: `darp-build/<domain>_<service>:<hash>`. The hash
+/// covers the generated Dockerfile, so editing the recipe gives a new tag and an unchanged
+/// one reuses the image already built.
+pub fn image_build_tag(domain: &str, service: &str, build: &ImageBuild) -> String {
+    let name = sanitize_container_name(&format!("{}_{}", domain, service)).to_lowercase();
+    let hash = fnv1a(image_build_dockerfile(build).as_bytes());
+    format!("darp-build/{}:{:012x}", name, hash & 0xffff_ffff_ffff)
+}
+
+/// Build `tag` from the recipe unless the engine already has it. The Dockerfile is kept
+/// under `paths.images_dir` for reference.
+pub(crate) fn ensure_image_built(
+    engine: &Engine,
+    paths: &DarpPaths,
+    tag: &str,
+    build: &ImageBuild,
+    platform: Option<&str>,
+) -> anyhow::Result<()> {
+    let present = engine
+        .command()
+        .args(["image", "inspect", tag])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status()
+        .is_ok_and(|s| s.success());
+    if present {
+        return Ok(());
+    }
+
+    let context = paths
+        .images_dir
+        .join(sanitize_container_name(&tag.replace(['/', ':'], "_")));
+    fs::create_dir_all(&context)?;
+    fs::write(context.join("Dockerfile"), image_build_dockerfile(build))?;
+
+    let _slot = engine.acquire_slot(&paths.engine_slots_dir, &format!("build {}", tag))?;
+    let _timing = timings::phase("image build");
+    println!("Building image {}...", tag.cyan());
+    let mut cmd = engine.command();
+    cmd.args(["build", "-t", tag]);
+    if let Some(platform) = platform {
+        cmd.args(["--platform", platform]);
+    }
+    let status = cmd.arg(&context).logged().status()?;
+    if !status.success() {
+        return Err(anyhow!(
+            "building {} failed; the Dockerfile is at {}",
+            tag,
+            context.join("Dockerfile").display()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// commands/live_reload.rs
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::config::{self, Config};
+use crate::livereload;
+
+/// `(host, folder)` for every service with `live_reload` turned on.
+fn live_reload_services(config: &Config) -> anyhow::Result<Vec<(String, std::path::PathBuf)>> {
+    let mut watched = Vec::new();
+    for (domain_name, domain) in config.domains.iter().flatten() {
+        for (group_name, group) in domain.groups.iter().flatten() {
+            for (service_name, service) in group.services.iter().flatten() {
+                if service.live_reload != Some(true) {
+                    continue;
+                }
+                let mut dir = config::resolve_location(&domain.location)?;
+                if group_name != "." {
+                    dir.push(group_name);
+                }
+                dir.push(service_name);
+                watched.push((config.service_host(service_name, domain_name), dir));
+            }
+        }
+    }
+    Ok(watched)
+}
+
+pub fn cmd_live_reload(config: &Config) -> anyhow::Result<()> {
+    let watched = live_reload_services(config)?;
+    if watched.is_empty() {
+        return Err(anyhow!(
+            "No service has live_reload enabled. Turn it on with \
+'darp config set svc live-reload <domain> <group> <service> true', then run 'darp deploy'."
+        ));
+    }
+
+    for (host, dir) in &watched {
+        println!("Watching {} for {}", dir.display(), host.green());
+    }
+    println!(
+        "Listening on port {}; press Ctrl-C to stop.",
+        livereload::LIVE_RELOAD_PORT
+    );
+    livereload::serve(watched)
+}
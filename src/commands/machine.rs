@@ -0,0 +1,32 @@
+// commands/machine.rs
+
+use anyhow::{Result, anyhow};
+use colored::*;
+
+use crate::cli::MachineCommand;
+use crate::engine::Engine;
+use crate::logging::Logged;
+
+pub fn cmd_machine(cmd: MachineCommand, engine: &Engine) -> Result<()> {
+    let action = match cmd {
+        MachineCommand::Start => "start",
+        MachineCommand::Stop => "stop",
+        MachineCommand::Status => "status",
+    };
+
+    let mut command = engine.machine_command(action)?;
+    println!("{}", engine.command_to_string(&command).cyan());
+
+    let status = command
+        .logged()
+        .status()
+        .map_err(|e| anyhow!("failed to run {}: {}", engine.kind.as_str(), e))?;
+    if !status.success() {
+        return Err(anyhow!(
+            "'{}' exited with {}",
+            engine.command_to_string(&command),
+            status
+        ));
+    }
+    Ok(())
+}
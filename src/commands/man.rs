@@ -0,0 +1,82 @@
+// commands/man.rs
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use clap::CommandFactory;
+use clap_mangen::Man;
+
+use crate::cli::Cli;
+
+/// One page per command in the tree, as `(file name, roff)`: `darp.1`, `darp-serve.1`,
+/// `darp-config-set-svc.1`, …. Hidden commands get no page.
+pub fn man_pages() -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    fn collect(cmd: clap::Command, pages: &mut Vec<(String, Vec<u8>)>) -> anyhow::Result<()> {
+        for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+            collect(sub.clone(), pages)?;
+        }
+        let man = Man::new(cmd).source(format!("darp {}", env!("CARGO_PKG_VERSION")));
+        let mut roff = Vec::new();
+        man.render(&mut roff)?;
+        pages.push((man.get_filename(), roff));
+        Ok(())
+    }
+
+    let mut cmd = Cli::command();
+    // Subcommands are named after their parents (`darp-config-set`) once built.
+    cmd.build();
+    let mut pages = Vec::new();
+    collect(cmd, &mut pages)?;
+    pages.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(pages)
+}
+
+/// Where `darp install` puts the pages: `$XDG_DATA_HOME/man/man1`, or
+/// `~/.local/share/man/man1`. man finds it through `~/.local/bin` on `PATH`, or
+/// through `MANPATH`.
+pub fn default_man_dir() -> Option<PathBuf> {
+    let data_home = match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs::home_dir()?.join(".local").join("share"),
+    };
+    Some(data_home.join("man").join("man1"))
+}
+
+/// Write every page into `dir`, replacing older ones. Returns how many were written.
+pub fn install_man_pages(dir: &Path) -> anyhow::Result<usize> {
+    let pages = man_pages()?;
+    fs::create_dir_all(dir)?;
+    for (name, roff) in &pages {
+        fs::write(dir.join(name), roff)?;
+    }
+    Ok(pages.len())
+}
+
+/// Remove the pages `install_man_pages` writes from `dir`. Returns how many were there.
+pub fn uninstall_man_pages(dir: &Path) -> anyhow::Result<usize> {
+    let mut removed = 0;
+    for (name, _) in man_pages()? {
+        match fs::remove_file(dir.join(&name)) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(removed)
+}
+
+pub fn cmd_man(dir: Option<String>, remove: bool) -> anyhow::Result<()> {
+    let dir = match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => default_man_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?,
+    };
+    if remove {
+        let removed = uninstall_man_pages(&dir)?;
+        println!("Removed {} man pages from {}", removed, dir.display());
+    } else {
+        let written = install_man_pages(&dir)?;
+        println!("Wrote {} man pages to {}", written, dir.display());
+    }
+    Ok(())
+}
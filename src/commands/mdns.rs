@@ -0,0 +1,23 @@
+// commands/mdns.rs
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::anyhow;
+
+use crate::config::{Config, DarpPaths};
+use crate::mdns;
+
+/// `darp mdns`: advertise the deployed services' `.local` names until Ctrl-C.
+pub fn cmd_mdns(paths: &DarpPaths, config: &Config) -> anyhow::Result<()> {
+    if config.mdns != Some(true) {
+        return Err(anyhow!(
+            "mDNS names are off. Turn them on with 'darp config set mdns true', then run 'darp deploy'."
+        ));
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = Arc::clone(&stop);
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::Relaxed))?;
+    println!("Answering mDNS queries; press Ctrl-C to stop.");
+    mdns::advertise(&paths.portmap_path, &stop)
+}
@@ -1,11 +1,72 @@
+mod add_service;
+mod artifacts;
+mod check;
 mod completions;
 mod config_cmds;
 mod deploy;
 mod doctor;
+mod export;
+mod generate;
+mod hook;
+mod image_build;
+mod live_reload;
+mod machine;
+mod man;
+mod mdns;
+mod next;
+mod open;
+mod picker;
+mod plan;
+mod proxy;
+mod ps;
 mod run;
+mod secret;
+mod share;
+mod up;
+mod verify;
+mod volume;
+mod which;
+mod wizard;
 
-pub use completions::{install_shell_completions, uninstall_shell_completions};
-pub use config_cmds::{cmd_add, cmd_pull, cmd_rm, cmd_set, cmd_show, cmd_urls};
-pub use deploy::{build_container_hosts, cmd_deploy};
+pub use add_service::cmd_add_service;
+pub use artifacts::{ARTIFACTS_FORMAT_VERSION, cmd_artifacts};
+pub use check::{CheckFailure, cmd_check, config_problem, run_checks};
+pub use completions::{
+    RcTarget, cmd_completion, completion_script, default_rc_path_with, install_shell_completions,
+    uninstall_shell_completions, with_rc_block,
+};
+pub use config_cmds::{
+    UrlsOptions, cmd_add, cmd_move, cmd_pull, cmd_rm, cmd_rollback, cmd_set, cmd_show, cmd_urls,
+};
+pub use deploy::cmd_deploy;
 pub use doctor::{cmd_check_image, cmd_doctor};
-pub use run::{cmd_serve, cmd_shell};
+pub use export::{cmd_export, compose_file, quadlet_unit, systemd_quote, systemd_unit};
+pub use generate::{cmd_generate, editor_tasks, jetbrains_run_config, vscode_tasks};
+pub use hook::{
+    GIT_HOOK_COMMAND, cmd_envrc, cmd_hook, direnv_snippet, sibling_url_var, sibling_urls,
+    with_envrc_block, with_git_hook_block, without_envrc_block, without_git_hook_block,
+};
+pub use image_build::{image_build_dockerfile, image_build_tag};
+pub use live_reload::cmd_live_reload;
+pub use machine::cmd_machine;
+pub use man::{cmd_man, default_man_dir, install_man_pages, man_pages, uninstall_man_pages};
+pub use mdns::cmd_mdns;
+pub use next::{OnboardingStep, cmd_next, is_unconfigured, onboarding_steps, render_onboarding};
+pub use open::{cmd_open, find_portmap_service, service_browser_url};
+pub use picker::{filter_choices, fuzzy_score, pick_from};
+pub use plan::{Mount, RunPlan};
+pub use proxy::{ProxyStatus, cmd_proxy, render_proxy_status};
+pub use ps::{build_ps_entries, cmd_ps};
+pub use run::{
+    Registration, ServeOptions, cmd_logs, cmd_serve, cmd_shell, cmd_stop, missing_registration,
+    replica_container_name, serve_script, service_run_plan,
+};
+pub use secret::cmd_secret;
+pub use share::{TunnelTarget, cmd_share, public_url, tunnel_args, tunnel_bin, tunnel_target};
+pub use up::{UpOptions, cmd_up, up_waves};
+pub use verify::{VerifyTarget, verify_deploy, verify_targets};
+pub use volume::{VolumeEntry, cmd_volume, volume_entries};
+pub use which::{cmd_which, which_entry};
+pub use wizard::{
+    cmd_wizard, parse_port_pair, parse_variable, parse_volume, project_hint, run_wizard,
+};
@@ -0,0 +1,161 @@
+// commands/next.rs
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::config::{self, Config, DarpPaths};
+use crate::engine::{self, Engine};
+
+/// One step of getting started, with the command that does it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OnboardingStep {
+    pub title: String,
+    pub command: String,
+    pub done: bool,
+}
+
+/// True for a config nothing has been set in yet: no engine and no domains.
+pub fn is_unconfigured(config: &Config) -> bool {
+    config.engine.is_none() && config.domains.as_ref().is_none_or(|d| d.is_empty())
+}
+
+/// `location` as a user would type it, with `{home}` written as `~`.
+fn display_location(location: &str) -> String {
+    location.replace("{home}", "~")
+}
+
+/// The first deployed service as (domain, service), to suggest serving.
+fn first_deployed(portmap: &serde_json::Value) -> Option<(String, String)> {
+    portmap.as_object()?.iter().find_map(|(domain, groups)| {
+        groups
+            .as_object()?
+            .values()
+            .find_map(|services| services.as_object()?.keys().next().cloned())
+            .map(|service| (domain.clone(), service))
+    })
+}
+
+/// Whether any deployed service's container is running.
+fn any_service_running(portmap: &serde_json::Value, engine: &Engine) -> bool {
+    let running = engine.running_container_names();
+    portmap
+        .as_object()
+        .into_iter()
+        .flatten()
+        .any(|(domain, groups)| {
+            groups
+                .as_object()
+                .into_iter()
+                .flatten()
+                .flat_map(|(_, services)| services.as_object().into_iter().flatten())
+                .any(|(service, _)| {
+                    running.contains(&engine::container_name(
+                        &engine.container_name,
+                        domain,
+                        service,
+                    ))
+                })
+        })
+}
+
+/// The getting-started steps (engine, install, domain, deploy, serve), each marked done
+/// from what's on disk. The commands name the user's own domain and services once there
+/// are some. `serving` says whether a deployed service is running.
+pub fn onboarding_steps(paths: &DarpPaths, config: &Config, serving: bool) -> Vec<OnboardingStep> {
+    let engine_command = match ["podman", "docker"]
+        .into_iter()
+        .find(|bin| engine::is_on_path(bin))
+    {
+        Some(bin) => format!("darp config set engine {}", bin),
+        None => "darp config set engine <podman|docker>".to_string(),
+    };
+
+    let domain = config
+        .domains
+        .as_ref()
+        .and_then(|domains| domains.iter().next());
+    let portmap: Option<serde_json::Value> = config::read_json(&paths.portmap_path).ok();
+    let deployed = portmap.as_ref().and_then(first_deployed);
+
+    let serve_command = match (&deployed, &config.domains) {
+        (Some((name, service)), Some(domains)) => match domains.get(name) {
+            Some(d) => format!(
+                "cd {}/{} && darp serve",
+                display_location(&d.location),
+                service
+            ),
+            None => "cd <service folder> && darp serve".to_string(),
+        },
+        _ => "cd <service folder> && darp serve".to_string(),
+    };
+    let deploy_title = match domain {
+        Some((_, d)) => format!("Deploy the services in {}", display_location(&d.location)),
+        None => "Deploy the domain's services".to_string(),
+    };
+
+    vec![
+        OnboardingStep {
+            title: "Choose a container engine".to_string(),
+            command: engine_command,
+            done: config.engine.is_some(),
+        },
+        OnboardingStep {
+            title: "Install the resolver and reverse proxy".to_string(),
+            command: "darp install".to_string(),
+            done: paths.dnsmasq_dir.join("test.conf").exists(),
+        },
+        OnboardingStep {
+            title: "Add a domain: a folder whose subfolders are services".to_string(),
+            command: "darp config set dom serve-command -l ~/projects projects 'npm start'"
+                .to_string(),
+            done: domain.is_some(),
+        },
+        OnboardingStep {
+            title: deploy_title,
+            command: "darp deploy".to_string(),
+            done: deployed.is_some(),
+        },
+        OnboardingStep {
+            title: "Serve a service".to_string(),
+            command: serve_command,
+            done: serving,
+        },
+    ]
+}
+
+/// The checklist: every step ticked or not, with the command for each one left to do,
+/// then the next command to run.
+pub fn render_onboarding(steps: &[OnboardingStep]) -> String {
+    let mut out = String::from("Getting started with darp:\n");
+    for step in steps {
+        if step.done {
+            out.push_str(&format!("  {} {}\n", "[x]".green(), step.title));
+        } else {
+            out.push_str(&format!("  [ ] {}\n", step.title));
+            out.push_str(&format!("        {}\n", step.command.cyan()));
+        }
+    }
+    match steps.iter().find(|s| !s.done) {
+        Some(step) => out.push_str(&format!("\nNext: {}\n", step.command)),
+        None => out.push_str("\nAll set. 'darp urls' lists every service's address.\n"),
+    }
+    out
+}
+
+/// `darp next`: the getting-started steps still to do.
+pub fn cmd_next(
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+    json: bool,
+) -> anyhow::Result<()> {
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let steps = onboarding_steps(paths, config, any_service_running(&portmap, engine));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&steps)?);
+    } else {
+        print!("{}", render_onboarding(&steps));
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// commands/open.rs
+
+use anyhow::anyhow;
+use colored::*;
+
+use super::picker::pick_service;
+use crate::config::{self, Config, DarpPaths};
+use crate::os;
+use crate::urls::entry_url;
+
+/// The portmap entry of a service as (domain, service, entry): `target` is
+/// `domain.service` or a service name unique across the portmap; without one, `cwd` (the
+/// current directory's domain and service) is used. Services without a portmap entry are
+/// refused.
+pub fn find_portmap_service<'a>(
+    portmap: &'a serde_json::Value,
+    target: Option<&'a str>,
+    cwd: Option<(&'a str, &'a str)>,
+) -> anyhow::Result<(&'a str, &'a str, &'a serde_json::Value)> {
+    let (domain, service) = match target {
+        Some(target) => match target.split_once('.') {
+            Some((domain, service)) => (Some(domain), service),
+            None => (None, target),
+        },
+        None => {
+            let (domain, service) = cwd.ok_or_else(|| {
+                anyhow!(
+                    "Current directory does not exist in any darp domain configuration. \
+Pass a service as 'domain.service'."
+                )
+            })?;
+            (Some(domain), service)
+        }
+    };
+
+    let mut matches = Vec::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        if domain.is_some_and(|d| d != domain_name) {
+            continue;
+        }
+        for (_, services) in groups.as_object().into_iter().flatten() {
+            if let Some(entry) = services.get(service) {
+                matches.push((domain_name.as_str(), entry));
+            }
+        }
+    }
+
+    match matches.len() {
+        0 => {
+            let name = domain.map_or(service.to_string(), |d| format!("{}.{}", d, service));
+            Err(anyhow!(
+                "Service '{}' has no port assigned yet. Run 'darp deploy' (or 'darp add-service' in its folder) first.",
+                name
+            ))
+        }
+        1 => {
+            let (domain_name, entry) = matches.remove(0);
+            Ok((domain_name, service, entry))
+        }
+        _ => {
+            let domains: Vec<&str> = matches.iter().map(|(d, _)| *d).collect();
+            Err(anyhow!(
+                "Service '{}' exists in several domains ({}); pass it as 'domain.{}'",
+                service,
+                domains.join(", "),
+                service
+            ))
+        }
+    }
+}
+
+/// The browser URL of a service, found as `find_portmap_service` does. Services reached
+/// over tcp are refused.
+pub fn service_browser_url(
+    portmap: &serde_json::Value,
+    target: Option<&str>,
+    cwd: Option<(&str, &str)>,
+) -> anyhow::Result<String> {
+    let (domain_name, service, entry) = find_portmap_service(portmap, target, cwd)?;
+    let (_, url) = entry_url(domain_name, service, entry);
+    if url.starts_with("tcp://") {
+        return Err(anyhow!(
+            "{} is a tcp service and can't be opened in a browser; connect to {}",
+            service,
+            url
+        ));
+    }
+    // Websocket services sit behind the same vhost; the browser wants http.
+    Ok(url.replacen("ws://", "http://", 1))
+}
+
+pub fn cmd_open(target: Option<String>, paths: &DarpPaths, config: &Config) -> anyhow::Result<()> {
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)
+        .map_err(|_| anyhow!("No portmap found. Run 'darp deploy' first."))?;
+    let ctx = match target {
+        Some(_) => None,
+        None => config.service_context_from_cwd(None),
+    };
+    // Outside a service folder, ask which service to open instead of failing.
+    let target = match (&target, &ctx) {
+        (None, None) => pick_service(paths, config)?
+            .map(|(domain_name, _, service_name)| format!("{}.{}", domain_name, service_name)),
+        _ => target,
+    };
+    let cwd = ctx
+        .as_ref()
+        .map(|c| (c.domain_name.as_str(), c.current_directory_name.as_str()));
+
+    let url = service_browser_url(&portmap, target.as_deref(), cwd)?;
+    println!("Opening {}", url.green());
+    os::open_url(&url, config.wsl == Some(true))
+}
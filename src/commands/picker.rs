@@ -0,0 +1,171 @@
+// commands/picker.rs
+
+use std::io::{BufRead, IsTerminal, Write};
+
+use colored::*;
+
+use crate::config::{self, Config, DarpPaths, ServiceContext};
+use crate::engine::{Engine, EngineKind};
+
+/// Most matches listed at once; typing more of the name narrows the rest down.
+const PICKER_ROWS: usize = 20;
+
+/// How well `query` matches `candidate`, lower being better, or None when it doesn't:
+/// the query's characters must appear in order (case-insensitively). Substrings beat
+/// scattered matches, and earlier matches beat later ones.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    if let Some(at) = candidate.find(&query) {
+        return Some(at);
+    }
+    let mut gaps = 0;
+    let mut last: Option<usize> = None;
+    let mut chars = candidate.char_indices();
+    for q in query.chars() {
+        let (at, _) = chars.by_ref().find(|(_, c)| *c == q)?;
+        gaps += last.map_or(at, |l| at - l - 1);
+        last = Some(at);
+    }
+    Some(candidate.len() + gaps)
+}
+
+/// Indexes of the `choices` matching `query`, best first (all of them, in order, for an
+/// empty query).
+pub fn filter_choices(query: &str, choices: &[String]) -> Vec<usize> {
+    let mut scored: Vec<(usize, usize)> = choices
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| fuzzy_score(query, c).map(|score| (score, i)))
+        .collect();
+    scored.sort();
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Let the user pick one of `choices` by typing part of its name or its number. A
+/// filter leaving one match picks it. An empty line or end of input cancels.
+pub fn pick_from(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+    choices: &[String],
+) -> anyhow::Result<Option<usize>> {
+    let mut query = String::new();
+    loop {
+        let matches = filter_choices(&query, choices);
+        match matches.len() {
+            0 => writeln!(output, "Nothing matches '{}'.", query)?,
+            1 if !query.is_empty() => return Ok(Some(matches[0])),
+            _ => {
+                for (n, i) in matches.iter().take(PICKER_ROWS).enumerate() {
+                    writeln!(output, "{:>3}) {}", n + 1, choices[*i])?;
+                }
+                if matches.len() > PICKER_ROWS {
+                    writeln!(
+                        output,
+                        "     ... and {} more; type to narrow",
+                        matches.len() - PICKER_ROWS
+                    )?;
+                }
+            }
+        }
+        write!(output, "{} (number or filter, empty to cancel): ", prompt)?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if let Ok(n) = line.parse::<usize>() {
+            if (1..=matches.len().min(PICKER_ROWS)).contains(&n) {
+                return Ok(Some(matches[n - 1]));
+            }
+        }
+        query = line.to_string();
+    }
+}
+
+/// Ask which registered service a command is for, when it ran outside a service folder.
+/// None when darp isn't attached to a terminal or is non-interactive, nothing is deployed,
+/// or the user cancels; callers then report why the current directory didn't do.
+pub(crate) fn pick_service(
+    paths: &DarpPaths,
+    config: &Config,
+) -> anyhow::Result<Option<(String, String, String)>> {
+    if crate::os::assume_yes()
+        || !std::io::stdin().is_terminal()
+        || !std::io::stderr().is_terminal()
+    {
+        return Ok(None);
+    }
+    let Ok(portmap) = config::read_json(&paths.portmap_path) else {
+        return Ok(None);
+    };
+    let mut services = config::portmap_services(&portmap);
+    services.retain(|(domain_name, _, _)| {
+        config
+            .domains
+            .as_ref()
+            .is_some_and(|d| d.contains_key(domain_name))
+    });
+    if services.is_empty() {
+        return Ok(None);
+    }
+    let labels: Vec<String> = services
+        .iter()
+        .map(|(domain_name, _, service_name)| config.service_host(service_name, domain_name))
+        .collect();
+
+    eprintln!("{}", "Not in a service folder; pick a service.".yellow());
+    let picked = pick_from(
+        &mut std::io::stdin().lock(),
+        &mut std::io::stderr(),
+        "Service",
+        &labels,
+    )?;
+    Ok(picked.map(|i| services.swap_remove(i)))
+}
+
+/// The current directory's service, or, outside a service folder, one the user picks.
+/// The error explaining the current directory stands when nothing is picked.
+pub(crate) fn service_context_or_pick<'a>(
+    paths: &DarpPaths,
+    config: &'a Config,
+    env_cli: Option<String>,
+) -> anyhow::Result<ServiceContext<'a>> {
+    let err = match config.require_service_context(env_cli.clone()) {
+        Ok(ctx) => return Ok(ctx),
+        Err(err) => err,
+    };
+    let Some((domain_name, group_name, service_name)) = pick_service(paths, config)? else {
+        return Err(err);
+    };
+    let Some(domain) = config.domains.as_ref().and_then(|d| d.get(&domain_name)) else {
+        return Err(err);
+    };
+    let mut dir = config::resolve_location(&domain.location)?;
+    if group_name != "." {
+        dir.push(&group_name);
+    }
+    dir.push(&service_name);
+    config.service_context_for_dir(dir, env_cli).ok_or(err)
+}
+
+/// The engine for `ctx` when it differs from `engine`. The binary picks a command's engine
+/// from the current directory, so a service picked from outside its folder got the global
+/// engine; None when that is the service's own.
+pub(crate) fn picked_service_engine(
+    config: &Config,
+    ctx: &ServiceContext,
+    engine: &Engine,
+) -> anyhow::Result<Option<Engine>> {
+    let engine_override = config::engine_override(ctx.domain, ctx.environment);
+    if EngineKind::for_service(config, engine_override) == engine.kind {
+        return Ok(None);
+    }
+    Engine::for_service(config, ctx.domain, ctx.environment).map(Some)
+}
@@ -0,0 +1,107 @@
+// commands/plan.rs
+
+use serde::Serialize;
+
+use crate::engine::Engine;
+
+/// A host directory or file bind-mounted into the container.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mount {
+    pub host: String,
+    pub container: String,
+    /// Mounted `:ro`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+}
+
+/// Everything `darp serve` / `darp shell` hand to the engine's `run`, gathered before
+/// anything runs. `--dry-run` prints it as a command line, `--print-plan` as JSON, and
+/// `darp export` turns it into a unit file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RunPlan {
+    /// Container name (`--name`).
+    pub name: String,
+    pub image: String,
+    /// `run` options not covered by the fields below, in order: `--rm` or `--restart`,
+    /// `-it` / `-d`, labels, `--pod`, `--network`, healthcheck, limits, ….
+    pub options: Vec<String>,
+    /// `-v` bind mounts.
+    pub mounts: Vec<Mount>,
+    /// `-p` mappings, `host:container` with an optional `/udp`.
+    pub ports: Vec<String>,
+    /// `--env-file` paths.
+    pub env_files: Vec<String>,
+    /// `-e` entries: `NAME=value`, or a bare `NAME` for a secret, whose value comes from
+    /// darp's own environment so it never shows up in argv or in the plan.
+    pub env: Vec<String>,
+    /// What runs in the container, after the image; empty for the image's default.
+    pub command: Vec<String>,
+    /// Secret values for the bare `env` names, set on the engine process only.
+    #[serde(skip)]
+    pub secrets: Vec<(String, String)>,
+}
+
+impl RunPlan {
+    pub fn new(name: &str, options: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            options,
+            ..Default::default()
+        }
+    }
+
+    pub fn mount(&mut self, host: impl Into<String>, container: impl Into<String>) {
+        self.mounts.push(Mount {
+            host: host.into(),
+            container: container.into(),
+            read_only: false,
+        });
+    }
+
+    /// Add options, e.g. `plan.option(["--network", "darp"])`.
+    pub fn option<const N: usize>(&mut self, args: [&str; N]) {
+        self.options.extend(args.iter().map(|a| a.to_string()));
+    }
+
+    /// The arguments after the engine binary, starting with `run`.
+    pub fn run_args(&self) -> Vec<String> {
+        let mut args = vec!["run".to_string()];
+        args.extend(self.options.iter().cloned());
+        args.push("--name".to_string());
+        args.push(self.name.clone());
+        for mount in &self.mounts {
+            args.push("-v".to_string());
+            args.push(format!(
+                "{}:{}{}",
+                mount.host,
+                mount.container,
+                if mount.read_only { ":ro" } else { "" }
+            ));
+        }
+        for port in &self.ports {
+            args.push("-p".to_string());
+            args.push(port.clone());
+        }
+        for env_file in &self.env_files {
+            args.push("--env-file".to_string());
+            args.push(env_file.clone());
+        }
+        for env in &self.env {
+            args.push("-e".to_string());
+            args.push(env.clone());
+        }
+        args.push(self.image.clone());
+        args.extend(self.command.iter().cloned());
+        args
+    }
+
+    /// The engine command that carries out the plan.
+    pub fn command(&self, engine: &Engine) -> std::process::Command {
+        let mut cmd = engine.command();
+        cmd.args(self.run_args());
+        for (name, value) in &self.secrets {
+            cmd.env(name, value);
+        }
+        cmd
+    }
+}
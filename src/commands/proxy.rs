@@ -0,0 +1,143 @@
+// commands/proxy.rs
+
+use std::process::Stdio;
+
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::Serialize;
+
+use crate::cli::ProxyCommand;
+use crate::config::DarpPaths;
+use crate::engine::{self, Engine};
+use crate::logging::Logged;
+
+/// What `darp proxy status` reports about the reverse proxy container.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProxyStatus {
+    pub running: bool,
+    /// Whether `nginx -t` accepted the configuration; None when the proxy isn't running.
+    pub config_ok: Option<bool>,
+    /// `nginx -t`'s output.
+    pub config_test: String,
+    /// Published ports as the engine lists them, e.g. `80/tcp -> 0.0.0.0:80`.
+    pub ports: Vec<String>,
+}
+
+impl ProxyStatus {
+    /// Ask the engine about the running proxy: its config test and published ports.
+    pub fn query(engine: &Engine) -> Result<Self> {
+        if !engine.is_container_running(engine::REVERSE_PROXY) {
+            return Ok(Self::default());
+        }
+        let test = engine
+            .command()
+            .args(["exec", engine::REVERSE_PROXY, "nginx", "-t"])
+            .logged()
+            .output()?;
+        let ports = engine
+            .command()
+            .args(["port", engine::REVERSE_PROXY])
+            .logged()
+            .output()?;
+        Ok(Self {
+            running: true,
+            config_ok: Some(test.status.success()),
+            // nginx -t reports on stderr.
+            config_test: String::from_utf8_lossy(&test.stderr).trim().to_string(),
+            ports: String::from_utf8_lossy(&ports.stdout)
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
+
+/// `status` as `darp proxy status` prints it.
+pub fn render_proxy_status(status: &ProxyStatus) -> String {
+    if !status.running {
+        return format!(
+            "{}: {} (run 'darp deploy' to start it)\n",
+            engine::REVERSE_PROXY,
+            "stopped".red()
+        );
+    }
+    let mut out = format!("{}: {}\n", engine::REVERSE_PROXY, "running".green());
+    let config = match status.config_ok {
+        Some(true) => "ok".green(),
+        _ => "failed".red(),
+    };
+    out.push_str(&format!("config: {}\n", config));
+    for line in status.config_test.lines() {
+        out.push_str(&format!("  {}\n", line));
+    }
+    out.push_str("ports:\n");
+    for port in &status.ports {
+        out.push_str(&format!("  {}\n", port));
+    }
+    out
+}
+
+pub fn cmd_proxy(cmd: ProxyCommand, json: bool, paths: &DarpPaths, engine: &Engine) -> Result<()> {
+    match cmd {
+        ProxyCommand::Logs {
+            follow,
+            tail,
+            errors,
+            access,
+        } => {
+            engine.require_ready()?;
+
+            let mut command = engine.command();
+            command.arg("logs");
+            if follow {
+                command.arg("-f");
+            }
+            if let Some(tail) = tail {
+                command.arg("--tail").arg(tail);
+            }
+            command.arg(engine::REVERSE_PROXY);
+
+            // The nginx image links access.log to stdout and error.log to stderr, and the
+            // engine replays each on the same stream.
+            if errors {
+                command.stdout(Stdio::null());
+            }
+            if access {
+                command.stderr(Stdio::null());
+            }
+
+            let status = command
+                .logged()
+                .status()
+                .map_err(|e| anyhow!("failed to run {}: {}", engine.kind.as_str(), e))?;
+            if !status.success() && !engine.is_container_running(engine::REVERSE_PROXY) {
+                return Err(anyhow!(
+                    "{} is not running. Run 'darp deploy' to start it.",
+                    engine::REVERSE_PROXY
+                ));
+            }
+            Ok(())
+        }
+        // Both start the proxy when it's stopped or its run settings changed since.
+        ProxyCommand::Reload => {
+            engine.require_ready()?;
+            engine.reload_reverse_proxy(paths)
+        }
+        ProxyCommand::Restart => {
+            engine.require_ready()?;
+            engine.restart_reverse_proxy(paths)
+        }
+        ProxyCommand::Status => {
+            engine.require_ready()?;
+            let status = ProxyStatus::query(engine)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print!("{}", render_proxy_status(&status));
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// commands/ps.rs
+
+use colored::*;
+use tracing::warn;
+
+use crate::config::{self, Config, DarpPaths};
+use crate::engine::{Engine, ServiceContainer};
+
+/// One `darp ps` row per container, joined with the portmap: the service's group, URL
+/// and proxy port, plus any other ports it publishes. `engine` names the engine the
+/// containers run on.
+pub fn build_ps_entries(
+    containers: &[ServiceContainer],
+    portmap: &serde_json::Value,
+    engine: &str,
+) -> Vec<serde_json::Value> {
+    containers
+        .iter()
+        .map(|c| {
+            let group = portmap
+                .get(&c.domain)
+                .and_then(|d| d.as_object())
+                .and_then(|groups| {
+                    groups
+                        .iter()
+                        .find(|(_, services)| services.get(&c.service).is_some())
+                })
+                .map(|(name, _)| name.clone());
+            let entry = group
+                .as_deref()
+                .and_then(|g| portmap.get(&c.domain)?.get(g)?.get(&c.service));
+            let proxy_port = group
+                .as_deref()
+                .and_then(|g| config::portmap_proxy_port(portmap, &c.domain, g, &c.service));
+            let conn_type = entry
+                .and_then(|e| e.get("type"))
+                .and_then(|t| t.as_str())
+                .unwrap_or("http");
+            let host = entry.map_or_else(
+                || config::service_host(&c.service, &c.domain, None),
+                |e| config::portmap_host(e, &c.domain, &c.service),
+            );
+            let url = match conn_type {
+                "tcp" => format!(
+                    "tcp://{}:{}",
+                    host,
+                    entry.map_or(0, config::portmap_tcp_port)
+                ),
+                "websocket" => format!("ws://{}", host),
+                _ => format!("http://{}", host),
+            };
+            let ports: Vec<serde_json::Value> = c
+                .ports
+                .iter()
+                .filter(|(host, _)| Some(*host) != proxy_port)
+                .map(
+                    |(host, container)| serde_json::json!({ "host": host, "container": container }),
+                )
+                .collect();
+
+            serde_json::json!({
+                "name": c.name,
+                "domain": c.domain,
+                "group": group,
+                "service": c.service,
+                "url": url,
+                "proxy_port": proxy_port,
+                "ports": ports,
+                "status": c.status,
+                "engine": engine,
+            })
+        })
+        .collect()
+}
+
+/// `darp ps`: the containers on `engine`, plus those on any engine a domain or environment
+/// overrides it with. An override engine that isn't running is skipped with a warning.
+pub fn cmd_ps(
+    json: bool,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    engine.require_ready()?;
+
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let engine_name = |engine: &Engine| match &engine.remote {
+        Some(remote) => format!("{} ({})", engine.kind.as_str(), remote.host),
+        None => engine.kind.as_str().to_string(),
+    };
+    let mut entries =
+        build_ps_entries(&engine.service_containers(), &portmap, &engine_name(engine));
+    for other in Engine::overrides(config)? {
+        match other.require_ready() {
+            Ok(()) => entries.extend(build_ps_entries(
+                &other.service_containers(),
+                &portmap,
+                &engine_name(&other),
+            )),
+            Err(e) => warn!("{} containers are left out: {}", other.kind.as_str(), e),
+        }
+    }
+    entries.sort_by_key(|e| e["name"].as_str().unwrap_or_default().to_string());
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No darp containers are running.");
+        return Ok(());
+    }
+
+    let text = |v: &serde_json::Value| match v {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => "-".to_string(),
+        other => other.to_string(),
+    };
+    let header = [
+        "NAME", "SERVICE", "URL", "PROXY", "PORTS", "STATUS", "ENGINE",
+    ];
+    let rows: Vec<[String; 7]> = entries
+        .iter()
+        .map(|e| {
+            let ports: Vec<String> = e["ports"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|p| format!("{}->{}", p["host"], text(&p["container"])))
+                .collect();
+            [
+                text(&e["name"]),
+                format!("{}.{}", text(&e["service"]), text(&e["domain"])),
+                text(&e["url"]),
+                text(&e["proxy_port"]),
+                if ports.is_empty() {
+                    "-".to_string()
+                } else {
+                    ports.join(", ")
+                },
+                text(&e["status"]),
+                text(&e["engine"]),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    // Pad before colouring so escape codes don't skew the columns.
+    let pad = |cells: &[String; 7]| -> Vec<String> {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{:<w$}", cell, w = w))
+            .collect()
+    };
+    println!(
+        "{}",
+        pad(&header.map(String::from)).join("  ").trim_end().bold()
+    );
+    for row in &rows {
+        let cells = pad(row);
+        println!("{}  {}", cells[0].cyan(), cells[1..].join("  ").trim_end());
+    }
+    Ok(())
+}
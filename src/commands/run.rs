@@ -1,39 +1,340 @@
+use std::io::{IsTerminal, Write};
+
+use anyhow::anyhow;
 use colored::*;
+use tracing::{info, warn};
 
+use super::add_service::register_service;
+use super::deploy::cmd_deploy;
+use super::image_build::{ensure_image_built, image_build_tag};
+use super::picker::{picked_service_engine, service_context_or_pick};
+use super::plan::{Mount, RunPlan};
+use crate::cli::RunArgs;
 use crate::config::{self, Config, DarpPaths, ResolvedSettings, ServiceContext};
-use crate::engine::{Engine, EngineKind};
+use crate::deploy::resolve_deploy_connection_type;
+use crate::engine::{self, Engine, EngineKind};
+use crate::error::DarpError;
+use crate::logging::Logged;
+use crate::os::OsIntegration;
+use crate::secrets::SecretStore;
+use crate::services_index::write_services_index;
+use crate::timings;
+
+/// The image a service runs in: `cli_image` when given, else the tag of its `image_build`
+/// recipe, else the configured image (`shell_image` first for `darp shell`).
+pub(crate) fn service_image(
+    ctx: &ServiceContext<'_>,
+    resolved: &ResolvedSettings,
+    cli_image: Option<&str>,
+    shell: bool,
+) -> Option<String> {
+    let recipe = ctx.service.and_then(|s| s.image_build.as_ref());
+    match (cli_image, recipe) {
+        (None, Some(build)) => Some(image_build_tag(
+            &ctx.domain_name,
+            &ctx.current_directory_name,
+            build,
+        )),
+        _ if shell => resolved.resolve_shell_image_name(cli_image),
+        _ => resolved.resolve_full_image_name(cli_image),
+    }
+}
+
+/// Build the service's `image_build` recipe when `service_image` picked it and the
+/// engine doesn't have it yet.
+pub(crate) fn build_service_image(
+    ctx: &ServiceContext<'_>,
+    resolved: &ResolvedSettings,
+    cli_image: Option<&str>,
+    paths: &DarpPaths,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let Some(build) = ctx.service.and_then(|s| s.image_build.as_ref()) else {
+        return Ok(());
+    };
+    if cli_image.is_some() {
+        return Ok(());
+    }
+    let tag = image_build_tag(&ctx.domain_name, &ctx.current_directory_name, build);
+    ensure_image_built(engine, paths, &tag, build, resolved.platform.as_deref())
+}
 
-fn add_platform_args(cmd: &mut std::process::Command, engine: &Engine, platform: &str) {
+fn add_platform_args(plan: &mut RunPlan, engine: &Engine, platform: &str) {
     match engine.kind {
-        EngineKind::Docker => {
-            cmd.arg("--platform").arg(platform);
+        EngineKind::Docker => plan.option(["--platform", platform]),
+        EngineKind::Podman => match platform.split_once('/') {
+            Some((os, rest)) => {
+                let arch = rest.split('/').next().unwrap_or(rest);
+                plan.option(["--os", os, "--arch", arch]);
+            }
+            None => plan.option(["--arch", platform]),
+        },
+        EngineKind::None => {}
+    }
+}
+
+/// Env files for a run: the service's configured `env_file` (skipped with a note if the
+/// file doesn't exist yet) followed by every `--env-file` from the CLI, which must exist.
+fn collect_env_files(
+    ctx: &ServiceContext<'_>,
+    cli_env_files: &[String],
+    config: &Config,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let domain_loc = config::resolve_location(&ctx.domain.location)?;
+    let mut files = Vec::new();
+
+    if let Some(template) = ctx.service.and_then(|s| s.env_file.as_deref()) {
+        let path = ctx.current_dir.join(config.resolve_host_path(
+            template,
+            &ctx.current_dir,
+            &domain_loc,
+        )?);
+        if path.exists() {
+            files.push(path);
+        } else {
+            info!(
+                "env_file {} not found, skipping",
+                path.display().to_string().yellow()
+            );
         }
-        EngineKind::Podman => {
-            let parts: Vec<&str> = platform.split('/').collect();
-            if parts.len() >= 2 {
-                let os = parts[0];
-                let arch = parts[1];
-                cmd.arg("--os").arg(os);
-                cmd.arg("--arch").arg(arch);
-            } else {
-                cmd.arg("--arch").arg(platform);
+    }
+
+    for file in cli_env_files {
+        let path = ctx.current_dir.join(file);
+        if !path.exists() {
+            return Err(anyhow!("env file {} does not exist", path.display()));
+        }
+        files.push(path);
+    }
+
+    Ok(files)
+}
+
+/// Pod the service's container joins: only when its domain opts in and the engine is Podman.
+fn domain_pod(ctx: &ServiceContext<'_>, engine: &Engine) -> Option<String> {
+    (ctx.domain.pod == Some(true) && matches!(engine.kind, EngineKind::Podman))
+        .then(|| engine::pod_name(&ctx.domain_name))
+}
+
+/// Create the domain's pod before its first container starts, publishing every port
+/// `darp deploy` assigned in the domain.
+pub(crate) fn prepare_pod(
+    ctx: &ServiceContext<'_>,
+    paths: &DarpPaths,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    if domain_pod(ctx, engine).is_none() {
+        return Ok(());
+    }
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    engine.ensure_pod(
+        &ctx.domain_name,
+        &config::portmap_domain_ports(&portmap, &ctx.domain_name),
+    )
+}
+
+/// Create the named volumes `plan` mounts that don't exist yet.
+pub(crate) fn prepare_volumes(plan: &RunPlan, engine: &Engine) -> anyhow::Result<()> {
+    for mount in &plan.mounts {
+        if config::is_volume_name(&mount.host) {
+            engine.ensure_volume(&mount.host)?;
+        }
+    }
+    Ok(())
+}
+
+/// How a service without a proxy port gets one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registration {
+    /// Other services are deployed; `darp add-service` registers this one alone.
+    AddService,
+    /// Nothing is deployed yet, so a full `darp deploy` is needed.
+    Deploy,
+}
+
+/// What registering the service takes, or None when `darp deploy` already gave it a port.
+pub fn missing_registration(
+    paths: &DarpPaths,
+    domain_name: &str,
+    group_name: &str,
+    service_name: &str,
+) -> Option<Registration> {
+    let Ok(portmap) = config::read_json::<serde_json::Value>(&paths.portmap_path) else {
+        return Some(Registration::Deploy);
+    };
+    if config::portmap_proxy_port(&portmap, domain_name, group_name, service_name).is_some() {
+        return None;
+    }
+    // add-service appends to these, so without them only a full deploy will do.
+    if paths.vhost_container_conf.exists() && paths.hosts_container_path.exists() {
+        Some(Registration::AddService)
+    } else {
+        Some(Registration::Deploy)
+    }
+}
+
+/// Before a service's container is built, offer to register it when `darp deploy` hasn't
+/// given it a port yet. Declining, or having no terminal to ask on, leaves the usual
+/// "run 'darp deploy'" error to `build_container_command`.
+fn offer_registration(
+    ctx: &ServiceContext<'_>,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let Some(registration) = missing_registration(
+        paths,
+        &ctx.domain_name,
+        &ctx.group_name,
+        &ctx.current_directory_name,
+    ) else {
+        return Ok(());
+    };
+    // Non-interactive mode takes the default answer, yes, without asking.
+    if !crate::os::assume_yes() {
+        if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+            return Ok(());
+        }
+
+        let url = config.service_host(&ctx.current_directory_name, &ctx.domain_name);
+        match registration {
+            Registration::AddService => eprint!(
+                "{} has no port yet. Register it now ('darp add-service')? [Y/n] ",
+                url.cyan()
+            ),
+            Registration::Deploy => eprint!(
+                "{} has no port yet and nothing is deployed. Run 'darp deploy' now? [Y/n] ",
+                url.cyan()
+            ),
+        }
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if matches!(answer.trim().to_lowercase().as_str(), "n" | "no") {
+            return Ok(());
+        }
+    }
+
+    match registration {
+        Registration::AddService => register_service(ctx, paths, config, engine),
+        Registration::Deploy => {
+            let os = OsIntegration::new(paths, config, &engine.kind);
+            cmd_deploy(paths, config, &os, engine, false)
+        }
+    }
+}
+
+/// Turn a configured healthcheck into the command the engine runs: URLs are fetched with
+/// whichever of curl/wget the image has, anything else runs as-is.
+fn health_command(check: &str) -> String {
+    if check.starts_with("http://") || check.starts_with("https://") {
+        format!(
+            "curl -fsS -o /dev/null '{url}' || wget -q -O /dev/null '{url}'",
+            url = check
+        )
+    } else {
+        check.to_string()
+    }
+}
+
+/// Poll a starting container's healthcheck and report the outcome. Returns once the
+/// service is healthy or unhealthy, the timeout passes, or the container goes away.
+fn watch_health(engine: &Engine, container_name: &str, url: &str, timeout: u64) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout);
+    let mut seen = false;
+    let mut last_output = None;
+
+    while std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        match engine.container_health(container_name) {
+            Some(health) => {
+                seen = true;
+                match health.status.as_str() {
+                    "healthy" => {
+                        println!("{} is healthy: {}", container_name.cyan(), url.green());
+                        return;
+                    }
+                    "unhealthy" => {
+                        eprintln!("{} is unhealthy", container_name.red());
+                        if let Some(output) = health.last_output {
+                            eprintln!("last healthcheck output:\n{}", output);
+                        }
+                        return;
+                    }
+                    _ => last_output = health.last_output,
+                }
             }
+            None if seen => return,
+            None => {}
         }
-        EngineKind::None => {}
+    }
+
+    eprintln!(
+        "{} did not become healthy within {}s",
+        container_name.red(),
+        timeout
+    );
+    if let Some(output) = last_output {
+        eprintln!("last healthcheck output:\n{}", output);
     }
 }
 
-/// Build the common container run command used by both cmd_shell and cmd_serve.
-fn build_container_command(
+/// Shells an `entrypoint` can name, by file name: those get darp's scripts as `-c <script>`.
+const SHELLS: &[&str] = &["sh", "ash", "bash", "dash", "ksh", "zsh"];
+
+fn is_shell(entrypoint: &str) -> bool {
+    let name = entrypoint.rsplit('/').next().unwrap_or(entrypoint);
+    SHELLS.contains(&name)
+}
+
+/// The service's entrypoint when it isn't a shell. `darp serve` runs it as is, with the
+/// serve command as its arguments, since there's no shell to run darp's script with.
+fn direct_entrypoint<'a>(ctx: &ServiceContext<'a>) -> Option<&'a str> {
+    ctx.service
+        .and_then(|s| s.entrypoint.as_deref())
+        .filter(|e| !is_shell(e))
+}
+
+/// The shell darp's scripts run with in a service's containers: its entrypoint when that
+/// is a shell, else `sh`.
+fn bootstrap_shell<'a>(ctx: &ServiceContext<'a>) -> &'a str {
+    ctx.service
+        .and_then(|s| s.entrypoint.as_deref())
+        .filter(|e| is_shell(e))
+        .unwrap_or("sh")
+}
+
+/// The command after the image that runs `script`. When the service has an entrypoint,
+/// `build_run_plan` already made the shell the entrypoint, so only `-c <script>` follows.
+fn bootstrap_command(ctx: &ServiceContext<'_>, script: &str) -> Vec<String> {
+    let mut command = Vec::new();
+    if ctx.service.and_then(|s| s.entrypoint.as_ref()).is_none() {
+        command.push("sh".to_string());
+    }
+    command.extend(["-c".to_string(), script.to_string()]);
+    command
+}
+
+/// Build the run plan shared by cmd_shell and cmd_serve.
+#[allow(clippy::too_many_arguments)]
+fn build_run_plan(
     resolved: &ResolvedSettings,
     ctx: &ServiceContext<'_>,
     image_name: &str,
     interactive: bool,
+    detach: bool,
+    cli_env_files: &[String],
+    cli_network: Option<&str>,
+    replica: usize,
     paths: &DarpPaths,
     config: &Config,
     engine: &Engine,
-) -> anyhow::Result<std::process::Command> {
-    let container_name = format!("darp_{}_{}", resolved.domain_name, resolved.service_name);
+) -> anyhow::Result<RunPlan> {
+    let container_name = replica_container_name(
+        &engine.service_container_name(&resolved.domain_name, &resolved.service_name),
+        replica,
+    );
 
     let portmap: serde_json::Value =
         config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
@@ -45,13 +346,24 @@ fn build_container_command(
         &resolved.group_name,
         &resolved.service_name,
     )
-    .unwrap_or_else(|| {
-        eprintln!(
-            "port not yet assigned to {}, run 'darp deploy'",
-            resolved.service_name
-        );
-        std::process::exit(1);
-    });
+    .ok_or_else(|| DarpError::PortNotAssigned(resolved.service_name.clone()))?;
+    // Each replica listens on a proxy port of its own; the first uses the service's.
+    let replica_ports = config::portmap_replica_ports(
+        &portmap,
+        &resolved.domain_name,
+        &resolved.group_name,
+        &resolved.service_name,
+    );
+    let rev_proxy_port = match replica {
+        0 => rev_proxy_port,
+        i => replica_ports.get(i - 1).copied().ok_or_else(|| {
+            anyhow!(
+                "replica {} of {} has no port yet, run 'darp deploy'",
+                i + 1,
+                resolved.service_name
+            )
+        })?,
+    };
 
     // Debug port is assigned by `darp deploy`; fall back to the base for stale portmaps
     // written before this feature (so pre-upgrade deploys keep working).
@@ -71,61 +383,122 @@ fn build_container_command(
         proxy_port: Some(rev_proxy_port),
     };
 
-    let mut cmd = if interactive {
-        engine.base_run_interactive(&container_name)
+    let options = if interactive {
+        engine.run_options_interactive(&resolved.domain_name, &resolved.service_name)
     } else {
-        engine.base_run_noninteractive(&container_name)
+        engine.run_options_noninteractive(
+            &resolved.domain_name,
+            &resolved.service_name,
+            resolved.restart.as_deref(),
+            detach,
+        )
     };
+    let mut plan = RunPlan::new(&container_name, options);
+    plan.image = image_name.to_string();
 
     if engine.is_docker() {
-        cmd.arg("--add-host")
-            .arg("host.docker.internal:host-gateway");
-    }
-
-    cmd.arg("-v")
-        .arg(format!("{}:/app", ctx.current_dir.display()))
-        .arg("-v")
-        .arg(format!(
-            "{}:/etc/hosts",
-            paths.hosts_container_path.display()
-        ))
-        .arg("-v")
-        .arg(format!(
-            "{}:/etc/nginx/nginx.conf",
-            paths.nginx_conf_path.display()
-        ))
-        .arg("-v")
-        .arg(format!(
-            "{}:/etc/nginx/http.d/vhost_container.conf",
-            paths.vhost_container_conf.display()
-        ));
+        plan.option(["--add-host", "host.docker.internal:host-gateway"]);
+    }
 
+    let pod = domain_pod(ctx, engine);
+    if let Some(pod) = &pod {
+        plan.option(["--pod", pod]);
+    } else if ctx.domain.pod == Some(true) {
+        info!(
+            "pods need podman; running {} without one",
+            resolved.service_name
+        );
+    }
+
+    plan.mounts.push(Mount {
+        host: ctx.current_dir.display().to_string(),
+        container: "/app".to_string(),
+        read_only: resolved.read_only_app,
+    });
+    plan.mount(
+        paths.hosts_container_path.display().to_string(),
+        "/etc/hosts",
+    );
+    plan.mount(
+        paths.nginx_conf_path.display().to_string(),
+        "/etc/nginx/nginx.conf",
+    );
+    plan.mount(
+        paths.vhost_container_conf.display().to_string(),
+        "/etc/nginx/http.d/vhost_container.conf",
+    );
+
+    // The service folder and its volumes; darp's own files are copied to a remote.
+    let mut user_mounts = vec![ctx.current_dir.display().to_string()];
     if let Some(vols) = &resolved.volumes {
         let domain_loc = config::resolve_location(&ctx.domain.location)?;
         for v in vols {
+            if let Some(name) = v.volume_name() {
+                plan.mounts.push(Mount {
+                    host: name.to_string(),
+                    container: v.container.clone(),
+                    read_only: v.read_only,
+                });
+                continue;
+            }
             let host = config.resolve_host_path(&v.host, &ctx.current_dir, &domain_loc)?;
             if !host.exists() {
-                eprintln!("Volume {} does not appear to exist.", v.host);
-                std::process::exit(1);
+                return Err(DarpError::VolumeNotFound(v.host.clone()).into());
             }
-            cmd.arg("-v")
-                .arg(format!("{}:{}", host.display(), v.container));
+            user_mounts.push(host.display().to_string());
+            plan.mounts.push(Mount {
+                host: host.display().to_string(),
+                container: v.container.clone(),
+                read_only: v.read_only,
+            });
         }
     }
+    if let (Some(remote), Some(missing)) = (
+        &engine.remote,
+        engine.missing_remote_paths(&user_mounts)?.first(),
+    ) {
+        return Err(DarpError::RemoteVolumeNotFound(missing.clone(), remote.host.clone()).into());
+    }
 
     if let Some(pm) = &resolved.host_portmappings {
-        for (host_port, container_port) in pm {
-            cmd.arg("-p").arg(format!(
-                "{host}:{container}",
-                host = config::substitute_tokens(host_port, &tokens),
-                container = config::substitute_tokens(container_port, &tokens)
-            ));
+        if let Some(pod) = &pod {
+            if !pm.is_empty() {
+                info!(
+                    "host_portmappings are ignored inside pod {}; it publishes the domain's proxy and debug ports",
+                    pod
+                );
+            }
+        } else {
+            for (host_port, container_port) in pm {
+                plan.ports.push(format!(
+                    "{host}:{container}",
+                    host = config::substitute_tokens(host_port, &tokens),
+                    container = config::substitute_tokens(container_port, &tokens)
+                ));
+            }
         }
     }
 
+    for env_file in collect_env_files(ctx, cli_env_files, config)? {
+        plan.env_files.push(env_file.display().to_string());
+    }
+
+    // Secrets go through darp's own environment (`-e NAME` with no value) so they never
+    // show up in the engine's argv, `ps`, or --dry-run output.
+    let secrets = SecretStore::load(&paths.secrets_path)?.for_domain(&resolved.domain_name);
+    for (name, value) in secrets {
+        plan.env.push(name.clone());
+        plan.secrets.push((name, value));
+    }
+
+    // Lets a replicated service tell its containers apart (logs, session-affinity tests).
+    if !replica_ports.is_empty() {
+        plan.env.push(format!("DARP_REPLICA={}", replica + 1));
+    }
+
     if let Some(vars) = &resolved.variables {
         for (name, value) in vars {
-            cmd.arg("-e").arg(format!(
+            plan.env.push(format!(
                 "{name}={value}",
                 name = name,
                 value = config::substitute_tokens(value, &tokens)
@@ -134,46 +507,145 @@ fn build_container_command(
     }
 
     if let Some(ref platform) = resolved.platform {
-        add_platform_args(&mut cmd, engine, platform);
+        add_platform_args(&mut plan, engine, platform);
     }
 
-    // Container-internal port convention keyed off connection_type:
-    //   http      -> 8000 (default)
-    //   websocket -> 8001
-    //   tcp       -> 8002
-    let container_port: u16 = match resolved.connection_type.as_deref() {
-        Some("websocket") => 8001,
-        Some("tcp") => 8002,
-        _ => 8000,
+    // A pod owns its network namespace, so its containers can't pick their own network.
+    let user_network = cli_network.or(ctx.domain.network.as_deref());
+    if pod.is_some() {
+        if let Some(network) = user_network {
+            info!("--network {} is ignored inside a pod", network);
+        }
+    } else {
+        if engine.darp_network {
+            plan.option(["--network", engine::DARP_NETWORK]);
+        }
+        if let Some(network) = user_network {
+            plan.option(["--network", network]);
+        }
+    }
+
+    // Shell containers get no healthcheck: they don't run the service.
+    if let Some(check) = ctx.service.and_then(|s| s.healthcheck.as_deref()) {
+        if !interactive {
+            plan.option([
+                "--health-cmd",
+                &health_command(check),
+                "--health-interval",
+                "5s",
+            ]);
+        }
+    }
+
+    if let Some(limits) = &resolved.limits {
+        if let Some(cpus) = &limits.cpus {
+            plan.option(["--cpus", cpus]);
+        }
+        if let Some(memory) = &limits.memory {
+            plan.option(["--memory", memory]);
+        }
+    }
+
+    if resolved.map_user {
+        let (uid, gid) = engine::host_user_ids()?;
+        plan.options
+            .extend(engine.map_user_options(uid, gid, pod.is_some()));
+    }
+
+    if let Some(entrypoint) = ctx.service.and_then(|s| s.entrypoint.as_deref()) {
+        // A shell gets `sh` in place of an entrypoint that isn't one.
+        let entrypoint = if interactive && !is_shell(entrypoint) {
+            bootstrap_shell(ctx)
+        } else {
+            entrypoint
+        };
+        plan.option(["--entrypoint", entrypoint]);
+    }
+
+    if let Some(run_args) = ctx.service.and_then(|s| s.run_args.as_ref()) {
+        plan.options.extend(run_args.iter().cloned());
+    }
+
+    let container_port = container_port(resolved.connection_type.as_deref());
+    // Services in a pod share one network namespace, so they can't all listen on the
+    // same port: each listens on its own proxy port, which the pod already publishes.
+    // On the darp network the proxy reaches HTTP/WebSocket services by container name;
+    // only TCP services, which nginx can't route by hostname, and replicas, which its
+    // upstream block reaches through the host gateway, still need a host port.
+    let is_tcp = resolved.connection_type.as_deref() == Some("tcp");
+    if pod.is_none() && (!engine.darp_network || is_tcp || !replica_ports.is_empty()) {
+        // A UDP stream port forwards datagrams, which a plain -p mapping wouldn't carry.
+        let udp = ctx
+            .service
+            .and_then(|s| s.stream.as_ref())
+            .is_some_and(|s| s.udp == Some(true));
+        plan.ports.push(format!(
+            "{}:{}{}",
+            rev_proxy_port,
+            container_port,
+            if udp { "/udp" } else { "" }
+        ));
+    }
+
+    Ok(plan)
+}
+
+/// Run a service container interactively, keeping the service switcher index in step:
+/// the container is listed as running while it runs and cleared once it exits. Output
+/// lines get the time and `prefix` in front when it's given.
+fn run_and_track(
+    engine: &Engine,
+    paths: &DarpPaths,
+    plan: &RunPlan,
+    prefix: Option<&str>,
+) -> anyhow::Result<()> {
+    let container_name = plan.name.as_str();
+    let refresh = |running: std::collections::HashSet<String>| {
+        if let Err(e) = write_services_index(paths, engine, &running)
+            .and_then(|_| engine.sync_remote_files(paths))
+        {
+            warn!("could not update services index: {}", e);
+        }
     };
-    cmd.arg("-p")
-        .arg(format!("{}:{}", rev_proxy_port, container_port));
-    cmd.arg(image_name);
 
-    Ok(cmd)
+    let mut running = engine.running_container_names();
+    running.insert(container_name.to_string());
+    refresh(running);
+
+    let result =
+        engine.run_container_interactive(plan.command(engine), container_name, &[], prefix);
+
+    let mut running = engine.running_container_names();
+    running.remove(container_name);
+    refresh(running);
+
+    result
 }
 
 pub fn cmd_shell(
-    environment_cli: Option<String>,
-    dry_run: bool,
-    container_image: Option<String>,
+    args: RunArgs,
+    persist: bool,
     paths: &DarpPaths,
     config: &Config,
     engine: &Engine,
 ) -> anyhow::Result<()> {
-    engine.require_ready()?;
+    let RunArgs {
+        environment: environment_cli,
+        dry_run,
+        print_plan,
+        env_files,
+        network,
+        container_image,
+    } = args;
 
-    let ctx = config
-        .service_context_from_cwd(environment_cli)
-        .unwrap_or_else(|| {
-            eprintln!("Current directory does not exist in any darp domain configuration.");
-            std::process::exit(1);
-        });
+    let ctx = service_context_or_pick(paths, config, environment_cli)?;
+    let picked_engine = picked_service_engine(config, &ctx, engine)?;
+    let engine = picked_engine.as_ref().unwrap_or(engine);
+    engine.require_ready()?;
 
     if let Some(ref env_name) = ctx.environment_name {
         if ctx.environment.is_none() {
-            eprintln!("Environment '{}' does not exist.", env_name);
-            std::process::exit(1);
+            return Err(DarpError::EnvironmentNotFound(env_name.clone()).into());
         }
     }
 
@@ -188,65 +660,93 @@ pub fn cmd_shell(
         ctx.environment,
     );
 
-    let container_name = format!("darp_{}_{}", ctx.domain_name, ctx.current_directory_name);
     let shell_command = resolved.shell_command.as_deref().unwrap_or("sh");
 
-    if engine.is_container_running(&container_name) {
+    let persistent_name = engine::persistent_shell_name(
+        &engine.service_container_name(&ctx.domain_name, &ctx.current_directory_name),
+    );
+    let existing = if persist && !print_plan {
+        engine
+            .container_status(&persistent_name)
+            .map(|status| (persistent_name.clone(), status))
+    } else {
+        // Use the real name: a user may have renamed the running container. A plan
+        // describes the container darp would start, so it skips attaching.
+        engine
+            .running_service_container(&ctx.domain_name, &ctx.current_directory_name)
+            .filter(|_| !print_plan && !persist)
+            .map(|name| (name, "running".to_string()))
+    };
+    if let Some((container_name, status)) = existing {
+        let bin = engine.bin.unwrap_or("docker");
         if dry_run {
+            if status != "running" {
+                println!("{} start {}", bin, container_name);
+            }
             println!(
-                "{} exec -it {} sh -c 'cd /app; exec {}'",
-                engine.bin.unwrap_or("docker"),
+                "{} exec -it {} {} -c 'cd /app; exec {}'",
+                bin,
                 container_name,
+                bootstrap_shell(&ctx),
                 shell_command
             );
             return Ok(());
         }
 
-        println!(
-            "Attaching to running container {}...",
-            ctx.current_directory_name.cyan()
-        );
-        let bin = engine.bin.expect("engine bin not set");
-        let exec_inner = format!("cd /app; exec {}", shell_command);
-        let status = std::process::Command::new(bin)
-            .arg("exec")
-            .arg("-it")
-            .arg(&container_name)
-            .arg("sh")
-            .arg("-c")
-            .arg(&exec_inner)
-            .status()?;
-
-        if let Some(code) = status.code() {
-            if code != 0 {
-                println!("exiting with status code {}", code);
+        if status != "running" {
+            let output = engine
+                .command()
+                .arg("start")
+                .arg(&container_name)
+                .logged()
+                .output()?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "could not start {}: {}",
+                    container_name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
             }
         }
-        return Ok(());
+        println!("Attaching to container {}...", container_name.cyan());
+        return exec_shell(
+            engine,
+            &container_name,
+            bootstrap_shell(&ctx),
+            shell_command,
+        );
+    }
+    if !dry_run && !print_plan {
+        offer_registration(&ctx, paths, config, engine)?;
     }
 
-    let image_name = resolved
-        .resolve_full_image_name(container_image.as_deref())
-        .unwrap_or_else(|| {
-            eprintln!(
-                "No container image provided for '{}.{}'.\n\
-                 Either pass an explicit image to 'darp shell' or configure a default_container_image:\n\
-                   darp config set svc default-container-image {} {} <image>\n\
-                 or\n\
-                   darp config set env default-container-image <env> <image>",
-                ctx.domain_name,
-                ctx.current_directory_name,
-                ctx.domain_name,
-                ctx.current_directory_name,
-            );
-            std::process::exit(1);
-        });
+    let image_name =
+        service_image(&ctx, &resolved, container_image.as_deref(), true).ok_or_else(|| {
+            DarpError::NoShellImage {
+                domain: ctx.domain_name.clone(),
+                service: ctx.current_directory_name.clone(),
+            }
+        })?;
 
-    let mut cmd =
-        build_container_command(&resolved, &ctx, &image_name, true, paths, config, engine)?;
+    let mut plan = build_run_plan(
+        &resolved,
+        &ctx,
+        &image_name,
+        true,
+        false,
+        &env_files,
+        network.as_deref(),
+        0,
+        paths,
+        config,
+        engine,
+    )?;
 
-    let inner_cmd = format!(
-        r#"if command -v nginx >/dev/null 2>&1; then
+    if persist {
+        plan = persistent_shell_plan(plan, &ctx, engine);
+    } else {
+        let inner_cmd = format!(
+            r#"if command -v nginx >/dev/null 2>&1; then
     echo "Starting nginx..."; nginx;
 else
     echo "nginx not found, skipping";
@@ -255,53 +755,117 @@ echo "";
 echo "To leave this shell and stop the container, type: $(printf '\033[33m')exit$(printf '\033[0m')"
 echo "";
 cd /app; exec {shell}"#,
-        shell = shell_command
-    );
-
-    cmd.arg("sh").arg("-c").arg(inner_cmd);
+            shell = shell_command
+        );
+        plan.command = bootstrap_command(&ctx, &inner_cmd);
+    }
 
+    if print_plan {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
     if dry_run {
-        println!("{}", engine.command_to_string(&cmd));
+        println!("{}", engine.command_to_string(&plan.command(engine)));
         return Ok(());
     }
 
-    engine.run_container_interactive(cmd, &container_name, &[])?;
-    Ok(())
+    build_service_image(&ctx, &resolved, container_image.as_deref(), paths, engine)?;
+    prepare_pod(&ctx, paths, engine)?;
+    prepare_volumes(&plan, engine)?;
+    if !persist {
+        return run_and_track(engine, paths, &plan, None);
+    }
+    let output = plan.command(engine).logged().output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "could not create {}: {}",
+            plan.name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    println!(
+        "Created {}; it keeps running until you stop or remove it.",
+        plan.name.cyan()
+    );
+    exec_shell(engine, &plan.name, bootstrap_shell(&ctx), shell_command)
 }
 
-pub fn cmd_serve(
-    environment_cli: Option<String>,
-    dry_run: bool,
-    container_image: Option<String>,
-    paths: &DarpPaths,
-    config: &Config,
+/// What the container of `darp shell --persist` runs: nothing but staying up, stopping
+/// promptly when the engine asks. Shells are opened in it with `exec`.
+const KEEP_ALIVE: &str = "trap 'exit 0' TERM INT; while :; do sleep 3600 & wait $!; done";
+
+/// Turn a shell's run plan into the one for its long-lived `--persist` container: its own
+/// name and labels, no `--rm`, no published ports (they belong to `darp serve`), and a
+/// keep-alive command.
+fn persistent_shell_plan(mut plan: RunPlan, ctx: &ServiceContext<'_>, engine: &Engine) -> RunPlan {
+    // build_run_plan starts the options with the interactive ones; swap them out.
+    let interactive = engine
+        .run_options_interactive(&ctx.domain_name, &ctx.current_directory_name)
+        .len();
+    plan.options.splice(
+        ..interactive,
+        engine.run_options_persistent_shell(&ctx.domain_name, &ctx.current_directory_name),
+    );
+    plan.name = engine::persistent_shell_name(&plan.name);
+    plan.ports.clear();
+    plan.command = bootstrap_command(ctx, KEEP_ALIVE);
+    plan
+}
+
+/// Open the service's shell in a running container, in /app, through `sh` (see
+/// `bootstrap_shell`).
+fn exec_shell(
     engine: &Engine,
+    container_name: &str,
+    sh: &str,
+    shell_command: &str,
 ) -> anyhow::Result<()> {
-    engine.require_ready()?;
-
-    let ctx = config
-        .service_context_from_cwd(environment_cli)
-        .unwrap_or_else(|| {
-            eprintln!("Current directory does not exist in any darp domain configuration.");
-            std::process::exit(1);
-        });
+    let exec_inner = format!("cd /app; exec {}", shell_command);
+    let status = engine
+        .command()
+        .arg("exec")
+        .arg("-it")
+        .arg(container_name)
+        .arg(sh)
+        .arg("-c")
+        .arg(&exec_inner)
+        .logged()
+        .status()?;
 
-    let environment_name = match &ctx.environment_name {
-        Some(name) => name.clone(),
-        None => {
-            eprintln!(
-                "Environment is required for 'darp serve' in domain '{}'.\n\
-Either pass an explicit environment:\n  darp serve --environment <env>\n\
-or configure a default_environment for this domain:\n  darp config set dom default-environment {} <env>",
-                ctx.domain_name, ctx.domain_name
-            );
-            std::process::exit(1);
+    if let Some(code) = status.code() {
+        if code != 0 {
+            println!("exiting with status code {}", code);
         }
-    };
+    }
+    Ok(())
+}
+
+/// What `darp serve` resolves for a service before running anything.
+pub(crate) struct ServeSetup {
+    pub resolved: ResolvedSettings,
+    pub environment_name: String,
+    /// serve_command with `{debug_port}`, `{proxy_port}`, … substituted.
+    pub serve_command: String,
+    /// The service's pre_serve / post_serve hooks, tokens substituted the same way.
+    pub pre_serve: Vec<String>,
+    pub post_serve: Vec<String>,
+}
+
+/// Resolve a service's settings and serve command, failing with guidance when the
+/// environment or serve_command is missing.
+pub(crate) fn resolve_serve(
+    ctx: &ServiceContext<'_>,
+    paths: &DarpPaths,
+) -> Result<ServeSetup, DarpError> {
+    let environment_name =
+        ctx.environment_name
+            .clone()
+            .ok_or_else(|| DarpError::EnvironmentRequired {
+                domain: ctx.domain_name.clone(),
+            })?;
 
     if ctx.environment.is_none() {
-        eprintln!("Environment '{}' does not exist.", environment_name);
-        std::process::exit(1);
+        return Err(DarpError::EnvironmentNotFound(environment_name));
     }
 
     let resolved = ResolvedSettings::resolve(
@@ -315,23 +879,13 @@ or configure a default_environment for this domain:\n  darp config set dom defau
         ctx.environment,
     );
 
-    let serve_command = resolved.serve_command.as_deref().unwrap_or_else(|| {
-        eprintln!(
-            "Neither service '{}.{}', domain '{}', nor environment '{}' has a serve_command configured.\n\
-Use 'darp config set svc serve-command {} {} <cmd>' or \
-'darp config set dom serve-command {} <cmd>' or \
-'darp config set env serve-command {} <cmd>' first.",
-            ctx.domain_name,
-            ctx.current_directory_name,
-            ctx.domain_name,
-            environment_name,
-            ctx.domain_name,
-            ctx.current_directory_name,
-            ctx.domain_name,
-            environment_name,
-        );
-        std::process::exit(1);
-    });
+    let Some(serve_command) = resolved.serve_command.as_deref() else {
+        return Err(DarpError::NoServeCommand {
+            domain: ctx.domain_name.clone(),
+            service: ctx.current_directory_name.clone(),
+            environment: environment_name,
+        });
+    };
 
     // Interpolate {debug_port}/{proxy_port}/… in the serve command so per-service
     // debugger flags (e.g. `dlv --listen=:{debug_port}`) resolve. Ports come from the
@@ -357,11 +911,388 @@ Use 'darp config set svc serve-command {} {} <cmd>' or \
         ),
     };
     let serve_command = config::substitute_tokens(serve_command, &serve_tokens);
-    let serve_command = serve_command.as_str();
+    let hooks = |hooks: Option<&Vec<String>>| -> Vec<String> {
+        hooks
+            .into_iter()
+            .flatten()
+            .map(|h| config::substitute_tokens(h, &serve_tokens))
+            .collect()
+    };
+    let pre_serve = hooks(ctx.service.and_then(|s| s.pre_serve.as_ref()));
+    let post_serve = hooks(ctx.service.and_then(|s| s.post_serve.as_ref()));
 
-    let container_name = format!("darp_{}_{}", ctx.domain_name, ctx.current_directory_name);
+    Ok(ServeSetup {
+        resolved,
+        environment_name,
+        serve_command,
+        pre_serve,
+        post_serve,
+    })
+}
 
-    if engine.is_container_running(&container_name) {
+/// The part of a serve container's `sh -c` script that runs the service: each
+/// `pre_serve` hook (the first failure exits with its status), then the serve command.
+/// With `post_serve` hooks, the serve command runs in the background so a stop signal
+/// can be passed on to it; the hooks run once it exits and the script keeps its status.
+pub fn serve_script(serve: &str, pre_serve: &[String], post_serve: &[String]) -> String {
+    if pre_serve.is_empty() && post_serve.is_empty() {
+        return format!("cd /app; {}", serve);
+    }
+
+    let mut script = String::from("cd /app;\n");
+    for hook in pre_serve {
+        script.push_str(&format!("{{ {}\n}} || exit $?;\n", hook));
+    }
+    if post_serve.is_empty() {
+        script.push_str(serve);
+        return script;
+    }
+
+    script.push_str(&format!(
+        r#"{{ {serve}
+}} &
+darp_serve=$!
+trap 'kill -TERM $darp_serve 2>/dev/null' INT TERM
+wait $darp_serve; darp_status=$?
+if kill -0 $darp_serve 2>/dev/null; then wait $darp_serve; darp_status=$?; fi
+trap - INT TERM
+"#,
+        serve = serve
+    ));
+    for hook in post_serve {
+        script.push_str(&format!("{};\n", hook));
+    }
+    script.push_str("exit $darp_status");
+    script
+}
+
+/// The name of a service replica's container: the service's `container_name`, with `_r2`,
+/// `_r3`, … appended for the replicas after the first (`replica` counts from 0).
+pub fn replica_container_name(container_name: &str, replica: usize) -> String {
+    match replica {
+        0 => container_name.to_string(),
+        i => format!("{}_r{}", container_name, i + 1),
+    }
+}
+
+/// The port a service listens on inside its container, by connection_type: 8000 for
+/// http (the default), 8001 for websocket and 8002 for tcp.
+pub(crate) fn container_port(connection_type: Option<&str>) -> u16 {
+    match connection_type {
+        Some("websocket") => 8001,
+        Some("tcp") => 8002,
+        _ => 8000,
+    }
+}
+
+/// The run plan that starts a service's container (or one of its replicas) with its
+/// serve command, in the background when `detach` is set.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn serve_run_plan(
+    ctx: &ServiceContext<'_>,
+    setup: &ServeSetup,
+    container_image: Option<&str>,
+    env_files: &[String],
+    network: Option<&str>,
+    detach: bool,
+    replica: usize,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<RunPlan> {
+    let ServeSetup {
+        resolved,
+        environment_name,
+        serve_command,
+        pre_serve,
+        post_serve,
+    } = setup;
+
+    let image_name = service_image(ctx, resolved, container_image, false).ok_or_else(|| {
+        DarpError::NoServeImage {
+            domain: ctx.domain_name.clone(),
+            service: ctx.current_directory_name.clone(),
+            environment: environment_name.clone(),
+        }
+    })?;
+
+    let mut plan = build_run_plan(
+        resolved,
+        ctx,
+        &image_name,
+        false,
+        detach,
+        env_files,
+        network,
+        replica,
+        paths,
+        config,
+        engine,
+    )?;
+
+    if direct_entrypoint(ctx).is_some() {
+        if !pre_serve.is_empty() || !post_serve.is_empty() {
+            warn!(
+                "{}'s entrypoint isn't a shell, so its pre_serve and post_serve commands are skipped",
+                ctx.current_directory_name
+            );
+        }
+        plan.command = serve_command
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        return Ok(plan);
+    }
+
+    let inner_cmd = format!(
+        r#"if command -v nginx >/dev/null 2>&1; then
+    echo "Starting nginx..."; nginx;
+else
+    echo "nginx not found, skipping";
+fi;
+{serve}"#,
+        serve = serve_script(serve_command, pre_serve, post_serve)
+    );
+
+    plan.command = bootstrap_command(ctx, &inner_cmd);
+
+    Ok(plan)
+}
+
+/// The run plan `darp serve --print-plan` shows for the service in `ctx` when no image,
+/// env file or network is given on the command line.
+pub fn service_run_plan(
+    ctx: &ServiceContext<'_>,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<RunPlan> {
+    let setup = resolve_serve(ctx, paths)?;
+    serve_run_plan(
+        ctx,
+        &setup,
+        None,
+        &[],
+        None,
+        false,
+        0,
+        paths,
+        config,
+        engine,
+    )
+}
+
+/// The run plan of each replica after the first, all detached; empty unless `darp deploy`
+/// gave the service replica ports.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn replica_plans(
+    ctx: &ServiceContext<'_>,
+    setup: &ServeSetup,
+    container_image: Option<&str>,
+    env_files: &[String],
+    network: Option<&str>,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<Vec<RunPlan>> {
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let replicas = config::portmap_replica_ports(
+        &portmap,
+        &ctx.domain_name,
+        &ctx.group_name,
+        &ctx.current_directory_name,
+    )
+    .len();
+    if replicas == 0 && ctx.service.and_then(|s| s.replicas).is_some_and(|n| n > 1) {
+        info!(
+            "starting one container of {}; run 'darp deploy' to give its replicas ports",
+            ctx.current_directory_name
+        );
+    }
+    (1..=replicas)
+        .map(|replica| {
+            serve_run_plan(
+                ctx,
+                setup,
+                container_image,
+                env_files,
+                network,
+                true,
+                replica,
+                paths,
+                config,
+                engine,
+            )
+        })
+        .collect()
+}
+
+/// The address a served service is reached at: `host:port` for TCP services, its
+/// `.test` URL otherwise.
+pub(crate) fn service_url(
+    ctx: &ServiceContext<'_>,
+    setup: &ServeSetup,
+    paths: &DarpPaths,
+) -> String {
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let entry = portmap
+        .get(&ctx.domain_name)
+        .and_then(|d| d.get(&ctx.group_name))
+        .and_then(|g| g.get(&ctx.current_directory_name));
+    let host = entry.map_or_else(
+        || config::service_host(&ctx.current_directory_name, &ctx.domain_name, None),
+        |e| config::portmap_host(e, &ctx.domain_name, &ctx.current_directory_name),
+    );
+    if setup.resolved.connection_type.as_deref() == Some("tcp") {
+        format!("{}:{}", host, entry.map_or(0, config::portmap_tcp_port))
+    } else {
+        format!("http://{}", host)
+    }
+}
+
+/// Start a `-d` service container and return once the engine has it running (and, with
+/// `wait`, once its healthcheck settles). It is listed as running in the services index;
+/// the next serve, deploy or `darp stop` brings the index up to date after it exits.
+pub(crate) fn start_detached(
+    engine: &Engine,
+    paths: &DarpPaths,
+    plan: &RunPlan,
+    url: &str,
+    wait: Option<u64>,
+) -> anyhow::Result<()> {
+    let container_name = plan.name.as_str();
+    let slot = engine.acquire_slot(
+        &paths.engine_slots_dir,
+        &format!("start {}", container_name),
+    )?;
+    let timing = timings::phase("container start");
+    let output = plan.command(engine).logged().output()?;
+    drop(timing);
+    drop(slot);
+    if !output.status.success() {
+        return Err(anyhow!(
+            "could not start {}: {}",
+            container_name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut running = engine.running_container_names();
+    running.insert(container_name.to_string());
+    if let Err(e) =
+        write_services_index(paths, engine, &running).and_then(|_| engine.sync_remote_files(paths))
+    {
+        warn!("could not update services index: {}", e);
+    }
+
+    println!("Serving {} in {}", url.green(), container_name.cyan());
+    if let Some(timeout) = wait {
+        watch_health(engine, container_name, url, timeout);
+    }
+    Ok(())
+}
+
+/// `darp serve` options that don't apply to `darp shell`.
+#[derive(Debug, Clone, Default)]
+pub struct ServeOptions {
+    /// Seconds to wait for the healthcheck to pass, when `--wait` was given.
+    pub wait: Option<u64>,
+    /// `--restart` policy; overrides the service/environment setting.
+    pub restart: Option<String>,
+    /// Start the container in the background and return once it's up.
+    pub detach: bool,
+    /// Put the time and the service name in front of each output line (`--prefix`).
+    pub prefix: bool,
+}
+
+pub fn cmd_serve(
+    args: RunArgs,
+    options: ServeOptions,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let RunArgs {
+        environment: environment_cli,
+        dry_run,
+        print_plan,
+        env_files,
+        network,
+        container_image,
+    } = args;
+
+    engine.require_ready()?;
+
+    let ctx = config.require_service_context(environment_cli)?;
+    if resolve_deploy_connection_type(ctx.domain, &ctx.group_name, &ctx.current_directory_name)
+        .as_deref()
+        == Some("static")
+    {
+        println!(
+            "{} is a static service; the reverse proxy serves its folder at {} (run 'darp deploy' if it isn't listed in 'darp urls').",
+            ctx.current_directory_name.cyan(),
+            format!(
+                "http://{}",
+                config.service_host(&ctx.current_directory_name, &ctx.domain_name)
+            )
+            .green()
+        );
+        return Ok(());
+    }
+    if let Some(host_port) = ctx.service.and_then(|s| s.host_port) {
+        println!(
+            "{} runs on the host; start it yourself on port {} and it's served at {}.",
+            ctx.current_directory_name.cyan(),
+            host_port,
+            format!(
+                "http://{}",
+                config.service_host(&ctx.current_directory_name, &ctx.domain_name)
+            )
+            .green()
+        );
+        return Ok(());
+    }
+
+    let ServeOptions {
+        wait,
+        restart,
+        detach,
+        prefix,
+    } = options;
+    if let Some(policy) = &restart {
+        config::validate_restart_policy(policy)?;
+    }
+
+    if !dry_run && !print_plan {
+        offer_registration(&ctx, paths, config, engine)?;
+    }
+    let mut setup = resolve_serve(&ctx, paths)?;
+    if restart.is_some() {
+        setup.resolved.restart = restart;
+    }
+    let serve_command = setup.serve_command.as_str();
+
+    if wait.is_some() && ctx.service.and_then(|s| s.healthcheck.as_ref()).is_none() {
+        return Err(anyhow!(
+            "Service '{}.{}' has no healthcheck to wait for.\n\
+Set one with 'darp config set svc healthcheck {} {} {} <cmd|url>'.",
+            ctx.domain_name,
+            ctx.current_directory_name,
+            ctx.domain_name,
+            ctx.group_name,
+            ctx.current_directory_name,
+        ));
+    }
+
+    let container_name =
+        engine.service_container_name(&ctx.domain_name, &ctx.current_directory_name);
+
+    if let Some(container_name) = engine
+        .running_service_container(&ctx.domain_name, &ctx.current_directory_name)
+        .filter(|_| !print_plan)
+    {
         let serve_binary = serve_command
             .split_whitespace()
             .next()
@@ -374,12 +1305,15 @@ Use 'darp config set svc serve-command {} {} <cmd>' or \
             return Ok(());
         }
 
+        let exec_inner = serve_script(serve_command, &setup.pre_serve, &setup.post_serve);
         if dry_run {
             println!(
-                "{} exec {} sh -c 'cd /app; {}'",
+                "{} exec {}{} {} -c '{}'",
                 engine.bin.unwrap_or("docker"),
+                if detach { "-d " } else { "" },
                 container_name,
-                serve_command
+                bootstrap_shell(&ctx),
+                exec_inner
             );
             return Ok(());
         }
@@ -388,14 +1322,17 @@ Use 'darp config set svc serve-command {} {} <cmd>' or \
             "Starting serve in running container {}...",
             ctx.current_directory_name.cyan()
         );
-        let bin = engine.bin.expect("engine bin not set");
-        let exec_inner = format!("cd /app; {}", serve_command);
-        let status = std::process::Command::new(bin)
-            .arg("exec")
+        let mut exec = engine.command();
+        exec.arg("exec");
+        if detach {
+            exec.arg("-d");
+        }
+        let status = exec
             .arg(&container_name)
-            .arg("sh")
+            .arg(bootstrap_shell(&ctx))
             .arg("-c")
             .arg(&exec_inner)
+            .logged()
             .status()?;
 
         if let Some(code) = status.code() {
@@ -406,45 +1343,157 @@ Use 'darp config set svc serve-command {} {} <cmd>' or \
         return Ok(());
     }
 
-    let image_name = resolved
-        .resolve_full_image_name(container_image.as_deref())
-        .unwrap_or_else(|| {
-            eprintln!(
-                "No container image provided for '{}.{}' in environment '{}'.\n\
-                 Either pass an explicit image to 'darp serve' or configure a default_container_image:\n\
-                   darp config set svc default-container-image {} {} <image>\n\
-                 or\n\
-                   darp config set env default-container-image {} <image>",
-                ctx.domain_name,
-                ctx.current_directory_name,
-                environment_name,
-                ctx.domain_name,
-                ctx.current_directory_name,
-                environment_name,
-            );
-            std::process::exit(1);
-        });
+    let plan = serve_run_plan(
+        &ctx,
+        &setup,
+        container_image.as_deref(),
+        &env_files,
+        network.as_deref(),
+        detach,
+        0,
+        paths,
+        config,
+        engine,
+    )?;
+    let replicas = replica_plans(
+        &ctx,
+        &setup,
+        container_image.as_deref(),
+        &env_files,
+        network.as_deref(),
+        paths,
+        config,
+        engine,
+    )?;
 
-    let mut cmd =
-        build_container_command(&resolved, &ctx, &image_name, false, paths, config, engine)?;
+    if print_plan {
+        // One entry per container, the service's own first; replicas follow.
+        let plans: Vec<&RunPlan> = std::iter::once(&plan).chain(&replicas).collect();
+        println!("{}", serde_json::to_string_pretty(&plans)?);
+        return Ok(());
+    }
+    if dry_run {
+        for replica in &replicas {
+            println!("{}", engine.command_to_string(&replica.command(engine)));
+        }
+        println!("{}", engine.command_to_string(&plan.command(engine)));
+        return Ok(());
+    }
 
-    let inner_cmd = format!(
-        r#"if command -v nginx >/dev/null 2>&1; then
-    echo "Starting nginx..."; nginx;
-else
-    echo "nginx not found, skipping";
-fi;
-cd /app; {serve}"#,
-        serve = serve_command
-    );
+    build_service_image(
+        &ctx,
+        &setup.resolved,
+        container_image.as_deref(),
+        paths,
+        engine,
+    )?;
+    prepare_pod(&ctx, paths, engine)?;
+    prepare_volumes(&plan, engine)?;
+    // Without --rm an exited container keeps its name; clear it so `run --name` succeeds.
+    let keeps_name = setup.resolved.restart.as_deref().is_some_and(|p| p != "no");
+    if keeps_name {
+        engine.remove_named_container(&container_name)?;
+    }
+    let url = service_url(&ctx, &setup, paths);
 
-    cmd.arg("sh").arg("-c").arg(inner_cmd);
+    // The other replicas run in the background; the first is served like any service.
+    for replica in &replicas {
+        if keeps_name {
+            engine.remove_named_container(&replica.name)?;
+        }
+        start_detached(engine, paths, replica, &url, None)?;
+    }
 
-    if dry_run {
-        println!("{}", engine.command_to_string(&cmd));
+    if detach {
+        start_detached(engine, paths, &plan, &url, wait)?;
+        println!("Use 'darp logs -f' to follow its output and 'darp stop' to stop it.");
         return Ok(());
     }
 
-    engine.run_container_interactive(cmd, &container_name, &[])?;
+    let prefix = prefix.then_some(ctx.current_directory_name.as_str());
+    let Some(timeout) = wait else {
+        return run_and_track(engine, paths, &plan, prefix);
+    };
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| watch_health(engine, &container_name, &url, timeout));
+        run_and_track(engine, paths, &plan, prefix)
+    })
+}
+
+/// The running containers of the current directory's service (or of one the user picks
+/// outside a service folder), the first replica first; an error when it isn't running.
+/// A picked service on another engine than `engine` comes with that engine.
+fn current_service_containers(
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<(Option<Engine>, Vec<String>)> {
+    let ctx = service_context_or_pick(paths, config, None)?;
+    let picked_engine = picked_service_engine(config, &ctx, engine)?;
+    let engine = picked_engine.as_ref().unwrap_or(engine);
+    engine.require_ready()?;
+
+    let containers =
+        engine.running_service_containers(&ctx.domain_name, &ctx.current_directory_name);
+    if containers.is_empty() {
+        return Err(anyhow!(
+            "{}.{} is not running.",
+            ctx.current_directory_name,
+            ctx.domain_name
+        ));
+    }
+    Ok((picked_engine, containers))
+}
+
+pub fn cmd_logs(
+    follow: bool,
+    tail: Option<&str>,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let (picked_engine, mut containers) = current_service_containers(paths, config, engine)?;
+    let engine = picked_engine.as_ref().unwrap_or(engine);
+    let container_name = containers.remove(0);
+
+    let mut cmd = engine.command();
+    cmd.arg("logs");
+    if follow {
+        cmd.arg("-f");
+    }
+    if let Some(tail) = tail {
+        cmd.arg("--tail").arg(tail);
+    }
+    cmd.arg(&container_name).logged().status()?;
+    Ok(())
+}
+
+pub fn cmd_stop(paths: &DarpPaths, config: &Config, engine: &Engine) -> anyhow::Result<()> {
+    // A replicated service has a container per replica to stop.
+    let (picked_engine, containers) = current_service_containers(paths, config, engine)?;
+    let engine = picked_engine.as_ref().unwrap_or(engine);
+    for container_name in containers {
+        let _slot =
+            engine.acquire_slot(&paths.engine_slots_dir, &format!("stop {}", container_name))?;
+        let _timing = timings::phase("container stop");
+        println!("stopping {}", container_name.cyan());
+        let status = engine
+            .command()
+            .arg("stop")
+            .arg(&container_name)
+            .stdout(std::process::Stdio::null())
+            .logged()
+            .status()?;
+        if !status.success() {
+            return Err(anyhow!("could not stop {}", container_name));
+        }
+    }
+
+    if let Err(e) = write_services_index(paths, engine, &engine.running_container_names())
+        .and_then(|_| engine.sync_remote_files(paths))
+    {
+        warn!("could not update services index: {}", e);
+    }
     Ok(())
 }
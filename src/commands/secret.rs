@@ -0,0 +1,74 @@
+// commands/secret.rs
+
+use std::io::{BufRead, Write};
+
+use colored::*;
+
+use crate::cli::SecretCommand;
+use crate::config::DarpPaths;
+use crate::secrets::{GLOBAL_SCOPE, SecretStore};
+
+pub fn cmd_secret(cmd: SecretCommand, paths: &DarpPaths, json: bool) -> anyhow::Result<()> {
+    let mut store = SecretStore::load(&paths.secrets_path)?;
+
+    match cmd {
+        SecretCommand::Set {
+            name,
+            value,
+            domain,
+        } => {
+            let value = match value {
+                Some(v) => v,
+                None => {
+                    // Reading from stdin keeps the value out of shell history.
+                    eprint!("Value for {}: ", name);
+                    std::io::stderr().flush()?;
+                    let mut line = String::new();
+                    std::io::stdin().lock().read_line(&mut line)?;
+                    line.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            let scope = domain.as_deref().unwrap_or(GLOBAL_SCOPE);
+            store.set(scope, &name, &value);
+            store.save(&paths.secrets_path)?;
+            println!("Stored secret {} ({})", name.green(), scope_label(scope));
+        }
+        SecretCommand::Rm { name, domain } => {
+            let scope = domain.as_deref().unwrap_or(GLOBAL_SCOPE);
+            store.rm(scope, &name)?;
+            store.save(&paths.secrets_path)?;
+            println!("Removed secret {} ({})", name.green(), scope_label(scope));
+        }
+        SecretCommand::List if json => {
+            // `domain` is null for secrets given to every domain.
+            let entries: Vec<serde_json::Value> = store
+                .list()
+                .into_iter()
+                .map(|(scope, name)| {
+                    let domain = (scope != GLOBAL_SCOPE).then_some(scope);
+                    serde_json::json!({ "name": name, "domain": domain })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        }
+        SecretCommand::List => {
+            let entries = store.list();
+            if entries.is_empty() {
+                println!("No secrets stored.");
+            }
+            for (scope, name) in entries {
+                println!("{}  {}", name.green(), scope_label(scope).dimmed());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn scope_label(scope: &str) -> String {
+    if scope == GLOBAL_SCOPE {
+        "all domains".to_string()
+    } else {
+        format!("domain {}", scope)
+    }
+}
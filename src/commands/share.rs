@@ -0,0 +1,228 @@
+// commands/share.rs
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+
+use anyhow::anyhow;
+use colored::*;
+use tracing::debug;
+
+use super::open::find_portmap_service;
+use crate::cli::TunnelKind;
+use crate::config::{self, Config, DarpPaths};
+use crate::engine;
+use crate::logging::Logged;
+use crate::urls::entry_url;
+
+/// Where a tunnel forwards to: a local origin URL, and the `Host` header the reverse
+/// proxy needs when that origin is its shared port 80.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelTarget {
+    pub origin: String,
+    pub host_header: Option<String>,
+}
+
+/// The tunnel target of a portmap entry: its direct port when it has one, which needs
+/// no `Host` header, else port 80 with the service's hostname. tcp services are refused.
+pub fn tunnel_target(
+    domain: &str,
+    service: &str,
+    entry: &serde_json::Value,
+) -> anyhow::Result<TunnelTarget> {
+    let (_, url) = entry_url(domain, service, entry);
+    if url.starts_with("tcp://") {
+        return Err(anyhow!(
+            "{} is a tcp service; tunnels only carry http",
+            service
+        ));
+    }
+    if let Some(direct) = entry.get("direct_url").and_then(|u| u.as_str()) {
+        return Ok(TunnelTarget {
+            origin: direct.replacen("ws://", "http://", 1),
+            host_header: None,
+        });
+    }
+    Ok(TunnelTarget {
+        origin: "http://localhost:80".to_string(),
+        host_header: Some(config::portmap_host(entry, domain, service)),
+    })
+}
+
+/// The program a tunnel kind runs.
+pub fn tunnel_bin(kind: TunnelKind) -> &'static str {
+    match kind {
+        TunnelKind::Cloudflared => "cloudflared",
+        TunnelKind::Ngrok => "ngrok",
+        TunnelKind::Localtunnel => "lt",
+    }
+}
+
+/// The arguments that open a tunnel of `kind` to `target`, with logs on stdout or stderr
+/// for `public_url` to read. localtunnel can't rewrite the `Host` header, so it needs a
+/// target with a direct port.
+pub fn tunnel_args(kind: TunnelKind, target: &TunnelTarget) -> anyhow::Result<Vec<String>> {
+    let mut args: Vec<String> = match kind {
+        TunnelKind::Cloudflared => vec!["tunnel".into(), "--no-autoupdate".into()],
+        TunnelKind::Ngrok => vec!["http".into()],
+        TunnelKind::Localtunnel => vec![],
+    };
+    match kind {
+        TunnelKind::Cloudflared => {
+            args.extend(["--url".into(), target.origin.clone()]);
+            if let Some(host) = &target.host_header {
+                args.extend(["--http-host-header".into(), host.clone()]);
+            }
+        }
+        TunnelKind::Ngrok => {
+            args.push(target.origin.clone());
+            if let Some(host) = &target.host_header {
+                args.push(format!("--host-header={}", host));
+            }
+            args.extend([
+                "--log".into(),
+                "stdout".into(),
+                "--log-format".into(),
+                "logfmt".into(),
+            ]);
+        }
+        TunnelKind::Localtunnel => {
+            if target.host_header.is_some() {
+                return Err(anyhow!(
+                    "localtunnel can't set the Host header the reverse proxy needs; \
+run 'darp deploy' to give the service a direct port, or use --via cloudflared or ngrok"
+                ));
+            }
+            let port = target
+                .origin
+                .rsplit(':')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            args.extend(["--port".into(), port]);
+        }
+    }
+    Ok(args)
+}
+
+/// The public URL in a line of a tunnel's output, once it prints one.
+pub fn public_url(kind: TunnelKind, line: &str) -> Option<String> {
+    let url = match kind {
+        // A banner line: "|  https://<words>.trycloudflare.com  |".
+        TunnelKind::Cloudflared => line
+            .split_whitespace()
+            .find(|w| w.starts_with("https://") && w.ends_with(".trycloudflare.com"))?,
+        // logfmt: `... msg="started tunnel" ... url=https://<id>.ngrok-free.app`.
+        TunnelKind::Ngrok => line
+            .split_whitespace()
+            .find_map(|w| w.strip_prefix("url="))
+            .filter(|u| u.starts_with("https://"))?,
+        // "your url is: https://<words>.loca.lt".
+        TunnelKind::Localtunnel => line.strip_prefix("your url is: ")?.trim(),
+    };
+    Some(url.to_string())
+}
+
+/// `--via`, or the first tunnel program on PATH.
+fn pick_tunnel(via: Option<TunnelKind>) -> anyhow::Result<TunnelKind> {
+    if let Some(kind) = via {
+        return Ok(kind);
+    }
+    [
+        TunnelKind::Cloudflared,
+        TunnelKind::Ngrok,
+        TunnelKind::Localtunnel,
+    ]
+    .into_iter()
+    .find(|kind| engine::is_on_path(tunnel_bin(*kind)))
+    .ok_or_else(|| {
+        anyhow!("No tunnel program found; install cloudflared, ngrok or localtunnel ('lt')")
+    })
+}
+
+/// `darp share`: open a public tunnel to a service (the current directory's by default)
+/// and print its URL. The tunnel stays up until Ctrl-C.
+pub fn cmd_share(
+    target: Option<String>,
+    via: Option<TunnelKind>,
+    paths: &DarpPaths,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)
+        .map_err(|_| anyhow!("No portmap found. Run 'darp deploy' first."))?;
+    let ctx = match target {
+        Some(_) => None,
+        None => config.service_context_from_cwd(None),
+    };
+    let cwd = ctx
+        .as_ref()
+        .map(|c| (c.domain_name.as_str(), c.current_directory_name.as_str()));
+    let (domain_name, service_name, entry) =
+        find_portmap_service(&portmap, target.as_deref(), cwd)?;
+    let (_, url) = entry_url(domain_name, service_name, entry);
+    let tunnel = tunnel_target(domain_name, service_name, entry)?;
+
+    let kind = pick_tunnel(via)?;
+    let bin = tunnel_bin(kind);
+    let mut child = Command::new(bin)
+        .args(tunnel_args(kind, &tunnel)?)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .logged()
+        .spawn()
+        .map_err(|e| anyhow!("could not run {}: {}", bin, e))?;
+
+    // The URL may come on either stream, so both feed one channel.
+    let (tx, rx) = mpsc::channel::<String>();
+    let stdout = child
+        .stdout
+        .take()
+        .map(|s| Box::new(s) as Box<dyn std::io::Read + Send>);
+    let stderr = child
+        .stderr
+        .take()
+        .map(|s| Box::new(s) as Box<dyn std::io::Read + Send>);
+    for stream in [stdout, stderr].into_iter().flatten() {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    println!("Opening a {} tunnel to {}…", bin, url.green());
+    let mut shared = false;
+    let mut recent: Vec<String> = Vec::new();
+    for line in rx {
+        debug!("{}: {}", bin, line);
+        if shared {
+            continue;
+        }
+        if let Some(public) = public_url(kind, &line) {
+            println!("Sharing {} at {}", url, public.green().bold());
+            println!("Anyone with the link can reach it; press Ctrl-C to stop.");
+            shared = true;
+        } else {
+            recent.push(line);
+            if recent.len() > 5 {
+                recent.remove(0);
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    if !shared {
+        return Err(anyhow!(
+            "{} exited ({}) before printing a public URL:\n{}",
+            bin,
+            status,
+            recent.join("\n")
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,274 @@
+// commands/up.rs
+
+use anyhow::anyhow;
+use colored::*;
+
+use super::run::{
+    build_service_image, prepare_pod, prepare_volumes, replica_plans, resolve_serve,
+    serve_run_plan, service_image, service_url, start_detached,
+};
+use crate::config::{self, Config, DarpPaths, ResolvedSettings, ServiceContext};
+use crate::deploy::resolve_deploy_connection_type;
+use crate::engine::Engine;
+use crate::resolver::{self, Placement};
+
+/// Why a service can't be started unattended, or None when `resolve_serve` and
+/// `serve_run_plan` will succeed for it (they exit the process otherwise).
+fn serve_blocker(ctx: &ServiceContext<'_>) -> Option<String> {
+    let Some(environment_name) = &ctx.environment_name else {
+        return Some("no environment".to_string());
+    };
+    if ctx.environment.is_none() {
+        return Some(format!("environment '{}' does not exist", environment_name));
+    }
+    let resolved = ResolvedSettings::resolve(
+        ctx.domain_name.clone(),
+        ctx.group_name.clone(),
+        ctx.current_directory_name.clone(),
+        ctx.environment_name.clone(),
+        ctx.service,
+        ctx.group,
+        ctx.domain,
+        ctx.environment,
+    );
+    if resolved.serve_command.is_none() {
+        return Some("no serve_command".to_string());
+    }
+    if service_image(ctx, &resolved, None, false).is_none() {
+        return Some("no default_container_image".to_string());
+    }
+    None
+}
+
+/// The domain `darp up` works on: `-d`, else the one the current directory belongs to
+/// (as a service folder or the domain folder itself).
+fn up_domain(config: &Config, domain: Option<String>) -> anyhow::Result<String> {
+    if let Some(domain) = domain {
+        if config
+            .domains
+            .as_ref()
+            .is_none_or(|d| !d.contains_key(&domain))
+        {
+            return Err(anyhow!("domain, {}, does not exist", domain));
+        }
+        return Ok(domain);
+    }
+
+    match resolver::locate(&resolver::current_dir()?, &config.domain_dirs()) {
+        Placement::Service { domain, .. }
+        | Placement::DomainRoot { domain }
+        | Placement::GroupRoot { domain, .. } => Ok(domain.to_string()),
+        _ => Err(anyhow!(
+            "Run 'darp up' inside a domain folder, or pass -d <domain>."
+        )),
+    }
+}
+
+/// `darp up` options besides the domain.
+#[derive(Debug, Clone, Default)]
+pub struct UpOptions {
+    /// Only services carrying one of these tags (`--tag`).
+    pub tags: Vec<String>,
+    /// Only these services (`--only`).
+    pub only: Vec<String>,
+    /// Services to leave out (`--except`).
+    pub except: Vec<String>,
+    /// Environment for every service, instead of each one's default.
+    pub environment: Option<String>,
+}
+
+/// The `(group, service)` pairs of a domain's portmap that `darp up` starts, split into
+/// waves by `start_order` (lowest first; unset counts as 0) and sorted within each wave.
+/// Names in `only` / `except` that aren't in the portmap are an error.
+pub fn up_waves(
+    config: &Config,
+    portmap: &serde_json::Value,
+    domain_name: &str,
+    only: &[String],
+    except: &[String],
+) -> anyhow::Result<Vec<Vec<(String, String)>>> {
+    let services: Vec<(String, String)> = portmap
+        .get(domain_name)
+        .and_then(|d| d.as_object())
+        .into_iter()
+        .flatten()
+        .flat_map(|(group_name, services)| {
+            services
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(move |(service_name, _)| (group_name.clone(), service_name.clone()))
+        })
+        .collect();
+
+    for name in only.iter().chain(except) {
+        if !services.iter().any(|(_, s)| s == name) {
+            return Err(anyhow!(
+                "Service '{}' is not registered in domain '{}'. Run 'darp urls' to list services.",
+                name,
+                domain_name
+            ));
+        }
+    }
+
+    let start_order = |group_name: &str, service_name: &str| {
+        config
+            .domains
+            .as_ref()
+            .and_then(|d| d.get(domain_name))
+            .and_then(|d| d.groups.as_ref())
+            .and_then(|g| g.get(group_name))
+            .and_then(|g| g.services.as_ref())
+            .and_then(|s| s.get(service_name))
+            .and_then(|s| s.start_order)
+            .unwrap_or(0)
+    };
+
+    let mut waves: std::collections::BTreeMap<i32, Vec<(String, String)>> = Default::default();
+    for (group_name, service_name) in services {
+        if (!only.is_empty() && !only.contains(&service_name)) || except.contains(&service_name) {
+            continue;
+        }
+        waves
+            .entry(start_order(&group_name, &service_name))
+            .or_default()
+            .push((group_name, service_name));
+    }
+    Ok(waves
+        .into_values()
+        .map(|mut wave| {
+            wave.sort();
+            wave
+        })
+        .collect())
+}
+
+/// How long `darp up` waits for a wave's healthchecks before starting the next one.
+const WAVE_HEALTH_TIMEOUT: u64 = 60;
+
+/// Start every registered service of a domain in the background, narrowed by `options`.
+/// Static and host-run services (which darp doesn't start), services already running, and
+/// services missing what `darp serve` needs are skipped. Each `start_order` wave waits for
+/// the previous wave's healthchecks.
+pub fn cmd_up(
+    domain: Option<String>,
+    options: UpOptions,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    engine.require_ready()?;
+
+    let domain_name = up_domain(config, domain)?;
+    let domain = config
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(&domain_name))
+        .ok_or_else(|| anyhow!("domain, {}, does not exist", domain_name))?;
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)
+        .map_err(|_| anyhow!("No portmap found. Run 'darp deploy' first."))?;
+    let portmap = config.filter_portmap_by_tags(&portmap, &options.tags);
+    let location = config::resolve_location(&domain.location)?;
+
+    let waves = up_waves(
+        config,
+        &portmap,
+        &domain_name,
+        &options.only,
+        &options.except,
+    )?;
+    if waves.is_empty() {
+        println!("No services to start in {}.", domain_name.green());
+        return Ok(());
+    }
+
+    let mut started = 0;
+    let wave_count = waves.len();
+    for (i, wave) in waves.into_iter().enumerate() {
+        let last_wave = i + 1 == wave_count;
+        for (group_name, service_name) in wave {
+            let mut dir = location.clone();
+            if group_name != "." {
+                dir.push(&group_name);
+            }
+            dir.push(&service_name);
+            let Some(ctx) = config.service_context_for_dir(dir, options.environment.clone()) else {
+                continue;
+            };
+
+            if resolve_deploy_connection_type(ctx.domain, &group_name, &service_name).as_deref()
+                == Some("static")
+                || ctx.service.is_some_and(|s| s.host_port.is_some())
+            {
+                continue;
+            }
+            let service_engine = Engine::for_service(config, ctx.domain, ctx.environment)?;
+            let engine = if service_engine.kind == engine.kind {
+                engine
+            } else {
+                service_engine.require_ready()?;
+                &service_engine
+            };
+            if engine
+                .running_service_container(&domain_name, &service_name)
+                .is_some()
+            {
+                println!("{} is already running", service_name.cyan());
+                continue;
+            }
+            if let Some(reason) = serve_blocker(&ctx) {
+                eprintln!("skipping {}: {}", service_name.yellow(), reason);
+                continue;
+            }
+
+            let setup = resolve_serve(&ctx, paths)?;
+            let container_name = engine.service_container_name(&domain_name, &service_name);
+            let plan = serve_run_plan(
+                &ctx,
+                &setup,
+                None,
+                &[],
+                None,
+                true,
+                0,
+                paths,
+                config,
+                engine,
+            )?;
+            let replicas = replica_plans(&ctx, &setup, None, &[], None, paths, config, engine)?;
+            build_service_image(&ctx, &setup.resolved, None, paths, engine)?;
+            prepare_pod(&ctx, paths, engine)?;
+            prepare_volumes(&plan, engine)?;
+            let keeps_name = setup.resolved.restart.as_deref().is_some_and(|p| p != "no");
+            if keeps_name {
+                engine.remove_named_container(&container_name)?;
+            }
+            let url = service_url(&ctx, &setup, paths);
+            for replica in &replicas {
+                if keeps_name {
+                    engine.remove_named_container(&replica.name)?;
+                }
+                if let Err(e) = start_detached(engine, paths, replica, &url, None) {
+                    eprintln!("{}: {}", replica.name.red(), e);
+                }
+            }
+            // Later waves depend on this one, so let its healthchecks settle first.
+            let wait = ctx
+                .service
+                .and_then(|s| s.healthcheck.as_ref())
+                .filter(|_| !last_wave)
+                .map(|_| WAVE_HEALTH_TIMEOUT);
+            match start_detached(engine, paths, &plan, &url, wait) {
+                Ok(()) => started += 1,
+                Err(e) => eprintln!("{}: {}", service_name.red(), e),
+            }
+        }
+    }
+
+    println!(
+        "\nStarted {} service(s) in {}.",
+        started,
+        domain_name.green()
+    );
+    Ok(())
+}
@@ -0,0 +1,179 @@
+// commands/verify.rs
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::config::{self, DarpPaths};
+use crate::engine;
+
+/// How long a connection or a response may take before the URL counts as failed.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Connection attempts per URL; the proxy may still be starting on the first.
+const PROBE_ATTEMPTS: u32 = 3;
+
+/// One URL `darp deploy --verify` checks: the host and port the proxy answers it on, and
+/// whether an HTTP request is sent or a TCP connection is enough (TLS, stream ports).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VerifyTarget {
+    pub url: String,
+    pub host: String,
+    pub port: u16,
+    pub http: bool,
+}
+
+impl VerifyTarget {
+    fn http(url: String, host: &str) -> Self {
+        Self {
+            url,
+            host: host.to_string(),
+            port: 80,
+            http: true,
+        }
+    }
+
+    fn tcp(url: String, host: &str, port: u16) -> Self {
+        Self {
+            url,
+            host: host.to_string(),
+            port,
+            http: false,
+        }
+    }
+}
+
+/// Every URL in the portmap the reverse proxy answers: each service's `.test` name and
+/// aliases on port 80, custom domains on 80 or `TLS_PROXY_PORT`, and tcp services' stream
+/// ports. Plain tcp services go straight to their container, so they aren't listed.
+pub fn verify_targets(portmap: &serde_json::Value) -> Vec<VerifyTarget> {
+    let mut targets = Vec::new();
+    for (domain_name, groups) in portmap.as_object().into_iter().flatten() {
+        for (_, services) in groups.as_object().into_iter().flatten() {
+            for (service_name, entry) in services.as_object().into_iter().flatten() {
+                let host = config::portmap_host(entry, domain_name, service_name);
+                match entry.get("type").and_then(|t| t.as_str()) {
+                    Some("tcp") => {
+                        if let Some(port) = entry.get("stream_port").and_then(|p| p.as_u64()) {
+                            targets.push(VerifyTarget::tcp(
+                                format!("tcp://{}:{}", host, port),
+                                &host,
+                                port as u16,
+                            ));
+                        }
+                        continue;
+                    }
+                    Some("websocket") => {
+                        targets.push(VerifyTarget::http(format!("ws://{}", host), &host))
+                    }
+                    _ => targets.push(VerifyTarget::http(format!("http://{}", host), &host)),
+                }
+                for alias in config::portmap_aliases(entry) {
+                    targets.push(VerifyTarget::http(format!("http://{}", alias), &alias));
+                }
+                for url in config::portmap_custom_urls(entry) {
+                    if let Some(hostname) = url.strip_prefix("https://") {
+                        targets.push(VerifyTarget::tcp(
+                            url.clone(),
+                            hostname,
+                            engine::TLS_PROXY_PORT,
+                        ));
+                    } else if let Some(hostname) = url.strip_prefix("http://") {
+                        targets.push(VerifyTarget::http(url.clone(), hostname));
+                    }
+                }
+            }
+        }
+    }
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// The status line of the response to a `GET /` for `host`, or why there was none.
+fn http_status(mut stream: TcpStream, host: &str) -> Result<String, String> {
+    stream
+        .set_read_timeout(Some(PROBE_TIMEOUT))
+        .and_then(|_| {
+            write!(
+                stream,
+                "GET / HTTP/1.0\r\nHost: {}\r\nUser-Agent: darp-verify\r\n\r\n",
+                host
+            )
+        })
+        .map_err(|e| format!("request failed: {}", e))?;
+    let mut head = [0u8; 64];
+    let n = stream
+        .read(&mut head)
+        .map_err(|e| format!("no response: {}", e))?;
+    let head = String::from_utf8_lossy(&head[..n]);
+    let status = head.lines().next().unwrap_or_default();
+    match status.strip_prefix("HTTP/") {
+        Some(rest) => Ok(rest
+            .split_once(' ')
+            .map_or(rest, |(_, status)| status)
+            .to_string()),
+        None => Err("the answer isn't HTTP".to_string()),
+    }
+}
+
+/// Resolve and reach `target`: Ok with what the proxy answered, or Err with the step that
+/// failed (DNS, TCP or HTTP).
+fn probe(target: &VerifyTarget) -> Result<String, String> {
+    let addrs: Vec<_> = (target.host.as_str(), target.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("DNS: {}", e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err("DNS: no addresses".to_string());
+    }
+    let mut last_err = String::new();
+    for attempt in 0..PROBE_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        for addr in &addrs {
+            match TcpStream::connect_timeout(addr, PROBE_TIMEOUT) {
+                Ok(stream) if target.http => return http_status(stream, &target.host),
+                Ok(_) => return Ok("connected".to_string()),
+                Err(e) => last_err = format!("TCP {}: {}", addr, e),
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// `darp deploy --verify`: request every URL the proxy serves and report those that
+/// don't resolve or connect. Any HTTP answer passes, a 502 from a stopped service too;
+/// what's checked is that DNS and the proxy are wired up.
+pub fn verify_deploy(paths: &DarpPaths) -> anyhow::Result<()> {
+    let portmap: serde_json::Value = config::read_json(&paths.portmap_path)?;
+    let targets = verify_targets(&portmap);
+    if targets.is_empty() {
+        println!("\nNo URLs to verify.");
+        return Ok(());
+    }
+
+    println!("\nVerifying {} URL(s)", targets.len());
+    let mut failed = 0;
+    for target in &targets {
+        match probe(target) {
+            Ok(answer) => println!("  {} {}  {}", "ok".green(), target.url, answer.dimmed()),
+            Err(reason) => {
+                failed += 1;
+                println!("  {} {}  {}", "FAIL".red(), target.url, reason);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} URL(s) didn't reach the proxy; 'darp doctor' checks DNS and the proxy.",
+            failed,
+            targets.len()
+        ));
+    }
+    println!("All URLs reached the proxy.");
+    Ok(())
+}
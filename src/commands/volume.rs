@@ -0,0 +1,118 @@
+// commands/volume.rs
+
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use colored::*;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::cli::VolumeCommand;
+use crate::config::Config;
+use crate::engine::Engine;
+
+/// One `darp volume ls` row: a named volume, whether the engine has it yet, and where
+/// the config mounts it (see `Config::named_volumes`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VolumeEntry {
+    pub name: String,
+    pub created: bool,
+    pub configured_in: Vec<String>,
+}
+
+/// The volumes darp created (`existing`) and the ones configured but not created yet,
+/// sorted by name.
+pub fn volume_entries(
+    existing: &[String],
+    configured: &BTreeMap<String, Vec<String>>,
+) -> Vec<VolumeEntry> {
+    let mut entries: Vec<VolumeEntry> = existing
+        .iter()
+        .map(|name| VolumeEntry {
+            name: name.clone(),
+            created: true,
+            configured_in: configured.get(name).cloned().unwrap_or_default(),
+        })
+        .collect();
+    entries.extend(
+        configured
+            .iter()
+            .filter(|(name, _)| !existing.contains(name))
+            .map(|(name, owners)| VolumeEntry {
+                name: name.clone(),
+                created: false,
+                configured_in: owners.clone(),
+            }),
+    );
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    entries
+}
+
+/// `darp volume`: list or remove the named volumes services mount.
+pub fn cmd_volume(
+    cmd: VolumeCommand,
+    output_json: bool,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    engine.require_ready()?;
+    let configured = config.named_volumes();
+    match cmd {
+        VolumeCommand::Ls { json } => {
+            let entries = volume_entries(&engine.darp_volumes(), &configured);
+            if json || output_json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+            if entries.is_empty() {
+                println!(
+                    "No named volumes; a volume whose host is a bare name (e.g. 'node-modules') is one."
+                );
+                return Ok(());
+            }
+            let width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+            for entry in entries {
+                let status = if entry.created {
+                    ""
+                } else {
+                    " (not created yet)"
+                };
+                let owners = if entry.configured_in.is_empty() {
+                    "not configured".to_string()
+                } else {
+                    entry.configured_in.join(", ")
+                };
+                println!(
+                    "{:<width$}  {}{}",
+                    entry.name.cyan(),
+                    owners,
+                    status,
+                    width = width
+                );
+            }
+        }
+        VolumeCommand::Rm { names } => {
+            let existing = engine.darp_volumes();
+            for name in &names {
+                if !existing.contains(name) {
+                    return Err(anyhow!(
+                        "{} isn't a volume darp created; 'darp volume ls' lists them",
+                        name
+                    ));
+                }
+            }
+            for name in &names {
+                engine.remove_volume(name)?;
+                println!("Removed volume {}", name.green());
+                if let Some(owners) = configured.get(name) {
+                    warn!(
+                        "{} is still configured in {}; the next serve creates it again, empty",
+                        name,
+                        owners.join(", ")
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// commands/which.rs
+
+use colored::*;
+
+use crate::config::{self, Config, DarpPaths, ServiceContext};
+use crate::engine::{self, Engine};
+use crate::urls::entry_url;
+
+/// What the current directory maps to: domain, group, service, its portmap port and
+/// URL (null until `darp deploy` assigns one), and the serve container's name, built from
+/// the `container_name` template.
+pub fn which_entry(
+    ctx: &ServiceContext<'_>,
+    portmap: &serde_json::Value,
+    container_name: &str,
+) -> serde_json::Value {
+    let service = ctx.current_directory_name.as_str();
+    let entry = portmap
+        .get(&ctx.domain_name)
+        .and_then(|d| d.get(&ctx.group_name))
+        .and_then(|g| g.get(service));
+    let (port, url) = match entry.map(|e| entry_url(&ctx.domain_name, service, e)) {
+        Some((port, url)) => (Some(port), Some(url)),
+        None => (None, None),
+    };
+    serde_json::json!({
+        "domain": ctx.domain_name,
+        "group": ctx.group_name,
+        "service": service,
+        "port": port,
+        "url": url,
+        "container": engine::container_name(container_name, &ctx.domain_name, service),
+    })
+}
+
+pub fn cmd_which(
+    json: bool,
+    paths: &DarpPaths,
+    config: &Config,
+    engine: &Engine,
+) -> anyhow::Result<()> {
+    let ctx = config.require_service_context(None)?;
+    let portmap: serde_json::Value =
+        config::read_json(&paths.portmap_path).unwrap_or_else(|_| serde_json::json!({}));
+    let mut entry = which_entry(&ctx, &portmap, &engine.container_name);
+
+    // Best effort: without a reachable engine, "running" is left null.
+    let running = engine.require_ready().ok().map(|_| {
+        engine
+            .running_service_container(&ctx.domain_name, &ctx.current_directory_name)
+            .is_some()
+    });
+    entry["running"] = serde_json::json!(running);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entry)?);
+        return Ok(());
+    }
+
+    let text = |key: &str| entry[key].as_str().unwrap_or_default().to_string();
+    println!("{:<10} {}", "domain", text("domain").green());
+    println!("{:<10} {}", "group", text("group"));
+    println!("{:<10} {}", "service", text("service").cyan());
+    match entry["port"].as_u64() {
+        Some(port) => {
+            println!("{:<10} {}", "port", port);
+            println!("{:<10} {}", "url", text("url").green());
+        }
+        None => println!(
+            "{:<10} {}",
+            "port",
+            "not assigned yet; run 'darp deploy' or 'darp add-service'".yellow()
+        ),
+    }
+    let state = match running {
+        Some(true) => " (running)".green().to_string(),
+        Some(false) => " (not running)".dimmed().to_string(),
+        None => String::new(),
+    };
+    println!("{:<10} {}{}", "container", text("container"), state);
+    Ok(())
+}
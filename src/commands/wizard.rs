@@ -0,0 +1,319 @@
+// commands/wizard.rs
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::config::{self, Config, DarpPaths};
+
+/// `(marker file, image, serve command)` for project types the wizard recognizes, first
+/// match wins. Serve commands listen on 8000, the port the proxy forwards to.
+const PROJECT_HINTS: &[(&str, &str, &str)] = &[
+    (
+        "artisan",
+        "php:8.3-cli",
+        "php artisan serve --host 0.0.0.0 --port 8000",
+    ),
+    (
+        "manage.py",
+        "python:3.12-slim",
+        "python manage.py runserver 0.0.0.0:8000",
+    ),
+    (
+        "package.json",
+        "node:22-alpine",
+        "npm run dev -- --host 0.0.0.0 --port 8000",
+    ),
+    ("go.mod", "golang:1.23", "go run ."),
+    ("Cargo.toml", "rust:1", "cargo run"),
+    ("Gemfile", "ruby:3.3", "bin/rails server -b 0.0.0.0 -p 8000"),
+    (
+        "pyproject.toml",
+        "python:3.12-slim",
+        "python -m http.server 8000",
+    ),
+    (
+        "requirements.txt",
+        "python:3.12-slim",
+        "python -m http.server 8000",
+    ),
+];
+
+/// The image and serve command suggested for the project in `dir`, from the first
+/// marker file it has.
+pub fn project_hint(dir: &Path) -> Option<(&'static str, &'static str)> {
+    PROJECT_HINTS
+        .iter()
+        .find(|(marker, _, _)| dir.join(marker).exists())
+        .map(|(_, image, serve)| (*image, *serve))
+}
+
+/// Parse `host:container` into two ports.
+pub fn parse_port_pair(answer: &str) -> anyhow::Result<(u16, u16)> {
+    let (host, container) = answer
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Expected host:container, e.g. 9229:9229."))?;
+    let port = |p: &str| {
+        p.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|p| *p > 0)
+            .ok_or_else(|| anyhow!("'{}' is not a port (1-65535).", p.trim()))
+    };
+    Ok((port(host)?, port(container)?))
+}
+
+/// Parse `host_dir:container_dir`; the container side must be absolute.
+pub fn parse_volume(answer: &str) -> anyhow::Result<(String, String)> {
+    let (host, container) = answer.rsplit_once(':').ok_or_else(|| {
+        anyhow!("Expected host_dir:container_dir, e.g. {{home}}/.ssh:/root/.ssh.")
+    })?;
+    if host.is_empty() {
+        return Err(anyhow!("The host directory is empty."));
+    }
+    if !container.starts_with('/') {
+        return Err(anyhow!(
+            "The container directory must be absolute, got '{}'.",
+            container
+        ));
+    }
+    Ok((host.to_string(), container.to_string()))
+}
+
+/// Parse `NAME=value`; names are letters, digits and `_`, not starting with a digit.
+pub fn parse_variable(answer: &str) -> anyhow::Result<(String, String)> {
+    let (name, value) = answer
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected NAME=value."))?;
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(anyhow!("'{}' is not a valid variable name.", name));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Reads answers and writes prompts; None once input runs out.
+struct Prompter<'a, R: BufRead, W: Write> {
+    input: &'a mut R,
+    output: &'a mut W,
+}
+
+impl<R: BufRead, W: Write> Prompter<'_, R, W> {
+    fn ask(&mut self, prompt: &str, default: Option<&str>) -> anyhow::Result<Option<String>> {
+        match default {
+            Some(default) => write!(self.output, "{} [{}]: ", prompt, default.cyan())?,
+            None => write!(self.output, "{}: ", prompt)?,
+        }
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim().to_string()))
+    }
+}
+
+/// Walk through a service's image, serve command, port mappings, volumes and variables,
+/// applying each answer to `config` as it's given, so invalid ones are asked again.
+/// Enter keeps the suggestion in brackets and `-` skips a question. Returns whether the
+/// user confirmed; `config` should only be saved then.
+pub fn run_wizard(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    config: &mut Config,
+    domain_name: &str,
+    group_name: &str,
+    service_name: &str,
+    hint: Option<(&str, &str)>,
+) -> anyhow::Result<bool> {
+    let mut p = Prompter { input, output };
+    let existing = config
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(domain_name))
+        .and_then(|d| d.groups.as_ref())
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .cloned()
+        .unwrap_or_default();
+
+    writeln!(
+        p.output,
+        "Configuring {} (Enter keeps the suggestion, '-' skips).",
+        config.service_host(service_name, domain_name).green()
+    )?;
+
+    let image_default = existing
+        .default_container_image
+        .clone()
+        .or_else(|| hint.map(|(image, _)| image.to_string()));
+    loop {
+        let Some(answer) = p.ask("Container image", image_default.as_deref())? else {
+            return Ok(false);
+        };
+        let image = match (answer.as_str(), &image_default) {
+            ("-", _) | ("", None) => break,
+            ("", Some(default)) => default.clone(),
+            _ => answer,
+        };
+        if image.contains(char::is_whitespace) {
+            writeln!(p.output, "{}", "An image name has no spaces.".red())?;
+            continue;
+        }
+        config.set_service_default_container_image(
+            domain_name,
+            group_name,
+            service_name,
+            &image,
+        )?;
+        break;
+    }
+
+    let serve_default = existing
+        .serve_command
+        .clone()
+        .or_else(|| hint.map(|(_, serve)| serve.to_string()));
+    let Some(answer) = p.ask(
+        "Serve command (listen on port 8000)",
+        serve_default.as_deref(),
+    )?
+    else {
+        return Ok(false);
+    };
+    let serve_command = match (answer.as_str(), &serve_default) {
+        ("-", _) | ("", None) => None,
+        ("", Some(default)) => Some(default.clone()),
+        _ => Some(answer),
+    };
+    if let Some(serve_command) = serve_command {
+        config.set_service_serve_command(domain_name, group_name, service_name, &serve_command)?;
+    }
+
+    loop {
+        let Some(answer) = p.ask("Port mapping host:container (empty when done)", None)? else {
+            return Ok(false);
+        };
+        if answer.is_empty() || answer == "-" {
+            break;
+        }
+        let added = parse_port_pair(&answer).and_then(|(host, container)| {
+            config.add_portmap(
+                domain_name,
+                group_name,
+                service_name,
+                &host.to_string(),
+                &container.to_string(),
+            )
+        });
+        if let Err(e) = added {
+            writeln!(p.output, "{}", e.to_string().red())?;
+        }
+    }
+
+    loop {
+        let Some(answer) = p.ask("Volume host_dir:container_dir (empty when done)", None)? else {
+            return Ok(false);
+        };
+        if answer.is_empty() || answer == "-" {
+            break;
+        }
+        let added = parse_volume(&answer).and_then(|(host, container)| {
+            config.add_service_volume(
+                domain_name,
+                group_name,
+                service_name,
+                &container,
+                &host,
+                false,
+            )
+        });
+        if let Err(e) = added {
+            writeln!(p.output, "{}", e.to_string().red())?;
+        }
+    }
+
+    loop {
+        let Some(answer) = p.ask("Variable NAME=value (empty when done)", None)? else {
+            return Ok(false);
+        };
+        if answer.is_empty() || answer == "-" {
+            break;
+        }
+        let added = parse_variable(&answer).and_then(|(name, value)| {
+            config.add_variable(domain_name, group_name, service_name, &name, &value)
+        });
+        if let Err(e) = added {
+            writeln!(p.output, "{}", e.to_string().red())?;
+        }
+    }
+
+    let service = config
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(domain_name))
+        .and_then(|d| d.groups.as_ref())
+        .and_then(|g| g.get(group_name))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service_name))
+        .cloned()
+        .unwrap_or_default();
+    writeln!(p.output, "\n{}", serde_json::to_string_pretty(&service)?)?;
+    let Some(answer) = p.ask("Save this service? [Y/n]", None)? else {
+        return Ok(false);
+    };
+    Ok(matches!(answer.to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+/// `darp config svc wizard`: set up a service interactively and save it in one write.
+pub fn cmd_wizard(
+    domain_name: String,
+    group_name: String,
+    service_name: String,
+    location: Option<String>,
+    paths: &DarpPaths,
+    config: &mut Config,
+) -> anyhow::Result<()> {
+    let mut draft = config.clone();
+    draft.ensure_domain_exists(&domain_name, location.as_deref())?;
+    let domain_location = draft
+        .domains
+        .as_ref()
+        .and_then(|d| d.get(&domain_name))
+        .map(|d| d.location.clone())
+        .unwrap_or_default();
+    let mut dir = config::resolve_location(&domain_location)?;
+    if group_name != "." {
+        dir.push(&group_name);
+    }
+    dir.push(&service_name);
+
+    let saved = run_wizard(
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+        &mut draft,
+        &domain_name,
+        &group_name,
+        &service_name,
+        project_hint(&dir),
+    )?;
+    if !saved {
+        println!("Nothing saved.");
+        return Ok(());
+    }
+    draft.save_with_backup(paths)?;
+    *config = draft;
+    println!(
+        "Saved {}.{} to {}.",
+        service_name,
+        domain_name,
+        paths.config_path.display()
+    );
+    Ok(())
+}
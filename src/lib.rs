@@ -1,5 +1,8 @@
+pub use darp_core::{
+    backups, config, deploy, engine, error, livereload, logging, mdns, os, resolver, scan, secrets,
+    services_index, timings, urls,
+};
+
+pub mod alias;
 pub mod cli;
 pub mod commands;
-pub mod config;
-pub mod engine;
-pub mod os;
@@ -1,15 +1,62 @@
+use std::io::Write;
+
 use clap::{CommandFactory, Parser};
+use colored::*;
+use tracing::{debug, info, warn};
 
+use darp::alias;
 use darp::cli::*;
 use darp::commands::*;
 use darp::config::{Config, DarpPaths};
+use darp::deploy::service_folders_changed;
 use darp::engine::{self, Engine, EngineKind};
-use darp::os::OsIntegration;
+use darp::error;
+use darp::logging;
+use darp::os::{self, OsIntegration};
+use darp::timings;
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+fn main() {
+    let started = std::time::Instant::now();
+    let result = run();
+    if timings::enabled() {
+        eprint!(
+            "{}",
+            timings::render(&timings::recorded(), started.elapsed())
+        );
+    }
+    if let Err(e) = result {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(error::exit_code(&e));
+    }
+}
 
-    let paths = DarpPaths::from_env()?;
+fn run() -> anyhow::Result<()> {
+    // Only read config for aliases when the first argument isn't a darp command. Path
+    // flags in front of it are set aside while the alias is expanded.
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let (path_args, leading) = PathArgs::scan(&args);
+    let path_flags: Vec<_> = args.drain(1..1 + leading).collect();
+    if alias::may_be_alias(args.get(1)) {
+        let paths =
+            DarpPaths::from_args(path_args.darp_root.as_deref(), path_args.config.as_deref())?;
+        if let Ok(config) = Config::load_merged(&paths.config_path) {
+            args = alias::expand_args(args, &config.aliases.unwrap_or_default())?;
+        }
+    }
+    args.splice(1..1, path_flags);
+    let cli = Cli::parse_from(args);
+    logging::init(cli.log.verbose, cli.log.quiet);
+    os::set_assume_yes(cli.yes);
+    if cli.timings {
+        timings::enable();
+    }
+    let output_json = cli.output == OutputFormat::Json;
+    let paths = DarpPaths::from_args(cli.paths.darp_root.as_deref(), cli.paths.config.as_deref())?;
+    debug!(
+        "config {}, state in {}",
+        paths.config_path.display(),
+        paths.darp_root.display()
+    );
 
     if let Some(cmd) = cli.command {
         match cmd {
@@ -22,6 +69,8 @@ fn main() -> anyhow::Result<()> {
                     let config = Config::load(&paths.config_path)?;
                     cmd_pull(&config)?;
                 }
+                ConfigCommand::Rollback { n, list } => cmd_rollback(n, list, output_json, &paths)?,
+                ConfigCommand::Move { cmd } => cmd_move(cmd, &paths)?,
                 _ => {
                     let mut config = Config::load(&paths.config_path)?;
                     let engine_kind = EngineKind::from_config(&config);
@@ -31,77 +80,319 @@ fn main() -> anyhow::Result<()> {
                         }
                         ConfigCommand::Add { cmd } => cmd_add(cmd, &paths, &mut config)?,
                         ConfigCommand::Rm { cmd } => cmd_rm(cmd, &paths, &mut config)?,
-                        ConfigCommand::Show { .. } | ConfigCommand::Pull => unreachable!(),
+                        ConfigCommand::Svc {
+                            cmd:
+                                ConfigSvcCommand::Wizard {
+                                    domain_name,
+                                    service_name,
+                                    group_name,
+                                    location,
+                                },
+                        } => cmd_wizard(
+                            domain_name,
+                            group_name,
+                            service_name,
+                            location,
+                            &paths,
+                            &mut config,
+                        )?,
+                        ConfigCommand::Show { .. }
+                        | ConfigCommand::Pull
+                        | ConfigCommand::Rollback { .. }
+                        | ConfigCommand::Move { .. } => unreachable!(),
                     }
                 }
             },
-            _ => {
+            Command::Completion { shell } => {
                 let config = Config::load_merged(&paths.config_path)?;
+                cmd_completion(shell, &config.aliases.unwrap_or_default())?;
+            }
+            Command::Man { dir, remove } => cmd_man(dir, remove)?,
+            Command::Check => std::process::exit(cmd_check(&paths)),
+            Command::Install {
+                engine,
+                rc_file,
+                no_rc,
+            } => {
+                let mut config = Config::load_merged(&paths.config_path)?;
+                configure_install_engine(&paths, &mut config, engine)?;
                 let engine_kind = EngineKind::from_config(&config);
                 let engine = Engine::new(engine_kind.clone(), &config)?;
                 let os = OsIntegration::new(&paths, &config, &engine_kind);
+                let rc = RcTarget::from_flags(rc_file, no_rc);
+                cmd_install(&paths, &config, &os, &engine, &rc)?;
+            }
+            _ => {
+                let config = Config::load_merged(&paths.config_path)?;
+                let engine_kind = EngineKind::from_config(&config);
+                let engine = command_engine(&cmd, &config)?;
+                let os = OsIntegration::new(&paths, &config, &engine_kind);
                 match cmd {
-                    Command::Install => cmd_install(&paths, &config, &os, &engine)?,
-                    Command::Uninstall => cmd_uninstall(&paths, &mut config.clone(), &os, &engine)?,
-                    Command::Deploy => cmd_deploy(&paths, &config, &os, &engine)?,
-                    Command::Shell {
-                        environment,
-                        dry_run,
-                        container_image,
-                    } => cmd_shell(
-                        environment,
-                        dry_run,
-                        container_image,
+                    Command::Uninstall { rc_file, no_rc } => {
+                        let rc = RcTarget::from_flags(rc_file, no_rc);
+                        cmd_uninstall(&paths, &mut config.clone(), &os, &engine, &rc)?
+                    }
+                    Command::Deploy {
+                        verify,
+                        skip_missing,
+                        if_changed,
+                    } => {
+                        if if_changed && !service_folders_changed(&paths, &config)? {
+                            debug!("no service folders added or removed; skipping deploy");
+                            return Ok(());
+                        }
+                        cmd_deploy(&paths, &config, &os, &engine, skip_missing)?;
+                        if verify {
+                            verify_deploy(&paths)?;
+                        }
+                    }
+                    Command::AddService { new } => cmd_add_service(new, &paths, &config, &engine)?,
+                    Command::Shell { run, persist } => {
+                        cmd_shell(run, persist, &paths, &config, &engine)?
+                    }
+                    Command::Serve {
+                        run,
+                        wait,
+                        wait_timeout,
+                        restart,
+                        detach,
+                        prefix,
+                    } => cmd_serve(
+                        run,
+                        ServeOptions {
+                            wait: wait.then_some(wait_timeout),
+                            restart,
+                            detach,
+                            prefix,
+                        },
                         &paths,
                         &config,
                         &engine,
                     )?,
-                    Command::Serve {
-                        environment,
-                        dry_run,
-                        container_image,
-                    } => cmd_serve(
+                    Command::Logs { follow, tail } => {
+                        cmd_logs(follow, tail.as_deref(), &paths, &config, &engine)?
+                    }
+                    Command::Stop => cmd_stop(&paths, &config, &engine)?,
+                    Command::Up {
+                        domain,
+                        tags,
+                        only,
+                        except,
                         environment,
-                        dry_run,
-                        container_image,
+                    } => cmd_up(
+                        domain,
+                        UpOptions {
+                            tags,
+                            only,
+                            except,
+                            environment,
+                        },
+                        &paths,
+                        &config,
+                        &engine,
+                    )?,
+                    Command::Urls {
+                        domain,
+                        filter,
+                        markdown,
+                        html,
+                        json,
+                        plain,
+                        direct,
+                        qr,
+                        tags,
+                    } => cmd_urls(
+                        UrlsOptions {
+                            domain,
+                            filter,
+                            tags,
+                            markdown,
+                            html,
+                            json: json || output_json,
+                            plain,
+                            direct,
+                            qr,
+                        },
                         &paths,
                         &config,
                         &engine,
                     )?,
-                    Command::Urls => cmd_urls(&paths, &config)?,
-                    Command::Doctor => cmd_doctor(&paths, &config, &engine)?,
+                    Command::Which { json } => {
+                        cmd_which(json || output_json, &paths, &config, &engine)?
+                    }
+                    Command::Hook { cmd } => cmd_hook(cmd, &paths, &config, &engine)?,
+                    Command::Open { service } => cmd_open(service, &paths, &config)?,
+                    Command::LiveReload => cmd_live_reload(&config)?,
+                    Command::Ps { json } => cmd_ps(json || output_json, &paths, &config, &engine)?,
+                    Command::Artifacts { json, watch } => {
+                        cmd_artifacts(&paths, json || output_json, watch)?
+                    }
+                    Command::Doctor => cmd_doctor(&paths, &config, &engine, output_json)?,
+                    Command::Generate { cmd } => cmd_generate(cmd, &config)?,
+                    Command::Envrc { domain, remove } => {
+                        cmd_envrc(domain, remove, &paths, &config)?
+                    }
+                    Command::Share { service, via } => cmd_share(service, via, &paths, &config)?,
+                    Command::Mdns => cmd_mdns(&paths, &config)?,
+                    Command::Next => cmd_next(&paths, &config, &engine, output_json)?,
+                    Command::Machine { cmd } => cmd_machine(cmd, &engine)?,
+                    Command::Volume { cmd } => cmd_volume(cmd, output_json, &config, &engine)?,
+                    Command::Proxy { cmd } => cmd_proxy(cmd, output_json, &paths, &engine)?,
+                    Command::Secret { cmd } => cmd_secret(cmd, &paths, output_json)?,
+                    Command::Export { cmd } => cmd_export(cmd, &paths, &config, &engine)?,
                     Command::CheckImage { image, environment } => {
                         cmd_check_image(image, environment, &paths, &config, &engine)?
                     }
-                    Command::Config { .. } => unreachable!(),
+                    Command::Config { .. }
+                    | Command::Install { .. }
+                    | Command::Completion { .. }
+                    | Command::Man { .. }
+                    | Command::Check => unreachable!(),
                 }
             }
         }
     } else {
-        let mut cmd = Cli::command();
-        cmd.print_help()?;
-        println!();
+        match Config::load_merged(&paths.config_path) {
+            // A fresh install: say how to get going rather than listing every command.
+            Ok(config) if is_unconfigured(&config) => {
+                print!(
+                    "{}",
+                    render_onboarding(&onboarding_steps(&paths, &config, false))
+                );
+                println!(
+                    "\nRun 'darp next' to check progress, or 'darp --help' for every command."
+                );
+            }
+            _ => {
+                let mut cmd = Cli::command();
+                cmd.print_help()?;
+                println!();
+            }
+        }
     }
 
     Ok(())
 }
 
+/// The engine `cmd` runs on. Commands that act on the current directory's service use
+/// that service's own engine when its domain or environment sets one; a service picked
+/// from outside its folder is resolved again once picked. The rest use the global
+/// engine, and `ps` and `urls` add any others themselves (`Engine::overrides`).
+fn command_engine(cmd: &Command, config: &Config) -> anyhow::Result<Engine> {
+    let environment = match cmd {
+        Command::Serve { run, .. } | Command::Shell { run, .. } => run.environment.clone(),
+        Command::Logs { .. } | Command::Stop => None,
+        _ => return Engine::new(EngineKind::from_config(config), config),
+    };
+    match config.service_context_from_cwd(environment) {
+        Some(ctx) => Engine::for_service(config, ctx.domain, ctx.environment),
+        None => Engine::new(EngineKind::from_config(config), config),
+    }
+}
+
+/// Pick the container engine for `darp install`: `--engine` wins; otherwise, when no
+/// engine is configured yet, probe PATH and offer the first reachable one. The choice is
+/// written to the leaf config and mirrored into `config` for the rest of the install.
+fn configure_install_engine(
+    paths: &DarpPaths,
+    config: &mut Config,
+    forced: Option<String>,
+) -> anyhow::Result<()> {
+    let chosen = if let Some(name) = forced {
+        EngineKind::parse(&name)
+            .ok_or_else(|| anyhow::anyhow!("engine must be 'podman' or 'docker'"))?
+    } else {
+        if config.engine.is_some() {
+            return Ok(());
+        }
+
+        let detected = engine::detect_engines(config);
+        if engine::is_on_path("nerdctl") {
+            info!("found nerdctl on PATH, but darp only supports podman and docker");
+        }
+        let Some(best) = detected.first() else {
+            warn!(
+                "no container engine found on PATH; install podman or docker, then run 'darp install' again"
+            );
+            return Ok(());
+        };
+
+        let state = if best.reachable {
+            "running"
+        } else {
+            "installed but not running"
+        };
+        if os::assume_yes() {
+            println!("Detected {} ({}).", best.kind.as_str().green(), state);
+            return save_install_engine(paths, config, best.kind.clone());
+        }
+        print!(
+            "Detected {} ({}). Use it as the darp container engine? [Y/n] ",
+            best.kind.as_str().green(),
+            state
+        );
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if matches!(answer.trim().to_lowercase().as_str(), "n" | "no") {
+            println!("Skipping engine setup. Use 'darp config set engine <podman|docker>' later.");
+            return Ok(());
+        }
+        best.kind.clone()
+    };
+    save_install_engine(paths, config, chosen)
+}
+
+fn save_install_engine(
+    paths: &DarpPaths,
+    config: &mut Config,
+    chosen: EngineKind,
+) -> anyhow::Result<()> {
+    let mut leaf = Config::load(&paths.config_path)?;
+    leaf.engine = Some(chosen.as_str().to_string());
+    leaf.save_with_backup(paths)?;
+    config.engine = Some(chosen.as_str().to_string());
+    println!("Engine set to {}.", chosen.as_str().green());
+    Ok(())
+}
+
 fn cmd_install(
     paths: &DarpPaths,
-    _config: &Config,
+    config: &Config,
     os: &OsIntegration,
     engine: &Engine,
+    rc: &RcTarget,
 ) -> anyhow::Result<()> {
     println!("Running installation");
 
+    if let Some(sandbox) = os.sandbox {
+        println!(
+            "{} darp is running inside {}. Some features work differently:",
+            "note:".yellow(),
+            sandbox.describe()
+        );
+        for limitation in os::SANDBOX_LIMITATIONS {
+            println!("  - {}", limitation);
+        }
+    }
+
     os.init_resolver()?;
     os.ensure_dnsmasq_dir()?;
     os.copy_nginx_conf()?;
     os.write_test_conf()?;
 
     engine.configure_unprivileged_ports_if_needed()?;
+    engine.enable_restart_on_boot()?;
+
+    install_shell_completions(rc, &config.aliases.clone().unwrap_or_default())?;
 
-    install_shell_completions()?;
+    match default_man_dir() {
+        Some(dir) => match install_man_pages(&dir) {
+            Ok(n) => println!("Installed {} man pages to {}", n, dir.display()),
+            Err(e) => warn!("could not install man pages ({})", e),
+        },
+        None => println!("Could not determine home directory; skipping man pages."),
+    }
 
     // Probe the container engine for its host-gateway IP and cache it for deploy.
     // Skipped if the engine isn't configured or isn't currently running — deploy
@@ -113,12 +404,15 @@ fn cmd_install(
                 println!("cached container host gateway: {}", ip);
             }
             Err(e) => {
-                eprintln!(
-                    "warning: could not probe container host gateway ({}); deploy will retry",
+                warn!(
+                    "could not probe container host gateway ({}); deploy will retry",
                     e
                 );
             }
         }
+        if let Err(e) = engine.ensure_darp_network() {
+            warn!("{}; deploy will retry", e);
+        }
     }
 
     Ok(())
@@ -129,16 +423,25 @@ fn cmd_uninstall(
     _config: &mut Config,
     os: &OsIntegration,
     engine: &Engine,
+    rc: &RcTarget,
 ) -> anyhow::Result<()> {
     println!("Running uninstallation");
 
     engine.stop_running_darps()?;
-    engine.stop_named_container("darp-reverse-proxy")?;
-    engine.stop_named_container("darp-masq")?;
+    engine.remove_darp_pods()?;
+    engine.remove_named_container(engine::REVERSE_PROXY)?;
+    engine.remove_named_container(engine::DNSMASQ)?;
+    engine.remove_darp_network()?;
 
     os.uninstall()?;
 
-    uninstall_shell_completions()?;
+    uninstall_shell_completions(rc)?;
+    if let Some(dir) = default_man_dir() {
+        let removed = uninstall_man_pages(&dir)?;
+        if removed > 0 {
+            println!("Removed {} man pages from {}", removed, dir.display());
+        }
+    }
 
     println!("Uninstall complete. Darp config.json has been left on disk.");
     Ok(())
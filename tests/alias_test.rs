@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+use darp::alias::{
+    builtin_names, command_with_aliases, expand_args, may_be_alias, split_words, validate_alias,
+};
+use darp::config::Config;
+
+fn args(list: &[&str]) -> Vec<OsString> {
+    list.iter().map(OsString::from).collect()
+}
+
+fn aliases(list: &[(&str, &str)]) -> BTreeMap<String, String> {
+    list.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// split_words
+// ---------------------------------------------------------------------------
+
+#[test]
+fn split_words_honours_quotes_and_escapes() {
+    assert_eq!(
+        split_words(r#"serve -e 'lara:13' --restart "on-failure:3" a\ b"#).unwrap(),
+        vec!["serve", "-e", "lara:13", "--restart", "on-failure:3", "a b"]
+    );
+    assert_eq!(
+        split_words("  up   --tag  web ").unwrap(),
+        vec!["up", "--tag", "web"]
+    );
+    assert_eq!(split_words("urls ''").unwrap(), vec!["urls", ""]);
+    assert!(split_words("serve -e 'dev").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// expand_args
+// ---------------------------------------------------------------------------
+
+#[test]
+fn expand_args_replaces_a_leading_alias_and_keeps_the_rest() {
+    let a = aliases(&[("s", "serve -e dev")]);
+    assert_eq!(
+        expand_args(args(&["darp", "s", "--detach"]), &a).unwrap(),
+        args(&["darp", "serve", "-e", "dev", "--detach"])
+    );
+    // Only the first argument is looked up.
+    assert_eq!(
+        expand_args(args(&["darp", "logs", "s"]), &a).unwrap(),
+        args(&["darp", "logs", "s"])
+    );
+    assert_eq!(expand_args(args(&["darp"]), &a).unwrap(), args(&["darp"]));
+}
+
+#[test]
+fn builtin_commands_win_over_aliases() {
+    let a = aliases(&[("serve", "shell")]);
+    assert_eq!(
+        expand_args(args(&["darp", "serve"]), &a).unwrap(),
+        args(&["darp", "serve"])
+    );
+    assert!(!may_be_alias(Some(&OsString::from("serve"))));
+    assert!(!may_be_alias(Some(&OsString::from("--help"))));
+    assert!(may_be_alias(Some(&OsString::from("s"))));
+    assert!(!may_be_alias(None));
+}
+
+// ---------------------------------------------------------------------------
+// Config aliases
+// ---------------------------------------------------------------------------
+
+#[test]
+fn alias_lifecycle() {
+    let builtins = builtin_names();
+    let mut c = Config::default();
+    c.add_alias("s", "serve -e dev", &builtins).unwrap();
+    c.add_alias("s", "serve -e prod", &builtins).unwrap();
+    assert_eq!(c.aliases, Some(aliases(&[("s", "serve -e prod")])));
+
+    assert!(c.add_alias("deploy", "up", &builtins).is_err());
+    assert!(c.add_alias("a b", "up", &builtins).is_err());
+    assert!(c.add_alias("-x", "up", &builtins).is_err());
+    assert!(c.add_alias("empty", "  ", &builtins).is_err());
+    assert!(validate_alias("sv", "serve -e 'dev").is_err());
+
+    c.rm_alias("s").unwrap();
+    assert_eq!(c.aliases, None);
+    assert!(c.rm_alias("s").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Completions
+// ---------------------------------------------------------------------------
+
+#[test]
+fn aliases_complete_like_the_command_they_expand_to() {
+    let cmd = command_with_aliases(&aliases(&[("s", "serve -e dev"), ("x", "not-a-command")]));
+    let s = cmd.find_subcommand("s").expect("alias subcommand");
+    assert!(s.get_arguments().any(|a| a.get_long() == Some("detach")));
+    assert_eq!(
+        s.get_about().map(|a| a.to_string()),
+        Some("Alias for 'darp serve -e dev'".to_string())
+    );
+    assert!(cmd.find_subcommand("x").is_some());
+    // Built-in names are never replaced.
+    assert!(
+        command_with_aliases(&aliases(&[("serve", "shell")]))
+            .find_subcommand("serve")
+            .unwrap()
+            .get_arguments()
+            .any(|a| a.get_long() == Some("wait"))
+    );
+}
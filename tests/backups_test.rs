@@ -0,0 +1,130 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use darp::backups::{MAX_BACKUPS, back_up, backup_file_name, list_backups, nth_backup};
+use darp::config::{Config, DarpPaths};
+
+// ---------------------------------------------------------------------------
+// File names
+// ---------------------------------------------------------------------------
+
+#[test]
+fn backup_names_carry_the_utc_time() {
+    assert_eq!(
+        backup_file_name(UNIX_EPOCH),
+        "config-19700101-000000-000.json"
+    );
+    // 2024-02-29T23:59:58.007Z
+    let leap_day = UNIX_EPOCH + Duration::from_millis(1_709_251_198_007);
+    assert_eq!(
+        backup_file_name(leap_day),
+        "config-20240229-235958-007.json"
+    );
+    // 2026-12-31T08:30:00Z
+    let new_years_eve = UNIX_EPOCH + Duration::from_secs(1_798_705_800);
+    assert_eq!(
+        backup_file_name(new_years_eve),
+        "config-20261231-083000-000.json"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Saving
+// ---------------------------------------------------------------------------
+
+#[test]
+fn saving_backs_up_the_previous_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+
+    let mut c = Config::default();
+    c.save_with_backup(&paths).unwrap();
+    // The first save has nothing to back up.
+    assert!(list_backups(&paths.backups_dir).unwrap().is_empty());
+
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.save_with_backup(&paths).unwrap();
+    let backups = list_backups(&paths.backups_dir).unwrap();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(std::fs::read_to_string(&backups[0]).unwrap(), "{}");
+
+    // Saving the same config again keeps no copy.
+    c.save_with_backup(&paths).unwrap();
+    assert_eq!(list_backups(&paths.backups_dir).unwrap().len(), 1);
+}
+
+#[test]
+fn backups_go_to_the_state_directory() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let state_dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(
+        config_dir.path().join("config.json"),
+        state_dir.path().to_path_buf(),
+    );
+    std::fs::write(&paths.config_path, "{}").unwrap();
+
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.save_with_backup(&paths).unwrap();
+    assert_eq!(paths.backups_dir, state_dir.path().join("backups"));
+    assert_eq!(list_backups(&paths.backups_dir).unwrap().len(), 1);
+    assert!(!config_dir.path().join("backups").exists());
+}
+
+#[test]
+fn only_the_newest_backups_are_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, "{}").unwrap();
+    let backups = dir.path().join("backups");
+    std::fs::create_dir_all(&backups).unwrap();
+    for i in 0..MAX_BACKUPS {
+        std::fs::write(
+            backups.join(format!("config-20240101-0000{:02}-000.json", i)),
+            "{}",
+        )
+        .unwrap();
+    }
+
+    back_up(&backups, &path, b"{\"engine\":\"docker\"}").unwrap();
+    let kept = list_backups(&backups).unwrap();
+    assert_eq!(kept.len(), MAX_BACKUPS);
+    assert!(!backups.join("config-20240101-000000-000.json").exists());
+    assert!(backups.join("config-20240101-000001-000.json").exists());
+}
+
+// ---------------------------------------------------------------------------
+// Picking a backup
+// ---------------------------------------------------------------------------
+
+#[test]
+fn backups_are_numbered_newest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let backups = dir.path().join("backups");
+    std::fs::create_dir_all(&backups).unwrap();
+    for name in [
+        "config-20240101-000000-000.json",
+        "config-20240301-000000-000.json",
+        "config-20240201-000000-000.json",
+        "notes.txt",
+    ] {
+        std::fs::write(backups.join(name), "{}").unwrap();
+    }
+
+    assert_eq!(
+        nth_backup(&backups, 1).unwrap(),
+        backups.join("config-20240301-000000-000.json")
+    );
+    assert_eq!(
+        nth_backup(&backups, 3).unwrap(),
+        backups.join("config-20240101-000000-000.json")
+    );
+    assert!(nth_backup(&backups, 0).is_err());
+    assert!(nth_backup(&backups, 4).is_err());
+}
+
+#[test]
+fn no_backups_is_an_error_for_rollback() {
+    let dir = tempfile::tempdir().unwrap();
+    let err = nth_backup(&dir.path().join("backups"), 1).unwrap_err();
+    assert!(err.to_string().contains("No config backups"), "{}", err);
+}
@@ -0,0 +1,67 @@
+use darp::config::{Canary, Config};
+use darp::deploy::canary_vhost;
+
+#[test]
+fn canary_vhost_splits_by_percent_between_upstreams() {
+    let vhost = canary_vhost(
+        "shop",
+        "api",
+        "http://172.17.0.1:50100",
+        "http://172.17.0.1:50103",
+        10,
+        None,
+    );
+
+    assert!(vhost.starts_with(
+        "split_clients \"${request_id}\" $darp_canary_shop_api {\n    10% http://172.17.0.1:50103;\n    * http://172.17.0.1:50100;\n}\n"
+    ));
+    assert!(vhost.contains("    server_name api.shop.test;\n    location / {\n        proxy_pass $darp_canary_shop_api;\n"));
+    assert!(!vhost.contains("resolver"));
+}
+
+#[test]
+fn canary_vhost_keeps_websocket_upgrades_and_idle_sockets() {
+    let vhost = canary_vhost("shop", "web", "http://a:1", "http://b:2", 50, None);
+    assert!(vhost.contains("        proxy_http_version 1.1;\n        proxy_set_header Upgrade $http_upgrade;\n        proxy_set_header Connection $connection_upgrade;\n        proxy_read_timeout 1h;\n"));
+}
+
+#[test]
+fn canary_vhost_uses_the_network_resolver_and_safe_variable_names() {
+    let vhost = canary_vhost(
+        "my-shop",
+        "api.v1",
+        "http://darp_my-shop_api.v1:8000",
+        "http://darp_my-shop_api-v2:8000",
+        25,
+        Some("10.89.0.1"),
+    );
+    assert!(vhost.contains("$darp_canary_my_shop_api_v1 {"));
+    assert!(vhost.contains("    resolver 10.89.0.1 valid=10s;\n    location / {"));
+}
+
+#[test]
+fn domain_canary_lifecycle_and_validation() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+
+    assert!(c.set_domain_canary("shop", "api", "api-v2", 0).is_err());
+    assert!(c.set_domain_canary("shop", "api", "api-v2", 100).is_err());
+    assert!(c.set_domain_canary("shop", "api", "api", 10).is_err());
+
+    c.set_domain_canary("shop", "api", "api-v2", 10).unwrap();
+    let canaries = c.domains.as_ref().unwrap()["shop"]
+        .canaries
+        .clone()
+        .unwrap();
+    assert_eq!(
+        canaries["api"],
+        Canary {
+            service: "api-v2".into(),
+            percent: 10
+        }
+    );
+
+    c.rm_domain_canary("shop", "api").unwrap();
+    assert!(c.domains.as_ref().unwrap()["shop"].canaries.is_none());
+    assert!(c.rm_domain_canary("shop", "api").is_err());
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use darp::commands::{CheckFailure, config_problem, run_checks};
+use darp::config::{Config, DarpPaths, Domain};
+
+fn config_at(location: &std::path::Path) -> Config {
+    Config {
+        engine: Some("docker".to_string()),
+        domains: Some(BTreeMap::from([(
+            "shop".to_string(),
+            Domain {
+                location: location.to_string_lossy().into_owned(),
+                ..Default::default()
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// config_problem
+// ---------------------------------------------------------------------------
+
+#[test]
+fn a_complete_config_has_no_problem() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(config_problem(&config_at(dir.path())), None);
+}
+
+#[test]
+fn an_engine_is_required() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = config_at(dir.path());
+    config.engine = None;
+    assert_eq!(
+        config_problem(&config).as_deref(),
+        Some("no engine configured")
+    );
+    config.engine = Some("lxc".to_string());
+    assert!(config_problem(&config).unwrap().contains("lxc"));
+}
+
+#[test]
+fn domains_are_required_and_must_exist() {
+    let config = Config {
+        engine: Some("podman".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        config_problem(&config).as_deref(),
+        Some("no domains configured")
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let gone = dir.path().join("gone");
+    let problem = config_problem(&config_at(&gone)).unwrap();
+    assert!(problem.starts_with("domain 'shop'"), "{problem}");
+    assert!(problem.contains("does not exist"), "{problem}");
+}
+
+// ---------------------------------------------------------------------------
+// run_checks
+// ---------------------------------------------------------------------------
+
+#[test]
+fn exit_codes_are_distinct_and_skip_clap_usage_errors() {
+    let codes: Vec<i32> = [
+        CheckFailure::Config,
+        CheckFailure::Engine,
+        CheckFailure::Proxy,
+        CheckFailure::Dns,
+    ]
+    .iter()
+    .map(|f| f.exit_code())
+    .collect();
+    assert_eq!(codes, [1, 3, 4, 5]);
+}
+
+#[test]
+fn an_unreadable_config_fails_as_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.json");
+    std::fs::write(&config_path, "{ not json").unwrap();
+    let paths = DarpPaths::new(config_path, dir.path().to_path_buf());
+    assert_eq!(run_checks(&paths).unwrap_err().0, CheckFailure::Config);
+}
+
+#[test]
+fn an_empty_config_fails_as_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+    let (failure, reason) = run_checks(&paths).unwrap_err();
+    assert_eq!(failure, CheckFailure::Config);
+    assert_eq!(reason, "no engine configured");
+}
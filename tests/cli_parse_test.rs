@@ -0,0 +1,1480 @@
+use clap::Parser;
+
+use darp::cli::*;
+
+// ---------------------------------------------------------------------------
+// envvar alias for variable subcommands
+// ---------------------------------------------------------------------------
+
+#[test]
+fn add_env_envvar_parses_as_variable() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "env",
+        "envvar",
+        "node",
+        "NODE_ENV",
+        "development",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Env {
+                            cmd:
+                                AddEnvCommand::Variable {
+                                    environment,
+                                    name,
+                                    value,
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(environment, "node");
+            assert_eq!(name, "NODE_ENV");
+            assert_eq!(value, "development");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn rm_svc_envvar_parses_as_variable() {
+    let cli = Cli::try_parse_from([
+        "darp", "config", "rm", "svc", "envvar", "dom", ".", "api", "API_KEY",
+    ])
+    .unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Rm {
+                cmd: RmCommand::Svc {
+                    cmd: RmSvcCommand::Variable { .. }
+                }
+            }
+        })
+    ));
+}
+
+// ---------------------------------------------------------------------------
+// serve/shell run options
+// ---------------------------------------------------------------------------
+
+#[test]
+fn serve_accepts_repeated_env_files() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "serve",
+        "-e",
+        "node",
+        "--env-file",
+        ".env",
+        "--env-file",
+        ".env.local",
+        "node:22",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Serve { run, .. }) => {
+            assert_eq!(run.environment.as_deref(), Some("node"));
+            assert_eq!(run.env_files, vec![".env", ".env.local"]);
+            assert_eq!(run.container_image.as_deref(), Some("node:22"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn serve_accepts_restart_policy() {
+    let cli = Cli::try_parse_from(["darp", "serve", "--restart", "unless-stopped"]).unwrap();
+    match cli.command {
+        Some(Command::Serve { run, restart, .. }) => {
+            assert_eq!(restart.as_deref(), Some("unless-stopped"));
+            assert!(run.container_image.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_add_svc_pre_serve_takes_a_command() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "svc",
+        "pre-serve",
+        "shop",
+        ".",
+        "api",
+        "npm ci",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Svc {
+                            cmd:
+                                AddSvcCommand::PreServe {
+                                    domain_name,
+                                    service_name,
+                                    command,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(domain_name, "shop");
+            assert_eq!(service_name, "api");
+            assert_eq!(command, "npm ci");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn serve_detach_short_flag() {
+    let cli = Cli::try_parse_from(["darp", "serve", "-d", "--wait"]).unwrap();
+    match cli.command {
+        Some(Command::Serve { detach, wait, .. }) => {
+            assert!(detach);
+            assert!(wait);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn logs_accepts_follow_and_tail() {
+    let cli = Cli::try_parse_from(["darp", "logs", "-f", "--tail", "50"]).unwrap();
+    match cli.command {
+        Some(Command::Logs { follow, tail }) => {
+            assert!(follow);
+            assert_eq!(tail.as_deref(), Some("50"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn up_accepts_repeated_tags() {
+    let cli = Cli::try_parse_from([
+        "darp", "up", "-d", "shop", "--tag", "backend", "--tag", "api",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Up {
+            domain,
+            tags,
+            environment,
+            ..
+        }) => {
+            assert_eq!(domain.as_deref(), Some("shop"));
+            assert_eq!(tags, vec!["backend", "api"]);
+            assert!(environment.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn up_splits_only_and_except_on_commas() {
+    let cli =
+        Cli::try_parse_from(["darp", "up", "--only", "db,api", "--except", "search"]).unwrap();
+    match cli.command {
+        Some(Command::Up { only, except, .. }) => {
+            assert_eq!(only, vec!["db", "api"]);
+            assert_eq!(except, vec!["search"]);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn set_svc_start_order_accepts_negative_values() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "svc",
+        "start-order",
+        "shop",
+        ".",
+        "db",
+        "-10",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd: SetSvcCommand::StartOrder { start_order, .. },
+                        },
+                },
+        }) => assert_eq!(start_order, -10),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn urls_accepts_tag_filter() {
+    let cli = Cli::try_parse_from(["darp", "urls", "--markdown", "--tag", "frontend"]).unwrap();
+    match cli.command {
+        Some(Command::Urls { markdown, tags, .. }) => {
+            assert!(markdown);
+            assert_eq!(tags, vec!["frontend"]);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn urls_takes_an_optional_domain_and_filter() {
+    let cli = Cli::try_parse_from(["darp", "urls", "shop", "--filter", "api*"]).unwrap();
+    match cli.command {
+        Some(Command::Urls { domain, filter, .. }) => {
+            assert_eq!(domain.as_deref(), Some("shop"));
+            assert_eq!(filter.as_deref(), Some("api*"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn urls_output_formats_are_mutually_exclusive() {
+    assert!(Cli::try_parse_from(["darp", "urls", "--json"]).is_ok());
+    assert!(Cli::try_parse_from(["darp", "urls", "--plain", "--tag", "api"]).is_ok());
+    assert!(Cli::try_parse_from(["darp", "urls", "--json", "--plain"]).is_err());
+    assert!(Cli::try_parse_from(["darp", "urls", "--markdown", "--json"]).is_err());
+}
+
+#[test]
+fn which_accepts_json() {
+    let cli = Cli::try_parse_from(["darp", "which", "--json"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Which { json: true })));
+}
+
+#[test]
+fn hook_git_install_parses() {
+    let cli = Cli::try_parse_from(["darp", "hook", "git", "install"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Hook {
+            cmd: HookCommand::Git {
+                cmd: GitHookCommand::Install
+            }
+        })
+    ));
+}
+
+#[test]
+fn deploy_accepts_if_changed() {
+    let cli = Cli::try_parse_from(["darp", "deploy", "--if-changed"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Deploy {
+            if_changed: true,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn hook_direnv_parses() {
+    let cli = Cli::try_parse_from(["darp", "hook", "direnv"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Hook {
+            cmd: HookCommand::Direnv
+        })
+    ));
+}
+
+#[test]
+fn proxy_logs_can_limit_to_the_error_log() {
+    let cli = Cli::try_parse_from(["darp", "proxy", "logs", "-f", "--errors"]).unwrap();
+    match cli.command {
+        Some(Command::Proxy {
+            cmd:
+                ProxyCommand::Logs {
+                    follow,
+                    errors,
+                    access,
+                    tail,
+                },
+        }) => {
+            assert!(follow && errors && !access);
+            assert!(tail.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["darp", "proxy", "logs", "--errors", "--access"]).is_err());
+}
+
+#[test]
+fn shell_accepts_network() {
+    let cli = Cli::try_parse_from(["darp", "shell", "--network", "shop_default"]).unwrap();
+    match cli.command {
+        Some(Command::Shell { run, .. }) => {
+            assert_eq!(run.network.as_deref(), Some("shop_default"));
+            assert!(run.container_image.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_set_darp_network_takes_a_value() {
+    let cli = Cli::try_parse_from(["darp", "config", "set", "darp-network", "false"]).unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd: SetCommand::DarpNetwork { value },
+                },
+        }) => assert_eq!(value, "false"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_set_remote_takes_host_address_and_flags() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "remote",
+        "me@devbox",
+        "192.168.1.20",
+        "--resolver",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Remote {
+                            host,
+                            address,
+                            url,
+                            resolver,
+                        },
+                },
+        }) => {
+            assert_eq!(host, "me@devbox");
+            assert_eq!(address, "192.168.1.20");
+            assert!(url.is_none());
+            assert!(resolver);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn install_rc_file_conflicts_with_no_rc() {
+    let cli = Cli::try_parse_from(["darp", "install", "--rc-file", "/tmp/rc"]).unwrap();
+    match cli.command {
+        Some(Command::Install { rc_file, no_rc, .. }) => {
+            assert_eq!(rc_file.as_deref(), Some("/tmp/rc"));
+            assert!(!no_rc);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["darp", "install", "--rc-file", "/tmp/rc", "--no-rc"]).is_err());
+}
+
+#[test]
+fn set_svc_protocol_parses() {
+    let cli = Cli::try_parse_from([
+        "darp", "config", "set", "svc", "protocol", "shop", ".", "api", "grpc",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd: SetSvcCommand::Protocol { value, .. },
+                        },
+                },
+        }) => assert_eq!(value, "grpc"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn set_svc_stream_takes_port_and_udp_flag() {
+    let cli = Cli::try_parse_from([
+        "darp", "config", "set", "svc", "stream", "shop", ".", "db", "5432", "--udp",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd: SetSvcCommand::Stream { port, udp, .. },
+                        },
+                },
+        }) => {
+            assert_eq!(port, 5432);
+            assert!(udp);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn set_svc_replicas_takes_a_count() {
+    let cli = Cli::try_parse_from([
+        "darp", "config", "set", "svc", "replicas", "shop", ".", "api", "3",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd: SetSvcCommand::Replicas { count, .. },
+                        },
+                },
+        }) => assert_eq!(count, 3),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn add_alias_takes_a_quoted_command_with_flags() {
+    let cli = Cli::try_parse_from(["darp", "config", "add", "alias", "s", "-e dev"]).unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd: AddCommand::Alias { name, command },
+                },
+        }) => {
+            assert_eq!(name, "s");
+            assert_eq!(command, "-e dev");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parses_config_add_dom_extra_host() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "dom",
+        "extra-host",
+        "shop",
+        "cdn.shop.test",
+        "10.0.0.5",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Dom {
+                            cmd:
+                                AddDomCommand::ExtraHost {
+                                    domain_name,
+                                    host,
+                                    ip,
+                                    location,
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(domain_name, "shop");
+            assert_eq!(host, "cdn.shop.test");
+            assert_eq!(ip, "10.0.0.5");
+            assert!(location.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parses_config_add_svc_alias() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "svc",
+        "alias",
+        "shop",
+        ".",
+        "api",
+        "api.myshop.test",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Svc {
+                            cmd:
+                                AddSvcCommand::Alias {
+                                    domain_name,
+                                    group_name,
+                                    service_name,
+                                    hostname,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(domain_name, "shop");
+            assert_eq!(group_name, ".");
+            assert_eq!(service_name, "api");
+            assert_eq!(hostname, "api.myshop.test");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parses_config_add_svc_custom_domain_with_tls() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "svc",
+        "custom-domain",
+        "shop",
+        ".",
+        "auth",
+        "login.mycompany.dev",
+        "--cert",
+        "/certs/login.pem",
+        "--key",
+        "/certs/login-key.pem",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Svc {
+                            cmd:
+                                AddSvcCommand::CustomDomain {
+                                    hostname,
+                                    cert,
+                                    key,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(hostname, "login.mycompany.dev");
+            assert_eq!(cert.as_deref(), Some("/certs/login.pem"));
+            assert_eq!(key.as_deref(), Some("/certs/login-key.pem"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+
+    // A certificate is useless without its key.
+    assert!(
+        Cli::try_parse_from([
+            "darp",
+            "config",
+            "add",
+            "svc",
+            "custom-domain",
+            "shop",
+            ".",
+            "auth",
+            "login.mycompany.dev",
+            "--cert",
+            "/certs/login.pem",
+        ])
+        .is_err()
+    );
+}
+
+#[test]
+fn parses_config_svc_wizard_with_group() {
+    let cli = Cli::try_parse_from([
+        "darp", "config", "svc", "wizard", "-g", "laravel", "shop", "admin",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Svc {
+                    cmd:
+                        ConfigSvcCommand::Wizard {
+                            domain_name,
+                            service_name,
+                            group_name,
+                            location,
+                        },
+                },
+        }) => {
+            assert_eq!(domain_name, "shop");
+            assert_eq!(service_name, "admin");
+            assert_eq!(group_name, "laravel");
+            assert!(location.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parses_config_set_container_name() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "container-name",
+        "dev-{service}-{domain}",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd: SetCommand::ContainerName { template },
+                },
+        }) => assert_eq!(template, "dev-{service}-{domain}"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parses_config_set_svc_host_port() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "svc",
+        "host-port",
+        "shop",
+        ".",
+        "web",
+        "3000",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd:
+                                SetSvcCommand::HostPort {
+                                    domain_name,
+                                    group_name,
+                                    service_name,
+                                    port,
+                                    location,
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(domain_name, "shop");
+            assert_eq!(group_name, ".");
+            assert_eq!(service_name, "web");
+            assert_eq!(port, 3000);
+            assert!(location.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn serve_prefix_flag_is_foreground_only() {
+    let cli = Cli::try_parse_from(["darp", "serve", "--prefix"]).unwrap();
+    match cli.command {
+        Some(Command::Serve { prefix, detach, .. }) => {
+            assert!(prefix);
+            assert!(!detach);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["darp", "serve", "--prefix", "-d"]).is_err());
+}
+
+#[test]
+fn deploy_takes_verify() {
+    let cli = Cli::try_parse_from(["darp", "deploy", "--verify"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Deploy { verify: true, .. })
+    ));
+    let cli = Cli::try_parse_from(["darp", "deploy"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Deploy { verify: false, .. })
+    ));
+}
+
+#[test]
+fn config_rollback_defaults_to_the_newest_backup() {
+    let cli = Cli::try_parse_from(["darp", "config", "rollback"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Rollback { n: 1, list: false }
+        })
+    ));
+    let cli = Cli::try_parse_from(["darp", "config", "rollback", "3"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Rollback { n: 3, .. }
+        })
+    ));
+    let cli = Cli::try_parse_from(["darp", "config", "rollback", "--list"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Rollback { list: true, .. }
+        })
+    ));
+}
+
+#[test]
+fn parses_config_set_state_dir() {
+    let cli =
+        Cli::try_parse_from(["darp", "config", "set", "state-dir", "~/.local/state/darp"]).unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd: SetCommand::StateDir { path },
+                },
+        }) => assert_eq!(path, "~/.local/state/darp"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn serve_and_shell_take_print_plan() {
+    let cli = Cli::try_parse_from(["darp", "serve", "--print-plan"]).unwrap();
+    match cli.command {
+        Some(Command::Serve { run, .. }) => assert!(run.print_plan && !run.dry_run),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    let cli = Cli::try_parse_from(["darp", "shell", "--print-plan", "node:22"]).unwrap();
+    match cli.command {
+        Some(Command::Shell { run, .. }) => {
+            assert!(run.print_plan);
+            assert_eq!(run.container_image.as_deref(), Some("node:22"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["darp", "serve", "--print-plan", "--dry-run"]).is_err());
+}
+
+#[test]
+fn path_flags_parse_before_and_after_the_subcommand() {
+    let cli = Cli::try_parse_from(["darp", "--darp-root", "/tmp/x", "deploy"]).unwrap();
+    assert_eq!(cli.paths.darp_root.as_deref(), Some("/tmp/x"));
+    assert!(matches!(cli.command, Some(Command::Deploy { .. })));
+
+    let cli = Cli::try_parse_from(["darp", "urls", "--config", "work.json"]).unwrap();
+    assert_eq!(cli.paths.config.as_deref(), Some("work.json"));
+}
+
+#[test]
+fn path_args_scan_finds_flags_and_leading_count() {
+    let args = |a: &[&str]| a.iter().map(std::ffi::OsString::from).collect::<Vec<_>>();
+
+    let (paths, leading) =
+        PathArgs::scan(&args(&["darp", "--darp-root", "/tmp/x", "s", "-e", "dev"]));
+    assert_eq!(paths.darp_root.as_deref(), Some("/tmp/x"));
+    assert_eq!(leading, 2);
+
+    let (paths, leading) = PathArgs::scan(&args(&[
+        "darp",
+        "--config=a.json",
+        "deploy",
+        "--darp-root",
+        "r",
+    ]));
+    assert_eq!(paths.config.as_deref(), Some("a.json"));
+    assert_eq!(paths.darp_root.as_deref(), Some("r"));
+    assert_eq!(leading, 1);
+
+    // Nothing after `--` is a darp flag.
+    let (paths, leading) = PathArgs::scan(&args(&["darp", "serve", "--", "--config", "x"]));
+    assert_eq!(paths, PathArgs::default());
+    assert_eq!(leading, 0);
+}
+
+#[test]
+fn shell_takes_persist() {
+    let cli = Cli::try_parse_from(["darp", "shell", "--persist", "-e", "node"]).unwrap();
+    match cli.command {
+        Some(Command::Shell { run, persist }) => {
+            assert!(persist);
+            assert_eq!(run.environment.as_deref(), Some("node"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn completion_takes_a_shell() {
+    let cli = Cli::try_parse_from(["darp", "completion", "zsh"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Completion {
+            shell: clap_complete::Shell::Zsh
+        })
+    ));
+    assert!(Cli::try_parse_from(["darp", "completion", "tcsh"]).is_err());
+}
+
+#[test]
+fn parses_config_set_svc_shell_image() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "svc",
+        "shell-image",
+        "shop",
+        ".",
+        "api",
+        "php:8.3-dev",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd:
+                                SetSvcCommand::ShellImage {
+                                    service_name,
+                                    image,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(service_name, "api");
+            assert_eq!(image, "php:8.3-dev");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parses_config_set_svc_image_build() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "svc",
+        "image-build",
+        "shop",
+        ".",
+        "api",
+        "php:8.3-cli",
+        "--package",
+        "git",
+        "--package",
+        "unzip",
+        "--run",
+        "pecl install xdebug",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd:
+                                SetSvcCommand::ImageBuild {
+                                    base,
+                                    packages,
+                                    run,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(base, "php:8.3-cli");
+            assert_eq!(packages, ["git", "unzip"]);
+            assert_eq!(run, ["pecl install xdebug"]);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn deploy_takes_skip_missing() {
+    let cli = Cli::try_parse_from(["darp", "deploy", "--skip-missing"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Deploy {
+            skip_missing: true,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn parses_config_move_domain() {
+    let cli =
+        Cli::try_parse_from(["darp", "config", "move", "domain", "shop", "~/code/shop"]).unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Move {
+                    cmd:
+                        MoveCommand::Domain {
+                            name,
+                            new_location,
+                            no_deploy,
+                        },
+                },
+        }) => {
+            assert_eq!(name, "shop");
+            assert_eq!(new_location, "~/code/shop");
+            assert!(!no_deploy);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn verbose_counts_and_quiet_are_global() {
+    let cli = Cli::try_parse_from(["darp", "-vv", "ps"]).unwrap();
+    assert_eq!(cli.log.verbose, 2);
+    let cli = Cli::try_parse_from(["darp", "deploy", "-q"]).unwrap();
+    assert!(cli.log.quiet);
+    assert!(Cli::try_parse_from(["darp", "-v", "-q", "ps"]).is_err());
+}
+
+#[test]
+fn output_format_is_global_and_defaults_to_text() {
+    let cli = Cli::try_parse_from(["darp", "ps"]).unwrap();
+    assert_eq!(cli.output, OutputFormat::Text);
+    let cli = Cli::try_parse_from(["darp", "--output", "json", "doctor"]).unwrap();
+    assert_eq!(cli.output, OutputFormat::Json);
+    let cli = Cli::try_parse_from(["darp", "secret", "list", "--output=json"]).unwrap();
+    assert_eq!(cli.output, OutputFormat::Json);
+    assert!(Cli::try_parse_from(["darp", "--output", "yaml", "ps"]).is_err());
+}
+
+#[test]
+fn export_writes_to_a_directory_with_output_dir() {
+    let cli = Cli::try_parse_from(["darp", "export", "systemd", "api", "-o", "."]).unwrap();
+    match cli.command {
+        Some(Command::Export {
+            cmd: ExportCommand::Systemd(args),
+        }) => assert_eq!(args.output_dir.as_deref(), Some(".")),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_set_shared_host_takes_user_and_port_base() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "shared-host",
+        "alice",
+        "20000",
+        "--proxy-dir",
+        "/srv/darp-proxy",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::SharedHost {
+                            user,
+                            port_base,
+                            proxy_dir,
+                        },
+                },
+        }) => {
+            assert_eq!(user, "alice");
+            assert_eq!(port_base, 20000);
+            assert_eq!(proxy_dir.as_deref(), Some("/srv/darp-proxy"));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(
+        Cli::try_parse_from(["darp", "config", "set", "shared-host", "alice", "99999"]).is_err()
+    );
+    assert!(Cli::try_parse_from(["darp", "config", "rm", "shared-host"]).is_ok());
+}
+
+#[test]
+fn check_takes_no_arguments() {
+    let cli = Cli::try_parse_from(["darp", "check"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Check)));
+    assert!(Cli::try_parse_from(["darp", "check", "extra"]).is_err());
+}
+
+#[test]
+fn yes_is_global() {
+    assert!(!Cli::try_parse_from(["darp", "install"]).unwrap().yes);
+    assert!(Cli::try_parse_from(["darp", "-y", "install"]).unwrap().yes);
+    assert!(
+        Cli::try_parse_from(["darp", "uninstall", "--yes"])
+            .unwrap()
+            .yes
+    );
+    assert!(Cli::try_parse_from(["darp", "deploy", "-y"]).unwrap().yes);
+}
+
+#[test]
+fn engine_concurrency_can_be_set_and_removed() {
+    let cli = Cli::try_parse_from(["darp", "config", "set", "engine-concurrency", "2"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Set {
+                cmd: SetCommand::EngineConcurrency { limit: 2 }
+            }
+        })
+    ));
+    assert!(Cli::try_parse_from(["darp", "config", "rm", "engine-concurrency"]).is_ok());
+    assert!(Cli::try_parse_from(["darp", "config", "set", "engine-concurrency", "-1"]).is_err());
+}
+
+#[test]
+fn timings_is_global() {
+    let cli = Cli::try_parse_from(["darp", "deploy", "--timings"]).unwrap();
+    assert!(cli.timings);
+    assert!(!Cli::try_parse_from(["darp", "deploy"]).unwrap().timings);
+}
+
+#[test]
+fn export_compose_takes_a_domain() {
+    let cli = Cli::try_parse_from(["darp", "export", "compose", "shop", "-e", "node"]).unwrap();
+    match cli.command {
+        Some(Command::Export {
+            cmd: ExportCommand::Compose(args),
+        }) => {
+            assert_eq!(args.domain, "shop");
+            assert_eq!(args.environment.as_deref(), Some("node"));
+            assert_eq!(args.output_dir, None);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["darp", "export", "compose"]).is_err());
+}
+
+#[test]
+fn urls_direct_excludes_other_formats() {
+    let cli = Cli::try_parse_from(["darp", "urls", "shop", "--direct"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Urls { direct: true, .. })
+    ));
+    assert!(Cli::try_parse_from(["darp", "urls", "--direct", "--json"]).is_err());
+    assert!(Cli::try_parse_from(["darp", "urls", "--direct", "--plain"]).is_err());
+}
+
+#[test]
+fn proxy_reload_restart_and_status_parse() {
+    for (arg, expected) in [
+        ("reload", "Reload"),
+        ("restart", "Restart"),
+        ("status", "Status"),
+    ] {
+        let cli = Cli::try_parse_from(["darp", "proxy", arg]).unwrap();
+        match cli.command {
+            Some(Command::Proxy { cmd }) => assert_eq!(format!("{:?}", cmd), expected),
+            other => panic!("unexpected parse: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn next_parses() {
+    let cli = Cli::try_parse_from(["darp", "next"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Next)));
+}
+
+#[test]
+fn envrc_parses_domain_and_remove() {
+    let cli = Cli::try_parse_from(["darp", "envrc", "--domain", "shop", "--remove"]).unwrap();
+    match cli.command {
+        Some(Command::Envrc { domain, remove }) => {
+            assert_eq!(domain.as_deref(), Some("shop"));
+            assert!(remove);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn generate_vscode_and_jetbrains_parse() {
+    let cli = Cli::try_parse_from(["darp", "generate", "vscode", "-e", "go"]).unwrap();
+    match cli.command {
+        Some(Command::Generate {
+            cmd: GenerateCommand::Vscode(args),
+        }) => assert_eq!(args.environment.as_deref(), Some("go")),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    let cli = Cli::try_parse_from(["darp", "generate", "jetbrains"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Generate {
+            cmd: GenerateCommand::Jetbrains(_)
+        })
+    ));
+}
+
+#[test]
+fn urls_qr_takes_an_optional_service() {
+    let cli = Cli::try_parse_from(["darp", "urls", "--qr"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Urls { qr: Some(None), .. })
+    ));
+    let cli = Cli::try_parse_from(["darp", "urls", "--qr", "shop.api"]).unwrap();
+    match cli.command {
+        Some(Command::Urls { qr, domain, .. }) => {
+            assert_eq!(qr, Some(Some("shop.api".to_string())));
+            assert_eq!(domain, None);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    let cli = Cli::try_parse_from(["darp", "urls", "shop", "--qr"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Urls {
+            qr: Some(None),
+            domain: Some(_),
+            ..
+        })
+    ));
+    assert!(Cli::try_parse_from(["darp", "urls", "--qr", "--json"]).is_err());
+}
+
+#[test]
+fn mdns_and_config_set_mdns_parse() {
+    let cli = Cli::try_parse_from(["darp", "mdns"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::Mdns)));
+    let cli = Cli::try_parse_from(["darp", "config", "set", "mdns", "true"]).unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd: ConfigCommand::Set {
+                cmd: SetCommand::Mdns { value },
+            },
+        }) => assert_eq!(value, "true"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn share_parses_service_and_tunnel() {
+    let cli = Cli::try_parse_from(["darp", "share", "shop.api", "--via", "ngrok"]).unwrap();
+    match cli.command {
+        Some(Command::Share { service, via }) => {
+            assert_eq!(service.as_deref(), Some("shop.api"));
+            assert_eq!(via, Some(TunnelKind::Ngrok));
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["darp", "share", "--via", "serveo"]).is_err());
+}
+
+#[test]
+fn svc_default_environment_takes_domain_group_service_and_environment() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "svc",
+        "default-environment",
+        "shop",
+        ".",
+        "api",
+        "go",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd:
+                                SetSvcCommand::DefaultEnvironment {
+                                    group_name,
+                                    service_name,
+                                    default_environment,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(group_name, ".");
+            assert_eq!(service_name, "api");
+            assert_eq!(default_environment, "go");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn dom_default_environment_set_and_rm_parse() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "dom",
+        "default-environment",
+        "shop",
+        "go",
+    ])
+    .unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Set {
+                cmd: SetCommand::Dom {
+                    cmd: SetDomCommand::DefaultEnvironment { .. }
+                }
+            }
+        })
+    ));
+    let cli = Cli::try_parse_from(["darp", "config", "rm", "dom", "default-environment", "shop"])
+        .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Rm {
+                    cmd:
+                        RmCommand::Dom {
+                            cmd: RmDomCommand::DefaultEnvironment { domain_name },
+                        },
+                },
+        }) => assert_eq!(domain_name, "shop"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn parse_config_set_and_rm_engine_overrides() {
+    let cli = Cli::try_parse_from(["darp", "config", "set", "dom", "engine", "client", "docker"])
+        .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Dom {
+                            cmd:
+                                SetDomCommand::Engine {
+                                    domain_name,
+                                    engine,
+                                    location,
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(domain_name, "client");
+            assert_eq!(engine, "docker");
+            assert!(location.is_none());
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    let cli = Cli::try_parse_from(["darp", "config", "set", "env", "engine", "legacy", "podman"])
+        .unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Set {
+                cmd: SetCommand::Env {
+                    cmd: SetEnvCommand::Engine { .. }
+                }
+            }
+        })
+    ));
+    let cli = Cli::try_parse_from(["darp", "config", "rm", "env", "engine", "legacy"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config {
+            cmd: ConfigCommand::Rm {
+                cmd: RmCommand::Env {
+                    cmd: RmEnvCommand::Engine { .. }
+                }
+            }
+        })
+    ));
+    assert!(Cli::try_parse_from(["darp", "config", "rm", "dom", "engine", "client"]).is_ok());
+}
+
+#[test]
+fn parse_config_add_and_rm_engine_arg() {
+    let cli = Cli::try_parse_from(["darp", "config", "add", "engine-arg", "--pull=never"]).unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd: AddCommand::EngineArg { arg },
+                },
+        }) => assert_eq!(arg, "--pull=never"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "rm",
+        "engine-arg",
+        "--security-opt=label=disable",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Rm {
+                    cmd: RmCommand::EngineArg { arg },
+                },
+        }) => assert_eq!(arg, "--security-opt=label=disable"),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_add_svc_run_arg_accepts_a_flag() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "svc",
+        "run-arg",
+        "shop",
+        ".",
+        "api",
+        "--shm-size=1g",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Svc {
+                            cmd:
+                                AddSvcCommand::RunArg {
+                                    service_name, arg, ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(service_name, "api");
+            assert_eq!(arg, "--shm-size=1g");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_set_svc_entrypoint_takes_a_path() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "set",
+        "svc",
+        "entrypoint",
+        "shop",
+        ".",
+        "api",
+        "/busybox/sh",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Set {
+                    cmd:
+                        SetCommand::Svc {
+                            cmd:
+                                SetSvcCommand::Entrypoint {
+                                    service_name, path, ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(service_name, "api");
+            assert_eq!(path, "/busybox/sh");
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn config_add_svc_volume_takes_read_only() {
+    let cli = Cli::try_parse_from([
+        "darp",
+        "config",
+        "add",
+        "svc",
+        "volume",
+        "shop",
+        ".",
+        "api",
+        "/src",
+        "{home}/src",
+        "--read-only",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Config {
+            cmd:
+                ConfigCommand::Add {
+                    cmd:
+                        AddCommand::Svc {
+                            cmd:
+                                AddSvcCommand::Volume {
+                                    container_dir,
+                                    read_only,
+                                    ..
+                                },
+                        },
+                },
+        }) => {
+            assert_eq!(container_dir, "/src");
+            assert!(read_only);
+        }
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
+
+#[test]
+fn volume_rm_needs_a_name() {
+    assert!(Cli::try_parse_from(["darp", "volume", "rm"]).is_err());
+    let cli = Cli::try_parse_from(["darp", "volume", "rm", "api-modules", "npm-cache"]).unwrap();
+    match cli.command {
+        Some(Command::Volume {
+            cmd: VolumeCommand::Rm { names },
+        }) => assert_eq!(names, ["api-modules", "npm-cache"]),
+        other => panic!("unexpected parse: {:?}", other),
+    }
+}
@@ -171,6 +171,63 @@ fn smoke_deploy_and_urls() {
     let _ = run_darp(&bin, root.path(), &["uninstall"]);
 }
 
+#[test]
+#[ignore]
+fn smoke_add_service_after_deploy() {
+    let bin = darp_bin();
+    let root = tempfile::tempdir().unwrap();
+    setup_engine(&bin, root.path());
+
+    let domain_dir = root.path().join("projects");
+    std::fs::create_dir_all(domain_dir.join("hello-world")).unwrap();
+    let output = run_darp(
+        &bin,
+        root.path(),
+        &[
+            "config",
+            "add",
+            "dom",
+            "portmap",
+            "projects",
+            "8080",
+            "80",
+            "-l",
+            &domain_dir.to_string_lossy(),
+        ],
+    );
+    assert!(output.status.success(), "add domain: {}", stderr(&output));
+
+    let output = run_darp(&bin, root.path(), &["deploy"]);
+    assert!(output.status.success(), "deploy: {}", stderr(&output));
+
+    // A folder created after the deploy is registered on its own
+    let service_dir = domain_dir.join("late-arrival");
+    std::fs::create_dir_all(&service_dir).unwrap();
+    let output = run_darp_in(&bin, root.path(), &service_dir, &["add-service"]);
+    assert!(
+        output.status.success(),
+        "add-service: {}{}",
+        stdout(&output),
+        stderr(&output)
+    );
+    assert!(stdout(&output).contains("late-arrival.projects.test"));
+
+    let portmap_content = std::fs::read_to_string(root.path().join("portmap.json")).unwrap();
+    assert!(portmap_content.contains("hello-world"));
+    assert!(portmap_content.contains("late-arrival"));
+    let vhost_content = std::fs::read_to_string(root.path().join("vhost_container.conf")).unwrap();
+    assert!(vhost_content.contains("hello-world.projects.test"));
+    assert!(vhost_content.contains("late-arrival.projects.test"));
+
+    // Running it again is a no-op
+    let output = run_darp_in(&bin, root.path(), &service_dir, &["add-service"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("already registered"));
+
+    // Cleanup
+    let _ = run_darp(&bin, root.path(), &["uninstall"]);
+}
+
 #[test]
 #[ignore]
 fn smoke_doctor() {
@@ -249,6 +306,11 @@ fn smoke_shell_dry_run() {
         "dry-run should contain engine command: {}",
         out
     );
+    assert!(
+        out.contains("darp.managed=true") && out.contains("darp.service=myapp"),
+        "dry-run should label the container: {}",
+        out
+    );
 
     // Cleanup
     let _ = run_darp(&bin, root.path(), &["uninstall"]);
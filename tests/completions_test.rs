@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+
+use std::collections::BTreeMap;
+
+use clap_complete::Shell;
+use darp::commands::{RcTarget, completion_script, default_rc_path_with, with_rc_block};
+
+fn env_of<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    move |name| {
+        vars.iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.to_string())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// default_rc_path_with
+// ---------------------------------------------------------------------------
+
+#[test]
+fn rc_defaults_to_home_dotfiles() {
+    let home = Path::new("/home/me");
+    assert_eq!(
+        default_rc_path_with("zsh", home, env_of(&[])),
+        Some(PathBuf::from("/home/me/.zshrc"))
+    );
+    assert_eq!(
+        default_rc_path_with("bash", home, env_of(&[])),
+        Some(PathBuf::from("/home/me/.bashrc"))
+    );
+}
+
+#[test]
+fn rc_respects_zdotdir_and_bash_env() {
+    let home = Path::new("/home/me");
+    let vars = [
+        ("ZDOTDIR", "/home/me/.config/zsh"),
+        ("BASH_ENV", "/home/me/dotfiles/bashenv"),
+    ];
+    assert_eq!(
+        default_rc_path_with("zsh", home, env_of(&vars)),
+        Some(PathBuf::from("/home/me/.config/zsh/.zshrc"))
+    );
+    assert_eq!(
+        default_rc_path_with("bash", home, env_of(&vars)),
+        Some(PathBuf::from("/home/me/dotfiles/bashenv"))
+    );
+}
+
+#[test]
+fn rc_ignores_empty_vars_and_fish() {
+    let home = Path::new("/home/me");
+    assert_eq!(
+        default_rc_path_with("zsh", home, env_of(&[("ZDOTDIR", "")])),
+        Some(PathBuf::from("/home/me/.zshrc"))
+    );
+    assert_eq!(default_rc_path_with("fish", home, env_of(&[])), None);
+}
+
+// ---------------------------------------------------------------------------
+// RcTarget::from_flags
+// ---------------------------------------------------------------------------
+
+#[test]
+fn rc_target_from_flags() {
+    assert_eq!(RcTarget::from_flags(None, false), RcTarget::Default);
+    assert_eq!(RcTarget::from_flags(None, true), RcTarget::Skip);
+    assert_eq!(
+        RcTarget::from_flags(Some("~/dotfiles/zsh/darp.zsh".into()), false),
+        RcTarget::File(PathBuf::from("~/dotfiles/zsh/darp.zsh"))
+    );
+}
+
+// ---------------------------------------------------------------------------
+// with_rc_block
+// ---------------------------------------------------------------------------
+
+const START: &str = "# >>> darp completion start >>>";
+const END: &str = "# <<< darp completion end <<<";
+
+#[test]
+fn rc_block_is_appended_once() {
+    let first = with_rc_block("export A=1", "source darp");
+    assert_eq!(first, format!("export A=1\n{START}\nsource darp\n{END}\n"));
+    assert_eq!(with_rc_block(&first, "source darp"), first);
+}
+
+#[test]
+fn stale_rc_block_is_replaced_in_place() {
+    let old = format!("export A=1\n{START}\nold body\n{END}\nexport B=2\n");
+    assert_eq!(
+        with_rc_block(&old, "new body"),
+        format!("export A=1\n{START}\nnew body\n{END}\nexport B=2\n")
+    );
+}
+
+#[test]
+fn rc_block_without_end_marker_is_rebuilt() {
+    let broken = format!("export A=1\n{START}\nhalf a block\n");
+    assert_eq!(
+        with_rc_block(&broken, "body"),
+        format!("export A=1\n{START}\nbody\n{END}\n")
+    );
+}
+
+// ---------------------------------------------------------------------------
+// completion_script
+// ---------------------------------------------------------------------------
+
+#[test]
+fn completion_script_is_generated_for_every_shell() {
+    for shell in [
+        Shell::Bash,
+        Shell::Zsh,
+        Shell::Fish,
+        Shell::PowerShell,
+        Shell::Elvish,
+    ] {
+        let script = String::from_utf8(completion_script(shell, &BTreeMap::new())).unwrap();
+        assert!(script.contains("darp"), "{:?}", shell);
+        assert!(script.contains("deploy"), "{:?}", shell);
+    }
+}
+
+#[test]
+fn completion_script_completes_aliases() {
+    let aliases = BTreeMap::from([("sv".to_string(), "serve -e dev".to_string())]);
+    let script = String::from_utf8(completion_script(Shell::Fish, &aliases)).unwrap();
+    assert!(script.contains("sv"));
+}
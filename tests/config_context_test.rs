@@ -6,7 +6,9 @@ use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-use darp::config::{Config, DarpPaths, Environment, Group, Service, read_json, resolve_location};
+use darp::config::{
+    Config, DarpPaths, Environment, Group, Service, absolute_dir, read_json, resolve_location,
+};
 
 /// Mutex to serialize tests that change cwd.
 static CWD_LOCK: Mutex<()> = Mutex::new(());
@@ -30,7 +32,7 @@ fn darp_paths_from_env_uses_darp_root() {
         std::env::remove_var("DARP_ROOT");
     }
 
-    assert_eq!(paths._darp_root, dir);
+    assert_eq!(paths.darp_root, dir);
     assert_eq!(paths.config_path, dir.join("config.json"));
     assert_eq!(paths.portmap_path, dir.join("portmap.json"));
     assert_eq!(paths.dnsmasq_dir, dir.join("dnsmasq.d"));
@@ -38,6 +40,107 @@ fn darp_paths_from_env_uses_darp_root() {
     let _ = std::fs::remove_dir_all(&dir);
 }
 
+#[test]
+fn darp_paths_follow_state_dir_from_config() {
+    let dir = std::env::temp_dir().join("darp_test_paths_state_dir");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("config.json"), r#"{"state_dir": "state"}"#).unwrap();
+
+    unsafe {
+        std::env::set_var("DARP_ROOT", &dir);
+    }
+    let paths = DarpPaths::from_env().unwrap();
+    unsafe {
+        std::env::remove_var("DARP_ROOT");
+    }
+
+    assert_eq!(paths.config_path, dir.join("config.json"));
+    assert_eq!(paths.portmap_path, dir.join("state").join("portmap.json"));
+    assert_eq!(paths.backups_dir, dir.join("state").join("backups"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn darp_paths_from_args_override_the_environment() {
+    let dir = std::env::temp_dir().join("darp_test_paths_from_args");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let root = dir.join("root");
+    let elsewhere = dir.join("elsewhere");
+
+    unsafe {
+        std::env::set_var("DARP_STATE_DIR", &elsewhere);
+    }
+    let paths = DarpPaths::from_args(root.to_str(), None).unwrap();
+    let with_config = DarpPaths::from_args(root.to_str(), dir.join("work.json").to_str()).unwrap();
+    unsafe {
+        std::env::remove_var("DARP_STATE_DIR");
+    }
+
+    // --darp-root isolates the run from DARP_STATE_DIR as well.
+    assert_eq!(paths.config_path, root.join("config.json"));
+    assert_eq!(paths.portmap_path, root.join("portmap.json"));
+    assert_eq!(with_config.config_path, dir.join("work.json"));
+    assert_eq!(with_config.portmap_path, root.join("portmap.json"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn darp_paths_new_keeps_only_config_in_config_dir() {
+    let paths = DarpPaths::new(
+        PathBuf::from("/dotfiles/darp/config.json"),
+        PathBuf::from("/state/darp"),
+    );
+    assert_eq!(
+        paths.config_path,
+        PathBuf::from("/dotfiles/darp/config.json")
+    );
+    assert_eq!(paths.darp_root, PathBuf::from("/state/darp"));
+    assert_eq!(
+        paths.secrets_path,
+        PathBuf::from("/state/darp/secrets.json")
+    );
+    assert_eq!(paths.backups_dir, PathBuf::from("/state/darp/backups"));
+    assert_eq!(
+        paths.services_index_path,
+        PathBuf::from("/state/darp/www/services.json")
+    );
+}
+
+#[test]
+fn absolute_dir_expands_home_and_joins_relative_paths() {
+    let home = dirs::home_dir().unwrap();
+    let base = std::path::Path::new("/base");
+    assert_eq!(absolute_dir("~", base).unwrap(), home);
+    assert_eq!(
+        absolute_dir("~/.local/state/darp", base).unwrap(),
+        home.join(".local/state/darp")
+    );
+    assert_eq!(
+        absolute_dir("{home}/darp", base).unwrap(),
+        home.join("darp")
+    );
+    assert_eq!(absolute_dir("state", base).unwrap(), base.join("state"));
+    assert_eq!(
+        absolute_dir("/var/darp", base).unwrap(),
+        PathBuf::from("/var/darp")
+    );
+}
+
+#[test]
+fn state_dir_set_and_rm() {
+    let mut c = Config::default();
+    assert!(c.set_state_dir("  ").is_err());
+    c.set_state_dir("~/.local/state/darp").unwrap();
+    assert_eq!(c.state_dir.as_deref(), Some("~/.local/state/darp"));
+    c.rm_state_dir().unwrap();
+    assert!(c.state_dir.is_none());
+    assert!(c.rm_state_dir().is_err());
+}
+
 // ---------------------------------------------------------------------------
 // read_json
 // ---------------------------------------------------------------------------
@@ -189,8 +292,10 @@ fn find_context_grandparent_is_domain() {
         .unwrap();
 
     // Add a "backend" group
-    let mut backend_group = Group::default();
-    backend_group.serve_command = Some("cargo run".into());
+    let backend_group = Group {
+        serve_command: Some("cargo run".into()),
+        ..Default::default()
+    };
     let mut groups = BTreeMap::new();
     groups.insert("backend".to_string(), backend_group);
     config
@@ -211,6 +316,28 @@ fn find_context_grandparent_is_domain() {
     let _ = std::fs::remove_dir_all(&base);
 }
 
+#[test]
+fn service_context_for_dir_from_a_subfolder_targets_the_service_folder() {
+    let base = std::env::temp_dir().join("darp_test_ctx_subfolder");
+    let _ = std::fs::remove_dir_all(&base);
+    let domain_dir = base.join("projects");
+    let src_dir = domain_dir.join("myapp").join("src");
+    std::fs::create_dir_all(&src_dir).unwrap();
+
+    let mut config = Config::default();
+    config
+        .add_domain("projects", &domain_dir.to_string_lossy())
+        .unwrap();
+
+    let ctx = config.service_context_for_dir(src_dir, None).unwrap();
+    assert_eq!(ctx.domain_name, "projects");
+    assert_eq!(ctx.group_name, ".");
+    assert_eq!(ctx.current_directory_name, "myapp");
+    assert_eq!(ctx.current_dir, domain_dir.join("myapp"));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
 #[test]
 fn find_context_no_matching_domain() {
     let dir = std::env::temp_dir().join("darp_test_ctx_nomatch");
@@ -223,6 +350,59 @@ fn find_context_no_matching_domain() {
     let _ = std::fs::remove_dir_all(&dir);
 }
 
+// ---------------------------------------------------------------------------
+// unmapped_dir_error — explains why a directory is not a service folder
+// ---------------------------------------------------------------------------
+
+#[test]
+fn unmapped_dir_error_without_domains_suggests_creating_one() {
+    let msg = Config::default()
+        .unmapped_dir_error(&std::env::temp_dir())
+        .to_string();
+    assert!(msg.contains("No domains are configured"), "{}", msg);
+}
+
+#[test]
+fn unmapped_dir_error_explains_domain_and_group_folders() {
+    let base = std::env::temp_dir().join("darp_test_unmapped");
+    let _ = std::fs::remove_dir_all(&base);
+    let domain_dir = base.join("projects");
+    let group_dir = domain_dir.join("backend");
+    std::fs::create_dir_all(group_dir.join("api")).unwrap();
+
+    let mut config = Config::default();
+    config
+        .add_domain("projects", &domain_dir.to_string_lossy())
+        .unwrap();
+    config
+        .set_service_description("projects", "backend", "api", "API")
+        .unwrap();
+
+    let msg = config.unmapped_dir_error(&domain_dir).to_string();
+    assert!(
+        msg.contains("is the folder of domain 'projects'"),
+        "{}",
+        msg
+    );
+
+    let msg = config.unmapped_dir_error(&group_dir).to_string();
+    assert!(
+        msg.contains("folder of group 'backend' in domain 'projects'"),
+        "{}",
+        msg
+    );
+
+    let msg = config.unmapped_dir_error(&base).to_string();
+    assert!(
+        msg.contains("does not exist in any darp domain configuration"),
+        "{}",
+        msg
+    );
+    assert!(msg.contains("  projects ("), "{}", msg);
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
 // ---------------------------------------------------------------------------
 // service_context_from_cwd — uses real cwd, so we chdir into a temp dir
 // ---------------------------------------------------------------------------
@@ -370,8 +550,10 @@ fn service_context_from_cwd_service_default_env_beats_group_and_domain() {
     domain.default_environment = Some("dom-env".to_string());
 
     // "." group with default = grp-env, and a service "myapp" with default = svc-env
-    let mut dot_group = Group::default();
-    dot_group.default_environment = Some("grp-env".to_string());
+    let mut dot_group = Group {
+        default_environment: Some("grp-env".to_string()),
+        ..Default::default()
+    };
     let mut services = BTreeMap::new();
     services.insert(
         "myapp".to_string(),
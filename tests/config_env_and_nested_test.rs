@@ -1,4 +1,4 @@
-use darp::config::Config;
+use darp::config::{Config, ResourceLimits};
 
 fn config_with_domain(name: &str, location: &str) -> Config {
     let mut c = Config::default();
@@ -151,7 +151,7 @@ fn env_variable_lifecycle() {
 #[test]
 fn env_volume_lifecycle() {
     let mut c = Config::default();
-    c.add_volume("go", "/cache", "/host/cache").unwrap();
+    c.add_volume("go", "/cache", "/host/cache", false).unwrap();
 
     let vols = c.environments.as_ref().unwrap()["go"]
         .volumes
@@ -160,7 +160,7 @@ fn env_volume_lifecycle() {
     assert_eq!(vols.len(), 1);
 
     // exact duplicate rejected
-    assert!(c.add_volume("go", "/cache", "/host/cache").is_err());
+    assert!(c.add_volume("go", "/cache", "/host/cache", false).is_err());
 
     c.rm_volume("go", "/cache", "/host/cache").unwrap();
     assert!(c.rm_volume("go", "/cache", "/host/cache").is_err());
@@ -304,7 +304,8 @@ fn rm_group_variable_lifecycle() {
 #[test]
 fn rm_group_volume_lifecycle() {
     let mut c = config_with_domain("d", "/tmp/d");
-    c.add_group_volume("d", "g", "/data", "/host/data").unwrap();
+    c.add_group_volume("d", "g", "/data", "/host/data", false)
+        .unwrap();
     c.rm_group_volume("d", "g", "/data", "/host/data").unwrap();
     assert!(c.rm_group_volume("d", "g", "/data", "/host/data").is_err());
 }
@@ -339,11 +340,11 @@ fn service_variable_lifecycle() {
 #[test]
 fn service_volume_lifecycle() {
     let mut c = config_with_domain("d", "/tmp/d");
-    c.add_service_volume("d", "g", "svc", "/app", "/host/app")
+    c.add_service_volume("d", "g", "svc", "/app", "/host/app", false)
         .unwrap();
 
     assert!(
-        c.add_service_volume("d", "g", "svc", "/app", "/host/app")
+        c.add_service_volume("d", "g", "svc", "/app", "/host/app", false)
             .is_err()
     );
 
@@ -427,3 +428,406 @@ fn service_default_container_image_lifecycle() {
             .is_err()
     );
 }
+
+#[test]
+fn env_limits_merge_per_key_and_rm() {
+    let mut c = Config::default();
+    assert!(
+        c.set_environment_limits("jvm", &ResourceLimits::default())
+            .is_err()
+    );
+    c.set_serve_command("jvm", "gradle bootRun").unwrap(); // create env
+    assert!(
+        c.set_environment_limits("jvm", &ResourceLimits::default())
+            .is_err()
+    );
+
+    c.set_environment_limits(
+        "jvm",
+        &ResourceLimits {
+            cpus: Some("2".into()),
+            memory: None,
+        },
+    )
+    .unwrap();
+    c.set_environment_limits(
+        "jvm",
+        &ResourceLimits {
+            cpus: None,
+            memory: Some("2g".into()),
+        },
+    )
+    .unwrap();
+
+    let limits = c.environments.as_ref().unwrap()["jvm"]
+        .limits
+        .clone()
+        .unwrap();
+    assert_eq!(limits.cpus.as_deref(), Some("2"));
+    assert_eq!(limits.memory.as_deref(), Some("2g"));
+
+    c.rm_environment_limits("jvm").unwrap();
+    assert!(c.rm_environment_limits("jvm").is_err());
+}
+
+#[test]
+fn service_limits_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_limits(
+        "d",
+        "g",
+        "svc",
+        &ResourceLimits {
+            cpus: Some("1.5".into()),
+            memory: Some("512m".into()),
+        },
+    )
+    .unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(svc.limits.as_ref().unwrap().cpus.as_deref(), Some("1.5"));
+
+    c.rm_service_limits("d", "g", "svc").unwrap();
+    assert!(c.rm_service_limits("d", "g", "svc").is_err());
+}
+
+#[test]
+fn service_env_file_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_env_file("d", "g", "svc", ".env").unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(svc.env_file.as_deref(), Some(".env"));
+
+    c.rm_service_env_file("d", "g", "svc").unwrap();
+    assert!(c.rm_service_env_file("d", "g", "svc").is_err());
+}
+
+#[test]
+fn service_healthcheck_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_healthcheck("d", "g", "svc", "http://localhost:8000/health")
+        .unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(
+        svc.healthcheck.as_deref(),
+        Some("http://localhost:8000/health")
+    );
+
+    c.rm_service_healthcheck("d", "g", "svc").unwrap();
+    assert!(c.rm_service_healthcheck("d", "g", "svc").is_err());
+}
+
+#[test]
+fn restart_policy_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_serve_command("node", "npm start").unwrap();
+    c.set_environment_restart("node", "unless-stopped").unwrap();
+    c.set_service_restart("d", "g", "svc", "on-failure:3")
+        .unwrap();
+
+    assert_eq!(
+        c.environments.as_ref().unwrap()["node"].restart.as_deref(),
+        Some("unless-stopped")
+    );
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(svc.restart.as_deref(), Some("on-failure:3"));
+
+    c.rm_service_restart("d", "g", "svc").unwrap();
+    assert!(c.rm_service_restart("d", "g", "svc").is_err());
+    c.rm_environment_restart("node").unwrap();
+    assert!(c.rm_environment_restart("node").is_err());
+}
+
+#[test]
+fn service_entrypoint_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_entrypoint("d", "g", "svc", "/busybox/sh")
+        .unwrap();
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(svc.entrypoint.as_deref(), Some("/busybox/sh"));
+
+    c.rm_service_entrypoint("d", "g", "svc").unwrap();
+    let err = c.rm_service_entrypoint("d", "g", "svc").unwrap_err();
+    assert!(err.to_string().contains("has no entrypoint"));
+}
+
+#[test]
+fn map_user_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_serve_command("node", "npm start").unwrap();
+    c.set_environment_map_user("node", true).unwrap();
+    c.set_service_map_user("d", "g", "svc", false).unwrap();
+
+    assert_eq!(
+        c.environments.as_ref().unwrap()["node"].map_user,
+        Some(true)
+    );
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(svc.map_user, Some(false));
+
+    c.rm_service_map_user("d", "g", "svc").unwrap();
+    assert!(c.rm_service_map_user("d", "g", "svc").is_err());
+    c.rm_environment_map_user("node").unwrap();
+    assert!(c.rm_environment_map_user("node").is_err());
+}
+
+#[test]
+fn restart_policy_rejects_unknown_values() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_serve_command("node", "npm start").unwrap();
+    let err = c.set_environment_restart("node", "sometimes").unwrap_err();
+    assert!(err.to_string().contains("invalid restart policy"));
+    assert!(
+        c.set_service_restart("d", "g", "svc", "on-failure:x")
+            .is_err()
+    );
+}
+
+#[test]
+fn serve_hooks_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.add_service_pre_serve("d", "g", "svc", "npm ci").unwrap();
+    // adding the same command again is a no-op
+    c.add_service_pre_serve("d", "g", "svc", "npm ci").unwrap();
+    c.add_service_pre_serve("d", "g", "svc", "php artisan migrate")
+        .unwrap();
+    c.add_service_post_serve("d", "g", "svc", "rm -f /tmp/app.pid")
+        .unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(
+        svc.pre_serve.as_deref(),
+        Some(&["npm ci".to_string(), "php artisan migrate".to_string()][..])
+    );
+
+    c.rm_service_pre_serve("d", "g", "svc", "npm ci").unwrap();
+    c.rm_service_pre_serve("d", "g", "svc", "php artisan migrate")
+        .unwrap();
+    c.rm_service_post_serve("d", "g", "svc", "rm -f /tmp/app.pid")
+        .unwrap();
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert!(svc.pre_serve.is_none());
+    assert!(svc.post_serve.is_none());
+
+    let err = c
+        .rm_service_pre_serve("d", "g", "svc", "npm ci")
+        .unwrap_err();
+    assert!(err.to_string().contains("has no pre_serve command"));
+}
+
+#[test]
+fn service_run_args_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.add_service_run_arg("d", "g", "svc", "--shm-size=1g")
+        .unwrap();
+    c.add_service_run_arg("d", "g", "svc", "--device=/dev/fuse")
+        .unwrap();
+    // adding the same argument again is a no-op
+    c.add_service_run_arg("d", "g", "svc", "--shm-size=1g")
+        .unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(
+        svc.run_args.as_deref(),
+        Some(
+            &[
+                "--shm-size=1g".to_string(),
+                "--device=/dev/fuse".to_string()
+            ][..]
+        )
+    );
+
+    c.rm_service_run_arg("d", "g", "svc", "--shm-size=1g")
+        .unwrap();
+    c.rm_service_run_arg("d", "g", "svc", "--device=/dev/fuse")
+        .unwrap();
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert!(svc.run_args.is_none());
+
+    let err = c
+        .rm_service_run_arg("d", "g", "svc", "--shm-size=1g")
+        .unwrap_err();
+    assert!(err.to_string().contains("has no run arg"));
+}
+
+#[test]
+fn service_run_args_need_a_flag_and_its_value_together() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    let err = c
+        .add_service_run_arg("d", "g", "svc", "--device")
+        .unwrap_err();
+    assert!(err.to_string().contains("'--device=<value>'"), "{}", err);
+    assert!(c.add_service_run_arg("d", "g", "svc", "/dev/fuse").is_err());
+    c.add_service_run_arg("d", "g", "svc", "--init").unwrap();
+    c.add_service_run_arg("d", "g", "svc", "--device=/dev/fuse")
+        .unwrap();
+}
+
+#[test]
+fn service_description_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_description("d", "g", "svc", "Public REST API")
+        .unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(svc.description.as_deref(), Some("Public REST API"));
+
+    c.rm_service_description("d", "g", "svc").unwrap();
+    assert!(c.rm_service_description("d", "g", "svc").is_err());
+}
+
+#[test]
+fn service_tag_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.add_service_tag("d", "g", "svc", "backend").unwrap();
+    c.add_service_tag("d", "g", "svc", "api").unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["svc"];
+    assert_eq!(
+        svc.tags.as_deref(),
+        Some(&["backend".to_string(), "api".to_string()][..])
+    );
+
+    c.rm_service_tag("d", "g", "svc", "backend").unwrap();
+    assert!(c.rm_service_tag("d", "g", "svc", "backend").is_err());
+}
+
+#[test]
+fn filter_portmap_by_tags_keeps_only_tagged_services() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.add_service_tag("d", ".", "api", "backend").unwrap();
+    c.add_service_tag("d", "web", "site", "frontend").unwrap();
+
+    let portmap = serde_json::json!({
+        "d": {
+            ".": { "api": { "port": 50100 }, "worker": { "port": 50101 } },
+            "web": { "site": { "port": 50102 } }
+        }
+    });
+
+    let backend = c.filter_portmap_by_tags(&portmap, &["backend".to_string()]);
+    assert_eq!(
+        backend,
+        serde_json::json!({ "d": { ".": { "api": { "port": 50100 } } } })
+    );
+
+    let none = c.filter_portmap_by_tags(&portmap, &["missing".to_string()]);
+    assert_eq!(none, serde_json::json!({}));
+
+    assert_eq!(c.filter_portmap_by_tags(&portmap, &[]), portmap);
+}
+
+#[test]
+fn service_start_order_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_start_order("d", "g", "db", -5).unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["g"]
+        .services
+        .as_ref()
+        .unwrap()["db"];
+    assert_eq!(svc.start_order, Some(-5));
+
+    c.rm_service_start_order("d", "g", "db").unwrap();
+    assert!(c.rm_service_start_order("d", "g", "db").is_err());
+}
+
+#[test]
+fn with_unassigned_services_adds_null_entries_for_unported_services() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_description("d", ".", "api", "API").unwrap();
+    c.set_service_description("d", "web", "site", "Site")
+        .unwrap();
+
+    let portmap = serde_json::json!({ "d": { ".": { "api": { "port": 50100 } } } });
+    assert_eq!(
+        c.with_unassigned_services(&portmap),
+        serde_json::json!({
+            "d": {
+                ".": { "api": { "port": 50100 } },
+                "web": { "site": null }
+            }
+        })
+    );
+}
+
+#[test]
+fn service_live_reload_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_live_reload("d", ".", "web", true).unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["."]
+        .services
+        .as_ref()
+        .unwrap()["web"];
+    assert_eq!(svc.live_reload, Some(true));
+
+    c.rm_service_live_reload("d", ".", "web").unwrap();
+    assert!(c.rm_service_live_reload("d", ".", "web").is_err());
+}
+
+#[test]
+fn service_mirror_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_service_mirror("d", ".", "api", "api-v2").unwrap();
+
+    let svc = &c.domains.as_ref().unwrap()["d"].groups.as_ref().unwrap()["."]
+        .services
+        .as_ref()
+        .unwrap()["api"];
+    assert_eq!(svc.mirror.as_deref(), Some("api-v2"));
+
+    c.rm_service_mirror("d", ".", "api").unwrap();
+    assert!(c.rm_service_mirror("d", ".", "api").is_err());
+}
+
+#[test]
+fn service_protocol_lifecycle() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    assert!(!c.uses_grpc());
+    assert!(c.set_service_protocol("d", ".", "api", "http2").is_err());
+    c.set_service_protocol("d", ".", "api", "grpc").unwrap();
+    assert!(c.uses_grpc());
+
+    c.rm_service_protocol("d", ".", "api").unwrap();
+    assert!(!c.uses_grpc());
+    assert!(c.rm_service_protocol("d", ".", "api").is_err());
+}
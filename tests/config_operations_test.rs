@@ -1,7 +1,11 @@
 use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
-use darp::config::{Config, Environment};
+use darp::config::{Config, Environment, Remote};
+
+/// Mutex to serialize tests that set DOCKER_HOST / CONTAINER_HOST.
+static ENGINE_ENV_LOCK: Mutex<()> = Mutex::new(());
 
 fn config_with_domain(name: &str, location: &str) -> Config {
     let mut config = Config::default();
@@ -155,6 +159,117 @@ fn rm_pre_config_errors_when_empty() {
     assert!(c.rm_pre_config("/anything.json").is_err());
 }
 
+// ---------------------------------------------------------------------------
+// add/rm helper container run args
+// ---------------------------------------------------------------------------
+
+#[test]
+fn helper_run_arg_lifecycle() {
+    let mut c = Config::default();
+    c.add_helper_run_arg("reverse-proxy", "--network=devnet")
+        .unwrap();
+    c.add_helper_run_arg("reverse-proxy", "--memory=128m")
+        .unwrap();
+    assert!(
+        c.add_helper_run_arg("reverse-proxy", "--network=devnet")
+            .is_err()
+    );
+    assert_eq!(
+        c.reverse_proxy.as_ref().unwrap().run_args.as_deref(),
+        Some(&["--network=devnet".to_string(), "--memory=128m".to_string()][..])
+    );
+    assert!(c.dnsmasq.is_none());
+
+    c.rm_helper_run_arg("reverse-proxy", "--network=devnet")
+        .unwrap();
+    c.rm_helper_run_arg("reverse-proxy", "--memory=128m")
+        .unwrap();
+    assert!(c.reverse_proxy.is_none());
+}
+
+#[test]
+fn helper_run_arg_rejects_unknown_helper_and_missing_arg() {
+    let mut c = Config::default();
+    assert!(c.add_helper_run_arg("postgres", "--rm").is_err());
+    assert!(c.rm_helper_run_arg("dnsmasq", "--restart=always").is_err());
+}
+
+#[test]
+fn engine_arg_lifecycle() {
+    let mut c = Config::default();
+    assert!(c.rm_engine_arg("--pull=never").is_err());
+    c.add_engine_arg("--security-opt=label=disable").unwrap();
+    c.add_engine_arg("--pull=never").unwrap();
+    assert!(c.add_engine_arg("--pull=never").is_err());
+    assert_eq!(
+        c.engine_args.as_deref(),
+        Some(
+            &[
+                "--security-opt=label=disable".to_string(),
+                "--pull=never".to_string()
+            ][..]
+        )
+    );
+
+    c.rm_engine_arg("--security-opt=label=disable").unwrap();
+    assert!(c.rm_engine_arg("--security-opt=label=disable").is_err());
+    c.rm_engine_arg("--pull=never").unwrap();
+    assert!(c.engine_args.is_none());
+}
+
+#[test]
+fn engine_args_need_a_flag_and_its_value_together() {
+    let mut c = Config::default();
+    let err = c.add_engine_arg("--security-opt").unwrap_err().to_string();
+    assert!(err.contains("--security-opt=<value>"), "{}", err);
+    assert!(c.add_engine_arg("label=disable").is_err());
+    assert!(c.engine_args.is_none());
+
+    // Flags without a value, and valued ones given as one token, are fine; the same flag
+    // can appear with different values.
+    c.add_engine_arg("--init").unwrap();
+    c.add_engine_arg("--security-opt=label=disable").unwrap();
+    c.add_engine_arg("--security-opt=seccomp=unconfined")
+        .unwrap();
+    assert_eq!(c.engine_args.as_ref().map(Vec::len), Some(3));
+}
+
+#[test]
+fn engine_args_follow_every_runs_own_options() {
+    let c = Config {
+        engine_args: Some(vec!["--pull=never".to_string()]),
+        ..Default::default()
+    };
+    let engine = darp::engine::Engine::new(darp::engine::EngineKind::None, &c).unwrap();
+    for options in [
+        engine.run_options_interactive("shop", "api"),
+        engine.run_options_noninteractive("shop", "api", Some("always"), true),
+        engine.run_options_persistent_shell("shop", "api"),
+    ] {
+        assert_eq!(options.last().map(String::as_str), Some("--pull=never"));
+    }
+}
+
+#[test]
+fn map_user_options_keep_the_id_on_podman_outside_pods() {
+    use darp::engine::{Engine, EngineKind};
+    let c = Config::default();
+    let podman = Engine::new(EngineKind::Podman, &c).unwrap();
+    assert_eq!(
+        podman.map_user_options(1000, 1000, false),
+        ["--userns=keep-id", "--user", "1000:1000"]
+    );
+    assert_eq!(
+        podman.map_user_options(1000, 100, true),
+        ["--user", "1000:100"]
+    );
+    let docker = Engine::new(EngineKind::Docker, &c).unwrap();
+    assert_eq!(
+        docker.map_user_options(501, 20, false),
+        ["--user", "501:20"]
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Domain lifecycle: add, set properties, remove
 // ---------------------------------------------------------------------------
@@ -207,6 +322,32 @@ fn rm_domain_serve_command_errors_when_unset() {
     assert!(c.rm_domain_serve_command("d").is_err());
 }
 
+#[test]
+fn set_and_rm_domain_network() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_domain_network("d", "shop_default").unwrap();
+    assert_eq!(
+        c.domains.as_ref().unwrap()["d"].network.as_deref(),
+        Some("shop_default")
+    );
+
+    c.rm_domain_network("d").unwrap();
+    assert!(c.domains.as_ref().unwrap()["d"].network.is_none());
+    assert!(c.rm_domain_network("d").is_err());
+    assert!(c.set_domain_network("missing", "net").is_err());
+}
+
+#[test]
+fn set_and_rm_domain_pod() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.set_domain_pod("d", true).unwrap();
+    assert_eq!(c.domains.as_ref().unwrap()["d"].pod, Some(true));
+
+    c.rm_domain_pod("d").unwrap();
+    assert!(c.domains.as_ref().unwrap()["d"].pod.is_none());
+    assert!(c.rm_domain_pod("d").is_err());
+}
+
 #[test]
 fn set_domain_default_environment_validates_env_exists() {
     let mut c = config_with_domain("d", "/tmp/d");
@@ -288,7 +429,8 @@ fn add_and_rm_domain_variable() {
 #[test]
 fn add_and_rm_domain_volume() {
     let mut c = config_with_domain("d", "/tmp/d");
-    c.add_domain_volume("d", "/data", "/host/data").unwrap();
+    c.add_domain_volume("d", "/data", "/host/data", false)
+        .unwrap();
 
     let vols = c.domains.as_ref().unwrap()["d"].volumes.as_ref().unwrap();
     assert_eq!(vols.len(), 1);
@@ -296,10 +438,14 @@ fn add_and_rm_domain_volume() {
     assert_eq!(vols[0].host, "/host/data");
 
     // Exact duplicate rejected
-    assert!(c.add_domain_volume("d", "/data", "/host/data").is_err());
+    assert!(
+        c.add_domain_volume("d", "/data", "/host/data", false)
+            .is_err()
+    );
 
     // Different host is OK (same container)
-    c.add_domain_volume("d", "/data", "/other/data").unwrap();
+    c.add_domain_volume("d", "/data", "/other/data", false)
+        .unwrap();
     assert_eq!(
         c.domains.as_ref().unwrap()["d"]
             .volumes
@@ -316,10 +462,26 @@ fn add_and_rm_domain_volume() {
     assert_eq!(vols[0].host, "/other/data");
 }
 
+#[test]
+fn read_only_volumes_are_marked_and_others_serialize_as_before() {
+    let mut c = config_with_domain("d", "/tmp/d");
+    c.add_domain_volume("d", "/src", "/host/src", true).unwrap();
+    c.add_domain_volume("d", "/data", "/host/data", false)
+        .unwrap();
+
+    let vols = c.domains.as_ref().unwrap()["d"].volumes.as_ref().unwrap();
+    assert!(vols[0].read_only);
+    assert!(!vols[1].read_only);
+    let json = serde_json::to_value(vols).unwrap();
+    assert_eq!(json[0]["read_only"], true);
+    assert!(json[1].get("read_only").is_none());
+}
+
 #[test]
 fn rm_domain_volume_errors_when_no_match() {
     let mut c = config_with_domain("d", "/tmp/d");
-    c.add_domain_volume("d", "/data", "/host/data").unwrap();
+    c.add_domain_volume("d", "/data", "/host/data", false)
+        .unwrap();
     assert!(c.rm_domain_volume("d", "/data", "/wrong/host").is_err());
 }
 
@@ -488,6 +650,95 @@ fn save_and_load_round_trip() {
     let _ = std::fs::remove_dir_all(&dir);
 }
 
+#[test]
+fn save_replaces_the_file_without_leaving_temporaries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, "{}").unwrap();
+
+    let mut config = Config::default();
+    config.add_domain("shop", "/tmp/shop").unwrap();
+    config.save(&path).unwrap();
+
+    let names: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names, vec![std::ffi::OsString::from("config.json")]);
+    assert!(
+        Config::load(&path)
+            .unwrap()
+            .domains
+            .unwrap()
+            .contains_key("shop")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn save_writes_through_a_symlinked_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("dotfiles-config.json");
+    let link = dir.path().join("config.json");
+    std::fs::write(&target, "{}").unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let config = Config {
+        engine: Some("podman".to_string()),
+        ..Default::default()
+    };
+    config.save(&link).unwrap();
+
+    assert!(
+        std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert!(std::fs::read_to_string(&target).unwrap().contains("podman"));
+}
+
+#[cfg(unix)]
+#[test]
+fn save_keeps_the_file_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, "{}").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+    Config::default().save(&path).unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn load_errors_on_a_corrupt_config_instead_of_resetting_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, r#"{"engine": "docker", "domains": {"#).unwrap();
+
+    let err = Config::load(&path).unwrap_err().to_string();
+    assert!(err.contains("darp config rollback"), "{}", err);
+    let err = Config::load_merged(&path).unwrap_err().to_string();
+    assert!(err.contains("darp config rollback"), "{}", err);
+    // The damaged file is left for the user to fix or roll back.
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"{"engine": "docker", "domains": {"#
+    );
+}
+
+#[test]
+fn load_errors_on_a_config_of_the_wrong_shape() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, r#"{"domains": []}"#).unwrap();
+    assert!(Config::load(&path).is_err());
+}
+
 // ---------------------------------------------------------------------------
 // `*field` override parsing and validation
 // ---------------------------------------------------------------------------
@@ -619,3 +870,160 @@ fn save_roundtrip_preserves_asterisk_fields() {
 
     let _ = std::fs::remove_dir_all(&dir);
 }
+
+// ---------------------------------------------------------------------------
+// remote
+// ---------------------------------------------------------------------------
+
+fn remote(url: Option<&str>) -> Remote {
+    Remote {
+        host: "me@devbox".into(),
+        address: "192.168.1.20".into(),
+        url: url.map(String::from),
+        resolver: None,
+    }
+}
+
+#[test]
+fn remote_engine_url_defaults_per_engine() {
+    assert_eq!(remote(None).engine_url(false), "ssh://me@devbox");
+    assert_eq!(
+        remote(None).engine_url(true),
+        "ssh://me@devbox/run/podman/podman.sock"
+    );
+}
+
+#[test]
+fn remote_engine_url_override_wins() {
+    let url = "ssh://me@devbox/run/user/1000/podman/podman.sock";
+    assert_eq!(remote(Some(url)).engine_url(true), url);
+    assert_eq!(remote(Some(url)).engine_url(false), url);
+}
+
+#[test]
+fn remote_round_trips_through_json() {
+    let config = Config {
+        remote: Some(remote(None)),
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    assert!(json.contains("\"remote\":{\"host\":\"me@devbox\",\"address\":\"192.168.1.20\"}"));
+    let back: Config = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.remote.unwrap().address, "192.168.1.20");
+}
+
+#[test]
+fn engine_host_urls_name_the_server() {
+    let parse = |url| Remote::parse_engine_host(url);
+    assert_eq!(
+        parse("ssh://me@devbox"),
+        Some(("me@devbox".into(), "devbox".into()))
+    );
+    assert_eq!(
+        parse("ssh://me@devbox:2222/run/podman/podman.sock"),
+        Some(("ssh://me@devbox:2222".into(), "devbox".into()))
+    );
+    assert_eq!(
+        parse("tcp://192.168.1.20:2376"),
+        Some(("192.168.1.20".into(), "192.168.1.20".into()))
+    );
+    assert_eq!(
+        parse("tcp://[fd00::20]:2376"),
+        Some(("fd00::20".into(), "fd00::20".into()))
+    );
+}
+
+#[test]
+fn local_engine_hosts_are_not_remote() {
+    assert_eq!(
+        Remote::parse_engine_host("unix:///var/run/docker.sock"),
+        None
+    );
+    assert_eq!(Remote::parse_engine_host("tcp://localhost:2375"), None);
+    assert_eq!(Remote::parse_engine_host("tcp://127.0.0.1:2375"), None);
+    assert_eq!(Remote::parse_engine_host(""), None);
+}
+
+#[test]
+fn container_host_makes_podman_remote() {
+    let _lock = ENGINE_ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        std::env::set_var("CONTAINER_HOST", "ssh://me@10.0.0.7/run/podman/podman.sock");
+    }
+    let from_env = Remote::from_engine_env(Some("podman"));
+    let no_engine = Remote::from_engine_env(None);
+    unsafe {
+        std::env::remove_var("CONTAINER_HOST");
+    }
+
+    let remote = from_env.unwrap();
+    assert_eq!(remote.host, "me@10.0.0.7");
+    assert_eq!(remote.address, "10.0.0.7");
+    assert_eq!(
+        remote.url.as_deref(),
+        Some("ssh://me@10.0.0.7/run/podman/podman.sock")
+    );
+    assert_eq!(remote.resolver, Some(true));
+    assert!(no_engine.is_none());
+}
+
+#[test]
+fn engine_host_names_are_not_looked_up_when_the_config_loads() {
+    let _lock = ENGINE_ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        std::env::set_var("DOCKER_HOST", "ssh://devbox.invalid");
+    }
+    let from_env = Remote::from_engine_env(Some("docker"));
+    unsafe {
+        std::env::remove_var("DOCKER_HOST");
+    }
+
+    let env_remote = from_env.unwrap();
+    assert_eq!(env_remote.host, "devbox.invalid");
+    assert_eq!(env_remote.address, "devbox.invalid");
+    assert_eq!(env_remote.resolved_address(), "devbox.invalid");
+    assert_eq!(remote(None).resolved_address(), "192.168.1.20");
+}
+
+#[test]
+fn tcp_and_unix_engine_hosts_set_no_remote() {
+    let _lock = ENGINE_ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let from_env = |var: &str, url: &str, engine: &str| {
+        unsafe {
+            std::env::set_var(var, url);
+        }
+        let remote = Remote::from_engine_env(Some(engine));
+        unsafe {
+            std::env::remove_var(var);
+        }
+        remote
+    };
+    assert!(from_env("DOCKER_HOST", "tcp://192.168.1.20:2376", "docker").is_none());
+    assert!(
+        from_env(
+            "DOCKER_HOST",
+            "unix:///home/me/.colima/docker.sock",
+            "docker"
+        )
+        .is_none()
+    );
+    assert!(from_env("CONTAINER_HOST", "tcp://devbox:8888", "podman").is_none());
+    assert!(
+        from_env(
+            "CONTAINER_HOST",
+            "unix:///run/user/1000/podman/podman.sock",
+            "podman"
+        )
+        .is_none()
+    );
+}
+
+#[test]
+fn direct_urls_point_at_the_remote_address() {
+    assert_eq!(Config::default().direct_host(), "localhost");
+    let config = Config {
+        remote: Some(remote(None)),
+        ..Default::default()
+    };
+    assert_eq!(config.direct_host(), "192.168.1.20");
+}
@@ -1,5 +1,7 @@
-use darp::commands::build_container_hosts;
-use darp::engine::{EngineKind, read_container_host_ip, write_container_host_ip};
+use darp::deploy::build_container_hosts;
+use darp::engine::{
+    EngineKind, HealthState, parse_health, read_container_host_ip, write_container_host_ip,
+};
 
 // ---------------------------------------------------------------------------
 // build_container_hosts — pure function, in-container /etc/hosts content
@@ -74,3 +76,35 @@ fn cache_returns_none_on_malformed_file() {
     assert!(read_container_host_ip(&path, &EngineKind::Docker).is_none());
     let _ = std::fs::remove_file(&path);
 }
+
+// ---------------------------------------------------------------------------
+// parse_health — `inspect --format '{{json .State.Health}}'`
+// ---------------------------------------------------------------------------
+
+#[test]
+fn parse_health_reads_status_and_last_output() {
+    let json = r#"{"Status":"unhealthy","FailingStreak":3,"Log":[
+        {"ExitCode":1,"Output":"first"},
+        {"ExitCode":7,"Output":"curl: (7) Failed to connect\n"}
+    ]}"#;
+    assert_eq!(
+        parse_health(json),
+        Some(HealthState {
+            status: "unhealthy".to_string(),
+            last_output: Some("curl: (7) Failed to connect".to_string()),
+        })
+    );
+}
+
+#[test]
+fn parse_health_without_healthcheck_is_none() {
+    // Containers without a healthcheck print `null`.
+    assert_eq!(parse_health("null\n"), None);
+    assert_eq!(
+        parse_health(r#"{"Status":"starting","Log":[]}"#),
+        Some(HealthState {
+            status: "starting".to_string(),
+            last_output: None,
+        })
+    );
+}
@@ -0,0 +1,123 @@
+use darp::commands::replica_container_name;
+use darp::config::{Config, validate_container_name};
+use darp::engine::{
+    DEFAULT_CONTAINER_NAME, Engine, EngineKind, container_name, pod_name, sanitize_container_name,
+};
+
+// ---------------------------------------------------------------------------
+// Sanitizing
+// ---------------------------------------------------------------------------
+
+#[test]
+fn valid_names_are_left_alone() {
+    assert_eq!(sanitize_container_name("darp_shop_api"), "darp_shop_api");
+    assert_eq!(sanitize_container_name("dev-api.shop"), "dev-api.shop");
+}
+
+#[test]
+fn invalid_characters_become_underscores() {
+    assert_eq!(
+        sanitize_container_name("darp_my shop_api (old)"),
+        "darp_my_shop_api__old_"
+    );
+    assert_eq!(sanitize_container_name("darp_café_api"), "darp_caf__api");
+}
+
+#[test]
+fn names_start_with_a_letter_or_digit() {
+    assert_eq!(sanitize_container_name("_api.shop"), "api.shop");
+    assert_eq!(sanitize_container_name("-.api"), "api");
+    assert_eq!(sanitize_container_name("__"), "darp");
+}
+
+#[test]
+fn pod_names_are_sanitized() {
+    assert_eq!(pod_name("shop"), "darp_shop");
+    assert_eq!(pod_name("my shop"), "darp_my_shop");
+}
+
+// ---------------------------------------------------------------------------
+// Templates
+// ---------------------------------------------------------------------------
+
+#[test]
+fn default_template_keeps_the_darp_prefix() {
+    assert_eq!(
+        container_name(DEFAULT_CONTAINER_NAME, "shop", "api"),
+        "darp_shop_api"
+    );
+}
+
+#[test]
+fn tokens_are_filled_in_and_sanitized() {
+    assert_eq!(
+        container_name("{service}.{domain}", "my shop", "web app"),
+        "web_app.my_shop"
+    );
+    assert_eq!(
+        container_name("{service}-{domain}", ".dotfiles", "api"),
+        "api-.dotfiles"
+    );
+    assert_eq!(
+        container_name("{service}-{domain}", "shop", ".hidden"),
+        "hidden-shop"
+    );
+}
+
+#[test]
+fn replicas_append_to_the_templated_name() {
+    let name = container_name("dev-{domain}-{service}", "shop", "api");
+    assert_eq!(replica_container_name(&name, 0), "dev-shop-api");
+    assert_eq!(replica_container_name(&name, 1), "dev-shop-api_r2");
+}
+
+#[test]
+fn templates_need_both_tokens() {
+    validate_container_name(DEFAULT_CONTAINER_NAME).unwrap();
+    validate_container_name("{service}.{domain}").unwrap();
+    assert!(validate_container_name("darp_{service}").is_err());
+    assert!(validate_container_name("darp_{domain}").is_err());
+}
+
+#[test]
+fn templates_reject_invalid_literals_and_unknown_tokens() {
+    assert!(validate_container_name("darp {domain}_{service}").is_err());
+    assert!(validate_container_name("darp/{domain}_{service}").is_err());
+    assert!(validate_container_name("{group}_{domain}_{service}").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn set_and_rm_container_name() {
+    let mut c = Config::default();
+    assert!(c.set_container_name("{service}").is_err());
+    assert!(c.container_name.is_none());
+
+    c.set_container_name("dev-{service}-{domain}").unwrap();
+    assert_eq!(c.container_name.as_deref(), Some("dev-{service}-{domain}"));
+
+    c.rm_container_name().unwrap();
+    assert!(c.container_name.is_none());
+    assert!(c.rm_container_name().is_err());
+}
+
+#[test]
+fn engine_names_containers_from_the_configured_template() {
+    let engine = Engine::new(EngineKind::None, &Config::default()).unwrap();
+    assert_eq!(
+        engine.service_container_name("shop", "api"),
+        "darp_shop_api"
+    );
+
+    let mut c = Config::default();
+    c.set_container_name("{service}.{domain}").unwrap();
+    let engine = Engine::new(EngineKind::None, &c).unwrap();
+    assert_eq!(engine.service_container_name("shop", "api"), "api.shop");
+    assert_eq!(
+        engine.service_container_name("my shop", "api"),
+        "api.my_shop"
+    );
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use darp::config::{Config, CustomDomain};
+use darp::deploy::{custom_domain_vhost, replicas_vhost, tls_vhost};
+use darp::os::darp_block_hostnames;
+
+// ---------------------------------------------------------------------------
+// Config custom domains
+// ---------------------------------------------------------------------------
+
+#[test]
+fn custom_domains_are_added_and_removed() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_service_custom_domain(
+        "shop",
+        ".",
+        "auth",
+        "login.mycompany.dev",
+        Some("/certs/login.pem"),
+        Some("/certs/login-key.pem"),
+    )
+    .unwrap();
+    c.add_service_custom_domain("shop", ".", "web", "www.mycompany.dev", None, None)
+        .unwrap();
+
+    let found: Vec<_> = c
+        .custom_domains()
+        .into_iter()
+        .map(|((d, s), host, tls)| (d, s, host, tls.cert.is_some()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("shop", "auth", "login.mycompany.dev", true),
+            ("shop", "web", "www.mycompany.dev", false),
+        ]
+    );
+    let certs = c.tls_certs().unwrap();
+    assert_eq!(certs.len(), 1);
+    assert_eq!(certs[0].0, "login.mycompany.dev");
+    assert_eq!(certs[0].1.to_str(), Some("/certs/login.pem"));
+
+    c.rm_service_custom_domain("shop", ".", "web", "www.mycompany.dev")
+        .unwrap();
+    assert!(
+        c.rm_service_custom_domain("shop", ".", "web", "www.mycompany.dev")
+            .is_err()
+    );
+    assert_eq!(c.custom_domains().len(), 1);
+}
+
+#[test]
+fn custom_domains_reject_test_names_half_tls_and_taken_hosts() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_service_alias("shop", ".", "api", "api.myshop.test")
+        .unwrap();
+    c.add_service_custom_domain("shop", ".", "auth", "login.mycompany.dev", None, None)
+        .unwrap();
+
+    assert!(
+        c.add_service_custom_domain("shop", ".", "web", "web.myshop.test", None, None)
+            .is_err()
+    );
+    assert!(
+        c.add_service_custom_domain(
+            "shop",
+            ".",
+            "web",
+            "www.mycompany.dev",
+            Some("/c.pem"),
+            None
+        )
+        .is_err()
+    );
+    assert!(
+        c.add_service_custom_domain("shop", ".", "web", "login.mycompany.dev", None, None)
+            .is_err()
+    );
+    assert!(
+        c.add_service_alias("shop", ".", "web", "login.mycompany.dev")
+            .is_err()
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Vhosts
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tls_vhost_serves_the_hostname_with_its_certificate() {
+    let vhost = replicas_vhost("shop", "auth", "host.docker.internal", &[50100, 50101]);
+    let out = tls_vhost(&vhost, "login.mycompany.dev");
+
+    assert!(out.starts_with("server {\n"), "{}", out);
+    assert!(!out.contains("upstream "), "{}", out);
+    assert!(!out.contains("listen 80;"), "{}", out);
+    assert!(out.contains("    listen 443 ssl;\n    server_name login.mycompany.dev;\n"));
+    assert!(out.contains("    ssl_certificate /etc/nginx/certs/login.mycompany.dev.crt;\n"));
+    assert!(out.contains("    ssl_certificate_key /etc/nginx/certs/login.mycompany.dev.key;\n"));
+    assert!(out.contains("        proxy_set_header X-Forwarded-Proto https;\n"));
+    assert!(out.contains("proxy_pass http://darp_replicas_shop_auth/;"));
+}
+
+#[test]
+fn custom_domain_vhost_adds_tls_blocks_only_for_certified_hosts() {
+    let vhost = replicas_vhost("shop", "auth", "host.docker.internal", &[50100, 50101]);
+    let mut domains = BTreeMap::new();
+    domains.insert(
+        "login.mycompany.dev".to_string(),
+        CustomDomain {
+            cert: Some("/c.pem".to_string()),
+            key: Some("/k.pem".to_string()),
+        },
+    );
+    domains.insert("sso.mycompany.dev".to_string(), CustomDomain::default());
+    let out = custom_domain_vhost(&vhost, &domains);
+
+    assert!(
+        out.contains("    server_name auth.shop.test login.mycompany.dev sso.mycompany.dev;\n")
+    );
+    assert_eq!(out.matches("listen 443 ssl;").count(), 1, "{}", out);
+    assert_eq!(out.matches("upstream darp_replicas_shop_auth {").count(), 1);
+}
+
+// ---------------------------------------------------------------------------
+// Hosts file
+// ---------------------------------------------------------------------------
+
+#[test]
+fn darp_block_hostnames_reads_only_the_darp_block() {
+    let hosts = "127.0.0.1 localhost\n\n# --- DARP HOSTS START ---\n127.0.0.1   api.shop.test\n127.0.0.1   login.mycompany.dev\n# --- DARP HOSTS END ---\n10.0.0.1 nas\n";
+    assert_eq!(
+        darp_block_hostnames(hosts),
+        vec!["api.shop.test", "login.mycompany.dev"]
+    );
+    assert!(darp_block_hostnames("127.0.0.1 localhost\n").is_empty());
+}
@@ -126,6 +126,38 @@ fn unknown_service_is_none() {
     );
 }
 
+#[test]
+fn domain_ports_cover_every_group_and_skip_other_domains() {
+    let pm = serde_json::json!({
+        "uhin": {
+            ".": { "api": 50100 },
+            "laravel": { "web": { "port": 50101, "type": "http", "debug_port": 9004 } }
+        },
+        "other": { ".": { "svc": { "port": 50102, "debug_port": 9005 } } }
+    });
+    assert_eq!(
+        config::portmap_domain_ports(&pm, "uhin"),
+        vec![9004, 50100, 50101]
+    );
+    assert!(config::portmap_domain_ports(&pm, "missing").is_empty());
+}
+
+#[test]
+fn next_proxy_port_follows_the_highest_assigned_port() {
+    let pm = serde_json::json!({
+        "uhin": {
+            ".": { "api": 50100 },
+            "laravel": { "web": { "port": 50104, "type": "http", "debug_port": 13000 } }
+        },
+        "other": { ".": { "svc": { "port": 50102, "debug_port": 13001 } } }
+    });
+    assert_eq!(config::next_proxy_port(&pm, config::PROXY_PORT_BASE), 50105);
+    assert_eq!(
+        config::next_proxy_port(&serde_json::json!({}), config::PROXY_PORT_BASE),
+        config::PROXY_PORT_BASE
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Port-range hardening: base, skip-list, choose_debug_port
 // ---------------------------------------------------------------------------
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use darp::config::portmap_direct_proxy_ports;
+use darp::deploy::{direct_port, direct_vhost};
+use darp::services_index::build_services_index;
+use darp::urls::{direct_url, render_urls_direct, render_urls_json};
+
+fn portmap() -> serde_json::Value {
+    serde_json::json!({
+        "shop": {
+            ".": {
+                "api": {
+                    "port": 50100,
+                    "type": "http",
+                    "direct_url": "http://localhost:50100",
+                    "direct_via_proxy": true
+                },
+                "docs": { "port": 50102, "type": "static" }
+            },
+            "workers": {
+                "queue": { "port": 50101, "type": "tcp", "direct_url": "tcp://localhost:50101" }
+            }
+        }
+    })
+}
+
+// ---------------------------------------------------------------------------
+// direct_port / direct_url
+// ---------------------------------------------------------------------------
+
+#[test]
+fn published_services_are_reached_on_their_own_port() {
+    assert_eq!(
+        direct_port("http", None, 50100, false, false, false),
+        Some((50100, false))
+    );
+    assert_eq!(
+        direct_port("tcp", None, 50101, false, false, false),
+        Some((50101, false))
+    );
+}
+
+#[test]
+fn host_run_services_use_their_host_port() {
+    assert_eq!(
+        direct_port("http", Some(3000), 50100, true, false, false),
+        Some((3000, false))
+    );
+}
+
+#[test]
+fn routed_services_get_a_proxy_listener() {
+    assert_eq!(
+        direct_port("http", None, 50100, true, false, false),
+        Some((50100, true))
+    );
+    assert_eq!(direct_port("http", None, 50100, true, true, false), None);
+    assert_eq!(direct_port("http", None, 50100, true, false, true), None);
+}
+
+#[test]
+fn static_services_have_no_direct_port() {
+    assert_eq!(
+        direct_port("static", None, 50100, false, false, false),
+        None
+    );
+}
+
+#[test]
+fn direct_url_scheme_follows_connection_type() {
+    assert_eq!(
+        direct_url("http", "localhost", 50100),
+        "http://localhost:50100"
+    );
+    assert_eq!(
+        direct_url("websocket", "localhost", 50100),
+        "ws://localhost:50100"
+    );
+    assert_eq!(
+        direct_url("tcp", "localhost", 50100),
+        "tcp://localhost:50100"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// direct_vhost
+// ---------------------------------------------------------------------------
+
+#[test]
+fn direct_vhost_listens_on_the_port_for_any_host() {
+    let vhost = "server {\n    listen 80;\n    server_name api.shop.test;\n    location / {\n        proxy_pass http://darp_shop_api:8000;\n    }\n}\n";
+    assert_eq!(
+        direct_vhost(vhost, 50100),
+        "server {\n    listen 50100;\n    location / {\n        proxy_pass http://darp_shop_api:8000;\n    }\n}\n"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// portmap_direct_proxy_ports
+// ---------------------------------------------------------------------------
+
+#[test]
+fn proxy_publishes_only_routed_ports() {
+    assert_eq!(portmap_direct_proxy_ports(&portmap()), vec![50100]);
+    assert!(portmap_direct_proxy_ports(&serde_json::json!({})).is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// render_urls_direct / render_urls_json / build_services_index
+// ---------------------------------------------------------------------------
+
+#[test]
+fn render_direct_lists_localhost_urls() {
+    assert_eq!(
+        render_urls_direct(&portmap()),
+        "api.shop.test    http://localhost:50100\nqueue.shop.test  tcp://localhost:50101\n"
+    );
+}
+
+#[test]
+fn json_includes_direct_url_when_known() {
+    let json = render_urls_json(&portmap());
+    assert_eq!(json["shop"]["api"]["direct_url"], "http://localhost:50100");
+    assert!(json["shop"]["docs"].get("direct_url").is_none());
+}
+
+#[test]
+fn services_index_includes_direct_url() {
+    let index = build_services_index(&portmap(), &HashSet::new(), "darp_{domain}_{service}");
+    let api = index["services"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["service"] == "api")
+        .unwrap();
+    assert_eq!(api["direct_url"], "http://localhost:50100");
+}
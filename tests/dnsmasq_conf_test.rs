@@ -0,0 +1,74 @@
+use darp::config::{Config, DarpPaths, Remote};
+use darp::engine::EngineKind;
+use darp::os::OsIntegration;
+
+fn paths(dir: &std::path::Path) -> DarpPaths {
+    DarpPaths::new(dir.join("config.json"), dir.to_path_buf())
+}
+
+fn remote_config() -> Config {
+    Config {
+        remote: Some(Remote {
+            host: "me@devbox".to_string(),
+            address: "192.168.1.20".to_string(),
+            url: None,
+            resolver: None,
+        }),
+        ..Default::default()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// test_conf
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_names_resolve_to_loopback_locally() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths(dir.path());
+    let os = OsIntegration::new(&paths, &Config::default(), &EngineKind::None);
+    assert_eq!(os.test_conf(), "address=/.test/127.0.0.1\n");
+}
+
+#[test]
+fn test_names_resolve_to_the_remote_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths(dir.path());
+    let os = OsIntegration::new(&paths, &remote_config(), &EngineKind::None);
+    assert_eq!(os.test_conf(), "address=/.test/192.168.1.20\n");
+}
+
+// ---------------------------------------------------------------------------
+// sync_test_conf
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sync_writes_a_missing_conf_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths(dir.path());
+    let os = OsIntegration::new(&paths, &Config::default(), &EngineKind::None);
+    assert!(os.sync_test_conf().unwrap());
+    assert!(!os.sync_test_conf().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(paths.dnsmasq_dir.join("test.conf")).unwrap(),
+        "address=/.test/127.0.0.1\n"
+    );
+}
+
+#[test]
+fn sync_rewrites_the_conf_when_remote_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths(dir.path());
+    let local = Config::default();
+    OsIntegration::new(&paths, &local, &EngineKind::None)
+        .sync_test_conf()
+        .unwrap();
+
+    let remote = remote_config();
+    let os = OsIntegration::new(&paths, &remote, &EngineKind::None);
+    assert!(os.sync_test_conf().unwrap());
+    assert_eq!(
+        std::fs::read_to_string(paths.dnsmasq_dir.join("test.conf")).unwrap(),
+        "address=/.test/192.168.1.20\n"
+    );
+}
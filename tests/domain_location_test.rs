@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use darp::config::{Config, Domain};
+use darp::deploy::domain_location_problems;
+
+fn config_with(locations: &[(&str, &std::path::Path)]) -> Config {
+    let domains: BTreeMap<String, Domain> = locations
+        .iter()
+        .map(|(name, path)| {
+            (
+                name.to_string(),
+                Domain {
+                    location: path.to_string_lossy().into_owned(),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    Config {
+        domains: Some(domains),
+        ..Default::default()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// domain_location_problems
+// ---------------------------------------------------------------------------
+
+#[test]
+fn existing_locations_have_no_problems() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config_with(&[("shop", dir.path())]);
+    assert!(domain_location_problems(&config).unwrap().is_empty());
+}
+
+#[test]
+fn every_bad_location_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("notes.txt");
+    std::fs::write(&file, "").unwrap();
+    let gone = dir.path().join("moved-away");
+    let config = config_with(&[("blog", &file), ("good", dir.path()), ("shop", &gone)]);
+
+    let problems = domain_location_problems(&config).unwrap();
+    let found: Vec<(&str, &str)> = problems
+        .iter()
+        .map(|p| (p.domain.as_str(), p.reason.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        [("blog", "is not a directory"), ("shop", "does not exist")]
+    );
+    assert_eq!(problems[1].location, gone);
+}
+
+#[test]
+fn no_domains_means_no_problems() {
+    assert!(
+        domain_location_problems(&Config::default())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Config::set_domain_location
+// ---------------------------------------------------------------------------
+
+#[test]
+fn set_domain_location_repoints_an_existing_domain() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = config_with(&[("shop", dir.path())]);
+    config
+        .set_domain_location("shop", "{home}/projects/shop")
+        .unwrap();
+    assert_eq!(
+        config.domains.as_ref().unwrap()["shop"].location,
+        "{home}/projects/shop"
+    );
+    assert!(config.set_domain_location("blog", "/tmp").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Config::move_domain
+// ---------------------------------------------------------------------------
+
+#[test]
+fn move_domain_keeps_services_and_stores_an_absolute_path() {
+    let old = tempfile::tempdir().unwrap();
+    let parent = tempfile::tempdir().unwrap();
+    std::fs::create_dir(parent.path().join("shop")).unwrap();
+    let mut config = config_with(&[("shop", old.path())]);
+    config
+        .set_service_description("shop", ".", "api", "Public API")
+        .unwrap();
+
+    config.move_domain("shop", "shop", parent.path()).unwrap();
+
+    let domain = &config.domains.as_ref().unwrap()["shop"];
+    assert_eq!(
+        std::path::Path::new(&domain.location),
+        parent.path().join("shop")
+    );
+    let api = &domain.groups.as_ref().unwrap()["."]
+        .services
+        .as_ref()
+        .unwrap()["api"];
+    assert_eq!(api.description.as_deref(), Some("Public API"));
+}
+
+#[test]
+fn move_domain_rejects_a_missing_folder() {
+    let old = tempfile::tempdir().unwrap();
+    let mut config = config_with(&[("shop", old.path())]);
+    let err = config
+        .move_domain("shop", "not-there", old.path())
+        .unwrap_err();
+    assert!(err.to_string().contains("is not a directory"));
+}
+
+#[test]
+fn move_domain_rejects_another_domains_folder() {
+    let shop = tempfile::tempdir().unwrap();
+    let blog = tempfile::tempdir().unwrap();
+    let mut config = config_with(&[("blog", blog.path()), ("shop", shop.path())]);
+    let err = config
+        .move_domain("shop", &blog.path().to_string_lossy(), shop.path())
+        .unwrap_err();
+    assert!(err.to_string().contains("Domain 'blog' is already at"));
+}
+
+#[test]
+fn move_domain_errors_for_an_unknown_domain() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = config_with(&[("shop", dir.path())]);
+    assert!(
+        config
+            .move_domain("blog", &dir.path().to_string_lossy(), dir.path())
+            .is_err()
+    );
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use darp::config::{self, Config, Domain, Environment, Group, Service};
+use darp::deploy::service_network_resolver;
+use darp::engine::{Engine, EngineKind};
+
+fn environment(engine: Option<&str>) -> Environment {
+    Environment {
+        engine: engine.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+/// Global engine podman; domain "client" whose services default to the "legacy"
+/// environment (docker), except "api", which defaults to "plain" (no engine).
+fn config() -> Config {
+    let api = Service {
+        default_environment: Some("plain".to_string()),
+        ..Default::default()
+    };
+    let group = Group {
+        services: Some(BTreeMap::from([("api".to_string(), api)])),
+        ..Default::default()
+    };
+    Config {
+        engine: Some("podman".to_string()),
+        environments: Some(BTreeMap::from([
+            ("legacy".to_string(), environment(Some("docker"))),
+            ("plain".to_string(), environment(None)),
+        ])),
+        domains: Some(BTreeMap::from([(
+            "client".to_string(),
+            Domain {
+                location: "/tmp/client".to_string(),
+                default_environment: Some("legacy".to_string()),
+                groups: Some(BTreeMap::from([(".".to_string(), group)])),
+                ..Default::default()
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+fn domain(config: &Config) -> &Domain {
+    &config.domains.as_ref().unwrap()["client"]
+}
+
+// ---------------------------------------------------------------------------
+// Setting and removing
+// ---------------------------------------------------------------------------
+
+#[test]
+fn set_and_rm_domain_engine() {
+    let mut c = config();
+    c.set_domain_engine("client", "Docker").unwrap();
+    assert_eq!(domain(&c).engine.as_deref(), Some("docker"));
+
+    c.rm_domain_engine("client").unwrap();
+    assert!(domain(&c).engine.is_none());
+    assert!(c.rm_domain_engine("client").is_err());
+    assert!(c.set_domain_engine("missing", "docker").is_err());
+}
+
+#[test]
+fn set_and_rm_environment_engine() {
+    let mut c = config();
+    c.set_environment_engine("plain", "podman").unwrap();
+    assert_eq!(
+        c.environments.as_ref().unwrap()["plain"].engine.as_deref(),
+        Some("podman")
+    );
+
+    c.rm_environment_engine("plain").unwrap();
+    assert!(c.rm_environment_engine("plain").is_err());
+    assert!(c.set_environment_engine("missing", "podman").is_err());
+}
+
+#[test]
+fn unknown_engines_are_rejected() {
+    let mut c = config();
+    assert!(c.set_domain_engine("client", "nerdctl").is_err());
+    assert!(c.set_environment_engine("plain", "").is_err());
+    assert!(domain(&c).engine.is_none());
+}
+
+// ---------------------------------------------------------------------------
+// Resolution
+// ---------------------------------------------------------------------------
+
+#[test]
+fn domain_engine_wins_over_the_environment() {
+    let mut c = config();
+    let legacy = c.environments.as_ref().unwrap()["legacy"].clone();
+    assert_eq!(
+        config::engine_override(domain(&c), Some(&legacy)),
+        Some("docker")
+    );
+    assert_eq!(config::engine_override(domain(&c), None), None);
+
+    c.set_domain_engine("client", "podman").unwrap();
+    assert_eq!(
+        config::engine_override(domain(&c), Some(&legacy)),
+        Some("podman")
+    );
+}
+
+#[test]
+fn default_engine_override_follows_the_default_environment() {
+    let c = config();
+    // "web" inherits the domain's legacy environment; "api" picks its own.
+    assert_eq!(
+        c.default_engine_override(domain(&c), ".", "web"),
+        Some("docker")
+    );
+    assert_eq!(c.default_engine_override(domain(&c), ".", "api"), None);
+}
+
+#[test]
+fn engine_kind_falls_back_to_the_global_engine() {
+    let c = config();
+    assert_eq!(
+        EngineKind::for_service(&c, Some("docker")),
+        EngineKind::Docker
+    );
+    assert_eq!(EngineKind::for_service(&c, None), EngineKind::Podman);
+}
+
+#[test]
+fn overrides_list_each_other_engine_once() {
+    let mut c = config();
+    let kinds = |c: &Config| -> Vec<EngineKind> {
+        Engine::overrides(c)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect()
+    };
+    assert_eq!(kinds(&c), vec![EngineKind::Docker]);
+
+    // The domain naming docker too adds nothing; the global engine is never listed.
+    c.set_domain_engine("client", "docker").unwrap();
+    assert_eq!(kinds(&c), vec![EngineKind::Docker]);
+    c.set_environment_engine("plain", "podman").unwrap();
+    assert_eq!(kinds(&c), vec![EngineKind::Docker]);
+
+    c.rm_domain_engine("client").unwrap();
+    c.rm_environment_engine("legacy").unwrap();
+    c.rm_environment_engine("plain").unwrap();
+    assert!(kinds(&c).is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// Networking
+// ---------------------------------------------------------------------------
+
+#[test]
+fn only_the_global_engine_joins_the_darp_network() {
+    let c = config();
+    let legacy = c.environments.as_ref().unwrap()["legacy"].clone();
+    let own = Engine::for_service(&c, domain(&c), Some(&legacy)).unwrap();
+    assert_eq!(own.kind, EngineKind::Docker);
+    assert!(!own.darp_network);
+
+    let global = Engine::for_service(&c, domain(&c), None).unwrap();
+    assert_eq!(global.kind, EngineKind::Podman);
+    assert!(global.darp_network);
+}
+
+#[test]
+fn services_on_another_engine_are_routed_through_the_gateway() {
+    let c = config();
+    let proxy = Engine::new(EngineKind::Podman, &c).unwrap();
+    let d = domain(&c);
+    assert_eq!(
+        service_network_resolver(&c, &proxy, Some("10.89.0.1"), d, ".", "api"),
+        Some("10.89.0.1")
+    );
+    assert_eq!(
+        service_network_resolver(&c, &proxy, Some("10.89.0.1"), d, ".", "web"),
+        None
+    );
+}
@@ -0,0 +1,74 @@
+use darp::config::Config;
+use darp::engine::{DEFAULT_ENGINE_CONCURRENCY, Engine, EngineKind};
+
+fn engine(limit: Option<u32>) -> Engine {
+    let mut config = Config::default();
+    if let Some(limit) = limit {
+        config.set_engine_concurrency(limit).unwrap();
+    }
+    Engine::new(EngineKind::None, &config).unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// engine_concurrency
+// ---------------------------------------------------------------------------
+
+#[test]
+fn concurrency_defaults_and_can_be_removed() {
+    assert_eq!(engine(None).concurrency, DEFAULT_ENGINE_CONCURRENCY);
+    assert_eq!(engine(Some(2)).concurrency, 2);
+
+    let mut config = Config::default();
+    assert!(config.rm_engine_concurrency().is_err());
+    config.set_engine_concurrency(0).unwrap();
+    config.rm_engine_concurrency().unwrap();
+    assert_eq!(config.engine_concurrency, None);
+}
+
+// ---------------------------------------------------------------------------
+// try_acquire_slot / acquire_slot
+// ---------------------------------------------------------------------------
+
+#[test]
+fn slots_run_out_at_the_limit_and_free_on_drop() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(Some(2));
+    let first = engine.try_acquire_slot(dir.path()).unwrap();
+    let second = engine.try_acquire_slot(dir.path()).unwrap();
+    assert!(first.is_some() && second.is_some());
+    assert!(engine.try_acquire_slot(dir.path()).unwrap().is_none());
+
+    drop(first);
+    assert!(engine.try_acquire_slot(dir.path()).unwrap().is_some());
+}
+
+#[test]
+fn zero_means_no_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(Some(0));
+    let slots: Vec<_> = (0..10)
+        .map(|_| engine.try_acquire_slot(dir.path()).unwrap())
+        .collect();
+    assert!(slots.iter().all(Option::is_some));
+}
+
+#[test]
+fn a_queued_operation_starts_when_a_slot_frees() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = engine(Some(1));
+    let held = engine.acquire_slot(dir.path(), "start a").unwrap();
+
+    let path = dir.path().to_path_buf();
+    let waiter = std::thread::spawn(move || {
+        let engine = self::engine(Some(1));
+        engine.acquire_slot(&path, "start b").map(|_| ())
+    });
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    assert!(!waiter.is_finished());
+
+    drop(held);
+    waiter.join().unwrap().unwrap();
+    // The waiter's queue ticket is gone once it got its slot.
+    let queue = std::fs::read_dir(dir.path().join("queue")).unwrap();
+    assert_eq!(queue.count(), 0);
+}
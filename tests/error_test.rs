@@ -0,0 +1,77 @@
+use anyhow::Context;
+
+use darp::cli::SetCommand;
+use darp::commands::cmd_set;
+use darp::config::{Config, DarpPaths};
+use darp::engine::EngineKind;
+use darp::error::{DarpError, exit_code};
+
+// ---------------------------------------------------------------------------
+// Exit codes
+// ---------------------------------------------------------------------------
+
+#[test]
+fn darp_errors_pick_the_exit_code() {
+    let err: anyhow::Error = DarpError::PortNotAssigned("api".into()).into();
+    assert_eq!(exit_code(&err), 4);
+    let err: anyhow::Error = DarpError::EnvironmentNotFound("dev".into()).into();
+    assert_eq!(exit_code(&err), 1);
+    assert_eq!(exit_code(&anyhow::anyhow!("something else")), 1);
+}
+
+#[test]
+fn exit_code_looks_through_context() {
+    let err = Err::<(), _>(DarpError::PortNotAssigned("api".into()))
+        .context("serving api")
+        .unwrap_err();
+    assert_eq!(exit_code(&err), 4);
+}
+
+// ---------------------------------------------------------------------------
+// Messages
+// ---------------------------------------------------------------------------
+
+#[test]
+fn messages_carry_the_fix() {
+    let err = DarpError::NoServeCommand {
+        domain: "shop".into(),
+        service: "api".into(),
+        environment: "dev".into(),
+    };
+    let msg = err.to_string();
+    assert!(msg.starts_with("Neither service 'shop.api', domain 'shop', nor environment 'dev'"));
+    assert!(msg.contains("darp config set svc serve-command shop api <cmd>"));
+    assert!(msg.contains("darp config set env serve-command dev <cmd>"));
+
+    let msg = DarpError::EnvironmentRequired {
+        domain: "shop".into(),
+    }
+    .to_string();
+    assert!(msg.contains("darp config set dom default-environment shop <env>"));
+}
+
+// ---------------------------------------------------------------------------
+// Commands return errors instead of exiting
+// ---------------------------------------------------------------------------
+
+#[test]
+fn setting_an_unknown_engine_returns_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+    let mut config = Config::default();
+    let err = cmd_set(
+        SetCommand::Engine {
+            engine: "lxc".into(),
+        },
+        &paths,
+        &mut config,
+        &EngineKind::None,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<DarpError>(),
+        Some(&DarpError::UnknownEngine("lxc".into()))
+    );
+    assert!(config.engine.is_none());
+    assert!(!paths.config_path.exists());
+}
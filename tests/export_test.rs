@@ -0,0 +1,351 @@
+use std::path::Path;
+
+use darp::commands::{RunPlan, compose_file, quadlet_unit, systemd_quote, systemd_unit};
+
+fn run_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn sample_run_args() -> Vec<String> {
+    run_args(&[
+        "--rm",
+        "--name",
+        "darp_shop_api",
+        "--label",
+        "darp.managed=true",
+        "-v",
+        "/home/me/shop/api:/app",
+        "-p",
+        "50100:8000",
+        "-e",
+        "API_KEY",
+        "-e",
+        "GREETING=hello world",
+        "--cpus",
+        "2",
+        "node:22",
+        "sh",
+        "-c",
+        "cd /app; npm start",
+    ])
+}
+
+// ---------------------------------------------------------------------------
+// systemd_quote
+// ---------------------------------------------------------------------------
+
+#[test]
+fn quote_leaves_plain_words_alone() {
+    assert_eq!(systemd_quote("/app"), "/app");
+    assert_eq!(systemd_quote("50100:8000"), "50100:8000");
+}
+
+#[test]
+fn quote_wraps_whitespace_and_escapes() {
+    assert_eq!(systemd_quote("a b"), "\"a b\"");
+    assert_eq!(systemd_quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+    assert_eq!(systemd_quote("x\ny"), "\"x\\ny\"");
+    assert_eq!(systemd_quote("50%"), "50%%");
+}
+
+// ---------------------------------------------------------------------------
+// quadlet_unit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn quadlet_maps_run_flags_to_keys() {
+    let unit = quadlet_unit("darp service api.shop.test", &sample_run_args());
+
+    assert!(unit.contains("Description=darp service api.shop.test\n"));
+    assert!(unit.contains("Image=node:22\n"));
+    assert!(unit.contains("ContainerName=darp_shop_api\n"));
+    assert!(unit.contains("Label=darp.managed=true\n"));
+    assert!(unit.contains("Volume=/home/me/shop/api:/app\n"));
+    assert!(unit.contains("PublishPort=50100:8000\n"));
+    assert!(unit.contains("Environment=\"GREETING=hello world\"\n"));
+    assert!(unit.contains("PodmanArgs=--cpus=2\n"));
+    assert!(unit.contains("Exec=sh -c \"cd /app; npm start\"\n"));
+    assert!(unit.contains("WantedBy=default.target"));
+}
+
+#[test]
+fn quadlet_drops_rm_and_valueless_secrets() {
+    let unit = quadlet_unit("d", &sample_run_args());
+    assert!(!unit.contains("--rm"));
+    assert!(!unit.contains("API_KEY"));
+}
+
+#[test]
+fn quadlet_passes_options_without_a_key_through_podman_args() {
+    let mut args = sample_run_args();
+    let image = args.iter().position(|a| a == "node:22").unwrap();
+    args.splice(
+        image..image,
+        run_args(&[
+            "--security-opt=label=disable",
+            "--init",
+            "--oom-score-adj=5",
+        ]),
+    );
+    let unit = quadlet_unit("d", &args);
+    assert!(
+        unit.contains(
+            "PodmanArgs=--cpus=2 --security-opt=label=disable --init --oom-score-adj=5\n"
+        )
+    );
+    assert!(unit.contains("Image=node:22\n"));
+}
+
+#[test]
+fn quadlet_maps_flag_equals_value_options_to_keys() {
+    let mut args = sample_run_args();
+    let image = args.iter().position(|a| a == "node:22").unwrap();
+    args.splice(
+        image..image,
+        run_args(&[
+            "--cap-add=SYS_PTRACE",
+            "--network=shop_default",
+            "--add-host=registry.test:10.0.0.5",
+            "--shm-size=1g",
+            "--device=/dev/fuse",
+            "--env=HTTPS_PROXY=http://proxy:3128",
+        ]),
+    );
+    let unit = quadlet_unit("d", &args);
+    assert!(unit.contains("AddCapability=SYS_PTRACE\n"));
+    assert!(unit.contains("Network=shop_default\n"));
+    assert!(unit.contains("AddHost=registry.test:10.0.0.5\n"));
+    assert!(unit.contains("ShmSize=1g\n"));
+    assert!(unit.contains("AddDevice=/dev/fuse\n"));
+    assert!(unit.contains("Environment=HTTPS_PROXY=http://proxy:3128\n"));
+    assert!(unit.contains("PodmanArgs=--cpus=2\n"));
+}
+
+#[test]
+fn quadlet_keeps_the_entrypoint() {
+    let mut args = sample_run_args();
+    let image = args.iter().position(|a| a == "node:22").unwrap();
+    args.splice(image..image, run_args(&["--entrypoint", "/bin/bash"]));
+    args.truncate(args.len() - 3);
+    args.extend(run_args(&["-c", "npm start"]));
+    let unit = quadlet_unit("d", &args);
+    assert!(unit.contains("Entrypoint=/bin/bash\n"));
+    assert!(unit.contains("Image=node:22\n"));
+    assert!(unit.contains("Exec=-c \"npm start\"\n"));
+}
+
+#[test]
+fn quadlet_maps_the_host_user() {
+    let mut args = sample_run_args();
+    let image = args.iter().position(|a| a == "node:22").unwrap();
+    args.splice(
+        image..image,
+        run_args(&["--userns=keep-id", "--user", "1000:100"]),
+    );
+    let unit = quadlet_unit("d", &args);
+    assert!(unit.contains("UserNS=keep-id\n"));
+    assert!(unit.contains("User=1000\nGroup=100\n"));
+    assert!(unit.contains("Image=node:22\n"));
+    assert!(!unit.contains("--user"));
+}
+
+// ---------------------------------------------------------------------------
+// systemd_unit
+// ---------------------------------------------------------------------------
+
+#[test]
+fn systemd_unit_wraps_engine_run() {
+    let unit = systemd_unit("d", "docker", "darp_shop_api", &sample_run_args());
+
+    assert!(unit.contains("Requires=docker.service\n"));
+    assert!(unit.contains("ExecStartPre=-docker rm -f darp_shop_api\n"));
+    assert!(unit.contains("ExecStart=docker run --rm --name darp_shop_api "));
+    assert!(unit.contains("\"cd /app; npm start\"\n"));
+    assert!(unit.contains("ExecStop=docker stop darp_shop_api\n"));
+}
+
+#[test]
+fn systemd_unit_escapes_dollar_for_execstart() {
+    let args = run_args(&["--rm", "alpine", "sh", "-c", "echo $HOME"]);
+    let unit = systemd_unit("d", "podman", "x", &args);
+    assert!(unit.contains("\"echo $$HOME\""));
+    assert!(unit.contains("After=network-online.target\n"));
+}
+
+#[test]
+fn units_leave_restarting_to_systemd() {
+    let args = run_args(&["--restart=always", "--name", "x", "alpine"]);
+    assert!(!quadlet_unit("d", &args).contains("--restart"));
+    let unit = systemd_unit("d", "podman", "x", &args);
+    assert!(unit.contains("ExecStart=podman run --name x alpine\n"));
+    assert!(unit.contains("Restart=always\n"));
+}
+
+// ---------------------------------------------------------------------------
+// compose_file
+// ---------------------------------------------------------------------------
+
+fn api_plan() -> RunPlan {
+    let mut plan = RunPlan::new(
+        "darp_shop_api",
+        run_args(&[
+            "--restart=unless-stopped",
+            "--label",
+            "darp.managed=true",
+            "--network",
+            "darp",
+            "--network",
+            "shop_default",
+            "--health-cmd",
+            "curl -f localhost:8000",
+            "--health-interval",
+            "5s",
+            "--memory",
+            "512m",
+        ]),
+    );
+    plan.image = "node:22".to_string();
+    plan.mount("/home/me/shop/api", "/app");
+    plan.mount("/home/me/.npmrc", "/root/.npmrc");
+    plan.ports.push("50100:8000".to_string());
+    plan.env_files.push("/home/me/shop/.env".to_string());
+    plan.env.push("GREETING=hello $USER".to_string());
+    plan.env.push("API_KEY".to_string());
+    plan.command = run_args(&["sh", "-c", "cd /app; npm start"]);
+    plan
+}
+
+#[test]
+fn compose_maps_the_serve_plan() {
+    let file = compose_file(
+        "Shop",
+        &[("api".to_string(), api_plan())],
+        Path::new("/home/me/shop"),
+        false,
+    );
+
+    assert!(file.contains("name: \"shop\"\nservices:\n  api:\n"));
+    assert!(file.contains("    image: \"node:22\"\n"));
+    assert!(file.contains("    restart: \"unless-stopped\"\n"));
+    assert!(file.contains("    command: [\"sh\", \"-c\", \"cd /app; npm start\"]\n"));
+    assert!(file.contains("      - \"./api:/app\"\n"));
+    assert!(file.contains("      - \"/home/me/.npmrc:/root/.npmrc\"\n"));
+    assert!(file.contains("    ports:\n      - \"50100:8000\"\n"));
+    assert!(file.contains("    env_file:\n      - \"./.env\"\n"));
+    assert!(file.contains("      - \"API_KEY\"\n"));
+    assert!(file.contains("    mem_limit: \"512m\"\n"));
+    assert!(file.contains("      test: [\"CMD-SHELL\", \"curl -f localhost:8000\"]\n"));
+    assert!(file.contains("      interval: \"5s\"\n"));
+}
+
+#[test]
+fn compose_escapes_dollar() {
+    let file = compose_file(
+        "shop",
+        &[("api".to_string(), api_plan())],
+        Path::new("/"),
+        false,
+    );
+    assert!(file.contains("\"GREETING=hello $$USER\""));
+}
+
+#[test]
+fn compose_leaves_out_darp_labels_and_network() {
+    let file = compose_file(
+        "shop",
+        &[("api".to_string(), api_plan())],
+        Path::new("/"),
+        false,
+    );
+    assert!(!file.contains("darp.managed"));
+    assert!(!file.contains("\"darp\""));
+    assert!(file.contains("    networks:\n      - \"default\"\n      - \"shop_default\"\n"));
+    assert!(file.ends_with("networks:\n  shop_default:\n    external: true\n"));
+}
+
+#[test]
+fn compose_without_options_has_only_the_basics() {
+    let mut plan = RunPlan::new("darp_shop_web", run_args(&["--rm"]));
+    plan.image = "nginx".to_string();
+    let file = compose_file("shop", &[("web".to_string(), plan)], Path::new("/"), false);
+    assert!(file.ends_with("services:\n  web:\n    image: \"nginx\"\n"));
+}
+
+#[test]
+fn compose_declares_named_volumes_under_their_own_names() {
+    let mut plan = api_plan();
+    plan.mount("shop-node-modules", "/app/node_modules");
+    let file = compose_file(
+        "shop",
+        &[("api".to_string(), plan)],
+        Path::new("/home/me/shop"),
+        false,
+    );
+    assert!(file.contains("      - \"shop-node-modules:/app/node_modules\"\n"));
+    assert!(file.contains("volumes:\n  shop-node-modules:\n    name: \"shop-node-modules\"\n"));
+}
+
+#[test]
+fn compose_leaves_out_options_it_has_no_key_for() {
+    let mut plan = api_plan();
+    plan.options.push("--oom-score-adj=5".to_string());
+    let file = compose_file("shop", &[("api".to_string(), plan)], Path::new("/"), false);
+    assert!(!file.contains("oom"));
+    assert!(file.contains("    mem_limit: \"512m\"\n"));
+}
+
+#[test]
+fn compose_maps_flag_equals_value_options() {
+    let mut plan = api_plan();
+    plan.options.extend(run_args(&[
+        "--cap-add=SYS_PTRACE",
+        "--network=devnet",
+        "--add-host=registry.test:10.0.0.5",
+        "--security-opt=label=disable",
+        "--shm-size=1g",
+        "--env=HTTPS_PROXY=http://proxy:3128",
+        "--init",
+    ]));
+    let file = compose_file("shop", &[("api".to_string(), plan)], Path::new("/"), false);
+    assert!(file.contains("    cap_add:\n      - \"SYS_PTRACE\"\n"));
+    assert!(file.contains(
+        "    networks:\n      - \"default\"\n      - \"shop_default\"\n      - \"devnet\"\n"
+    ));
+    assert!(file.contains("    extra_hosts:\n      - \"registry.test:10.0.0.5\"\n"));
+    assert!(file.contains("    security_opt:\n      - \"label=disable\"\n"));
+    assert!(file.contains("    shm_size: \"1g\"\n"));
+    assert!(file.contains("      - \"HTTPS_PROXY=http://proxy:3128\"\n"));
+    assert!(file.contains("    init: true\n"));
+    assert!(file.contains("  devnet:\n    external: true\n"));
+}
+
+#[test]
+fn compose_keeps_the_entrypoint_before_the_command() {
+    let mut plan = api_plan();
+    plan.option(["--entrypoint", "/bin/bash"]);
+    plan.command = run_args(&["-c", "npm start"]);
+    let file = compose_file("shop", &[("api".to_string(), plan)], Path::new("/"), false);
+    assert!(
+        file.contains("    entrypoint: [\"/bin/bash\"]\n    command: [\"-c\", \"npm start\"]\n")
+    );
+}
+
+#[test]
+fn compose_maps_the_host_user() {
+    let mut plan = api_plan();
+    plan.options.push("--userns=keep-id".to_string());
+    plan.option(["--user", "1000:100"]);
+    let file = compose_file("shop", &[("api".to_string(), plan)], Path::new("/"), true);
+    assert!(file.contains("    user: \"${DARP_UID:-1000}:${DARP_GID:-100}\"\n"));
+    assert!(file.contains("    userns_mode: \"keep-id\"\n"));
+}
+
+#[test]
+fn compose_leaves_out_userns_mode_unless_podman() {
+    let mut plan = api_plan();
+    plan.options.push("--userns=keep-id".to_string());
+    plan.option(["--user", "node"]);
+    let file = compose_file("shop", &[("api".to_string(), plan)], Path::new("/"), false);
+    assert!(file.contains("    user: \"node\"\n"));
+    assert!(!file.contains("userns_mode"));
+}
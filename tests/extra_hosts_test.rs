@@ -0,0 +1,71 @@
+use darp::config::{Config, validate_extra_host};
+use darp::deploy::build_container_hosts;
+use darp::os::build_hosts_content;
+
+// ---------------------------------------------------------------------------
+// Validation
+// ---------------------------------------------------------------------------
+
+#[test]
+fn extra_hosts_need_a_hostname_and_an_ip() {
+    validate_extra_host("cdn.myapp.test", "10.0.0.5").unwrap();
+    validate_extra_host("minio", "::1").unwrap();
+    assert!(validate_extra_host("cdn.myapp.test", "10.0.0").is_err());
+    assert!(validate_extra_host("cdn myapp", "10.0.0.5").is_err());
+    assert!(validate_extra_host("-cdn", "10.0.0.5").is_err());
+    assert!(validate_extra_host("cdn.", "10.0.0.5").is_err());
+    assert!(validate_extra_host("", "10.0.0.5").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Config extra hosts
+// ---------------------------------------------------------------------------
+
+#[test]
+fn extra_host_lines_list_global_entries_then_each_domain() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_extra_host("registry.test", "192.168.1.20").unwrap();
+    c.add_domain_extra_host("shop", "cdn.shop.test", "10.0.0.5")
+        .unwrap();
+    // Re-adding a host replaces its IP.
+    c.add_domain_extra_host("shop", "cdn.shop.test", "10.0.0.6")
+        .unwrap();
+
+    assert_eq!(
+        c.extra_host_lines(),
+        vec![
+            "192.168.1.20   registry.test\n".to_string(),
+            "10.0.0.6   cdn.shop.test\n".to_string(),
+        ]
+    );
+
+    c.rm_domain_extra_host("shop", "cdn.shop.test").unwrap();
+    assert!(c.rm_domain_extra_host("shop", "cdn.shop.test").is_err());
+    assert!(c.domains.as_ref().unwrap()["shop"].extra_hosts.is_none());
+    c.rm_extra_host("registry.test").unwrap();
+    assert!(c.rm_extra_host("registry.test").is_err());
+    assert!(c.extra_hosts.is_none());
+    assert!(
+        c.add_domain_extra_host("blog", "a.test", "10.0.0.1")
+            .is_err()
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Hosts files
+// ---------------------------------------------------------------------------
+
+#[test]
+fn extra_hosts_keep_their_ip_in_both_hosts_files() {
+    let lines = vec![
+        "0.0.0.0   api.shop.test\n".to_string(),
+        "10.0.0.5   cdn.shop.test\n".to_string(),
+    ];
+    let container = build_container_hosts("10.0.2.2", "host.docker.internal", &lines);
+    assert!(container.ends_with("0.0.0.0   api.shop.test\n10.0.0.5   cdn.shop.test\n"));
+
+    let system = build_hosts_content("127.0.0.1 localhost\n", &lines);
+    assert!(system.contains("127.0.0.1   api.shop.test\n"), "{}", system);
+    assert!(system.contains("10.0.0.5   cdn.shop.test\n"), "{}", system);
+}
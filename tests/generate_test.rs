@@ -0,0 +1,67 @@
+use darp::commands::{editor_tasks, jetbrains_run_config, vscode_tasks};
+
+// ---------------------------------------------------------------------------
+// editor_tasks
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tasks_pass_the_environment_to_serve_and_shell() {
+    assert_eq!(
+        editor_tasks(Some("go")),
+        [
+            ("serve", "darp serve -e go".to_string()),
+            ("shell", "darp shell -e go".to_string()),
+        ]
+    );
+    assert_eq!(editor_tasks(None)[0].1, "darp serve");
+    assert_eq!(editor_tasks(Some("my env"))[1].1, "darp shell -e 'my env'");
+}
+
+// ---------------------------------------------------------------------------
+// vscode_tasks
+// ---------------------------------------------------------------------------
+
+#[test]
+fn vscode_tasks_start_a_new_file() {
+    let tasks = vscode_tasks(None, Some("go"));
+    assert_eq!(tasks["version"], "2.0.0");
+    assert_eq!(tasks["tasks"][0]["label"], "darp: serve");
+    assert_eq!(tasks["tasks"][0]["command"], "darp serve -e go");
+    assert_eq!(tasks["tasks"][0]["isBackground"], true);
+    assert_eq!(tasks["tasks"][1]["label"], "darp: shell");
+    assert_eq!(tasks["tasks"][1]["presentation"]["focus"], true);
+}
+
+#[test]
+fn vscode_tasks_keep_other_tasks_and_replace_darps() {
+    let existing = serde_json::json!({
+        "version": "2.0.0",
+        "inputs": [],
+        "tasks": [
+            {"label": "build", "type": "shell", "command": "make"},
+            {"label": "darp: serve", "type": "shell", "command": "darp serve -e old"},
+        ],
+    });
+    let tasks = vscode_tasks(Some(&existing), Some("go"));
+    let labels: Vec<&str> = tasks["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["label"].as_str().unwrap())
+        .collect();
+    assert_eq!(labels, ["build", "darp: serve", "darp: shell"]);
+    assert_eq!(tasks["tasks"][1]["command"], "darp serve -e go");
+    assert_eq!(tasks["inputs"], serde_json::json!([]));
+}
+
+// ---------------------------------------------------------------------------
+// jetbrains_run_config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn jetbrains_run_config_runs_the_command_in_a_terminal() {
+    let xml = jetbrains_run_config("darp serve", "darp serve -e 'a&b'");
+    assert!(xml.contains(r#"name="darp serve" type="ShConfigurationType""#));
+    assert!(xml.contains(r#"<option name="SCRIPT_TEXT" value="darp serve -e 'a&amp;b'" />"#));
+    assert!(xml.contains(r#"<option name="EXECUTE_IN_TERMINAL" value="true" />"#));
+}
@@ -0,0 +1,104 @@
+use clap::Parser;
+use darp::cli::{Cli, Command};
+use darp::commands::{GIT_HOOK_COMMAND, with_git_hook_block, without_git_hook_block};
+use darp::config::{Config, DarpPaths};
+use darp::deploy::{scanned_services, service_folders_changed};
+use darp::scan::ScanManifest;
+
+// ---------------------------------------------------------------------------
+// with_git_hook_block / without_git_hook_block
+// ---------------------------------------------------------------------------
+
+#[test]
+fn new_hook_gets_a_shebang_and_the_registration() {
+    let hook = with_git_hook_block("");
+    assert!(hook.starts_with("#!/bin/sh\n# >>> darp post-checkout >>>\n"));
+    assert!(hook.contains("  darp --yes add-service --new || "));
+    assert!(hook.ends_with("# <<< darp post-checkout <<<\n"));
+}
+
+#[test]
+fn hook_only_registers_new_services_and_never_stops_containers() {
+    // A deploy stops every running service container; the hook must not run one.
+    let hook = with_git_hook_block("");
+    let runs: Vec<&str> = hook
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with("darp "))
+        .collect();
+    assert_eq!(runs.len(), 1);
+    assert!(runs[0].starts_with(GIT_HOOK_COMMAND));
+
+    let cli = Cli::try_parse_from(GIT_HOOK_COMMAND.split_whitespace()).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::AddService { new: true })
+    ));
+}
+
+#[test]
+fn existing_hook_keeps_its_commands() {
+    let existing = "#!/bin/sh\nnpm install";
+    let hook = with_git_hook_block(existing);
+    assert!(hook.starts_with("#!/bin/sh\nnpm install\n# >>> darp post-checkout >>>\n"));
+    assert_eq!(
+        without_git_hook_block(&hook).as_deref(),
+        Some("#!/bin/sh\nnpm install\n")
+    );
+}
+
+#[test]
+fn installing_twice_leaves_one_block() {
+    let once = with_git_hook_block("#!/bin/sh\necho hi\n");
+    assert_eq!(with_git_hook_block(&once), once);
+}
+
+#[test]
+fn removing_the_only_block_leaves_nothing() {
+    assert_eq!(without_git_hook_block(&with_git_hook_block("")), None);
+}
+
+// ---------------------------------------------------------------------------
+// scanned_services / service_folders_changed
+// ---------------------------------------------------------------------------
+
+fn shop(dir: &std::path::Path) -> Config {
+    let location = dir.join("shop");
+    std::fs::create_dir_all(location.join("api")).unwrap();
+    std::fs::create_dir_all(location.join("web")).unwrap();
+    std::fs::write(location.join("README.md"), "").unwrap();
+    let mut config = Config::default();
+    config
+        .add_domain("shop", &location.to_string_lossy())
+        .unwrap();
+    config
+}
+
+#[test]
+fn scanned_services_lists_service_folders() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = shop(dir.path());
+    let service = |name: &str| ("shop".to_string(), ".".to_string(), name.to_string());
+    assert_eq!(
+        scanned_services(&config, &mut ScanManifest::default()).unwrap(),
+        vec![service("api"), service("web")]
+    );
+}
+
+#[test]
+fn service_folders_changed_compares_with_the_portmap() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = shop(dir.path());
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+    assert!(service_folders_changed(&paths, &config).unwrap());
+
+    let portmap = serde_json::json!({"shop": {".": {
+        "api": {"port": 50100, "type": "http"},
+        "web": {"port": 50101, "type": "http"},
+    }}});
+    std::fs::write(&paths.portmap_path, portmap.to_string()).unwrap();
+    assert!(!service_folders_changed(&paths, &config).unwrap());
+
+    std::fs::create_dir_all(dir.path().join("shop").join("admin")).unwrap();
+    assert!(service_folders_changed(&paths, &config).unwrap());
+}
@@ -0,0 +1,23 @@
+use darp::deploy::grpc_vhost;
+
+// ---------------------------------------------------------------------------
+// grpc_vhost
+// ---------------------------------------------------------------------------
+
+#[test]
+fn grpc_vhost_moves_host_route_to_the_http2_listener() {
+    let vhost = "server {\n    listen 80;\n    server_name api.shop.test;\n    location / {\n        proxy_pass http://host.docker.internal:50100/;\n        proxy_set_header Host $host;\n        proxy_http_version 1.1;\n        proxy_set_header Upgrade $http_upgrade;\n        proxy_set_header Connection $connection_upgrade;\n        proxy_read_timeout 1h;\n        proxy_send_timeout 1h;\n    }\n}\n";
+    assert_eq!(
+        grpc_vhost(vhost),
+        "server {\n    listen 50051;\n    http2 on;\n    server_name api.shop.test;\n    location / {\n        grpc_pass grpc://host.docker.internal:50100;\n        grpc_set_header Host $host;\n        grpc_read_timeout 1h;\n        grpc_send_timeout 1h;\n    }\n}\n"
+    );
+}
+
+#[test]
+fn grpc_vhost_rewrites_the_network_upstream_variable() {
+    let vhost = "server {\n    listen 80;\n    resolver 127.0.0.11 valid=10s;\n    location / {\n        set $darp_upstream http://darp_shop_api:8000;\n        proxy_pass $darp_upstream;\n    }\n}\n";
+    let out = grpc_vhost(vhost);
+    assert!(out.contains("set $darp_upstream grpc://darp_shop_api:8000;"));
+    assert!(out.contains("grpc_pass $darp_upstream;"));
+    assert!(!out.contains("proxy_pass"));
+}
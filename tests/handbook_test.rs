@@ -0,0 +1,110 @@
+use darp::config::Config;
+use darp::urls::{render_urls_html, render_urls_json, render_urls_markdown, render_urls_plain};
+
+fn fixture() -> (serde_json::Value, Config) {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": { "api": { "port": 50100, "type": "http", "debug_port": 13000 } },
+            "workers": { "queue": { "port": 50101, "type": "tcp", "debug_port": 13001 } }
+        }
+    });
+    let mut config = Config::default();
+    config.add_domain("shop", "/tmp/shop").unwrap();
+    config
+        .set_service_description("shop", ".", "api", "Public API | v2 <beta>")
+        .unwrap();
+    (portmap, config)
+}
+
+#[test]
+fn markdown_lists_services_with_descriptions_per_group() {
+    let (portmap, config) = fixture();
+    let md = render_urls_markdown(&portmap, &config);
+
+    assert!(md.starts_with("# darp services\n"));
+    assert!(md.contains("\n## shop\n"));
+    assert!(md.contains("| api | <http://api.shop.test> | Public API \\| v2 <beta> |\n"));
+    assert!(md.contains("\n### workers\n"));
+    assert!(md.contains("| queue | <tcp://queue.shop.test:50101> |  |\n"));
+    // The default group comes before named groups
+    assert!(md.find("| api |").unwrap() < md.find("### workers").unwrap());
+}
+
+#[test]
+fn html_escapes_descriptions_and_links_only_browsable_urls() {
+    let (portmap, config) = fixture();
+    let html = render_urls_html(&portmap, &config);
+
+    assert!(html.contains(
+        "<td><a href=\"http://api.shop.test\">http://api.shop.test</a></td><td>Public API | v2 &lt;beta&gt;</td>"
+    ));
+    assert!(html.contains("<td>tcp://queue.shop.test:50101</td>"));
+    assert!(html.contains("<h3>workers</h3>"));
+}
+
+// ---------------------------------------------------------------------------
+// darp urls --json / --plain
+// ---------------------------------------------------------------------------
+
+#[test]
+fn json_maps_domain_and_service_to_url_and_port() {
+    let (mut portmap, _) = fixture();
+    portmap["shop"]["."]["legacy"] = serde_json::json!(50102);
+
+    assert_eq!(
+        render_urls_json(&portmap),
+        serde_json::json!({
+            "shop": {
+                "api": { "url": "http://api.shop.test", "port": 50100 },
+                "queue": { "url": "tcp://queue.shop.test:50101", "port": 50101 },
+                "legacy": { "url": "http://legacy.shop.test", "port": 50102 }
+            }
+        })
+    );
+}
+
+#[test]
+fn plain_prints_one_sorted_line_per_service() {
+    let (portmap, _) = fixture();
+    assert_eq!(
+        render_urls_plain(&portmap),
+        "http://api.shop.test 50100\ntcp://queue.shop.test:50101 50101\n"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// darp urls [domain] --filter
+// ---------------------------------------------------------------------------
+
+#[test]
+fn glob_match_supports_star_and_question_mark() {
+    use darp::config::glob_match;
+    assert!(glob_match("api*", "api-gateway"));
+    assert!(glob_match("*.shop.test", "api.shop.test"));
+    assert!(glob_match("we?", "web"));
+    assert!(glob_match("*a*b*", "xxaxxbxx"));
+    assert!(!glob_match("api*", "web-api"));
+    assert!(!glob_match("we?", "webs"));
+}
+
+#[test]
+fn filter_portmap_narrows_by_domain_and_pattern() {
+    use darp::config::filter_portmap;
+    let portmap = serde_json::json!({
+        "shop": { ".": { "api": 50100, "web": 50101 }, "workers": { "queue": 50102 } },
+        "blog": { ".": { "api": 50103 } }
+    });
+
+    assert_eq!(
+        filter_portmap(&portmap, Some("shop"), Some("api")),
+        serde_json::json!({ "shop": { ".": { "api": 50100 } } })
+    );
+    assert_eq!(
+        filter_portmap(&portmap, None, Some("*.blog.test")),
+        serde_json::json!({ "blog": { ".": { "api": 50103 } } })
+    );
+    assert_eq!(
+        filter_portmap(&portmap, Some("shop"), None)["shop"],
+        portmap["shop"]
+    );
+}
@@ -0,0 +1,74 @@
+use darp::config::{Config, portmap_tcp_port};
+
+fn config() -> Config {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c
+}
+
+fn host_port(c: &Config, service: &str) -> Option<u16> {
+    c.domains.as_ref().unwrap()["shop"]
+        .groups
+        .as_ref()
+        .and_then(|g| g.get("."))
+        .and_then(|g| g.services.as_ref())
+        .and_then(|s| s.get(service))
+        .and_then(|s| s.host_port)
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn set_and_rm_host_port() {
+    let mut c = config();
+    c.set_service_host_port("shop", ".", "web", 3000).unwrap();
+    assert_eq!(host_port(&c, "web"), Some(3000));
+
+    c.set_service_host_port("shop", ".", "web", 5173).unwrap();
+    assert_eq!(host_port(&c, "web"), Some(5173));
+
+    c.rm_service_host_port("shop", ".", "web").unwrap();
+    assert_eq!(host_port(&c, "web"), None);
+    let err = c.rm_service_host_port("shop", ".", "web").unwrap_err();
+    assert!(err.to_string().contains("has no host port"), "{}", err);
+}
+
+#[test]
+fn host_port_rejects_ports_darp_listens_on() {
+    let mut c = config();
+    for port in [0, 53, 80, 443, 50051] {
+        assert!(
+            c.set_service_host_port("shop", ".", "web", port).is_err(),
+            "{}",
+            port
+        );
+    }
+    assert_eq!(host_port(&c, "web"), None);
+}
+
+#[test]
+fn host_port_round_trips_through_json() {
+    let mut c = config();
+    c.set_service_host_port("shop", ".", "web", 3000).unwrap();
+    let json = serde_json::to_string(&c).unwrap();
+    assert!(json.contains("\"host_port\":3000"), "{}", json);
+    let back: Config = serde_json::from_str(&json).unwrap();
+    assert_eq!(host_port(&back, "web"), Some(3000));
+}
+
+// ---------------------------------------------------------------------------
+// Portmap
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tcp_clients_dial_the_host_port_of_a_host_run_service() {
+    let entry = serde_json::json!({ "port": 50100, "type": "tcp", "host_port": 5432 });
+    assert_eq!(portmap_tcp_port(&entry), 5432);
+
+    let entry = serde_json::json!({
+        "port": 50100, "type": "tcp", "host_port": 5432, "stream_port": 15432
+    });
+    assert_eq!(portmap_tcp_port(&entry), 15432);
+}
@@ -1,4 +1,8 @@
-use darp::os::{HOSTS_FOOTER, HOSTS_HEADER, build_hosts_content};
+use darp::config::{Config, Remote};
+use darp::os::{
+    HOSTS_FOOTER, HOSTS_HEADER, Sandbox, build_hosts_content, detect_sandbox_with,
+    resolver_nameserver,
+};
 
 fn lines(entries: &[&str]) -> Vec<String> {
     entries.iter().map(|s| s.to_string()).collect()
@@ -70,6 +74,20 @@ fn crlf_normalization() {
     assert!(result.contains("127.0.0.1   app.test"));
 }
 
+#[test]
+fn keeps_non_wildcard_addresses() {
+    let result = build_hosts_content(
+        "",
+        &lines(&[
+            "192.168.1.20   api.shop.test\n",
+            "0.0.0.0   web.shop.test\n",
+        ]),
+    );
+
+    assert!(result.contains("192.168.1.20   api.shop.test"));
+    assert!(result.contains("127.0.0.1   web.shop.test"));
+}
+
 #[test]
 fn empty_entries() {
     let result = build_hosts_content("127.0.0.1   localhost\n", &[]);
@@ -82,3 +100,62 @@ fn empty_entries() {
     let count = result.matches("127.0.0.1").count();
     assert_eq!(count, 1);
 }
+
+// ---------------------------------------------------------------------------
+// detect_sandbox_with
+// ---------------------------------------------------------------------------
+
+fn env_of<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    move |name| {
+        vars.iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.to_string())
+    }
+}
+
+#[test]
+fn detects_codespaces_before_devcontainer() {
+    let vars = [("CODESPACES", "true"), ("REMOTE_CONTAINERS", "true")];
+    assert_eq!(
+        detect_sandbox_with(env_of(&vars), |_| true),
+        Some(Sandbox::Codespaces)
+    );
+}
+
+#[test]
+fn detects_devcontainer_from_env() {
+    let vars = [("REMOTE_CONTAINERS", "true")];
+    assert_eq!(
+        detect_sandbox_with(env_of(&vars), |_| false),
+        Some(Sandbox::Devcontainer)
+    );
+}
+
+#[test]
+fn detects_plain_container_from_marker_files() {
+    assert_eq!(
+        detect_sandbox_with(env_of(&[]), |p| p == "/run/.containerenv"),
+        Some(Sandbox::Container)
+    );
+    assert_eq!(detect_sandbox_with(env_of(&[]), |_| false), None);
+}
+
+// ---------------------------------------------------------------------------
+// resolver_nameserver
+// ---------------------------------------------------------------------------
+
+#[test]
+fn resolver_points_at_remote_only_when_enabled() {
+    let mut config = Config::default();
+    assert_eq!(resolver_nameserver(&config), "127.0.0.1");
+
+    config.remote = Some(Remote {
+        host: "devbox".into(),
+        address: "10.0.0.5".into(),
+        ..Default::default()
+    });
+    assert_eq!(resolver_nameserver(&config), "127.0.0.1");
+
+    config.remote.as_mut().unwrap().resolver = Some(true);
+    assert_eq!(resolver_nameserver(&config), "10.0.0.5");
+}
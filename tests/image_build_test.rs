@@ -0,0 +1,115 @@
+use darp::commands::{image_build_dockerfile, image_build_tag};
+use darp::config::{Config, Domain, ImageBuild};
+
+fn recipe() -> ImageBuild {
+    ImageBuild {
+        base: "php:8.3-cli".into(),
+        packages: vec!["git".into(), "unzip".into()],
+        run: vec!["pecl install xdebug".into()],
+    }
+}
+
+// ---------------------------------------------------------------------------
+// image_build_dockerfile
+// ---------------------------------------------------------------------------
+
+#[test]
+fn dockerfile_starts_from_the_base_and_runs_each_command() {
+    let dockerfile = image_build_dockerfile(&recipe());
+    let lines: Vec<&str> = dockerfile.lines().collect();
+    assert_eq!(lines[0], "FROM php:8.3-cli");
+    assert_eq!(lines.last(), Some(&"RUN pecl install xdebug"));
+}
+
+#[test]
+fn dockerfile_installs_packages_with_any_supported_manager() {
+    let dockerfile = image_build_dockerfile(&recipe());
+    for install in [
+        "apk add --no-cache git unzip",
+        "--no-install-recommends git unzip",
+        "dnf install -y git unzip",
+        "microdnf install -y git unzip",
+    ] {
+        assert!(
+            dockerfile.contains(install),
+            "missing {install}:\n{dockerfile}"
+        );
+    }
+}
+
+#[test]
+fn dockerfile_without_packages_has_no_install_step() {
+    let build = ImageBuild {
+        base: "alpine".into(),
+        ..Default::default()
+    };
+    assert_eq!(image_build_dockerfile(&build), "FROM alpine\n");
+}
+
+// ---------------------------------------------------------------------------
+// image_build_tag
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tag_is_stable_for_the_same_recipe() {
+    let tag = image_build_tag("shop", "api", &recipe());
+    assert_eq!(tag, image_build_tag("shop", "api", &recipe()));
+    assert!(tag.starts_with("darp-build/shop_api:"), "{tag}");
+}
+
+#[test]
+fn tag_changes_with_the_recipe() {
+    let mut changed = recipe();
+    changed.packages.push("curl".into());
+    assert_ne!(
+        image_build_tag("shop", "api", &recipe()),
+        image_build_tag("shop", "api", &changed)
+    );
+}
+
+#[test]
+fn tag_is_a_valid_lowercase_image_name() {
+    let tag = image_build_tag("My Shop", "API", &recipe());
+    let (name, _) = tag.split_once(':').unwrap();
+    assert_eq!(name, "darp-build/my_shop_api");
+}
+
+// ---------------------------------------------------------------------------
+// Config::set_service_image_build / rm_service_image_build
+// ---------------------------------------------------------------------------
+
+#[test]
+fn image_build_lifecycle() {
+    let mut c = Config {
+        domains: Some(
+            [(
+                "d".to_string(),
+                Domain {
+                    location: "/tmp/d".into(),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+        ),
+        ..Default::default()
+    };
+    c.set_service_image_build("d", ".", "svc", recipe())
+        .unwrap();
+
+    let json = serde_json::to_value(&c).unwrap();
+    let saved = &json["domains"]["d"]["groups"]["."]["services"]["svc"]["image_build"];
+    assert_eq!(saved["base"], "php:8.3-cli");
+    assert_eq!(saved["packages"], serde_json::json!(["git", "unzip"]));
+
+    c.rm_service_image_build("d", ".", "svc").unwrap();
+    assert!(c.rm_service_image_build("d", ".", "svc").is_err());
+}
+
+#[test]
+fn image_build_needs_a_base() {
+    let mut c = Config::default();
+    let err = c
+        .set_service_image_build("d", ".", "svc", ImageBuild::default())
+        .unwrap_err();
+    assert!(err.to_string().contains("base image"));
+}
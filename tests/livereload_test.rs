@@ -0,0 +1,61 @@
+use darp::deploy::live_reload_vhost;
+use darp::livereload::{text_frame, tree_fingerprint, websocket_accept};
+
+// ---------------------------------------------------------------------------
+// Websocket handshake and frames
+// ---------------------------------------------------------------------------
+
+#[test]
+fn accept_key_matches_rfc_6455_example() {
+    assert_eq!(
+        websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[test]
+fn text_frame_is_unmasked_and_length_prefixed() {
+    assert_eq!(text_frame("reload"), b"\x81\x06reload".to_vec());
+
+    let long = "x".repeat(300);
+    let frame = text_frame(&long);
+    assert_eq!(&frame[..4], &[0x81, 126, 0x01, 0x2c]);
+    assert_eq!(frame.len(), 304);
+}
+
+// ---------------------------------------------------------------------------
+// Change detection
+// ---------------------------------------------------------------------------
+
+#[test]
+fn fingerprint_changes_on_new_files_but_ignores_dependency_folders() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("index.html"), "<body></body>").unwrap();
+    let before = tree_fingerprint(dir.path());
+
+    std::fs::create_dir(dir.path().join("node_modules")).unwrap();
+    std::fs::write(dir.path().join("node_modules/dep.js"), "").unwrap();
+    assert_eq!(tree_fingerprint(dir.path()), before);
+
+    std::fs::create_dir(dir.path().join("css")).unwrap();
+    std::fs::write(dir.path().join("css/site.css"), "body {}").unwrap();
+    assert_ne!(tree_fingerprint(dir.path()), before);
+}
+
+// ---------------------------------------------------------------------------
+// Proxy vhost
+// ---------------------------------------------------------------------------
+
+#[test]
+fn vhost_routes_darp_paths_to_the_host_and_injects_the_script() {
+    let vhost = "server {\n    listen 80;\n    server_name web.shop.test;\n    location / {\n        proxy_pass http://host.docker.internal:50100/;\n    }\n}\n";
+    let out = live_reload_vhost(vhost, "host.docker.internal");
+
+    assert!(out.contains(
+        "    location /__darp/ {\n        proxy_pass http://host.docker.internal:35729;\n"
+    ));
+    assert!(out.contains(
+        "    location / {\n        proxy_set_header Accept-Encoding \"\";\n        sub_filter '</body>' '<script src=\"/__darp/livereload.js\"></script></body>';\n"
+    ));
+    assert!(out.find("location /__darp/").unwrap() < out.find("location / {").unwrap());
+}
@@ -0,0 +1,46 @@
+use std::process::Command;
+
+use darp::engine::command_line;
+use darp::logging::level_filter;
+
+// ---------------------------------------------------------------------------
+// level_filter
+// ---------------------------------------------------------------------------
+
+#[test]
+fn default_level_shows_notes_and_warnings() {
+    assert_eq!(level_filter(0, false), "info");
+}
+
+#[test]
+fn each_verbose_flag_shows_more() {
+    assert_eq!(level_filter(1, false), "debug");
+    assert_eq!(level_filter(2, false), "trace");
+    assert_eq!(level_filter(5, false), "trace");
+}
+
+#[test]
+fn quiet_shows_only_errors() {
+    assert_eq!(level_filter(0, true), "error");
+}
+
+// ---------------------------------------------------------------------------
+// command_line
+// ---------------------------------------------------------------------------
+
+#[test]
+fn command_line_quotes_only_what_needs_it() {
+    let mut cmd = Command::new("docker");
+    cmd.args([
+        "run",
+        "--name",
+        "darp_shop_api",
+        "sh",
+        "-c",
+        "cd /app; exec it's",
+    ]);
+    assert_eq!(
+        command_line(&cmd),
+        r"docker run --name darp_shop_api sh -c 'cd /app; exec it'\''s'"
+    );
+}
@@ -0,0 +1,49 @@
+use darp::commands::{install_man_pages, man_pages, uninstall_man_pages};
+
+// ---------------------------------------------------------------------------
+// man_pages
+// ---------------------------------------------------------------------------
+
+#[test]
+fn every_command_gets_a_page_named_after_its_path() {
+    let pages = man_pages().unwrap();
+    let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+    for expected in ["darp.1", "darp-serve.1", "darp-config-set-svc.1"] {
+        assert!(names.contains(&expected), "missing {expected}");
+    }
+    assert!(
+        !names.contains(&"darp-man.1"),
+        "hidden commands get no page"
+    );
+}
+
+#[test]
+fn pages_are_roff_with_the_command_description() {
+    let pages = man_pages().unwrap();
+    let (_, roff) = pages
+        .iter()
+        .find(|(name, _)| name == "darp-shell.1")
+        .unwrap();
+    let roff = String::from_utf8_lossy(roff);
+    assert!(roff.starts_with(".ie"), "{}", &roff[..40]);
+    assert!(roff.contains("Starts a shell instance"));
+}
+
+// ---------------------------------------------------------------------------
+// install_man_pages / uninstall_man_pages
+// ---------------------------------------------------------------------------
+
+#[test]
+fn install_then_uninstall_leaves_other_pages_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let man1 = dir.path().join("man1");
+    let written = install_man_pages(&man1).unwrap();
+    assert_eq!(written, man_pages().unwrap().len());
+    assert!(man1.join("darp.1").exists());
+
+    std::fs::write(man1.join("git.1"), "not darp's").unwrap();
+    assert_eq!(uninstall_man_pages(&man1).unwrap(), written);
+    assert!(!man1.join("darp.1").exists());
+    assert!(man1.join("git.1").exists());
+    assert_eq!(uninstall_man_pages(&man1).unwrap(), 0);
+}
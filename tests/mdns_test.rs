@@ -0,0 +1,38 @@
+use darp::mdns::{mdns_host, mdns_hosts};
+
+// ---------------------------------------------------------------------------
+// mdns_host
+// ---------------------------------------------------------------------------
+
+#[test]
+fn mdns_host_is_one_label_under_local() {
+    assert_eq!(mdns_host("shop", "api"), "api-shop.local");
+    assert_eq!(mdns_host("Shop", "web_app"), "web-app-shop.local");
+    assert_eq!(mdns_host("my.domain", "api"), "api-my-domain.local");
+}
+
+// ---------------------------------------------------------------------------
+// mdns_hosts
+// ---------------------------------------------------------------------------
+
+#[test]
+fn mdns_hosts_come_from_the_portmap() {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": {
+                "web": {"port": 50102, "mdns_host": "web-shop.local"},
+                "api": {"port": 50101, "mdns_host": "api-shop.local"},
+                "db": {"port": 50103, "type": "tcp"},
+            },
+            "admin": {"api": {"port": 50104, "mdns_host": "api-shop.local"}},
+        },
+        "blog": {".": {"site": 50105}},
+    });
+    assert_eq!(mdns_hosts(&portmap), ["api-shop.local", "web-shop.local"]);
+}
+
+#[test]
+fn mdns_hosts_are_empty_without_a_portmap() {
+    assert!(mdns_hosts(&serde_json::json!({})).is_empty());
+    assert!(mdns_hosts(&serde_json::Value::Null).is_empty());
+}
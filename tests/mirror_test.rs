@@ -0,0 +1,19 @@
+use darp::deploy::{mirror_target_host, mirror_vhost};
+
+#[test]
+fn mirror_targets_resolve_within_or_across_domains() {
+    assert_eq!(mirror_target_host("shop", "api-v2"), "api-v2.shop.test");
+    assert_eq!(mirror_target_host("shop", "legacy.api"), "api.legacy.test");
+}
+
+#[test]
+fn vhost_mirrors_requests_through_the_proxy_under_the_target_host() {
+    let vhost = "server {\n    listen 80;\n    server_name api.shop.test;\n    location / {\n        proxy_pass http://host.docker.internal:50100/;\n    }\n}\n";
+    let out = mirror_vhost(vhost, "api-v2.shop.test");
+
+    assert!(out.contains(
+        "    location / {\n        mirror /__darp_mirror;\n        mirror_request_body on;\n        proxy_pass"
+    ));
+    assert!(out.contains("    location = /__darp_mirror {\n        internal;\n"));
+    assert!(out.contains("proxy_set_header Host api-v2.shop.test;"));
+}
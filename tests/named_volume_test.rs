@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use darp::commands::{VolumeEntry, volume_entries};
+use darp::config::{self, Config};
+
+// ---------------------------------------------------------------------------
+// is_volume_name
+// ---------------------------------------------------------------------------
+
+#[test]
+fn bare_names_are_volumes_and_paths_are_not() {
+    for name in ["node-modules", "shop_cache", "pg.data", "v2"] {
+        assert!(config::is_volume_name(name), "{}", name);
+    }
+    for path in [
+        "/home/me/cache",
+        "{home}/.ssh",
+        "{pwd}",
+        "./data",
+        "..",
+        "-cache",
+        "",
+    ] {
+        assert!(!config::is_volume_name(path), "{}", path);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Config::named_volumes
+// ---------------------------------------------------------------------------
+
+#[test]
+fn named_volumes_lists_where_each_is_configured() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_volume("node", "/root/.npm", "npm-cache", false)
+        .unwrap();
+    c.add_service_volume(
+        "shop",
+        ".",
+        "api",
+        "/app/node_modules",
+        "api-modules",
+        false,
+    )
+    .unwrap();
+    c.add_service_volume("shop", ".", "web", "/root/.npm", "npm-cache", false)
+        .unwrap();
+    c.add_domain_volume("shop", "/root/.ssh", "{home}/.ssh", true)
+        .unwrap();
+
+    let named = c.named_volumes();
+    assert_eq!(named.len(), 2);
+    assert_eq!(named["api-modules"], ["shop.api"]);
+    assert_eq!(named["npm-cache"], ["env node", "shop.web"]);
+}
+
+// ---------------------------------------------------------------------------
+// volume_entries
+// ---------------------------------------------------------------------------
+
+#[test]
+fn entries_include_configured_volumes_not_created_yet() {
+    let configured = BTreeMap::from([
+        ("api-modules".to_string(), vec!["shop.api".to_string()]),
+        ("npm-cache".to_string(), vec!["env node".to_string()]),
+    ]);
+    let existing = vec!["old-cache".to_string(), "npm-cache".to_string()];
+
+    assert_eq!(
+        volume_entries(&existing, &configured),
+        [
+            VolumeEntry {
+                name: "api-modules".to_string(),
+                created: false,
+                configured_in: vec!["shop.api".to_string()],
+            },
+            VolumeEntry {
+                name: "npm-cache".to_string(),
+                created: true,
+                configured_in: vec!["env node".to_string()],
+            },
+            VolumeEntry {
+                name: "old-cache".to_string(),
+                created: true,
+                configured_in: vec![],
+            },
+        ]
+    );
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use darp::commands::{OnboardingStep, is_unconfigured, onboarding_steps, render_onboarding};
+use darp::config::{Config, DarpPaths, Domain};
+
+fn shop_config() -> Config {
+    Config {
+        engine: Some("docker".to_string()),
+        domains: Some(BTreeMap::from([(
+            "shop".to_string(),
+            Domain {
+                location: "{home}/shop".to_string(),
+                ..Default::default()
+            },
+        )])),
+        ..Default::default()
+    }
+}
+
+fn done(steps: &[OnboardingStep]) -> Vec<bool> {
+    steps.iter().map(|s| s.done).collect()
+}
+
+// ---------------------------------------------------------------------------
+// is_unconfigured
+// ---------------------------------------------------------------------------
+
+#[test]
+fn an_empty_config_is_unconfigured() {
+    assert!(is_unconfigured(&Config::default()));
+    assert!(!is_unconfigured(&shop_config()));
+    assert!(!is_unconfigured(&Config {
+        engine: Some("podman".to_string()),
+        ..Default::default()
+    }));
+}
+
+// ---------------------------------------------------------------------------
+// onboarding_steps
+// ---------------------------------------------------------------------------
+
+#[test]
+fn nothing_is_done_on_a_fresh_install() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+    let steps = onboarding_steps(&paths, &Config::default(), false);
+    assert_eq!(done(&steps), [false; 5]);
+    assert_eq!(steps[3].command, "darp deploy");
+    assert_eq!(steps[4].command, "cd <service folder> && darp serve");
+}
+
+#[test]
+fn steps_follow_the_config_and_deploy_artifacts() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+    let config = shop_config();
+    assert_eq!(
+        done(&onboarding_steps(&paths, &config, false)),
+        [true, false, true, false, false]
+    );
+
+    std::fs::create_dir_all(&paths.dnsmasq_dir).unwrap();
+    std::fs::write(paths.dnsmasq_dir.join("test.conf"), "").unwrap();
+    std::fs::write(
+        &paths.portmap_path,
+        r#"{"shop": {".": {"api": {"port": 50100}}}}"#,
+    )
+    .unwrap();
+    let steps = onboarding_steps(&paths, &config, false);
+    assert_eq!(done(&steps), [true, true, true, true, false]);
+    assert_eq!(steps[3].title, "Deploy the services in ~/shop");
+    assert_eq!(steps[4].command, "cd ~/shop/api && darp serve");
+
+    assert_eq!(done(&onboarding_steps(&paths, &config, true)), [true; 5]);
+}
+
+// ---------------------------------------------------------------------------
+// render_onboarding
+// ---------------------------------------------------------------------------
+
+fn step(title: &str, command: &str, done: bool) -> OnboardingStep {
+    OnboardingStep {
+        title: title.to_string(),
+        command: command.to_string(),
+        done,
+    }
+}
+
+#[test]
+fn checklist_shows_commands_for_remaining_steps_and_the_next_one() {
+    colored::control::set_override(false);
+    let steps = [
+        step(
+            "Choose a container engine",
+            "darp config set engine docker",
+            true,
+        ),
+        step(
+            "Install the resolver and reverse proxy",
+            "darp install",
+            false,
+        ),
+        step("Deploy", "darp deploy", false),
+    ];
+    assert_eq!(
+        render_onboarding(&steps),
+        "Getting started with darp:\n\
+         \x20 [x] Choose a container engine\n\
+         \x20 [ ] Install the resolver and reverse proxy\n\
+         \x20       darp install\n\
+         \x20 [ ] Deploy\n\
+         \x20       darp deploy\n\
+         \n\
+         Next: darp install\n"
+    );
+}
+
+#[test]
+fn finished_checklist_says_so() {
+    colored::control::set_override(false);
+    let out = render_onboarding(&[step("Serve a service", "darp serve", true)]);
+    assert!(out.ends_with("\nAll set. 'darp urls' lists every service's address.\n"));
+    assert!(!out.contains("Next:"));
+}
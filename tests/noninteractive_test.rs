@@ -0,0 +1,35 @@
+use darp::config::{Config, DarpPaths};
+use darp::engine::EngineKind;
+use darp::os::{self, OsIntegration, truthy_env_value};
+
+// ---------------------------------------------------------------------------
+// DARP_NONINTERACTIVE
+// ---------------------------------------------------------------------------
+
+#[test]
+fn env_values_that_turn_non_interactive_mode_on() {
+    for on in ["1", "true", "yes", "YES", "y"] {
+        assert!(truthy_env_value(on), "{on:?}");
+    }
+    for off in ["", " ", "0", "false", "False", "no"] {
+        assert!(!truthy_env_value(off), "{off:?}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// --yes
+// ---------------------------------------------------------------------------
+
+#[test]
+fn yes_makes_os_integration_non_interactive() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = DarpPaths::new(dir.path().join("config.json"), dir.path().to_path_buf());
+    let config = Config::default();
+
+    // set_assume_yes(false) never turns the mode back off.
+    os::set_assume_yes(false);
+    os::set_assume_yes(true);
+    os::set_assume_yes(false);
+    assert!(os::assume_yes());
+    assert!(OsIntegration::new(&paths, &config, &EngineKind::None).non_interactive);
+}
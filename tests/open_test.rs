@@ -0,0 +1,63 @@
+use darp::commands::service_browser_url;
+use darp::os::url_opener;
+
+fn portmap() -> serde_json::Value {
+    serde_json::json!({
+        "shop": {
+            ".": { "api": { "port": 50100, "type": "http" }, "ws": { "port": 50101, "type": "websocket" } },
+            "infra": { "db": { "port": 50102, "type": "tcp" } }
+        },
+        "blog": {
+            ".": { "api": 50103, "web": { "port": 50104 } }
+        }
+    })
+}
+
+// ---------------------------------------------------------------------------
+// darp open target resolution
+// ---------------------------------------------------------------------------
+
+#[test]
+fn explicit_domain_dot_service() {
+    let url = service_browser_url(&portmap(), Some("shop.api"), None).unwrap();
+    assert_eq!(url, "http://api.shop.test");
+}
+
+#[test]
+fn bare_service_name_must_be_unique() {
+    let url = service_browser_url(&portmap(), Some("web"), None).unwrap();
+    assert_eq!(url, "http://web.blog.test");
+
+    let err = service_browser_url(&portmap(), Some("api"), None).unwrap_err();
+    assert!(err.to_string().contains("several domains"));
+}
+
+#[test]
+fn falls_back_to_the_current_directory_service() {
+    let url = service_browser_url(&portmap(), None, Some(("blog", "api"))).unwrap();
+    assert_eq!(url, "http://api.blog.test");
+    assert!(service_browser_url(&portmap(), None, None).is_err());
+}
+
+#[test]
+fn unassigned_and_tcp_services_are_refused() {
+    let err = service_browser_url(&portmap(), Some("shop.admin"), None).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'shop.admin' has no port assigned yet")
+    );
+
+    let err = service_browser_url(&portmap(), Some("shop.db"), None).unwrap_err();
+    assert!(err.to_string().contains("tcp://db.shop.test:50102"));
+}
+
+#[test]
+fn websocket_services_open_over_http() {
+    let url = service_browser_url(&portmap(), Some("shop.ws"), None).unwrap();
+    assert_eq!(url, "http://ws.shop.test");
+}
+
+#[test]
+fn wsl_opens_urls_with_the_windows_browser() {
+    assert_eq!(url_opener(true), ("cmd.exe", &["/c", "start", ""][..]));
+}
@@ -0,0 +1,38 @@
+use darp::config::Config;
+use darp::engine::{
+    DOMAIN_LABEL, Engine, EngineKind, SERVICE_LABEL, SHELL_LABEL, persistent_shell_name,
+};
+
+// ---------------------------------------------------------------------------
+// persistent_shell_name
+// ---------------------------------------------------------------------------
+
+#[test]
+fn persistent_shell_is_named_after_the_service_container() {
+    assert_eq!(
+        persistent_shell_name("darp_shop_api"),
+        "darp_shop_api_shell"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// run_options_persistent_shell
+// ---------------------------------------------------------------------------
+
+#[test]
+fn persistent_shell_is_detached_and_kept() {
+    let engine = Engine::new(EngineKind::None, &Config::default()).unwrap();
+    let options = engine.run_options_persistent_shell("shop", "api");
+    assert!(options.contains(&"-d".to_string()));
+    assert!(!options.contains(&"--rm".to_string()));
+    assert!(!options.contains(&"-it".to_string()));
+}
+
+#[test]
+fn persistent_shell_is_not_labelled_as_the_service() {
+    let engine = Engine::new(EngineKind::None, &Config::default()).unwrap();
+    let options = engine.run_options_persistent_shell("shop", "api");
+    assert!(options.contains(&format!("{}=shop", DOMAIN_LABEL)));
+    assert!(options.contains(&format!("{}=api", SHELL_LABEL)));
+    assert!(!options.iter().any(|o| o.starts_with(SERVICE_LABEL)));
+}
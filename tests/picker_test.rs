@@ -0,0 +1,103 @@
+use std::io::Cursor;
+
+use darp::commands::{filter_choices, fuzzy_score, pick_from};
+use darp::config::portmap_services;
+
+fn choices(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+fn pick(input: &str, list: &[&str]) -> (Option<usize>, String) {
+    let mut output = Vec::new();
+    let picked = pick_from(
+        &mut Cursor::new(input.as_bytes()),
+        &mut output,
+        "Service",
+        &choices(list),
+    )
+    .unwrap();
+    (picked, String::from_utf8(output).unwrap())
+}
+
+// ---------------------------------------------------------------------------
+// Fuzzy matching
+// ---------------------------------------------------------------------------
+
+#[test]
+fn fuzzy_score_needs_the_query_in_order() {
+    assert!(fuzzy_score("api", "api.shop.test").is_some());
+    assert!(fuzzy_score("ast", "api.shop.test").is_some());
+    assert!(fuzzy_score("API", "api.shop.test").is_some());
+    assert!(fuzzy_score("tsa", "api.shop.test").is_none());
+    assert_eq!(fuzzy_score("", "api.shop.test"), Some(0));
+}
+
+#[test]
+fn substrings_rank_before_scattered_matches() {
+    let list = choices(&["admin.shop.test", "web.shop.test", "shop-web.blog.test"]);
+    assert_eq!(filter_choices("web", &list), vec![1, 2]);
+    assert_eq!(filter_choices("", &list), vec![0, 1, 2]);
+    // "asp" only matches admin.shop.test, as a scattered subsequence.
+    assert_eq!(filter_choices("asp", &list), vec![0]);
+}
+
+// ---------------------------------------------------------------------------
+// pick_from
+// ---------------------------------------------------------------------------
+
+#[test]
+fn picks_by_number() {
+    let (picked, output) = pick("2\n", &["api.shop.test", "web.shop.test"]);
+    assert_eq!(picked, Some(1));
+    assert!(output.contains("  1) api.shop.test\n"), "{}", output);
+    assert!(output.contains("  2) web.shop.test\n"), "{}", output);
+}
+
+#[test]
+fn a_filter_with_one_match_picks_it() {
+    let (picked, _) = pick(
+        "eb\n",
+        &["api.shop.test", "web.shop.test", "worker.shop.test"],
+    );
+    assert_eq!(picked, Some(1));
+}
+
+#[test]
+fn numbers_refer_to_the_filtered_list() {
+    let (picked, output) = pick(
+        "w\n2\n",
+        &["api.shop.test", "web.shop.test", "worker.shop.test"],
+    );
+    assert_eq!(picked, Some(2));
+    assert!(output.contains("  1) web.shop.test\n"), "{}", output);
+}
+
+#[test]
+fn no_match_and_cancel() {
+    let (picked, output) = pick("zzz\n\n", &["api.shop.test", "web.shop.test"]);
+    assert_eq!(picked, None);
+    assert!(output.contains("Nothing matches 'zzz'."), "{}", output);
+    assert_eq!(pick("", &["api.shop.test"]).0, None);
+}
+
+// ---------------------------------------------------------------------------
+// portmap_services
+// ---------------------------------------------------------------------------
+
+#[test]
+fn portmap_services_are_sorted_triples() {
+    let portmap = serde_json::json!({
+        "shop": {".": {"web": {"port": 50101}, "api": {"port": 50100}}, "admin": {"panel": 50102}},
+        "blog": {".": {"site": {"port": 50103}}}
+    });
+    let services: Vec<(String, String, String)> = [
+        ("blog", ".", "site"),
+        ("shop", ".", "api"),
+        ("shop", ".", "web"),
+        ("shop", "admin", "panel"),
+    ]
+    .iter()
+    .map(|(d, g, s)| (d.to_string(), g.to_string(), s.to_string()))
+    .collect();
+    assert_eq!(portmap_services(&portmap), services);
+}
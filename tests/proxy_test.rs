@@ -0,0 +1,62 @@
+use darp::commands::{ProxyStatus, render_proxy_status};
+
+fn plain(status: &ProxyStatus) -> String {
+    colored::control::set_override(false);
+    render_proxy_status(status)
+}
+
+// ---------------------------------------------------------------------------
+// render_proxy_status
+// ---------------------------------------------------------------------------
+
+#[test]
+fn stopped_proxy_says_how_to_start_it() {
+    assert_eq!(
+        plain(&ProxyStatus::default()),
+        "darp-reverse-proxy: stopped (run 'darp deploy' to start it)\n"
+    );
+}
+
+#[test]
+fn running_proxy_lists_config_test_and_ports() {
+    let status = ProxyStatus {
+        running: true,
+        config_ok: Some(true),
+        config_test: "nginx: the configuration file /etc/nginx/nginx.conf syntax is ok\nnginx: configuration file /etc/nginx/nginx.conf test is successful".to_string(),
+        ports: vec![
+            "80/tcp -> 0.0.0.0:80".to_string(),
+            "50100/tcp -> 0.0.0.0:50100".to_string(),
+        ],
+    };
+    assert_eq!(
+        plain(&status),
+        "darp-reverse-proxy: running\n\
+         config: ok\n  \
+         nginx: the configuration file /etc/nginx/nginx.conf syntax is ok\n  \
+         nginx: configuration file /etc/nginx/nginx.conf test is successful\n\
+         ports:\n  \
+         80/tcp -> 0.0.0.0:80\n  \
+         50100/tcp -> 0.0.0.0:50100\n"
+    );
+}
+
+#[test]
+fn failed_config_test_is_reported() {
+    let status = ProxyStatus {
+        running: true,
+        config_ok: Some(false),
+        config_test: "nginx: [emerg] unknown directive \"lsten\"".to_string(),
+        ports: Vec::new(),
+    };
+    let out = plain(&status);
+    assert!(out.contains("config: failed\n  nginx: [emerg] unknown directive \"lsten\"\n"));
+}
+
+#[test]
+fn status_serializes_for_json_output() {
+    let json = serde_json::to_value(ProxyStatus::default()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"running": false, "config_ok": null, "config_test": "", "ports": []})
+    );
+}
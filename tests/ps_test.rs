@@ -0,0 +1,88 @@
+use darp::commands::build_ps_entries;
+use darp::engine::{ServiceContainer, parse_published_ports};
+
+fn container(domain: &str, service: &str, ports: Vec<(u16, String)>) -> ServiceContainer {
+    ServiceContainer {
+        name: format!("darp_{}_{}", domain, service),
+        domain: domain.to_string(),
+        service: service.to_string(),
+        status: "Up 5 minutes".to_string(),
+        ports,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// parse_published_ports
+// ---------------------------------------------------------------------------
+
+#[test]
+fn published_ports_skip_unpublished_and_dedupe_ipv6() {
+    let json = r#"{
+        "8000/tcp": [{"HostIp": "0.0.0.0", "HostPort": "50100"}, {"HostIp": "::", "HostPort": "50100"}],
+        "8001/tcp": null,
+        "3000/tcp": [{"HostIp": "", "HostPort": "3000"}]
+    }"#;
+    assert_eq!(
+        parse_published_ports(json),
+        vec![
+            (3000, "3000/tcp".to_string()),
+            (50100, "8000/tcp".to_string())
+        ]
+    );
+}
+
+#[test]
+fn published_ports_tolerate_empty_output() {
+    assert!(parse_published_ports("null").is_empty());
+    assert!(parse_published_ports("").is_empty());
+    assert!(parse_published_ports("{}").is_empty());
+}
+
+// ---------------------------------------------------------------------------
+// build_ps_entries
+// ---------------------------------------------------------------------------
+
+#[test]
+fn entries_join_the_portmap_and_drop_the_proxy_port_from_extra_ports() {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": { "api": { "port": 50100, "type": "http", "debug_port": 13000 } },
+            "workers": { "queue": { "port": 50101, "type": "tcp", "debug_port": 13001 } }
+        }
+    });
+    let containers = vec![
+        container(
+            "shop",
+            "api",
+            vec![(3000, "3000/tcp".into()), (50100, "8000/tcp".into())],
+        ),
+        container("shop", "queue", vec![(50101, "8002/tcp".into())]),
+    ];
+
+    let entries = build_ps_entries(&containers, &portmap, "docker");
+    assert_eq!(entries[0]["group"], ".");
+    assert_eq!(entries[0]["url"], "http://api.shop.test");
+    assert_eq!(entries[0]["proxy_port"], 50100);
+    assert_eq!(
+        entries[0]["ports"],
+        serde_json::json!([{ "host": 3000, "container": "3000/tcp" }])
+    );
+    assert_eq!(entries[0]["engine"], "docker");
+
+    assert_eq!(entries[1]["group"], "workers");
+    assert_eq!(entries[1]["url"], "tcp://queue.shop.test:50101");
+    assert_eq!(entries[1]["ports"], serde_json::json!([]));
+}
+
+#[test]
+fn container_missing_from_portmap_has_no_group_or_proxy_port() {
+    let entries = build_ps_entries(
+        &[container("shop", "new", vec![])],
+        &serde_json::json!({}),
+        "podman",
+    );
+    assert!(entries[0]["group"].is_null());
+    assert!(entries[0]["proxy_port"].is_null());
+    assert_eq!(entries[0]["url"], "http://new.shop.test");
+    assert_eq!(entries[0]["status"], "Up 5 minutes");
+}
@@ -0,0 +1,115 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use darp::urls::{phone_url, qr_code, render_urls_qr};
+
+const LAN_IP: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)));
+
+// ---------------------------------------------------------------------------
+// qr_code
+// ---------------------------------------------------------------------------
+
+#[test]
+fn qr_code_is_a_square_of_half_blocks() {
+    let qr = qr_code("http://api.shop.test").unwrap();
+    let lines: Vec<&str> = qr.lines().collect();
+    let width = lines[0].chars().count();
+    // Version 2 (25 modules) plus a 4-module quiet zone each side, two rows per line.
+    assert_eq!(width, 33);
+    assert_eq!(lines.len(), 17);
+    assert!(lines.iter().all(|l| l.chars().count() == width));
+    assert!(
+        qr.chars()
+            .all(|c| matches!(c, ' ' | '▀' | '▄' | '█' | '\n'))
+    );
+}
+
+#[test]
+fn qr_code_differs_per_url() {
+    assert_ne!(
+        qr_code("http://api.shop.test").unwrap(),
+        qr_code("http://web.shop.test").unwrap()
+    );
+}
+
+// ---------------------------------------------------------------------------
+// phone_url
+// ---------------------------------------------------------------------------
+
+#[test]
+fn phone_url_never_uses_the_test_name() {
+    let entry = serde_json::json!({"port": 50101, "direct_url": "http://localhost:50101"});
+    assert_eq!(
+        phone_url(&entry, LAN_IP).as_deref(),
+        Some("http://192.168.1.20:50101")
+    );
+    // Without a LAN address there's nothing a phone could open.
+    assert_eq!(phone_url(&entry, None), None);
+}
+
+#[test]
+fn phone_url_prefers_the_mdns_name() {
+    let entry = serde_json::json!({
+        "port": 50101,
+        "mdns_host": "api-shop.local",
+        "direct_url": "http://localhost:50101",
+    });
+    assert_eq!(
+        phone_url(&entry, LAN_IP).as_deref(),
+        Some("http://api-shop.local")
+    );
+}
+
+#[test]
+fn phone_url_keeps_a_remote_servers_address_and_skips_tcp() {
+    let remote = serde_json::json!({"port": 50101, "direct_url": "http://10.0.0.5:50101"});
+    assert_eq!(
+        phone_url(&remote, LAN_IP).as_deref(),
+        Some("http://10.0.0.5:50101")
+    );
+    let ws = serde_json::json!({"port": 50102, "type": "websocket", "direct_url": "ws://localhost:50102"});
+    assert_eq!(
+        phone_url(&ws, LAN_IP).as_deref(),
+        Some("http://192.168.1.20:50102")
+    );
+    let tcp =
+        serde_json::json!({"port": 50103, "type": "tcp", "direct_url": "tcp://localhost:50103"});
+    assert_eq!(phone_url(&tcp, LAN_IP), None);
+}
+
+// ---------------------------------------------------------------------------
+// render_urls_qr
+// ---------------------------------------------------------------------------
+
+#[test]
+fn render_urls_qr_encodes_reachable_urls_in_order() {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": {
+                "web": {"port": 50102, "direct_url": "http://localhost:50102"},
+                "api": {"port": 50101, "type": "websocket", "mdns_host": "api-shop.local"},
+                "db": {"port": 50103, "type": "tcp"},
+            }
+        }
+    });
+    let out = render_urls_qr(&portmap, LAN_IP).unwrap();
+    let urls: Vec<&str> = out.lines().filter(|l| l.starts_with("http")).collect();
+    assert_eq!(
+        urls,
+        [
+            "http://api.shop.test  http://api-shop.local",
+            "http://web.shop.test  http://192.168.1.20:50102"
+        ]
+    );
+    assert!(out.starts_with(&format!(
+        "http://api.shop.test  http://api-shop.local\n{}\n",
+        qr_code("http://api-shop.local").unwrap()
+    )));
+    assert!(!out.contains("tcp://"));
+}
+
+#[test]
+fn render_urls_qr_lists_services_a_phone_cant_reach() {
+    let portmap = serde_json::json!({"shop": {".": {"web": {"port": 50102}}}});
+    let out = render_urls_qr(&portmap, LAN_IP).unwrap();
+    assert!(out.starts_with("No address another device can reach for: http://web.shop.test\n"));
+}
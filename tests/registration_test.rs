@@ -0,0 +1,58 @@
+use darp::commands::{Registration, missing_registration};
+use darp::config::DarpPaths;
+
+fn paths_in(dir: &std::path::Path) -> DarpPaths {
+    DarpPaths::new(dir.join("config.json"), dir.to_path_buf())
+}
+
+fn write_deploy(paths: &DarpPaths, portmap: &str) {
+    std::fs::write(&paths.portmap_path, portmap).unwrap();
+    std::fs::write(&paths.vhost_container_conf, "").unwrap();
+    std::fs::write(&paths.hosts_container_path, "").unwrap();
+}
+
+// ---------------------------------------------------------------------------
+// missing_registration
+// ---------------------------------------------------------------------------
+
+#[test]
+fn a_service_with_a_port_needs_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths_in(dir.path());
+    write_deploy(
+        &paths,
+        r#"{"shop": {".": {"api": {"port": 50100, "type": "http"}}}}"#,
+    );
+    assert_eq!(missing_registration(&paths, "shop", ".", "api"), None);
+}
+
+#[test]
+fn a_new_service_next_to_a_deploy_is_added_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths_in(dir.path());
+    write_deploy(
+        &paths,
+        r#"{"shop": {".": {"api": {"port": 50100, "type": "http"}}}}"#,
+    );
+    assert_eq!(
+        missing_registration(&paths, "shop", ".", "web"),
+        Some(Registration::AddService)
+    );
+}
+
+#[test]
+fn nothing_deployed_needs_a_full_deploy() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = paths_in(dir.path());
+    assert_eq!(
+        missing_registration(&paths, "shop", ".", "api"),
+        Some(Registration::Deploy)
+    );
+
+    // A portmap without the proxy files add-service appends to isn't enough either.
+    std::fs::write(&paths.portmap_path, "{}").unwrap();
+    assert_eq!(
+        missing_registration(&paths, "shop", ".", "api"),
+        Some(Registration::Deploy)
+    );
+}
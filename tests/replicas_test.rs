@@ -0,0 +1,118 @@
+use darp::commands::replica_container_name;
+use darp::config::{Config, MAX_REPLICAS, next_proxy_port, portmap_replica_ports};
+use darp::deploy::{live_reload_vhost, replicas_vhost};
+
+// ---------------------------------------------------------------------------
+// replicas_vhost
+// ---------------------------------------------------------------------------
+
+#[test]
+fn replicas_vhost_balances_across_every_port() {
+    let out = replicas_vhost(
+        "shop",
+        "api",
+        "host.docker.internal",
+        &[50100, 50101, 50102],
+    );
+    assert!(
+        out.starts_with("upstream darp_replicas_shop_api {\n"),
+        "{}",
+        out
+    );
+    for port in [50100, 50101, 50102] {
+        assert!(
+            out.contains(&format!(
+                "    server host.docker.internal:{} max_fails=1 fail_timeout=5s;\n",
+                port
+            )),
+            "{}",
+            out
+        );
+    }
+    assert!(out.contains("server_name api.shop.test;"));
+    assert!(out.contains("proxy_pass http://darp_replicas_shop_api/;"));
+    assert!(out.contains("add_header X-Darp-Upstream $upstream_addr always;"));
+}
+
+#[test]
+fn replicas_vhost_sanitizes_the_upstream_name() {
+    let out = replicas_vhost("my-shop", "web.app", "10.0.2.2", &[50100, 50101]);
+    assert!(out.contains("upstream darp_replicas_my_shop_web_app {"));
+    assert!(out.contains("server_name web.app.my-shop.test;"));
+}
+
+#[test]
+fn replicas_vhost_takes_live_reload() {
+    let out = live_reload_vhost(
+        &replicas_vhost("shop", "web", "host.docker.internal", &[50100, 50101]),
+        "host.docker.internal",
+    );
+    assert!(out.contains("location /__darp/ {"));
+    assert!(out.contains("sub_filter '</body>'"));
+    assert!(out.contains("proxy_pass http://darp_replicas_shop_web/;"));
+}
+
+// ---------------------------------------------------------------------------
+// Container names and ports
+// ---------------------------------------------------------------------------
+
+#[test]
+fn first_replica_keeps_the_service_container_name() {
+    assert_eq!(replica_container_name("darp_shop_api", 0), "darp_shop_api");
+    assert_eq!(
+        replica_container_name("darp_shop_api", 1),
+        "darp_shop_api_r2"
+    );
+    assert_eq!(
+        replica_container_name("darp_shop_api", 2),
+        "darp_shop_api_r3"
+    );
+}
+
+#[test]
+fn replica_ports_are_read_from_the_portmap_and_skipped_by_new_services() {
+    let portmap = serde_json::json!({
+        "shop": {".": {
+            "api": {"port": 50100, "type": "http", "debug_port": 13000, "replica_ports": [50101, 50102]},
+            "web": {"port": 50099, "type": "http", "debug_port": 13001}
+        }}
+    });
+    assert_eq!(
+        portmap_replica_ports(&portmap, "shop", ".", "api"),
+        vec![50101, 50102]
+    );
+    assert!(portmap_replica_ports(&portmap, "shop", ".", "web").is_empty());
+    assert_eq!(next_proxy_port(&portmap, 50100), 50103);
+}
+
+// ---------------------------------------------------------------------------
+// Config replicas
+// ---------------------------------------------------------------------------
+
+#[test]
+fn service_replicas_lifecycle() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+
+    c.set_service_replicas("shop", ".", "api", 3).unwrap();
+    let service = |c: &Config| {
+        c.domains.as_ref().unwrap()["shop"].groups.as_ref().unwrap()["."]
+            .services
+            .as_ref()
+            .unwrap()["api"]
+            .replicas
+    };
+    assert_eq!(service(&c), Some(3));
+
+    assert!(c.set_service_replicas("shop", ".", "api", 0).is_err());
+    assert!(
+        c.set_service_replicas("shop", ".", "api", MAX_REPLICAS + 1)
+            .is_err()
+    );
+    assert_eq!(service(&c), Some(3));
+
+    c.rm_service_replicas("shop", ".", "api").unwrap();
+    assert_eq!(service(&c), None);
+    let err = c.rm_service_replicas("shop", ".", "api").unwrap_err();
+    assert!(err.to_string().contains("has no replicas"), "{}", err);
+}
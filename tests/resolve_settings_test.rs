@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use darp::config::{Domain, Environment, Group, ResolvedSettings, Service, Volume};
+use darp::config::{Domain, Environment, Group, ResolvedSettings, ResourceLimits, Service, Volume};
 
 fn bare_domain() -> Domain {
     Domain {
@@ -184,6 +184,7 @@ fn vol(host: &str, container: &str) -> Volume {
     Volume {
         host: host.into(),
         container: container.into(),
+        read_only: false,
     }
 }
 
@@ -564,6 +565,145 @@ fn scalar_plain_null_is_ignored_falls_through() {
     assert_eq!(r.serve_command.as_deref(), Some("air"));
 }
 
+// ---------------------------------------------------------------------------
+// ResolvedSettings::resolve — resource limits
+// ---------------------------------------------------------------------------
+
+#[test]
+fn limits_service_overrides_environment_per_key() {
+    let env = Environment {
+        limits: Some(ResourceLimits {
+            cpus: Some("2".into()),
+            memory: Some("2g".into()),
+        }),
+        ..Default::default()
+    };
+    let svc = Service {
+        limits: Some(ResourceLimits {
+            cpus: None,
+            memory: Some("4g".into()),
+        }),
+        ..Default::default()
+    };
+
+    let r = ResolvedSettings::resolve(
+        "d".into(),
+        ".".into(),
+        "s".into(),
+        Some("e".into()),
+        Some(&svc),
+        None,
+        &bare_domain(),
+        Some(&env),
+    );
+
+    let limits = r.limits.expect("limits should resolve");
+    assert_eq!(limits.cpus.as_deref(), Some("2"));
+    assert_eq!(limits.memory.as_deref(), Some("4g"));
+}
+
+#[test]
+fn limits_absent_everywhere_resolves_to_none() {
+    let r = ResolvedSettings::resolve(
+        "d".into(),
+        ".".into(),
+        "s".into(),
+        None,
+        None,
+        None,
+        &bare_domain(),
+        None,
+    );
+    assert!(r.limits.is_none());
+}
+
+// ---------------------------------------------------------------------------
+// ResolvedSettings::resolve — restart policy
+// ---------------------------------------------------------------------------
+
+#[test]
+fn restart_service_overrides_environment() {
+    let env = Environment {
+        restart: Some("always".into()),
+        ..Default::default()
+    };
+    let svc = Service {
+        restart: Some("no".into()),
+        ..Default::default()
+    };
+    let resolve = |svc: Option<&Service>| {
+        ResolvedSettings::resolve(
+            "d".into(),
+            ".".into(),
+            "s".into(),
+            Some("e".into()),
+            svc,
+            None,
+            &bare_domain(),
+            Some(&env),
+        )
+    };
+
+    assert_eq!(resolve(Some(&svc)).restart.as_deref(), Some("no"));
+    assert_eq!(resolve(None).restart.as_deref(), Some("always"));
+}
+
+#[test]
+fn map_user_service_overrides_environment_and_defaults_off() {
+    let env = Environment {
+        map_user: Some(true),
+        ..Default::default()
+    };
+    let svc = Service {
+        map_user: Some(false),
+        ..Default::default()
+    };
+    let resolve = |svc: Option<&Service>, env: Option<&Environment>| {
+        ResolvedSettings::resolve(
+            "d".into(),
+            ".".into(),
+            "s".into(),
+            Some("e".into()),
+            svc,
+            None,
+            &bare_domain(),
+            env,
+        )
+    };
+
+    assert!(!resolve(Some(&svc), Some(&env)).map_user);
+    assert!(resolve(None, Some(&env)).map_user);
+    assert!(!resolve(None, None).map_user);
+}
+
+#[test]
+fn read_only_app_service_overrides_environment_and_defaults_off() {
+    let env = Environment {
+        read_only_app: Some(true),
+        ..Default::default()
+    };
+    let svc = Service {
+        read_only_app: Some(false),
+        ..Default::default()
+    };
+    let resolve = |svc: Option<&Service>, env: Option<&Environment>| {
+        ResolvedSettings::resolve(
+            "d".into(),
+            ".".into(),
+            "s".into(),
+            Some("e".into()),
+            svc,
+            None,
+            &bare_domain(),
+            env,
+        )
+    };
+
+    assert!(!resolve(Some(&svc), Some(&env)).read_only_app);
+    assert!(resolve(None, Some(&env)).read_only_app);
+    assert!(!resolve(None, None).read_only_app);
+}
+
 // ---------------------------------------------------------------------------
 // ResolvedSettings::resolve_full_image_name
 // ---------------------------------------------------------------------------
@@ -700,3 +840,91 @@ fn cli_overrides_default() {
         Some("override".into())
     );
 }
+
+// ---------------------------------------------------------------------------
+// ResolvedSettings::resolve_shell_image_name
+// ---------------------------------------------------------------------------
+
+#[test]
+fn shell_image_service_overrides_environment() {
+    let env = Environment {
+        shell_image: Some("env-tools".into()),
+        ..Default::default()
+    };
+    let svc = Service {
+        shell_image: Some("svc-tools".into()),
+        ..Default::default()
+    };
+    let resolve = |svc: Option<&Service>| {
+        ResolvedSettings::resolve(
+            "d".into(),
+            ".".into(),
+            "s".into(),
+            Some("e".into()),
+            svc,
+            None,
+            &bare_domain(),
+            Some(&env),
+        )
+    };
+
+    assert_eq!(
+        resolve(Some(&svc)).shell_image.as_deref(),
+        Some("svc-tools")
+    );
+    assert_eq!(resolve(None).shell_image.as_deref(), Some("env-tools"));
+}
+
+#[test]
+fn shell_image_is_preferred_for_shell_but_not_for_serve() {
+    let env = Environment {
+        image_repository: Some("registry.example.com/php".into()),
+        default_container_image: Some("8.3".into()),
+        shell_image: Some("8.3-dev".into()),
+        ..Default::default()
+    };
+    let r = ResolvedSettings::resolve(
+        "d".into(),
+        ".".into(),
+        "s".into(),
+        Some("e".into()),
+        None,
+        None,
+        &bare_domain(),
+        Some(&env),
+    );
+
+    assert_eq!(
+        r.resolve_shell_image_name(None),
+        Some("registry.example.com/php:8.3-dev".into())
+    );
+    assert_eq!(
+        r.resolve_full_image_name(None),
+        Some("registry.example.com/php:8.3".into())
+    );
+    assert_eq!(
+        r.resolve_shell_image_name(Some("8.2")),
+        Some("registry.example.com/php:8.2".into())
+    );
+}
+
+#[test]
+fn shell_image_falls_back_to_default_container_image() {
+    let dom = Domain {
+        location: "/tmp".into(),
+        default_container_image: Some("default-img".into()),
+        ..Default::default()
+    };
+    let r = ResolvedSettings::resolve(
+        "d".into(),
+        ".".into(),
+        "s".into(),
+        None,
+        None,
+        None,
+        &dom,
+        None,
+    );
+
+    assert_eq!(r.resolve_shell_image_name(None), Some("default-img".into()));
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use darp::resolver::{DomainDir, Placement, lexical, locate, same_dir};
+
+fn fresh(name: &str) -> PathBuf {
+    let base = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    base
+}
+
+// ---------------------------------------------------------------------------
+// lexical / same_dir
+// ---------------------------------------------------------------------------
+
+#[test]
+fn lexical_drops_dots_and_trailing_separators() {
+    assert_eq!(lexical(Path::new("/a/./b/../c/")), PathBuf::from("/a/c"));
+}
+
+#[test]
+fn same_dir_ignores_trailing_slash_and_dot_dot() {
+    let base = fresh("darp_test_resolver_same");
+    std::fs::create_dir_all(base.join("shop")).unwrap();
+
+    let with_slash = PathBuf::from(format!("{}/", base.join("shop").display()));
+    assert!(same_dir(&with_slash, &base.join("shop")));
+    assert!(same_dir(&base.join("shop").join(".."), &base));
+    assert!(!same_dir(&base.join("shop"), &base));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+// ---------------------------------------------------------------------------
+// locate
+// ---------------------------------------------------------------------------
+
+fn shop(location: PathBuf) -> [DomainDir<'static>; 1] {
+    [DomainDir {
+        name: "shop",
+        location,
+        groups: vec!["backend"],
+    }]
+}
+
+fn service(group: &str, name: &str, dir: PathBuf) -> Placement<'static> {
+    Placement::Service {
+        domain: "shop",
+        group: group.to_string(),
+        service: name.to_string(),
+        dir,
+    }
+}
+
+#[test]
+fn locate_places_services_group_and_domain_folders() {
+    let base = fresh("darp_test_resolver_locate");
+    let domain = base.join("shop");
+    std::fs::create_dir_all(domain.join("web")).unwrap();
+    std::fs::create_dir_all(domain.join("backend").join("api")).unwrap();
+    let domains = shop(domain.clone());
+
+    assert_eq!(
+        locate(&domain.join("web"), &domains),
+        service(".", "web", domain.join("web"))
+    );
+    assert_eq!(
+        locate(&domain.join("backend").join("api"), &domains),
+        service("backend", "api", domain.join("backend").join("api"))
+    );
+    assert_eq!(
+        locate(&domain, &domains),
+        Placement::DomainRoot { domain: "shop" }
+    );
+    assert_eq!(
+        locate(&domain.join("backend"), &domains),
+        Placement::GroupRoot {
+            domain: "shop",
+            group: "backend".to_string(),
+        }
+    );
+    assert_eq!(locate(&base, &domains), Placement::Outside);
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn locate_walks_up_from_subfolders_of_a_service() {
+    let base = fresh("darp_test_resolver_walk_up");
+    let domain = base.join("shop");
+    let web_src = domain.join("web").join("src").join("components");
+    let api_src = domain.join("backend").join("api").join("src");
+    std::fs::create_dir_all(&web_src).unwrap();
+    std::fs::create_dir_all(&api_src).unwrap();
+    let domains = shop(domain.clone());
+
+    assert_eq!(
+        locate(&web_src, &domains),
+        service(".", "web", domain.join("web"))
+    );
+    assert_eq!(
+        locate(&api_src, &domains),
+        service("backend", "api", domain.join("backend").join("api"))
+    );
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[cfg(unix)]
+#[test]
+fn locate_follows_a_symlinked_domain_folder() {
+    let base = fresh("darp_test_resolver_symlinked_domain");
+    let real = base.join("real");
+    std::fs::create_dir_all(real.join("web")).unwrap();
+    std::os::unix::fs::symlink(&real, base.join("link")).unwrap();
+
+    // Configured through the symlink, visited through the real path.
+    let domains = shop(base.join("link"));
+    assert!(matches!(
+        locate(&real.join("web"), &domains),
+        Placement::Service { domain: "shop", .. }
+    ));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[cfg(unix)]
+#[test]
+fn locate_finds_a_service_folder_symlinked_from_elsewhere() {
+    let base = fresh("darp_test_resolver_symlinked_service");
+    let checkout = base.join("checkouts").join("api-repo");
+    let domain = base.join("shop");
+    std::fs::create_dir_all(checkout.join("src")).unwrap();
+    std::fs::create_dir_all(domain.join("backend")).unwrap();
+    let link = domain.join("backend").join("api");
+    std::os::unix::fs::symlink(&checkout, &link).unwrap();
+    let domains = shop(domain.clone());
+
+    let expected = service("backend", "api", link.clone());
+    // Via the logical path (as $PWD has it) and via the physical one (as getcwd has it).
+    assert_eq!(locate(&link, &domains), expected);
+    assert_eq!(locate(&checkout, &domains), expected);
+    assert_eq!(locate(&checkout.join("src"), &domains), expected);
+
+    let _ = std::fs::remove_dir_all(&base);
+}
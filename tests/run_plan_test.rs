@@ -0,0 +1,206 @@
+use darp::commands::{Mount, RunPlan, service_run_plan};
+use darp::config::{Config, DarpPaths};
+use darp::engine::{Engine, EngineKind};
+
+fn plan() -> RunPlan {
+    let mut plan = RunPlan::new("darp_shop_api", vec!["--rm".to_string(), "-it".to_string()]);
+    plan.image = "node:22".to_string();
+    plan.option(["--network", "darp"]);
+    plan.mount("/home/me/shop/api", "/app");
+    plan.ports.push("50100:8000".to_string());
+    plan.env_files.push("/home/me/shop/.env".to_string());
+    plan.env.push("NODE_ENV=development".to_string());
+    plan.env.push("API_TOKEN".to_string());
+    plan.secrets
+        .push(("API_TOKEN".to_string(), "s3cret".to_string()));
+    plan.command = vec!["sh".to_string(), "-c".to_string(), "npm start".to_string()];
+    plan
+}
+
+// ---------------------------------------------------------------------------
+// RunPlan::run_args
+// ---------------------------------------------------------------------------
+
+#[test]
+fn run_args_put_the_image_between_options_and_command() {
+    assert_eq!(
+        plan().run_args(),
+        [
+            "run",
+            "--rm",
+            "-it",
+            "--network",
+            "darp",
+            "--name",
+            "darp_shop_api",
+            "-v",
+            "/home/me/shop/api:/app",
+            "-p",
+            "50100:8000",
+            "--env-file",
+            "/home/me/shop/.env",
+            "-e",
+            "NODE_ENV=development",
+            "-e",
+            "API_TOKEN",
+            "node:22",
+            "sh",
+            "-c",
+            "npm start",
+        ]
+    );
+}
+
+#[test]
+fn read_only_mounts_end_in_ro() {
+    let mut plan = plan();
+    plan.mounts[0].read_only = true;
+    assert!(
+        plan.run_args()
+            .contains(&"/home/me/shop/api:/app:ro".to_string())
+    );
+    let json = serde_json::to_value(&plan).unwrap();
+    assert_eq!(json["mounts"][0]["read_only"], true);
+}
+
+#[test]
+fn run_args_leave_out_secret_values() {
+    assert!(!plan().run_args().iter().any(|a| a.contains("s3cret")));
+}
+
+// ---------------------------------------------------------------------------
+// --print-plan JSON
+// ---------------------------------------------------------------------------
+
+#[test]
+fn plan_json_has_structured_fields_and_no_secrets() {
+    let json = serde_json::to_value(plan()).unwrap();
+    assert_eq!(json["name"], "darp_shop_api");
+    assert_eq!(json["image"], "node:22");
+    assert_eq!(
+        json["mounts"],
+        serde_json::to_value(vec![Mount {
+            host: "/home/me/shop/api".to_string(),
+            container: "/app".to_string(),
+            read_only: false,
+        }])
+        .unwrap()
+    );
+    assert_eq!(json["ports"], serde_json::json!(["50100:8000"]));
+    assert_eq!(
+        json["env"],
+        serde_json::json!(["NODE_ENV=development", "API_TOKEN"])
+    );
+    assert!(json.get("secrets").is_none());
+    assert!(!json.to_string().contains("s3cret"));
+}
+
+// ---------------------------------------------------------------------------
+// service_run_plan
+// ---------------------------------------------------------------------------
+
+/// A deployed `shop` domain with an `api` service folder under a temp dir named `name`,
+/// served by environment `dev` with `serve_command` on node:22.
+fn shop_api(name: &str, serve_command: &str) -> (std::path::PathBuf, DarpPaths, Config) {
+    let base = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("shop").join("api")).unwrap();
+    let paths = DarpPaths::new(base.join("config.json"), base.clone());
+    std::fs::write(
+        &paths.portmap_path,
+        r#"{"shop": {".": {"api": {"port": 50100}}}}"#,
+    )
+    .unwrap();
+
+    let mut config = Config::default();
+    config
+        .add_domain("shop", &base.join("shop").to_string_lossy())
+        .unwrap();
+    config.set_serve_command("dev", serve_command).unwrap();
+    config
+        .set_default_container_image("dev", "node:22")
+        .unwrap();
+    (base, paths, config)
+}
+
+fn api_plan(
+    base: &std::path::Path,
+    paths: &DarpPaths,
+    config: &Config,
+    kind: EngineKind,
+) -> RunPlan {
+    let engine = Engine::new(kind, config).unwrap();
+    let ctx = config
+        .service_context_for_dir(base.join("shop").join("api"), Some("dev".to_string()))
+        .unwrap();
+    service_run_plan(&ctx, paths, config, &engine).unwrap()
+}
+
+#[test]
+fn service_run_args_follow_engine_args() {
+    let (base, paths, mut config) = shop_api("darp_test_run_plan_run_args", "npm start");
+    config.add_engine_arg("--pull=never").unwrap();
+    config
+        .add_service_run_arg("shop", ".", "api", "--shm-size=1g")
+        .unwrap();
+
+    let options = api_plan(&base, &paths, &config, EngineKind::None).options;
+    let position = |arg: &str| options.iter().position(|o| o == arg).unwrap();
+    assert!(position("--pull=never") < position("--shm-size=1g"));
+    assert_eq!(options.last().map(String::as_str), Some("--shm-size=1g"));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn map_user_runs_as_the_host_user_in_its_own_namespace() {
+    let (base, paths, mut config) = shop_api("darp_test_run_plan_map_user", "npm start");
+    config.set_environment_map_user("dev", true).unwrap();
+
+    let (uid, gid) = darp::engine::host_user_ids().unwrap();
+    let options = api_plan(&base, &paths, &config, EngineKind::Podman).options;
+    let user = options.iter().position(|o| o == "--user").unwrap();
+    assert_eq!(options[user + 1], format!("{}:{}", uid, gid));
+    assert!(options.contains(&"--userns=keep-id".to_string()));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn a_shell_entrypoint_runs_the_serve_script() {
+    let (base, paths, mut config) = shop_api("darp_test_run_plan_shell_entrypoint", "npm start");
+    config
+        .set_service_entrypoint("shop", ".", "api", "/busybox/sh")
+        .unwrap();
+
+    let plan = api_plan(&base, &paths, &config, EngineKind::None);
+    let entrypoint = plan
+        .options
+        .iter()
+        .position(|o| o == "--entrypoint")
+        .unwrap();
+    assert_eq!(plan.options[entrypoint + 1], "/busybox/sh");
+    assert_eq!(plan.command[0], "-c");
+    assert!(plan.command[1].contains("npm start"));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn any_other_entrypoint_gets_the_serve_command_as_its_arguments() {
+    let (base, paths, mut config) = shop_api("darp_test_run_plan_direct_entrypoint", "--port 8000");
+    config
+        .set_service_entrypoint("shop", ".", "api", "/server")
+        .unwrap();
+
+    let plan = api_plan(&base, &paths, &config, EngineKind::None);
+    let entrypoint = plan
+        .options
+        .iter()
+        .position(|o| o == "--entrypoint")
+        .unwrap();
+    assert_eq!(plan.options[entrypoint + 1], "/server");
+    assert_eq!(plan.command, ["--port", "8000"]);
+
+    let _ = std::fs::remove_dir_all(&base);
+}
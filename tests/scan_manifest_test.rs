@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use darp::scan::ScanManifest;
+
+/// Give `dir` the same old mtime every time, so the manifest treats it as settled.
+fn backdate(dir: &Path) {
+    let earlier = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    File::open(dir).unwrap().set_modified(earlier).unwrap();
+}
+
+fn sorted(mut folders: Vec<String>) -> Vec<String> {
+    folders.sort();
+    folders
+}
+
+// ---------------------------------------------------------------------------
+// ScanManifest::folders
+// ---------------------------------------------------------------------------
+
+#[test]
+fn folders_lists_only_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("api")).unwrap();
+    std::fs::create_dir(dir.path().join("web")).unwrap();
+    std::fs::write(dir.path().join("README.md"), "").unwrap();
+    let mut manifest = ScanManifest::default();
+    assert_eq!(
+        sorted(manifest.folders(dir.path()).unwrap()),
+        vec!["api", "web"]
+    );
+}
+
+#[test]
+fn unchanged_folder_comes_from_the_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("api")).unwrap();
+    backdate(dir.path());
+    let mut manifest = ScanManifest::default();
+    assert_eq!(manifest.folders(dir.path()).unwrap(), vec!["api"]);
+    assert!(manifest.get(dir.path()).is_some());
+
+    // With the mtime put back, the new folder goes unseen: the listing wasn't redone.
+    std::fs::create_dir(dir.path().join("web")).unwrap();
+    backdate(dir.path());
+    assert_eq!(manifest.folders(dir.path()).unwrap(), vec!["api"]);
+}
+
+#[test]
+fn added_folder_is_picked_up() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("api")).unwrap();
+    backdate(dir.path());
+    let mut manifest = ScanManifest::default();
+    manifest.folders(dir.path()).unwrap();
+
+    std::fs::create_dir(dir.path().join("web")).unwrap();
+    assert_eq!(
+        sorted(manifest.folders(dir.path()).unwrap()),
+        vec!["api", "web"]
+    );
+}
+
+#[test]
+fn recently_changed_folder_is_not_cached() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("api")).unwrap();
+    let mut manifest = ScanManifest::default();
+    manifest.folders(dir.path()).unwrap();
+    assert!(manifest.get(dir.path()).is_none());
+}
+
+#[test]
+fn missing_folder_has_no_folders() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut manifest = ScanManifest::default();
+    assert!(
+        manifest
+            .folders(&dir.path().join("gone"))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_folder_is_an_error() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let locked = dir.path().join("locked");
+    std::fs::create_dir_all(locked.join("api")).unwrap();
+    std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+    // Root reads it regardless; there's nothing to check then.
+    let readable = std::fs::read_dir(&locked).is_ok();
+
+    let result = ScanManifest::default().folders(&locked);
+    std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    if !readable {
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("could not read"), "{}", err);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ScanManifest::load / save
+// ---------------------------------------------------------------------------
+
+#[test]
+fn save_and_load_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let services = dir.path().join("shop");
+    std::fs::create_dir_all(services.join("api")).unwrap();
+    backdate(&services);
+    let path = dir.path().join("scan_manifest.json");
+
+    let mut manifest = ScanManifest::default();
+    manifest.folders(&services).unwrap();
+    manifest.save(&path).unwrap();
+
+    let loaded = ScanManifest::load(&path);
+    assert_eq!(loaded.get(&services).unwrap().folders, vec!["api"]);
+}
+
+#[test]
+fn save_drops_folders_not_scanned() {
+    let dir = tempfile::tempdir().unwrap();
+    let shop = dir.path().join("shop");
+    let blog = dir.path().join("blog");
+    std::fs::create_dir_all(shop.join("api")).unwrap();
+    std::fs::create_dir_all(blog.join("web")).unwrap();
+    backdate(&shop);
+    backdate(&blog);
+    let path = dir.path().join("scan_manifest.json");
+
+    let mut manifest = ScanManifest::default();
+    manifest.folders(&shop).unwrap();
+    manifest.folders(&blog).unwrap();
+    manifest.save(&path).unwrap();
+
+    let mut manifest = ScanManifest::load(&path);
+    manifest.folders(&shop).unwrap();
+    manifest.save(&path).unwrap();
+    let loaded = ScanManifest::load(&path);
+    assert!(loaded.get(&shop).is_some());
+    assert!(loaded.get(&blog).is_none());
+}
+
+#[test]
+fn unreadable_manifest_starts_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("scan_manifest.json");
+    std::fs::write(&path, "not json").unwrap();
+    assert!(ScanManifest::load(&path).get(dir.path()).is_none());
+}
@@ -0,0 +1,53 @@
+use darp::secrets::{GLOBAL_SCOPE, SecretStore};
+
+#[test]
+fn domain_scope_overrides_global() {
+    let mut store = SecretStore::default();
+    store.set(GLOBAL_SCOPE, "API_KEY", "global");
+    store.set(GLOBAL_SCOPE, "NPM_TOKEN", "npm");
+    store.set("shop", "API_KEY", "shop-only");
+
+    let shop = store.for_domain("shop");
+    assert_eq!(shop["API_KEY"], "shop-only");
+    assert_eq!(shop["NPM_TOKEN"], "npm");
+
+    let other = store.for_domain("blog");
+    assert_eq!(other["API_KEY"], "global");
+}
+
+#[test]
+fn rm_drops_empty_scopes_and_errors_on_missing() {
+    let mut store = SecretStore::default();
+    store.set("shop", "API_KEY", "x");
+    store.rm("shop", "API_KEY").unwrap();
+    assert!(store.list().is_empty());
+    assert!(store.rm("shop", "API_KEY").is_err());
+}
+
+#[test]
+fn save_and_load_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("secrets.json");
+
+    let mut store = SecretStore::default();
+    store.set(GLOBAL_SCOPE, "TOKEN", "s3cret");
+    store.save(&path).unwrap();
+
+    let loaded = SecretStore::load(&path).unwrap();
+    assert_eq!(loaded.list(), vec![(GLOBAL_SCOPE, "TOKEN")]);
+    assert_eq!(loaded.for_domain("any")["TOKEN"], "s3cret");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn missing_file_loads_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = SecretStore::load(&dir.path().join("nope.json")).unwrap();
+    assert!(store.list().is_empty());
+}
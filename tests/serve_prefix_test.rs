@@ -0,0 +1,27 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use darp::engine::prefixed_line;
+
+// ---------------------------------------------------------------------------
+// prefixed_line
+// ---------------------------------------------------------------------------
+
+#[test]
+fn prefixed_line_has_utc_time_and_service() {
+    // 2024-03-01T13:05:09.042Z
+    let time = UNIX_EPOCH + Duration::from_millis(1_709_298_309_042);
+    assert_eq!(
+        prefixed_line(time, "api", "listening on 8000"),
+        "13:05:09.042 api | listening on 8000"
+    );
+}
+
+#[test]
+fn prefixed_line_drops_line_endings() {
+    let time = UNIX_EPOCH;
+    assert_eq!(
+        prefixed_line(time, "web", "ready\r\n"),
+        "00:00:00.000 web | ready"
+    );
+    assert_eq!(prefixed_line(time, "web", "\n"), "00:00:00.000 web | ");
+}
@@ -0,0 +1,31 @@
+use darp::commands::serve_script;
+
+fn hooks(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn no_hooks_keeps_the_plain_serve_command() {
+    assert_eq!(serve_script("npm start", &[], &[]), "cd /app; npm start");
+}
+
+#[test]
+fn pre_serve_hooks_run_in_order_and_abort_on_failure() {
+    let script = serve_script("npm start", &hooks(&["npm ci", "npm run build"]), &[]);
+    let ci = script.find("{ npm ci\n} || exit $?;").unwrap();
+    let build = script.find("{ npm run build\n} || exit $?;").unwrap();
+    let serve = script.rfind("npm start").unwrap();
+    assert!(script.starts_with("cd /app;"));
+    assert!(ci < build && build < serve);
+    assert!(!script.contains("trap"));
+}
+
+#[test]
+fn post_serve_hooks_run_after_serve_exits() {
+    let script = serve_script("npm start", &[], &hooks(&["rm -f /tmp/app.pid"]));
+    assert!(script.contains("{ npm start\n} &"));
+    assert!(script.contains("trap 'kill -TERM $darp_serve 2>/dev/null' INT TERM"));
+    let post = script.find("rm -f /tmp/app.pid;").unwrap();
+    assert!(script.find("wait $darp_serve").unwrap() < post);
+    assert!(script.ends_with("exit $darp_status"));
+}
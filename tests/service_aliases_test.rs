@@ -0,0 +1,100 @@
+use darp::config::{Config, portmap_aliases, validate_hostname};
+use darp::deploy::{aliased_vhost, canary_vhost};
+use darp::urls::render_urls_json;
+
+// ---------------------------------------------------------------------------
+// Config aliases
+// ---------------------------------------------------------------------------
+
+#[test]
+fn service_aliases_are_added_once_and_removed() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_service_alias("shop", ".", "api", "api.myshop.test")
+        .unwrap();
+    c.add_service_alias("shop", ".", "api", "api.myshop.test")
+        .unwrap();
+    c.add_service_alias("shop", ".", "api", "myshop.test")
+        .unwrap();
+
+    let api = &c.domains.as_ref().unwrap()["shop"].groups.as_ref().unwrap()["."]
+        .services
+        .as_ref()
+        .unwrap()["api"];
+    assert_eq!(
+        api.aliases.as_deref().unwrap(),
+        ["api.myshop.test", "myshop.test"]
+    );
+
+    c.rm_service_alias("shop", ".", "api", "api.myshop.test")
+        .unwrap();
+    c.rm_service_alias("shop", ".", "api", "myshop.test")
+        .unwrap();
+    assert!(
+        c.rm_service_alias("shop", ".", "api", "myshop.test")
+            .is_err()
+    );
+}
+
+#[test]
+fn service_aliases_reject_taken_or_invalid_hostnames() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_service_alias("shop", ".", "api", "myshop.test")
+        .unwrap();
+
+    assert!(
+        c.add_service_alias("shop", ".", "web", "myshop.test")
+            .is_err()
+    );
+    assert!(
+        c.add_service_alias("shop", ".", "web", "web.shop.test")
+            .is_err()
+    );
+    assert!(c.add_service_alias("shop", ".", "web", "my shop").is_err());
+    assert!(validate_hostname("myshop.test").is_ok());
+    assert!(validate_hostname(".myshop.test").is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Vhosts
+// ---------------------------------------------------------------------------
+
+#[test]
+fn aliased_vhost_adds_every_alias_to_server_name() {
+    let vhost = canary_vhost("shop", "api", "http://a:1", "http://b:2", 10, None);
+    let out = aliased_vhost(
+        &vhost,
+        &["api.myshop.test".to_string(), "myshop.test".to_string()],
+    );
+    assert!(
+        out.contains("    server_name api.shop.test api.myshop.test myshop.test;\n"),
+        "{}",
+        out
+    );
+    assert_eq!(aliased_vhost(&vhost, &[]), vhost);
+}
+
+// ---------------------------------------------------------------------------
+// Portmap
+// ---------------------------------------------------------------------------
+
+#[test]
+fn urls_json_lists_aliases_from_the_portmap() {
+    let portmap = serde_json::json!({
+        "shop": {".": {
+            "api": {"port": 50100, "type": "http", "debug_port": 9100, "aliases": ["myshop.test"]},
+            "web": {"port": 50101, "type": "http", "debug_port": 9101}
+        }}
+    });
+    assert_eq!(
+        portmap_aliases(&portmap["shop"]["."]["api"]),
+        ["myshop.test"]
+    );
+    let json = render_urls_json(&portmap);
+    assert_eq!(
+        json["shop"]["api"]["aliases"],
+        serde_json::json!(["myshop.test"])
+    );
+    assert!(json["shop"]["web"].get("aliases").is_none());
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use darp::engine::DEFAULT_CONTAINER_NAME;
+use darp::services_index::{SERVICES_INDEX_FORMAT_VERSION, build_services_index};
+
+fn sample_portmap() -> serde_json::Value {
+    serde_json::json!({
+        "proj": {
+            ".": {
+                "api": { "port": 50100, "type": "http", "debug_port": 13000 },
+                "db": { "port": 50101, "type": "tcp", "debug_port": 13001 }
+            },
+            "laravel": {
+                "admin": { "port": 50102, "type": "websocket", "debug_port": 13002 }
+            }
+        }
+    })
+}
+
+fn find<'a>(index: &'a serde_json::Value, service: &str) -> &'a serde_json::Value {
+    index["services"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["service"] == service)
+        .unwrap_or_else(|| panic!("{} missing from index: {}", service, index))
+}
+
+#[test]
+fn index_lists_every_service_with_url_and_type() {
+    let index = build_services_index(&sample_portmap(), &HashSet::new(), DEFAULT_CONTAINER_NAME);
+
+    assert_eq!(index["format_version"], SERVICES_INDEX_FORMAT_VERSION);
+    assert_eq!(index["services"].as_array().unwrap().len(), 3);
+
+    let api = find(&index, "api");
+    assert_eq!(api["url"], "http://api.proj.test");
+    assert_eq!(api["group"], ".");
+    assert_eq!(api["port"], 50100);
+
+    assert_eq!(find(&index, "db")["url"], "tcp://db.proj.test:50101");
+    assert_eq!(find(&index, "admin")["url"], "ws://admin.proj.test");
+    assert_eq!(find(&index, "admin")["group"], "laravel");
+}
+
+#[test]
+fn index_marks_running_containers() {
+    let running: HashSet<String> = ["darp_proj_api".to_string()].into_iter().collect();
+    let index = build_services_index(&sample_portmap(), &running, DEFAULT_CONTAINER_NAME);
+
+    assert_eq!(find(&index, "api")["running"], true);
+    assert_eq!(find(&index, "db")["running"], false);
+}
+
+#[test]
+fn index_of_empty_portmap_has_no_services() {
+    let index = build_services_index(
+        &serde_json::json!({}),
+        &HashSet::new(),
+        DEFAULT_CONTAINER_NAME,
+    );
+    assert!(index["services"].as_array().unwrap().is_empty());
+}
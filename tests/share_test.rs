@@ -0,0 +1,135 @@
+use darp::cli::TunnelKind;
+use darp::commands::{TunnelTarget, public_url, tunnel_args, tunnel_target};
+
+fn direct() -> TunnelTarget {
+    TunnelTarget {
+        origin: "http://localhost:50100".to_string(),
+        host_header: None,
+    }
+}
+
+fn via_proxy() -> TunnelTarget {
+    TunnelTarget {
+        origin: "http://localhost:80".to_string(),
+        host_header: Some("api.shop.test".to_string()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// tunnel_target
+// ---------------------------------------------------------------------------
+
+#[test]
+fn tunnel_target_prefers_the_direct_port() {
+    let entry = serde_json::json!({"port": 50100, "direct_url": "http://localhost:50100"});
+    assert_eq!(tunnel_target("shop", "api", &entry).unwrap(), direct());
+    let ws = serde_json::json!({"port": 50100, "type": "websocket", "direct_url": "ws://localhost:50100"});
+    assert_eq!(
+        tunnel_target("shop", "api", &ws).unwrap().origin,
+        "http://localhost:50100"
+    );
+}
+
+#[test]
+fn tunnel_target_without_a_direct_port_goes_through_port_80() {
+    let entry = serde_json::json!({"port": 50100});
+    assert_eq!(tunnel_target("shop", "api", &entry).unwrap(), via_proxy());
+}
+
+#[test]
+fn tunnel_target_refuses_tcp_services() {
+    let entry = serde_json::json!({"port": 50100, "type": "tcp"});
+    assert!(tunnel_target("shop", "db", &entry).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// tunnel_args
+// ---------------------------------------------------------------------------
+
+#[test]
+fn cloudflared_sets_the_host_header_when_needed() {
+    assert_eq!(
+        tunnel_args(TunnelKind::Cloudflared, &direct()).unwrap(),
+        [
+            "tunnel",
+            "--no-autoupdate",
+            "--url",
+            "http://localhost:50100"
+        ]
+    );
+    assert_eq!(
+        tunnel_args(TunnelKind::Cloudflared, &via_proxy()).unwrap(),
+        [
+            "tunnel",
+            "--no-autoupdate",
+            "--url",
+            "http://localhost:80",
+            "--http-host-header",
+            "api.shop.test"
+        ]
+    );
+}
+
+#[test]
+fn ngrok_logs_to_stdout() {
+    assert_eq!(
+        tunnel_args(TunnelKind::Ngrok, &via_proxy()).unwrap(),
+        [
+            "http",
+            "http://localhost:80",
+            "--host-header=api.shop.test",
+            "--log",
+            "stdout",
+            "--log-format",
+            "logfmt"
+        ]
+    );
+}
+
+#[test]
+fn localtunnel_needs_a_direct_port() {
+    assert_eq!(
+        tunnel_args(TunnelKind::Localtunnel, &direct()).unwrap(),
+        ["--port", "50100"]
+    );
+    assert!(tunnel_args(TunnelKind::Localtunnel, &via_proxy()).is_err());
+}
+
+// ---------------------------------------------------------------------------
+// public_url
+// ---------------------------------------------------------------------------
+
+#[test]
+fn public_url_is_read_from_each_tunnels_output() {
+    assert_eq!(
+        public_url(
+            TunnelKind::Cloudflared,
+            "2024-05-01T10:00:00Z INF |  https://calm-river-words.trycloudflare.com                |"
+        )
+        .as_deref(),
+        Some("https://calm-river-words.trycloudflare.com")
+    );
+    assert_eq!(
+        public_url(
+            TunnelKind::Cloudflared,
+            "INF Thank you for trying Cloudflare Tunnel. https://developers.cloudflare.com/cloudflare-one/"
+        ),
+        None
+    );
+    assert_eq!(
+        public_url(
+            TunnelKind::Ngrok,
+            r#"t=2024-05-01T10:00:00+0000 lvl=info msg="started tunnel" obj=tunnels name=command_line addr=http://localhost:80 url=https://ab12.ngrok-free.app"#
+        )
+        .as_deref(),
+        Some("https://ab12.ngrok-free.app")
+    );
+    assert_eq!(
+        public_url(
+            TunnelKind::Localtunnel,
+            "your url is: https://tidy-cats-say.loca.lt"
+        )
+        .as_deref(),
+        Some("https://tidy-cats-say.loca.lt")
+    );
+}
@@ -0,0 +1,139 @@
+use darp::config::{self, Config, SHARED_PROXY_DIR};
+use darp::deploy::{replicas_vhost, static_vhost, user_vhosts};
+use darp::engine::{Engine, EngineKind};
+use darp::urls::render_urls_json;
+
+fn shared(user: &str, port_base: u16) -> Config {
+    let mut c = Config::default();
+    c.set_shared_host(user, port_base, None).unwrap();
+    c
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn set_and_rm_shared_host() {
+    let mut c = shared("alice", 20000);
+    let s = c.shared_host.as_ref().unwrap();
+    assert_eq!(s.user, "alice");
+    assert_eq!(s.proxy_dir().to_str(), Some(SHARED_PROXY_DIR));
+
+    c.rm_shared_host().unwrap();
+    assert!(c.shared_host.is_none());
+    assert!(c.rm_shared_host().is_err());
+}
+
+#[test]
+fn shared_host_user_must_be_a_dns_label() {
+    let mut c = Config::default();
+    for bad in ["", "Alice", "al ice", "-alice", "alice-", "al.ice"] {
+        assert!(c.set_shared_host(bad, 20000, None).is_err(), "{bad:?}");
+    }
+    c.set_shared_host("alice-2", 20000, None).unwrap();
+}
+
+#[test]
+fn shared_host_port_base_leaves_room_for_the_range() {
+    let mut c = Config::default();
+    assert!(c.set_shared_host("alice", 80, None).is_err());
+    assert!(c.set_shared_host("alice", 65000, None).is_err());
+    assert!(c.set_shared_host("alice", 20000, Some("shared")).is_err());
+    c.set_shared_host("alice", 64535, Some("/srv/darp"))
+        .unwrap();
+}
+
+#[test]
+fn ports_come_from_the_users_range() {
+    let c = Config::default();
+    assert_eq!(c.first_proxy_port(), config::PROXY_PORT_BASE);
+    assert_eq!(c.first_debug_port(), config::DEBUG_PORT_BASE);
+
+    let mut c = shared("alice", 20000);
+    assert_eq!(c.first_proxy_port(), 20000);
+    assert_eq!(c.first_debug_port(), 20500);
+    c.debug_port_base = Some(14000);
+    assert_eq!(c.first_debug_port(), 14000);
+}
+
+// ---------------------------------------------------------------------------
+// Hostnames
+// ---------------------------------------------------------------------------
+
+#[test]
+fn hostnames_carry_the_user() {
+    assert_eq!(
+        Config::default().service_host("api", "shop"),
+        "api.shop.test"
+    );
+    assert_eq!(
+        shared("alice", 20000).service_host("api", "shop"),
+        "api.shop.alice.test"
+    );
+}
+
+#[test]
+fn urls_use_the_host_recorded_in_the_portmap() {
+    let portmap = serde_json::json!({
+        "shop": {".": {
+            "api": {"port": 20000, "type": "http", "host": "api.shop.alice.test"},
+            "db": {"port": 50101, "type": "tcp"},
+        }}
+    });
+    let json = render_urls_json(&portmap);
+    assert_eq!(json["shop"]["api"]["url"], "http://api.shop.alice.test");
+    assert_eq!(json["shop"]["db"]["url"], "tcp://db.shop.test:50101");
+}
+
+// ---------------------------------------------------------------------------
+// Vhosts
+// ---------------------------------------------------------------------------
+
+#[test]
+fn user_vhosts_renames_the_primary_server_name_only() {
+    let vhost = static_vhost("docs", ".", "handbook").replace(
+        "server_name handbook.docs.test;",
+        "server_name handbook.docs.test docs.example.com;",
+    );
+    let out = user_vhosts(&vhost, "alice");
+    assert!(out.contains("    server_name handbook.docs.alice.test docs.example.com;\n"));
+    assert!(out.contains("    root /srv/darp/docs/handbook;\n"));
+    assert_eq!(out.lines().count(), vhost.lines().count());
+}
+
+#[test]
+fn user_vhosts_namespaces_nginx_wide_names() {
+    let vhost = replicas_vhost("shop", "web", "host.docker.internal", &[20000, 20001]);
+    let out = user_vhosts(&vhost, "bob-2");
+    assert!(out.contains("upstream darp_replicas_bob_2_shop_web {"));
+    assert!(out.contains("proxy_pass http://darp_replicas_bob_2_shop_web/;"));
+    assert!(out.contains("server_name web.shop.bob-2.test;"));
+}
+
+#[test]
+fn user_vhosts_leaves_other_hosts_alone() {
+    let conf = "server {\n    server_name darp.example.com;\n    proxy_set_header Host $host;\n}\n";
+    assert_eq!(user_vhosts(conf, "alice"), conf);
+}
+
+// ---------------------------------------------------------------------------
+// Container names
+// ---------------------------------------------------------------------------
+
+#[test]
+fn containers_are_named_per_user() {
+    let engine = Engine::new(EngineKind::None, &shared("alice", 20000)).unwrap();
+    assert_eq!(
+        engine.service_container_name("shop", "api"),
+        "darp_alice_shop_api"
+    );
+
+    let mut c = shared("alice", 20000);
+    c.set_container_name("{user}-{service}-{domain}").unwrap();
+    let engine = Engine::new(EngineKind::None, &c).unwrap();
+    assert_eq!(
+        engine.service_container_name("shop", "api"),
+        "alice-api-shop"
+    );
+}
@@ -0,0 +1,63 @@
+use darp::config::{Config, validate_connection_type};
+use darp::deploy::{live_reload_vhost, static_vhost};
+
+// ---------------------------------------------------------------------------
+// static_vhost
+// ---------------------------------------------------------------------------
+
+#[test]
+fn static_vhost_serves_the_service_folder_from_the_domain_mount() {
+    let out = static_vhost("docs", ".", "handbook");
+    assert!(
+        out.contains("    server_name handbook.docs.test;\n"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("    root /srv/darp/docs/handbook;\n"),
+        "{}",
+        out
+    );
+    assert!(out.contains("try_files $uri $uri/ =404;"), "{}", out);
+    assert!(!out.contains("proxy_pass"), "{}", out);
+
+    let grouped = static_vhost("docs", "design", "mockups");
+    assert!(grouped.contains("    root /srv/darp/docs/design/mockups;\n"));
+}
+
+#[test]
+fn static_vhost_takes_live_reload() {
+    let out = live_reload_vhost(
+        &static_vhost("docs", ".", "mockups"),
+        "host.docker.internal",
+    );
+    assert!(out.contains("livereload.js"), "{}", out);
+    assert!(out.contains("location /__darp/ {"), "{}", out);
+}
+
+// ---------------------------------------------------------------------------
+// Config
+// ---------------------------------------------------------------------------
+
+#[test]
+fn static_is_a_connection_type() {
+    validate_connection_type("static").unwrap();
+}
+
+#[test]
+fn only_domains_with_static_services_are_mounted() {
+    let mut c = Config::default();
+    c.add_domain("docs", "/tmp/docs").unwrap();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_domain("site", "/tmp/site").unwrap();
+    c.set_service_connection_type("docs", ".", "handbook", "static")
+        .unwrap();
+    c.set_service_connection_type("shop", ".", "api", "http")
+        .unwrap();
+    c.set_domain_connection_type("site", "static").unwrap();
+
+    let mounts = c.static_domain_locations().unwrap();
+    let names: Vec<&str> = mounts.iter().map(|(d, _)| d.as_str()).collect();
+    assert_eq!(names, ["docs", "site"]);
+    assert_eq!(mounts[0].1.to_str(), Some("/tmp/docs"));
+}
@@ -0,0 +1,65 @@
+use darp::config::{Config, portmap_tcp_port};
+use darp::deploy::stream_server;
+
+// ---------------------------------------------------------------------------
+// stream_server
+// ---------------------------------------------------------------------------
+
+#[test]
+fn stream_server_forwards_to_the_host_gateway() {
+    assert_eq!(
+        stream_server(5432, false, "host.docker.internal:50100", None),
+        "    server {\n        listen 5432;\n        proxy_pass host.docker.internal:50100;\n    }\n"
+    );
+}
+
+#[test]
+fn stream_server_resolves_network_upstreams_per_connection() {
+    let out = stream_server(5353, true, "darp_shop_dns:8002", Some("127.0.0.11"));
+    assert!(out.contains("listen 5353 udp;"));
+    assert!(out.contains("resolver 127.0.0.11 valid=10s;"));
+    assert!(out.contains("set $darp_upstream darp_shop_dns:8002;"));
+    assert!(out.contains("proxy_pass $darp_upstream;"));
+}
+
+// ---------------------------------------------------------------------------
+// Config stream ports
+// ---------------------------------------------------------------------------
+
+#[test]
+fn stream_ports_are_unique_per_protocol() {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c.add_domain("blog", "/tmp/blog").unwrap();
+
+    c.set_service_stream("shop", ".", "db", 5432, false)
+        .unwrap();
+    // Re-setting the same service is fine; another service on the port isn't.
+    c.set_service_stream("shop", ".", "db", 5432, false)
+        .unwrap();
+    let err = c
+        .set_service_stream("blog", ".", "db", 5432, false)
+        .unwrap_err();
+    assert!(err.to_string().contains("'shop.db'"), "{}", err);
+    // UDP and TCP ports don't clash.
+    c.set_service_stream("blog", ".", "db", 5432, true).unwrap();
+    assert!(c.set_service_stream("blog", ".", "web", 80, false).is_err());
+
+    assert_eq!(
+        c.stream_ports(),
+        vec![(("blog", "db"), 5432, true), (("shop", "db"), 5432, false)]
+    );
+
+    c.rm_service_stream("shop", ".", "db").unwrap();
+    assert!(c.rm_service_stream("shop", ".", "db").is_err());
+}
+
+#[test]
+fn tcp_port_prefers_the_stream_port() {
+    assert_eq!(
+        portmap_tcp_port(&serde_json::json!({"port": 50100, "stream_port": 5432})),
+        5432
+    );
+    assert_eq!(portmap_tcp_port(&serde_json::json!({"port": 50100})), 50100);
+    assert_eq!(portmap_tcp_port(&serde_json::json!(50100)), 50100);
+}
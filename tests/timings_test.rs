@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use darp::timings::{self, render};
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+// ---------------------------------------------------------------------------
+// render
+// ---------------------------------------------------------------------------
+
+#[test]
+fn render_lists_phases_in_order_with_the_total() {
+    let phases = vec![
+        ("config load".to_string(), ms(3)),
+        ("engine readiness".to_string(), ms(1500)),
+    ];
+    assert_eq!(
+        render(&phases, ms(1620)),
+        "timings:\n  config load            3ms\n  engine readiness     1.50s\n  total                1.62s\n"
+    );
+}
+
+#[test]
+fn render_adds_up_repeated_phases() {
+    let phases = vec![
+        ("container start".to_string(), ms(200)),
+        ("scan".to_string(), ms(5)),
+        ("container start".to_string(), ms(300)),
+    ];
+    let report = render(&phases, ms(600));
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[1], "  container start     500ms  (2×)");
+    assert_eq!(lines[2], "  scan                  5ms");
+}
+
+// ---------------------------------------------------------------------------
+// phase
+// ---------------------------------------------------------------------------
+
+#[test]
+fn phases_are_only_recorded_once_enabled() {
+    drop(timings::phase("before"));
+    timings::enable();
+    drop(timings::phase("after"));
+    let names: Vec<String> = timings::recorded().into_iter().map(|(n, _)| n).collect();
+    assert_eq!(names, vec!["after".to_string()]);
+}
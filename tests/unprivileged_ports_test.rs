@@ -0,0 +1,44 @@
+use darp::engine::{
+    UNPRIVILEGED_PORT_DROP_IN, UNPRIVILEGED_PORT_START, unprivileged_ports_allowed,
+    unprivileged_ports_script,
+};
+
+// ---------------------------------------------------------------------------
+// unprivileged_ports_allowed
+// ---------------------------------------------------------------------------
+
+#[test]
+fn default_kernel_start_needs_lowering() {
+    assert!(!unprivileged_ports_allowed("1024\n"));
+    assert!(!unprivileged_ports_allowed("81"));
+}
+
+#[test]
+fn a_start_at_or_below_53_is_enough() {
+    assert!(unprivileged_ports_allowed("53\n"));
+    assert!(unprivileged_ports_allowed("0"));
+}
+
+#[test]
+fn unreadable_values_count_as_not_allowed() {
+    assert!(!unprivileged_ports_allowed(""));
+    assert!(!unprivileged_ports_allowed("n/a"));
+}
+
+// ---------------------------------------------------------------------------
+// unprivileged_ports_script
+// ---------------------------------------------------------------------------
+
+#[test]
+fn script_sets_the_sysctl_now_and_after_reboots() {
+    let script = unprivileged_ports_script();
+    let setting = format!(
+        "net.ipv4.ip_unprivileged_port_start = {}",
+        UNPRIVILEGED_PORT_START
+    );
+    assert!(script.contains(&format!(
+        "echo '{}' > {}",
+        setting, UNPRIVILEGED_PORT_DROP_IN
+    )));
+    assert!(script.ends_with("sysctl -w net.ipv4.ip_unprivileged_port_start=53"));
+}
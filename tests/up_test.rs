@@ -0,0 +1,71 @@
+use darp::commands::up_waves;
+use darp::config::Config;
+
+fn fixture() -> (serde_json::Value, Config) {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": { "api": { "port": 50100 }, "web": { "port": 50101 } },
+            "infra": { "db": { "port": 50102 }, "search": { "port": 50103 } }
+        }
+    });
+    let mut config = Config::default();
+    config.add_domain("shop", "/tmp/shop").unwrap();
+    config
+        .set_service_start_order("shop", "infra", "db", -10)
+        .unwrap();
+    config
+        .set_service_start_order("shop", "infra", "search", -10)
+        .unwrap();
+    config
+        .set_service_start_order("shop", ".", "web", 5)
+        .unwrap();
+    (portmap, config)
+}
+
+fn pair(group: &str, service: &str) -> (String, String) {
+    (group.to_string(), service.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// darp up ordering and selection
+// ---------------------------------------------------------------------------
+
+#[test]
+fn waves_follow_start_order_with_unset_as_zero() {
+    let (portmap, config) = fixture();
+    let waves = up_waves(&config, &portmap, "shop", &[], &[]).unwrap();
+    assert_eq!(
+        waves,
+        vec![
+            vec![pair("infra", "db"), pair("infra", "search")],
+            vec![pair(".", "api")],
+            vec![pair(".", "web")],
+        ]
+    );
+}
+
+#[test]
+fn only_and_except_narrow_the_waves() {
+    let (portmap, config) = fixture();
+    let only = vec!["db".to_string(), "web".to_string()];
+    let waves = up_waves(&config, &portmap, "shop", &only, &[]).unwrap();
+    assert_eq!(
+        waves,
+        vec![vec![pair("infra", "db")], vec![pair(".", "web")]]
+    );
+
+    let except = vec!["search".to_string(), "api".to_string()];
+    let waves = up_waves(&config, &portmap, "shop", &[], &except).unwrap();
+    assert_eq!(
+        waves,
+        vec![vec![pair("infra", "db")], vec![pair(".", "web")]]
+    );
+}
+
+#[test]
+fn unknown_service_names_are_rejected() {
+    let (portmap, config) = fixture();
+    let err = up_waves(&config, &portmap, "shop", &["nope".to_string()], &[]).unwrap_err();
+    assert!(err.to_string().contains("'nope' is not registered"));
+    assert!(up_waves(&config, &portmap, "shop", &[], &["nope".to_string()]).is_err());
+}
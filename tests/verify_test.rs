@@ -0,0 +1,92 @@
+use darp::commands::{VerifyTarget, verify_targets};
+
+fn target(url: &str, host: &str, port: u16, http: bool) -> VerifyTarget {
+    VerifyTarget {
+        url: url.to_string(),
+        host: host.to_string(),
+        port,
+        http,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// verify_targets
+// ---------------------------------------------------------------------------
+
+#[test]
+fn http_and_websocket_services_are_requested_on_port_80() {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": {
+                "api": { "port": 50100, "type": "http", "debug_port": 13000 },
+                "live": { "port": 50101, "type": "websocket", "debug_port": 13001 },
+                "docs": { "port": 50102, "type": "static", "debug_port": 13002 }
+            }
+        }
+    });
+    assert_eq!(
+        verify_targets(&portmap),
+        vec![
+            target("http://api.shop.test", "api.shop.test", 80, true),
+            target("http://docs.shop.test", "docs.shop.test", 80, true),
+            target("ws://live.shop.test", "live.shop.test", 80, true),
+        ]
+    );
+}
+
+#[test]
+fn tcp_services_are_only_checked_through_their_stream_port() {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": {
+                "db": { "port": 50100, "type": "tcp", "debug_port": 13000, "stream_port": 5432 },
+                "cache": { "port": 50101, "type": "tcp", "debug_port": 13001 }
+            }
+        }
+    });
+    assert_eq!(
+        verify_targets(&portmap),
+        vec![target(
+            "tcp://db.shop.test:5432",
+            "db.shop.test",
+            5432,
+            false
+        )]
+    );
+}
+
+#[test]
+fn aliases_and_custom_domains_are_checked_too() {
+    let portmap = serde_json::json!({
+        "shop": {
+            "laravel": {
+                "auth": {
+                    "port": 50100,
+                    "type": "http",
+                    "debug_port": 13000,
+                    "aliases": ["auth.test"],
+                    "custom_urls": ["https://login.mycompany.dev", "http://sso.mycompany.dev"]
+                }
+            }
+        }
+    });
+    assert_eq!(
+        verify_targets(&portmap),
+        vec![
+            target("http://auth.shop.test", "auth.shop.test", 80, true),
+            target("http://auth.test", "auth.test", 80, true),
+            target("http://sso.mycompany.dev", "sso.mycompany.dev", 80, true),
+            target(
+                "https://login.mycompany.dev",
+                "login.mycompany.dev",
+                443,
+                false
+            ),
+        ]
+    );
+}
+
+#[test]
+fn empty_portmap_has_nothing_to_verify() {
+    assert!(verify_targets(&serde_json::json!({})).is_empty());
+}
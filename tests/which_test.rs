@@ -0,0 +1,169 @@
+use darp::commands::{
+    direnv_snippet, sibling_url_var, sibling_urls, which_entry, with_envrc_block,
+    without_envrc_block,
+};
+use darp::config::Config;
+use darp::engine::DEFAULT_CONTAINER_NAME;
+
+// ---------------------------------------------------------------------------
+// which_entry
+// ---------------------------------------------------------------------------
+
+fn service_dir(name: &str) -> (std::path::PathBuf, Config) {
+    let base = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&base);
+    let domain_dir = base.join("shop");
+    std::fs::create_dir_all(domain_dir.join("api")).unwrap();
+    let mut config = Config::default();
+    config
+        .add_domain("shop", &domain_dir.to_string_lossy())
+        .unwrap();
+    (base, config)
+}
+
+#[test]
+fn which_entry_reports_port_url_and_container() {
+    let (base, config) = service_dir("darp_test_which_assigned");
+    let ctx = config
+        .service_context_for_dir(base.join("shop").join("api"), None)
+        .unwrap();
+    let portmap = serde_json::json!({"shop": {".": {"api": {"port": 50101, "type": "http"}}}});
+
+    let entry = which_entry(&ctx, &portmap, DEFAULT_CONTAINER_NAME);
+    assert_eq!(entry["domain"], "shop");
+    assert_eq!(entry["group"], ".");
+    assert_eq!(entry["service"], "api");
+    assert_eq!(entry["port"], 50101);
+    assert_eq!(entry["url"], "http://api.shop.test");
+    assert_eq!(entry["container"], "darp_shop_api");
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn which_entry_without_portmap_entry_has_null_port_and_url() {
+    let (base, config) = service_dir("darp_test_which_unassigned");
+    let ctx = config
+        .service_context_for_dir(base.join("shop").join("api"), None)
+        .unwrap();
+
+    let entry = which_entry(&ctx, &serde_json::json!({}), DEFAULT_CONTAINER_NAME);
+    assert!(entry["port"].is_null());
+    assert!(entry["url"].is_null());
+    assert_eq!(entry["container"], "darp_shop_api");
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+// ---------------------------------------------------------------------------
+// direnv_snippet
+// ---------------------------------------------------------------------------
+
+#[test]
+fn direnv_snippet_exports_service_url_and_port() {
+    let entry = serde_json::json!({
+        "domain": "shop",
+        "service": "api",
+        "port": 50101,
+        "url": "http://api.shop.test",
+    });
+    let watch = std::path::Path::new("/home/me/.darp/portmap.json");
+    assert_eq!(
+        direnv_snippet(&entry, &[watch]),
+        "watch_file /home/me/.darp/portmap.json\n\
+         export DARP_SERVICE=shop.api\n\
+         export DARP_URL=http://api.shop.test\n\
+         export DARP_PORT=50101\n"
+    );
+}
+
+#[test]
+fn direnv_snippet_skips_url_and_port_until_deployed() {
+    let entry = serde_json::json!({"domain": "shop", "service": "api", "port": null, "url": null});
+    assert_eq!(
+        direnv_snippet(&entry, &[]),
+        "export DARP_SERVICE=shop.api\n"
+    );
+}
+
+#[test]
+fn direnv_snippet_quotes_watched_paths() {
+    let entry = serde_json::json!({"domain": "shop", "service": "api"});
+    let watch = std::path::Path::new("/Users/me/My Config/config.json");
+    assert!(
+        direnv_snippet(&entry, &[watch])
+            .starts_with("watch_file '/Users/me/My Config/config.json'\n")
+    );
+}
+
+#[test]
+fn direnv_snippet_exports_direct_url_and_siblings() {
+    let entry = serde_json::json!({
+        "domain": "shop",
+        "service": "api",
+        "port": 50101,
+        "url": "http://api.shop.test",
+        "direct_url": "http://localhost:50101",
+        "siblings": {"DARP_URL_WEB_APP": "http://web-app.shop.test"},
+    });
+    assert_eq!(
+        direnv_snippet(&entry, &[]),
+        "export DARP_SERVICE=shop.api\n\
+         export DARP_URL=http://api.shop.test\n\
+         export DARP_PORT=50101\n\
+         export DARP_DIRECT_URL=http://localhost:50101\n\
+         export DARP_URL_WEB_APP=http://web-app.shop.test\n"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// sibling_urls
+// ---------------------------------------------------------------------------
+
+#[test]
+fn sibling_url_var_is_an_env_name() {
+    assert_eq!(sibling_url_var("api"), "DARP_URL_API");
+    assert_eq!(sibling_url_var("web-app"), "DARP_URL_WEB_APP");
+    assert_eq!(sibling_url_var("auth.v2"), "DARP_URL_AUTH_V2");
+}
+
+#[test]
+fn sibling_urls_cover_the_domain_but_not_the_service_itself() {
+    let portmap = serde_json::json!({
+        "shop": {
+            ".": {"api": {"port": 50101}, "web": {"port": 50102}},
+            "jobs": {"mailer": {"port": 50103, "type": "tcp"}},
+        },
+        "blog": {".": {"site": {"port": 50104}}},
+    });
+    let urls = sibling_urls(&portmap, "shop", "api");
+    assert_eq!(
+        serde_json::Value::from(urls),
+        serde_json::json!({
+            "DARP_URL_WEB": "http://web.shop.test",
+            "DARP_URL_MAILER": "tcp://mailer.shop.test:50103",
+        })
+    );
+}
+
+// ---------------------------------------------------------------------------
+// with_envrc_block / without_envrc_block
+// ---------------------------------------------------------------------------
+
+const ENVRC_BLOCK: &str = "# >>> darp >>>\neval \"$(darp hook direnv)\"\n# <<< darp <<<\n";
+
+#[test]
+fn envrc_block_is_added_once() {
+    assert_eq!(with_envrc_block(""), ENVRC_BLOCK);
+    let envrc = "use node\n";
+    let added = with_envrc_block(envrc);
+    assert_eq!(added, format!("use node\n{}", ENVRC_BLOCK));
+    assert_eq!(with_envrc_block(&added), added);
+}
+
+#[test]
+fn envrc_block_removal_keeps_other_lines() {
+    let envrc = format!("use node\n{}", ENVRC_BLOCK);
+    assert_eq!(without_envrc_block(&envrc).as_deref(), Some("use node\n"));
+    assert_eq!(without_envrc_block(ENVRC_BLOCK), None);
+}
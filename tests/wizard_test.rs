@@ -0,0 +1,129 @@
+use std::io::Cursor;
+
+use darp::commands::{parse_port_pair, parse_variable, parse_volume, project_hint, run_wizard};
+use darp::config::Config;
+
+fn shop_config() -> Config {
+    let mut c = Config::default();
+    c.add_domain("shop", "/tmp/shop").unwrap();
+    c
+}
+
+fn service(c: &Config, name: &str) -> darp::config::Service {
+    c.domains.as_ref().unwrap()["shop"].groups.as_ref().unwrap()["."]
+        .services
+        .as_ref()
+        .unwrap()[name]
+        .clone()
+}
+
+// ---------------------------------------------------------------------------
+// Answer parsing
+// ---------------------------------------------------------------------------
+
+#[test]
+fn wizard_answers_are_validated() {
+    assert_eq!(parse_port_pair("9229:9229").unwrap(), (9229, 9229));
+    assert!(parse_port_pair("9229").is_err());
+    assert!(parse_port_pair("0:80").is_err());
+    assert!(parse_port_pair("80:http").is_err());
+
+    assert_eq!(
+        parse_volume("{home}/.ssh:/root/.ssh").unwrap(),
+        ("{home}/.ssh".to_string(), "/root/.ssh".to_string())
+    );
+    assert!(parse_volume("/data:data").is_err());
+    assert!(parse_volume(":/data").is_err());
+
+    assert_eq!(
+        parse_variable("API_URL=http://api.shop.test").unwrap(),
+        ("API_URL".to_string(), "http://api.shop.test".to_string())
+    );
+    assert!(parse_variable("1ST=x").is_err());
+    assert!(parse_variable("NO_VALUE").is_err());
+}
+
+#[test]
+fn project_hint_follows_marker_files() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(project_hint(dir.path()).is_none());
+    std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+    assert_eq!(project_hint(dir.path()).unwrap().0, "node:22-alpine");
+    std::fs::write(dir.path().join("artisan"), "").unwrap();
+    assert_eq!(project_hint(dir.path()).unwrap().0, "php:8.3-cli");
+}
+
+// ---------------------------------------------------------------------------
+// run_wizard
+// ---------------------------------------------------------------------------
+
+#[test]
+fn wizard_takes_suggestions_and_asks_again_after_bad_answers() {
+    let mut c = shop_config();
+    let answers = "\n\
+                   npm start\n\
+                   9229\n\
+                   9229:9229\n\
+                   \n\
+                   {home}/.npmrc:/root/.npmrc\n\
+                   \n\
+                   bad name=1\n\
+                   NODE_ENV=development\n\
+                   \n\
+                   y\n";
+    let mut output = Vec::new();
+    let saved = run_wizard(
+        &mut Cursor::new(answers),
+        &mut output,
+        &mut c,
+        "shop",
+        ".",
+        "web",
+        Some(("node:22-alpine", "npm run dev")),
+    )
+    .unwrap();
+    assert!(saved);
+
+    let web = service(&c, "web");
+    assert_eq!(
+        web.default_container_image.as_deref(),
+        Some("node:22-alpine")
+    );
+    assert_eq!(web.serve_command.as_deref(), Some("npm start"));
+    assert_eq!(web.host_portmappings.unwrap()["9229"], "9229");
+    assert_eq!(web.volumes.unwrap()[0].container, "/root/.npmrc");
+    assert_eq!(web.variables.unwrap()["NODE_ENV"], "development");
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Expected host:container"), "{}", output);
+    assert!(output.contains("'bad name' is not a valid variable name."));
+}
+
+#[test]
+fn wizard_skips_questions_and_reports_cancelling() {
+    let mut c = shop_config();
+    let saved = run_wizard(
+        &mut Cursor::new("-\n-\n\n\n\nn\n"),
+        &mut Vec::new(),
+        &mut c,
+        "shop",
+        ".",
+        "web",
+        Some(("node:22-alpine", "npm run dev")),
+    )
+    .unwrap();
+    assert!(!saved);
+
+    // Running out of input part way cancels too.
+    let saved = run_wizard(
+        &mut Cursor::new("node:22\n"),
+        &mut Vec::new(),
+        &mut c,
+        "shop",
+        ".",
+        "web",
+        None,
+    )
+    .unwrap();
+    assert!(!saved);
+}